@@ -6,7 +6,10 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     tonic_build::configure()
         .file_descriptor_set_path(out_dir.join("tapp_service_descriptor.bin"))
-        .compile_protos(&["proto/tapp_service.proto"], &["proto"])?;
+        .compile_protos(
+            &["proto/tapp_service.proto", "proto/tapp_service_v2.proto"],
+            &["proto"],
+        )?;
 
     Ok(())
 }
@@ -0,0 +1,51 @@
+//! Fetch an app's key and keep a secret sealed, from inside its container
+//!
+//! Run in a container of the app, with TAPP_SERVER set when the server isn't
+//! on the default gateway:
+//!
+//!     APP_ID=my-app cargo run --example app_client --features client
+//!
+//! With DEPLOYER_KEY (hex private key) set it also fetches the app's secret
+//! key and signs a message with it, which works from the host only.
+
+use tapp_service::client::TappClient;
+use tapp_service::{SealBinding, TappError};
+
+#[tokio::main]
+async fn main() -> Result<(), TappError> {
+    let app_id = std::env::var("APP_ID").unwrap_or_else(|_| "my-app".to_string());
+    let client = TappClient::connect_local().await?;
+
+    let key = client.get_app_key(&app_id).await?;
+    println!("App key address: 0x{}", hex::encode(&key.eth_address));
+
+    match client.unseal(&app_id, "example-secret").await {
+        Ok(secret) => println!("Unsealed {} bytes", secret.len()),
+        Err(TappError::SealedBlobNotFound { .. }) => {
+            let blob = client
+                .seal(
+                    &app_id,
+                    "example-secret",
+                    b"created on first run",
+                    SealBinding::SealToAppId,
+                )
+                .await?;
+            println!("Sealed {} bytes as '{}'", blob.size, blob.label);
+        }
+        Err(e) => return Err(e),
+    }
+
+    if let Ok(deployer_key) = std::env::var("DEPLOYER_KEY") {
+        let deployer_key = hex::decode(deployer_key.trim_start_matches("0x")).map_err(|e| {
+            TappError::InvalidParameter {
+                field: "DEPLOYER_KEY".to_string(),
+                reason: e.to_string(),
+            }
+        })?;
+        let signature = client
+            .sign_with_app_key(&app_id, &deployer_key, b"hello")
+            .await?;
+        println!("Signature over 'hello': 0x{}", hex::encode(signature));
+    }
+    Ok(())
+}
@@ -1,7 +1,7 @@
 #!/bin/bash
 
 # Usage:
-#   ./get_service_logs.sh [HOST] [PORT] [FILE_NAME] [LINES]
+#   ./get_service_logs.sh [HOST] [PORT] [FILE_NAME] [LINES] [MIN_LEVEL] [SINCE]
 #
 # Examples:
 #   ./get_service_logs.sh                                    # List all log files (default host)
@@ -9,6 +9,8 @@
 #   ./get_service_logs.sh 39.97.249.15 50051                # List all log files
 #   ./get_service_logs.sh 39.97.249.15 50051 app.log        # Get last 100 lines of app.log
 #   ./get_service_logs.sh 39.97.249.15 50051 app.log 200    # Get last 200 lines
+#   ./get_service_logs.sh 39.97.249.15 50051 app.log 200 error $(( $(date +%s) - 3600 ))
+#                                                           # Last 200 errors of the past hour
 
 # Default configuration
 DEFAULT_HOST="your-cvm-instance-host"
@@ -20,6 +22,8 @@ TARGET_HOST=${1:-$DEFAULT_HOST}
 TARGET_PORT=${2:-$DEFAULT_PORT}
 FILE_NAME=${3:-""}
 LINES=${4:-$DEFAULT_LINES}
+MIN_LEVEL=${5:-""}
+SINCE=${6:-0}
 TARGET_ADDRESS="$TARGET_HOST:$TARGET_PORT"
 
 echo "======================================"
@@ -31,6 +35,8 @@ if [ -z "$FILE_NAME" ]; then
 else
     echo "File:          $FILE_NAME"
     echo "Lines:         $LINES"
+    [ -n "$MIN_LEVEL" ] && echo "Min level:     $MIN_LEVEL"
+    [ "$SINCE" != "0" ] && echo "Since:         $SINCE"
 fi
 echo "======================================"
 echo ""
@@ -39,9 +45,13 @@ echo ""
 request_json=$(jq -n \
   --arg file_name "$FILE_NAME" \
   --argjson lines "$LINES" \
+  --arg min_level "$MIN_LEVEL" \
+  --argjson since "$SINCE" \
   '{
     file_name: $file_name,
-    lines: $lines
+    lines: $lines,
+    min_level: $min_level,
+    since: $since
   }')
 
 echo "Sending GetServiceLogs request..."
@@ -1,40 +1,44 @@
 #!/bin/bash
 
+# Operator force-stop: stops an application without the deployer's
+# signature, which the server only allows for an admin API key
+# (role = "admin"). Deployers stop their apps with
+#   tapp-cli stop-app --app-id <APP_ID> --deployer-key <DEPLOYER_PRIVATE_KEY>
+#
 # Usage:
-#   ./stop_app.sh [APP_ID] [HOST] [PORT] [API_KEY]
+#   ./stop_app.sh <APP_ID> [HOST] [PORT] <ADMIN_API_KEY>
 #
 # Examples:
-#   ./stop_app.sh
-#   ./stop_app.sh test-nginx-app
-#   ./stop_app.sh test-nginx-app your-cvm-instance-host 50051
-#   ./stop_app.sh test-nginx-app your-cvm-instance-host 50051 my-secret-api-key-12345
+#   ./stop_app.sh test-nginx-app your-cvm-instance-host 50051 my-admin-api-key
 #
 # Or use environment variable:
-#   export TAPP_API_KEY="my-secret-api-key-12345"
+#   export TAPP_API_KEY="my-admin-api-key"
 #   ./stop_app.sh test-nginx-app
 
 # Default configuration
-DEFAULT_APP_ID="test-nginx-app"
 DEFAULT_HOST="your-cvm-instance-host"
 DEFAULT_PORT="50051"
 
 # Parse command line arguments
-APP_ID=${1:-$DEFAULT_APP_ID}
+APP_ID=$1
 TARGET_HOST=${2:-$DEFAULT_HOST}
 TARGET_PORT=${3:-$DEFAULT_PORT}
 API_KEY=${4:-$TAPP_API_KEY}  # From argument or environment variable
 TARGET_ADDRESS="$TARGET_HOST:$TARGET_PORT"
 
+if [ -z "$APP_ID" ] || [ -z "$API_KEY" ]; then
+    echo "Usage: $0 <APP_ID> [HOST] [PORT] <ADMIN_API_KEY>" >&2
+    echo "An admin API key is required (argument or TAPP_API_KEY); without one," >&2
+    echo "stop the app with: tapp-cli stop-app --app-id <APP_ID> --deployer-key <KEY>" >&2
+    exit 1
+fi
+
 echo "======================================"
 echo "StopApp Request Configuration"
 echo "======================================"
 echo "Target:        $TARGET_ADDRESS"
 echo "App ID:        $APP_ID"
-if [ -n "$API_KEY" ]; then
-    echo "API Key:       ${API_KEY:0:8}... (configured)"
-else
-    echo "API Key:       (not set)"
-fi
+echo "API Key:       ${API_KEY:0:8}... (admin force-stop)"
 echo "======================================"
 echo ""
 
@@ -54,13 +58,8 @@ echo "$request_json"
 echo "--------------------------------------"
 echo ""
 
-# Build grpcurl command with optional API key
-GRPCURL_CMD="grpcurl -plaintext"
-
-# Add API key header if provided
-if [ -n "$API_KEY" ]; then
-    GRPCURL_CMD="$GRPCURL_CMD -H \"x-api-key: $API_KEY\""
-fi
+# Build grpcurl command with the admin API key
+GRPCURL_CMD="grpcurl -plaintext -H \"x-api-key: $API_KEY\""
 
 GRPCURL_CMD="$GRPCURL_CMD -import-path ./proto -proto tapp_service.proto -d @ \"$TARGET_ADDRESS\" tapp_service.TappService/StopApp"
 
@@ -0,0 +1,765 @@
+//! Backups of the keys an AppKeyService holds
+//!
+//! In in-memory mode the app keys and the sealing master key exist in this
+//! process only. ExportKeystoreBackup writes them to a backup encrypted to an
+//! operator's recovery key, and ImportKeystoreBackup restores them on
+//! another node, e.g. one replacing a lost machine. With a KBS the keys are
+//! resources of the KBS and are backed up there.
+//!
+//! A backup is the JSON of `KeystoreBackup`. Its manifest lists every key by
+//! app ID, type and fingerprint, never key material: hex of the first 16
+//! bytes of SHA-256("tapp-keystore-fingerprint:v1" || SEC1 public key), or
+//! of the key itself for the sealing master key. `manifest_hash` is SHA-256
+//! of the manifest's JSON.
+//!
+//! The keys are in `ciphertext` only: an `envelope::seal_to` envelope to the
+//! recovery key with info "tapp-keystore-backup:v1" and created_at
+//! (little-endian i64) || manifest hash as the associated data. A backup
+//! opens only if its manifest matches the hash, the hash and creation time
+//! match the envelope, and the keys inside match the manifest.
+
+use super::envelope::{self, EnvelopeError};
+use super::{eth_address, public_key_from_private, sealed, AppKeyService, EthKeyPair};
+use crate::error::{DockerError, FailureCause, TappError, TappResult};
+use crate::proto;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashSet};
+use tracing::{info, warn};
+
+/// Version of the backup format
+pub const BACKUP_VERSION: u32 = 1;
+
+/// HKDF info of the backup envelope key
+const KEY_INFO: &[u8] = b"tapp-keystore-backup:v1";
+
+/// Domain separator of key fingerprints
+const FINGERPRINT_DOMAIN: &[u8] = b"tapp-keystore-fingerprint:v1";
+
+/// Key type of app keys
+pub const KEY_TYPE_ETHEREUM: &str = "ethereum";
+
+/// Key type of the sealing master key
+pub const KEY_TYPE_SEALING_MASTER: &str = "sealing-master";
+
+/// A key listed in a backup manifest
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ManifestEntry {
+    /// Empty for the sealing master key
+    pub app_id: String,
+    pub key_type: String,
+    pub fingerprint: String,
+}
+
+impl ManifestEntry {
+    fn app_key(app_id: &str, key_pair: &EthKeyPair) -> Self {
+        Self {
+            app_id: app_id.to_string(),
+            key_type: KEY_TYPE_ETHEREUM.to_string(),
+            fingerprint: fingerprint(&key_pair.public_key),
+        }
+    }
+
+    fn sealing_master_key(key: &[u8]) -> Self {
+        Self {
+            app_id: String::new(),
+            key_type: KEY_TYPE_SEALING_MASTER.to_string(),
+            fingerprint: fingerprint(key),
+        }
+    }
+
+    /// App ID, or the key type for the sealing master key
+    pub fn name(&self) -> &str {
+        if self.app_id.is_empty() {
+            &self.key_type
+        } else {
+            &self.app_id
+        }
+    }
+
+    pub fn to_proto(&self) -> proto::KeystoreEntry {
+        proto::KeystoreEntry {
+            app_id: self.app_id.clone(),
+            key_type: self.key_type.clone(),
+            fingerprint: self.fingerprint.clone(),
+        }
+    }
+}
+
+/// A backup as exported
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KeystoreBackup {
+    pub version: u32,
+    /// Unix seconds
+    pub created_at: i64,
+    /// Fingerprint of the recovery key the backup is encrypted to
+    pub recovery_key: String,
+    /// Sealing master key first, then app keys by app ID
+    pub manifest: Vec<ManifestEntry>,
+    pub manifest_hash: String,
+    /// Hex of the envelope of the keys
+    pub ciphertext: String,
+}
+
+/// Keys inside the envelope
+#[derive(Serialize, Deserialize)]
+struct BackupKeys {
+    /// Hex
+    sealing_master_key: Option<String>,
+    /// App ID -> hex of the 32-byte private key
+    app_keys: BTreeMap<String, String>,
+}
+
+/// Keys of a backup that opened
+struct OpenedBackup {
+    sealing_master_key: Option<(ManifestEntry, Vec<u8>)>,
+    app_keys: Vec<(ManifestEntry, EthKeyPair)>,
+}
+
+impl KeystoreBackup {
+    pub fn to_bytes(&self) -> TappResult<Vec<u8>> {
+        Ok(serde_json::to_vec(self)?)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> TappResult<Self> {
+        serde_json::from_slice(bytes)
+            .map_err(|e| TappError::InvalidBackup(format!("Not a keystore backup: {}", e)))
+    }
+
+    /// Check the manifest, decrypt the keys with the 32-byte recovery
+    /// private key and check them against the manifest
+    fn open(&self, recovery_private_key: &[u8]) -> TappResult<OpenedBackup> {
+        if self.version != BACKUP_VERSION {
+            return Err(TappError::InvalidBackup(format!(
+                "Unsupported backup version {}",
+                self.version
+            )));
+        }
+        if manifest_hash(&self.manifest)? != self.manifest_hash {
+            return Err(TappError::InvalidBackup(
+                "Manifest does not match its hash".to_string(),
+            ));
+        }
+
+        let invalid_key = |reason: String| TappError::InvalidParameter {
+            field: "recovery_private_key".to_string(),
+            reason,
+        };
+        let recovery_public_key = public_key_from_private(recovery_private_key)
+            .map_err(|_| invalid_key("Not a secp256k1 private key".to_string()))?;
+        if fingerprint(&sec1(&recovery_public_key)) != self.recovery_key {
+            return Err(invalid_key(format!(
+                "Not the recovery key the backup is encrypted to ({})",
+                self.recovery_key
+            )));
+        }
+
+        let ciphertext = hex::decode(&self.ciphertext)
+            .map_err(|e| TappError::InvalidBackup(format!("Ciphertext is not hex: {}", e)))?;
+        let plaintext = envelope::open_with(
+            recovery_private_key,
+            KEY_INFO,
+            &associated_data(self.created_at, &self.manifest_hash),
+            &ciphertext,
+        )
+        .map_err(|e| match e {
+            EnvelopeError::Decryption => TappError::InvalidBackup(
+                "Ciphertext does not open: altered, or not made with this manifest".to_string(),
+            ),
+            e => TappError::InvalidBackup(e.to_string()),
+        })?;
+        let keys: BackupKeys = serde_json::from_slice(&plaintext)
+            .map_err(|e| TappError::InvalidBackup(format!("Malformed keys: {}", e)))?;
+
+        let malformed = |name: &str| TappError::InvalidBackup(format!("Malformed key of {}", name));
+        let sealing_master_key = match keys.sealing_master_key {
+            Some(key) => {
+                let key = hex::decode(key).map_err(|_| malformed(KEY_TYPE_SEALING_MASTER))?;
+                if key.len() < sealed::MIN_MASTER_KEY_LEN {
+                    return Err(malformed(KEY_TYPE_SEALING_MASTER));
+                }
+                Some((ManifestEntry::sealing_master_key(&key), key))
+            }
+            None => None,
+        };
+        let mut app_keys = Vec::with_capacity(keys.app_keys.len());
+        for (app_id, private_key) in keys.app_keys {
+            let key_pair = hex::decode(private_key)
+                .ok()
+                .and_then(|private_key| key_pair(private_key).ok())
+                .ok_or_else(|| malformed(&app_id))?;
+            app_keys.push((ManifestEntry::app_key(&app_id, &key_pair), key_pair));
+        }
+
+        let opened = OpenedBackup {
+            sealing_master_key,
+            app_keys,
+        };
+        if opened.manifest() != self.manifest {
+            return Err(TappError::InvalidBackup(
+                "Keys do not match the manifest".to_string(),
+            ));
+        }
+        Ok(opened)
+    }
+}
+
+impl OpenedBackup {
+    fn manifest(&self) -> Vec<ManifestEntry> {
+        self.sealing_master_key
+            .iter()
+            .map(|(entry, _)| entry.clone())
+            .chain(self.app_keys.iter().map(|(entry, _)| entry.clone()))
+            .collect()
+    }
+}
+
+/// How an import combines a backup with the keys of the node
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ImportMode {
+    /// Add the keys of the backup to the node's
+    #[default]
+    Merge,
+    /// Hold exactly the keys of the backup
+    Replace,
+}
+
+/// What a merge does with a key the node holds with another value
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConflictPolicy {
+    /// Import nothing
+    #[default]
+    Fail,
+    KeepExisting,
+    Overwrite,
+}
+
+impl ImportMode {
+    pub fn from_proto(mode: proto::KeystoreImportMode) -> Self {
+        match mode {
+            proto::KeystoreImportMode::KeystoreMerge => ImportMode::Merge,
+            proto::KeystoreImportMode::KeystoreReplace => ImportMode::Replace,
+        }
+    }
+}
+
+impl ConflictPolicy {
+    pub fn from_proto(policy: proto::KeystoreConflictPolicy) -> Self {
+        match policy {
+            proto::KeystoreConflictPolicy::ConflictFail => ConflictPolicy::Fail,
+            proto::KeystoreConflictPolicy::ConflictKeepExisting => ConflictPolicy::KeepExisting,
+            proto::KeystoreConflictPolicy::ConflictOverwrite => ConflictPolicy::Overwrite,
+        }
+    }
+}
+
+/// What an import did with each key
+#[derive(Debug, Default, Serialize)]
+pub struct ImportReport {
+    pub manifest_hash: String,
+    pub created_at: i64,
+    /// New on this node
+    pub imported: Vec<ManifestEntry>,
+    /// Replaced a key the node held
+    pub overwritten: Vec<ManifestEntry>,
+    /// Conflicts where the node's key was kept
+    pub kept: Vec<ManifestEntry>,
+    /// Already held with the same key
+    pub unchanged: Vec<ManifestEntry>,
+    /// Held by the node but not in the backup, dropped by a replace
+    pub removed: Vec<ManifestEntry>,
+}
+
+impl AppKeyService {
+    fn require_in_memory(&self, operation: &str) -> TappResult<()> {
+        if self.use_in_memory {
+            return Ok(());
+        }
+        Err(DockerError::ContainerOperationFailed {
+            operation: operation.to_string(),
+            reason: "Keystore backups are only supported in in-memory mode; keys held by the \
+                     KBS are backed up there"
+                .to_string(),
+            cause: FailureCause::Precondition,
+        }
+        .into())
+    }
+
+    /// Back up the app keys and the sealing master key, encrypted to the
+    /// secp256k1 key `recovery_public_key` (64 bytes without the 0x04
+    /// prefix, or SEC1)
+    pub async fn export_backup(&self, recovery_public_key: &[u8]) -> TappResult<KeystoreBackup> {
+        self.require_in_memory("export_keystore_backup")?;
+        let recovery_public_key = recovery_sec1(recovery_public_key)?;
+
+        let mut keys = BackupKeys {
+            sealing_master_key: None,
+            app_keys: BTreeMap::new(),
+        };
+        let mut manifest = Vec::new();
+        {
+            let sealing_key = self.sealing_key.lock().await;
+            let app_keys = self.app_keys.lock().await;
+            if let Some(key) = sealing_key.as_ref() {
+                manifest.push(ManifestEntry::sealing_master_key(key));
+                keys.sealing_master_key = Some(hex::encode(key));
+            }
+            let mut app_ids: Vec<&String> = app_keys.keys().collect();
+            app_ids.sort();
+            for app_id in app_ids {
+                let key_pair = &app_keys[app_id];
+                manifest.push(ManifestEntry::app_key(app_id, key_pair));
+                keys.app_keys
+                    .insert(app_id.clone(), hex::encode(&key_pair.private_key));
+            }
+        }
+
+        let created_at = crate::utils::current_timestamp();
+        let manifest_hash = manifest_hash(&manifest)?;
+        let ciphertext = envelope::seal_to(
+            &recovery_public_key,
+            KEY_INFO,
+            &associated_data(created_at, &manifest_hash),
+            &serde_json::to_vec(&keys)?,
+        )
+        .map_err(|e| TappError::Crypto(format!("Sealing the keystore backup failed: {}", e)))?;
+
+        info!(
+            keys = manifest.len(),
+            manifest_hash = %manifest_hash,
+            "Exported keystore backup"
+        );
+        Ok(KeystoreBackup {
+            version: BACKUP_VERSION,
+            created_at,
+            recovery_key: fingerprint(&recovery_public_key),
+            manifest,
+            manifest_hash,
+            ciphertext: hex::encode(ciphertext),
+        })
+    }
+
+    /// Restore the keys of a backup with the 32-byte recovery private key
+    /// A conflict is a key the node holds with another value than the
+    /// backup. With `ConflictPolicy::Fail`, conflicts of a merge fail the
+    /// import before any key changes.
+    pub async fn import_backup(
+        &self,
+        backup: &[u8],
+        recovery_private_key: &[u8],
+        mode: ImportMode,
+        on_conflict: ConflictPolicy,
+    ) -> TappResult<ImportReport> {
+        self.require_in_memory("import_keystore_backup")?;
+        let backup = KeystoreBackup::from_bytes(backup)?;
+        let opened = backup.open(recovery_private_key)?;
+
+        let mut sealing_key = self.sealing_key.lock().await;
+        let mut app_keys = self.app_keys.lock().await;
+
+        let mut conflicts = Vec::new();
+        if let Some((entry, key)) = &opened.sealing_master_key {
+            if sealing_key.as_ref().is_some_and(|held| held != key) {
+                conflicts.push(entry.name().to_string());
+            }
+        }
+        for (entry, key_pair) in &opened.app_keys {
+            if app_keys
+                .get(&entry.app_id)
+                .is_some_and(|held| held.private_key != key_pair.private_key)
+            {
+                conflicts.push(entry.name().to_string());
+            }
+        }
+        if mode == ImportMode::Merge && on_conflict == ConflictPolicy::Fail && !conflicts.is_empty()
+        {
+            return Err(TappError::KeystoreConflict { keys: conflicts });
+        }
+        let overwrite = mode == ImportMode::Replace || on_conflict == ConflictPolicy::Overwrite;
+
+        let mut report = ImportReport {
+            manifest_hash: backup.manifest_hash.clone(),
+            created_at: backup.created_at,
+            ..Default::default()
+        };
+        match opened.sealing_master_key {
+            Some((entry, key)) => match sealing_key.as_ref() {
+                None => {
+                    *sealing_key = Some(key);
+                    report.imported.push(entry);
+                }
+                Some(held) if *held == key => report.unchanged.push(entry),
+                Some(_) if overwrite => {
+                    *sealing_key = Some(key);
+                    report.overwritten.push(entry);
+                }
+                Some(_) => report.kept.push(entry),
+            },
+            None if mode == ImportMode::Replace => {
+                if let Some(held) = sealing_key.take() {
+                    report
+                        .removed
+                        .push(ManifestEntry::sealing_master_key(&held));
+                }
+            }
+            None => {}
+        }
+
+        let restored: HashSet<String> = opened
+            .app_keys
+            .iter()
+            .map(|(entry, _)| entry.app_id.clone())
+            .collect();
+        for (entry, key_pair) in opened.app_keys {
+            match app_keys.get(&entry.app_id) {
+                None => {
+                    app_keys.insert(entry.app_id.clone(), key_pair);
+                    report.imported.push(entry);
+                }
+                Some(held) if held.private_key == key_pair.private_key => {
+                    report.unchanged.push(entry)
+                }
+                Some(_) if overwrite => {
+                    app_keys.insert(entry.app_id.clone(), key_pair);
+                    report.overwritten.push(entry);
+                }
+                Some(_) => report.kept.push(entry),
+            }
+        }
+        if mode == ImportMode::Replace {
+            app_keys.retain(|app_id, key_pair| {
+                let keep = restored.contains(app_id);
+                if !keep {
+                    report
+                        .removed
+                        .push(ManifestEntry::app_key(app_id, key_pair));
+                }
+                keep
+            });
+            report.removed.sort_by(|a, b| a.app_id.cmp(&b.app_id));
+        }
+
+        if !report.overwritten.is_empty() || !report.removed.is_empty() {
+            warn!(
+                overwritten = report.overwritten.len(),
+                removed = report.removed.len(),
+                manifest_hash = %report.manifest_hash,
+                "Keystore backup import replaced keys of this node"
+            );
+        }
+        info!(
+            imported = report.imported.len(),
+            kept = report.kept.len(),
+            unchanged = report.unchanged.len(),
+            manifest_hash = %report.manifest_hash,
+            "Imported keystore backup"
+        );
+        Ok(report)
+    }
+}
+
+/// Fingerprint of a public key (SEC1) or of the sealing master key
+pub fn fingerprint(material: &[u8]) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(FINGERPRINT_DOMAIN);
+    hasher.update(material);
+    hex::encode(&hasher.finalize()[..16])
+}
+
+/// Hex SHA-256 of the manifest's JSON
+pub fn manifest_hash(manifest: &[ManifestEntry]) -> TappResult<String> {
+    Ok(hex::encode(Sha256::digest(serde_json::to_vec(manifest)?)))
+}
+
+fn associated_data(created_at: i64, manifest_hash: &str) -> Vec<u8> {
+    let mut aad = created_at.to_le_bytes().to_vec();
+    aad.extend_from_slice(manifest_hash.as_bytes());
+    aad
+}
+
+/// 65-byte SEC1 form of a 64-byte public key without the 0x04 prefix
+fn sec1(public_key: &[u8]) -> Vec<u8> {
+    [&[0x04][..], public_key].concat()
+}
+
+/// Uncompressed SEC1 form of a recovery public key, so every encoding of a
+/// key has the same fingerprint
+fn recovery_sec1(public_key: &[u8]) -> TappResult<Vec<u8>> {
+    use k256::elliptic_curve::sec1::ToEncodedPoint;
+
+    let encoded = if public_key.len() == 64 {
+        sec1(public_key)
+    } else {
+        public_key.to_vec()
+    };
+    let public_key =
+        k256::PublicKey::from_sec1_bytes(&encoded).map_err(|e| TappError::InvalidParameter {
+            field: "recovery_public_key".to_string(),
+            reason: format!("Not a secp256k1 public key: {}", e),
+        })?;
+    Ok(public_key.to_encoded_point(false).as_bytes().to_vec())
+}
+
+fn key_pair(private_key: Vec<u8>) -> TappResult<EthKeyPair> {
+    let public_key = public_key_from_private(&private_key)?;
+    Ok(EthKeyPair {
+        eth_address: eth_address(&public_key),
+        public_key: sec1(&public_key),
+        private_key,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::KbsConfig;
+    use crate::error::ErrorReason;
+
+    const RECOVERY_KEY: [u8; 32] = [0x42; 32];
+
+    async fn service() -> AppKeyService {
+        AppKeyService::new(&KbsConfig::default(), true)
+            .await
+            .unwrap()
+    }
+
+    async fn public_key(service: &AppKeyService, app_id: &str) -> Vec<u8> {
+        service
+            .get_app_key(app_id, "ethereum")
+            .await
+            .unwrap()
+            .public_key
+    }
+
+    fn names(entries: &[ManifestEntry]) -> Vec<&str> {
+        entries.iter().map(ManifestEntry::name).collect()
+    }
+
+    async fn exported() -> (AppKeyService, Vec<u8>) {
+        let service = service().await;
+        public_key(&service, "app-1").await;
+        public_key(&service, "app-2").await;
+        service.sealing_master_key().await.unwrap();
+        let recovery_public_key = public_key_from_private(&RECOVERY_KEY).unwrap();
+        let backup = service.export_backup(&recovery_public_key).await.unwrap();
+        (service, backup.to_bytes().unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_backup_round_trip() {
+        let (source, blob) = exported().await;
+        let backup = KeystoreBackup::from_bytes(&blob).unwrap();
+        assert_eq!(
+            names(&backup.manifest),
+            [KEY_TYPE_SEALING_MASTER, "app-1", "app-2"]
+        );
+        assert_eq!(
+            backup.manifest_hash,
+            manifest_hash(&backup.manifest).unwrap()
+        );
+        // The backup never holds key material in the clear
+        let text = String::from_utf8(blob.clone()).unwrap();
+        let private_key = source.get_private_key("app-1").await.unwrap();
+        assert!(!text.contains(&hex::encode(&private_key)));
+        let master_key = source.sealing_master_key().await.unwrap();
+        assert!(!text.contains(&hex::encode(&master_key)));
+
+        // A fresh node gets the same keys
+        let target = service().await;
+        let report = target
+            .import_backup(
+                &blob,
+                &RECOVERY_KEY,
+                ImportMode::Merge,
+                ConflictPolicy::Fail,
+            )
+            .await
+            .unwrap();
+        assert_eq!(report.imported, backup.manifest);
+        assert_eq!(report.manifest_hash, backup.manifest_hash);
+        for app_id in ["app-1", "app-2"] {
+            assert_eq!(
+                public_key(&target, app_id).await,
+                public_key(&source, app_id).await
+            );
+        }
+        assert_eq!(target.get_private_key("app-1").await.unwrap(), private_key);
+        assert_eq!(
+            target.compose_secret("app-1", "db_password").await.unwrap(),
+            source.compose_secret("app-1", "db_password").await.unwrap()
+        );
+
+        // Importing again changes nothing
+        let report = target
+            .import_backup(
+                &blob,
+                &RECOVERY_KEY,
+                ImportMode::Merge,
+                ConflictPolicy::Fail,
+            )
+            .await
+            .unwrap();
+        assert!(report.imported.is_empty());
+        assert_eq!(report.unchanged, backup.manifest);
+
+        // A backup of the target lists the same keys
+        let recovery_public_key = public_key_from_private(&RECOVERY_KEY).unwrap();
+        let again = target.export_backup(&recovery_public_key).await.unwrap();
+        assert_eq!(again.manifest, backup.manifest);
+    }
+
+    #[tokio::test]
+    async fn test_backup_conflicts() {
+        let (source, blob) = exported().await;
+
+        let target = service().await;
+        let own_key = public_key(&target, "app-1").await;
+        public_key(&target, "app-3").await;
+
+        let error = target
+            .import_backup(
+                &blob,
+                &RECOVERY_KEY,
+                ImportMode::Merge,
+                ConflictPolicy::Fail,
+            )
+            .await
+            .unwrap_err();
+        assert_eq!(error.reason(), ErrorReason::KeystoreConflict);
+        assert!(error.to_string().contains("app-1"), "{}", error);
+        // Nothing was imported
+        assert!(target.get_private_key("app-2").await.is_err());
+
+        let report = target
+            .import_backup(
+                &blob,
+                &RECOVERY_KEY,
+                ImportMode::Merge,
+                ConflictPolicy::KeepExisting,
+            )
+            .await
+            .unwrap();
+        assert_eq!(names(&report.kept), ["app-1"]);
+        assert_eq!(names(&report.imported), [KEY_TYPE_SEALING_MASTER, "app-2"]);
+        assert_eq!(public_key(&target, "app-1").await, own_key);
+
+        let report = target
+            .import_backup(
+                &blob,
+                &RECOVERY_KEY,
+                ImportMode::Merge,
+                ConflictPolicy::Overwrite,
+            )
+            .await
+            .unwrap();
+        assert_eq!(names(&report.overwritten), ["app-1"]);
+        assert_eq!(
+            public_key(&target, "app-1").await,
+            public_key(&source, "app-1").await
+        );
+        assert!(target.get_private_key("app-3").await.is_ok());
+
+        // A replace drops the keys the backup does not have
+        let report = target
+            .import_backup(
+                &blob,
+                &RECOVERY_KEY,
+                ImportMode::Replace,
+                ConflictPolicy::Fail,
+            )
+            .await
+            .unwrap();
+        assert_eq!(names(&report.removed), ["app-3"]);
+        assert!(target.get_private_key("app-3").await.is_err());
+    }
+
+    async fn import_error(
+        target: &AppKeyService,
+        backup: KeystoreBackup,
+        recovery_key: [u8; 32],
+    ) -> TappError {
+        target
+            .import_backup(
+                &backup.to_bytes().unwrap(),
+                &recovery_key,
+                ImportMode::Merge,
+                ConflictPolicy::Overwrite,
+            )
+            .await
+            .unwrap_err()
+    }
+
+    #[tokio::test]
+    async fn test_backup_rejects_tampering() {
+        let (_, blob) = exported().await;
+        let backup = KeystoreBackup::from_bytes(&blob).unwrap();
+        let target = service().await;
+
+        // A manifest entry altered
+        let mut tampered = backup.clone();
+        tampered.manifest[1].app_id = "app-9".to_string();
+        let error = import_error(&target, tampered.clone(), RECOVERY_KEY).await;
+        assert!(
+            error.to_string().contains("does not match its hash"),
+            "{}",
+            error
+        );
+
+        // ... with its hash updated, the envelope no longer opens
+        tampered.manifest_hash = manifest_hash(&tampered.manifest).unwrap();
+        let error = import_error(&target, tampered, RECOVERY_KEY).await;
+        assert_eq!(error.reason(), ErrorReason::InvalidBackup);
+        assert!(error.to_string().contains("does not open"), "{}", error);
+
+        let mut tampered = backup.clone();
+        tampered.created_at += 1;
+        assert!(import_error(&target, tampered, RECOVERY_KEY)
+            .await
+            .to_string()
+            .contains("does not open"));
+
+        let mut tampered = backup.clone();
+        let mut ciphertext = hex::decode(&tampered.ciphertext).unwrap();
+        *ciphertext.last_mut().unwrap() ^= 1;
+        tampered.ciphertext = hex::encode(ciphertext);
+        assert!(import_error(&target, tampered, RECOVERY_KEY)
+            .await
+            .to_string()
+            .contains("does not open"));
+
+        // Keys of another node under this backup's manifest
+        let (_, other) = exported().await;
+        let mut tampered = backup.clone();
+        tampered.ciphertext = KeystoreBackup::from_bytes(&other).unwrap().ciphertext;
+        assert_eq!(
+            import_error(&target, tampered, RECOVERY_KEY).await.reason(),
+            ErrorReason::InvalidBackup
+        );
+
+        let error = import_error(&target, backup.clone(), [0x43; 32]).await;
+        assert_eq!(error.reason(), ErrorReason::InvalidParameter);
+
+        let mut tampered = backup;
+        tampered.version = 2;
+        assert!(import_error(&target, tampered, RECOVERY_KEY)
+            .await
+            .to_string()
+            .contains("Unsupported backup version 2"));
+
+        let error = target
+            .import_backup(
+                b"{}",
+                &RECOVERY_KEY,
+                ImportMode::Merge,
+                ConflictPolicy::Fail,
+            )
+            .await
+            .unwrap_err();
+        assert_eq!(error.reason(), ErrorReason::InvalidBackup);
+
+        // Nothing was imported
+        assert!(target.get_private_key("app-1").await.is_err());
+    }
+}
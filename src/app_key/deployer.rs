@@ -0,0 +1,327 @@
+//! Deployer identities
+//!
+//! A deployer is named by a secp256k1 public key (33-byte compressed, 64-byte
+//! uncompressed without the 0x04 prefix, or 65-byte SEC1 uncompressed) or by
+//! its 20-byte Ethereum address. Every form is reduced to one canonical
+//! identity: the address, plus the 64-byte key when it is known.
+//!
+//! Deployer requests are signed with one of two schemes:
+//! - v2: `0x02 || r || s || v` (66 bytes), a recoverable signature over
+//!   Keccak-256 of `request_message_v2`. The server recovers the signer and
+//!   compares its address, so only the address of the deployer is needed.
+//! - legacy: an unversioned 64-byte SHA-256 signature, or 65-byte recoverable
+//!   signature, over `deployer_request_message`. Kept during the migration,
+//!   except for the requests that release or use the app key.
+
+use super::{
+    eip191_message, eth_address, push_field, recover_public_key, sign_message_recoverable,
+    verify_signature,
+};
+use crate::error::{TappError, TappResult};
+use k256::ecdsa::VerifyingKey;
+use k256::elliptic_curve::sec1::ToEncodedPoint;
+
+/// Length of an Ethereum address
+const ADDRESS_LEN: usize = 20;
+
+/// Version byte leading a v2 deployer signature
+pub const SIGNATURE_VERSION_2: u8 = 0x02;
+
+/// Length of a v2 deployer signature: version byte, r, s and v
+const SIGNATURE_V2_LEN: usize = 66;
+
+/// Domain tag opening every v2 deployer request message
+const REQUEST_DOMAIN_V2: &[u8] = b"tapp-deployer-request:v2";
+
+/// Build the message of a v2 deployer signature
+/// Format: domain || app_id || nonce || timestamp (little-endian i64) ||
+/// operation || action, every field but the timestamp prefixed with its
+/// length (see `push_field`). `operation` names the request
+/// (`get_secret_key`, `stop_app`, `transfer_ownership`, `cancel_task`,
+/// `retry_task`, ...) and `action` is its action tag (empty for
+/// GetAppSecretKey).
+pub fn request_message_v2(
+    app_id: &str,
+    nonce: &str,
+    timestamp: i64,
+    operation: &str,
+    action: &[u8],
+) -> Vec<u8> {
+    let mut message = Vec::new();
+    push_field(&mut message, REQUEST_DOMAIN_V2);
+    push_field(&mut message, app_id.as_bytes());
+    push_field(&mut message, nonce.as_bytes());
+    message.extend_from_slice(&timestamp.to_le_bytes());
+    push_field(&mut message, operation.as_bytes());
+    push_field(&mut message, action);
+    message
+}
+
+/// Sign a `request_message_v2` message with a 32-byte private key
+pub fn sign_request_v2(private_key: &[u8], message: &[u8]) -> TappResult<Vec<u8>> {
+    let mut signature = vec![SIGNATURE_VERSION_2];
+    signature.extend(sign_message_recoverable(private_key, message)?);
+    Ok(signature)
+}
+
+/// Canonical deployer identity
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeployerIdentity {
+    /// 20-byte Ethereum address
+    pub address: Vec<u8>,
+    /// 64-byte uncompressed public key without the 0x04 prefix, when given
+    pub public_key: Option<Vec<u8>>,
+}
+
+impl DeployerIdentity {
+    /// Parse a deployer in any accepted form
+    /// Public keys must be valid curve points; the zero address is rejected.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, String> {
+        let sec1 = match bytes.len() {
+            ADDRESS_LEN => {
+                if bytes.iter().all(|b| *b == 0) {
+                    return Err("Deployer address must not be the zero address".to_string());
+                }
+                return Ok(Self {
+                    address: bytes.to_vec(),
+                    public_key: None,
+                });
+            }
+            33 | 65 => bytes.to_vec(),
+            64 => [&[0x04u8][..], bytes].concat(),
+            len => {
+                return Err(format!(
+                    "Deployer must be a 20-byte address or a 33, 64 or 65-byte \
+                     secp256k1 public key, got {} bytes",
+                    len
+                ))
+            }
+        };
+
+        let key = VerifyingKey::from_sec1_bytes(&sec1)
+            .map_err(|_| "Deployer is not a valid secp256k1 public key".to_string())?;
+        let public_key = key.to_encoded_point(false).as_bytes()[1..].to_vec();
+        Ok(Self {
+            address: eth_address(&public_key),
+            public_key: Some(public_key),
+        })
+    }
+
+    /// Parse a hex-encoded deployer, with or without a 0x prefix
+    pub fn from_hex(deployer: &str) -> Result<Self, String> {
+        let bytes = hex::decode(deployer.trim_start_matches("0x"))
+            .map_err(|e| format!("Deployer is not valid hex: {}", e))?;
+        Self::from_bytes(&bytes)
+    }
+
+    /// Lower-case hex of the public key if known, of the address otherwise
+    /// This is the form stored as an app's deployer.
+    pub fn canonical_hex(&self) -> String {
+        hex::encode(self.public_key.as_ref().unwrap_or(&self.address))
+    }
+
+    /// Lower-case hex of the address, without a 0x prefix
+    pub fn address_hex(&self) -> String {
+        hex::encode(&self.address)
+    }
+
+    /// Whether `signature` over `message` was made by this deployer
+    /// A 64-byte signature (`sign_message`) needs the public key. A 65-byte
+    /// recoverable signature over Keccak-256 of the message, or of its
+    /// EIP-191 form (personal_sign), is checked against the address, so it
+    /// also works for address-only deployers.
+    pub fn verify(&self, message: &[u8], signature: &[u8]) -> TappResult<bool> {
+        match signature.len() {
+            64 => match &self.public_key {
+                Some(public_key) => verify_signature(public_key, message, signature),
+                None => Err(TappError::InvalidParameter {
+                    field: "signature".to_string(),
+                    reason: "The deployer is only known by its address: sign with a \
+                             v2 or 65-byte recoverable signature"
+                        .to_string(),
+                }),
+            },
+            65 => Ok(self.recovers_to(message, signature)
+                || self.recovers_to(&eip191_message(message), signature)),
+            len => Err(TappError::InvalidParameter {
+                field: "signature".to_string(),
+                reason: format!("Signature must be 64, 65 or 66 bytes, got {}", len),
+            }),
+        }
+    }
+
+    /// Whether `signature` authorizes a deployer request
+    /// A v2 signature is checked against `message_v2` (see
+    /// `request_message_v2`); any other against `legacy_message` with
+    /// `verify`, or is an error without one (legacy signatures refused).
+    pub fn verify_request(
+        &self,
+        legacy_message: Option<&[u8]>,
+        message_v2: &[u8],
+        signature: &[u8],
+    ) -> TappResult<bool> {
+        match (signature.split_first(), legacy_message) {
+            (Some((&SIGNATURE_VERSION_2, recoverable)), _)
+                if signature.len() == SIGNATURE_V2_LEN =>
+            {
+                Ok(self.recovers_to(message_v2, recoverable))
+            }
+            (_, Some(legacy_message)) => self.verify(legacy_message, signature),
+            (_, None) => Err(TappError::InvalidParameter {
+                field: "signature".to_string(),
+                reason: "This request requires a v2 (0x02-prefixed) deployer signature".to_string(),
+            }),
+        }
+    }
+
+    /// Whether the 65-byte recoverable `signature` over Keccak-256 of
+    /// `message` was made by the key of this address
+    fn recovers_to(&self, message: &[u8], signature: &[u8]) -> bool {
+        recover_public_key(message, signature).is_ok_and(|key| eth_address(&key) == self.address)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app_key::{public_key_from_private, sign_message};
+    use k256::ecdsa::SigningKey;
+    use k256::elliptic_curve::rand_core::OsRng;
+
+    fn keypair() -> (Vec<u8>, VerifyingKey) {
+        let signing_key = SigningKey::random(&mut OsRng);
+        let verifying_key = *signing_key.verifying_key();
+        (signing_key.to_bytes().to_vec(), verifying_key)
+    }
+
+    #[test]
+    fn test_every_form_has_the_same_address() {
+        let (private_key, key) = keypair();
+        let public_key = public_key_from_private(&private_key).unwrap();
+        let address = eth_address(&public_key);
+
+        let compressed = key.to_encoded_point(true).as_bytes().to_vec();
+        let uncompressed = key.to_encoded_point(false).as_bytes().to_vec();
+        for bytes in [&compressed, &public_key, &uncompressed] {
+            let identity = DeployerIdentity::from_bytes(bytes).unwrap();
+            assert_eq!(identity.address, address);
+            assert_eq!(identity.public_key.as_ref(), Some(&public_key));
+            assert_eq!(identity.canonical_hex(), hex::encode(&public_key));
+        }
+
+        let identity = DeployerIdentity::from_bytes(&address).unwrap();
+        assert_eq!(identity.public_key, None);
+        assert_eq!(identity.canonical_hex(), hex::encode(&address));
+        assert_eq!(
+            DeployerIdentity::from_hex(&format!("0x{}", hex::encode(&address))).unwrap(),
+            identity
+        );
+    }
+
+    #[test]
+    fn test_invalid_deployers() {
+        assert!(DeployerIdentity::from_bytes(&[0; 32])
+            .unwrap_err()
+            .contains("got 32 bytes"));
+        assert!(DeployerIdentity::from_bytes(&[0; 20]).is_err());
+        assert!(DeployerIdentity::from_bytes(&[0; 64]).is_err());
+        // 65 bytes must carry the uncompressed prefix
+        let (_, key) = keypair();
+        let mut uncompressed = key.to_encoded_point(false).as_bytes().to_vec();
+        uncompressed[0] = 0x05;
+        assert!(DeployerIdentity::from_bytes(&uncompressed).is_err());
+        assert!(DeployerIdentity::from_hex("not hex").is_err());
+    }
+
+    #[test]
+    fn test_verify_with_public_key() {
+        let (private_key, _) = keypair();
+        let public_key = public_key_from_private(&private_key).unwrap();
+        let identity = DeployerIdentity::from_bytes(&public_key).unwrap();
+        let message = b"app-1stop";
+
+        let signature = sign_message(&private_key, message).unwrap();
+        assert!(identity.verify(message, &signature).unwrap());
+        assert!(!identity.verify(b"app-2stop", &signature).unwrap());
+
+        let recoverable = sign_message_recoverable(&private_key, message).unwrap();
+        assert!(identity.verify(message, &recoverable).unwrap());
+    }
+
+    #[test]
+    fn test_verify_address_only() {
+        let (private_key, _) = keypair();
+        let public_key = public_key_from_private(&private_key).unwrap();
+        let identity = DeployerIdentity::from_bytes(&eth_address(&public_key)).unwrap();
+        let message = b"app-1stop";
+
+        let raw = sign_message_recoverable(&private_key, message).unwrap();
+        assert!(identity.verify(message, &raw).unwrap());
+        let personal = sign_message_recoverable(&private_key, &eip191_message(message)).unwrap();
+        assert!(identity.verify(message, &personal).unwrap());
+        assert!(!identity.verify(b"app-2stop", &raw).unwrap());
+
+        let (other_key, _) = keypair();
+        let forged = sign_message_recoverable(&other_key, message).unwrap();
+        assert!(!identity.verify(message, &forged).unwrap());
+
+        // Without the public key a plain signature cannot be checked
+        let signature = sign_message(&private_key, message).unwrap();
+        assert!(matches!(
+            identity.verify(message, &signature),
+            Err(TappError::InvalidParameter { .. })
+        ));
+    }
+
+    #[test]
+    fn test_verify_request_versions() {
+        let (private_key, _) = keypair();
+        let public_key = public_key_from_private(&private_key).unwrap();
+        let by_key = DeployerIdentity::from_bytes(&public_key).unwrap();
+        let by_address = DeployerIdentity::from_bytes(&eth_address(&public_key)).unwrap();
+        let legacy =
+            crate::app_key::deployer_request_message("stop_app", "app-1", b"stop", "n1", 7);
+        let v2 = request_message_v2("app-1", "n1", 7, "stop_app", b"stop");
+
+        // v2 works whether or not the public key is stored
+        let signature = sign_request_v2(&private_key, &v2).unwrap();
+        assert_eq!(signature.len(), 66);
+        assert!(by_key
+            .verify_request(Some(&legacy), &v2, &signature)
+            .unwrap());
+        assert!(by_address
+            .verify_request(Some(&legacy), &v2, &signature)
+            .unwrap());
+        assert!(by_key.verify_request(None, &v2, &signature).unwrap());
+
+        // ...and only over the v2 message of this request
+        let other = request_message_v2("app-1", "n1", 7, "get_secret_key", b"");
+        assert!(!by_key
+            .verify_request(Some(&legacy), &other, &signature)
+            .unwrap());
+        let over_legacy = sign_request_v2(&private_key, &legacy).unwrap();
+        assert!(!by_key
+            .verify_request(Some(&legacy), &v2, &over_legacy)
+            .unwrap());
+
+        // Fields are framed: moving bytes between them changes the message
+        assert_ne!(
+            request_message_v2("app-1", "ab", 7, "stop_app", b""),
+            request_message_v2("app-1a", "b", 7, "stop_app", b"")
+        );
+
+        // Legacy signatures are accepted where a legacy message is given
+        let signature = sign_message(&private_key, &legacy).unwrap();
+        assert!(by_key
+            .verify_request(Some(&legacy), &v2, &signature)
+            .unwrap());
+        assert!(matches!(
+            by_key.verify_request(None, &v2, &signature),
+            Err(TappError::InvalidParameter { .. })
+        ));
+        let signature = sign_message_recoverable(&private_key, &legacy).unwrap();
+        assert!(by_address
+            .verify_request(Some(&legacy), &v2, &signature)
+            .unwrap());
+    }
+}
@@ -0,0 +1,235 @@
+//! ECIES envelope for mount files sealed to an app key
+//!
+//! Format: version (0x01) || ephemeral public key (65 bytes, uncompressed
+//! SEC1) || nonce (12 bytes) || AES-256-GCM ciphertext and tag
+//!
+//! The AES key is HKDF-SHA256 of the ECDH x-coordinate between the ephemeral
+//! key and the app key, salted with the ephemeral public key and with info
+//! "tapp-mount-file:v1". The app ID is the associated data, so a file sealed
+//! for one app cannot be deployed with another.
+//!
+//! `seal_to` and `open_with` build the same envelope with the caller's info
+//! and associated data, for other secrets sent to a secp256k1 key.
+
+use crate::error::{DockerError, FailureCause, TappResult};
+use k256::ecdh::{diffie_hellman, EphemeralSecret};
+use k256::elliptic_curve::rand_core::OsRng;
+use k256::elliptic_curve::sec1::ToEncodedPoint;
+use k256::{PublicKey, SecretKey};
+use ring::aead::{Aad, LessSafeKey, Nonce, UnboundKey, AES_256_GCM, NONCE_LEN};
+use ring::hkdf::{Salt, HKDF_SHA256};
+use ring::rand::{SecureRandom, SystemRandom};
+
+/// First byte of every envelope
+pub const ENVELOPE_VERSION: u8 = 1;
+
+/// HKDF info of the mount file envelope key
+const KEY_INFO: &[u8] = b"tapp-mount-file:v1";
+
+/// Uncompressed SEC1 public key length
+const PUBLIC_KEY_LEN: usize = 65;
+
+/// Bytes an envelope adds to its plaintext
+pub const ENVELOPE_OVERHEAD: usize = 1 + PUBLIC_KEY_LEN + NONCE_LEN + 16;
+
+fn failed(operation: &str, reason: impl Into<String>) -> DockerError {
+    DockerError::ContainerOperationFailed {
+        operation: operation.to_string(),
+        reason: reason.into(),
+        cause: FailureCause::Internal,
+    }
+}
+
+/// AES-256-GCM key shared by the ephemeral and the recipient's key
+fn envelope_key(key_info: &[u8], shared_secret: &[u8], ephemeral_public: &[u8]) -> LessSafeKey {
+    let prk = Salt::new(HKDF_SHA256, ephemeral_public).extract(shared_secret);
+    // Expanding 32 bytes cannot exceed HKDF's output limit
+    let okm = prk.expand(&[key_info], &AES_256_GCM).unwrap();
+    LessSafeKey::new(UnboundKey::from(okm))
+}
+
+/// Seal `plaintext` for the app key `public_key`
+/// The key may be 64 bytes without the 0x04 prefix, or SEC1-encoded.
+pub fn seal(public_key: &[u8], app_id: &str, plaintext: &[u8]) -> TappResult<Vec<u8>> {
+    seal_to(public_key, KEY_INFO, app_id.as_bytes(), plaintext)
+        .map_err(|reason| failed("seal_mount_file", reason).into())
+}
+
+/// Open an envelope sealed for `app_id` with the app's 32-byte private key
+pub fn open(private_key: &[u8], app_id: &str, envelope: &[u8]) -> TappResult<Vec<u8>> {
+    open_with(private_key, KEY_INFO, app_id.as_bytes(), envelope).map_err(|reason| {
+        let reason = match reason {
+            EnvelopeError::Decryption => {
+                format!("Decryption failed: not sealed to the key of app {}", app_id)
+            }
+            reason => reason.to_string(),
+        };
+        failed("open_mount_file", reason).into()
+    })
+}
+
+/// Why an envelope could not be built or opened
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum EnvelopeError {
+    #[error("Invalid public key: {0}")]
+    InvalidPublicKey(String),
+    #[error("Invalid private key: {0}")]
+    InvalidPrivateKey(String),
+    #[error("{0} bytes is too short for an envelope")]
+    Truncated(usize),
+    #[error("Unsupported envelope version {0}")]
+    UnsupportedVersion(u8),
+    #[error("Invalid ephemeral key: {0}")]
+    InvalidEphemeralKey(String),
+    #[error("No randomness for the nonce")]
+    Randomness,
+    #[error("Encryption failed")]
+    Encryption,
+    /// Sealed to another key, info or associated data, or altered
+    #[error("Decryption failed")]
+    Decryption,
+}
+
+/// Seal `plaintext` for the secp256k1 key `public_key` (64 bytes without the
+/// 0x04 prefix, or SEC1-encoded), deriving the key with `key_info` and
+/// authenticating `aad`
+pub fn seal_to(
+    public_key: &[u8],
+    key_info: &[u8],
+    aad: &[u8],
+    plaintext: &[u8],
+) -> Result<Vec<u8>, EnvelopeError> {
+    let sec1 = if public_key.len() == 64 {
+        [&[0x04][..], public_key].concat()
+    } else {
+        public_key.to_vec()
+    };
+    let public_key = PublicKey::from_sec1_bytes(&sec1)
+        .map_err(|e| EnvelopeError::InvalidPublicKey(e.to_string()))?;
+
+    let ephemeral = EphemeralSecret::random(&mut OsRng);
+    let ephemeral_public = ephemeral.public_key().to_encoded_point(false);
+    let shared = ephemeral.diffie_hellman(&public_key);
+    let key = envelope_key(
+        key_info,
+        shared.raw_secret_bytes(),
+        ephemeral_public.as_bytes(),
+    );
+
+    let mut nonce = [0u8; NONCE_LEN];
+    SystemRandom::new()
+        .fill(&mut nonce)
+        .map_err(|_| EnvelopeError::Randomness)?;
+
+    let mut ciphertext = plaintext.to_vec();
+    key.seal_in_place_append_tag(
+        Nonce::assume_unique_for_key(nonce),
+        Aad::from(aad),
+        &mut ciphertext,
+    )
+    .map_err(|_| EnvelopeError::Encryption)?;
+
+    let mut envelope = Vec::with_capacity(ENVELOPE_OVERHEAD + plaintext.len());
+    envelope.push(ENVELOPE_VERSION);
+    envelope.extend_from_slice(ephemeral_public.as_bytes());
+    envelope.extend_from_slice(&nonce);
+    envelope.extend_from_slice(&ciphertext);
+    Ok(envelope)
+}
+
+/// Open an envelope of `seal_to` with the 32-byte private key it was sealed
+/// for, the same `key_info` and `aad`
+pub fn open_with(
+    private_key: &[u8],
+    key_info: &[u8],
+    aad: &[u8],
+    envelope: &[u8],
+) -> Result<Vec<u8>, EnvelopeError> {
+    if envelope.len() < ENVELOPE_OVERHEAD {
+        return Err(EnvelopeError::Truncated(envelope.len()));
+    }
+    if envelope[0] != ENVELOPE_VERSION {
+        return Err(EnvelopeError::UnsupportedVersion(envelope[0]));
+    }
+
+    let (ephemeral_public, rest) = envelope[1..].split_at(PUBLIC_KEY_LEN);
+    let (nonce, ciphertext) = rest.split_at(NONCE_LEN);
+    let ephemeral = PublicKey::from_sec1_bytes(ephemeral_public)
+        .map_err(|e| EnvelopeError::InvalidEphemeralKey(e.to_string()))?;
+    let secret = SecretKey::from_slice(private_key)
+        .map_err(|e| EnvelopeError::InvalidPrivateKey(e.to_string()))?;
+
+    let shared = diffie_hellman(secret.to_nonzero_scalar(), ephemeral.as_affine());
+    let key = envelope_key(key_info, shared.raw_secret_bytes(), ephemeral_public);
+    let nonce = Nonce::try_assume_unique_for_key(nonce).unwrap();
+
+    let mut plaintext = ciphertext.to_vec();
+    let len = key
+        .open_in_place(nonce, Aad::from(aad), &mut plaintext)
+        .map_err(|_| EnvelopeError::Decryption)?
+        .len();
+    plaintext.truncate(len);
+    Ok(plaintext)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app_key::public_key_from_private;
+
+    #[test]
+    fn test_seal_and_open() {
+        let private_key = [0x42; 32];
+        let public_key = public_key_from_private(&private_key).unwrap();
+        let plaintext = b"password: hunter2\n";
+
+        let envelope = seal(&public_key, "app-1", plaintext).unwrap();
+        assert_eq!(envelope.len(), ENVELOPE_OVERHEAD + plaintext.len());
+        assert_eq!(envelope[0], ENVELOPE_VERSION);
+        assert_eq!(open(&private_key, "app-1", &envelope).unwrap(), plaintext);
+
+        // The SEC1 form of the key seals the same way
+        let sec1 = [&[0x04][..], public_key.as_slice()].concat();
+        let other = seal(&sec1, "app-1", plaintext).unwrap();
+        assert_ne!(other, envelope);
+        assert_eq!(open(&private_key, "app-1", &other).unwrap(), plaintext);
+
+        let empty = seal(&public_key, "app-1", b"").unwrap();
+        assert!(open(&private_key, "app-1", &empty).unwrap().is_empty());
+
+        // Other secrets derive their key with their own info
+        let other = seal_to(&public_key, b"other:v1", b"app-1", plaintext).unwrap();
+        assert_eq!(
+            open_with(&private_key, b"other:v1", b"app-1", &other).unwrap(),
+            plaintext
+        );
+        assert_eq!(
+            open_with(&private_key, KEY_INFO, b"app-1", &other),
+            Err(EnvelopeError::Decryption)
+        );
+    }
+
+    #[test]
+    fn test_open_rejects() {
+        let private_key = [0x42; 32];
+        let public_key = public_key_from_private(&private_key).unwrap();
+        let envelope = seal(&public_key, "app-1", b"secret").unwrap();
+        let error = |private_key: &[u8], app_id: &str, envelope: &[u8]| {
+            open(private_key, app_id, envelope).unwrap_err().to_string()
+        };
+
+        assert!(error(&private_key, "app-2", &envelope).contains("Decryption failed"));
+        assert!(error(&[0x43; 32], "app-1", &envelope).contains("Decryption failed"));
+
+        let mut tampered = envelope.clone();
+        *tampered.last_mut().unwrap() ^= 1;
+        assert!(error(&private_key, "app-1", &tampered).contains("Decryption failed"));
+
+        let mut v2 = envelope.clone();
+        v2[0] = 2;
+        assert!(error(&private_key, "app-1", &v2).contains("Unsupported envelope version 2"));
+        assert!(error(&private_key, "app-1", &envelope[..40]).contains("too short"));
+
+        assert!(seal(&[0x04; 10], "app-1", b"secret").is_err());
+    }
+}
@@ -1,15 +1,23 @@
+pub mod backup;
+pub mod deployer;
+pub mod envelope;
 pub mod kbs_client;
+pub mod sealed;
 pub use kbs_client::KbsClient;
 
 use crate::config::KbsConfig;
-use crate::error::{DockerError, TappResult};
+use crate::error::{DockerError, FailureCause, KbsError, TappError, TappResult};
 use crate::proto::GetAppKeyResponse;
-use k256::ecdsa::{signature::Signer, signature::Verifier, Signature, SigningKey, VerifyingKey};
+use crate::telemetry::SPAN_TARGET;
+use k256::ecdsa::{
+    signature::Signer, signature::Verifier, RecoveryId, Signature, SigningKey, VerifyingKey,
+};
 use k256::elliptic_curve::sec1::ToEncodedPoint;
+use sha2::Sha512;
 use sha3::{Digest, Keccak256};
 use std::collections::HashMap;
 use tokio::sync::Mutex;
-use tracing::{debug, info, warn};
+use tracing::{debug, info, warn, Instrument};
 
 /// Ethereum key pair
 #[derive(Clone)]
@@ -26,6 +34,8 @@ pub struct AppKeyService {
     app_keys: Mutex<HashMap<String, EthKeyPair>>,
     /// Whether to use in-memory keys (if false, use KBS)
     use_in_memory: bool,
+    /// Master key of sealed data, fetched on first use
+    sealing_key: Mutex<Option<Vec<u8>>>,
 }
 
 impl AppKeyService {
@@ -43,9 +53,67 @@ impl AppKeyService {
             kbs_client,
             app_keys: Mutex::new(HashMap::new()),
             use_in_memory,
+            sealing_key: Mutex::new(None),
         })
     }
 
+    /// Master key the keys of sealed data are derived from
+    /// In-memory mode generates one per process, so sealed data does not
+    /// survive a restart; otherwise it is the KBS resource
+    /// `default/key/tapp-sealing-key`, the same on every start.
+    pub async fn sealing_master_key(&self) -> TappResult<Vec<u8>> {
+        let mut sealing_key = self.sealing_key.lock().await;
+        if let Some(key) = sealing_key.as_ref() {
+            return Ok(key.clone());
+        }
+
+        let key = if self.use_in_memory {
+            use ring::rand::SecureRandom;
+
+            let mut key = vec![0u8; sealed::MIN_MASTER_KEY_LEN];
+            ring::rand::SystemRandom::new()
+                .fill(&mut key)
+                .map_err(|_| TappError::Crypto("No randomness for the sealing key".to_string()))?;
+            info!("Generated in-memory sealing key");
+            key
+        } else {
+            let span = tracing::info_span!(
+                target: SPAN_TARGET,
+                "kbs.get_resource",
+                endpoint = %self.kbs_client.endpoint(),
+                resource = "tapp-sealing-key",
+            );
+            let key = self
+                .kbs_client
+                .get_resource("kbs:///default/key/tapp-sealing-key")
+                .instrument(span)
+                .await?;
+            if key.len() < sealed::MIN_MASTER_KEY_LEN {
+                return Err(KbsError::KeyDerivationFailed {
+                    reason: format!(
+                        "Sealing key has {} bytes, at least {} are required",
+                        key.len(),
+                        sealed::MIN_MASTER_KEY_LEN
+                    ),
+                }
+                .into());
+            }
+            info!("Fetched sealing key from KBS");
+            key
+        };
+        *sealing_key = Some(key.clone());
+        Ok(key)
+    }
+
+    /// Value of the compose secret placeholder `name` of an app (see
+    /// `crate::boot::compose_secrets`)
+    /// Derived from the sealing master key, so it is the same on every
+    /// deployment of the app for as long as the master key is.
+    pub async fn compose_secret(&self, app_id: &str, name: &str) -> TappResult<String> {
+        let master_key = self.sealing_master_key().await?;
+        Ok(derive_compose_secret(&master_key, app_id, name))
+    }
+
     /// Generate a new Ethereum key pair for an app
     fn generate_eth_keypair(app_id: &str) -> TappResult<EthKeyPair> {
         use k256::elliptic_curve::rand_core::OsRng;
@@ -65,10 +133,7 @@ impl AppKeyService {
         let public_key = public_key_bytes.to_vec();
 
         // Calculate Ethereum address from 64-byte public key (without prefix)
-        let mut hasher = Keccak256::new();
-        hasher.update(public_key_without_prefix);
-        let hash = hasher.finalize();
-        let eth_address = hash[12..].to_vec(); // Last 20 bytes
+        let eth_address = eth_address(public_key_without_prefix);
 
         debug!(
             app_id = %app_id,
@@ -110,6 +175,7 @@ impl AppKeyService {
             return Err(DockerError::ContainerOperationFailed {
                 operation: "get_private_key".to_string(),
                 reason: "Private key retrieval only supported in in-memory mode".to_string(),
+                cause: FailureCause::Precondition,
             }
             .into());
         }
@@ -157,14 +223,30 @@ impl AppKeyService {
                     Err(DockerError::ContainerOperationFailed {
                         operation: "get_app_key".to_string(),
                         reason: format!("Unsupported key type: {}", key_type),
+                        cause: FailureCause::Precondition,
                     }
                     .into())
                 }
             }
         } else {
             // Use KBS
-            let resource_uri = format!("kbs:///default/key/{}", app_id);
-            match self.kbs_client.get_resource(&resource_uri).await {
+            // Namespaced IDs contain '/', which would add a path segment
+            let resource_uri = format!(
+                "kbs:///default/key/{}",
+                crate::namespace::storage_name(app_id)
+            );
+            let span = tracing::info_span!(
+                target: SPAN_TARGET,
+                "kbs.get_resource",
+                endpoint = %self.kbs_client.endpoint(),
+                app_id = %app_id,
+            );
+            match self
+                .kbs_client
+                .get_resource(&resource_uri)
+                .instrument(span)
+                .await
+            {
                 Ok(key_data) => Ok(GetAppKeyResponse {
                     success: true,
                     message: format!("Key from KBS for app {}", app_id),
@@ -185,12 +267,106 @@ impl AppKeyService {
     }
 }
 
+/// HKDF info prefix of the compose secret values
+const COMPOSE_SECRET_INFO: &[u8] = b"tapp-compose-secret:v1";
+
+/// Hex of HKDF-SHA256 of `master_key` with info "tapp-compose-secret:v1" ||
+/// app_id || 0x00 || name, 32 bytes
+fn derive_compose_secret(master_key: &[u8], app_id: &str, name: &str) -> String {
+    use ring::hkdf::{Salt, HKDF_SHA256};
+
+    let prk = Salt::new(HKDF_SHA256, &[]).extract(master_key);
+    let info: [&[u8]; 4] = [
+        COMPOSE_SECRET_INFO,
+        app_id.as_bytes(),
+        &[0],
+        name.as_bytes(),
+    ];
+    let mut value = [0u8; 32];
+    // Expanding 32 bytes cannot exceed HKDF's output limit
+    prk.expand(&info, HKDF_SHA256)
+        .and_then(|okm| okm.fill(&mut value))
+        .unwrap();
+    hex::encode(value)
+}
+
+/// Domain tag opening every legacy deployer request message
+const DEPLOYER_REQUEST_DOMAIN: &[u8] = b"tapp-deployer-request:v1";
+
+/// Append `field` to `message`, prefixed with its length (4 bytes, big-endian)
+/// so that adjacent fields cannot be re-split into other values
+fn push_field(message: &mut Vec<u8>, field: &[u8]) {
+    message.extend_from_slice(&(field.len() as u32).to_be_bytes());
+    message.extend_from_slice(field);
+}
+
+/// Build the message a deployer signs to authorize an operation on an app
+/// Format: domain || operation || app_id || action || nonce, each prefixed
+/// with its length (see `push_field`), then timestamp (little-endian i64).
+/// `operation` names the request (`stop_app`, `get_secret_key`, ...) and
+/// `action` binds its parameters (empty for GetAppSecretKey).
+pub fn deployer_request_message(
+    operation: &str,
+    app_id: &str,
+    action: &[u8],
+    nonce: &str,
+    timestamp: i64,
+) -> Vec<u8> {
+    let mut message = Vec::new();
+    push_field(&mut message, DEPLOYER_REQUEST_DOMAIN);
+    push_field(&mut message, operation.as_bytes());
+    push_field(&mut message, app_id.as_bytes());
+    push_field(&mut message, action);
+    push_field(&mut message, nonce.as_bytes());
+    message.extend_from_slice(&timestamp.to_le_bytes());
+    message
+}
+
+/// 64-byte uncompressed public key (without the 0x04 prefix) of a 32-byte
+/// private key
+pub fn public_key_from_private(private_key: &[u8]) -> TappResult<Vec<u8>> {
+    let signing_key =
+        SigningKey::from_slice(private_key).map_err(|e| DockerError::ContainerOperationFailed {
+            operation: "public_key_from_private".to_string(),
+            reason: format!("Invalid private key: {}", e),
+            cause: FailureCause::Internal,
+        })?;
+    let public_key = signing_key.verifying_key().to_encoded_point(false);
+    Ok(public_key.as_bytes()[1..].to_vec())
+}
+
+/// Ethereum address of a 64-byte public key: the last 20 bytes of its Keccak-256
+pub fn eth_address(public_key: &[u8]) -> Vec<u8> {
+    Keccak256::digest(public_key)[12..].to_vec()
+}
+
+/// EIP-55 mixed-case form of a 20-byte Ethereum address, `0x`-prefixed
+pub fn checksum_address(address: &[u8]) -> String {
+    let lower = hex::encode(address);
+    let hash = Keccak256::digest(lower.as_bytes());
+    let checksummed: String = lower
+        .chars()
+        .enumerate()
+        .map(|(i, c)| {
+            // Letters are upper-cased where the hash nibble is 8 or more
+            let nibble = (hash[i / 2] >> (if i % 2 == 0 { 4 } else { 0 })) & 0x0f;
+            if nibble >= 8 {
+                c.to_ascii_uppercase()
+            } else {
+                c
+            }
+        })
+        .collect();
+    format!("0x{}", checksummed)
+}
+
 /// Sign a message using a private key
 pub fn sign_message(private_key: &[u8], message: &[u8]) -> TappResult<Vec<u8>> {
     if private_key.len() != 32 {
         return Err(DockerError::ContainerOperationFailed {
             operation: "sign_message".to_string(),
             reason: format!("Private key must be 32 bytes, got {}", private_key.len()),
+            cause: FailureCause::Internal,
         }
         .into());
     }
@@ -199,6 +375,7 @@ pub fn sign_message(private_key: &[u8], message: &[u8]) -> TappResult<Vec<u8>> {
         SigningKey::from_slice(private_key).map_err(|e| DockerError::ContainerOperationFailed {
             operation: "sign_message".to_string(),
             reason: format!("Invalid private key: {}", e),
+            cause: FailureCause::Internal,
         })?;
 
     let signature: Signature = signing_key.sign(message);
@@ -211,6 +388,7 @@ pub fn verify_signature(public_key: &[u8], message: &[u8], signature: &[u8]) ->
         return Err(DockerError::ContainerOperationFailed {
             operation: "verify_signature".to_string(),
             reason: format!("Public key must be 64 bytes, got {}", public_key.len()),
+            cause: FailureCause::Internal,
         }
         .into());
     }
@@ -222,6 +400,7 @@ pub fn verify_signature(public_key: &[u8], message: &[u8], signature: &[u8]) ->
         DockerError::ContainerOperationFailed {
             operation: "verify_signature".to_string(),
             reason: format!("Invalid public key: {}", e),
+            cause: FailureCause::Internal,
         }
     })?;
 
@@ -229,6 +408,7 @@ pub fn verify_signature(public_key: &[u8], message: &[u8], signature: &[u8]) ->
         Signature::from_slice(signature).map_err(|e| DockerError::ContainerOperationFailed {
             operation: "verify_signature".to_string(),
             reason: format!("Invalid signature: {}", e),
+            cause: FailureCause::Internal,
         })?;
 
     match verifying_key.verify(message, &sig) {
@@ -237,6 +417,86 @@ pub fn verify_signature(public_key: &[u8], message: &[u8], signature: &[u8]) ->
     }
 }
 
+/// `message` with the prefix Ethereum's personal_sign adds before hashing
+/// (EIP-191 version 0x45)
+pub fn eip191_message(message: &[u8]) -> Vec<u8> {
+    let mut prefixed = format!("\x19Ethereum Signed Message:\n{}", message.len()).into_bytes();
+    prefixed.extend_from_slice(message);
+    prefixed
+}
+
+/// Sign Keccak-256(message) the way Ethereum does
+/// Returns r || s || v with v = 27 + recovery ID.
+pub fn sign_message_recoverable(private_key: &[u8], message: &[u8]) -> TappResult<Vec<u8>> {
+    let signing_key =
+        SigningKey::from_slice(private_key).map_err(|e| DockerError::ContainerOperationFailed {
+            operation: "sign_message_recoverable".to_string(),
+            reason: format!("Invalid private key: {}", e),
+            cause: FailureCause::Internal,
+        })?;
+
+    let (signature, recovery_id) = signing_key
+        .sign_prehash_recoverable(&Keccak256::digest(message))
+        .map_err(|e| DockerError::ContainerOperationFailed {
+            operation: "sign_message_recoverable".to_string(),
+            reason: format!("Signing failed: {}", e),
+            cause: FailureCause::Internal,
+        })?;
+
+    let mut bytes = signature.to_bytes().to_vec();
+    bytes.push(27 + recovery_id.to_byte());
+    Ok(bytes)
+}
+
+/// 64-byte public key that made a `sign_message_recoverable` signature
+/// `v` may be given as 27/28 or as the bare recovery ID.
+pub fn recover_public_key(message: &[u8], signature: &[u8]) -> TappResult<Vec<u8>> {
+    let invalid = |reason: String| DockerError::ContainerOperationFailed {
+        operation: "recover_public_key".to_string(),
+        reason,
+        cause: FailureCause::Internal,
+    };
+    if signature.len() != 65 {
+        return Err(invalid(format!(
+            "Recoverable signature must be 65 bytes, got {}",
+            signature.len()
+        ))
+        .into());
+    }
+
+    let v = signature[64];
+    let recovery_id = RecoveryId::from_byte(if v >= 27 { v - 27 } else { v })
+        .ok_or_else(|| invalid(format!("Invalid recovery ID v = {}", v)))?;
+    let sig = Signature::from_slice(&signature[..64])
+        .map_err(|e| invalid(format!("Invalid signature: {}", e)))?;
+
+    let verifying_key =
+        VerifyingKey::recover_from_prehash(&Keccak256::digest(message), &sig, recovery_id)
+            .map_err(|e| invalid(format!("Public key recovery failed: {}", e)))?;
+    Ok(verifying_key.to_encoded_point(false).as_bytes()[1..].to_vec())
+}
+
+/// Domain separator of the report data binding an app key to its app
+const ATTESTED_KEY_DOMAIN: &[u8] = b"tapp-attested-key:v1";
+
+/// Longest nonce GetAttestedAppKey accepts
+pub const MAX_ATTESTED_KEY_NONCE_LEN: usize = 64;
+
+/// Report data of GetAttestedAppKey's evidence, binding an app's public key
+/// to its compose hash and the caller's nonce:
+/// SHA-512("tapp-attested-key:v1" || len(nonce) as u8 || nonce || public_key || compose_hash)
+/// `public_key` is the 64-byte key without the 0x04 prefix and `compose_hash`
+/// the raw bytes of the app's compose hash.
+pub fn attested_key_report_data(nonce: &[u8], public_key: &[u8], compose_hash: &[u8]) -> Vec<u8> {
+    let mut hasher = Sha512::new();
+    hasher.update(ATTESTED_KEY_DOMAIN);
+    hasher.update([nonce.len() as u8]);
+    hasher.update(nonce);
+    hasher.update(public_key);
+    hasher.update(compose_hash);
+    hasher.finalize().to_vec()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -261,4 +521,163 @@ mod tests {
         let is_valid = verify_signature(&key_pair.public_key, wrong_message, &signature).unwrap();
         assert!(!is_valid);
     }
+
+    #[test]
+    fn test_deployer_request_message_binds_action() {
+        let key_pair = AppKeyService::generate_eth_keypair("test-app").unwrap();
+        // Deployer keys are recorded without the 0x04 SEC1 prefix
+        let deployer = &key_pair.public_key[key_pair.public_key.len() - 64..];
+
+        let stop = deployer_request_message("stop_app", "test-app", b"stop", "ab01", 1_700_000_000);
+        let signature = sign_message(&key_pair.private_key, &stop).unwrap();
+        assert!(verify_signature(deployer, &stop, &signature).unwrap());
+
+        // A stop signature cannot be replayed as a secret key request, even
+        // with the action moved into the nonce
+        for secret in [
+            deployer_request_message("get_secret_key", "test-app", b"", "ab01", 1_700_000_000),
+            deployer_request_message("stop_app", "test-app", b"", "stopab01", 1_700_000_000),
+        ] {
+            assert!(!verify_signature(deployer, &secret, &signature).unwrap());
+        }
+
+        // Fields are framed: moving bytes between them changes the message
+        assert_ne!(
+            deployer_request_message("stop_app", "app", b"stop", "ab", 1),
+            deployer_request_message("stop_app", "apps", b"top", "ab", 1)
+        );
+    }
+
+    #[test]
+    fn test_checksum_address() {
+        // Test vectors from EIP-55
+        for expected in [
+            "0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed",
+            "0xfB6916095ca1df60bB79Ce92cE3Ea74c37c5d359",
+            "0xdbF03B407c01E7cD3CBea99509d93f8DDDC8C6FB",
+        ] {
+            let address = hex::decode(expected[2..].to_lowercase()).unwrap();
+            assert_eq!(checksum_address(&address), expected);
+        }
+    }
+
+    #[test]
+    fn test_public_key_from_private() {
+        let private_key =
+            hex::decode("4c0883a69102937d6231471b5dbb6204fe5129617082792ae468d01a3f362318")
+                .unwrap();
+        let public_key = public_key_from_private(&private_key).unwrap();
+        assert_eq!(public_key.len(), 64);
+        assert_eq!(
+            checksum_address(&eth_address(&public_key)),
+            "0x2c7536E3605D9C16a7a3D7b1898e529396a65c23"
+        );
+
+        // Signatures made with the private key verify against it
+        let signature = sign_message(&private_key, b"deploy").unwrap();
+        assert!(verify_signature(&public_key, b"deploy", &signature).unwrap());
+
+        assert!(public_key_from_private(&[0u8; 32]).is_err());
+        assert!(public_key_from_private(&private_key[..31]).is_err());
+    }
+
+    #[test]
+    fn test_eip191_signature() {
+        // ethers' Wallet.signMessage("Some data") with this key
+        let private_key =
+            hex::decode("4c0883a69102937d6231471b5dbb6204fe5129617082792ae468d01a3f362318")
+                .unwrap();
+        let expected = "b91467e570a6466aa9e9876cbcd013baba02900b8979d43fe208a4a4f339f5fd\
+                        6007e74cd82e037b800186422fc2da167c747ef045e5d18a5f5d4300f8e1a0291c";
+
+        let message = eip191_message(b"Some data");
+        let signature = sign_message_recoverable(&private_key, &message).unwrap();
+        assert_eq!(hex::encode(&signature), expected);
+
+        let public_key = recover_public_key(&message, &signature).unwrap();
+        assert_eq!(public_key, public_key_from_private(&private_key).unwrap());
+        assert_eq!(
+            checksum_address(&eth_address(&public_key)),
+            "0x2c7536E3605D9C16a7a3D7b1898e529396a65c23"
+        );
+
+        // v as the bare recovery ID recovers the same key
+        let mut bare_v = signature.clone();
+        bare_v[64] -= 27;
+        assert_eq!(recover_public_key(&message, &bare_v).unwrap(), public_key);
+
+        // Another message recovers another key
+        let other = recover_public_key(&eip191_message(b"Other data"), &signature);
+        assert!(other.map_or(true, |key| key != public_key));
+
+        assert!(recover_public_key(&message, &signature[..64]).is_err());
+    }
+
+    #[test]
+    fn test_attested_key_report_data() {
+        let nonce = hex::decode("0102030405060708").unwrap();
+        let report_data = attested_key_report_data(&nonce, &[0x11; 64], &[0x22; 48]);
+        assert_eq!(
+            hex::encode(&report_data),
+            "40a85508ae926e62d9685a2354242daa829f995ffa3b0ab41de27938e9554a2e\
+             1d371698fd644f5520e76cf283a7b7fea525471722ae395996eb016fdfdd0924"
+        );
+
+        // Every input is bound
+        assert_ne!(
+            attested_key_report_data(&nonce[1..], &[0x11; 64], &[0x22; 48]),
+            report_data
+        );
+        assert_ne!(
+            attested_key_report_data(&nonce, &[0x12; 64], &[0x22; 48]),
+            report_data
+        );
+        assert_ne!(
+            attested_key_report_data(&nonce, &[0x11; 64], &[0x23; 48]),
+            report_data
+        );
+    }
+
+    #[tokio::test]
+    async fn test_compose_secret() {
+        let master_key = [7u8; 32];
+        let value = derive_compose_secret(&master_key, "app-1", "db_password");
+        assert_eq!(value.len(), 64);
+        assert_eq!(
+            derive_compose_secret(&master_key, "app-1", "db_password"),
+            value
+        );
+        // Bound to the app, the name and the master key
+        for other in [
+            derive_compose_secret(&master_key, "app-2", "db_password"),
+            derive_compose_secret(&master_key, "app-1", "api_token"),
+            derive_compose_secret(&[8u8; 32], "app-1", "db_password"),
+            // The separator keeps the app ID and the name apart
+            derive_compose_secret(&master_key, "app-1d", "b_password"),
+        ] {
+            assert_ne!(other, value);
+        }
+
+        let service = AppKeyService::new(&KbsConfig::default(), true)
+            .await
+            .unwrap();
+        let value = service
+            .compose_secret("app-1", "db_password")
+            .await
+            .unwrap();
+        assert_eq!(
+            service
+                .compose_secret("app-1", "db_password")
+                .await
+                .unwrap(),
+            value
+        );
+        assert_ne!(
+            service
+                .compose_secret("app-2", "db_password")
+                .await
+                .unwrap(),
+            value
+        );
+    }
 }
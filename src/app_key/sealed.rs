@@ -0,0 +1,585 @@
+//! Sealed data of apps
+//!
+//! SealData encrypts bytes of an app under a key only this node derives for
+//! it and keeps the envelope in the app directory as `sealed/<label>.blob`.
+//! UnsealData opens it for the same app only, and for a blob bound to the
+//! measurement only while the app runs the compose file it was sealed under.
+//!
+//! Envelope format (version 1): version (0x01) || binding (0x00 measurement,
+//! 0x01 app ID) || sealed_at (little-endian i64, unix seconds) || salt
+//! (32 bytes) || nonce (12 bytes) || AES-256-GCM ciphertext and tag
+//!
+//! The AES key is HKDF-SHA256 of the sealing master key (see
+//! `AppKeyService::sealing_master_key`) salted with `salt`, with info
+//! "tapp-sealed-data:v1" || binding || app_id, plus 0x00 || compose_hash for
+//! the measurement binding. The header and the label are the associated
+//! data, so a blob can be neither renamed nor rebound.
+
+use crate::config::SealedDataConfig;
+use crate::error::{TappError, TappResult};
+use ring::aead::{Aad, LessSafeKey, Nonce, UnboundKey, AES_256_GCM, NONCE_LEN};
+use ring::hkdf::{Salt, HKDF_SHA256};
+use ring::rand::{SecureRandom, SystemRandom};
+use std::path::{Path, PathBuf};
+use tokio::fs;
+use tokio::sync::Mutex;
+
+/// First byte of every envelope
+pub const ENVELOPE_VERSION: u8 = 1;
+
+/// HKDF info prefix of the blob keys
+const KEY_INFO: &[u8] = b"tapp-sealed-data:v1";
+
+/// Shortest sealing master key accepted
+pub const MIN_MASTER_KEY_LEN: usize = 32;
+
+/// Longest label accepted
+pub const MAX_LABEL_LEN: usize = 64;
+
+const SALT_LEN: usize = 32;
+const TAG_LEN: usize = 16;
+const HEADER_LEN: usize = 1 + 1 + 8 + SALT_LEN + NONCE_LEN;
+
+/// Directory of the blobs within an app directory
+const SEALED_DIR: &str = "sealed";
+
+/// What a blob is bound to, besides the node's master key
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Binding {
+    /// The app ID and the compose hash the app ran when it was sealed
+    Measurement,
+    /// The app ID only, whatever compose file the app runs
+    AppId,
+}
+
+impl Binding {
+    fn to_byte(self) -> u8 {
+        match self {
+            Binding::Measurement => 0,
+            Binding::AppId => 1,
+        }
+    }
+
+    fn from_byte(byte: u8) -> Option<Self> {
+        match byte {
+            0 => Some(Binding::Measurement),
+            1 => Some(Binding::AppId),
+            _ => None,
+        }
+    }
+
+    pub fn from_proto(binding: crate::proto::SealBinding) -> Self {
+        match binding {
+            crate::proto::SealBinding::SealToMeasurement => Binding::Measurement,
+            crate::proto::SealBinding::SealToAppId => Binding::AppId,
+        }
+    }
+
+    pub fn to_proto(self) -> crate::proto::SealBinding {
+        match self {
+            Binding::Measurement => crate::proto::SealBinding::SealToMeasurement,
+            Binding::AppId => crate::proto::SealBinding::SealToAppId,
+        }
+    }
+}
+
+/// A stored blob, as listed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SealedBlob {
+    pub label: String,
+    pub binding: Binding,
+    /// Plaintext size in bytes
+    pub size: usize,
+    /// When it was sealed (unix seconds)
+    pub sealed_at: i64,
+}
+
+impl SealedBlob {
+    pub fn to_proto(&self) -> crate::proto::SealedBlobInfo {
+        crate::proto::SealedBlobInfo {
+            label: self.label.clone(),
+            binding: self.binding.to_proto() as i32,
+            size: self.size as i64,
+            sealed_at: self.sealed_at,
+        }
+    }
+}
+
+/// Whether `label` is usable: 1 to 64 letters, digits, `.`, `_` and `-`,
+/// not starting with `.`
+pub fn validate_label(label: &str) -> bool {
+    !label.is_empty()
+        && label.len() <= MAX_LABEL_LEN
+        && !label.starts_with('.')
+        && label
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-'))
+}
+
+fn check_label(label: &str) -> TappResult<()> {
+    if validate_label(label) {
+        return Ok(());
+    }
+    Err(TappError::InvalidParameter {
+        field: "label".to_string(),
+        reason: format!(
+            "'{}' must be 1 to {} letters, digits, '.', '_' or '-', not starting with '.'",
+            label, MAX_LABEL_LEN
+        ),
+    })
+}
+
+/// AES-256-GCM key of a blob
+fn blob_key(
+    master_key: &[u8],
+    salt: &[u8],
+    binding: Binding,
+    app_id: &str,
+    compose_hash: &str,
+) -> LessSafeKey {
+    let prk = Salt::new(HKDF_SHA256, salt).extract(master_key);
+    let binding_byte = [binding.to_byte()];
+    let mut info: Vec<&[u8]> = vec![KEY_INFO, &binding_byte, app_id.as_bytes()];
+    if binding == Binding::Measurement {
+        info.extend([&[0u8][..], compose_hash.as_bytes()]);
+    }
+    // Expanding 32 bytes cannot exceed HKDF's output limit
+    let okm = prk.expand(&info, &AES_256_GCM).unwrap();
+    LessSafeKey::new(UnboundKey::from(okm))
+}
+
+/// Associated data of a blob: its header and label
+fn associated_data(header: &[u8], label: &str) -> Aad<Vec<u8>> {
+    Aad::from([header, label.as_bytes()].concat())
+}
+
+/// Seal `data` of `app_id` for storage under `label`
+pub fn seal(
+    master_key: &[u8],
+    app_id: &str,
+    compose_hash: &str,
+    label: &str,
+    binding: Binding,
+    sealed_at: i64,
+    data: &[u8],
+) -> TappResult<Vec<u8>> {
+    let rng = SystemRandom::new();
+    let mut salt = [0u8; SALT_LEN];
+    let mut nonce = [0u8; NONCE_LEN];
+    rng.fill(&mut salt)
+        .and_then(|_| rng.fill(&mut nonce))
+        .map_err(|_| TappError::Crypto("No randomness for the sealed data key".to_string()))?;
+
+    let mut envelope = Vec::with_capacity(HEADER_LEN + data.len() + TAG_LEN);
+    envelope.push(ENVELOPE_VERSION);
+    envelope.push(binding.to_byte());
+    envelope.extend_from_slice(&sealed_at.to_le_bytes());
+    envelope.extend_from_slice(&salt);
+    envelope.extend_from_slice(&nonce);
+
+    let key = blob_key(master_key, &salt, binding, app_id, compose_hash);
+    let mut ciphertext = data.to_vec();
+    key.seal_in_place_append_tag(
+        Nonce::assume_unique_for_key(nonce),
+        associated_data(&envelope, label),
+        &mut ciphertext,
+    )
+    .map_err(|_| TappError::Crypto("Sealing failed".to_string()))?;
+    envelope.extend_from_slice(&ciphertext);
+    Ok(envelope)
+}
+
+/// Describe an envelope stored under `label` without opening it
+pub fn read_header(label: &str, envelope: &[u8]) -> TappResult<SealedBlob> {
+    if envelope.len() < HEADER_LEN + TAG_LEN {
+        return Err(TappError::Crypto(format!(
+            "Sealed blob {} is truncated",
+            label
+        )));
+    }
+    if envelope[0] != ENVELOPE_VERSION {
+        return Err(TappError::Crypto(format!(
+            "Sealed blob {} has unsupported envelope version {}",
+            label, envelope[0]
+        )));
+    }
+    let binding = Binding::from_byte(envelope[1]).ok_or_else(|| {
+        TappError::Crypto(format!(
+            "Sealed blob {} has unknown binding {}",
+            label, envelope[1]
+        ))
+    })?;
+    // The length was checked above
+    let sealed_at = i64::from_le_bytes(envelope[2..10].try_into().unwrap());
+    Ok(SealedBlob {
+        label: label.to_string(),
+        binding,
+        size: envelope.len() - HEADER_LEN - TAG_LEN,
+        sealed_at,
+    })
+}
+
+/// Open an envelope stored under `label` for `app_id`, running `compose_hash`
+/// Fails with `SealBindingMismatch` when it was sealed for another app,
+/// compose hash (measurement binding) or node, or was altered.
+pub fn open(
+    master_key: &[u8],
+    app_id: &str,
+    compose_hash: &str,
+    label: &str,
+    envelope: &[u8],
+) -> TappResult<(SealedBlob, Vec<u8>)> {
+    let blob = read_header(label, envelope)?;
+    let (header, ciphertext) = envelope.split_at(HEADER_LEN);
+    let salt = &header[10..10 + SALT_LEN];
+    // The header length was checked by read_header
+    let nonce: [u8; NONCE_LEN] = header[10 + SALT_LEN..].try_into().unwrap();
+
+    let key = blob_key(master_key, salt, blob.binding, app_id, compose_hash);
+    let mut plaintext = ciphertext.to_vec();
+    let len = key
+        .open_in_place(
+            Nonce::assume_unique_for_key(nonce),
+            associated_data(header, label),
+            &mut plaintext,
+        )
+        .map_err(|_| TappError::SealBindingMismatch {
+            app_id: app_id.to_string(),
+            label: label.to_string(),
+        })?
+        .len();
+    plaintext.truncate(len);
+    Ok((blob, plaintext))
+}
+
+/// Sealed blobs of the apps under a data directory
+/// Blobs live in their app's directory, so they are removed with it
+/// (StopApp, ForceRemoveApp).
+pub struct SealedDataStore {
+    data_dir: PathBuf,
+    limits: SealedDataConfig,
+    /// Serializes writes, so the blob count holds and a label is written
+    /// by one call at a time
+    writes: Mutex<()>,
+}
+
+impl SealedDataStore {
+    pub fn new(data_dir: &Path, limits: SealedDataConfig) -> Self {
+        Self {
+            data_dir: data_dir.to_path_buf(),
+            limits,
+            writes: Mutex::new(()),
+        }
+    }
+
+    fn app_dir(&self, app_id: &str) -> PathBuf {
+        self.data_dir
+            .join(crate::namespace::storage_name(app_id).as_ref())
+    }
+
+    fn blob_path(&self, app_id: &str, label: &str) -> PathBuf {
+        self.app_dir(app_id)
+            .join(SEALED_DIR)
+            .join(format!("{}.blob", label))
+    }
+
+    /// Seal `data` and store it under `label`, replacing a blob of that label
+    pub async fn seal(
+        &self,
+        master_key: &[u8],
+        app_id: &str,
+        compose_hash: &str,
+        label: &str,
+        binding: Binding,
+        data: &[u8],
+    ) -> TappResult<SealedBlob> {
+        check_label(label)?;
+        if data.len() > self.limits.max_blob_bytes {
+            return Err(TappError::InvalidParameter {
+                field: "data".to_string(),
+                reason: format!(
+                    "{} bytes exceed the limit of {} bytes",
+                    data.len(),
+                    self.limits.max_blob_bytes
+                ),
+            });
+        }
+        let app_dir = self.app_dir(app_id);
+        if !app_dir.exists() {
+            return Err(TappError::AppNotFound {
+                app_id: app_id.to_string(),
+            });
+        }
+
+        let _write = self.writes.lock().await;
+        let path = self.blob_path(app_id, label);
+        if !path.exists() && self.list(app_id).await?.len() >= self.limits.max_blobs_per_app {
+            return Err(TappError::ResourceExhausted(format!(
+                "App {} already keeps {} sealed blobs",
+                app_id, self.limits.max_blobs_per_app
+            )));
+        }
+
+        let sealed_at = crate::utils::current_timestamp();
+        let envelope = seal(
+            master_key,
+            app_id,
+            compose_hash,
+            label,
+            binding,
+            sealed_at,
+            data,
+        )?;
+        // Renamed into place, so a blob is never seen half written
+        let dir = app_dir.join(SEALED_DIR);
+        fs::create_dir_all(&dir).await?;
+        let partial = dir.join(format!(".{}.partial", label));
+        fs::write(&partial, &envelope).await?;
+        fs::rename(&partial, &path).await?;
+
+        Ok(SealedBlob {
+            label: label.to_string(),
+            binding,
+            size: data.len(),
+            sealed_at,
+        })
+    }
+
+    /// Open the blob stored under `label`
+    pub async fn unseal(
+        &self,
+        master_key: &[u8],
+        app_id: &str,
+        compose_hash: &str,
+        label: &str,
+    ) -> TappResult<(SealedBlob, Vec<u8>)> {
+        check_label(label)?;
+        let envelope = match fs::read(self.blob_path(app_id, label)).await {
+            Ok(envelope) => envelope,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                return Err(TappError::SealedBlobNotFound {
+                    app_id: app_id.to_string(),
+                    label: label.to_string(),
+                })
+            }
+            Err(e) => return Err(e.into()),
+        };
+        open(master_key, app_id, compose_hash, label, &envelope)
+    }
+
+    /// Blobs of an app, by label
+    pub async fn list(&self, app_id: &str) -> TappResult<Vec<SealedBlob>> {
+        let mut entries = match fs::read_dir(self.app_dir(app_id).join(SEALED_DIR)).await {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e.into()),
+        };
+        let mut blobs = Vec::new();
+        while let Some(entry) = entries.next_entry().await? {
+            let name = entry.file_name();
+            let Some(label) = name.to_str().and_then(|name| name.strip_suffix(".blob")) else {
+                continue;
+            };
+            if !validate_label(label) {
+                continue;
+            }
+            blobs.push(read_header(label, &fs::read(entry.path()).await?)?);
+        }
+        blobs.sort_by(|a, b| a.label.cmp(&b.label));
+        Ok(blobs)
+    }
+
+    /// Delete the blob stored under `label`; false if there was none
+    pub async fn delete(&self, app_id: &str, label: &str) -> TappResult<bool> {
+        check_label(label)?;
+        let _write = self.writes.lock().await;
+        match fs::remove_file(self.blob_path(app_id, label)).await {
+            Ok(()) => Ok(true),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(false),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MASTER_KEY: &[u8] = &[7; 32];
+
+    /// "secret" of app-1, running hash-1, sealed as "token" at 100
+    fn sealed(binding: Binding) -> Vec<u8> {
+        seal(
+            MASTER_KEY, "app-1", "hash-1", "token", binding, 100, b"secret",
+        )
+        .unwrap()
+    }
+
+    /// Whether opening fails because the envelope is not for this caller
+    fn mismatch(master_key: &[u8], app_id: &str, label: &str, envelope: &[u8]) -> bool {
+        matches!(
+            open(master_key, app_id, "hash-1", label, envelope),
+            Err(TappError::SealBindingMismatch { .. })
+        )
+    }
+
+    #[test]
+    fn test_seal_and_open() {
+        for binding in [Binding::Measurement, Binding::AppId] {
+            let envelope = sealed(binding);
+            assert_eq!(envelope[0], ENVELOPE_VERSION);
+            let (blob, plaintext) =
+                open(MASTER_KEY, "app-1", "hash-1", "token", &envelope).unwrap();
+            assert_eq!(plaintext, b"secret");
+            assert_eq!(
+                blob,
+                SealedBlob {
+                    label: "token".to_string(),
+                    binding,
+                    size: 6,
+                    sealed_at: 100,
+                }
+            );
+            assert_eq!(read_header("token", &envelope).unwrap(), blob);
+        }
+
+        let empty = seal(MASTER_KEY, "app-1", "", "empty", Binding::AppId, 0, b"").unwrap();
+        assert!(open(MASTER_KEY, "app-1", "", "empty", &empty)
+            .unwrap()
+            .1
+            .is_empty());
+    }
+
+    #[test]
+    fn test_wrong_binding() {
+        let measured = sealed(Binding::Measurement);
+        let by_app = sealed(Binding::AppId);
+
+        // Another compose file opens only the blob bound to the app ID
+        assert!(matches!(
+            open(MASTER_KEY, "app-1", "hash-2", "token", &measured),
+            Err(TappError::SealBindingMismatch { .. })
+        ));
+        let (_, plaintext) = open(MASTER_KEY, "app-1", "hash-2", "token", &by_app).unwrap();
+        assert_eq!(plaintext, b"secret");
+
+        for envelope in [&measured, &by_app] {
+            // Another app, node or label
+            assert!(mismatch(MASTER_KEY, "app-2", "token", envelope));
+            assert!(mismatch(&[8; 32], "app-1", "token", envelope));
+            assert!(mismatch(MASTER_KEY, "app-1", "other", envelope));
+
+            // A rebound, backdated or altered envelope
+            for offset in [1, 2, envelope.len() - 1] {
+                let mut altered = envelope.clone();
+                altered[offset] ^= 1;
+                assert!(mismatch(MASTER_KEY, "app-1", "token", &altered));
+            }
+        }
+
+        // Unknown versions and truncated envelopes are not opened
+        let mut future = measured.clone();
+        future[0] = 2;
+        assert!(matches!(
+            open(MASTER_KEY, "app-1", "hash-1", "token", &future),
+            Err(TappError::Crypto(_))
+        ));
+        assert!(read_header("token", &measured[..HEADER_LEN]).is_err());
+    }
+
+    #[test]
+    fn test_validate_label() {
+        let longest = "a".repeat(MAX_LABEL_LEN);
+        for label in ["token", "db.checkpoint-2", "A_1", &longest] {
+            assert!(validate_label(label), "{}", label);
+        }
+        for label in ["", ".hidden", "a/b", "../x", "a b", &"a".repeat(65)] {
+            assert!(!validate_label(label), "{}", label);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_store() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = SealedDataStore::new(
+            dir.path(),
+            SealedDataConfig {
+                max_blob_bytes: 16,
+                max_blobs_per_app: 2,
+            },
+        );
+        let put = |app_id: &'static str, label: &'static str, data: &'static [u8]| {
+            let store = &store;
+            async move {
+                store
+                    .seal(
+                        MASTER_KEY,
+                        app_id,
+                        "hash-1",
+                        label,
+                        Binding::Measurement,
+                        data,
+                    )
+                    .await
+            }
+        };
+
+        // Apps must be deployed
+        assert!(matches!(
+            put("app-1", "token", b"v1").await,
+            Err(TappError::AppNotFound { .. })
+        ));
+        std::fs::create_dir_all(dir.path().join("app-1")).unwrap();
+        std::fs::create_dir_all(dir.path().join("team-a__app-1")).unwrap();
+
+        put("app-1", "token", b"v1").await.unwrap();
+        let blob = put("app-1", "token", b"v2").await.unwrap();
+        assert_eq!(blob.size, 2);
+        assert!(dir.path().join("app-1/sealed/token.blob").exists());
+        let (_, data) = store
+            .unseal(MASTER_KEY, "app-1", "hash-1", "token")
+            .await
+            .unwrap();
+        assert_eq!(data, b"v2");
+
+        // Limits
+        assert!(matches!(
+            put("app-1", "big", &[0; 17]).await,
+            Err(TappError::InvalidParameter { .. })
+        ));
+        put("app-1", "state", b"s").await.unwrap();
+        assert!(matches!(
+            put("app-1", "third", b"x").await,
+            Err(TappError::ResourceExhausted(_))
+        ));
+        put("app-1", "state", b"replaced").await.unwrap();
+        let labels: Vec<String> = store
+            .list("app-1")
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|blob| blob.label)
+            .collect();
+        assert_eq!(labels, vec!["state", "token"]);
+
+        // Apps in namespaces have blobs of their own
+        assert!(store.list("team-a/app-1").await.unwrap().is_empty());
+        assert!(matches!(
+            store
+                .unseal(MASTER_KEY, "team-a/app-1", "hash-1", "token")
+                .await,
+            Err(TappError::SealedBlobNotFound { .. })
+        ));
+
+        assert!(store.delete("app-1", "token").await.unwrap());
+        assert!(!store.delete("app-1", "token").await.unwrap());
+        assert!(matches!(
+            store.unseal(MASTER_KEY, "app-1", "hash-1", "token").await,
+            Err(TappError::SealedBlobNotFound { .. })
+        ));
+        assert!(matches!(
+            store.delete("app-1", "../app-2").await,
+            Err(TappError::InvalidParameter { .. })
+        ));
+    }
+}
@@ -0,0 +1,585 @@
+use crate::app_key::{
+    eip191_message, eth_address, public_key_from_private, recover_public_key,
+    sign_message_recoverable,
+};
+use crate::audit_layer::AuditAnnotation;
+use crate::config::{AttestorAuthConfig, AttestorFailurePolicy};
+use crate::contract::ContractClient;
+use crate::error::TappResult;
+use crate::nonce_manager::NonceManager;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::Duration;
+use tokio::time::Instant;
+use tonic::body::BoxBody;
+use tonic::Status;
+use tower::{Layer, Service};
+use tracing::{debug, warn};
+
+/// Headers of an attestor-signed request
+pub const ATTESTOR_ADDRESS_HEADER: &str = "x-attestor-address";
+pub const ATTESTOR_NONCE_HEADER: &str = "x-attestor-nonce";
+pub const ATTESTOR_SIGNATURE_HEADER: &str = "x-attestor-signature";
+
+/// Methods whose callers must be registered attestors
+pub const ATTESTOR_METHODS: &[&str] = &["GetAppKey", "GetAttestedAppKey"];
+
+/// Message signed by an attestor: method_path || nonce
+pub fn attestor_message(method_path: &str, nonce: &str) -> Vec<u8> {
+    [method_path, nonce].concat().into_bytes()
+}
+
+/// Headers authorizing a call to `method_path` as the attestor holding
+/// `private_key`, with a challenge from GetNonce
+/// The signature is recoverable, over Keccak-256 of `attestor_message`.
+pub fn attestor_auth_headers(
+    private_key: &[u8],
+    method_path: &str,
+    nonce: &str,
+) -> TappResult<[(&'static str, String); 3]> {
+    let address = eth_address(&public_key_from_private(private_key)?);
+    let signature = sign_message_recoverable(private_key, &attestor_message(method_path, nonce))?;
+    Ok([
+        (
+            ATTESTOR_ADDRESS_HEADER,
+            format!("0x{}", hex::encode(address)),
+        ),
+        (ATTESTOR_NONCE_HEADER, nonce.to_string()),
+        (ATTESTOR_SIGNATURE_HEADER, hex::encode(signature)),
+    ])
+}
+
+/// A contract answer about one attestor
+#[derive(Debug, Clone, Copy)]
+struct CachedAnswer {
+    registered: bool,
+    checked_at: Instant,
+}
+
+/// Outcome of an attestor check
+pub struct AttestorDecision {
+    /// Why the request is rejected; `None` lets it through
+    pub rejection: Option<Status>,
+    /// The attestor and the on-chain answer, for the audit log
+    pub annotation: AuditAnnotation,
+}
+
+/// Checks that app key callers are registered attestors
+/// A caller signs a GetNonce challenge; the signer must be the claimed
+/// address, which the contract must report with `isRegisteredAttestor`.
+/// Answers are reused for `cache_ttl_seconds`. While the contract cannot be
+/// reached, an answer up to `max_stale_seconds` past its TTL is used, and
+/// without one `failure_policy` decides.
+pub struct AttestorAuth {
+    contract: Arc<dyn ContractClient>,
+    nonces: Arc<NonceManager>,
+    cache_ttl: Duration,
+    max_stale: Duration,
+    failure_policy: AttestorFailurePolicy,
+    cache: Mutex<HashMap<[u8; 20], CachedAnswer>>,
+}
+
+impl AttestorAuth {
+    pub fn new(
+        contract: Arc<dyn ContractClient>,
+        nonces: Arc<NonceManager>,
+        config: &AttestorAuthConfig,
+    ) -> Self {
+        Self {
+            contract,
+            nonces,
+            cache_ttl: Duration::from_secs(config.cache_ttl_seconds),
+            max_stale: Duration::from_secs(config.max_stale_seconds),
+            failure_policy: config.failure_policy,
+            cache: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Decide on a call to `path` with the attestor headers in `headers`
+    pub async fn authorize(&self, headers: &http::HeaderMap, path: &str) -> AttestorDecision {
+        let header = |name: &str| headers.get(name).and_then(|v| v.to_str().ok());
+        let deny = |attestor: &str, status: Status| AttestorDecision {
+            rejection: Some(status),
+            annotation: AuditAnnotation(format!("attestor={} onchain=unchecked", attestor)),
+        };
+
+        let (Some(claimed), Some(nonce), Some(signature)) = (
+            header(ATTESTOR_ADDRESS_HEADER),
+            header(ATTESTOR_NONCE_HEADER),
+            header(ATTESTOR_SIGNATURE_HEADER),
+        ) else {
+            return deny(
+                "none",
+                Status::unauthenticated(format!(
+                    "Attestor requests need '{}', '{}' and '{}' metadata",
+                    ATTESTOR_ADDRESS_HEADER, ATTESTOR_NONCE_HEADER, ATTESTOR_SIGNATURE_HEADER
+                )),
+            );
+        };
+        let Some(address) = hex::decode(claimed.trim_start_matches("0x"))
+            .ok()
+            .and_then(|address| <[u8; 20]>::try_from(address).ok())
+        else {
+            return deny(
+                "invalid",
+                Status::unauthenticated(format!(
+                    "'{}' is not a 20-byte address",
+                    ATTESTOR_ADDRESS_HEADER
+                )),
+            );
+        };
+        let attestor = format!("0x{}", hex::encode(address));
+
+        // SECURITY: The signature proves the key, the challenge its freshness
+        let message = attestor_message(path, nonce);
+        let signed = hex::decode(signature.trim_start_matches("0x"))
+            .map(|signature| {
+                [eip191_message(&message), message].iter().any(|message| {
+                    recover_public_key(message, &signature)
+                        .is_ok_and(|public_key| eth_address(&public_key) == address)
+                })
+            })
+            .unwrap_or(false);
+        if !signed {
+            warn!(
+                attestor = %attestor,
+                event = "ATTESTOR_REJECTED",
+                "Invalid attestor signature"
+            );
+            return deny(
+                &attestor,
+                Status::unauthenticated("Attestor signature does not match the address"),
+            );
+        }
+        match self.nonces.consume_issued_challenge(nonce).await {
+            Ok(true) => {}
+            Ok(false) => {
+                return deny(
+                    &attestor,
+                    Status::unauthenticated(
+                        "Attestor nonce was not issued by GetNonce or is already used",
+                    ),
+                )
+            }
+            Err(e) => return deny(&attestor, Status::unauthenticated(e.to_string())),
+        }
+
+        match self.registered(&address).await {
+            Ok((true, source)) => {
+                debug!(
+                    attestor = %attestor,
+                    source,
+                    event = "ATTESTOR_AUTHORIZED",
+                    "Registered attestor"
+                );
+                AttestorDecision {
+                    rejection: None,
+                    annotation: AuditAnnotation(format!(
+                        "attestor={} onchain=registered source={}",
+                        attestor, source
+                    )),
+                }
+            }
+            Ok((false, source)) => {
+                warn!(
+                    attestor = %attestor,
+                    source,
+                    event = "ATTESTOR_REJECTED",
+                    "Attestor is not registered on the tapp contract"
+                );
+                AttestorDecision {
+                    rejection: Some(Status::permission_denied(format!(
+                        "Attestor {} is not registered on the tapp contract",
+                        attestor
+                    ))),
+                    annotation: AuditAnnotation(format!(
+                        "attestor={} onchain=unregistered source={}",
+                        attestor, source
+                    )),
+                }
+            }
+            Err(e) => {
+                let annotation = AuditAnnotation(format!(
+                    "attestor={} onchain=unavailable policy={}",
+                    attestor,
+                    match self.failure_policy {
+                        AttestorFailurePolicy::Open => "open",
+                        AttestorFailurePolicy::Closed => "closed",
+                    }
+                ));
+                warn!(
+                    attestor = %attestor,
+                    policy = ?self.failure_policy,
+                    error = %e,
+                    event = "ATTESTOR_CHECK_UNAVAILABLE",
+                    "Cannot check the attestor on the tapp contract"
+                );
+                let rejection = match self.failure_policy {
+                    AttestorFailurePolicy::Open => None,
+                    AttestorFailurePolicy::Closed => Some(Status::unavailable(format!(
+                        "Cannot check the attestor registration: {}",
+                        e
+                    ))),
+                };
+                AttestorDecision {
+                    rejection,
+                    annotation,
+                }
+            }
+        }
+    }
+
+    /// Whether `address` is registered, and where the answer came from
+    async fn registered(&self, address: &[u8; 20]) -> TappResult<(bool, &'static str)> {
+        let now = Instant::now();
+        let cached = self.cache.lock().unwrap().get(address).copied();
+        if let Some(answer) = cached {
+            if now.duration_since(answer.checked_at) < self.cache_ttl {
+                return Ok((answer.registered, "cache"));
+            }
+        }
+
+        match self.contract.is_registered_attestor(address).await {
+            Ok(registered) => {
+                let mut cache = self.cache.lock().unwrap();
+                let usable = self.cache_ttl + self.max_stale;
+                cache.retain(|_, answer| now.duration_since(answer.checked_at) < usable);
+                cache.insert(
+                    *address,
+                    CachedAnswer {
+                        registered,
+                        checked_at: now,
+                    },
+                );
+                Ok((registered, "contract"))
+            }
+            Err(e) => match cached {
+                Some(answer)
+                    if now.duration_since(answer.checked_at) < self.cache_ttl + self.max_stale =>
+                {
+                    debug!(error = %e, "Contract unreachable, using an expired attestor answer");
+                    Ok((answer.registered, "stale"))
+                }
+                _ => Err(e),
+            },
+        }
+    }
+}
+
+/// Tower Layer checking callers of `ATTESTOR_METHODS` against the contract
+/// Passes every request through when attestor authorization is disabled.
+#[derive(Clone)]
+pub struct AttestorAuthLayer {
+    auth: Option<Arc<AttestorAuth>>,
+}
+
+impl AttestorAuthLayer {
+    pub fn new(auth: Option<Arc<AttestorAuth>>) -> Self {
+        Self { auth }
+    }
+}
+
+impl<S> Layer<S> for AttestorAuthLayer {
+    type Service = AttestorAuthMiddleware<S>;
+
+    fn layer(&self, service: S) -> Self::Service {
+        AttestorAuthMiddleware {
+            inner: service,
+            auth: self.auth.clone(),
+        }
+    }
+}
+
+/// Middleware that rejects app key requests of unregistered attestors
+#[derive(Clone)]
+pub struct AttestorAuthMiddleware<S> {
+    inner: S,
+    auth: Option<Arc<AttestorAuth>>,
+}
+
+impl<S> Service<http::Request<BoxBody>> for AttestorAuthMiddleware<S>
+where
+    S: Service<http::Request<BoxBody>, Response = http::Response<BoxBody>>
+        + Clone
+        + Send
+        + 'static,
+    S::Error: Into<Box<dyn std::error::Error + Send + Sync>> + Send,
+    S::Future: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = futures_util::future::BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: http::Request<BoxBody>) -> Self::Future {
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+        let auth = self.auth.clone();
+
+        Box::pin(async move {
+            let path = req.uri().path().to_string();
+            let auth = match auth {
+                Some(auth) if ATTESTOR_METHODS.contains(&crate::utils::grpc_method_name(&path)) => {
+                    auth
+                }
+                _ => return inner.call(req).await,
+            };
+
+            // The decision goes into the audit log either way
+            let decision = auth.authorize(req.headers(), &path).await;
+            let mut response = match decision.rejection {
+                Some(status) => status.into_http(),
+                None => inner.call(req).await?,
+            };
+            response.extensions_mut().insert(decision.annotation);
+            Ok(response)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::contract::{AppRegistration, TxStatus};
+    use crate::error::{ContractError, TappError};
+    use futures_util::future::BoxFuture;
+    use std::collections::VecDeque;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    const PATH: &str = "/tapp_service.TappService/GetAppKey";
+    const ATTESTOR_KEY: [u8; 32] = [0x42; 32];
+
+    /// Contract answering `isRegisteredAttestor` from a script
+    #[derive(Default)]
+    struct MockContract {
+        answers: Mutex<VecDeque<TappResult<bool>>>,
+        calls: AtomicUsize,
+    }
+
+    impl ContractClient for MockContract {
+        fn register_app<'a>(
+            &'a self,
+            _app: &'a AppRegistration,
+        ) -> BoxFuture<'a, TappResult<String>> {
+            Box::pin(async { Err(no_transactions()) })
+        }
+
+        fn transaction_status<'a>(
+            &'a self,
+            _tx_hash: &'a str,
+        ) -> BoxFuture<'a, TappResult<TxStatus>> {
+            Box::pin(async { Err(no_transactions()) })
+        }
+
+        fn is_registered_attestor<'a>(
+            &'a self,
+            _attestor: &'a [u8; 20],
+        ) -> BoxFuture<'a, TappResult<bool>> {
+            self.calls.fetch_add(1, Ordering::Relaxed);
+            let answer = self.answers.lock().unwrap().pop_front().unwrap();
+            Box::pin(async move { answer })
+        }
+    }
+
+    /// Attestor checks only read the contract
+    fn no_transactions() -> TappError {
+        ContractError::InvalidResponse {
+            method: "eth_sendRawTransaction".to_string(),
+            reason: "attestor checks send no transactions".to_string(),
+        }
+        .into()
+    }
+
+    fn rpc_error() -> TappError {
+        ContractError::ConnectionFailed {
+            endpoint: "http://127.0.0.1:8545".to_string(),
+            reason: "connection refused".to_string(),
+        }
+        .into()
+    }
+
+    fn attestor_auth(contract: Arc<MockContract>, config: AttestorAuthConfig) -> AttestorAuth {
+        AttestorAuth::new(contract, Arc::new(NonceManager::new()), &config)
+    }
+
+    fn request(path: &str, headers: &[(&str, String)]) -> http::Request<BoxBody> {
+        let mut builder = http::Request::builder().uri(path);
+        for (name, value) in headers {
+            builder = builder.header(*name, value);
+        }
+        builder.body(tonic::body::empty_body()).unwrap()
+    }
+
+    /// Headers of a call to `PATH` signed with `key` over a fresh challenge
+    async fn signed(auth: &AttestorAuth, key: &[u8]) -> http::HeaderMap {
+        let (nonce, _) = auth.nonces.issue_challenge("ip:10.0.0.1").await.unwrap();
+        request(PATH, &attestor_auth_headers(key, PATH, &nonce).unwrap())
+            .headers()
+            .clone()
+    }
+
+    /// gRPC code and audit annotation of a decision
+    fn outcome(decision: AttestorDecision) -> (tonic::Code, String) {
+        let code = decision
+            .rejection
+            .map(|status| status.code())
+            .unwrap_or(tonic::Code::Ok);
+        (code, decision.annotation.0)
+    }
+
+    fn attestor() -> String {
+        let public_key = public_key_from_private(&ATTESTOR_KEY).unwrap();
+        format!("0x{}", hex::encode(eth_address(&public_key)))
+    }
+
+    #[tokio::test]
+    async fn test_registered_and_unregistered_attestors() {
+        let contract = Arc::new(MockContract::default());
+        contract
+            .answers
+            .lock()
+            .unwrap()
+            .extend([Ok(true), Ok(false)]);
+        let auth = attestor_auth(contract.clone(), AttestorAuthConfig::default());
+
+        let headers = signed(&auth, &ATTESTOR_KEY).await;
+        assert_eq!(
+            outcome(auth.authorize(&headers, PATH).await),
+            (
+                tonic::Code::Ok,
+                format!("attestor={} onchain=registered source=contract", attestor())
+            )
+        );
+        // Answered from the cache within the TTL
+        let headers = signed(&auth, &ATTESTOR_KEY).await;
+        assert_eq!(
+            outcome(auth.authorize(&headers, PATH).await).1,
+            format!("attestor={} onchain=registered source=cache", attestor())
+        );
+        assert_eq!(contract.calls.load(Ordering::Relaxed), 1);
+
+        let headers = signed(&auth, &[0x43; 32]).await;
+        let (code, annotation) = outcome(auth.authorize(&headers, PATH).await);
+        assert_eq!(code, tonic::Code::PermissionDenied);
+        assert!(annotation.ends_with("onchain=unregistered source=contract"));
+    }
+
+    #[tokio::test]
+    async fn test_signature_and_nonce_checks() {
+        let contract = Arc::new(MockContract::default());
+        let auth = attestor_auth(contract.clone(), AttestorAuthConfig::default());
+        let decide = |headers: [(&'static str, String); 3]| {
+            let headers = request(PATH, &headers).headers().clone();
+            let auth = &auth;
+            async move { outcome(auth.authorize(&headers, PATH).await) }
+        };
+
+        assert_eq!(
+            outcome(auth.authorize(&http::HeaderMap::new(), PATH).await),
+            (
+                tonic::Code::Unauthenticated,
+                "attestor=none onchain=unchecked".to_string()
+            )
+        );
+
+        // Signed for another method, or claiming another address
+        let (nonce, _) = auth.nonces.issue_challenge("ip:10.0.0.1").await.unwrap();
+        let other_method = "/tapp_service.TappService/GetAttestedAppKey";
+        let headers = attestor_auth_headers(&ATTESTOR_KEY, other_method, &nonce).unwrap();
+        assert_eq!(decide(headers).await.0, tonic::Code::Unauthenticated);
+        let mut headers = attestor_auth_headers(&ATTESTOR_KEY, PATH, &nonce).unwrap();
+        headers[0].1 = format!("0x{}", "11".repeat(20));
+        assert_eq!(decide(headers).await.0, tonic::Code::Unauthenticated);
+
+        // Client-chosen and replayed nonces are refused
+        let headers = attestor_auth_headers(&ATTESTOR_KEY, PATH, "my-own-nonce").unwrap();
+        assert_eq!(decide(headers).await.0, tonic::Code::Unauthenticated);
+        contract.answers.lock().unwrap().push_back(Ok(true));
+        let headers = attestor_auth_headers(&ATTESTOR_KEY, PATH, &nonce).unwrap();
+        assert_eq!(decide(headers.clone()).await.0, tonic::Code::Ok);
+        assert_eq!(decide(headers).await.0, tonic::Code::Unauthenticated);
+        assert_eq!(contract.calls.load(Ordering::Relaxed), 1);
+    }
+
+    #[tokio::test]
+    async fn test_rpc_errors_under_both_policies() {
+        for (policy, expected) in [
+            (AttestorFailurePolicy::Closed, tonic::Code::Unavailable),
+            (AttestorFailurePolicy::Open, tonic::Code::Ok),
+        ] {
+            let contract = Arc::new(MockContract::default());
+            contract.answers.lock().unwrap().extend([
+                Err(rpc_error()),
+                Ok(false),
+                Err(rpc_error()),
+            ]);
+            // Every answer expires at once, but stays usable for a while
+            let config = AttestorAuthConfig {
+                cache_ttl_seconds: 0,
+                failure_policy: policy,
+                ..Default::default()
+            };
+            let auth = attestor_auth(contract.clone(), config);
+
+            let headers = signed(&auth, &ATTESTOR_KEY).await;
+            let (code, annotation) = outcome(auth.authorize(&headers, PATH).await);
+            assert_eq!(code, expected, "{:?}", policy);
+            assert!(annotation.ends_with(&format!(
+                "onchain=unavailable policy={}",
+                format!("{:?}", policy).to_lowercase()
+            )));
+
+            // An expired answer beats the policy while the contract is down
+            let headers = signed(&auth, &ATTESTOR_KEY).await;
+            auth.authorize(&headers, PATH).await;
+            let headers = signed(&auth, &ATTESTOR_KEY).await;
+            assert_eq!(
+                outcome(auth.authorize(&headers, PATH).await),
+                (
+                    tonic::Code::PermissionDenied,
+                    format!("attestor={} onchain=unregistered source=stale", attestor())
+                )
+            );
+            assert_eq!(contract.calls.load(Ordering::Relaxed), 3);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_layer_annotates_app_key_methods_only() {
+        let contract = Arc::new(MockContract::default());
+        contract.answers.lock().unwrap().push_back(Ok(false));
+        let auth = Arc::new(attestor_auth(contract, AttestorAuthConfig::default()));
+        let headers = signed(&auth, &ATTESTOR_KEY).await;
+        let mut service = AttestorAuthLayer::new(Some(auth)).layer(tower::service_fn(
+            |_req: http::Request<BoxBody>| async {
+                Ok::<_, std::convert::Infallible>(http::Response::new(tonic::body::empty_body()))
+            },
+        ));
+
+        let mut denied = request(PATH, &[]);
+        *denied.headers_mut() = headers;
+        let response = service.call(denied).await.unwrap();
+        assert_eq!(
+            Status::from_header_map(response.headers()).unwrap().code(),
+            tonic::Code::PermissionDenied
+        );
+        assert_eq!(
+            response.extensions().get::<AuditAnnotation>().unwrap().0,
+            format!(
+                "attestor={} onchain=unregistered source=contract",
+                attestor()
+            )
+        );
+
+        let response = service
+            .call(request("/tapp_service.TappService/GetEvidence", &[]))
+            .await
+            .unwrap();
+        assert!(Status::from_header_map(response.headers()).is_none());
+        assert!(response.extensions().get::<AuditAnnotation>().is_none());
+    }
+}
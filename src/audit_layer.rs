@@ -0,0 +1,398 @@
+use crate::auth_layer::AuthIdentity;
+use crate::config::{AuditConfig, LoggingConfig};
+use crate::request_id_layer::RequestId;
+use crate::rolling_file::RollingFileWriter;
+use http_body_util::BodyExt;
+use serde::Serialize;
+use std::collections::HashSet;
+use std::io::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::Instant;
+use tonic::body::BoxBody;
+use tonic::transport::server::{TcpConnectInfo, UdsConnectInfo};
+use tonic::{Code, Status};
+use tower::{Layer, Service};
+use tracing::error;
+
+/// A single audit log entry, written as one JSON line
+/// Only metadata about the call is recorded, never request or response contents
+#[derive(Debug, Clone, Serialize)]
+pub struct AuditRecord {
+    pub timestamp: String,
+    pub method: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
+    pub source: String,
+    pub identity: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub action: Option<String>,
+    pub request_bytes: u64,
+    pub grpc_code: i32,
+    pub grpc_status: String,
+    pub latency_ms: u64,
+}
+
+/// Extra attribution a handler attaches to its response extensions
+/// (e.g. an operator override), copied into the audit record's `action`
+#[derive(Debug, Clone, PartialEq)]
+pub struct AuditAnnotation(pub String);
+
+/// Audit log sink shared by all connections
+pub struct AuditLogger {
+    methods: HashSet<String>,
+    writer: Mutex<Box<dyn Write + Send>>,
+}
+
+impl AuditLogger {
+    /// Open the audit log file, rotating with the application log's size and retention
+    pub fn new(config: &AuditConfig, logging: &LoggingConfig) -> std::io::Result<Self> {
+        let writer = RollingFileWriter::new(
+            &config.file_path,
+            logging.max_file_size_mb,
+            logging.max_files,
+        )?;
+        Ok(Self::with_writer(config.methods.clone(), Box::new(writer)))
+    }
+
+    /// Create a logger writing to an arbitrary sink
+    pub fn with_writer(methods: Vec<String>, writer: Box<dyn Write + Send>) -> Self {
+        Self {
+            methods: methods.into_iter().collect(),
+            writer: Mutex::new(writer),
+        }
+    }
+
+    /// Whether calls to `method` are audited (empty list means all methods)
+    pub fn should_audit(&self, method: &str) -> bool {
+        self.methods.is_empty() || self.methods.contains(method)
+    }
+
+    /// Append a record to the audit log
+    pub fn record(&self, record: &AuditRecord) {
+        let mut line = match serde_json::to_vec(record) {
+            Ok(line) => line,
+            Err(e) => {
+                error!("Failed to serialize audit record: {}", e);
+                return;
+            }
+        };
+        line.push(b'\n');
+
+        let mut writer = self.writer.lock().unwrap();
+        if let Err(e) = writer.write_all(&line).and_then(|_| writer.flush()) {
+            error!(
+                method = %record.method,
+                error = %e,
+                event = "AUDIT_WRITE_FAILED",
+                "Failed to write audit record"
+            );
+        }
+    }
+}
+
+/// Audit record waiting for the call outcome
+/// If the response body is dropped before the gRPC status is seen (client
+/// went away), the record is written as CANCELLED.
+struct PendingAudit {
+    logger: Arc<AuditLogger>,
+    record: Option<AuditRecord>,
+    started: Instant,
+    request_bytes: Arc<AtomicU64>,
+}
+
+impl PendingAudit {
+    fn finish(&mut self, code: Code) {
+        if let Some(mut record) = self.record.take() {
+            record.request_bytes = self.request_bytes.load(Ordering::Relaxed);
+            record.grpc_code = code.into();
+            record.grpc_status = format!("{:?}", code);
+            record.latency_ms = self.started.elapsed().as_millis() as u64;
+            self.logger.record(&record);
+        }
+    }
+}
+
+impl Drop for PendingAudit {
+    fn drop(&mut self) {
+        self.finish(Code::Cancelled);
+    }
+}
+
+/// Tower Layer writing an audit record for every audited RPC
+/// Place it outside the auth layer so rejected calls are recorded too
+#[derive(Clone)]
+pub struct AuditLayer {
+    logger: Option<Arc<AuditLogger>>,
+}
+
+impl AuditLayer {
+    /// `None` disables auditing; requests pass straight through
+    pub fn new(logger: Option<Arc<AuditLogger>>) -> Self {
+        Self { logger }
+    }
+}
+
+impl<S> Layer<S> for AuditLayer {
+    type Service = AuditMiddleware<S>;
+
+    fn layer(&self, service: S) -> Self::Service {
+        AuditMiddleware {
+            inner: service,
+            logger: self.logger.clone(),
+        }
+    }
+}
+
+/// Middleware that records caller, outcome and latency of each RPC
+#[derive(Clone)]
+pub struct AuditMiddleware<S> {
+    inner: S,
+    logger: Option<Arc<AuditLogger>>,
+}
+
+impl<S> Service<http::Request<BoxBody>> for AuditMiddleware<S>
+where
+    S: Service<http::Request<BoxBody>, Response = http::Response<BoxBody>>
+        + Clone
+        + Send
+        + 'static,
+    S::Error: Into<Box<dyn std::error::Error + Send + Sync>> + Send,
+    S::Future: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = futures_util::future::BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: http::Request<BoxBody>) -> Self::Future {
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+
+        let method_name = crate::utils::grpc_method_name(req.uri().path()).to_string();
+        let logger = match self.logger {
+            Some(ref logger) if logger.should_audit(&method_name) => logger.clone(),
+            _ => return Box::pin(inner.call(req)),
+        };
+
+        Box::pin(async move {
+            let started = Instant::now();
+            let source = request_source(&req);
+            let request_id = req.extensions().get::<RequestId>().map(|id| id.0.clone());
+
+            // Count request bytes as the handler consumes the body
+            let request_bytes = Arc::new(AtomicU64::new(0));
+            let counter = request_bytes.clone();
+            let req = req.map(|body| {
+                body.map_frame(move |frame| {
+                    if let Some(data) = frame.data_ref() {
+                        counter.fetch_add(data.len() as u64, Ordering::Relaxed);
+                    }
+                    frame
+                })
+                .boxed_unsync()
+            });
+
+            let response = inner.call(req).await?;
+
+            let mut pending = PendingAudit {
+                logger,
+                record: Some(AuditRecord {
+                    timestamp: chrono::Utc::now().to_rfc3339(),
+                    method: method_name,
+                    request_id,
+                    source,
+                    identity: response
+                        .extensions()
+                        .get::<AuthIdentity>()
+                        .map(|identity| identity.key_name.clone()),
+                    action: response
+                        .extensions()
+                        .get::<AuditAnnotation>()
+                        .map(|annotation| annotation.0.clone()),
+                    request_bytes: 0,
+                    grpc_code: 0,
+                    grpc_status: String::new(),
+                    latency_ms: 0,
+                }),
+                started,
+                request_bytes,
+            };
+
+            // Trailers-only responses (e.g. auth rejections) carry the status in headers
+            if let Some(status) = Status::from_header_map(response.headers()) {
+                pending.finish(status.code());
+                return Ok(response);
+            }
+
+            // Otherwise the status arrives in the trailers at the end of the body
+            Ok(response.map(|body| {
+                body.map_frame(move |frame| {
+                    if let Some(trailers) = frame.trailers_ref() {
+                        let code = Status::from_header_map(trailers)
+                            .map(|status| status.code())
+                            .unwrap_or(Code::Ok);
+                        pending.finish(code);
+                    }
+                    frame
+                })
+                .boxed_unsync()
+            }))
+        })
+    }
+}
+
+/// Describe where the request came from: TCP peer address or Unix socket peer
+pub(crate) fn request_source<B>(req: &http::Request<B>) -> String {
+    if let Some(info) = req.extensions().get::<UdsConnectInfo>() {
+        return match info.peer_cred {
+            Some(cred) => match cred.pid() {
+                Some(pid) => format!("uds:uid={},gid={},pid={}", cred.uid(), cred.gid(), pid),
+                None => format!("uds:uid={},gid={}", cred.uid(), cred.gid()),
+            },
+            None => "uds".to_string(),
+        };
+    }
+
+    req.extensions()
+        .get::<TcpConnectInfo>()
+        .and_then(|info| info.remote_addr())
+        .map(|addr| addr.to_string())
+        .unwrap_or_else(|| "unknown".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// In-memory sink so tests can read back what was written
+    #[derive(Clone, Default)]
+    struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl SharedBuffer {
+        fn records(&self) -> Vec<serde_json::Value> {
+            String::from_utf8(self.0.lock().unwrap().clone())
+                .unwrap()
+                .lines()
+                .map(|line| serde_json::from_str(line).unwrap())
+                .collect()
+        }
+    }
+
+    fn layer(methods: &[&str]) -> (AuditLayer, SharedBuffer) {
+        let buffer = SharedBuffer::default();
+        let logger = AuditLogger::with_writer(
+            methods.iter().map(|m| m.to_string()).collect(),
+            Box::new(buffer.clone()),
+        );
+        (AuditLayer::new(Some(Arc::new(logger))), buffer)
+    }
+
+    fn request(method: &str, body: &'static [u8]) -> http::Request<BoxBody> {
+        http::Request::builder()
+            .uri(format!("/tapp_service.TappService/{}", method))
+            .header("x-api-key", "super-secret")
+            .body(
+                http_body_util::Full::new(bytes::Bytes::from_static(body))
+                    .map_err(|never| match never {})
+                    .boxed_unsync(),
+            )
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_records_identity_size_and_trailer_status() {
+        let (layer, buffer) = layer(&["StartApp"]);
+        let mut service = layer.layer(tower::service_fn(|req: http::Request<BoxBody>| async {
+            // Handler consumes the request body, then replies with OK trailers
+            let _ = req.into_body().collect().await;
+            let mut trailers = http::HeaderMap::new();
+            trailers.insert("grpc-status", "0".parse().unwrap());
+            let body = http_body_util::StreamBody::new(futures_util::stream::iter(vec![
+                Ok::<_, Status>(http_body::Frame::data(bytes::Bytes::from_static(b"ok"))),
+                Ok(http_body::Frame::trailers(trailers)),
+            ]));
+            let mut response = http::Response::new(body.boxed_unsync());
+            response.extensions_mut().insert(AuthIdentity {
+                key_name: "deployer".to_string(),
+                role: crate::config::ApiKeyRole::Client,
+                namespace: None,
+            });
+            response
+                .extensions_mut()
+                .insert(AuditAnnotation("admin_override".to_string()));
+            Ok::<_, std::convert::Infallible>(response)
+        }));
+
+        let mut req = request("StartApp", b"0123456789");
+        req.extensions_mut().insert(RequestId("req-1".to_string()));
+        let response = service.call(req).await.unwrap();
+        // Nothing is written until the call completes
+        assert!(buffer.records().is_empty());
+        let _ = response.into_body().collect().await.unwrap();
+
+        let records = buffer.records();
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0]["method"], "StartApp");
+        assert_eq!(records[0]["identity"], "deployer");
+        assert_eq!(records[0]["action"], "admin_override");
+        assert_eq!(records[0]["request_bytes"], 10);
+        assert_eq!(records[0]["grpc_code"], 0);
+        assert_eq!(records[0]["source"], "unknown");
+        assert_eq!(records[0]["request_id"], "req-1");
+        // Request metadata and contents never reach the audit log
+        let raw = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+        assert!(!raw.contains("super-secret"));
+        assert!(!raw.contains("0123456789"));
+    }
+
+    #[tokio::test]
+    async fn test_trailers_only_rejection_and_method_filter() {
+        let (layer, buffer) = layer(&["StartApp"]);
+        let mut service = layer.layer(tower::service_fn(|_req: http::Request<BoxBody>| async {
+            Ok::<_, std::convert::Infallible>(
+                Status::permission_denied("Invalid API key").into_http(),
+            )
+        }));
+
+        let _ = service.call(request("StartApp", b"")).await.unwrap();
+        let _ = service.call(request("GetEvidence", b"")).await.unwrap();
+
+        let records = buffer.records();
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0]["grpc_status"], "PermissionDenied");
+        assert!(records[0]["identity"].is_null());
+        assert!(records[0].get("action").is_none());
+        assert!(records[0].get("request_id").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_dropped_response_recorded_as_cancelled() {
+        let (layer, buffer) = layer(&[]);
+        let mut service = layer.layer(tower::service_fn(|_req: http::Request<BoxBody>| async {
+            Ok::<_, std::convert::Infallible>(http::Response::new(tonic::body::empty_body()))
+        }));
+
+        let response = service.call(request("StopApp", b"")).await.unwrap();
+        drop(response);
+
+        let records = buffer.records();
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0]["grpc_status"], "Cancelled");
+    }
+}
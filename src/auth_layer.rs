@@ -1,20 +1,107 @@
-use crate::config::ApiKeyConfig;
+use crate::auth_lockout::AuthLockout;
+use crate::config::{ApiKeyConfig, ApiKeyEntry, ApiKeyRole, ApiKeyType, KeyValidity};
+use crate::error::NonceError;
+use crate::nonce_manager::NonceManager;
+use ring::hmac;
+use std::net::IpAddr;
+use std::sync::{Arc, OnceLock, RwLock};
 use std::task::{Context, Poll};
+use std::time::Instant;
 use tonic::body::BoxBody;
 use tonic::Status;
 use tower::{Layer, Service};
 use tracing::{debug, warn};
 
+/// Identity of an authenticated caller
+/// Inserted into the request extensions (visible to handlers) and the
+/// response extensions (visible to outer layers such as the audit log)
+#[derive(Debug, Clone, PartialEq)]
+pub struct AuthIdentity {
+    /// API key name (never the key itself)
+    pub key_name: String,
+    /// Role granted to the key
+    pub role: ApiKeyRole,
+    /// Namespace of the key's apps (see `crate::namespace`)
+    pub namespace: Option<String>,
+}
+
+/// API key configuration shared between the layer and the config reloader
+pub type SharedApiKeyConfig = Arc<RwLock<Option<ApiKeyConfig>>>;
+
+/// Header carrying a plain API key
+pub const API_KEY_HEADER: &str = "x-api-key";
+
+/// Headers of an HMAC-signed request
+pub const HMAC_KEY_ID_HEADER: &str = "x-auth-keyid";
+pub const HMAC_TIMESTAMP_HEADER: &str = "x-auth-timestamp";
+pub const HMAC_NONCE_HEADER: &str = "x-auth-nonce";
+pub const HMAC_SIGNATURE_HEADER: &str = "x-auth-signature";
+
+/// Services that require an API key whenever authentication is enabled,
+/// whatever `protected_methods` lists: reflection describes the whole API
+pub const ALWAYS_PROTECTED_SERVICES: &[&str] = &[
+    crate::reflection::REFLECTION_V1,
+    crate::reflection::REFLECTION_V1ALPHA,
+];
+
+/// Message signed by an HMAC key: method_path || timestamp || nonce
+fn hmac_message(method_path: &str, timestamp: &str, nonce: &str) -> Vec<u8> {
+    [method_path, timestamp, nonce].concat().into_bytes()
+}
+
+/// Hex encoded HMAC-SHA256 signature of a call to `method_path`
+/// (e.g. "/tapp_service.TappService/StartApp"), timestamp in decimal seconds
+pub fn hmac_signature(secret: &str, method_path: &str, timestamp: i64, nonce: &str) -> String {
+    let key = hmac::Key::new(hmac::HMAC_SHA256, secret.as_bytes());
+    let message = hmac_message(method_path, &timestamp.to_string(), nonce);
+    hex::encode(hmac::sign(&key, &message).as_ref())
+}
+
+/// Headers authenticating a call to `method_path` with an HMAC key
+/// Uses a fresh nonce and the current time, so build them per call.
+pub fn hmac_auth_headers(
+    key_id: &str,
+    secret: &str,
+    method_path: &str,
+) -> [(&'static str, String); 4] {
+    let timestamp = crate::utils::current_timestamp();
+    let nonce = crate::utils::generate_session_id();
+    let signature = hmac_signature(secret, method_path, timestamp, &nonce);
+    [
+        (HMAC_KEY_ID_HEADER, key_id.to_string()),
+        (HMAC_TIMESTAMP_HEADER, timestamp.to_string()),
+        (HMAC_NONCE_HEADER, nonce),
+        (HMAC_SIGNATURE_HEADER, signature),
+    ]
+}
+
 /// Tower Layer for API key authentication
 /// This wraps the entire gRPC service and can access method paths
 #[derive(Clone)]
 pub struct ApiKeyLayer {
-    config: Option<ApiKeyConfig>,
+    config: SharedApiKeyConfig,
+    lockout: Arc<AuthLockout>,
+    /// Nonces of HMAC-signed requests, created on the first signed request
+    nonces: Arc<OnceLock<NonceManager>>,
 }
 
 impl ApiKeyLayer {
     pub fn new(config: Option<ApiKeyConfig>) -> Self {
-        Self { config }
+        Self::with_config_handle(Arc::new(RwLock::new(config)))
+    }
+
+    /// Layer reading an existing shared configuration
+    pub fn with_config_handle(config: SharedApiKeyConfig) -> Self {
+        Self {
+            config,
+            lockout: Arc::new(AuthLockout::new()),
+            nonces: Arc::new(OnceLock::new()),
+        }
+    }
+
+    /// Handle to the active configuration, swapped atomically on reload
+    pub fn config_handle(&self) -> SharedApiKeyConfig {
+        self.config.clone()
     }
 }
 
@@ -25,6 +112,8 @@ impl<S> Layer<S> for ApiKeyLayer {
         ApiKeyMiddleware {
             inner: service,
             config: self.config.clone(),
+            lockout: self.lockout.clone(),
+            nonces: self.nonces.clone(),
         }
     }
 }
@@ -33,7 +122,9 @@ impl<S> Layer<S> for ApiKeyLayer {
 #[derive(Clone)]
 pub struct ApiKeyMiddleware<S> {
     inner: S,
-    config: Option<ApiKeyConfig>,
+    config: SharedApiKeyConfig,
+    lockout: Arc<AuthLockout>,
+    nonces: Arc<OnceLock<NonceManager>>,
 }
 
 impl<S> Service<http::Request<BoxBody>> for ApiKeyMiddleware<S>
@@ -53,49 +144,139 @@ where
         self.inner.poll_ready(cx)
     }
 
-    fn call(&mut self, req: http::Request<BoxBody>) -> Self::Future {
+    fn call(&mut self, mut req: http::Request<BoxBody>) -> Self::Future {
         let clone = self.inner.clone();
         let mut inner = std::mem::replace(&mut self.inner, clone);
         let config = self.config.clone();
+        let lockout = self.lockout.clone();
+        let nonces = self.nonces.clone();
 
         Box::pin(async move {
             // Extract method name from URI path
             // gRPC method path format: /package.Service/Method
-            let path = req.uri().path();
-            let method_name = path.split('/').last().unwrap_or("Unknown");
+            let path = req.uri().path().to_string();
+            let method_name = crate::utils::grpc_method_name(&path);
 
             debug!(method = %method_name, path = %path, "API key validation");
 
-            // Validate API key if configured
-            if let Err(status) = validate_request(&config, &req, method_name) {
-                // Convert Status to HTTP response
-                let response = status.into_http();
-                return Ok(response);
+            // Validate against a snapshot of the currently active configuration
+            // (HMAC nonce checks are async, so no lock is held while validating)
+            let config = config.read().unwrap().clone();
+            let result = validate_request(
+                config.as_ref(),
+                &lockout,
+                &nonces,
+                req.headers(),
+                crate::utils::remote_ip(&req),
+                &path,
+            )
+            .await;
+            let identity = match result {
+                Ok(identity) => identity,
+                Err(status) => {
+                    // Convert Status to HTTP response
+                    let response = status.into_http();
+                    return Ok(response);
+                }
+            };
+
+            if let Some(ref identity) = identity {
+                req.extensions_mut().insert(identity.clone());
             }
 
             // Call the inner service
-            inner.call(req).await
+            let mut response = inner.call(req).await?;
+            if let Some(identity) = identity {
+                response.extensions_mut().insert(identity);
+            }
+            Ok(response)
         })
     }
 }
 
+/// Credentials presented with a request
+enum Credentials<'a> {
+    /// Plain key in `x-api-key`
+    ApiKey(&'a str),
+    /// HMAC-signed request headers
+    Hmac {
+        key_id: &'a str,
+        timestamp: &'a str,
+        nonce: &'a str,
+        signature: &'a str,
+    },
+}
+
+impl<'a> Credentials<'a> {
+    /// Read credentials from the request headers; signed-request headers win
+    /// Fails if a signed request is missing any of its headers
+    fn from_headers(headers: &'a http::HeaderMap) -> Result<Option<Self>, Status> {
+        let header = |name: &str| headers.get(name).and_then(|v| v.to_str().ok());
+
+        if let Some(key_id) = header(HMAC_KEY_ID_HEADER) {
+            let required = |name: &'static str| {
+                header(name).ok_or_else(|| {
+                    Status::unauthenticated(format!("Signed request is missing '{}'", name))
+                })
+            };
+            return Ok(Some(Credentials::Hmac {
+                key_id,
+                timestamp: required(HMAC_TIMESTAMP_HEADER)?,
+                nonce: required(HMAC_NONCE_HEADER)?,
+                signature: required(HMAC_SIGNATURE_HEADER)?,
+            }));
+        }
+
+        Ok(header(API_KEY_HEADER).map(Credentials::ApiKey))
+    }
+
+    /// Lockout subject of the configured key the credentials appear to target
+    fn targeted_key(&self, api_config: &ApiKeyConfig) -> Option<String> {
+        let name = match self {
+            Credentials::ApiKey(api_key) => find_prefix_match(api_config, api_key),
+            Credentials::Hmac { key_id, .. } => find_hmac_key(api_config, key_id).map(|e| e.name()),
+        };
+        name.map(|name| format!("key:{}", name))
+    }
+}
+
+/// Why presented credentials were rejected
+struct AuthFailure {
+    status: Status,
+    /// Event name for the log
+    event: &'static str,
+    /// Wrong keys and signatures count toward a lockout; stale timestamps
+    /// and replayed nonces do not, since anyone who saw a request can replay it
+    counts_toward_lockout: bool,
+}
+
 /// Validate the request based on API key configuration
-fn validate_request(
-    config: &Option<ApiKeyConfig>,
-    req: &http::Request<BoxBody>,
-    method_name: &str,
-) -> Result<(), Status> {
+/// Returns the caller identity when valid credentials were presented
+async fn validate_request(
+    config: Option<&ApiKeyConfig>,
+    lockout: &AuthLockout,
+    nonces: &OnceLock<NonceManager>,
+    headers: &http::HeaderMap,
+    remote_ip: Option<IpAddr>,
+    path: &str,
+) -> Result<Option<AuthIdentity>, Status> {
     // If API key auth is not configured or disabled, allow all requests
     let Some(api_config) = config else {
-        return Ok(());
+        return Ok(None);
     };
 
     if !api_config.enabled {
-        return Ok(());
+        return Ok(None);
     }
 
+    let method_name = crate::utils::grpc_method_name(path);
+    let credentials = Credentials::from_headers(headers)?;
+
     // Check if this method requires authentication
-    let requires_auth = if api_config.protected_methods.is_empty() {
+    let service_name = crate::utils::grpc_service_name(path);
+    let requires_auth = if ALWAYS_PROTECTED_SERVICES.contains(&service_name) {
+        true
+    } else if api_config.protected_methods.is_empty() {
         // If empty, all methods require auth
         true
     } else {
@@ -108,38 +289,574 @@ fn validate_request(
 
     if !requires_auth {
         debug!(method = %method_name, "Method does not require API key");
-        return Ok(());
+        // Still identify the caller if valid credentials were presented
+        return Ok(match credentials {
+            Some(credentials) => authenticate(api_config, nonces, &credentials, path)
+                .await
+                .ok(),
+            None => None,
+        });
     }
 
-    // Extract API key from headers (gRPC metadata becomes HTTP headers)
-    let api_key = req
-        .headers()
-        .get("x-api-key")
-        .and_then(|v| v.to_str().ok())
-        .ok_or_else(|| {
+    // Credentials come from headers (gRPC metadata becomes HTTP headers)
+    let credentials = credentials.ok_or_else(|| {
+        warn!(
+            method = %method_name,
+            event = "AUTH_MISSING_API_KEY",
+            "API key missing in request"
+        );
+        Status::unauthenticated(
+            "Missing API key. Please provide 'x-api-key' or signed 'x-auth-*' headers in metadata",
+        )
+    })?;
+
+    // Failures are tracked per source IP and per key name the credentials
+    // appear to target
+    let now = Instant::now();
+    let source = remote_ip.map(|ip| format!("ip:{}", ip));
+    let targeted_key = credentials.targeted_key(api_config);
+    let subjects: Vec<&String> = source.iter().chain(targeted_key.iter()).collect();
+
+    // SECURITY: Locked out subjects are rejected without checking the key
+    if let Some(remaining) = subjects
+        .iter()
+        .filter_map(|subject| lockout.locked_for(&api_config.lockout, subject, now))
+        .max()
+    {
+        debug!(
+            method = %method_name,
+            source = ?source,
+            remaining_seconds = remaining.as_secs(),
+            event = "AUTH_LOCKED_OUT",
+            "Request rejected during authentication lockout"
+        );
+        return Err(Status::unauthenticated(format!(
+            "Too many failed authentication attempts. Retry after {}s",
+            remaining.as_secs().max(1)
+        )));
+    }
+
+    // Validate the key or signature
+    let identity = match authenticate(api_config, nonces, &credentials, path).await {
+        Ok(identity) => identity,
+        Err(failure) => {
             warn!(
                 method = %method_name,
-                event = "AUTH_MISSING_API_KEY",
-                "API key missing in request"
+                key = ?targeted_key,
+                reason = %failure.status.message(),
+                event = failure.event,
+                "Authentication failed"
             );
-            Status::unauthenticated("Missing API key. Please provide 'x-api-key' in metadata")
-        })?;
+            if failure.counts_toward_lockout {
+                for subject in &subjects {
+                    if let Some(duration) =
+                        lockout.record_failure(&api_config.lockout, subject, now)
+                    {
+                        warn!(
+                            method = %method_name,
+                            subject = %subject,
+                            lockout_seconds = duration.as_secs(),
+                            event = "AUTH_LOCKOUT",
+                            "Too many authentication failures, locking out"
+                        );
+                    }
+                }
+            }
+            return Err(failure.status);
+        }
+    };
 
-    // Validate API key
-    if !api_config.keys.contains(&api_key.to_string()) {
-        warn!(
-            method = %method_name,
-            event = "AUTH_INVALID_API_KEY",
-            "Invalid API key attempted"
-        );
-        return Err(Status::permission_denied("Invalid API key"));
+    for subject in &subjects {
+        lockout.record_success(subject);
     }
 
     debug!(
         method = %method_name,
+        key_name = %identity.key_name,
         event = "AUTH_SUCCESS",
         "API key validation successful"
     );
 
-    Ok(())
+    Ok(Some(identity))
+}
+
+/// Check presented credentials against the configured keys
+async fn authenticate(
+    api_config: &ApiKeyConfig,
+    nonces: &OnceLock<NonceManager>,
+    credentials: &Credentials<'_>,
+    path: &str,
+) -> Result<AuthIdentity, AuthFailure> {
+    let entry = match credentials {
+        Credentials::ApiKey(api_key) => {
+            find_plain_key(api_config, api_key).ok_or_else(|| AuthFailure {
+                status: Status::permission_denied("Invalid API key"),
+                event: "AUTH_INVALID_API_KEY",
+                counts_toward_lockout: true,
+            })?
+        }
+        Credentials::Hmac {
+            key_id,
+            timestamp,
+            nonce,
+            signature,
+        } => verify_hmac_signature(api_config, key_id, timestamp, nonce, signature, path)?,
+    };
+
+    // Keys outside their validity period are rejected without counting
+    // toward a lockout: the caller holds a real key, just not a current one
+    let now = chrono::Utc::now();
+    match entry.validity_at(now) {
+        KeyValidity::Valid => {}
+        KeyValidity::NotYetValid => {
+            return Err(AuthFailure {
+                status: Status::permission_denied("API key is not valid yet"),
+                event: "AUTH_KEY_NOT_YET_VALID",
+                counts_toward_lockout: false,
+            });
+        }
+        KeyValidity::Expired => {
+            return Err(AuthFailure {
+                status: Status::permission_denied("API key has expired"),
+                event: "AUTH_KEY_EXPIRED",
+                counts_toward_lockout: false,
+            });
+        }
+    }
+
+    if let Credentials::Hmac {
+        key_id,
+        timestamp,
+        nonce,
+        ..
+    } = credentials
+    {
+        check_freshness(api_config, nonces, key_id, timestamp, nonce).await?;
+    }
+
+    if entry.deprecated() {
+        warn!(
+            key_name = %entry.name(),
+            expires_at = ?entry.expires_at(),
+            event = "AUTH_DEPRECATED_KEY",
+            "Deprecated API key used"
+        );
+    }
+
+    Ok(AuthIdentity {
+        key_name: entry.name(),
+        role: entry.role(),
+        namespace: entry.namespace().map(str::to_string),
+    })
+}
+
+/// Verify the MAC of a signed request (in constant time)
+/// Returns the HMAC key entry that signed it
+fn verify_hmac_signature<'a>(
+    api_config: &'a ApiKeyConfig,
+    key_id: &str,
+    timestamp: &str,
+    nonce: &str,
+    signature: &str,
+    path: &str,
+) -> Result<&'a ApiKeyEntry, AuthFailure> {
+    let invalid_signature = || AuthFailure {
+        status: Status::permission_denied("Invalid request signature"),
+        event: "AUTH_INVALID_SIGNATURE",
+        counts_toward_lockout: true,
+    };
+
+    let entry = find_hmac_key(api_config, key_id).ok_or_else(invalid_signature)?;
+    let signature = hex::decode(signature).map_err(|_| invalid_signature())?;
+    let key = hmac::Key::new(hmac::HMAC_SHA256, entry.key().as_bytes());
+    hmac::verify(&key, &hmac_message(path, timestamp, nonce), &signature)
+        .map_err(|_| invalid_signature())?;
+
+    Ok(entry)
+}
+
+/// Check the timestamp and consume the nonce of an authentic signed request
+/// Done after the MAC check so that only authentic requests consume nonces
+async fn check_freshness(
+    api_config: &ApiKeyConfig,
+    nonces: &OnceLock<NonceManager>,
+    key_id: &str,
+    timestamp: &str,
+    nonce: &str,
+) -> Result<(), AuthFailure> {
+    let stale = |reason: String| AuthFailure {
+        status: Status::unauthenticated(format!("Stale or replayed request: {}", reason)),
+        event: "AUTH_STALE_REQUEST",
+        counts_toward_lockout: false,
+    };
+    let timestamp: i64 = timestamp
+        .parse()
+        .map_err(|_| stale(format!("invalid {}", HMAC_TIMESTAMP_HEADER)))?;
+
+    // Nonces are scoped per key so clients cannot collide with each other.
+    // The manager outlives config reloads so no nonce is forgotten; it takes
+    // the capacity of the active config, and the retention comes with each
+    // request
+    let nonces = nonces.get_or_init(|| {
+        NonceManager::with_limits(
+            api_config.hmac.nonce_window_seconds,
+            api_config.hmac.max_tracked_nonces,
+        )
+    });
+    nonces.set_capacity(api_config.hmac.max_tracked_nonces);
+    nonces
+        .verify_and_consume_within(
+            &format!("{}:{}", key_id, nonce),
+            timestamp,
+            api_config.hmac.max_clock_skew_seconds,
+            api_config.hmac.nonce_window_seconds,
+        )
+        .await
+        .map_err(|e| match e {
+            NonceError::CapacityExceeded { .. } => AuthFailure {
+                status: Status::resource_exhausted(e.to_string()),
+                event: "AUTH_NONCE_CAPACITY",
+                counts_toward_lockout: false,
+            },
+            e => stale(e.to_string()),
+        })
+}
+
+/// Length of the key prefix used to attribute failed attempts to a key name
+const KEY_PREFIX_LEN: usize = 8;
+
+/// Configured plain keys (HMAC secrets are never accepted as plain keys)
+fn plain_keys(api_config: &ApiKeyConfig) -> impl Iterator<Item = &ApiKeyEntry> {
+    api_config
+        .keys
+        .iter()
+        .filter(|entry| entry.key_type() == ApiKeyType::Plain)
+}
+
+/// Name of the configured key whose prefix matches the presented key
+fn find_prefix_match(api_config: &ApiKeyConfig, api_key: &str) -> Option<String> {
+    plain_keys(api_config)
+        .find(|entry| {
+            let key = entry.key().as_bytes();
+            key.len() > KEY_PREFIX_LEN && api_key.as_bytes().starts_with(&key[..KEY_PREFIX_LEN])
+        })
+        .map(|entry| entry.name())
+}
+
+/// Look up the configured plain key matching the presented key
+fn find_plain_key<'a>(api_config: &'a ApiKeyConfig, api_key: &str) -> Option<&'a ApiKeyEntry> {
+    plain_keys(api_config).find(|entry| entry.key() == api_key)
+}
+
+/// Look up the HMAC key with the given key ID
+fn find_hmac_key<'a>(api_config: &'a ApiKeyConfig, key_id: &str) -> Option<&'a ApiKeyEntry> {
+    api_config
+        .keys
+        .iter()
+        .find(|entry| entry.key_type() == ApiKeyType::Hmac && entry.name() == key_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn api_config(keys: &[&str]) -> ApiKeyConfig {
+        ApiKeyConfig {
+            enabled: true,
+            keys: keys
+                .iter()
+                .map(|k| ApiKeyEntry::Inline(k.to_string()))
+                .collect(),
+            protected_methods: vec![],
+            lockout: Default::default(),
+            hmac: Default::default(),
+            expiry_horizon_days: 14,
+            namespaces: false,
+        }
+    }
+
+    fn request(api_key: &str) -> http::Request<BoxBody> {
+        http::Request::builder()
+            .uri("/tapp_service.TappService/StartApp")
+            .header("x-api-key", api_key)
+            .body(tonic::body::empty_body())
+            .unwrap()
+    }
+
+    fn grpc_code(response: &http::Response<BoxBody>) -> tonic::Code {
+        Status::from_header_map(response.headers())
+            .map(|s| s.code())
+            .unwrap_or(tonic::Code::Ok)
+    }
+
+    #[tokio::test]
+    async fn test_reload_swaps_keys_without_dropping_in_flight() {
+        let layer = ApiKeyLayer::new(Some(api_config(&["old-key"])));
+        let handle = layer.config_handle();
+        // Inner service that takes a while, standing in for an in-flight RPC
+        let mut service = layer.layer(tower::service_fn(|_req: http::Request<BoxBody>| async {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            Ok::<_, std::convert::Infallible>(http::Response::new(tonic::body::empty_body()))
+        }));
+
+        // Start a request with the old key, then rotate keys while it is in flight
+        let in_flight = tokio::spawn(service.call(request("old-key")));
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        *handle.write().unwrap() = Some(api_config(&["new-key"]));
+
+        let response = in_flight.await.unwrap().unwrap();
+        assert_eq!(grpc_code(&response), tonic::Code::Ok);
+
+        let response = service.call(request("old-key")).await.unwrap();
+        assert_eq!(grpc_code(&response), tonic::Code::PermissionDenied);
+
+        let response = service.call(request("new-key")).await.unwrap();
+        assert_eq!(grpc_code(&response), tonic::Code::Ok);
+    }
+
+    #[tokio::test]
+    async fn test_lockout_after_repeated_failures() {
+        let mut config = api_config(&["correct-api-key"]);
+        config.lockout.max_failures = 3;
+        let layer = ApiKeyLayer::new(Some(config));
+        let mut service = layer.layer(tower::service_fn(|_req: http::Request<BoxBody>| async {
+            Ok::<_, std::convert::Infallible>(http::Response::new(tonic::body::empty_body()))
+        }));
+
+        let from = |ip: &str, api_key: &str| {
+            let mut req = request(api_key);
+            req.extensions_mut()
+                .insert(tonic::transport::server::TcpConnectInfo {
+                    local_addr: None,
+                    remote_addr: Some(format!("{}:40000", ip).parse().unwrap()),
+                });
+            req
+        };
+
+        for _ in 0..3 {
+            let response = service.call(from("10.0.0.1", "guess")).await.unwrap();
+            assert_eq!(grpc_code(&response), tonic::Code::PermissionDenied);
+        }
+
+        // Locked out: even the correct key is refused without being checked
+        let response = service
+            .call(from("10.0.0.1", "correct-api-key"))
+            .await
+            .unwrap();
+        assert_eq!(grpc_code(&response), tonic::Code::Unauthenticated);
+
+        // Other sources are unaffected and a success resets their counter
+        let response = service.call(from("10.0.0.2", "guess")).await.unwrap();
+        assert_eq!(grpc_code(&response), tonic::Code::PermissionDenied);
+        let response = service
+            .call(from("10.0.0.2", "correct-api-key"))
+            .await
+            .unwrap();
+        assert_eq!(grpc_code(&response), tonic::Code::Ok);
+        assert_eq!(layer.lockout.tracked_subjects(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_hmac_signed_requests() {
+        const PATH: &str = "/tapp_service.TappService/StartApp";
+        let mut config = api_config(&["plain-key"]);
+        config.keys.push(ApiKeyEntry::Named {
+            name: "ci-signer".to_string(),
+            key: "hmac-shared-secret".to_string(),
+            key_file: None,
+            key_env: None,
+            role: ApiKeyRole::Client,
+            key_type: ApiKeyType::Hmac,
+            not_before: None,
+            expires_at: None,
+            deprecated: false,
+            namespace: None,
+        });
+        config.hmac.max_clock_skew_seconds = 60;
+        let layer = ApiKeyLayer::new(Some(config));
+        let mut service = layer.layer(tower::service_fn(
+            |req: http::Request<BoxBody>| async move {
+                assert_eq!(
+                    req.extensions().get::<AuthIdentity>().unwrap().key_name,
+                    "ci-signer"
+                );
+                Ok::<_, std::convert::Infallible>(http::Response::new(tonic::body::empty_body()))
+            },
+        ));
+
+        let signed = |headers: &[(&str, String)]| {
+            let mut builder = http::Request::builder().uri(PATH);
+            for (name, value) in headers {
+                builder = builder.header(*name, value);
+            }
+            builder.body(tonic::body::empty_body()).unwrap()
+        };
+
+        let headers = hmac_auth_headers("ci-signer", "hmac-shared-secret", PATH);
+        let response = service.call(signed(&headers)).await.unwrap();
+        assert_eq!(grpc_code(&response), tonic::Code::Ok);
+
+        // Replaying the same nonce is rejected
+        let response = service.call(signed(&headers)).await.unwrap();
+        assert_eq!(grpc_code(&response), tonic::Code::Unauthenticated);
+
+        // Timestamps outside the clock skew are rejected even when signed correctly
+        let skewed = crate::utils::current_timestamp() - 120;
+        let nonce = "skewed-nonce";
+        let response = service
+            .call(signed(&[
+                (HMAC_KEY_ID_HEADER, "ci-signer".to_string()),
+                (HMAC_TIMESTAMP_HEADER, skewed.to_string()),
+                (HMAC_NONCE_HEADER, nonce.to_string()),
+                (
+                    HMAC_SIGNATURE_HEADER,
+                    hmac_signature("hmac-shared-secret", PATH, skewed, nonce),
+                ),
+            ]))
+            .await
+            .unwrap();
+        assert_eq!(grpc_code(&response), tonic::Code::Unauthenticated);
+
+        // Wrong secret, a signature for another method and missing headers
+        let headers = hmac_auth_headers("ci-signer", "wrong-secret", PATH);
+        let response = service.call(signed(&headers)).await.unwrap();
+        assert_eq!(grpc_code(&response), tonic::Code::PermissionDenied);
+
+        let headers = hmac_auth_headers(
+            "ci-signer",
+            "hmac-shared-secret",
+            "/tapp_service.TappService/GetEvidence",
+        );
+        let response = service.call(signed(&headers)).await.unwrap();
+        assert_eq!(grpc_code(&response), tonic::Code::PermissionDenied);
+
+        let response = service
+            .call(signed(&[(HMAC_KEY_ID_HEADER, "ci-signer".to_string())]))
+            .await
+            .unwrap();
+        assert_eq!(grpc_code(&response), tonic::Code::Unauthenticated);
+
+        // The HMAC secret is not accepted as a plain key
+        let response = service.call(request("hmac-shared-secret")).await.unwrap();
+        assert_eq!(grpc_code(&response), tonic::Code::PermissionDenied);
+
+        // A reload changes the nonce capacity, keeping the nonces already seen
+        let handle = layer.config_handle();
+        let mut reloaded = handle.read().unwrap().clone().unwrap();
+        reloaded.hmac.max_tracked_nonces = 5;
+        *handle.write().unwrap() = Some(reloaded);
+        let headers = hmac_auth_headers("ci-signer", "hmac-shared-secret", PATH);
+        let response = service.call(signed(&headers)).await.unwrap();
+        assert_eq!(grpc_code(&response), tonic::Code::Ok);
+        assert_eq!(layer.nonces.get().unwrap().capacity(), 5);
+        assert_eq!(layer.nonces.get().unwrap().stats().await.active_nonces, 2);
+    }
+
+    #[tokio::test]
+    async fn test_reflection_always_protected() {
+        let mut config = api_config(&["client-api-key"]);
+        config.protected_methods = vec!["StartApp".to_string()];
+        let layer = ApiKeyLayer::new(Some(config));
+        let mut service = layer.layer(tower::service_fn(|_req: http::Request<BoxBody>| async {
+            Ok::<_, std::convert::Infallible>(http::Response::new(tonic::body::empty_body()))
+        }));
+        let call = |path: &str, api_key: Option<&str>| {
+            let mut builder = http::Request::builder().uri(path);
+            if let Some(api_key) = api_key {
+                builder = builder.header("x-api-key", api_key);
+            }
+            builder.body(tonic::body::empty_body()).unwrap()
+        };
+
+        // Unlisted methods are open, reflection is not
+        let response = service
+            .call(call("/tapp_service.TappService/GetEvidence", None))
+            .await
+            .unwrap();
+        assert_eq!(grpc_code(&response), tonic::Code::Ok);
+        for service_name in ALWAYS_PROTECTED_SERVICES {
+            let path = format!("/{}/ServerReflectionInfo", service_name);
+            let response = service.call(call(&path, None)).await.unwrap();
+            assert_eq!(grpc_code(&response), tonic::Code::Unauthenticated);
+            let response = service
+                .call(call(&path, Some("client-api-key")))
+                .await
+                .unwrap();
+            assert_eq!(grpc_code(&response), tonic::Code::Ok);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_key_validity_period() {
+        let now = chrono::Utc::now();
+        let hours = |h: i64| Some((now + chrono::Duration::hours(h)).to_rfc3339());
+        let named = |name: &str, not_before, expires_at, deprecated| ApiKeyEntry::Named {
+            name: name.to_string(),
+            key: format!("{}-api-key", name),
+            key_file: None,
+            key_env: None,
+            role: ApiKeyRole::Client,
+            key_type: ApiKeyType::Plain,
+            not_before,
+            expires_at,
+            deprecated,
+            namespace: None,
+        };
+
+        let mut config = api_config(&[]);
+        config.keys = vec![
+            named("current", hours(-1), hours(1), false),
+            named("contractor", hours(1), hours(2), false),
+            named("expired", hours(-2), hours(-1), false),
+            named("legacy", None, hours(24), true),
+        ];
+        config.validate().unwrap();
+        let layer = ApiKeyLayer::new(Some(config));
+        let mut service = layer.layer(tower::service_fn(|_req: http::Request<BoxBody>| async {
+            Ok::<_, std::convert::Infallible>(http::Response::new(tonic::body::empty_body()))
+        }));
+
+        let response = service.call(request("current-api-key")).await.unwrap();
+        assert_eq!(grpc_code(&response), tonic::Code::Ok);
+
+        let response = service.call(request("contractor-api-key")).await.unwrap();
+        assert_eq!(grpc_code(&response), tonic::Code::PermissionDenied);
+
+        let response = service.call(request("expired-api-key")).await.unwrap();
+        assert_eq!(grpc_code(&response), tonic::Code::PermissionDenied);
+
+        // Deprecated keys still work (with a warning per use)
+        let response = service.call(request("legacy-api-key")).await.unwrap();
+        assert_eq!(grpc_code(&response), tonic::Code::Ok);
+
+        // Out-of-period keys do not count toward the lockout
+        assert_eq!(layer.lockout.tracked_subjects(), 0);
+    }
+
+    #[test]
+    fn test_key_validity_period_is_validated() {
+        let entry = |not_before: &str, expires_at: &str| ApiKeyEntry::Named {
+            name: "ci".to_string(),
+            key: "ci-api-key".to_string(),
+            key_file: None,
+            key_env: None,
+            role: ApiKeyRole::Client,
+            key_type: ApiKeyType::Plain,
+            not_before: Some(not_before.to_string()),
+            expires_at: Some(expires_at.to_string()),
+            deprecated: false,
+            namespace: None,
+        };
+
+        assert!(entry("2030-01-01T00:00:00Z", "2030-02-01T00:00:00+02:00")
+            .validate()
+            .is_ok());
+        assert!(entry("2030-01-01", "2030-02-01T00:00:00Z")
+            .validate()
+            .is_err());
+        assert!(entry("2030-02-01T00:00:00Z", "2030-01-01T00:00:00Z")
+            .validate()
+            .is_err());
+    }
 }
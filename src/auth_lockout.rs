@@ -0,0 +1,232 @@
+use crate::config::AuthLockoutConfig;
+use lru::LruCache;
+use std::num::NonZeroUsize;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Failure history of a single subject (source IP or key name)
+struct FailureState {
+    failures: u32,
+    window_start: Instant,
+    lockouts: u32,
+    locked_until: Option<Instant>,
+}
+
+/// Tracks authentication failures and lockouts
+/// Subjects are opaque strings such as "ip:10.0.0.1" or "key:ci-deployer";
+/// the table is bounded by an LRU cache. Thresholds are passed in on every
+/// call so a config reload takes effect without resetting the state.
+pub struct AuthLockout {
+    subjects: Mutex<LruCache<String, FailureState>>,
+}
+
+impl AuthLockout {
+    pub fn new() -> Self {
+        Self {
+            subjects: Mutex::new(LruCache::new(Self::capacity(&AuthLockoutConfig::default()))),
+        }
+    }
+
+    fn capacity(config: &AuthLockoutConfig) -> NonZeroUsize {
+        NonZeroUsize::new(config.max_tracked_sources).unwrap_or(NonZeroUsize::MIN)
+    }
+
+    fn table(
+        &self,
+        config: &AuthLockoutConfig,
+    ) -> std::sync::MutexGuard<'_, LruCache<String, FailureState>> {
+        let mut subjects = self.subjects.lock().unwrap();
+        let capacity = Self::capacity(config);
+        if subjects.cap() != capacity {
+            subjects.resize(capacity);
+        }
+        subjects
+    }
+
+    /// Remaining lockout time of a subject, if it is currently locked out
+    pub fn locked_for(
+        &self,
+        config: &AuthLockoutConfig,
+        subject: &str,
+        now: Instant,
+    ) -> Option<Duration> {
+        if config.max_failures == 0 {
+            return None;
+        }
+
+        self.table(config)
+            .peek(subject)
+            .and_then(|state| state.locked_until)
+            .filter(|until| *until > now)
+            .map(|until| until - now)
+    }
+
+    /// Record a failed attempt
+    /// Returns the lockout duration when this failure triggered a lockout
+    pub fn record_failure(
+        &self,
+        config: &AuthLockoutConfig,
+        subject: &str,
+        now: Instant,
+    ) -> Option<Duration> {
+        if config.max_failures == 0 {
+            return None;
+        }
+
+        let window = Duration::from_secs(config.failure_window_seconds);
+        let mut subjects = self.table(config);
+        let state = subjects.get_or_insert_mut(subject.to_string(), || FailureState {
+            failures: 0,
+            window_start: now,
+            lockouts: 0,
+            locked_until: None,
+        });
+
+        // Failures outside the window no longer count
+        if now.duration_since(state.window_start) > window {
+            state.failures = 0;
+            state.window_start = now;
+        }
+
+        state.failures += 1;
+        if state.failures < config.max_failures {
+            return None;
+        }
+
+        // Exponential backoff: base, 2x base, 4x base, ... capped at max
+        let factor = 2u64.saturating_pow(state.lockouts);
+        let duration = Duration::from_secs(
+            config
+                .base_lockout_seconds
+                .saturating_mul(factor)
+                .min(config.max_lockout_seconds),
+        );
+
+        state.lockouts += 1;
+        state.failures = 0;
+        state.window_start = now;
+        state.locked_until = Some(now + duration);
+
+        Some(duration)
+    }
+
+    /// Forget a subject's history after a successful authentication
+    pub fn record_success(&self, subject: &str) {
+        self.subjects.lock().unwrap().pop(subject);
+    }
+
+    /// Number of subjects currently tracked
+    pub fn tracked_subjects(&self) -> usize {
+        self.subjects.lock().unwrap().len()
+    }
+}
+
+impl Default for AuthLockout {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(max_failures: u32) -> AuthLockoutConfig {
+        AuthLockoutConfig {
+            max_failures,
+            failure_window_seconds: 60,
+            base_lockout_seconds: 10,
+            max_lockout_seconds: 25,
+            max_tracked_sources: 100,
+        }
+    }
+
+    #[test]
+    fn test_failure_lockout_expiry_and_reset() {
+        let config = config(3);
+        let lockout = AuthLockout::new();
+        let start = Instant::now();
+
+        // Failures below the threshold do not lock
+        assert_eq!(lockout.record_failure(&config, "ip:10.0.0.1", start), None);
+        assert_eq!(lockout.record_failure(&config, "ip:10.0.0.1", start), None);
+        assert_eq!(lockout.locked_for(&config, "ip:10.0.0.1", start), None);
+
+        // Third failure locks for the base duration
+        assert_eq!(
+            lockout.record_failure(&config, "ip:10.0.0.1", start),
+            Some(Duration::from_secs(10))
+        );
+        let later = start + Duration::from_secs(4);
+        assert_eq!(
+            lockout.locked_for(&config, "ip:10.0.0.1", later),
+            Some(Duration::from_secs(6))
+        );
+        // Other subjects are unaffected
+        assert_eq!(lockout.locked_for(&config, "ip:10.0.0.2", later), None);
+
+        // Lockout expires
+        let expired = start + Duration::from_secs(10);
+        assert_eq!(lockout.locked_for(&config, "ip:10.0.0.1", expired), None);
+
+        // Next lockout doubles, then is capped at the maximum
+        for _ in 0..2 {
+            lockout.record_failure(&config, "ip:10.0.0.1", expired);
+        }
+        assert_eq!(
+            lockout.record_failure(&config, "ip:10.0.0.1", expired),
+            Some(Duration::from_secs(20))
+        );
+        let expired = expired + Duration::from_secs(20);
+        for _ in 0..2 {
+            lockout.record_failure(&config, "ip:10.0.0.1", expired);
+        }
+        assert_eq!(
+            lockout.record_failure(&config, "ip:10.0.0.1", expired),
+            Some(Duration::from_secs(25))
+        );
+
+        // Success resets the history, so the next lockout starts at the base again
+        lockout.record_success("ip:10.0.0.1");
+        assert_eq!(lockout.locked_for(&config, "ip:10.0.0.1", expired), None);
+        for _ in 0..2 {
+            lockout.record_failure(&config, "ip:10.0.0.1", expired);
+        }
+        assert_eq!(
+            lockout.record_failure(&config, "ip:10.0.0.1", expired),
+            Some(Duration::from_secs(10))
+        );
+    }
+
+    #[test]
+    fn test_failures_outside_window_do_not_count() {
+        let config = config(2);
+        let lockout = AuthLockout::new();
+        let start = Instant::now();
+
+        assert_eq!(lockout.record_failure(&config, "key:ci", start), None);
+        let later = start + Duration::from_secs(61);
+        assert_eq!(lockout.record_failure(&config, "key:ci", later), None);
+        assert!(lockout.record_failure(&config, "key:ci", later).is_some());
+    }
+
+    #[test]
+    fn test_disabled_and_bounded() {
+        let lockout = AuthLockout::new();
+        let start = Instant::now();
+        for _ in 0..100 {
+            assert_eq!(
+                lockout.record_failure(&config(0), "ip:10.0.0.1", start),
+                None
+            );
+        }
+        assert_eq!(lockout.tracked_subjects(), 0);
+
+        let mut bounded = config(5);
+        bounded.max_tracked_sources = 10;
+        for i in 0..50 {
+            lockout.record_failure(&bounded, &format!("ip:10.0.0.{}", i), start);
+        }
+        assert_eq!(lockout.tracked_subjects(), 10);
+    }
+}
@@ -0,0 +1,218 @@
+use crate::config::AaConfig;
+use crate::error::{DockerError, FailureCause, TappResult};
+use std::path::Path;
+use tracing::{debug, info};
+
+/// First line of a file written by `ensure`
+const HEADER: &str = "# Generated by tapp-server from [boot.aa]\n";
+
+/// Attestation agent configuration file for `config`
+pub fn render(config: &AaConfig) -> String {
+    let mut eventlog = toml::Table::new();
+    eventlog.insert(
+        "enable_eventlog".to_string(),
+        toml::Value::Boolean(config.eventlog_enabled),
+    );
+    if let Some(path) = &config.eventlog_path {
+        eventlog.insert(
+            "eventlog_path".to_string(),
+            toml::Value::String(path.clone()),
+        );
+    }
+
+    let mut table = toml::Table::new();
+    table.insert("eventlog_config".to_string(), toml::Value::Table(eventlog));
+    if !config.token_configs.is_empty() {
+        table.insert(
+            "token_configs".to_string(),
+            toml::Value::Table(config.token_configs.clone()),
+        );
+    }
+    format!("{}{}", HEADER, crate::config_template::render_table(&table))
+}
+
+/// Write the attestation agent configuration to `path`
+/// A missing file is always written. An existing one is rewritten only when
+/// `managed` (`boot.manage_aa_config`), and the changed lines are logged;
+/// otherwise it is left to the operator.
+pub fn ensure(path: &Path, config: &AaConfig, managed: bool) -> TappResult<()> {
+    let rendered = render(config);
+    let failed = |operation: &str, reason: String| DockerError::ContainerOperationFailed {
+        operation: operation.to_string(),
+        reason,
+        cause: FailureCause::Internal,
+    };
+
+    if !path.exists() {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).map_err(|e| {
+                failed(
+                    "create_config_dir",
+                    format!("Failed to create config directory: {}", e),
+                )
+            })?;
+        }
+        std::fs::write(path, &rendered).map_err(|e| {
+            failed(
+                "write_config",
+                format!("Failed to write default config: {}", e),
+            )
+        })?;
+        info!(path = %path.display(), "Created attestation-agent config");
+        return Ok(());
+    }
+
+    if !managed {
+        debug!(
+            path = %path.display(),
+            "Attestation-agent config is user-managed, leaving it as is"
+        );
+        return Ok(());
+    }
+
+    let current = std::fs::read_to_string(path)
+        .map_err(|e| failed("read_config", format!("Failed to read config: {}", e)))?;
+    let changes = changes(&current, &rendered);
+    if changes.is_empty() {
+        return Ok(());
+    }
+    std::fs::write(path, &rendered)
+        .map_err(|e| failed("write_config", format!("Failed to write config: {}", e)))?;
+    info!(
+        path = %path.display(),
+        "Rewrote attestation-agent config from [boot.aa]:\n{}",
+        changes.join("\n")
+    );
+    Ok(())
+}
+
+/// Lines only in `old` ("- ") then lines only in `new` ("+ "), blank lines
+/// ignored
+fn changes(old: &str, new: &str) -> Vec<String> {
+    let lines = |text: &str| -> Vec<String> {
+        text.lines()
+            .map(str::trim_end)
+            .filter(|line| !line.is_empty())
+            .map(str::to_string)
+            .collect()
+    };
+    let (old, new) = (lines(old), lines(new));
+    let removed = old
+        .iter()
+        .filter(|line| !new.contains(*line))
+        .map(|line| format!("- {}", line));
+    let added = new
+        .iter()
+        .filter(|line| !old.contains(*line))
+        .map(|line| format!("+ {}", line));
+    removed.chain(added).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn aa_config(content: &str) -> AaConfig {
+        toml::from_str(content).unwrap()
+    }
+
+    #[test]
+    fn test_render() {
+        // Defaults: the file the service always wrote
+        assert_eq!(
+            render(&AaConfig::default()),
+            "# Generated by tapp-server from [boot.aa]\n\
+             \n\
+             [eventlog_config]\n\
+             enable_eventlog = true\n"
+        );
+
+        let config = aa_config(
+            r#"
+eventlog_enabled = false
+eventlog_path = "/run/tapp/eventlog"
+"#,
+        );
+        assert_eq!(
+            render(&config),
+            "# Generated by tapp-server from [boot.aa]\n\
+             \n\
+             [eventlog_config]\n\
+             enable_eventlog = false\n\
+             eventlog_path = \"/run/tapp/eventlog\"\n"
+        );
+
+        // Token settings are passed through, nested tables included
+        let config = aa_config(
+            r#"
+[token_configs.kbs]
+url = "https://kbs.example:8080"
+cert = "/etc/tapp/kbs-ca.pem"
+
+[token_configs.coco_as]
+url = "http://127.0.0.1:8080"
+"#,
+        );
+        let rendered = render(&config);
+        assert_eq!(
+            rendered,
+            "# Generated by tapp-server from [boot.aa]\n\
+             \n\
+             [eventlog_config]\n\
+             enable_eventlog = true\n\
+             \n\
+             [token_configs]\n\
+             \n\
+             [token_configs.coco_as]\n\
+             url = \"http://127.0.0.1:8080\"\n\
+             \n\
+             [token_configs.kbs]\n\
+             cert = \"/etc/tapp/kbs-ca.pem\"\n\
+             url = \"https://kbs.example:8080\"\n"
+        );
+        let parsed: toml::Table = toml::from_str(&rendered).unwrap();
+        assert_eq!(
+            parsed["token_configs"],
+            toml::Value::Table(config.token_configs)
+        );
+    }
+
+    #[test]
+    fn test_ensure() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("aa").join("attestation-agent.toml");
+        let default = AaConfig::default();
+
+        // A missing file is written, parent directory included
+        ensure(&path, &default, false).unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), render(&default));
+
+        // A user-managed file is left alone
+        std::fs::write(&path, "[eventlog_config]\nenable_eventlog = false\n").unwrap();
+        ensure(&path, &default, false).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "[eventlog_config]\nenable_eventlog = false\n"
+        );
+
+        // A managed one is reconciled
+        ensure(&path, &default, true).unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), render(&default));
+    }
+
+    #[test]
+    fn test_changes() {
+        let old = "[eventlog_config]\nenable_eventlog = false\n\n[token_configs]\n";
+        let new = render(&AaConfig::default());
+        assert_eq!(
+            changes(old, &new),
+            vec![
+                "- enable_eventlog = false",
+                "- [token_configs]",
+                "+ # Generated by tapp-server from [boot.aa]",
+                "+ enable_eventlog = true",
+            ]
+        );
+        assert!(changes(&new, &new).is_empty());
+    }
+}
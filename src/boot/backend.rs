@@ -0,0 +1,516 @@
+//! Container runtime of the boot service
+//!
+//! `ContainerBackend` is every Docker operation `BootService` performs:
+//! the deployment steps, stopping and removing apps, logs and status.
+//! `DockerComposeManager` implements it with the docker compose CLI and the
+//! Docker API, or podman's; tests use `FakeBackend`, which keeps containers
+//! in memory.
+
+use super::manager::{AppStatus, DockerComposeManager, DockerHostInfo, MountFile};
+use crate::config::ContainerRuntime;
+use crate::error::TappResult;
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tokio_util::sync::CancellationToken;
+
+/// Docker operations of the boot service, by app ID
+/// Futures are `Send` so deployments can run on spawned tasks. Operations
+/// taking `cancel` return `TappError::Cancelled` once it is cancelled; the
+/// RPC-scoped ones (`stop`, `remove_project`, `logs`) get a token cancelled
+/// when the RPC goes away.
+pub trait ContainerBackend: Send + Sync + 'static {
+    /// Container engine the apps run on
+    fn runtime(&self) -> ContainerRuntime;
+
+    /// Directory holding the app directories
+    fn data_dir(&self) -> &Path;
+
+    /// Directory of an app's compose file, mount files and deploy logs
+    fn app_dir(&self, app_id: &str) -> PathBuf {
+        self.data_dir()
+            .join(crate::namespace::storage_name(app_id).as_ref())
+    }
+
+    /// Check that the daemon answers
+    fn ping(&self) -> impl Future<Output = TappResult<()>> + Send;
+
+    /// Docker data root, number of running containers and host platform
+    fn host_info(&self) -> impl Future<Output = TappResult<DockerHostInfo>> + Send;
+
+    /// Containers of an app's compose project, running or not
+    fn status(&self, app_id: &str) -> impl Future<Output = TappResult<AppStatus>> + Send;
+
+    /// Write an app's compose file and mount files to its directory
+    fn write_files(
+        &self,
+        app_id: &str,
+        compose_content: &str,
+        mount_files: &[MountFile],
+    ) -> impl Future<Output = TappResult<()>> + Send;
+
+    /// Pull the images of an app
+    fn pull(
+        &self,
+        app_id: &str,
+        task_id: &str,
+        cancel: &CancellationToken,
+    ) -> impl Future<Output = TappResult<()>> + Send;
+
+    /// Pulled images of an app, each pinned to a digest or image ID
+    fn image_digests(&self, app_id: &str) -> impl Future<Output = TappResult<Vec<String>>> + Send;
+
+    /// Create and start the containers of an app, giving up after `timeout`
+    fn up(
+        &self,
+        app_id: &str,
+        task_id: &str,
+        timeout: Duration,
+        cancel: &CancellationToken,
+    ) -> impl Future<Output = TappResult<()>> + Send;
+
+    /// Wait until the containers of an app are running and healthy
+    fn wait_healthy(
+        &self,
+        app_id: &str,
+        timeout: Duration,
+        cancel: &CancellationToken,
+    ) -> impl Future<Output = TappResult<()>> + Send;
+
+    /// Take an app's containers down; its directory must exist
+    fn stop(
+        &self,
+        app_id: &str,
+        cancel: &CancellationToken,
+    ) -> impl Future<Output = TappResult<()>> + Send;
+
+    /// Take an app's compose project down by name, with or without its
+    /// directory, optionally with its volumes and images
+    fn remove_project(
+        &self,
+        app_id: &str,
+        purge_volumes: bool,
+        purge_images: bool,
+        cancel: &CancellationToken,
+    ) -> impl Future<Output = TappResult<()>> + Send;
+
+    /// Last `lines` log lines of an app, or of one of its services
+    fn logs(
+        &self,
+        app_id: &str,
+        lines: i32,
+        service_name: Option<&str>,
+        cancel: &CancellationToken,
+    ) -> impl Future<Output = TappResult<String>> + Send;
+}
+
+/// The docker compose CLI, run in the app directories of the manager
+impl ContainerBackend for DockerComposeManager {
+    fn runtime(&self) -> ContainerRuntime {
+        DockerComposeManager::runtime(self)
+    }
+
+    fn data_dir(&self) -> &Path {
+        DockerComposeManager::data_dir(self)
+    }
+
+    async fn ping(&self) -> TappResult<()> {
+        DockerComposeManager::ping(self).await
+    }
+
+    async fn host_info(&self) -> TappResult<DockerHostInfo> {
+        DockerComposeManager::host_info(self).await
+    }
+
+    async fn status(&self, app_id: &str) -> TappResult<AppStatus> {
+        self.get_compose_status(app_id).await
+    }
+
+    async fn write_files(
+        &self,
+        app_id: &str,
+        compose_content: &str,
+        mount_files: &[MountFile],
+    ) -> TappResult<()> {
+        self.write_compose_files(app_id, compose_content, mount_files)
+            .await
+    }
+
+    async fn pull(
+        &self,
+        app_id: &str,
+        task_id: &str,
+        cancel: &CancellationToken,
+    ) -> TappResult<()> {
+        self.pull_compose(app_id, task_id, cancel).await
+    }
+
+    async fn image_digests(&self, app_id: &str) -> TappResult<Vec<String>> {
+        DockerComposeManager::image_digests(self, app_id).await
+    }
+
+    async fn up(
+        &self,
+        app_id: &str,
+        task_id: &str,
+        timeout: Duration,
+        cancel: &CancellationToken,
+    ) -> TappResult<()> {
+        self.up_compose(app_id, task_id, timeout, cancel).await
+    }
+
+    async fn wait_healthy(
+        &self,
+        app_id: &str,
+        timeout: Duration,
+        cancel: &CancellationToken,
+    ) -> TappResult<()> {
+        DockerComposeManager::wait_healthy(self, app_id, timeout, cancel).await
+    }
+
+    async fn stop(&self, app_id: &str, cancel: &CancellationToken) -> TappResult<()> {
+        self.stop_compose(app_id, cancel).await
+    }
+
+    async fn remove_project(
+        &self,
+        app_id: &str,
+        purge_volumes: bool,
+        purge_images: bool,
+        cancel: &CancellationToken,
+    ) -> TappResult<()> {
+        DockerComposeManager::remove_project(self, app_id, purge_volumes, purge_images, cancel)
+            .await
+    }
+
+    async fn logs(
+        &self,
+        app_id: &str,
+        lines: i32,
+        service_name: Option<&str>,
+        cancel: &CancellationToken,
+    ) -> TappResult<String> {
+        self.get_app_logs(app_id, lines, service_name, cancel).await
+    }
+}
+
+#[cfg(test)]
+pub(crate) use fake::{FakeBackend, FakeFailure};
+
+#[cfg(test)]
+mod fake {
+    use super::*;
+    use crate::boot::image_policy::compose_images;
+    use crate::error::{DockerError, FailureCause, TappError};
+    use futures_util::future::BoxFuture;
+    use sha2::{Digest, Sha256};
+    use std::collections::{HashMap, VecDeque};
+    use std::sync::Mutex;
+
+    /// Scripted failure of a `FakeBackend` operation
+    #[derive(Debug, Clone)]
+    pub(crate) enum FakeFailure {
+        /// The daemon cannot be reached
+        Unavailable,
+        /// The command fails with this output
+        Error(String),
+        /// The command runs past its timeout
+        Timeout,
+        /// The command never returns; cancellable operations return
+        /// `TappError::Cancelled` once cancelled
+        Hang,
+        /// The backend panics, as a bug would
+        Panic,
+    }
+
+    impl FakeFailure {
+        fn into_error(self, operation: &str, runtime: ContainerRuntime) -> TappError {
+            match self {
+                FakeFailure::Unavailable => DockerError::ConnectionFailed {
+                    endpoint: runtime.default_sockets()[0].to_string_lossy().to_string(),
+                    reason: "connection refused".to_string(),
+                }
+                .into(),
+
+                FakeFailure::Error(reason) => DockerError::ContainerOperationFailed {
+                    operation: operation.to_string(),
+                    reason,
+                    cause: FailureCause::Internal,
+                }
+                .into(),
+                FakeFailure::Timeout => DockerError::Timeout {
+                    operation: operation.to_string(),
+                    seconds: 300,
+                }
+                .into(),
+                FakeFailure::Hang | FakeFailure::Panic => {
+                    unreachable!("hangs and panics are not returned as errors")
+                }
+            }
+        }
+    }
+
+    /// Called with the name of each operation before it runs
+    type Observer = Box<dyn Fn(&'static str) -> BoxFuture<'static, ()> + Send + Sync>;
+
+    /// In-memory Docker for tests
+    /// App files are written to `data_dir` as for the real backend; each
+    /// `up` creates one container, kept until `stop` or `remove_project`.
+    /// Every call is recorded as "<operation> <app_id>" (plus the purge flags
+    /// of `remove_project`), and operations fail as scripted with `fail`.
+    pub(crate) struct FakeBackend {
+        runtime: ContainerRuntime,
+        data_dir: PathBuf,
+        calls: Mutex<Vec<String>>,
+        // Containers per app ID
+        containers: Mutex<HashMap<String, usize>>,
+        // Failures per operation, used up one call at a time
+        failures: Mutex<HashMap<&'static str, VecDeque<FakeFailure>>>,
+        observer: Option<Observer>,
+    }
+
+    impl FakeBackend {
+        pub(crate) fn new(data_dir: &Path) -> Self {
+            Self {
+                runtime: ContainerRuntime::Docker,
+                data_dir: data_dir.to_path_buf(),
+                calls: Mutex::new(Vec::new()),
+                containers: Mutex::new(HashMap::new()),
+                failures: Mutex::new(HashMap::new()),
+                observer: None,
+            }
+        }
+
+        /// Pretend to run apps on `runtime`
+        pub(crate) fn with_runtime(mut self, runtime: ContainerRuntime) -> Self {
+            self.runtime = runtime;
+            self
+        }
+
+        /// Run `observer` with the name of each operation before it runs
+        pub(crate) fn observe(
+            mut self,
+            observer: impl Fn(&'static str) -> BoxFuture<'static, ()> + Send + Sync + 'static,
+        ) -> Self {
+            self.observer = Some(Box::new(observer));
+            self
+        }
+
+        /// Fail the next `times` calls of `operation`
+        pub(crate) fn fail(&self, operation: &'static str, failure: FakeFailure, times: usize) {
+            self.failures
+                .lock()
+                .unwrap()
+                .entry(operation)
+                .or_default()
+                .extend(std::iter::repeat_n(failure, times));
+        }
+
+        /// Give an app containers, as if deployed outside the service
+        pub(crate) fn add_containers(&self, app_id: &str, count: usize) {
+            self.containers
+                .lock()
+                .unwrap()
+                .insert(app_id.to_string(), count);
+        }
+
+        /// Containers an app has
+        pub(crate) fn containers(&self, app_id: &str) -> usize {
+            self.containers
+                .lock()
+                .unwrap()
+                .get(app_id)
+                .copied()
+                .unwrap_or(0)
+        }
+
+        /// Calls made so far, oldest first
+        pub(crate) fn calls(&self) -> Vec<String> {
+            self.calls.lock().unwrap().clone()
+        }
+
+        /// Number of calls of `operation`
+        pub(crate) fn count(&self, operation: &str) -> usize {
+            self.calls
+                .lock()
+                .unwrap()
+                .iter()
+                .filter(|call| call.split(' ').next() == Some(operation))
+                .count()
+        }
+
+        /// Record a call and play its scripted failure, if any
+        async fn call(
+            &self,
+            operation: &'static str,
+            app_id: &str,
+            cancel: Option<&CancellationToken>,
+        ) -> TappResult<()> {
+            self.calls
+                .lock()
+                .unwrap()
+                .push(format!("{} {}", operation, app_id).trim_end().to_string());
+            if let Some(observer) = &self.observer {
+                observer(operation).await;
+            }
+            let failure = self
+                .failures
+                .lock()
+                .unwrap()
+                .get_mut(operation)
+                .and_then(VecDeque::pop_front);
+            match failure {
+                None => Ok(()),
+                Some(FakeFailure::Panic) => panic!("{} of {} panicked", operation, app_id),
+                Some(FakeFailure::Hang) => match cancel {
+                    Some(cancel) => {
+                        cancel.cancelled().await;
+                        Err(TappError::Cancelled)
+                    }
+                    None => std::future::pending().await,
+                },
+                Some(failure) => Err(failure.into_error(operation, self.runtime)),
+            }
+        }
+
+        /// Fail like the compose CLI does for an app without a directory
+        fn require_app_dir(&self, app_id: &str) -> TappResult<()> {
+            if self.app_dir(app_id).exists() {
+                return Ok(());
+            }
+            Err(TappError::InvalidParameter {
+                field: "app_id".to_string(),
+                reason: format!("App {} not found", app_id),
+            })
+        }
+    }
+
+    impl ContainerBackend for FakeBackend {
+        fn runtime(&self) -> ContainerRuntime {
+            self.runtime
+        }
+
+        fn data_dir(&self) -> &Path {
+            &self.data_dir
+        }
+
+        async fn ping(&self) -> TappResult<()> {
+            self.call("ping", "", None).await
+        }
+
+        async fn host_info(&self) -> TappResult<DockerHostInfo> {
+            self.call("host_info", "", None).await?;
+            Ok(DockerHostInfo {
+                root_dir: None,
+                running_containers: self.containers.lock().unwrap().values().sum::<usize>() as i64,
+                os_type: Some("linux".to_string()),
+                architecture: Some("x86_64".to_string()),
+            })
+        }
+
+        async fn status(&self, app_id: &str) -> TappResult<AppStatus> {
+            self.call("status", app_id, None).await?;
+            let container_count = self.containers(app_id);
+            Ok(AppStatus {
+                app_id: app_id.to_string(),
+                running: container_count > 0,
+                container_count,
+                containers: Vec::new(),
+                started_at: None,
+                restart_count: 0,
+            })
+        }
+
+        async fn write_files(
+            &self,
+            app_id: &str,
+            compose_content: &str,
+            mount_files: &[MountFile],
+        ) -> TappResult<()> {
+            self.call("write_files", app_id, None).await?;
+            DockerComposeManager::write_app_files(
+                &self.app_dir(app_id),
+                compose_content,
+                mount_files,
+            )
+            .await
+        }
+
+        async fn pull(&self, app_id: &str, _: &str, cancel: &CancellationToken) -> TappResult<()> {
+            self.call("pull", app_id, Some(cancel)).await
+        }
+
+        /// The compose file's images, pinned to a digest of their name
+        async fn image_digests(&self, app_id: &str) -> TappResult<Vec<String>> {
+            self.call("image_digests", app_id, None).await?;
+            let compose_content =
+                std::fs::read_to_string(self.app_dir(app_id).join("docker-compose.yml"))?;
+            Ok(compose_images(&compose_content)?
+                .iter()
+                .map(|image| {
+                    let digest = hex::encode(Sha256::digest(image.to_string()));
+                    format!("{}@sha256:{}", image.name(), digest)
+                })
+                .collect())
+        }
+
+        async fn up(
+            &self,
+            app_id: &str,
+            _: &str,
+            _: Duration,
+            cancel: &CancellationToken,
+        ) -> TappResult<()> {
+            self.call("up", app_id, Some(cancel)).await?;
+            self.add_containers(app_id, 1);
+            Ok(())
+        }
+
+        async fn wait_healthy(
+            &self,
+            app_id: &str,
+            _: Duration,
+            cancel: &CancellationToken,
+        ) -> TappResult<()> {
+            self.call("wait_healthy", app_id, Some(cancel)).await
+        }
+
+        async fn stop(&self, app_id: &str, cancel: &CancellationToken) -> TappResult<()> {
+            self.call("stop", app_id, Some(cancel)).await?;
+            self.require_app_dir(app_id)?;
+            self.containers.lock().unwrap().remove(app_id);
+            Ok(())
+        }
+
+        async fn remove_project(
+            &self,
+            app_id: &str,
+            purge_volumes: bool,
+            purge_images: bool,
+            cancel: &CancellationToken,
+        ) -> TappResult<()> {
+            let mut target = app_id.to_string();
+            if purge_volumes {
+                target.push_str(" --volumes");
+            }
+            if purge_images {
+                target.push_str(" --rmi all");
+            }
+            self.call("remove_project", &target, Some(cancel)).await?;
+            self.containers.lock().unwrap().remove(app_id);
+            Ok(())
+        }
+
+        async fn logs(
+            &self,
+            app_id: &str,
+            lines: i32,
+            _: Option<&str>,
+            cancel: &CancellationToken,
+        ) -> TappResult<String> {
+            self.call("logs", app_id, Some(cancel)).await?;
+            self.require_app_dir(app_id)?;
+            Ok(format!("{} | log line\n", app_id).repeat(lines.max(0) as usize))
+        }
+    }
+}
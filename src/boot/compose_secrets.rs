@@ -0,0 +1,270 @@
+//! Secret placeholders in compose files
+//!
+//! A compose file may use `${TAPP_SECRET:<name>}` wherever compose
+//! interpolates variables. The submitted content keeps the placeholder, so
+//! the compose hash never covers a value; the measurement lists the names in
+//! `secret_names`. The file written for compose refers to the variable
+//! `TAPP_SECRET_<name>` instead, defined in the `.env` of the app directory
+//! with the value `AppKeyService::compose_secret` derives for the app.
+//!
+//! Names are 1 to 64 letters, digits and `_`, not starting with a digit.
+//! `$$` escapes a `$` as in compose. Any other use of a `TAPP_SECRET`
+//! variable, e.g. `${TAPP_SECRET_db}` or `${TAPP_SECRET:-x}`, is rejected.
+
+use super::MountFile;
+use crate::error::{DockerError, TappResult};
+use serde_yaml::Value;
+use std::ops::Range;
+
+/// Variable name of the placeholders, and prefix of the variables they become
+pub const SECRET_VARIABLE: &str = "TAPP_SECRET";
+
+/// Env file compose reads the values from
+pub const ENV_FILE: &str = "./.env";
+
+/// Longest placeholder name accepted
+pub const MAX_NAME_LEN: usize = 64;
+
+/// Whether `name` is usable in a placeholder
+pub fn validate_name(name: &str) -> bool {
+    !name.is_empty()
+        && name.len() <= MAX_NAME_LEN
+        && !name.starts_with(|c: char| c.is_ascii_digit())
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Names of the placeholders in the values of `compose_content`, sorted and
+/// without duplicates
+/// Keys and comments are not interpolated by compose and not looked at.
+pub fn scan(compose_content: &str) -> TappResult<Vec<String>> {
+    let parsed: Value =
+        serde_yaml::from_str(compose_content).map_err(|e| DockerError::InvalidComposeContent {
+            reason: format!("YAML parse error: {}", e),
+        })?;
+    let mut names = Vec::new();
+    collect_names(&parsed, &mut names)?;
+    names.sort();
+    names.dedup();
+    Ok(names)
+}
+
+fn collect_names(value: &Value, names: &mut Vec<String>) -> TappResult<()> {
+    match value {
+        Value::String(text) => {
+            for (_, reference) in references(text) {
+                let name = reference.map_err(|reason| DockerError::InvalidComposeContent {
+                    reason: format!("Invalid secret placeholder in '{}': {}", text, reason),
+                })?;
+                names.push(name.to_string());
+            }
+        }
+        Value::Sequence(items) => {
+            for item in items {
+                collect_names(item, names)?;
+            }
+        }
+        Value::Mapping(map) => {
+            for item in map.values() {
+                collect_names(item, names)?;
+            }
+        }
+        Value::Tagged(tagged) => collect_names(&tagged.value, names)?,
+        _ => {}
+    }
+    Ok(())
+}
+
+/// `compose_content` as written for compose: every placeholder replaced by
+/// a reference to its variable in `.env`
+pub fn render(compose_content: &str) -> String {
+    let mut rendered = String::with_capacity(compose_content.len());
+    let mut copied = 0;
+    for (range, reference) in references(compose_content) {
+        if let Ok(name) = reference {
+            rendered.push_str(&compose_content[copied..range.start]);
+            rendered.push_str(&format!("${{{}}}", variable(name)));
+            copied = range.end;
+        }
+    }
+    rendered.push_str(&compose_content[copied..]);
+    rendered
+}
+
+/// Variable holding the value of the placeholder `name`
+pub fn variable(name: &str) -> String {
+    format!("{}_{}", SECRET_VARIABLE, name)
+}
+
+/// `mount_files` with the variables of `values` (name, value) added to
+/// `.env`, after the content of a `.env` the deployer sent
+pub fn with_env_file(
+    mut mount_files: Vec<MountFile>,
+    values: &[(String, String)],
+) -> Vec<MountFile> {
+    let lines: String = values
+        .iter()
+        .map(|(name, value)| format!("{}={}\n", variable(name), value))
+        .collect();
+    let sent = mount_files
+        .iter_mut()
+        .find(|file| file.source_path.trim_start_matches("./") == ".env");
+    match sent {
+        Some(file) => {
+            if !file.content.is_empty() && !file.content.ends_with(b"\n") {
+                file.content.push(b'\n');
+            }
+            file.content.extend_from_slice(lines.as_bytes());
+        }
+        None => mount_files.push(MountFile {
+            source_path: ENV_FILE.to_string(),
+            content: lines.into_bytes(),
+            mode: "0600".to_string(),
+            encrypted: false,
+        }),
+    }
+    mount_files
+}
+
+/// References to a `TAPP_SECRET` variable in `text`: where they are, and the
+/// placeholder name or why the reference is malformed
+fn references(text: &str) -> Vec<(Range<usize>, Result<&str, String>)> {
+    // Another variable merely starting with the name, e.g. TAPP_SECRETS
+    let ours = |rest: &str| {
+        rest.strip_prefix(SECRET_VARIABLE)
+            .is_some_and(|after| !after.starts_with(|c: char| c.is_ascii_alphanumeric()))
+    };
+
+    let reserved = || {
+        format!(
+            "{} variables are set by the service, use ${{{}:<name>}}",
+            SECRET_VARIABLE, SECRET_VARIABLE
+        )
+    };
+
+    let mut references = Vec::new();
+    let mut start = 0;
+    while let Some(offset) = text[start..].find('$') {
+        let at = start + offset;
+        let rest = &text[at + 1..];
+        start = at + 1;
+        if rest.starts_with('$') {
+            start += 1;
+        } else if let Some(braced) = rest.strip_prefix('{').filter(|braced| ours(braced)) {
+            let Some(len) = braced.find('}') else {
+                references.push((at..text.len(), Err("missing '}'".to_string())));
+                break;
+            };
+            let end = at + 2 + len + 1;
+            let reference = match braced[SECRET_VARIABLE.len()..len].strip_prefix(':') {
+                Some(name) if validate_name(name) => Ok(name),
+                Some(name) => Err(format!(
+                    "'{}' is not a name of 1 to {} letters, digits and '_'",
+                    name, MAX_NAME_LEN
+                )),
+                None => Err(reserved()),
+            };
+            references.push((at..end, reference));
+            start = end;
+        } else if ours(rest) {
+            let end = at + 1 + SECRET_VARIABLE.len();
+            references.push((at..end, Err(reserved())));
+            start = end;
+        }
+    }
+    references
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const COMPOSE: &str = r#"
+# ${TAPP_SECRET} in a comment is left alone
+services:
+  db:
+    image: postgres
+    environment:
+      POSTGRES_PASSWORD: ${TAPP_SECRET:db_password}
+      PRICE: $$5
+  api:
+    image: api
+    command: ["--token=${TAPP_SECRET:api_token}", "--db=${TAPP_SECRET:db_password}"]
+"#;
+
+    #[test]
+    fn test_scan() {
+        assert_eq!(scan(COMPOSE).unwrap(), ["api_token", "db_password"]);
+        assert!(scan("services: {}\n").unwrap().is_empty());
+        // Other variables, escaped placeholders and lookalike names
+        let compose = "services:\n  a:\n    environment:\n      - A=${HOME}\n      - B=$${TAPP_SECRET:x}\n      - C=${TAPP_SECRETS}\n";
+        assert!(scan(compose).unwrap().is_empty());
+
+        for malformed in [
+            "${TAPP_SECRET}",
+            "${TAPP_SECRET:}",
+            "${TAPP_SECRET:-fallback}",
+            "${TAPP_SECRET:db password}",
+            "${TAPP_SECRET:9lives}",
+            "${TAPP_SECRET_db_password}",
+            "$TAPP_SECRET_db_password",
+            "${TAPP_SECRET:db_password",
+        ] {
+            let compose = format!(
+                "services:\n  a:\n    environment:\n      A: \"{}\"\n",
+                malformed
+            );
+            let err = scan(&compose).unwrap_err().to_string();
+            assert!(
+                err.contains("Invalid secret placeholder"),
+                "{}: {}",
+                malformed,
+                err
+            );
+        }
+        let long = format!("x: ${{TAPP_SECRET:{}}}\n", "a".repeat(MAX_NAME_LEN + 1));
+        assert!(scan(&long).is_err());
+    }
+
+    #[test]
+    fn test_render() {
+        let rendered = render(COMPOSE);
+        assert!(rendered.contains("POSTGRES_PASSWORD: ${TAPP_SECRET_db_password}"));
+        assert!(rendered.contains(
+            r#"["--token=${TAPP_SECRET_api_token}", "--db=${TAPP_SECRET_db_password}"]"#
+        ));
+        assert!(rendered.contains("# ${TAPP_SECRET} in a comment"));
+        assert!(rendered.contains("PRICE: $$5"));
+        assert_eq!(render("services: {}\n"), "services: {}\n");
+    }
+
+    #[test]
+    fn test_with_env_file() {
+        let values = [
+            ("api_token".to_string(), "aa".to_string()),
+            ("db_password".to_string(), "bb".to_string()),
+        ];
+        let files = with_env_file(vec![], &values);
+        assert_eq!(files.len(), 1);
+        assert_eq!(files[0].source_path, ENV_FILE);
+        assert_eq!(files[0].mode, "0600");
+        assert_eq!(
+            files[0].content,
+            b"TAPP_SECRET_api_token=aa\nTAPP_SECRET_db_password=bb\n"
+        );
+
+        // Appended to the deployer's own .env
+        let sent = MountFile {
+            source_path: ".env".to_string(),
+            content: b"LOG_LEVEL=debug".to_vec(),
+            mode: "0640".to_string(),
+            encrypted: false,
+        };
+        let files = with_env_file(vec![sent], &values[..1]);
+        assert_eq!(files.len(), 1);
+        assert_eq!(files[0].mode, "0640");
+        assert_eq!(
+            files[0].content,
+            b"LOG_LEVEL=debug\nTAPP_SECRET_api_token=aa\n"
+        );
+    }
+}
@@ -0,0 +1,206 @@
+use crate::error::{TappError, TappResult};
+use std::path::{Path, PathBuf};
+use tokio::fs;
+use tokio::io::AsyncWriteExt;
+
+const FILE_PREFIX: &str = "deploy-";
+const FILE_SUFFIX: &str = ".log";
+
+/// Output file of a deployment task in its app directory
+/// (`deploy-<task_id>.log`); automatic retries append to the same file.
+pub fn log_path(app_dir: &Path, task_id: &str) -> PathBuf {
+    app_dir.join(format!("{}{}{}", FILE_PREFIX, task_id, FILE_SUFFIX))
+}
+
+/// Append the output of one docker compose command to a task's file
+/// `output` holds the lines in the order they were printed.
+pub async fn append(
+    app_dir: &Path,
+    task_id: &str,
+    command: &str,
+    outcome: &str,
+    output: &[String],
+) -> std::io::Result<()> {
+    let mut section = format!(
+        "=== {} docker compose {}: {}\n",
+        chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Secs, true),
+        command,
+        outcome
+    );
+    for line in output {
+        section.push_str(line);
+        section.push('\n');
+    }
+
+    let mut file = fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(log_path(app_dir, task_id))
+        .await?;
+    file.write_all(section.as_bytes()).await
+}
+
+/// Deploy output files of an app as (task_id, path), oldest first
+pub async fn list(app_dir: &Path) -> std::io::Result<Vec<(String, PathBuf)>> {
+    let mut entries = match fs::read_dir(app_dir).await {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    };
+
+    let mut files = Vec::new();
+    while let Some(entry) = entries.next_entry().await? {
+        let name = entry.file_name().to_string_lossy().to_string();
+        let Some(task_id) = name
+            .strip_prefix(FILE_PREFIX)
+            .and_then(|rest| rest.strip_suffix(FILE_SUFFIX))
+        else {
+            continue;
+        };
+        let modified = entry.metadata().await?.modified()?;
+        files.push((modified, task_id.to_string(), entry.path()));
+    }
+
+    files.sort();
+    Ok(files
+        .into_iter()
+        .map(|(_, task_id, path)| (task_id, path))
+        .collect())
+}
+
+/// Delete the oldest files beyond `keep`; returns the removed paths
+pub async fn trim(app_dir: &Path, keep: usize) -> std::io::Result<Vec<PathBuf>> {
+    let files = list(app_dir).await?;
+    let excess = files.len().saturating_sub(keep);
+
+    let mut removed = Vec::new();
+    for (_, path) in files.into_iter().take(excess) {
+        fs::remove_file(&path).await?;
+        removed.push(path);
+    }
+    Ok(removed)
+}
+
+/// Output of a task's deployment, or of the latest one, as (task_id, content)
+/// Only files found in the app directory are read, so `task_id` never
+/// becomes part of a path.
+pub async fn read(app_dir: &Path, task_id: Option<&str>) -> TappResult<(String, String)> {
+    let files = list(app_dir).await?;
+    let found = match task_id {
+        Some(task_id) => files.into_iter().find(|(id, _)| id == task_id),
+        None => files.into_iter().last(),
+    };
+    let Some((task_id, path)) = found else {
+        return Err(TappError::InvalidParameter {
+            field: "task_id".to_string(),
+            reason: match task_id {
+                Some(task_id) => format!("No deploy output for task {}", task_id),
+                None => "No deploy output for this app".to_string(),
+            },
+        });
+    };
+
+    let content = fs::read(&path).await?;
+    Ok((task_id, String::from_utf8_lossy(&content).into_owned()))
+}
+
+/// Last `lines` lines of `content`
+pub fn tail(content: &str, lines: usize) -> String {
+    let all: Vec<&str> = content.lines().collect();
+    let mut tail = all[all.len().saturating_sub(lines)..].join("\n");
+    if !tail.is_empty() {
+        tail.push('\n');
+    }
+    tail
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{Duration, SystemTime};
+
+    /// Push a file's modification time into the past
+    fn age(path: &Path, seconds: u64) {
+        std::fs::File::options()
+            .write(true)
+            .open(path)
+            .unwrap()
+            .set_modified(SystemTime::now() - Duration::from_secs(seconds))
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_write_and_read() {
+        let dir = tempfile::tempdir().unwrap();
+        let output = vec![
+            "[stderr]  Network app_default  Created".to_string(),
+            "[stderr]  Container app-web-1  Started".to_string(),
+        ];
+        append(dir.path(), "task-1", "pull", "exit code 0", &[])
+            .await
+            .unwrap();
+        append(dir.path(), "task-1", "up", "exit code 0", &output)
+            .await
+            .unwrap();
+        age(&log_path(dir.path(), "task-1"), 60);
+        append(dir.path(), "task-2", "up", "timed out after 300s", &[])
+            .await
+            .unwrap();
+        std::fs::write(dir.path().join("docker-compose.yml"), "services: {}\n").unwrap();
+
+        let (task_id, content) = read(dir.path(), Some("task-1")).await.unwrap();
+        assert_eq!(task_id, "task-1");
+        let lines: Vec<&str> = content.lines().collect();
+        assert_eq!(lines.len(), 4);
+        assert!(
+            lines[0].starts_with("=== ") && lines[0].ends_with("docker compose pull: exit code 0")
+        );
+        assert!(lines[1].ends_with("docker compose up: exit code 0"));
+        assert_eq!(lines[2..], output[..]);
+        assert_eq!(tail(&content, 2), format!("{}\n{}\n", output[0], output[1]));
+        assert_eq!(tail(&content, 10), content);
+
+        // Latest by default
+        let (task_id, content) = read(dir.path(), None).await.unwrap();
+        assert_eq!(task_id, "task-2");
+        assert!(content.contains("timed out after 300s"));
+
+        let err = read(dir.path(), Some("../docker-compose.yml"))
+            .await
+            .unwrap_err();
+        assert!(matches!(err, TappError::InvalidParameter { .. }));
+        let empty = tempfile::tempdir().unwrap();
+        assert!(read(&empty.path().join("gone"), None).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_trim_keeps_newest() {
+        let dir = tempfile::tempdir().unwrap();
+        for (i, task_id) in ["task-a", "task-b", "task-c", "task-d"].iter().enumerate() {
+            append(dir.path(), task_id, "up", "exit code 0", &[])
+                .await
+                .unwrap();
+            age(&log_path(dir.path(), task_id), 100 - i as u64 * 10);
+        }
+        std::fs::write(dir.path().join("docker-compose.yml"), "services: {}\n").unwrap();
+
+        let removed = trim(dir.path(), 2).await.unwrap();
+        assert_eq!(
+            removed,
+            vec![
+                log_path(dir.path(), "task-a"),
+                log_path(dir.path(), "task-b")
+            ]
+        );
+        let kept: Vec<String> = list(dir.path())
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|(task_id, _)| task_id)
+            .collect();
+        assert_eq!(kept, vec!["task-c", "task-d"]);
+        assert!(dir.path().join("docker-compose.yml").exists());
+
+        assert!(trim(dir.path(), 2).await.unwrap().is_empty());
+    }
+}
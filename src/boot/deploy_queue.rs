@@ -0,0 +1,203 @@
+use crate::config::BootServiceConfig;
+use crate::error::{TappError, TappResult};
+use crate::proto::StartAppRequest;
+use std::collections::VecDeque;
+use std::sync::Mutex;
+
+/// Slots and waiting line of StartApp deployments
+struct QueueState {
+    running: usize,
+    waiting: VecDeque<(String, StartAppRequest)>,
+}
+
+/// FIFO admission of deployments
+/// At most `max_running` deployments run at once; later ones wait in order,
+/// up to `max_queued` of them. A worker that finishes a deployment takes the
+/// next waiting one, so its slot is handed over rather than released.
+pub struct DeployQueue {
+    max_running: usize,
+    max_queued: usize,
+    state: Mutex<QueueState>,
+}
+
+impl DeployQueue {
+    pub fn new(max_running: usize, max_queued: usize) -> Self {
+        Self {
+            max_running: max_running.max(1),
+            max_queued,
+            state: Mutex::new(QueueState {
+                running: 0,
+                waiting: VecDeque::new(),
+            }),
+        }
+    }
+
+    pub fn from_config(config: &BootServiceConfig) -> Self {
+        Self::new(
+            config.max_concurrent_deployments,
+            config.max_queued_deployments,
+        )
+    }
+
+    /// Admit a deployment
+    /// Returns it back if a slot is free and it should start now, None if it
+    /// was queued, and `ResourceExhausted` if the queue is full.
+    pub fn enqueue(
+        &self,
+        task_id: String,
+        request: StartAppRequest,
+    ) -> TappResult<Option<(String, StartAppRequest)>> {
+        let mut state = self.state.lock().unwrap();
+        if state.running < self.max_running {
+            state.running += 1;
+            return Ok(Some((task_id, request)));
+        }
+        if state.waiting.len() >= self.max_queued {
+            return Err(TappError::ResourceExhausted(format!(
+                "{} deployments running and {} queued, retry later",
+                state.running,
+                state.waiting.len()
+            )));
+        }
+        state.waiting.push_back((task_id, request));
+        Ok(None)
+    }
+
+    /// Finish a running deployment
+    /// Returns the next queued deployment, which takes over the slot.
+    pub fn finish(&self) -> Option<(String, StartAppRequest)> {
+        let mut state = self.state.lock().unwrap();
+        let next = state.waiting.pop_front();
+        if next.is_none() {
+            state.running = state.running.saturating_sub(1);
+        }
+        next
+    }
+
+    /// Drop a queued deployment; returns false if it is not waiting
+    pub fn remove(&self, task_id: &str) -> bool {
+        let mut state = self.state.lock().unwrap();
+        let before = state.waiting.len();
+        state.waiting.retain(|(id, _)| id != task_id);
+        state.waiting.len() != before
+    }
+
+    /// Drop every queued deployment; returns their task IDs in queue order
+    pub fn drain(&self) -> Vec<String> {
+        let mut state = self.state.lock().unwrap();
+        state.waiting.drain(..).map(|(id, _)| id).collect()
+    }
+
+    /// 1-based position of a queued deployment
+    pub fn position(&self, task_id: &str) -> Option<usize> {
+        let state = self.state.lock().unwrap();
+        state
+            .waiting
+            .iter()
+            .position(|(id, _)| id == task_id)
+            .map(|i| i + 1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(app_id: &str) -> StartAppRequest {
+        StartAppRequest {
+            app_id: app_id.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_queue_positions_and_order() {
+        let limit = 2;
+        let queue = DeployQueue::new(limit, 3);
+
+        let mut started = Vec::new();
+        for i in 0..limit + 3 {
+            let task_id = format!("task-{}", i);
+            if let Some((id, _)) = queue.enqueue(task_id, request("app")).unwrap() {
+                started.push(id);
+            }
+        }
+        assert_eq!(started, vec!["task-0", "task-1"]);
+        assert_eq!(queue.position("task-0"), None);
+        assert_eq!(queue.position("task-2"), Some(1));
+        assert_eq!(queue.position("task-3"), Some(2));
+        assert_eq!(queue.position("task-4"), Some(3));
+
+        // Full: further submissions are rejected
+        assert!(matches!(
+            queue.enqueue("task-5".to_string(), request("app")),
+            Err(TappError::ResourceExhausted(_))
+        ));
+
+        // Positions move up as earlier deployments finish
+        let (next, _) = queue.finish().unwrap();
+        started.push(next);
+        assert_eq!(queue.position("task-3"), Some(1));
+        assert_eq!(queue.position("task-4"), Some(2));
+
+        while let Some((next, _)) = queue.finish() {
+            started.push(next);
+        }
+        assert_eq!(
+            started,
+            vec!["task-0", "task-1", "task-2", "task-3", "task-4"]
+        );
+
+        // Both slots are released once the line is empty
+        queue.finish();
+        assert!(queue
+            .enqueue("task-6".to_string(), request("app"))
+            .unwrap()
+            .is_some());
+        assert!(queue
+            .enqueue("task-7".to_string(), request("app"))
+            .unwrap()
+            .is_some());
+    }
+
+    #[test]
+    fn test_removed_task_never_runs() {
+        let queue = DeployQueue::new(1, 2);
+        assert!(queue
+            .enqueue("running".to_string(), request("a"))
+            .unwrap()
+            .is_some());
+        assert!(queue
+            .enqueue("cancelled".to_string(), request("b"))
+            .unwrap()
+            .is_none());
+        assert!(queue
+            .enqueue("next".to_string(), request("c"))
+            .unwrap()
+            .is_none());
+
+        assert!(queue.remove("cancelled"));
+        assert!(!queue.remove("cancelled"));
+        assert!(!queue.remove("running"));
+        assert_eq!(queue.position("next"), Some(1));
+
+        let (next, request) = queue.finish().unwrap();
+        assert_eq!(next, "next");
+        assert_eq!(request.app_id, "c");
+        assert!(queue.finish().is_none());
+    }
+
+    #[test]
+    fn test_drain() {
+        let queue = DeployQueue::new(1, 3);
+        for id in ["running", "first", "second"] {
+            queue.enqueue(id.to_string(), request(id)).unwrap();
+        }
+
+        assert_eq!(queue.drain(), vec!["first", "second"]);
+        assert_eq!(queue.position("first"), None);
+        // The running deployment releases its slot with nothing to hand over
+        assert!(queue.finish().is_none());
+        assert!(queue.drain().is_empty());
+    }
+}
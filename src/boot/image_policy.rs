@@ -0,0 +1,574 @@
+use crate::config::ImagePolicyConfig;
+use crate::error::{ConfigError, DockerError, TappResult};
+use crate::proto::ImagePolicyStatus;
+use serde_yaml::Value;
+use std::fmt;
+use std::str::FromStr;
+
+/// Registry of image names without one
+const DEFAULT_REGISTRY: &str = "docker.io";
+/// Namespace of official images on the default registry (`nginx`)
+const OFFICIAL_NAMESPACE: &str = "library";
+/// Other names of the default registry
+const DEFAULT_REGISTRY_ALIASES: &[&str] = &["index.docker.io", "registry-1.docker.io"];
+
+/// Fully qualified image reference
+/// `nginx` is `docker.io/library/nginx` with no tag or digest given.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImageRef {
+    pub registry: String,
+    pub repository: String,
+    pub tag: Option<String>,
+    pub digest: Option<String>,
+}
+
+impl ImageRef {
+    /// `registry/repository`, e.g. `docker.io/library/nginx`
+    pub fn name(&self) -> String {
+        format!("{}/{}", self.registry, self.repository)
+    }
+
+    /// Tag Docker pulls: the given one, or `latest` when there is no digest
+    fn effective_tag(&self) -> Option<&str> {
+        match (&self.tag, &self.digest) {
+            (Some(tag), _) => Some(tag),
+            (None, None) => Some("latest"),
+            (None, Some(_)) => None,
+        }
+    }
+
+    /// Whether `image` is this list entry
+    /// An entry without a tag or digest matches every tag of its repository.
+    fn matches(&self, image: &ImageRef) -> bool {
+        if self.registry != image.registry || self.repository != image.repository {
+            return false;
+        }
+        let tag_matches = self
+            .tag
+            .as_deref()
+            .map_or(true, |tag| image.effective_tag() == Some(tag));
+        let digest_matches = self
+            .digest
+            .as_ref()
+            .map_or(true, |digest| image.digest.as_ref() == Some(digest));
+        tag_matches && digest_matches
+    }
+}
+
+impl fmt::Display for ImageRef {
+    /// Normalized form, with the tag Docker pulls
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())?;
+        if let Some(tag) = self.effective_tag() {
+            write!(f, ":{}", tag)?;
+        }
+        if let Some(digest) = &self.digest {
+            write!(f, "@{}", digest)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for ImageRef {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = |reason: &str| format!("'{}' is not an image reference: {}", s, reason);
+        if s.is_empty() {
+            return Err(invalid("empty"));
+        }
+        if s.contains('$') {
+            return Err(invalid("variables are not resolved"));
+        }
+
+        let (rest, digest) = match s.split_once('@') {
+            Some((rest, digest)) => (
+                rest,
+                Some(
+                    parse_digest(digest)
+                        .ok_or_else(|| invalid("digest must be <algorithm>:<hex>"))?,
+                ),
+            ),
+            None => (s, None),
+        };
+
+        // A ':' after the last '/' starts the tag; one before it is a port
+        let name_start = rest.rfind('/').map_or(0, |i| i + 1);
+        let (name, tag) = match rest[name_start..].find(':') {
+            Some(i) => (&rest[..name_start + i], Some(&rest[name_start + i + 1..])),
+            None => (rest, None),
+        };
+        if let Some(tag) = tag {
+            let valid = !tag.is_empty()
+                && tag.len() <= 128
+                && !tag.starts_with(['.', '-'])
+                && tag
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '.' | '-'));
+            if !valid {
+                return Err(invalid("invalid tag"));
+            }
+        }
+
+        let (registry, repository) = match name.split_once('/') {
+            Some((host, path)) if is_registry_host(host) => (normalize_registry(host), path),
+            _ => (DEFAULT_REGISTRY.to_string(), name),
+        };
+        let valid = repository.split('/').all(|component| {
+            !component.is_empty()
+                && component.chars().all(|c| {
+                    c.is_ascii_lowercase() || c.is_ascii_digit() || matches!(c, '_' | '.' | '-')
+                })
+        });
+        if !valid {
+            return Err(invalid("invalid repository name"));
+        }
+        let repository = if registry == DEFAULT_REGISTRY && !repository.contains('/') {
+            format!("{}/{}", OFFICIAL_NAMESPACE, repository)
+        } else {
+            repository.to_string()
+        };
+
+        Ok(Self {
+            registry,
+            repository,
+            tag: tag.map(str::to_string),
+            digest,
+        })
+    }
+}
+
+/// Whether the first component of a name is a registry rather than a
+/// namespace, as Docker decides it
+fn is_registry_host(component: &str) -> bool {
+    component.contains('.') || component.contains(':') || component == "localhost"
+}
+
+fn normalize_registry(host: &str) -> String {
+    let host = host.to_ascii_lowercase();
+    if DEFAULT_REGISTRY_ALIASES.contains(&host.as_str()) {
+        DEFAULT_REGISTRY.to_string()
+    } else {
+        host
+    }
+}
+
+/// `sha256:<hex>` style digest, lowercased
+fn parse_digest(digest: &str) -> Option<String> {
+    let (algorithm, encoded) = digest.split_once(':')?;
+    let valid = !algorithm.is_empty()
+        && algorithm
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '.' | '_' | '-'))
+        && encoded.len() >= 32
+        && encoded.chars().all(|c| c.is_ascii_hexdigit());
+    valid.then(|| digest.to_ascii_lowercase())
+}
+
+/// Images of a compose file's services, without duplicates
+/// Services that build their image are left out.
+pub fn compose_images(compose_content: &str) -> TappResult<Vec<ImageRef>> {
+    let compose: Value =
+        serde_yaml::from_str(compose_content).map_err(|e| DockerError::InvalidComposeContent {
+            reason: format!("YAML parse error: {}", e),
+        })?;
+    let mut images = Vec::new();
+    let services = compose.get("services").and_then(Value::as_mapping);
+    for (name, service) in services.into_iter().flatten() {
+        let Some(image) = service.get("image").and_then(Value::as_str) else {
+            continue;
+        };
+        if service.get("build").is_some() {
+            continue;
+        }
+        let image =
+            image
+                .parse::<ImageRef>()
+                .map_err(|reason| DockerError::InvalidComposeContent {
+                    reason: format!("{}: {}", name.as_str().unwrap_or_default(), reason),
+                })?;
+        if !images.contains(&image) {
+            images.push(image);
+        }
+    }
+    Ok(images)
+}
+
+/// Registry or repository prefix of `allowed_registries`, normalized
+/// (`Registry.0g.dev/team/*` is `registry.0g.dev/team`)
+fn parse_prefix(entry: &str) -> Result<String, String> {
+    let trimmed = entry.trim_end_matches("/*").trim_end_matches('/');
+    let (host, path) = match trimmed.split_once('/') {
+        Some((host, path)) => (host, Some(path)),
+        None => (trimmed, None),
+    };
+    if !is_registry_host(host)
+        || trimmed.contains(['@', '*'])
+        || trimmed.contains(char::is_whitespace)
+    {
+        return Err(format!(
+            "'{}' is not a registry such as registry.0g.dev or ghcr.io/0glabs",
+            entry
+        ));
+    }
+    let host = normalize_registry(host);
+    Ok(match path {
+        Some(path) => format!("{}/{}", host, path),
+        None => host,
+    })
+}
+
+/// Compiled `boot.image_policy`
+#[derive(Debug)]
+pub struct ImagePolicy {
+    registries: Vec<String>,
+    allow: Vec<ImageRef>,
+    deny: Vec<ImageRef>,
+}
+
+impl ImagePolicy {
+    pub fn new(config: &ImagePolicyConfig) -> Result<Self, ConfigError> {
+        let invalid = |field: &str| {
+            let field = field.to_string();
+            move |reason: String| ConfigError::InvalidValue { field, reason }
+        };
+        Ok(Self {
+            registries: config
+                .allowed_registries
+                .iter()
+                .map(|entry| parse_prefix(entry))
+                .collect::<Result<_, _>>()
+                .map_err(invalid("boot.image_policy.allowed_registries"))?,
+            allow: config
+                .allow
+                .iter()
+                .map(|entry| entry.parse::<ImageRef>())
+                .collect::<Result<_, _>>()
+                .map_err(invalid("boot.image_policy.allow"))?,
+            deny: config
+                .deny
+                .iter()
+                .map(|entry| entry.parse::<ImageRef>())
+                .collect::<Result<_, _>>()
+                .map_err(invalid("boot.image_policy.deny"))?,
+        })
+    }
+
+    /// The policy in force, or None if there is none
+    pub fn from_config(config: Option<&ImagePolicyConfig>) -> Result<Option<Self>, ConfigError> {
+        config.filter(|c| c.enabled).map(Self::new).transpose()
+    }
+
+    /// Why `image` may not be deployed, if it may not
+    /// Deny wins over allow; with no allowed registries or images, every
+    /// image not denied is allowed.
+    pub fn check(&self, image: &ImageRef) -> Result<(), &'static str> {
+        if self.deny.iter().any(|entry| entry.matches(image)) {
+            return Err("denied");
+        }
+        if self.registries.is_empty() && self.allow.is_empty() {
+            return Ok(());
+        }
+        let name = image.name();
+        let from_allowed_registry = self.registries.iter().any(|prefix| {
+            name.strip_prefix(prefix.as_str())
+                .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+        });
+        if from_allowed_registry || self.allow.iter().any(|entry| entry.matches(image)) {
+            Ok(())
+        } else {
+            Err("not from an allowed registry or in the allow list")
+        }
+    }
+
+    /// Check every image of a compose file, listing each one not allowed
+    /// Services that build their image are rejected: what they run can't
+    /// be checked.
+    pub fn check_compose(&self, compose_content: &str) -> TappResult<()> {
+        let compose: Value = serde_yaml::from_str(compose_content).map_err(|e| {
+            DockerError::InvalidComposeContent {
+                reason: format!("YAML parse error: {}", e),
+            }
+        })?;
+
+        let mut violations = Vec::new();
+        let services = compose.get("services").and_then(Value::as_mapping);
+        for (name, service) in services.into_iter().flatten() {
+            let name = name.as_str().unwrap_or_default();
+            let image = service.get("image").and_then(Value::as_str);
+            if service.get("build").is_some() {
+                violations.push(format!("{}: builds its image", name));
+            } else if let Some(image) = image {
+                match image.parse::<ImageRef>() {
+                    Ok(parsed) => {
+                        if let Err(reason) = self.check(&parsed) {
+                            violations.push(format!("{}: {} ({})", name, parsed, reason));
+                        }
+                    }
+                    Err(reason) => violations.push(format!("{}: {}", name, reason)),
+                }
+            }
+        }
+
+        if violations.is_empty() {
+            Ok(())
+        } else {
+            Err(DockerError::ImagesNotAllowed { violations }.into())
+        }
+    }
+
+    /// Policy as reported by GetServiceStatus
+    pub fn status(&self) -> ImagePolicyStatus {
+        ImagePolicyStatus {
+            enabled: true,
+            allowed_registries: self.registries.clone(),
+            allowed_images: self.allow.iter().map(list_entry).collect(),
+            denied_images: self.deny.iter().map(list_entry).collect(),
+        }
+    }
+}
+
+/// Normalized list entry, keeping "any tag" entries without a tag
+fn list_entry(entry: &ImageRef) -> String {
+    if entry.tag.is_none() && entry.digest.is_none() {
+        entry.name()
+    } else {
+        entry.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::TappError;
+
+    const DIGEST: &str = "sha256:0123456789abcdef0123456789abcdef0123456789abcdef0123456789abcdef";
+
+    fn image(s: &str) -> ImageRef {
+        s.parse().unwrap()
+    }
+
+    fn compile(registries: &[&str], allow: &[&str], deny: &[&str]) -> ImagePolicy {
+        let strings = |list: &[&str]| list.iter().map(|s| s.to_string()).collect();
+        ImagePolicy::new(&ImagePolicyConfig {
+            enabled: true,
+            allowed_registries: strings(registries),
+            allow: strings(allow),
+            deny: strings(deny),
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn test_normalization() {
+        for (reference, normalized) in [
+            ("nginx", "docker.io/library/nginx:latest"),
+            ("nginx:1.27", "docker.io/library/nginx:1.27"),
+            ("library/nginx", "docker.io/library/nginx:latest"),
+            ("docker.io/nginx", "docker.io/library/nginx:latest"),
+            (
+                "index.docker.io/library/nginx",
+                "docker.io/library/nginx:latest",
+            ),
+            ("bitnami/redis:7", "docker.io/bitnami/redis:7"),
+            ("ghcr.io/0glabs/tapp:v1", "ghcr.io/0glabs/tapp:v1"),
+            ("Registry.0G.dev/app", "registry.0g.dev/app:latest"),
+            // Port-qualified registries, with and without a tag
+            ("localhost:5000/app", "localhost:5000/app:latest"),
+            (
+                "registry.0g.dev:5000/team/app:2.0",
+                "registry.0g.dev:5000/team/app:2.0",
+            ),
+            ("localhost/app:1", "localhost/app:1"),
+        ] {
+            assert_eq!(image(reference).to_string(), normalized, "{}", reference);
+        }
+
+        // Digests, alone (no implicit tag) or with a tag
+        let by_digest = image(&format!("nginx@{}", DIGEST));
+        assert_eq!(
+            by_digest.to_string(),
+            format!("docker.io/library/nginx@{}", DIGEST)
+        );
+        assert_eq!(by_digest.tag, None);
+        let both = image(&format!("localhost:5000/app:1.0@{}", DIGEST));
+        assert_eq!(both.registry, "localhost:5000");
+        assert_eq!(both.tag.as_deref(), Some("1.0"));
+        assert_eq!(both.digest.as_deref(), Some(DIGEST));
+
+        for invalid in [
+            "",
+            "Nginx",
+            "nginx:",
+            "nginx@sha256:abc",
+            "ghcr.io//app",
+            "${IMAGE}",
+            "nginx:${TAG}",
+        ] {
+            assert!(invalid.parse::<ImageRef>().is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn test_registry_prefixes() {
+        let policy = compile(
+            &["registry.0g.dev/*", "ghcr.io/0glabs", "localhost:5000"],
+            &[],
+            &[],
+        );
+        for allowed in [
+            "registry.0g.dev/app",
+            "registry.0g.dev/team/app:1",
+            "ghcr.io/0glabs/tapp",
+            "localhost:5000/app:dev",
+        ] {
+            assert!(policy.check(&image(allowed)).is_ok(), "{}", allowed);
+        }
+        for rejected in [
+            "nginx",
+            // Prefixes end at a path boundary
+            "registry.0g.dev.evil.com/app",
+            "ghcr.io/0glabs-fork/tapp",
+            // A port is part of the registry
+            "localhost/app",
+            "localhost:5001/app",
+        ] {
+            assert!(policy.check(&image(rejected)).is_err(), "{}", rejected);
+        }
+
+        for invalid in ["nginx", "", "registry.0g.dev/*/app", "ghcr.io/app@sha256"] {
+            assert!(parse_prefix(invalid).is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn test_allow_and_deny_lists() {
+        let pinned = format!("redis@{}", DIGEST);
+        let policy = compile(
+            &["registry.0g.dev"],
+            &["nginx:1.27", "postgres", &pinned],
+            &["registry.0g.dev/legacy", "postgres:9"],
+        );
+
+        // Implicit docker.io/library names match their explicit forms
+        for allowed in [
+            "nginx:1.27",
+            "docker.io/library/nginx:1.27",
+            "postgres",
+            "postgres:16",
+            "redis@sha256:0123456789abcdef0123456789abcdef0123456789abcdef0123456789abcdef",
+            "redis:7@sha256:0123456789abcdef0123456789abcdef0123456789abcdef0123456789abcdef",
+        ] {
+            assert!(policy.check(&image(allowed)).is_ok(), "{}", allowed);
+        }
+        for rejected in [
+            // Other tags of a pinned image, and the implicit latest
+            "nginx",
+            "nginx:1.28",
+            "nginx@sha256:0123456789abcdef0123456789abcdef0123456789abcdef0123456789abcdef",
+            "redis:7",
+            // Deny wins over allowed registries and allowed images
+            "registry.0g.dev/legacy:3",
+            "postgres:9",
+            "ghcr.io/library/nginx:1.27",
+        ] {
+            assert!(policy.check(&image(rejected)).is_err(), "{}", rejected);
+        }
+
+        // Only a deny list: everything else is allowed
+        let policy = compile(&[], &[], &["busybox"]);
+        assert!(policy.check(&image("alpine")).is_ok());
+        assert_eq!(policy.check(&image("busybox:1.36")), Err("denied"));
+    }
+
+    #[test]
+    fn test_check_compose_lists_every_violation() {
+        let policy = compile(&["registry.0g.dev"], &["nginx:1.27"], &[]);
+        let compose = r#"
+services:
+  web:
+    image: nginx:1.27
+  api:
+    image: registry.0g.dev/api:2
+  cache:
+    image: redis
+  worker:
+    build: ./worker
+  tool:
+    image: "${TOOL_IMAGE}"
+"#;
+        match policy.check_compose(compose) {
+            Err(TappError::Docker(DockerError::ImagesNotAllowed { violations })) => assert_eq!(
+                violations,
+                vec![
+                    "cache: docker.io/library/redis:latest (not from an allowed registry or in the allow list)",
+                    "worker: builds its image",
+                    "tool: '${TOOL_IMAGE}' is not an image reference: variables are not resolved",
+                ]
+            ),
+            other => panic!("unexpected result: {:?}", other),
+        }
+
+        assert!(policy
+            .check_compose("services:\n  web:\n    image: registry.0g.dev/web\n")
+            .is_ok());
+        assert!(policy.check_compose("services: [").is_err());
+    }
+
+    #[test]
+    fn test_policy_config() {
+        let mut config = ImagePolicyConfig {
+            enabled: false,
+            allowed_registries: vec!["registry.0g.dev".to_string()],
+            allow: vec![],
+            deny: vec!["Not An Image".to_string()],
+        };
+        assert!(ImagePolicy::from_config(Some(&config)).unwrap().is_none());
+
+        config.enabled = true;
+        match ImagePolicy::from_config(Some(&config)) {
+            Err(ConfigError::InvalidValue { field, .. }) => {
+                assert_eq!(field, "boot.image_policy.deny")
+            }
+            other => panic!("unexpected result: {:?}", other),
+        }
+
+        config.deny = vec!["docker.io/busybox".to_string()];
+        let status = ImagePolicy::from_config(Some(&config))
+            .unwrap()
+            .unwrap()
+            .status();
+        assert!(status.enabled);
+        assert_eq!(status.allowed_registries, vec!["registry.0g.dev"]);
+        assert_eq!(status.denied_images, vec!["docker.io/library/busybox"]);
+    }
+
+    #[test]
+    fn test_compose_images() {
+        let compose = r#"
+services:
+  web:
+    image: nginx:1.27
+  worker:
+    image: docker.io/library/nginx:1.27
+  app:
+    build: .
+    image: my-app
+  cache:
+    image: redis@sha256:0123456789abcdef0123456789abcdef0123456789abcdef0123456789abcdef
+"#;
+        let images: Vec<String> = compose_images(compose)
+            .unwrap()
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        assert_eq!(
+            images,
+            [
+                "docker.io/library/nginx:1.27",
+                "docker.io/library/redis@sha256:0123456789abcdef0123456789abcdef0123456789abcdef0123456789abcdef",
+            ]
+        );
+        assert!(compose_images("services:\n  web:\n    image: ${IMAGE}\n").is_err());
+    }
+}
@@ -0,0 +1,515 @@
+//! Vulnerability scan of deployment images
+//!
+//! With `boot.image_scan` enabled, every image of a deployment is scanned
+//! after the pull and before `up`, by digest, so the scan covers what is
+//! about to run. The scanner is the configured program, run without a shell
+//! and with `{image}` replaced in its arguments, or the configured endpoint.
+//! Nothing from the compose file reaches it but the image references, which
+//! are checked to be plain references first.
+//!
+//! Trivy reports (`Results[].Vulnerabilities[].Severity`) and grype reports
+//! (`matches[].vulnerability.severity`) are counted by severity. An image
+//! passes with no finding at or above `severity_threshold`; findings of
+//! unknown severity are reported but never fail it. A scanner that fails,
+//! times out or answers something else fails the deployment, unless
+//! `fail_open` is set.
+
+use crate::config::{ImageScanConfig, ScanSeverity};
+use crate::error::{ConfigError, TappError, TappResult};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::fmt;
+use std::process::Stdio;
+use std::time::Duration;
+use tokio::process::Command;
+use tokio_util::sync::CancellationToken;
+use tracing::{info, warn};
+
+/// Placeholder of the image in the scanner arguments
+pub const IMAGE_PLACEHOLDER: &str = "{image}";
+
+/// Longest image reference handed to the scanner
+const MAX_IMAGE_LEN: usize = 512;
+
+/// Scanner output kept in an error message
+const MAX_ERROR_LEN: usize = 200;
+
+/// Findings of one image, by severity
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SeverityCounts {
+    pub critical: u32,
+    pub high: u32,
+    pub medium: u32,
+    pub low: u32,
+    pub unknown: u32,
+}
+
+impl SeverityCounts {
+    /// Findings at or above `threshold`
+    pub fn at_least(&self, threshold: ScanSeverity) -> u32 {
+        [
+            (ScanSeverity::Critical, self.critical),
+            (ScanSeverity::High, self.high),
+            (ScanSeverity::Medium, self.medium),
+            (ScanSeverity::Low, self.low),
+        ]
+        .iter()
+        .filter(|(severity, _)| *severity >= threshold)
+        .map(|(_, count)| count)
+        .sum()
+    }
+
+    fn add(&mut self, severity: &str) {
+        match severity.to_ascii_lowercase().as_str() {
+            "critical" => self.critical += 1,
+            "high" => self.high += 1,
+            "medium" => self.medium += 1,
+            "low" | "negligible" => self.low += 1,
+            _ => self.unknown += 1,
+        }
+    }
+}
+
+impl fmt::Display for SeverityCounts {
+    /// Non-zero counts, e.g. `2 critical, 1 high`
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let counts: Vec<String> = [
+            (self.critical, "critical"),
+            (self.high, "high"),
+            (self.medium, "medium"),
+            (self.low, "low"),
+            (self.unknown, "unknown"),
+        ]
+        .iter()
+        .filter(|(count, _)| *count > 0)
+        .map(|(count, severity)| format!("{} {}", count, severity))
+        .collect();
+        if counts.is_empty() {
+            write!(f, "no findings")
+        } else {
+            write!(f, "{}", counts.join(", "))
+        }
+    }
+}
+
+/// Outcome of the scan of one image, as recorded on the task
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ImageScanResult {
+    /// `<repository>@<digest>`, or the image ID
+    pub image: String,
+    #[serde(flatten)]
+    pub counts: SeverityCounts,
+    /// Why the image could not be scanned
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// Whether the image may be deployed: no finding at or above the
+    /// threshold, or a scanner failure with `fail_open`
+    pub passed: bool,
+}
+
+impl ImageScanResult {
+    pub fn to_proto(&self) -> crate::proto::ImageScanResult {
+        let count = |count: u32| count.try_into().unwrap_or(i32::MAX);
+        crate::proto::ImageScanResult {
+            image: self.image.clone(),
+            critical: count(self.counts.critical),
+            high: count(self.counts.high),
+            medium: count(self.counts.medium),
+            low: count(self.counts.low),
+            unknown: count(self.counts.unknown),
+            passed: self.passed,
+            error: self.error.clone().unwrap_or_default(),
+        }
+    }
+}
+
+/// Why each image of `results` may not be deployed, e.g.
+/// `docker.io/library/nginx@sha256:…: 2 critical, 1 high (threshold high)`
+pub fn failures(results: &[ImageScanResult], threshold: ScanSeverity) -> Vec<String> {
+    results
+        .iter()
+        .filter(|result| !result.passed)
+        .map(|result| match &result.error {
+            Some(error) => format!("{}: {}", result.image, error),
+            None => format!(
+                "{}: {} (threshold {})",
+                result.image,
+                result.counts,
+                threshold.as_str()
+            ),
+        })
+        .collect()
+}
+
+/// The scanner of `boot.image_scan`
+pub struct ImageScanner {
+    config: ImageScanConfig,
+    client: reqwest::Client,
+}
+
+impl ImageScanner {
+    /// Scanner of an enabled scan section (None: images are not scanned)
+    pub fn from_config(config: Option<&ImageScanConfig>) -> TappResult<Option<Self>> {
+        let Some(config) = config.filter(|config| config.enabled) else {
+            return Ok(None);
+        };
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(config.timeout_seconds))
+            .build()
+            .map_err(|e| ConfigError::InvalidValue {
+                field: "boot.image_scan.endpoint".to_string(),
+                reason: format!("HTTP client setup failed: {}", e),
+            })?;
+        Ok(Some(Self {
+            config: config.clone(),
+            client,
+        }))
+    }
+
+    /// Lowest severity that fails a deployment
+    pub fn threshold(&self) -> ScanSeverity {
+        self.config.severity_threshold
+    }
+
+    /// Scan `images` one after the other
+    /// Returns `TappError::Cancelled` once `cancel` is cancelled; the
+    /// running scanner is killed.
+    pub async fn scan_all(
+        &self,
+        images: &[String],
+        cancel: &CancellationToken,
+    ) -> TappResult<Vec<ImageScanResult>> {
+        let mut results = Vec::with_capacity(images.len());
+        for image in images {
+            let scanned = tokio::select! {
+                scanned = self.scan(image) => scanned,
+                _ = cancel.cancelled() => return Err(TappError::Cancelled),
+            };
+            let result = match scanned {
+                Ok(counts) => ImageScanResult {
+                    image: image.clone(),
+                    counts,
+                    error: None,
+                    passed: counts.at_least(self.threshold()) == 0,
+                },
+                Err(error) => {
+                    warn!(
+                        image = %image,
+                        error = %error,
+                        fail_open = self.config.fail_open,
+                        "Image scan failed"
+                    );
+                    ImageScanResult {
+                        image: image.clone(),
+                        counts: SeverityCounts::default(),
+                        error: Some(error),
+                        passed: self.config.fail_open,
+                    }
+                }
+            };
+            info!(
+                image = %image,
+                findings = %result.counts,
+                passed = result.passed,
+                "Image scanned"
+            );
+            results.push(result);
+        }
+        Ok(results)
+    }
+
+    /// Findings of one image, or why it could not be scanned
+    async fn scan(&self, image: &str) -> Result<SeverityCounts, String> {
+        check_image(image)?;
+        let report = match &self.config.endpoint {
+            Some(endpoint) => self.post(endpoint, image).await?,
+            None => self.run_command(image).await?,
+        };
+        parse_report(&report)
+    }
+
+    /// Standard output of the scanner program
+    async fn run_command(&self, image: &str) -> Result<Vec<u8>, String> {
+        let mut args = self
+            .config
+            .command
+            .iter()
+            .map(|arg| arg.replace(IMAGE_PLACEHOLDER, image));
+        let program = args.next().ok_or("no scanner command")?;
+        let mut command = Command::new(&program);
+        command
+            .args(args)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true);
+
+        let timeout = Duration::from_secs(self.config.timeout_seconds);
+        let output = tokio::time::timeout(timeout, command.output())
+            .await
+            .map_err(|_| format!("scanner timed out after {}s", timeout.as_secs()))?
+            .map_err(|e| format!("cannot run {}: {}", program, e))?;
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(format!(
+                "scanner exited with {}: {}",
+                output.status,
+                truncate(stderr.trim())
+            ));
+        }
+        Ok(output.stdout)
+    }
+
+    /// Answer of the HTTP scanner
+    async fn post(&self, endpoint: &str, image: &str) -> Result<Vec<u8>, String> {
+        let response = self
+            .client
+            .post(endpoint)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .body(json!({ "image": image }).to_string())
+            .send()
+            .await
+            .map_err(|e| format!("scanner unreachable: {}", e))?;
+        let status = response.status();
+        if !status.is_success() {
+            return Err(format!("scanner answered HTTP {}", status));
+        }
+        let body = response
+            .bytes()
+            .await
+            .map_err(|e| format!("scanner unreachable: {}", e))?;
+        Ok(body.to_vec())
+    }
+}
+
+/// Refuse anything but a plain image reference, so that the scanner never
+/// gets an option or a path in its place
+fn check_image(image: &str) -> Result<(), String> {
+    let valid = !image.is_empty()
+        && image.len() <= MAX_IMAGE_LEN
+        && image.starts_with(|c: char| c.is_ascii_alphanumeric())
+        && image
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '/' | ':' | '@' | '.' | '_' | '-'))
+        && !image.contains("..");
+    if valid {
+        Ok(())
+    } else {
+        Err(format!("'{}' is not an image reference", truncate(image)))
+    }
+}
+
+/// Findings of a trivy or grype JSON report
+fn parse_report(report: &[u8]) -> Result<SeverityCounts, String> {
+    let report: Value =
+        serde_json::from_slice(report).map_err(|e| format!("scanner report is not JSON: {}", e))?;
+    let mut counts = SeverityCounts::default();
+    let severities: Vec<&Value> =
+        if report.get("SchemaVersion").is_some() || report.get("Results").is_some() {
+            // trivy; a clean image may have no Results or no Vulnerabilities
+            report["Results"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .flat_map(|result| result["Vulnerabilities"].as_array().into_iter().flatten())
+                .map(|vulnerability| &vulnerability["Severity"])
+                .collect()
+        } else if let Some(matches) = report.get("matches").and_then(Value::as_array) {
+            matches
+                .iter()
+                .map(|found| &found["vulnerability"]["severity"])
+                .collect()
+        } else {
+            return Err("scanner report is neither a trivy nor a grype JSON report".to_string());
+        };
+    for severity in severities {
+        counts.add(severity.as_str().unwrap_or_default());
+    }
+    Ok(counts)
+}
+
+fn truncate(text: &str) -> String {
+    match text.char_indices().nth(MAX_ERROR_LEN) {
+        Some((end, _)) => format!("{}...", &text[..end]),
+        None => text.to_string(),
+    }
+}
+
+/// Scan section running a stub scanner written to `dir`, for tests
+/// Images whose reference contains `vulnerable` get a trivy report with
+/// 1 critical, 2 high and 1 low finding, `grype` a grype report with one
+/// medium finding, `broken` a scanner error and `slow` no answer in time;
+/// any other image a clean trivy report.
+#[cfg(test)]
+pub(crate) fn stub_scanner_config(dir: &std::path::Path) -> ImageScanConfig {
+    const STUB: &str = r#"case "$1" in
+  *vulnerable*)
+    echo '{"SchemaVersion": 2, "Results": [{"Target": "os", "Vulnerabilities": ['
+    echo '{"VulnerabilityID": "CVE-1", "Severity": "CRITICAL"},'
+    echo '{"VulnerabilityID": "CVE-2", "Severity": "HIGH"},'
+    echo '{"VulnerabilityID": "CVE-3", "Severity": "HIGH"}]},'
+    echo '{"Target": "app", "Vulnerabilities": [{"VulnerabilityID": "CVE-4", "Severity": "LOW"}]}]}'
+    ;;
+  *grype*)
+    echo '{"matches": [{"vulnerability": {"id": "CVE-5", "severity": "Medium"}}]}'
+    ;;
+  *broken*)
+    echo "cannot read image" >&2
+    exit 2
+    ;;
+  *slow*)
+    sleep 30
+    ;;
+  *)
+    echo '{"SchemaVersion": 2, "Results": [{"Target": "os"}]}'
+    ;;
+esac
+"#;
+    let script = dir.join("scanner.sh");
+    std::fs::write(&script, STUB).unwrap();
+    // Run through sh, so the script needs no exec bit
+    ImageScanConfig {
+        enabled: true,
+        command: vec![
+            "/bin/sh".to_string(),
+            script.to_string_lossy().to_string(),
+            IMAGE_PLACEHOLDER.to_string(),
+        ],
+        endpoint: None,
+        severity_threshold: ScanSeverity::High,
+        timeout_seconds: 10,
+        fail_open: false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DIGEST: &str = "sha256:0123456789abcdef0123456789abcdef0123456789abcdef0123456789abcdef";
+
+    fn image(name: &str) -> String {
+        format!("registry.example.com/{}@{}", name, DIGEST)
+    }
+
+    #[test]
+    fn test_parse_report() {
+        let counts = parse_report(br#"{"SchemaVersion": 2}"#).unwrap();
+        assert_eq!(counts, SeverityCounts::default());
+        assert_eq!(counts.to_string(), "no findings");
+
+        let trivy = br#"{"SchemaVersion": 2, "Results": [
+            {"Target": "os", "Vulnerabilities": [{"Severity": "HIGH"}, {"Severity": "UNKNOWN"}]},
+            {"Target": "go", "Vulnerabilities": null}
+        ]}"#;
+        let counts = parse_report(trivy).unwrap();
+        assert_eq!(counts.to_string(), "1 high, 1 unknown");
+        assert_eq!(counts.at_least(ScanSeverity::High), 1);
+        assert_eq!(counts.at_least(ScanSeverity::Critical), 0);
+
+        let grype = br#"{"matches": [
+            {"vulnerability": {"severity": "Critical"}},
+            {"vulnerability": {"severity": "Negligible"}}
+        ]}"#;
+        let counts = parse_report(grype).unwrap();
+        assert_eq!(counts.to_string(), "1 critical, 1 low");
+        assert_eq!(counts.at_least(ScanSeverity::Low), 2);
+
+        assert!(parse_report(b"not json").is_err());
+        assert!(parse_report(br#"{"vulnerabilities": []}"#).is_err());
+    }
+
+    #[test]
+    fn test_check_image() {
+        assert!(check_image(&image("app")).is_ok());
+        assert!(check_image("sha256:0123abcd").is_ok());
+        for invalid in [
+            "",
+            "--config=/etc/shadow",
+            "/etc/passwd",
+            "app; rm -rf /",
+            "app $(id)",
+            "registry/../app",
+        ] {
+            assert!(check_image(invalid).is_err(), "{}", invalid);
+        }
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_scan_with_stub_scanner() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = stub_scanner_config(dir.path());
+        let scanner = ImageScanner::from_config(Some(&config)).unwrap().unwrap();
+        let cancel = CancellationToken::new();
+
+        let images = [image("clean"), image("vulnerable"), image("grype")];
+        let results = scanner.scan_all(&images, &cancel).await.unwrap();
+        assert!(results[0].passed);
+        assert_eq!(results[0].counts, SeverityCounts::default());
+        assert!(!results[1].passed);
+        assert_eq!(
+            results[1].counts,
+            SeverityCounts {
+                critical: 1,
+                high: 2,
+                low: 1,
+                ..Default::default()
+            }
+        );
+        // Medium is below the threshold
+        assert!(results[2].passed);
+        assert_eq!(results[2].counts.medium, 1);
+        assert_eq!(
+            failures(&results, scanner.threshold()),
+            [format!(
+                "{}: 1 critical, 2 high, 1 low (threshold high)",
+                image("vulnerable")
+            )]
+        );
+
+        // Scanner errors fail closed, or open when configured
+        let results = scanner.scan_all(&[image("broken")], &cancel).await.unwrap();
+        assert!(!results[0].passed);
+        let error = results[0].error.as_deref().unwrap();
+        assert!(error.contains("cannot read image"), "{}", error);
+
+        let scanner = ImageScanner::from_config(Some(&ImageScanConfig {
+            fail_open: true,
+            timeout_seconds: 1,
+            ..config.clone()
+        }))
+        .unwrap()
+        .unwrap();
+        let results = scanner.scan_all(&[image("slow")], &cancel).await.unwrap();
+        assert!(results[0].passed);
+        assert!(results[0].error.as_deref().unwrap().contains("timed out"));
+        assert!(failures(&results, scanner.threshold()).is_empty());
+
+        // Nothing but a plain reference reaches the scanner
+        let results = scanner
+            .scan_all(&["-x".to_string()], &cancel)
+            .await
+            .unwrap();
+        assert!(results[0]
+            .error
+            .as_deref()
+            .unwrap()
+            .contains("not an image reference"));
+
+        cancel.cancel();
+        assert!(matches!(
+            scanner.scan_all(&[image("slow")], &cancel).await,
+            Err(TappError::Cancelled)
+        ));
+
+        let disabled = ImageScanConfig {
+            enabled: false,
+            ..config
+        };
+        assert!(ImageScanner::from_config(Some(&disabled))
+            .unwrap()
+            .is_none());
+        assert!(ImageScanner::from_config(None).unwrap().is_none());
+    }
+}
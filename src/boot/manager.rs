@@ -1,15 +1,111 @@
-use crate::error::{DockerError, TappError, TappResult};
+use super::deploy_log;
+use super::image_policy::{compose_images, ImageRef};
+use crate::config::{BootServiceConfig, ContainerRuntime, RetryConfig};
+use crate::container_identity::{COMPOSE_PROJECT_LABEL, PODMAN_COMPOSE_PROJECT_LABEL};
+use crate::error::{DockerError, FailureCause, TappError, TappResult};
 use bollard::container::{ListContainersOptions, StopContainerOptions};
 use bollard::models::ContainerInspectResponse;
-use bollard::Docker;
+use bollard::{Docker, API_DEFAULT_VERSION};
+use rand::Rng;
 use std::collections::HashMap;
-use std::path::PathBuf;
-use std::process::Stdio;
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::process::{Output, Stdio};
+use std::time::Duration;
 use tokio::fs;
-use tokio::io::AsyncWriteExt;
-use tokio::process::Command;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWriteExt};
+use tokio::process::{Child, Command};
+use tokio_util::sync::CancellationToken;
 use tracing::{error, info, warn};
 
+/// Timeout of requests to the Docker daemon (bollard's default)
+const DOCKER_TIMEOUT_SECONDS: u64 = 120;
+
+/// Interval between container health polls after `docker compose up`
+const HEALTH_POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// docker compose options podman-compose does without: `ps` lists stopped
+/// containers anyway, and `pull` skips the services it builds
+const PODMAN_COMPOSE_UNSUPPORTED: &[&str] = &["--all", "--ignore-pull-failures"];
+
+/// Run `call` until it succeeds, fails with an error `retryable` rejects,
+/// or `policy.max_retries` retries are used up
+/// Only for operations that are safe to repeat. The delay starts at
+/// `initial_delay_ms` and doubles up to `max_delay_ms`, with jitter so that
+/// concurrent deployments don't retry in lockstep. Cancelling `cancel`
+/// during a delay returns `TappError::Cancelled`.
+pub async fn retry_transient<T, F, Fut>(
+    policy: &RetryConfig,
+    operation: &str,
+    retryable: impl Fn(&TappError) -> bool,
+    cancel: Option<&CancellationToken>,
+    mut call: F,
+) -> TappResult<T>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = TappResult<T>>,
+{
+    let mut attempt = 0;
+    loop {
+        match call().await {
+            Err(e) if attempt < policy.max_retries && retryable(&e) => {
+                let delay = retry_delay(policy, attempt);
+                attempt += 1;
+                warn!(
+                    operation = %operation,
+                    attempt = attempt,
+                    max_retries = policy.max_retries,
+                    delay_ms = delay.as_millis() as u64,
+                    error = %e,
+                    "Transient failure, retrying"
+                );
+                match cancel {
+                    Some(cancel) => tokio::select! {
+                        _ = tokio::time::sleep(delay) => {}
+                        _ = cancel.cancelled() => return Err(TappError::Cancelled),
+                    },
+                    None => tokio::time::sleep(delay).await,
+                }
+            }
+            result => return result,
+        }
+    }
+}
+
+/// Delay before retry number `attempt` (from 0): exponential backoff with
+/// up to half of it taken off at random
+fn retry_delay(policy: &RetryConfig, attempt: usize) -> Duration {
+    let backoff = policy
+        .initial_delay_ms
+        .saturating_mul(1u64 << attempt.min(20))
+        .min(policy.max_delay_ms);
+    let jitter = rand::thread_rng().gen_range(0..=backoff / 2);
+    Duration::from_millis(backoff - jitter)
+}
+
+/// Daemon failures that may go away on their own: the daemon is unreachable
+/// (restarting, socket busy) or answered with a server error
+pub fn is_transient_daemon_error(err: &TappError) -> bool {
+    matches!(
+        err,
+        TappError::Docker(DockerError::ConnectionFailed { .. })
+            | TappError::Docker(DockerError::DaemonError {
+                status_code: 500..,
+                ..
+            })
+    )
+}
+
+/// Client of the Docker API at `socket`: a unix socket path (optionally
+/// `unix://`) or an HTTP address; podman serves the same API
+pub fn docker_client(socket: &str) -> Result<Docker, bollard::errors::Error> {
+    if socket.starts_with("unix://") || socket.starts_with('/') {
+        Docker::connect_with_socket(socket, DOCKER_TIMEOUT_SECONDS, API_DEFAULT_VERSION)
+    } else {
+        Docker::connect_with_http(socket, DOCKER_TIMEOUT_SECONDS, API_DEFAULT_VERSION)
+    }
+}
+
 /// Application status
 #[derive(Debug, Clone)]
 pub struct AppStatus {
@@ -17,7 +113,10 @@ pub struct AppStatus {
     pub running: bool,
     pub container_count: usize,
     pub containers: Vec<ContainerStatus>,
+    /// Earliest start (unix seconds) of the running containers
     pub started_at: Option<i64>,
+    /// Restarts Docker made under the containers' restart policies
+    pub restart_count: i64,
 }
 
 /// Container status
@@ -35,14 +134,101 @@ pub struct MountFile {
     pub source_path: String, // Source path from compose file (e.g., ./nginx.conf)
     pub content: Vec<u8>,
     pub mode: String,
+    /// `content` is an envelope sealed to the app key
+    pub encrypted: bool,
+}
+
+/// Compose CLI run in the app directories (`boot.compose_command`)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ComposeCli {
+    program: String,
+    /// Arguments before the compose options, e.g. `compose`
+    leading_args: Vec<String>,
+}
+
+impl ComposeCli {
+    /// `command` split at whitespace, or the runtime's default CLI
+    pub fn new(runtime: ContainerRuntime, command: Option<&str>) -> Self {
+        let mut words = command
+            .unwrap_or(runtime.default_compose_command())
+            .split_whitespace()
+            .map(str::to_string);
+        Self {
+            program: words.next().unwrap_or_default(),
+            leading_args: words.collect(),
+        }
+    }
+
+    /// The standalone podman-compose, whose options and labels differ
+    fn is_podman_compose(&self) -> bool {
+        Path::new(&self.program)
+            .file_name()
+            .is_some_and(|name| name == "podman-compose")
+    }
+
+    /// Label holding the compose project of a container
+    /// podman-compose has its own, and sets the docker compose one only
+    /// since 1.0.
+    pub fn project_label(&self) -> &'static str {
+        if self.is_podman_compose() {
+            PODMAN_COMPOSE_PROJECT_LABEL
+        } else {
+            COMPOSE_PROJECT_LABEL
+        }
+    }
+
+    /// Command running compose with `args` in `dir`, leaving out the options
+    /// podman-compose lacks
+    fn command(&self, dir: &Path, args: &[&str]) -> Command {
+        let podman_compose = self.is_podman_compose();
+        let mut command = Command::new(&self.program);
+        command
+            .args(&self.leading_args)
+            .args(
+                args.iter()
+                    .copied()
+                    .filter(|arg| !(podman_compose && PODMAN_COMPOSE_UNSUPPORTED.contains(arg))),
+            )
+            .current_dir(dir);
+        command
+    }
+}
+
+impl std::fmt::Display for ComposeCli {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.program)?;
+        for arg in &self.leading_args {
+            write!(f, " {}", arg)?;
+        }
+        Ok(())
+    }
 }
 
 /// Docker Compose manager for container lifecycle
+/// Also runs apps on podman, through its Docker-compatible API and a
+/// podman compose CLI.
 pub struct DockerComposeManager {
     docker: Docker,
+    /// Socket path or address the client connects to
+    endpoint: String,
+    runtime: ContainerRuntime,
+    compose: ComposeCli,
+    /// Retries of transient daemon failures
+    retry: RetryConfig,
+    // Holds one directory per app (compose file, mount files, deploy logs)
+    data_dir: PathBuf,
     app_containers: HashMap<String, Vec<String>>, // app_id -> container_names
 }
 
+/// State of an app's containers while waiting for them to come up
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum ComposeHealth {
+    Ready,
+    Waiting,
+    /// Names of containers whose health check failed
+    Unhealthy(Vec<String>),
+}
+
 /// Deployment result
 #[derive(Debug, Clone)]
 pub struct DeploymentResult {
@@ -52,42 +238,278 @@ pub struct DeploymentResult {
     pub started_at: i64,
 }
 
+/// Docker daemon facts reported by GetHostResources
+#[derive(Debug, Clone, Default)]
+pub struct DockerHostInfo {
+    pub root_dir: Option<PathBuf>,
+    pub running_containers: i64,
+    /// OS type and architecture of the host, as the daemon names them
+    /// (e.g. `linux`, `x86_64`)
+    pub os_type: Option<String>,
+    pub architecture: Option<String>,
+}
+
 impl DockerComposeManager {
     /// Get the directory path for an app
-    pub fn get_app_dir(app_id: &str) -> PathBuf {
-        PathBuf::from(format!("/var/lib/tapp/apps/{}", app_id))
+    /// Namespaced apps live in `namespace__app_id` (see `crate::namespace`)
+    pub fn get_app_dir(&self, app_id: &str) -> PathBuf {
+        self.data_dir
+            .join(crate::namespace::storage_name(app_id).as_ref())
     }
 
-    /// Create new Docker Compose manager
-    pub async fn new(docker_socket: &str) -> TappResult<Self> {
-        let docker = if docker_socket.starts_with("unix://") || docker_socket.starts_with("/") {
-            Docker::connect_with_socket_defaults().map_err(|_e| DockerError::ConnectionFailed)?
-        } else {
-            Docker::connect_with_http_defaults().map_err(|_e| DockerError::ConnectionFailed)?
-        };
+    /// Directory holding the app directories
+    pub fn data_dir(&self) -> &Path {
+        &self.data_dir
+    }
 
-        // Test connection
-        docker
-            .ping()
-            .await
-            .map_err(|_| DockerError::ConnectionFailed)?;
+    /// Container engine the apps run on
+    pub fn runtime(&self) -> ContainerRuntime {
+        self.runtime
+    }
+
+    /// Create the data directory if missing, readable by its owner only
+    /// since mount files may hold secrets; an existing one is left as is
+    pub fn create_data_dir(data_dir: &Path) -> TappResult<()> {
+        let mut builder = std::fs::DirBuilder::new();
+        builder.recursive(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::DirBuilderExt;
+            builder.mode(0o700);
+        }
+        builder.create(data_dir).map_err(|e| {
+            DockerError::ContainerOperationFailed {
+                operation: "create_data_dir".to_string(),
+                reason: format!("Failed to create {}: {}", data_dir.display(), e),
+                cause: FailureCause::Internal,
+            }
+            .into()
+        })
+    }
+
+    /// Compose project name of an app
+    /// `docker compose up` runs in the app directory without `-p`, so the
+    /// project is the directory name normalized the way compose does it.
+    pub fn compose_project_name(app_id: &str) -> String {
+        crate::namespace::storage_name(app_id)
+            .to_lowercase()
+            .chars()
+            .filter(|c| c.is_ascii_alphanumeric() || *c == '-' || *c == '_')
+            .collect::<String>()
+            .trim_start_matches(['-', '_'])
+            .to_string()
+    }
 
-        info!("Connected to Docker daemon");
+    /// Create new Docker Compose manager keeping app files under
+    /// `boot.data_dir`
+    /// The runtime socket (see `BootServiceConfig::runtime_socket`) is a unix
+    /// socket path (optionally `unix://`) or an HTTP address of the daemon.
+    /// Transient daemon failures are retried as `boot.retry` says.
+    pub async fn new(config: &BootServiceConfig) -> TappResult<Self> {
+        let docker_socket = config.runtime_socket();
+        let docker_socket = docker_socket.as_str();
+        let data_dir = config.data_dir.as_path();
+        Self::create_data_dir(data_dir)?;
 
-        Ok(Self {
+        let docker = docker_client(docker_socket)
+            .map_err(|e| DockerError::from(e).at_endpoint(docker_socket))?;
+
+        let manager = Self {
             docker,
+            endpoint: docker_socket.to_string(),
+            runtime: config.runtime,
+            compose: ComposeCli::new(config.runtime, config.compose_command.as_deref()),
+            retry: config.retry.clone(),
+            data_dir: data_dir.to_path_buf(),
             app_containers: HashMap::new(),
+        };
+        // Test connection
+        manager.ping().await?;
+
+        info!(
+            endpoint = %docker_socket,
+            runtime = manager.runtime.as_str(),
+            compose = %manager.compose,
+            "Connected to container runtime"
+        );
+
+        Ok(manager)
+    }
+
+    /// Check that the Docker daemon answers
+    pub async fn ping(&self) -> TappResult<()> {
+        self.with_retry("ping", || async {
+            self.docker.ping().await.map_err(|e| self.daemon_error(e))?;
+            Ok(())
         })
+        .await
+    }
+
+    /// Run an idempotent daemon call, retrying transient failures
+    async fn with_retry<T, F, Fut>(&self, operation: &str, call: F) -> TappResult<T>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = TappResult<T>>,
+    {
+        retry_transient(
+            &self.retry,
+            operation,
+            is_transient_daemon_error,
+            None,
+            call,
+        )
+        .await
+    }
+
+    /// Error of a daemon call, naming the endpoint if it could not be reached
+    fn daemon_error(&self, err: bollard::errors::Error) -> TappError {
+        DockerError::from(err).at_endpoint(&self.endpoint).into()
+    }
+
+    /// Docker data root and number of running containers
+    pub async fn host_info(&self) -> TappResult<DockerHostInfo> {
+        let info = self
+            .with_retry("info", || async {
+                self.docker.info().await.map_err(|e| self.daemon_error(e))
+            })
+            .await?;
+        Ok(DockerHostInfo {
+            root_dir: info.docker_root_dir.map(PathBuf::from),
+            running_containers: info.containers_running.unwrap_or(0),
+            os_type: info.os_type,
+            architecture: info.architecture,
+        })
+    }
+
+    /// Status of an app's containers from the Docker API
+    /// Containers of the app's compose project are inspected one by one; the
+    /// earliest start of the running ones is when the app last came up.
+    pub async fn get_compose_status(&self, app_id: &str) -> TappResult<AppStatus> {
+        let project = Self::compose_project_name(app_id);
+        let filters = HashMap::from([(
+            "label".to_string(),
+            vec![format!("{}={}", self.compose.project_label(), project)],
+        )]);
+        let summaries = self
+            .with_retry("list_containers", || async {
+                let options = ListContainersOptions::<String> {
+                    all: true,
+                    filters: filters.clone(),
+                    ..Default::default()
+                };
+                self.docker
+                    .list_containers(Some(options))
+                    .await
+                    .map_err(|e| self.daemon_error(e))
+            })
+            .await?;
+
+        let mut status = AppStatus {
+            app_id: app_id.to_string(),
+            running: false,
+            container_count: 0,
+            containers: Vec::new(),
+            started_at: None,
+            restart_count: 0,
+        };
+        for summary in summaries {
+            let Some(id) = summary.id else {
+                continue;
+            };
+            let inspect: ContainerInspectResponse = self
+                .with_retry("inspect_container", || async {
+                    self.docker
+                        .inspect_container(&id, None)
+                        .await
+                        .map_err(|e| self.daemon_error(e))
+                })
+                .await?;
+            let state = inspect.state.unwrap_or_default();
+
+            status.restart_count += inspect.restart_count.unwrap_or(0);
+            if state.running == Some(true) {
+                status.running = true;
+                if let Some(started) = state.started_at.as_deref().and_then(parse_docker_time) {
+                    status.started_at = Some(status.started_at.map_or(started, |s| s.min(started)));
+                }
+            }
+
+            status.containers.push(ContainerStatus {
+                name: inspect
+                    .name
+                    .unwrap_or_default()
+                    .trim_start_matches('/')
+                    .to_string(),
+                state: state.status.map(|s| s.to_string()).unwrap_or_default(),
+                health: state.health.and_then(|h| h.status).map(|s| s.to_string()),
+                ports: summary
+                    .ports
+                    .unwrap_or_default()
+                    .iter()
+                    .filter_map(|p| {
+                        p.public_port
+                            .map(|public| format!("{}:{}", public, p.private_port))
+                    })
+                    .collect(),
+            });
+        }
+        status.container_count = status.containers.len();
+        Ok(status)
+    }
+
+    /// Pulled images of an app's compose file, pinned to what the daemon
+    /// holds: `<name>@<digest>` from the repo digests, or the image ID for
+    /// an image never pushed to a registry
+    pub async fn image_digests(&self, app_id: &str) -> TappResult<Vec<String>> {
+        let compose_path = self.get_app_dir(app_id).join("docker-compose.yml");
+        let compose_content = fs::read_to_string(&compose_path).await?;
+        let mut digests = Vec::new();
+        for image in compose_images(&compose_content)? {
+            let reference = image.to_string();
+            let inspect = self
+                .with_retry("inspect_image", || async {
+                    self.docker
+                        .inspect_image(&reference)
+                        .await
+                        .map_err(|e| self.daemon_error(e))
+                })
+                .await?;
+            let pinned = inspect
+                .repo_digests
+                .unwrap_or_default()
+                .iter()
+                .filter_map(|digest| digest.parse::<ImageRef>().ok())
+                .find(|digest| digest.name() == image.name() && digest.digest.is_some())
+                .and_then(|digest| digest.digest)
+                .map(|digest| format!("{}@{}", image.name(), digest));
+            match pinned.or(inspect.id) {
+                Some(digest) => digests.push(digest),
+                None => {
+                    return Err(DockerError::ContainerOperationFailed {
+                        operation: "inspect_image".to_string(),
+                        reason: format!("No ID or digest for {}", reference),
+                        cause: FailureCause::Internal,
+                    }
+                    .into())
+                }
+            }
+        }
+        Ok(digests)
     }
 
     /// Create mock manager for testing
-    pub fn mock() -> Self {
+    pub fn mock(data_dir: &Path) -> Self {
         // This will fail if actually used, but good for testing structure
         Self {
             docker: Docker::connect_with_socket_defaults().unwrap_or_else(|_| {
                 // This is a hack for testing - in real tests we'd use a proper mock
                 panic!("Mock Docker not available")
             }),
+            endpoint: String::new(),
+            runtime: ContainerRuntime::Docker,
+            compose: ComposeCli::new(ContainerRuntime::Docker, None),
+            retry: RetryConfig::default(),
+            data_dir: data_dir.to_path_buf(),
             app_containers: HashMap::new(),
         }
     }
@@ -95,7 +517,7 @@ impl DockerComposeManager {
     /// Store mount files to host filesystem and create mapping
     /// Returns a HashMap of source_path -> actual_host_path
     async fn store_mount_files(
-        base_path: &PathBuf,
+        base_path: &Path,
         mount_files: &[MountFile],
     ) -> TappResult<HashMap<String, String>> {
         let mut source_to_host = HashMap::new();
@@ -166,20 +588,86 @@ impl DockerComposeManager {
         Ok(source_to_host)
     }
 
-    /// Deploy Docker Compose application
-    pub async fn deploy_compose(
+    /// Kill a child process and every process in its group
+    async fn kill_process_group(child: &mut Child) {
+        if let Some(pid) = child.id() {
+            // The child leads its own process group (see process_group(0))
+            let _ = Command::new("kill")
+                .args(["-KILL", "--", &format!("-{}", pid)])
+                .status()
+                .await;
+        }
+        let _ = child.kill().await;
+    }
+
+    /// Run an RPC-scoped docker command and collect its output
+    /// The command runs in its own process group, waited on by a separate
+    /// task. Once `cancel` is cancelled, or this future is dropped because
+    /// the client's deadline passed or it disconnected, the task kills the
+    /// whole group: the docker CLI and the compose plugin it started.
+    /// Returns None when cancelled. Deployments, which outlive the RPC that
+    /// queued them, use `run_compose` instead.
+    async fn scoped_output(
+        mut command: Command,
+        cancel: &CancellationToken,
+    ) -> std::io::Result<Option<Output>> {
+        async fn read_all(pipe: Option<impl AsyncRead + Unpin>) -> Vec<u8> {
+            let mut buf = Vec::new();
+            if let Some(mut pipe) = pipe {
+                let _ = pipe.read_to_end(&mut buf).await;
+            }
+            buf
+        }
+
+        command
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true);
+        #[cfg(unix)]
+        command.process_group(0);
+        let mut child = command.spawn()?;
+
+        let scope = cancel.child_token();
+        let _cancel_on_drop = scope.clone().drop_guard();
+        let waiter = tokio::spawn(async move {
+            let stdout = child.stdout.take();
+            let stderr = child.stderr.take();
+            tokio::select! {
+                (status, stdout, stderr) = async {
+                    tokio::join!(child.wait(), read_all(stdout), read_all(stderr))
+                } => Some(status.map(|status| Output { status, stdout, stderr })),
+                _ = scope.cancelled() => {
+                    Self::kill_process_group(&mut child).await;
+                    None
+                }
+            }
+        });
+        match waiter.await {
+            Ok(output) => output.transpose(),
+            Err(e) => Err(std::io::Error::other(e)),
+        }
+    }
+
+    /// Write the compose file and mount files of an app to its directory
+    pub async fn write_compose_files(
+        &self,
         app_id: &str,
         compose_content: &str,
         mount_files: &[MountFile],
     ) -> TappResult<()> {
-        use std::sync::Arc;
-        use tokio::io::{AsyncBufReadExt, BufReader};
-        use tokio::sync::Mutex;
+        Self::write_app_files(&self.get_app_dir(app_id), compose_content, mount_files).await
+    }
 
-        // 1. store compose file
-        let base_path = Self::get_app_dir(app_id);
+    /// Write a compose file and its mount files to `base_path`, creating it
+    /// This is the layout `docker compose` runs in; it needs no daemon.
+    pub async fn write_app_files(
+        base_path: &Path,
+        compose_content: &str,
+        mount_files: &[MountFile],
+    ) -> TappResult<()> {
         if !base_path.exists() {
-            fs::create_dir_all(&base_path).await.map_err(|e| {
+            fs::create_dir_all(base_path).await.map_err(|e| {
                 DockerError::VolumeMeasurementFailed {
                     path: format!("Failed to create volumes directory: {}", e),
                 }
@@ -188,32 +676,87 @@ impl DockerComposeManager {
         let compose_path = base_path.join("docker-compose.yml");
         fs::write(&compose_path, compose_content).await?;
 
-        // 2. store mount files to corresponding location
-        Self::store_mount_files(&base_path, mount_files).await?;
+        Self::store_mount_files(base_path, mount_files).await?;
+        Ok(())
+    }
 
-        // 3. start compose with real-time output
-        info!(app_id = %app_id, "🚀 Starting docker compose up");
+    /// Pull the images of an app
+    /// Images that cannot be pulled (e.g. built locally) are left to `up`.
+    pub async fn pull_compose(
+        &self,
+        app_id: &str,
+        task_id: &str,
+        cancel: &CancellationToken,
+    ) -> TappResult<()> {
+        self.run_compose(
+            app_id,
+            task_id,
+            &["pull", "--ignore-pull-failures"],
+            None,
+            cancel,
+        )
+        .await
+    }
 
-        let mut child = Command::new("docker")
-            .current_dir(&base_path)
-            .args(["compose", "-f", "docker-compose.yml", "up", "-d"])
-            .stdout(Stdio::piped())
-            .stderr(Stdio::piped())
+    /// Create and start the containers of an app, giving up after `timeout`
+    pub async fn up_compose(
+        &self,
+        app_id: &str,
+        task_id: &str,
+        timeout: Duration,
+        cancel: &CancellationToken,
+    ) -> TappResult<()> {
+        self.run_compose(app_id, task_id, &["up", "-d"], Some(timeout), cancel)
+            .await
+    }
+
+    /// Run a docker compose command in the app directory with real-time output
+    /// The output also goes to the task's deploy log in the app directory.
+    /// Cancelling `cancel` kills the command and its children and returns
+    /// `TappError::Cancelled`, running past `timeout` returns
+    /// `DockerError::Timeout`; rolling back is up to the caller.
+    async fn run_compose(
+        &self,
+        app_id: &str,
+        task_id: &str,
+        args: &[&str],
+        timeout: Option<Duration>,
+        cancel: &CancellationToken,
+    ) -> TappResult<()> {
+        use std::sync::Arc;
+        use tokio::io::{AsyncBufReadExt, BufReader};
+        use tokio::sync::Mutex;
+
+        let subcommand = args[0];
+        let operation = format!("docker_compose_{}", subcommand);
+        info!(app_id = %app_id, "🚀 Starting {} {}", self.compose, subcommand);
+
+        let args = [&["-f", "docker-compose.yml"][..], args].concat();
+        let mut command = self.compose.command(&self.get_app_dir(app_id), &args);
+        command.stdout(Stdio::piped()).stderr(Stdio::piped());
+        // Own process group, so cancellation also reaches compose's children
+        #[cfg(unix)]
+        command.process_group(0);
+
+        let mut child = command
             .spawn()
             .map_err(|e| DockerError::ContainerOperationFailed {
-                operation: "docker_compose_up".to_string(),
-                reason: format!("Failed to execute docker compose command: {}", e),
+                operation: operation.clone(),
+                reason: format!("Failed to execute {}: {}", self.compose, e),
+                cause: FailureCause::Internal,
             })?;
 
         let stdout = child.stdout.take().unwrap();
         let stderr = child.stderr.take().unwrap();
 
-        // Collect output
+        // Collect output, separately and in the order it was printed
         let stdout_lines = Arc::new(Mutex::new(Vec::new()));
         let stderr_lines = Arc::new(Mutex::new(Vec::new()));
+        let output = Arc::new(Mutex::new(Vec::new()));
 
         let app_id_clone = app_id.to_string();
         let stdout_lines_clone = stdout_lines.clone();
+        let output_clone = output.clone();
         let stdout_task = tokio::spawn(async move {
             let reader = BufReader::new(stdout);
             let mut lines = reader.lines();
@@ -223,12 +766,14 @@ impl DockerComposeManager {
                     output_type = "stdout",
                     "🐳 {}", line
                 );
+                output_clone.lock().await.push(format!("[stdout] {}", line));
                 stdout_lines_clone.lock().await.push(line);
             }
         });
 
         let app_id_clone = app_id.to_string();
         let stderr_lines_clone = stderr_lines.clone();
+        let output_clone = output.clone();
         let stderr_task = tokio::spawn(async move {
             let reader = BufReader::new(stderr);
             let mut lines = reader.lines();
@@ -237,19 +782,46 @@ impl DockerComposeManager {
                     app_id = %app_id_clone,
                     "🐳 {}", line
                 );
+                output_clone.lock().await.push(format!("[stderr] {}", line));
                 stderr_lines_clone.lock().await.push(line);
             }
         });
 
-        let status = child
-            .wait()
-            .await
-            .map_err(|e| DockerError::ContainerOperationFailed {
-                operation: "docker_compose_up".to_string(),
-                reason: format!("Failed to wait for docker compose: {}", e),
-            })?;
+        let status = tokio::select! {
+            status = child.wait() => status.map_err(|e| DockerError::ContainerOperationFailed {
+                operation: operation.clone(),
+                reason: format!("Failed to wait for {}: {}", self.compose, e),
+                cause: FailureCause::Internal,
+            })?,
+            _ = cancel.cancelled() => {
+                warn!(app_id = %app_id, "Cancelling docker compose {}", subcommand);
+                Self::kill_process_group(&mut child).await;
+                let _ = tokio::join!(stdout_task, stderr_task);
+                self.save_deploy_output(app_id, task_id, subcommand, "cancelled", &output).await;
+                return Err(TappError::Cancelled);
+            }
+            _ = Self::sleep_or_forever(timeout) => {
+                let seconds = timeout.unwrap_or_default().as_secs();
+                error!(
+                    app_id = %app_id,
+                    timeout_seconds = seconds,
+                    "❌ Docker compose {} timed out", subcommand
+                );
+                Self::kill_process_group(&mut child).await;
+                let _ = tokio::join!(stdout_task, stderr_task);
+                let outcome = format!("timed out after {}s", seconds);
+                self.save_deploy_output(app_id, task_id, subcommand, &outcome, &output).await;
+                return Err(DockerError::Timeout { operation, seconds }.into());
+            }
+        };
 
         let _ = tokio::join!(stdout_task, stderr_task);
+        let outcome = match status.code() {
+            Some(code) => format!("exit code {}", code),
+            None => "killed by a signal".to_string(),
+        };
+        self.save_deploy_output(app_id, task_id, subcommand, &outcome, &output)
+            .await;
 
         let all_stdout = stdout_lines.lock().await.join("\n");
         let all_stderr = stderr_lines.lock().await.join("\n");
@@ -264,13 +836,14 @@ impl DockerComposeManager {
             );
 
             return Err(DockerError::ContainerOperationFailed {
-                operation: "docker_compose_up".to_string(),
+                operation,
                 reason: format!(
                     "Docker compose failed with exit code {:?}\nStderr: {}\nStdout: {}",
                     status.code(),
                     all_stderr,
                     all_stdout
                 ),
+                cause: FailureCause::Internal,
             }
             .into());
         }
@@ -278,15 +851,169 @@ impl DockerComposeManager {
         info!(
             app_id = %app_id,
             output = %all_stdout,
-            "✅ Docker compose up completed successfully"
+            "✅ Docker compose {} completed successfully", subcommand
         );
 
         Ok(())
     }
 
+    /// Append a command's output to the task's deploy log; failing to is
+    /// only logged
+    async fn save_deploy_output(
+        &self,
+        app_id: &str,
+        task_id: &str,
+        subcommand: &str,
+        outcome: &str,
+        output: &tokio::sync::Mutex<Vec<String>>,
+    ) {
+        let output = output.lock().await;
+        let app_dir = self.get_app_dir(app_id);
+        if let Err(e) = deploy_log::append(&app_dir, task_id, subcommand, outcome, &output).await {
+            warn!(
+                app_id = %app_id,
+                task_id = %task_id,
+                error = %e,
+                "Failed to write deploy log"
+            );
+        }
+    }
+
+    async fn sleep_or_forever(timeout: Option<Duration>) {
+        match timeout {
+            Some(timeout) => tokio::time::sleep(timeout).await,
+            None => std::future::pending().await,
+        }
+    }
+
+    /// Wait until the containers of an app are up and their health checks pass
+    /// Unhealthy containers, a timeout or a failing `docker compose ps` are
+    /// logged but do not fail the deployment; only cancellation is an error.
+    pub async fn wait_healthy(
+        &self,
+        app_id: &str,
+        timeout: Duration,
+        cancel: &CancellationToken,
+    ) -> TappResult<()> {
+        let deadline = tokio::time::Instant::now() + timeout;
+
+        loop {
+            let output = self
+                .compose
+                .command(
+                    &self.get_app_dir(app_id),
+                    &["ps", "--all", "--format", "json"],
+                )
+                .kill_on_drop(true)
+                .output()
+                .await;
+
+            let health = match output {
+                Ok(output) if output.status.success() => {
+                    Self::compose_health(&String::from_utf8_lossy(&output.stdout))
+                }
+                Ok(output) => {
+                    warn!(
+                        app_id = %app_id,
+                        stderr = %String::from_utf8_lossy(&output.stderr),
+                        "docker compose ps failed, not waiting for health checks"
+                    );
+                    return Ok(());
+                }
+                Err(e) => {
+                    warn!(app_id = %app_id, error = %e, "Failed to run docker compose ps");
+                    return Ok(());
+                }
+            };
+
+            match health {
+                ComposeHealth::Ready => return Ok(()),
+                ComposeHealth::Unhealthy(containers) => {
+                    warn!(app_id = %app_id, containers = ?containers, "Containers are unhealthy");
+                    return Ok(());
+                }
+                ComposeHealth::Waiting => {}
+            }
+
+            if tokio::time::Instant::now() >= deadline {
+                warn!(
+                    app_id = %app_id,
+                    timeout_seconds = timeout.as_secs(),
+                    "Timed out waiting for containers to become healthy"
+                );
+                return Ok(());
+            }
+
+            tokio::select! {
+                _ = tokio::time::sleep(HEALTH_POLL_INTERVAL) => {}
+                _ = cancel.cancelled() => return Err(TappError::Cancelled),
+            }
+        }
+    }
+
+    /// Summarize `docker compose ps --format json` output
+    /// Compose prints either one JSON array or one object per line.
+    /// podman-compose prints `podman ps` containers instead, with `Names`
+    /// and the health in `Status`, e.g. "Up 5 seconds (healthy)".
+    fn compose_health(output: &str) -> ComposeHealth {
+        let output = output.trim();
+        let containers: Vec<serde_json::Value> = if output.starts_with('[') {
+            serde_json::from_str(output).unwrap_or_default()
+        } else {
+            output
+                .lines()
+                .filter_map(|line| serde_json::from_str(line).ok())
+                .collect()
+        };
+
+        let field = |container: &serde_json::Value, name: &str| {
+            container[name].as_str().unwrap_or_default().to_lowercase()
+        };
+        let name = |container: &serde_json::Value| {
+            container["Name"]
+                .as_str()
+                .or_else(|| container["Names"][0].as_str())
+                .unwrap_or_default()
+                .to_lowercase()
+        };
+        let health = |container: &serde_json::Value| {
+            let health = field(container, "Health");
+            if !health.is_empty() {
+                return health;
+            }
+            let status = field(container, "Status");
+            ["unhealthy", "healthy", "starting"]
+                .into_iter()
+                .find(|health| status.ends_with(&format!("({})", health)))
+                .unwrap_or_default()
+                .to_string()
+        };
+
+        let unhealthy: Vec<String> = containers
+            .iter()
+            .filter(|c| health(c) == "unhealthy")
+            .map(name)
+            .collect();
+        if !unhealthy.is_empty() {
+            return ComposeHealth::Unhealthy(unhealthy);
+        }
+
+        // Exited one-shot containers count as settled
+        let waiting = containers.iter().any(|c| {
+            health(c) == "starting"
+                || matches!(field(c, "State").as_str(), "created" | "restarting")
+        });
+        if waiting {
+            ComposeHealth::Waiting
+        } else {
+            ComposeHealth::Ready
+        }
+    }
+
     /// Stop Docker Compose application
-    pub async fn stop_compose(app_id: &str) -> TappResult<()> {
-        let app_dir = Self::get_app_dir(app_id);
+    /// `docker compose down` is killed once `cancel` is cancelled.
+    pub async fn stop_compose(&self, app_id: &str, cancel: &CancellationToken) -> TappResult<()> {
+        let app_dir = self.get_app_dir(app_id);
 
         if !app_dir.exists() {
             return Err(TappError::InvalidParameter {
@@ -298,17 +1025,17 @@ impl DockerComposeManager {
         info!(app_id = %app_id, "🛑 Stopping Docker Compose application");
 
         // Execute docker compose down in app directory
-        let output = tokio::process::Command::new("docker")
-            .args(&["compose", "down"])
-            .current_dir(&app_dir)
-            .output()
+        let command = self.compose.command(&app_dir, &["down"]);
+        let output = Self::scoped_output(command, cancel)
             .await
             .map_err(|e| {
                 TappError::Docker(DockerError::ContainerOperationFailed {
                     operation: "stop".to_string(),
-                    reason: format!("Failed to execute docker compose down: {}", e),
+                    reason: format!("Failed to execute {} down: {}", self.compose, e),
+                    cause: FailureCause::Internal,
                 })
-            })?;
+            })?
+            .ok_or(TappError::Cancelled)?;
 
         if !output.status.success() {
             let stderr = String::from_utf8_lossy(&output.stderr);
@@ -319,7 +1046,8 @@ impl DockerComposeManager {
             );
             return Err(TappError::Docker(DockerError::ContainerOperationFailed {
                 operation: "stop".to_string(),
-                reason: format!("docker compose down failed: {}", stderr),
+                reason: format!("{} down failed: {}", self.compose, stderr),
+                cause: FailureCause::Internal,
             }));
         }
 
@@ -333,11 +1061,67 @@ impl DockerComposeManager {
         Ok(())
     }
 
+    /// Take down an app's compose project by name, without its compose file
+    /// Also finds containers whose app directory is gone. `purge_volumes`
+    /// removes the project's volumes and `purge_images` the images its
+    /// services used. `docker compose down` is killed once `cancel` is
+    /// cancelled.
+    pub async fn remove_project(
+        &self,
+        app_id: &str,
+        purge_volumes: bool,
+        purge_images: bool,
+        cancel: &CancellationToken,
+    ) -> TappResult<()> {
+        let project = Self::compose_project_name(app_id);
+        let mut args = vec!["-p", project.as_str(), "down", "--remove-orphans"];
+        if purge_volumes {
+            args.push("--volumes");
+        }
+        if purge_images {
+            args.extend(["--rmi", "all"]);
+        }
+
+        // Run outside the app directory so no compose file is picked up
+        let command = self.compose.command(&self.data_dir, &args);
+        let output = Self::scoped_output(command, cancel)
+            .await
+            .map_err(|e| {
+                TappError::Docker(DockerError::ContainerOperationFailed {
+                    operation: "remove_project".to_string(),
+                    reason: format!("Failed to execute {} down: {}", self.compose, e),
+                    cause: FailureCause::Internal,
+                })
+            })?
+            .ok_or(TappError::Cancelled)?;
+
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(TappError::Docker(DockerError::ContainerOperationFailed {
+                operation: "remove_project".to_string(),
+                reason: format!("{} down failed: {}", self.compose, stderr),
+                cause: FailureCause::Internal,
+            }));
+        }
+
+        info!(
+            app_id = %app_id,
+            project = %project,
+            purge_volumes,
+            purge_images,
+            "Compose project removed"
+        );
+        Ok(())
+    }
+
     /// Get application logs from docker compose
+    /// `docker compose logs` is killed once `cancel` is cancelled.
     pub async fn get_app_logs(
+        &self,
         app_id: &str,
         lines: i32,
         service_name: Option<&str>,
+        cancel: &CancellationToken,
     ) -> TappResult<String> {
         info!(
             app_id = %app_id,
@@ -346,7 +1130,7 @@ impl DockerComposeManager {
             "Getting application logs"
         );
 
-        let app_dir = Self::get_app_dir(app_id);
+        let app_dir = self.get_app_dir(app_id);
 
         if !app_dir.exists() {
             warn!(app_id = %app_id, "App directory not found");
@@ -363,7 +1147,7 @@ impl DockerComposeManager {
             "100".to_string()
         };
 
-        let mut args = vec!["compose", "logs", "--tail", &lines_arg];
+        let mut args = vec!["logs", "--tail", &lines_arg];
 
         // Add service name if specified
         if let Some(svc) = service_name {
@@ -373,23 +1157,24 @@ impl DockerComposeManager {
         }
 
         // Execute command in app directory
-        let output = tokio::process::Command::new("docker")
-            .args(&args)
-            .current_dir(&app_dir)
-            .output()
+        let command = self.compose.command(&app_dir, &args);
+        let output = Self::scoped_output(command, cancel)
             .await
             .map_err(|e| {
                 TappError::Docker(DockerError::ContainerOperationFailed {
                     operation: "get logs".to_string(),
-                    reason: format!("Failed to execute docker compose logs: {}", e),
+                    reason: format!("Failed to execute {} logs: {}", self.compose, e),
+                    cause: FailureCause::Internal,
                 })
-            })?;
+            })?
+            .ok_or(TappError::Cancelled)?;
 
         if !output.status.success() {
             let stderr = String::from_utf8_lossy(&output.stderr);
             return Err(TappError::Docker(DockerError::ContainerOperationFailed {
                 operation: "get logs".to_string(),
-                reason: format!("docker compose logs failed: {}", stderr),
+                reason: format!("{} logs failed: {}", self.compose, stderr),
+                cause: FailureCause::Internal,
             }));
         }
 
@@ -398,5 +1183,278 @@ impl DockerComposeManager {
     }
 }
 
+/// Unix seconds of a Docker API timestamp (RFC 3339, with up to
+/// nanosecond precision); None for Docker's zero time, used for containers
+/// that never started
+pub fn parse_docker_time(value: &str) -> Option<i64> {
+    chrono::DateTime::parse_from_rfc3339(value)
+        .ok()
+        .map(|time| time.timestamp())
+        .filter(|seconds| *seconds > 0)
+}
+
 #[cfg(test)]
-mod tests {}
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_app_files_under_data_dir() {
+        let dir = tempfile::tempdir().unwrap();
+        let data_dir = dir.path().join("apps");
+        DockerComposeManager::create_data_dir(&data_dir).unwrap();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::metadata(&data_dir).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o700);
+        }
+        // An existing directory is fine
+        DockerComposeManager::create_data_dir(&data_dir).unwrap();
+
+        let manager = DockerComposeManager::mock(&data_dir);
+        let app_dir = manager.get_app_dir("app-1");
+        assert_eq!(app_dir, data_dir.join("app-1"));
+        // Namespaced apps get their own directory and compose project
+        assert_eq!(
+            manager.get_app_dir("team-a/app-1"),
+            data_dir.join("team-a__app-1")
+        );
+        assert_eq!(
+            DockerComposeManager::compose_project_name("team-a/App-1"),
+            "team-a__app-1"
+        );
+
+        let mount_files = vec![MountFile {
+            source_path: "./conf/nginx.conf".to_string(),
+            content: b"user nginx;\n".to_vec(),
+            mode: "0600".to_string(),
+            encrypted: false,
+        }];
+        manager
+            .write_compose_files("app-1", "services: {}\n", &mount_files)
+            .await
+            .unwrap();
+        assert_eq!(
+            std::fs::read_to_string(app_dir.join("docker-compose.yml")).unwrap(),
+            "services: {}\n"
+        );
+        assert_eq!(
+            std::fs::read(app_dir.join("conf_nginx.conf")).unwrap(),
+            b"user nginx;\n"
+        );
+
+        // Deploy output goes next to them
+        let output = tokio::sync::Mutex::new(vec!["[stdout] done".to_string()]);
+        manager
+            .save_deploy_output("app-1", "task-1", "up", "exit code 0", &output)
+            .await;
+        assert!(deploy_log::log_path(&app_dir, "task-1").exists());
+    }
+
+    /// Whether a process has exited (a zombie counts)
+    #[cfg(target_os = "linux")]
+    fn process_gone(pid: &str) -> bool {
+        match std::fs::read_to_string(format!("/proc/{}/stat", pid)) {
+            Ok(stat) => stat
+                .rsplit_once(')')
+                .is_some_and(|(_, rest)| rest.trim_start().starts_with('Z')),
+            Err(_) => true,
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn test_scoped_command_dies_with_its_rpc() {
+        let dir = tempfile::tempdir().unwrap();
+        // A shell standing in for the docker CLI, and a long sleep for the
+        // compose plugin it runs; both PIDs are written to a file
+        let sleeper = |name: &str| {
+            let pids = dir.path().join(name);
+            let mut command = Command::new("sh");
+            command
+                .arg("-c")
+                .arg(format!("sleep 300 & echo $$ $! > {}; wait", pids.display()));
+            (command, pids)
+        };
+        let started = |path: PathBuf| async move {
+            loop {
+                if let Ok(pids) = std::fs::read_to_string(&path) {
+                    if pids.ends_with('\n') {
+                        let pids: Vec<String> =
+                            pids.split_whitespace().map(str::to_string).collect();
+                        assert!(pids.iter().all(|pid| !process_gone(pid)));
+                        return pids;
+                    }
+                }
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        };
+        let all_gone = |pids: Vec<String>| async move {
+            for _ in 0..200 {
+                if pids.iter().all(|pid| process_gone(pid)) {
+                    return true;
+                }
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+            false
+        };
+        let cancel = CancellationToken::new();
+
+        // Dropped, as tonic drops a handler past its deadline
+        let (command, path) = sleeper("dropped");
+        let pids = tokio::select! {
+            _ = DockerComposeManager::scoped_output(command, &cancel) => unreachable!("sleep exited"),
+            pids = started(path) => pids,
+        };
+        assert!(all_gone(pids).await);
+
+        // Cancelled
+        let (command, path) = sleeper("cancelled");
+        let (output, pids) = tokio::join!(
+            DockerComposeManager::scoped_output(command, &cancel),
+            async {
+                let pids = started(path).await;
+                cancel.cancel();
+                pids
+            }
+        );
+        assert!(output.unwrap().is_none());
+        assert!(all_gone(pids).await);
+
+        // Commands that finish return their output
+        let mut command = Command::new("sh");
+        command.args(["-c", "echo out; echo err >&2; exit 3"]);
+        let output = DockerComposeManager::scoped_output(command, &CancellationToken::new())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(output.status.code(), Some(3));
+        assert_eq!(output.stdout, b"out\n");
+        assert_eq!(output.stderr, b"err\n");
+    }
+
+    #[test]
+    fn test_retry_delay_bounds() {
+        let policy = RetryConfig {
+            max_retries: 10,
+            initial_delay_ms: 1000,
+            max_delay_ms: 5000,
+        };
+        for (attempt, backoff) in [(0, 1000), (1, 2000), (2, 4000), (3, 5000), (40, 5000)] {
+            for _ in 0..20 {
+                let delay = retry_delay(&policy, attempt).as_millis() as u64;
+                assert!(
+                    (backoff / 2..=backoff).contains(&delay),
+                    "attempt {}: {}ms",
+                    attempt,
+                    delay
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_parse_docker_time() {
+        assert_eq!(
+            parse_docker_time("2026-10-16T08:12:45Z"),
+            Some(1_792_138_365)
+        );
+        // Docker reports nanoseconds
+        assert_eq!(
+            parse_docker_time("2026-10-16T08:12:45.123456789Z"),
+            Some(1_792_138_365)
+        );
+        assert_eq!(
+            parse_docker_time("2026-10-16T10:12:45.5+02:00"),
+            Some(1_792_138_365)
+        );
+        assert_eq!(parse_docker_time("0001-01-01T00:00:00Z"), None);
+        assert_eq!(parse_docker_time(""), None);
+        assert_eq!(parse_docker_time("2026-10-16 08:12:45"), None);
+    }
+
+    #[test]
+    fn test_compose_health() {
+        // Newer compose prints one object per line
+        let lines = r#"{"Name":"app-web-1","State":"running","Health":"healthy"}
+{"Name":"app-init-1","State":"exited","Health":"","ExitCode":0}"#;
+        assert_eq!(
+            DockerComposeManager::compose_health(lines),
+            ComposeHealth::Ready
+        );
+
+        // Older compose prints an array
+        let array = r#"[{"Name":"app-web-1","State":"running","Health":"starting"}]"#;
+        assert_eq!(
+            DockerComposeManager::compose_health(array),
+            ComposeHealth::Waiting
+        );
+
+        let unhealthy = r#"{"Name":"app-db-1","State":"running","Health":"unhealthy"}"#;
+        assert_eq!(
+            DockerComposeManager::compose_health(unhealthy),
+            ComposeHealth::Unhealthy(vec!["app-db-1".to_string()])
+        );
+        assert_eq!(
+            DockerComposeManager::compose_health(""),
+            ComposeHealth::Ready
+        );
+
+        // podman-compose prints podman's containers
+        let podman = r#"[
+            {"Names":["app_web_1"],"State":"running","Status":"Up 5 seconds (starting)"},
+            {"Names":["app_init_1"],"State":"exited","Status":"Exited (0) 3 seconds ago"}
+        ]"#;
+        assert_eq!(
+            DockerComposeManager::compose_health(podman),
+            ComposeHealth::Waiting
+        );
+        let podman =
+            r#"[{"Names":["app_db_1"],"State":"running","Status":"Up 1 minute (unhealthy)"}]"#;
+        assert_eq!(
+            DockerComposeManager::compose_health(podman),
+            ComposeHealth::Unhealthy(vec!["app_db_1".to_string()])
+        );
+    }
+
+    #[test]
+    fn test_compose_cli() {
+        let args = |cli: &ComposeCli, args: &[&str]| {
+            let command = cli.command(Path::new("/tmp"), args);
+            let command = command.as_std();
+            std::iter::once(command.get_program())
+                .chain(command.get_args())
+                .map(|arg| arg.to_string_lossy().to_string())
+                .collect::<Vec<_>>()
+                .join(" ")
+        };
+        let ps = ["ps", "--all", "--format", "json"];
+
+        let docker = ComposeCli::new(ContainerRuntime::Docker, None);
+        assert_eq!(docker.to_string(), "docker compose");
+        assert_eq!(args(&docker, &ps), "docker compose ps --all --format json");
+        assert_eq!(docker.project_label(), COMPOSE_PROJECT_LABEL);
+
+        let podman = ComposeCli::new(ContainerRuntime::Podman, None);
+        assert_eq!(args(&podman, &ps), "podman compose ps --all --format json");
+        assert_eq!(podman.project_label(), COMPOSE_PROJECT_LABEL);
+
+        // The standalone tool lacks some options and has its own label
+        let podman_compose = ComposeCli::new(
+            ContainerRuntime::Podman,
+            Some("/usr/local/bin/podman-compose"),
+        );
+        assert_eq!(
+            args(&podman_compose, &ps),
+            "/usr/local/bin/podman-compose ps --format json"
+        );
+        assert_eq!(
+            args(
+                &podman_compose,
+                &["-f", "docker-compose.yml", "pull", "--ignore-pull-failures"]
+            ),
+            "/usr/local/bin/podman-compose -f docker-compose.yml pull"
+        );
+        assert_eq!(podman_compose.project_label(), PODMAN_COMPOSE_PROJECT_LABEL);
+    }
+}
@@ -1,7 +1,34 @@
-use crate::error::{DockerError, TappResult};
+use crate::error::{DockerError, TappError, TappResult};
 use serde::{Deserialize, Serialize};
 use serde_json::{Map, Value as JsonValue};
 use serde_yaml::Value;
+use std::collections::HashMap;
+use std::path::Path;
+use tracing::warn;
+
+/// File in an app's directory holding its measurement, so it survives a
+/// restart of the service
+pub const MEASUREMENT_FILE: &str = "measurement.json";
+/// File in an app's directory holding its compose file as measured, before
+/// secret values and platforms were filled in
+pub const MEASURED_COMPOSE_FILE: &str = "measured-compose.yml";
+/// File in an app's directory holding its combined mount file content
+/// (GetAppInfo's `volumes_content`)
+pub const MEASURED_VOLUMES_FILE: &str = "measured-volumes";
+
+/// Separator between the files of the combined mount file content
+const FILE_SEPARATOR: &str = "\x1E"; // Record Separator
+
+/// Split the combined mount file content built by
+/// `calculate_mount_files_hash` (GetAppInfo's `volumes_content`) back into
+/// (source path, content) pairs
+/// Content that was not UTF-8 comes back lossily converted.
+pub fn split_mount_files(content: &str) -> Vec<(&str, &str)> {
+    content
+        .split(FILE_SEPARATOR)
+        .filter_map(|file| file.strip_prefix("--- FILE: ")?.split_once(" ---\n"))
+        .collect()
+}
 
 /// Hash algorithm for measurement calculation
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -27,15 +54,235 @@ impl Default for HashAlgorithm {
 }
 
 /// Application measurement data
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct AppMeasurement {
     pub app_id: String,
     pub compose_hash: String,
     pub volumes_hash: String,
+    /// Canonical deployer: hex of the 64-byte public key, or of the address
+    /// when the app was deployed by address only
     pub deployer: String,
+    /// Hex of the deployer's 20-byte Ethereum address
+    #[serde(default)]
+    pub deployer_address: String,
+    pub timestamp: i64,
+    /// Namespace of the app (see `crate::namespace`); `app_id` includes it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub namespace: Option<String>,
+    /// Names of the secret placeholders of the compose file (see
+    /// `crate::boot::compose_secrets`), sorted; their values are not measured
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub secret_names: Vec<String>,
+}
+
+impl AppMeasurement {
+    /// Write to `MEASUREMENT_FILE` in `app_dir`, replacing the previous file
+    /// atomically
+    pub fn save(&self, app_dir: &Path) -> TappResult<()> {
+        write_atomic(app_dir, MEASUREMENT_FILE, &serde_json::to_vec_pretty(self)?)
+    }
+
+    /// Write the compose file and mount file content the measurement was
+    /// calculated from, for `load_contents` after a restart
+    pub fn save_contents(
+        app_dir: &Path,
+        compose_content: &str,
+        volumes_content: &str,
+    ) -> TappResult<()> {
+        write_atomic(app_dir, MEASURED_COMPOSE_FILE, compose_content.as_bytes())?;
+        write_atomic(app_dir, MEASURED_VOLUMES_FILE, volumes_content.as_bytes())
+    }
+
+    /// Compose file and mount file content of the app, as GetAppInfo reports
+    /// them, read back from `app_dir` (None: not known)
+    /// The compose file is `MEASURED_COMPOSE_FILE`, or the deployed
+    /// `docker-compose.yml` of apps measured before it was kept, whichever
+    /// matches `compose_hash`. Without `MEASURED_VOLUMES_FILE` the mount files
+    /// are only known when there were none.
+    pub fn load_contents(&self, app_dir: &Path) -> (Option<String>, Option<String>) {
+        let measurer = ComposeMeasurement::new();
+        let compose_content = [MEASURED_COMPOSE_FILE, "docker-compose.yml"]
+            .iter()
+            .filter_map(|name| std::fs::read_to_string(app_dir.join(name)).ok())
+            .find(|content| {
+                measurer
+                    .calculate_compose_hash(content)
+                    .is_ok_and(|hash| hash == self.compose_hash)
+            });
+        let volumes_content = match std::fs::read_to_string(app_dir.join(MEASURED_VOLUMES_FILE)) {
+            Ok(content) => Some(content),
+            Err(_) => measurer
+                .calculate_mount_files_hash(&[])
+                .is_ok_and(|(hash, _)| hash == self.volumes_hash)
+                .then(String::new),
+        };
+        (compose_content, volumes_content)
+    }
+
+    /// Measurements saved in the app directories under `data_dir`, by app ID
+    /// Unreadable files, and files naming an app other than the one of
+    /// their directory, are skipped with a warning.
+    pub fn load_all(data_dir: &Path) -> HashMap<String, AppMeasurement> {
+        let mut measurements = HashMap::new();
+        let entries = match std::fs::read_dir(data_dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return measurements,
+            Err(e) => {
+                warn!(data_dir = %data_dir.display(), error = %e, "Cannot read the app directories");
+                return measurements;
+            }
+        };
+        for entry in entries.flatten() {
+            let path = entry.path().join(MEASUREMENT_FILE);
+            if !path.is_file() {
+                continue;
+            }
+            let measurement = std::fs::read(&path)
+                .map_err(TappError::from)
+                .and_then(|bytes| {
+                    serde_json::from_slice::<AppMeasurement>(&bytes).map_err(Into::into)
+                });
+            match measurement {
+                Ok(measurement)
+                    if crate::namespace::storage_name(&measurement.app_id)
+                        == entry.file_name().to_string_lossy() =>
+                {
+                    measurements.insert(measurement.app_id.clone(), measurement);
+                }
+                Ok(measurement) => warn!(
+                    path = %path.display(),
+                    app_id = %measurement.app_id,
+                    "Skipping measurement file of another app"
+                ),
+                Err(e) => {
+                    warn!(path = %path.display(), error = %e, "Skipping unreadable measurement file")
+                }
+            }
+        }
+        measurements
+    }
+}
+
+/// App directory left by a deployment from before measurements were saved
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnmeasuredApp {
+    /// The deployed `docker-compose.yml`
+    pub compose_content: String,
+    /// Combined mount file content; only known when the app had none
+    pub volumes_content: Option<String>,
+}
+
+impl UnmeasuredApp {
+    /// App directories under `data_dir` with a `docker-compose.yml` but no
+    /// `MEASUREMENT_FILE`, by the app ID their directory name stands for
+    /// Mount files are written opened, so they are never read back; the
+    /// directory only tells whether there were any.
+    pub fn load_all(data_dir: &Path) -> HashMap<String, UnmeasuredApp> {
+        let mut apps = HashMap::new();
+        let Ok(entries) = std::fs::read_dir(data_dir) else {
+            return apps;
+        };
+        for entry in entries.flatten() {
+            let app_dir = entry.path();
+            if !app_dir.is_dir() || app_dir.join(MEASUREMENT_FILE).exists() {
+                continue;
+            }
+            let app_id = crate::namespace::from_storage_name(&entry.file_name().to_string_lossy());
+            if !crate::namespace::validate_qualified_app_id(&app_id) {
+                continue;
+            }
+            let Ok(compose_content) = std::fs::read_to_string(app_dir.join("docker-compose.yml"))
+            else {
+                continue;
+            };
+            warn!(
+                app_id = %app_id,
+                app_dir = %app_dir.display(),
+                event = "APP_UNMEASURED",
+                "App directory without a measurement, left by an earlier deployment"
+            );
+            let env_file = super::compose_secrets::ENV_FILE.trim_start_matches("./");
+            let has_mount_files = std::fs::read_dir(&app_dir).is_ok_and(|files| {
+                files.flatten().any(|file| {
+                    let name = file.file_name();
+                    name != "docker-compose.yml" && name != env_file
+                })
+            });
+            apps.insert(
+                app_id,
+                UnmeasuredApp {
+                    compose_content,
+                    volumes_content: (!has_mount_files).then(String::new),
+                },
+            );
+        }
+        apps
+    }
+}
+
+/// Replace `name` in `dir` atomically with `content`
+fn write_atomic(dir: &Path, name: &str, content: &[u8]) -> TappResult<()> {
+    let tmp = dir.join(format!(".{}.tmp", name));
+    std::fs::write(&tmp, content)?;
+    std::fs::rename(&tmp, dir.join(name))?;
+    Ok(())
+}
+
+/// Entry in an application's event history
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AppEvent {
+    pub event: String,
+    pub detail: String,
+    pub timestamp: i64,
+}
+
+/// Ownership transfer record, extended into the runtime measurement
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OwnershipTransfer {
+    pub app_id: String,
+    pub previous_deployer: String,
+    pub new_deployer: String,
+    pub timestamp: i64,
+}
+
+/// Force removal record, extended into the runtime measurement when the app
+/// had one, and kept in the app's event history
+/// Each field says whether that step found something to clean up.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ForceRemoval {
+    pub app_id: String,
+    pub purge_volumes: bool,
+    pub purge_images: bool,
+    /// In-flight task that held the app ID, now cancelled
+    pub cancelled_task: Option<String>,
+    /// Containers of the app's compose project that were taken down
+    pub containers_removed: usize,
+    pub app_dir_removed: bool,
+    pub measurement_removed: bool,
+    pub compose_content_removed: bool,
+    pub mount_files_removed: bool,
+    /// Steps that failed; the others still ran
+    pub errors: Vec<String>,
     pub timestamp: i64,
 }
 
+impl ForceRemoval {
+    /// Names of the steps that found something to clean up
+    pub fn cleaned(&self) -> Vec<&'static str> {
+        [
+            ("task", self.cancelled_task.is_some()),
+            ("containers", self.containers_removed > 0),
+            ("app_dir", self.app_dir_removed),
+            ("measurement", self.measurement_removed),
+            ("compose_content", self.compose_content_removed),
+            ("mount_files", self.mount_files_removed),
+        ]
+        .into_iter()
+        .filter_map(|(step, cleaned)| cleaned.then_some(step))
+        .collect()
+    }
+}
+
 /// Docker Compose measurement calculator
 pub struct ComposeMeasurement {
     hash_algorithm: HashAlgorithm,
@@ -81,7 +328,6 @@ impl ComposeMeasurement {
         let root_hash = self.build_merkle_root(&leaf_hashes)?;
 
         // Combine file contents with filename headers
-        const FILE_SEPARATOR: &str = "\x1E"; // Record Separator
         let combined_content: String = sorted_files
             .iter()
             .map(|file| {
@@ -233,4 +479,102 @@ services:
         // Should be the same despite key ordering
         assert_eq!(norm1, norm2);
     }
+
+    #[test]
+    fn test_split_mount_files() {
+        let file = |source_path: &str, content: &str| crate::boot::MountFile {
+            source_path: source_path.to_string(),
+            content: content.as_bytes().to_vec(),
+            mode: "0644".to_string(),
+            encrypted: false,
+        };
+        let (_, combined) = ComposeMeasurement::new()
+            .calculate_mount_files_hash(&[
+                file("/config/nginx.conf", "server {\n}\n"),
+                file("/config/app.env", "A=1\n"),
+            ])
+            .unwrap();
+
+        // Files come back sorted by source path, as measured
+        assert_eq!(
+            split_mount_files(&combined),
+            vec![
+                ("/config/app.env", "A=1\n"),
+                ("/config/nginx.conf", "server {\n}\n"),
+            ]
+        );
+        assert!(split_mount_files("").is_empty());
+    }
+
+    #[test]
+    fn test_load_contents() {
+        let dir = tempfile::tempdir().unwrap();
+        let measurer = ComposeMeasurement::new();
+        let compose = "services:\n  web:\n    image: nginx\n";
+        let mut measurement = AppMeasurement {
+            app_id: "app".to_string(),
+            compose_hash: measurer.calculate_compose_hash(compose).unwrap(),
+            volumes_hash: measurer.calculate_mount_files_hash(&[]).unwrap().0,
+            deployer: "ab".repeat(32),
+            deployer_address: "ab".repeat(20),
+            timestamp: 0,
+            namespace: None,
+            secret_names: Vec::new(),
+        };
+
+        // Nothing on disk: only the empty mount files are known
+        assert_eq!(
+            measurement.load_contents(dir.path()),
+            (None, Some(String::new()))
+        );
+
+        // The deployed compose file counts only while it matches the hash
+        let deployed = dir.path().join("docker-compose.yml");
+        std::fs::write(&deployed, compose).unwrap();
+        assert_eq!(
+            measurement.load_contents(dir.path()).0.as_deref(),
+            Some(compose)
+        );
+        std::fs::write(&deployed, compose.replace("nginx", "nginx:1.27")).unwrap();
+        assert_eq!(measurement.load_contents(dir.path()).0, None);
+
+        measurement.volumes_hash = "ab".repeat(48);
+        assert_eq!(measurement.load_contents(dir.path()), (None, None));
+
+        AppMeasurement::save_contents(dir.path(), compose, "--- FILE: a ---\nA").unwrap();
+        assert_eq!(
+            measurement.load_contents(dir.path()),
+            (
+                Some(compose.to_string()),
+                Some("--- FILE: a ---\nA".to_string())
+            )
+        );
+    }
+
+    #[test]
+    fn test_load_unmeasured() {
+        let dir = tempfile::tempdir().unwrap();
+        let compose = "services:\n  web:\n    image: nginx\n";
+        let app_dir = |name: &str| {
+            let path = dir.path().join(name);
+            std::fs::create_dir_all(&path).unwrap();
+            std::fs::write(path.join("docker-compose.yml"), compose).unwrap();
+            path
+        };
+        app_dir("plain");
+        std::fs::write(app_dir("team-a__web").join("nginx.conf"), "user nginx;").unwrap();
+        std::fs::write(app_dir("measured").join(MEASUREMENT_FILE), "{}").unwrap();
+        std::fs::create_dir_all(dir.path().join("empty")).unwrap();
+
+        let apps = UnmeasuredApp::load_all(dir.path());
+        assert_eq!(apps.len(), 2);
+        assert_eq!(
+            apps["plain"],
+            UnmeasuredApp {
+                compose_content: compose.to_string(),
+                volumes_content: Some(String::new()),
+            }
+        );
+        assert_eq!(apps["team-a/web"].volumes_content, None);
+    }
 }
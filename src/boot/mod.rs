@@ -1,104 +1,616 @@
+pub mod aa_config;
+pub mod backend;
+pub mod compose_secrets;
+pub mod deploy_log;
+pub mod deploy_queue;
+pub mod image_policy;
+pub mod image_scan;
 pub mod manager;
 pub mod measurement;
+pub mod platform;
+pub mod quote;
+pub mod rtmr;
 pub mod task_manager;
 
-pub use manager::{AppStatus, ContainerStatus, DockerComposeManager, MountFile};
-pub use measurement::{AppMeasurement, ComposeMeasurement, HashAlgorithm};
-pub use task_manager::{Task, TaskManager, TaskStatus as TaskState, TaskSuccessResult};
-
-use crate::config::BootServiceConfig;
-use crate::error::{DockerError, TappError, TappResult};
+pub use backend::ContainerBackend;
+pub use deploy_queue::DeployQueue;
+pub use image_policy::{ImagePolicy, ImageRef};
+use image_scan::ImageScanner;
+use manager::{is_transient_daemon_error, retry_transient};
+pub use manager::{AppStatus, ContainerStatus, DockerComposeManager, DockerHostInfo, MountFile};
+pub use measurement::{
+    AppEvent, AppMeasurement, ComposeMeasurement, ForceRemoval, HashAlgorithm, OwnershipTransfer,
+    UnmeasuredApp,
+};
+use rtmr::{ExtendHistory, Replay};
+pub use task_manager::{
+    Task, TaskErrorCode, TaskKind, TaskLookup, TaskManager, TaskStage, TaskStatus as TaskState,
+    TaskSuccessResult, SHUTDOWN_REASON,
+};
+
+use crate::app_key::deployer::DeployerIdentity;
+use crate::app_key::{envelope, AppKeyService};
+use crate::config::{BootServiceConfig, ContainerRuntime};
+use crate::contract::registrar::{Registrar, RegistrationStatus};
+use crate::contract::AppRegistration;
+use crate::error::{DockerError, FailureCause, TappError, TappResult};
+use crate::notifications::{NotificationEvent, Notifier};
+use crate::panic_layer::{catch_panic, CaughtPanic};
 use crate::proto::{GetEvidenceRequest, GetEvidenceResponse, StartAppRequest, StartAppResponse};
+use crate::telemetry::SPAN_TARGET;
 use attestation_agent::{AttestationAPIs, AttestationAgent};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
 use tokio::sync::Mutex;
-use tracing::info;
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, error, info, info_span, warn, Instrument};
 
 pub const ZGEL_DOMAIN: &str = "tapp.0g.com";
 pub const OPERATION_NAME_START_APP: &str = "start_app";
 pub const OPERATION_NAME_STOP_APP: &str = "stop_app";
+pub const OPERATION_NAME_TRANSFER_OWNERSHIP: &str = "transfer_ownership";
+pub const OPERATION_NAME_FORCE_REMOVE_APP: &str = "force_remove_app";
+pub const OPERATION_NAME_EXPORT_KEYSTORE_BACKUP: &str = "export_keystore_backup";
+pub const OPERATION_NAME_IMPORT_KEYSTORE_BACKUP: &str = "import_keystore_backup";
+/// App event recording the image scan of a deployment (not measured)
+pub const EVENT_IMAGE_SCAN: &str = "image_scan";
+
+/// Delay before the first automatic retry of a failed deployment; doubles per attempt
+const RETRY_BASE_DELAY: Duration = Duration::from_secs(5);
+const RETRY_MAX_DELAY: Duration = Duration::from_secs(60);
+
+/// How long cancelled deployments get to roll back at shutdown
+const SHUTDOWN_CANCEL_WAIT: Duration = Duration::from_secs(5);
+
+/// Docker output when a published port is taken
+const PORT_CONFLICT_MARKERS: &[&str] = &["port is already allocated", "address already in use"];
+/// Docker and podman CLI output when the daemon is unreachable
+const DOCKER_UNAVAILABLE_MARKERS: &[&str] = &[
+    "Cannot connect to the Docker daemon",
+    "Cannot connect to Podman",
+    "unable to connect to Podman socket",
+];
+
+/// Error of a deployment step with the failure code it is reported under
+type StepError = (TaskErrorCode, TappError);
+
+/// App state rebuilt from the app directories at startup
+#[derive(Default)]
+struct RestoredApps {
+    measurements: HashMap<String, AppMeasurement>,
+    compose_content: HashMap<String, String>,
+    mount_files: HashMap<String, String>,
+    /// Apps deployed before measurements were saved
+    unmeasured: HashSet<String>,
+    /// Apps none of whose containers are left
+    containers_missing: HashSet<String>,
+}
+
+impl RestoredApps {
+    /// Rebuild the apps found under the data directory of `backend`,
+    /// checking which ones still have containers
+    /// An app whose containers cannot be checked is not flagged.
+    async fn scan<B: ContainerBackend>(backend: &B) -> Self {
+        let mut restored = Self {
+            measurements: AppMeasurement::load_all(backend.data_dir()),
+            ..Default::default()
+        };
+        for (app_id, measurement) in &restored.measurements {
+            let (compose_content, volumes_content) =
+                measurement.load_contents(&backend.app_dir(app_id));
+            match compose_content {
+                Some(content) => {
+                    restored.compose_content.insert(app_id.clone(), content);
+                }
+                None => warn!(app_id = %app_id, "Measured compose file of the app not found"),
+            }
+            match volumes_content {
+                Some(content) => {
+                    restored.mount_files.insert(app_id.clone(), content);
+                }
+                None => warn!(app_id = %app_id, "Measured mount files of the app not found"),
+            }
+        }
+        for (app_id, app) in UnmeasuredApp::load_all(backend.data_dir()) {
+            restored
+                .compose_content
+                .insert(app_id.clone(), app.compose_content);
+            if let Some(content) = app.volumes_content {
+                restored.mount_files.insert(app_id.clone(), content);
+            }
+            restored.unmeasured.insert(app_id);
+        }
+
+        let app_ids: Vec<String> = restored
+            .measurements
+            .keys()
+            .chain(&restored.unmeasured)
+            .cloned()
+            .collect();
+        for app_id in app_ids {
+            match backend.status(&app_id).await {
+                Ok(status) if status.container_count == 0 => {
+                    warn!(
+                        app_id = %app_id,
+                        event = "APP_CONTAINERS_MISSING",
+                        "App directory left without containers; remove it with ForceRemoveApp"
+                    );
+                    restored.containers_missing.insert(app_id);
+                }
+                Ok(_) => {}
+                Err(e) => warn!(
+                    app_id = %app_id,
+                    error = %e,
+                    "Cannot check the containers of a restored app"
+                ),
+            }
+        }
+        if !restored.measurements.is_empty() || !restored.unmeasured.is_empty() {
+            info!(
+                count = restored.measurements.len(),
+                unmeasured = restored.unmeasured.len(),
+                containers_missing = restored.containers_missing.len(),
+                "Restored apps from the app directories"
+            );
+        }
+        restored
+    }
+}
 
-pub struct BootService {
+/// Deploys apps on a container backend (Docker by default) and keeps their
+/// tasks, measurements and history
+pub struct BootService<B: ContainerBackend = DockerComposeManager> {
     config: BootServiceConfig,
-    manager: Mutex<DockerComposeManager>,
+    backend: B,
+    image_policy: Option<ImagePolicy>,
     app_measurements: Mutex<HashMap<String, AppMeasurement>>,
     aa: Mutex<AttestationAgent>,
+    /// Extends made through `aa` since the guest booted
+    extend_history: ExtendHistory,
     task_manager: TaskManager,
+    deploy_queue: DeployQueue,
     app_compose_content: Mutex<HashMap<String, String>>,
     app_mount_files: Mutex<HashMap<String, String>>,
+    /// Apps restored at startup without containers, until they are removed
+    containers_missing: Mutex<HashSet<String>>,
+    /// Apps restored at startup without a measurement, until they are removed
+    unmeasured_apps: Mutex<HashSet<String>>,
+    app_events: Mutex<HashMap<String, Vec<AppEvent>>>,
+    notifier: Notifier,
+    /// Opens encrypted mount files (None: they are rejected)
+    app_keys: Option<Arc<AppKeyService>>,
+    /// Registers deployed apps on the tapp contract (None: not configured)
+    registrar: Option<Registrar>,
+    /// Cancelled once the service is shutting down
+    shutdown: CancellationToken,
 }
 
 impl BootService {
-    /// Ensure attestation agent config file exists with default values
-    fn ensure_aa_config(config_path: &str) -> TappResult<()> {
-        let path = Path::new(config_path);
+    /// Create new Docker Compose service
+    pub async fn new(config: &BootServiceConfig) -> TappResult<Self> {
+        let manager = DockerComposeManager::new(config).await?;
+        Self::with_backend(config, manager).await
+    }
+}
 
-        // If file already exists, do nothing
-        if path.exists() {
-            return Ok(());
+impl<B: ContainerBackend> BootService<B> {
+    /// Create the service on `backend`, initializing the attestation agent
+    pub async fn with_backend(config: &BootServiceConfig, backend: B) -> TappResult<Self> {
+        // Write the AA config from [boot.aa] unless the operator manages it
+        if let Some(ref aa_config_path) = config.aa_config_path {
+            aa_config::ensure(
+                Path::new(aa_config_path),
+                &config.aa,
+                config.manage_aa_config,
+            )?;
         }
 
-        // Create parent directory if needed
-        if let Some(parent) = path.parent() {
-            std::fs::create_dir_all(parent).map_err(|e| DockerError::ContainerOperationFailed {
-                operation: "create_config_dir".to_string(),
-                reason: format!("Failed to create config directory: {}", e),
-            })?;
+        let mut aa = AttestationAgent::new(config.aa_config_path.as_deref()).unwrap();
+        aa.init().await.unwrap();
+        info!("Detected TEE type: {:?}", aa.get_tee_type());
+
+        let extend_history = ExtendHistory::from_path(&config.extend_history_path)?;
+
+        let task_manager = TaskManager::from_config(config)?;
+        task_manager.start_sweeper();
+
+        let image_policy = ImagePolicy::from_config(config.image_policy.as_ref())?;
+        if let Some(policy) = &image_policy {
+            info!(policy = ?policy.status(), "Image policy enforced for deployments");
         }
 
-        // Create default config file with enable_eventlog = true
-        let default_config = r#"[eventlog_config]
-enable_eventlog = true
-"#;
+        // Apps deployed before a restart
+        let restored = if config.restore_apps {
+            RestoredApps::scan(&backend).await
+        } else {
+            RestoredApps::default()
+        };
 
-        std::fs::write(path, default_config).map_err(|e| {
-            DockerError::ContainerOperationFailed {
-                operation: "write_config".to_string(),
-                reason: format!("Failed to write default config: {}", e),
-            }
-        })?;
+        Ok(Self {
+            config: config.clone(),
+            backend,
+            image_policy,
+            app_measurements: Mutex::new(restored.measurements),
+            aa: Mutex::new(aa),
+            extend_history,
+            task_manager,
+            deploy_queue: DeployQueue::from_config(config),
+            app_compose_content: Mutex::new(restored.compose_content),
+            app_mount_files: Mutex::new(restored.mount_files),
+            containers_missing: Mutex::new(restored.containers_missing),
+            unmeasured_apps: Mutex::new(restored.unmeasured),
+            app_events: Mutex::new(HashMap::new()),
+            notifier: Notifier::disabled(),
+            app_keys: None,
+            registrar: None,
+            shutdown: CancellationToken::new(),
+        })
+    }
+
+    /// Report failed deployments through `notifier`
+    pub fn with_notifier(mut self, notifier: Notifier) -> Self {
+        self.notifier = notifier;
+        self
+    }
+
+    /// Open encrypted mount files with the keys of `app_keys`
+    pub fn with_app_keys(mut self, app_keys: Arc<AppKeyService>) -> Self {
+        self.app_keys = Some(app_keys);
+        self
+    }
+
+    /// Register deployed apps on the tapp contract through `registrar`
+    pub fn with_registrar(mut self, registrar: Registrar) -> Self {
+        self.registrar = Some(registrar);
+        self
+    }
+
+    /// Register a measured app on the tapp contract in the background,
+    /// unless its deployer opted out
+    fn register_on_chain(&self, request: &StartAppRequest, measurement: &AppMeasurement) {
+        let Some(registrar) = &self.registrar else {
+            return;
+        };
+        if !request.register_on_chain.unwrap_or(true) {
+            info!(
+                app_id = %measurement.app_id,
+                "Deployer opted out of the contract registration"
+            );
+            return;
+        }
+        registrar.submit(AppRegistration {
+            app_id: measurement.app_id.clone(),
+            compose_hash: measurement.compose_hash.clone(),
+            volumes_hash: measurement.volumes_hash.clone(),
+            deployer: measurement.deployer_address.clone(),
+        });
+    }
+
+    /// Mount files as written for the containers: encrypted ones opened with
+    /// the app's private key, the others unchanged
+    async fn open_mount_files(
+        &self,
+        app_id: &str,
+        mount_files: &[MountFile],
+    ) -> TappResult<Vec<MountFile>> {
+        if !mount_files.iter().any(|file| file.encrypted) {
+            return Ok(mount_files.to_vec());
+        }
+        let Some(app_keys) = &self.app_keys else {
+            return Err(TappError::InvalidParameter {
+                field: "mount_files".to_string(),
+                reason: "Encrypted mount files are not supported by this service".to_string(),
+            });
+        };
+        let private_key = app_keys.get_private_key(app_id).await?;
 
+        mount_files
+            .iter()
+            .map(|file| {
+                if !file.encrypted {
+                    return Ok(file.clone());
+                }
+                let content = envelope::open(&private_key, app_id, &file.content).map_err(|e| {
+                    TappError::InvalidParameter {
+                        field: "mount_files".to_string(),
+                        reason: format!("Cannot open {}: {}", file.source_path, e),
+                    }
+                })?;
+                Ok(MountFile {
+                    content,
+                    encrypted: false,
+                    ..file.clone()
+                })
+            })
+            .collect()
+    }
+
+    /// The request and mount files as written for compose when the compose
+    /// file has secret placeholders: each one refers to a variable that
+    /// `.env` sets to the value derived for the app
+    async fn resolve_compose_secrets(
+        &self,
+        request: &StartAppRequest,
+        secret_names: &[String],
+        mount_files: Vec<MountFile>,
+    ) -> TappResult<(StartAppRequest, Vec<MountFile>)> {
+        if secret_names.is_empty() {
+            return Ok((request.clone(), mount_files));
+        }
+        let Some(app_keys) = &self.app_keys else {
+            return Err(TappError::InvalidParameter {
+                field: "compose_content".to_string(),
+                reason: format!(
+                    "Secret {} is undefined: this service derives no secrets",
+                    secret_names.join(", ")
+                ),
+            });
+        };
+
+        let mut values = Vec::with_capacity(secret_names.len());
+        for name in secret_names {
+            let value = app_keys.compose_secret(&request.app_id, name).await?;
+            values.push((name.clone(), value));
+        }
         info!(
-            "Created default attestation-agent config at: {}",
-            config_path
+            app_id = %request.app_id,
+            secrets = ?secret_names,
+            "Resolved compose secret placeholders"
         );
+        Ok((
+            StartAppRequest {
+                compose_content: compose_secrets::render(&request.compose_content),
+                ..request.clone()
+            },
+            compose_secrets::with_env_file(mount_files, &values),
+        ))
+    }
+
+    /// Fail with `TappError::Cancelled` once the task was cancelled
+    /// (cancelled tasks are not failed, so the code is never reported)
+    fn check_cancelled(cancel: &CancellationToken) -> Result<(), StepError> {
+        if cancel.is_cancelled() {
+            return Err((TaskErrorCode::Internal, TappError::Cancelled));
+        }
         Ok(())
     }
 
-    /// Create new Docker Compose service
-    pub async fn new(config: &BootServiceConfig) -> TappResult<Self> {
-        let manager = DockerComposeManager::new(&config.socket_path).await?;
+    /// Map the error of a step to its failure code
+    /// Errors that identify their own cause (Docker unreachable, timeout,
+    /// port conflict) take precedence over the step's code.
+    fn at_step(code: TaskErrorCode) -> impl FnOnce(TappError) -> StepError {
+        move |e| {
+            let code = match &e {
+                TappError::Docker(DockerError::ConnectionFailed { .. }) => {
+                    TaskErrorCode::DockerUnavailable
+                }
+                TappError::Docker(DockerError::Timeout { .. }) => TaskErrorCode::Timeout,
+                TappError::Docker(DockerError::ContainerOperationFailed { reason, .. }) => {
+                    if DOCKER_UNAVAILABLE_MARKERS
+                        .iter()
+                        .any(|m| reason.contains(m))
+                    {
+                        TaskErrorCode::DockerUnavailable
+                    } else if PORT_CONFLICT_MARKERS.iter().any(|m| reason.contains(m)) {
+                        TaskErrorCode::PortConflict
+                    } else {
+                        code
+                    }
+                }
+                _ => code,
+            };
+            (code, e)
+        }
+    }
 
-        // Ensure AA config exists with defaults
-        if let Some(ref aa_config_path) = config.aa_config_path {
-            Self::ensure_aa_config(aa_config_path)?;
+    /// Undo a cancelled deployment: take the containers down and release the app ID
+    async fn rollback_start(&self, app_id: &str) {
+        if self.backend.app_dir(app_id).exists() {
+            // Part of the deployment task, not of any RPC
+            if let Err(e) = self.backend.stop(app_id, &CancellationToken::new()).await {
+                warn!(app_id = %app_id, error = %e, "Rollback of cancelled deployment failed");
+            }
         }
+        self.app_compose_content.lock().await.remove(app_id);
+        self.app_mount_files.lock().await.remove(app_id);
+    }
 
-        let mut aa = AttestationAgent::new(config.aa_config_path.as_deref()).unwrap();
-        aa.init().await.unwrap();
-        info!("Detected TEE type: {:?}", aa.get_tee_type());
-        Ok(Self {
-            config: config.clone(),
-            manager: Mutex::new(manager),
-            app_measurements: Mutex::new(HashMap::new()),
-            aa: Mutex::new(aa),
-            task_manager: TaskManager::new(),
-            app_compose_content: Mutex::new(HashMap::new()),
-            app_mount_files: Mutex::new(HashMap::new()),
-        })
+    /// Failures worth retrying: the Docker daemon or a compose command failed
+    fn is_transient(err: &TappError) -> bool {
+        matches!(
+            err,
+            TappError::Docker(
+                DockerError::ConnectionFailed { .. }
+                    | DockerError::ContainerOperationFailed { .. }
+                    | DockerError::Timeout { .. }
+            ) | TappError::Io(_)
+        )
+    }
+
+    /// Pull failures worth retrying right away: the daemon hiccuped or the
+    /// compose command itself failed (registry timeouts and the like)
+    fn is_transient_pull_error(err: &TappError) -> bool {
+        is_transient_daemon_error(err)
+            || matches!(
+                err,
+                TappError::Docker(DockerError::ContainerOperationFailed {
+                    cause: FailureCause::Internal,
+                    ..
+                })
+            )
+    }
+
+    /// Run the Docker steps of a deployment, recording each stage on the task
+    /// Transient failures are retried up to `auto_retry_attempts` times with
+    /// exponential backoff.
+    async fn deploy_steps(
+        task_manager: &TaskManager,
+        backend: &impl ContainerBackend,
+        task_id: &str,
+        request: &StartAppRequest,
+        mount_files: &[MountFile],
+        config: &BootServiceConfig,
+        cancel: &CancellationToken,
+    ) -> Result<(), StepError> {
+        let mut attempt = 0;
+        loop {
+            let result = Self::deploy_attempt(
+                task_manager,
+                backend,
+                task_id,
+                request,
+                mount_files,
+                config,
+                cancel,
+            )
+            .await;
+
+            match result {
+                Err((_, e)) if attempt < config.auto_retry_attempts && Self::is_transient(&e) => {
+                    let delay = RETRY_BASE_DELAY
+                        .saturating_mul(2u32.saturating_pow(attempt))
+                        .min(RETRY_MAX_DELAY);
+                    attempt += 1;
+                    warn!(
+                        task_id = %task_id,
+                        app_id = %request.app_id,
+                        attempt = attempt,
+                        delay_seconds = delay.as_secs(),
+                        error = %e,
+                        "Transient deployment failure, retrying"
+                    );
+                    tokio::select! {
+                        _ = tokio::time::sleep(delay) => {}
+                        _ = cancel.cancelled() => {
+                            return Err((TaskErrorCode::Internal, TappError::Cancelled));
+                        }
+                    }
+                }
+                result => return result,
+            }
+        }
+    }
+
+    /// One pass over the Docker steps; cancellation is checked before every step
+    /// `container_timeout_seconds` bounds `docker compose up` and the health
+    /// wait. The pull is retried on transient failures as `boot.retry` says;
+    /// `up` is not, since a half-finished `up` is left to the retry of the
+    /// whole deployment.
+    async fn deploy_attempt(
+        task_manager: &TaskManager,
+        backend: &impl ContainerBackend,
+        task_id: &str,
+        request: &StartAppRequest,
+        mount_files: &[MountFile],
+        config: &BootServiceConfig,
+        cancel: &CancellationToken,
+    ) -> Result<(), StepError> {
+        let app_id = request.app_id.as_str();
+        let timeout = Duration::from_secs(config.container_timeout_seconds);
+
+        Self::check_cancelled(cancel)?;
+        task_manager
+            .enter_stage(task_id, TaskStage::WritingFiles)
+            .await;
+        backend
+            .write_files(app_id, &request.compose_content, mount_files)
+            .instrument(info_span!(target: SPAN_TARGET, "deploy.write_files"))
+            .await
+            .map_err(Self::at_step(TaskErrorCode::Internal))?;
+
+        Self::check_cancelled(cancel)?;
+        task_manager.enter_stage(task_id, TaskStage::Pulling).await;
+        retry_transient(
+            &config.retry,
+            "docker_compose_pull",
+            Self::is_transient_pull_error,
+            Some(cancel),
+            || backend.pull(app_id, task_id, cancel),
+        )
+        .instrument(info_span!(target: SPAN_TARGET, "deploy.pull"))
+        .await
+        .map_err(Self::at_step(TaskErrorCode::PullFailed))?;
+
+        let scanner = ImageScanner::from_config(config.image_scan.as_ref())
+            .map_err(Self::at_step(TaskErrorCode::Internal))?;
+        if let Some(scanner) = scanner {
+            Self::check_cancelled(cancel)?;
+            task_manager.enter_stage(task_id, TaskStage::Scanning).await;
+            Self::scan_images(task_manager, backend, task_id, app_id, &scanner, cancel)
+                .instrument(info_span!(target: SPAN_TARGET, "deploy.scan"))
+                .await?;
+        }
+
+        Self::check_cancelled(cancel)?;
+        task_manager.enter_stage(task_id, TaskStage::Starting).await;
+        backend
+            .up(app_id, task_id, timeout, cancel)
+            .instrument(info_span!(target: SPAN_TARGET, "deploy.up"))
+            .await
+            .map_err(Self::at_step(TaskErrorCode::StartFailed))?;
+
+        Self::check_cancelled(cancel)?;
+        task_manager
+            .enter_stage(task_id, TaskStage::HealthWait)
+            .await;
+        backend
+            .wait_healthy(app_id, timeout, cancel)
+            .instrument(info_span!(target: SPAN_TARGET, "deploy.wait_healthy"))
+            .await
+            .map_err(Self::at_step(TaskErrorCode::StartFailed))?;
+
+        Self::check_cancelled(cancel)
+    }
+
+    /// Scan the pulled images of an app and record the results on the task
+    /// Fails with the findings of the images that may not be deployed.
+    async fn scan_images(
+        task_manager: &TaskManager,
+        backend: &impl ContainerBackend,
+        task_id: &str,
+        app_id: &str,
+        scanner: &ImageScanner,
+        cancel: &CancellationToken,
+    ) -> Result<(), StepError> {
+        let images = backend
+            .image_digests(app_id)
+            .await
+            .map_err(Self::at_step(TaskErrorCode::ImageScanFailed))?;
+        let results = scanner
+            .scan_all(&images, cancel)
+            .await
+            .map_err(Self::at_step(TaskErrorCode::ImageScanFailed))?;
+        let findings = image_scan::failures(&results, scanner.threshold());
+        task_manager.record_image_scans(task_id, results).await;
+        if findings.is_empty() {
+            return Ok(());
+        }
+        Err((
+            TaskErrorCode::ImageScanFailed,
+            DockerError::ImageScanFailed { findings }.into(),
+        ))
     }
 
     /// Internal method to handle the actual app start logic
+    /// Cancellation is checked between phases; once the measurement is
+    /// recorded the deployment can no longer be cancelled.
     async fn _start_app(&self, request: StartAppRequest, task_id: String) {
+        let cancel = self.task_manager.cancellation_token(&task_id).await;
+        let requested_app_id = request.app_id.clone();
         let result = async {
             let app_id = request.app_id.clone();
+            self.task_manager
+                .enter_stage(&task_id, TaskStage::Validating)
+                .await;
             if self.app_measurements.lock().await.contains_key(&app_id) {
-                return Err(TappError::InvalidParameter {
-                    field: "app_id".to_string(),
-                    reason: format!("Application {} already exists", app_id),
-                }
-                .into());
+                return Err((
+                    TaskErrorCode::InvalidRequest,
+                    TappError::AppAlreadyExists {
+                        app_id: app_id.clone(),
+                    },
+                ));
             }
 
             info!(
@@ -120,47 +632,101 @@ enable_eventlog = true
                     } else {
                         mf.mode.clone()
                     },
+                    encrypted: mf.encrypted,
                 })
                 .collect();
 
+            // Encrypted files are measured as sent and opened only on disk
+            let opened_files = self
+                .open_mount_files(&app_id, &mount_files)
+                .await
+                .map_err(Self::at_step(TaskErrorCode::InvalidRequest))?;
+
+            Self::check_cancelled(&cancel)?;
+
             // Calculate application measurement
             let (measurement, compose_content, volumes_content) = self
                 .calculate_app_measurement(&request, &mount_files, &app_id)
-                .await?;
+                .instrument(info_span!(target: SPAN_TARGET, "deploy.measurement"))
+                .await
+                .map_err(Self::at_step(TaskErrorCode::InvalidCompose))?;
 
-            let measurement_json = serde_json::to_string(&measurement)?;
+            let measurement_json = serde_json::to_string(&measurement)
+                .map_err(|e| Self::at_step(TaskErrorCode::Internal)(e.into()))?;
             info!("measurement_json: {}", measurement_json);
 
+            // Secret placeholders get their values only in the files on disk
+            let (deploy_request, opened_files) = self
+                .resolve_compose_secrets(&request, &measurement.secret_names, opened_files)
+                .await
+                .map_err(Self::at_step(TaskErrorCode::InvalidRequest))?;
+
             self.app_compose_content
                 .lock()
                 .await
-                .insert(app_id.clone(), compose_content);
+                .insert(app_id.clone(), compose_content.clone());
 
             self.app_mount_files
                 .lock()
                 .await
-                .insert(app_id.clone(), volumes_content);
+                .insert(app_id.clone(), volumes_content.clone());
 
             // Start the Docker Compose application with mount files
-            DockerComposeManager::deploy_compose(&app_id, &request.compose_content, &mount_files)
-                .await?;
-
-            // Store measurement in memory
+            let deployed = Self::deploy_steps(
+                &self.task_manager,
+                &self.backend,
+                &task_id,
+                &deploy_request,
+                &opened_files,
+                &self.config,
+                &cancel,
+            )
+            .await;
+            self.record_image_scan_event(&app_id, &task_id).await;
+            deployed?;
+
+            self.task_manager
+                .enter_stage(&task_id, TaskStage::Measuring)
+                .await;
+
+            // Store the measurement in memory, and in the app directory only
+            // once it is part of the RTMR, so a restart never restores one
+            // that was not measured
             self.app_measurements
                 .lock()
                 .await
                 .insert(app_id.clone(), measurement.clone());
 
-            self.aa
-                .lock()
+            if let Err(e) = self
+                .extend_rtmr(OPERATION_NAME_START_APP, &measurement_json)
+                .instrument(info_span!(target: SPAN_TARGET, "deploy.extend_rtmr"))
                 .await
-                .extend_runtime_measurement(
-                    ZGEL_DOMAIN,
-                    OPERATION_NAME_START_APP,
-                    &measurement_json,
-                    None,
-                )
-                .await?;
+            {
+                self.app_measurements.lock().await.remove(&app_id);
+                return Err(Self::at_step(TaskErrorCode::MeasurementFailed)(e));
+            }
+
+            self.save_measurement(&measurement);
+            if let Err(e) = AppMeasurement::save_contents(
+                &self.backend.app_dir(&app_id),
+                &compose_content,
+                &volumes_content,
+            ) {
+                warn!(
+                    app_id = %app_id,
+                    error = %e,
+                    event = "MEASUREMENT_SAVE_FAILED",
+                    "Cannot save the measured compose and mount files; GetAppInfo loses them on restart"
+                );
+            }
+
+            self.record_app_event(&app_id, OPERATION_NAME_START_APP, measurement_json)
+                .await;
+            self.register_on_chain(&request, &measurement);
+
+            self.task_manager
+                .enter_stage(&task_id, TaskStage::Done)
+                .await;
 
             info!(
                 task_id = %task_id,
@@ -168,9 +734,16 @@ enable_eventlog = true
                 "Application started successfully"
             );
 
-            Ok::<_, crate::error::TappError>((app_id, request.deployer))
+            Ok::<_, StepError>((app_id, request.deployer))
         }
+        .instrument(info_span!(
+            target: SPAN_TARGET,
+            "deploy",
+            task_id = %task_id,
+            app_id = %requested_app_id,
+        ))
         .await;
+        self.trim_deploy_logs(&requested_app_id).await;
 
         // Update task status based on result
         match result {
@@ -179,9 +752,43 @@ enable_eventlog = true
                     .mark_completed(&task_id, TaskSuccessResult { app_id, deployer })
                     .await;
             }
-            Err(e) => {
+            // Only cancellation points return Cancelled, all after the
+            // duplicate check, so the rollback never touches another deployment
+            Err((_, TappError::Cancelled)) => {
+                info!(
+                    task_id = %task_id,
+                    app_id = %requested_app_id,
+                    "Deployment cancelled, rolling back"
+                );
+                self.rollback_start(&requested_app_id).await;
+                // Stopped by the shutdown rather than by its deployer
+                if self.shutdown.is_cancelled() {
+                    self.task_manager
+                        .mark_failed(
+                            &task_id,
+                            TaskErrorCode::Interrupted,
+                            SHUTDOWN_REASON.to_string(),
+                        )
+                        .await;
+                } else {
+                    self.task_manager.mark_cancelled(&task_id).await;
+                }
+            }
+            Err((code, e)) => {
+                warn!(
+                    task_id = %task_id,
+                    app_id = %requested_app_id,
+                    error = %e,
+                    "Deployment failed"
+                );
+                self.notifier.notify(NotificationEvent::TaskFailed {
+                    task_id: task_id.clone(),
+                    app_id: requested_app_id.clone(),
+                    error_code: code.to_proto().as_str_name().to_string(),
+                    message: e.to_string(),
+                });
                 self.task_manager
-                    .mark_failed(&task_id, format!("{}", e))
+                    .mark_failed(&task_id, code, format!("{}", e))
                     .await;
             }
         }
@@ -189,15 +796,33 @@ enable_eventlog = true
 
     /// Handle start app request (async - returns task ID immediately)
     /// This method should be called with Arc<Self> from the service implementation
+    /// `request_id` identifies the RPC; the deployment's logs carry it too.
     pub async fn start_app(
         self: std::sync::Arc<Self>,
         request: StartAppRequest,
+        request_id: Option<&str>,
     ) -> TappResult<StartAppResponse> {
         // Validate request
         self.validate_request(&request)?;
-
-        // Create a new task
-        let task = self.task_manager.create_task().await;
+        let request = self.apply_platforms(request).await?;
+
+        // Create a new task; one deployment per app at a time
+        let task = self
+            .task_manager
+            .create_task_if_idle(
+                TaskKind::StartApp,
+                &request.app_id,
+                &request.deployer,
+                request_id,
+            )
+            .await
+            .map_err(|existing| TappError::InvalidParameter {
+                field: "app_id".to_string(),
+                reason: format!(
+                    "Application {} already has a task in progress: {}",
+                    request.app_id, existing.id
+                ),
+            })?;
         let task_id = task.id.clone();
 
         info!(
@@ -206,17 +831,7 @@ enable_eventlog = true
             "Created task for starting application"
         );
 
-        // Mark task as running
-        self.task_manager.mark_running(&task_id).await;
-
-        // Clone Arc for background task
-        let service = self.clone();
-        let task_id_clone = task_id.clone();
-
-        // Spawn background task
-        tokio::spawn(async move {
-            service._start_app(request, task_id_clone).await;
-        });
+        self.launch(task_id.clone(), request).await?;
 
         Ok(StartAppResponse {
             success: true,
@@ -226,9 +841,246 @@ enable_eventlog = true
         })
     }
 
+    /// Run a created StartApp task in the background, or queue it if every
+    /// deployment slot is taken
+    /// The task is discarded if the queue is full or the service is shutting
+    /// down.
+    async fn launch(
+        self: std::sync::Arc<Self>,
+        task_id: String,
+        request: StartAppRequest,
+    ) -> TappResult<()> {
+        if self.shutdown.is_cancelled() {
+            self.task_manager.discard(&task_id).await;
+            return Err(TappError::ServiceUnavailable {
+                service: "TAPP (shutting down)".to_string(),
+            });
+        }
+        self.task_manager
+            .store_inputs(&task_id, request.clone())
+            .await;
+
+        let next = match self.deploy_queue.enqueue(task_id.clone(), request) {
+            Ok(next) => next,
+            Err(e) => {
+                self.task_manager.discard(&task_id).await;
+                return Err(e);
+            }
+        };
+        let Some(next) = next else {
+            self.task_manager.mark_queued(&task_id).await;
+            info!(
+                task_id = %task_id,
+                position = ?self.deploy_queue.position(&task_id),
+                "Deployment queued"
+            );
+            return Ok(());
+        };
+
+        // Spawn background task; it keeps the slot while deployments are waiting
+        tokio::spawn(async move {
+            let mut next = Some(next);
+            while let Some((task_id, request)) = next {
+                let span = self.task_span(&task_id).await;
+                self.task_manager.mark_running(&task_id).await;
+                let app_id = request.app_id.clone();
+                let deployment = self._start_app(request, task_id.clone());
+                if let Err(panic) = catch_panic(deployment.instrument(span.clone())).await {
+                    self.fail_panicked(&task_id, &app_id, panic)
+                        .instrument(span)
+                        .await;
+                }
+                next = self.deploy_queue.finish();
+            }
+        });
+        Ok(())
+    }
+
+    /// Fail a deployment that panicked instead of leaving it running
+    async fn fail_panicked(&self, task_id: &str, app_id: &str, panic: CaughtPanic) {
+        error!(
+            event = "DEPLOYMENT_PANICKED",
+            task_id = %task_id,
+            app_id = %app_id,
+            panic = %panic.message,
+            backtrace = %panic.backtrace(),
+            "Deployment panicked"
+        );
+        let message = format!("deployment panicked: {}", panic.message);
+        self.notifier.notify(NotificationEvent::TaskFailed {
+            task_id: task_id.to_string(),
+            app_id: app_id.to_string(),
+            error_code: TaskErrorCode::Panicked.to_proto().as_str_name().to_string(),
+            message: message.clone(),
+        });
+        self.task_manager
+            .mark_failed(task_id, TaskErrorCode::Panicked, message)
+            .await;
+    }
+
+    /// Wind deployments down before the process exits
+    /// New deployments are refused and queued ones fail as interrupted.
+    /// Running ones get `grace` to finish; after that they are cancelled at
+    /// their next step, rolled back and failed as interrupted. Tasks still
+    /// unfinished then are recorded as interrupted, as a restart would.
+    pub async fn shutdown(&self, grace: Duration) {
+        self.shutdown.cancel();
+        for task_id in self.deploy_queue.drain() {
+            info!(task_id = %task_id, "Queued deployment dropped by shutdown");
+            self.task_manager
+                .mark_failed(
+                    &task_id,
+                    TaskErrorCode::Interrupted,
+                    SHUTDOWN_REASON.to_string(),
+                )
+                .await;
+        }
+
+        if tokio::time::timeout(grace, self.tasks_finished())
+            .await
+            .is_ok()
+        {
+            return;
+        }
+        let running = self.task_manager.unfinished_task_ids().await;
+        warn!(
+            count = running.len(),
+            "Deployments still running after the shutdown grace period, cancelling them"
+        );
+        for task_id in &running {
+            self.task_manager.request_cancel(task_id).await;
+        }
+
+        if tokio::time::timeout(SHUTDOWN_CANCEL_WAIT, self.tasks_finished())
+            .await
+            .is_err()
+        {
+            self.task_manager
+                .interrupt_unfinished(SHUTDOWN_REASON)
+                .await;
+        }
+    }
+
+    /// Wait until no task is unfinished
+    async fn tasks_finished(&self) {
+        let mut updates = self.task_manager.subscribe().await;
+        while !self.task_manager.unfinished_task_ids().await.is_empty() {
+            // Lagging behind only means there is more to re-check
+            let _ = updates.recv().await;
+        }
+    }
+
+    /// Log span of a deployment, carrying the ID of the request that created it
+    async fn task_span(&self, task_id: &str) -> tracing::Span {
+        let request_id = self
+            .task_manager
+            .get_task(task_id)
+            .await
+            .and_then(|task| task.request_id);
+        info_span!(
+            "task",
+            task_id = %task_id,
+            request_id = request_id.as_deref().map(tracing::field::display),
+        )
+    }
+
+    /// Re-run a failed StartApp task with its original request
+    /// Returns the new task, which records the failed one in `retried_from`.
+    pub async fn retry_task(
+        self: std::sync::Arc<Self>,
+        task: &Task,
+        request_id: Option<&str>,
+    ) -> TappResult<Task> {
+        if !matches!(task.status, TaskState::Failed(..)) {
+            return Err(TappError::InvalidParameter {
+                field: "task_id".to_string(),
+                reason: format!("Only failed tasks can be retried, {} is not", task.id),
+            });
+        }
+
+        // Another task may have deployed the app since
+        if self
+            .app_measurements
+            .lock()
+            .await
+            .contains_key(&task.app_id)
+        {
+            return Err(TappError::InvalidParameter {
+                field: "task_id".to_string(),
+                reason: format!(
+                    "Application {} has since been started successfully",
+                    task.app_id
+                ),
+            });
+        }
+        let in_flight = |existing: Task| TappError::InvalidParameter {
+            field: "task_id".to_string(),
+            reason: format!(
+                "Application {} already has a task in progress: {}",
+                task.app_id, existing.id
+            ),
+        };
+        if let Some(existing) = self.task_manager.in_flight_task(&task.app_id).await {
+            return Err(in_flight(existing));
+        }
+
+        let request = self
+            .task_manager
+            .retry_inputs(&task.id)
+            .await
+            .ok_or_else(|| TappError::InvalidParameter {
+                field: "task_id".to_string(),
+                reason: format!(
+                    "The request of task {} is no longer available (lost on restart)",
+                    task.id
+                ),
+            })?;
+
+        let new_task = self
+            .task_manager
+            .create_retry_task(task, request_id)
+            .await
+            .map_err(in_flight)?;
+        info!(
+            task_id = %new_task.id,
+            retried_from = %task.id,
+            app_id = %task.app_id,
+            "Created task retrying failed deployment"
+        );
+
+        self.launch(new_task.id.clone(), request).await?;
+        Ok(new_task)
+    }
+
     /// Get task status
-    pub async fn get_task_status(&self, task_id: &str) -> Option<Task> {
-        self.task_manager.get_task(task_id).await
+    pub async fn get_task_status(&self, task_id: &str) -> TaskLookup {
+        self.task_manager.lookup_task(task_id).await
+    }
+
+    /// Request cancellation of a StartApp task
+    /// A queued task is taken out of the queue and cancelled without running.
+    pub async fn cancel_task(&self, task_id: &str) -> TaskLookup {
+        if self.deploy_queue.remove(task_id) {
+            info!(task_id = %task_id, "Queued deployment cancelled");
+            self.task_manager.mark_cancelled(task_id).await;
+            return self.task_manager.lookup_task(task_id).await;
+        }
+        self.task_manager.request_cancel(task_id).await
+    }
+
+    /// IDs of tasks as they change, for following a task
+    pub async fn watch_tasks(&self) -> tokio::sync::broadcast::Receiver<String> {
+        self.task_manager.subscribe().await
+    }
+
+    /// 1-based position of a task in the deployment queue, if it is waiting
+    pub fn queue_position(&self, task_id: &str) -> Option<usize> {
+        self.deploy_queue.position(task_id)
+    }
+
+    /// How long finished tasks stay queryable
+    pub fn task_retention(&self) -> std::time::Duration {
+        self.task_manager.retention()
     }
 
     /// Get the hash algorithm currently in use
@@ -242,36 +1094,122 @@ enable_eventlog = true
     }
 
     /// Get application logs from docker compose
+    /// `cancel` ends the RPC's `docker compose logs`.
     pub async fn get_app_logs(
         &self,
         app_id: &str,
         lines: i32,
         service_name: Option<&str>,
+        cancel: &CancellationToken,
     ) -> TappResult<String> {
-        DockerComposeManager::get_app_logs(app_id, lines, service_name).await
+        self.backend.logs(app_id, lines, service_name, cancel).await
     }
 
-    /// List all app measurements
-    pub async fn list_app_measurements(
+    /// Captured docker compose output of a deployment, or of the app's
+    /// latest one, as (task_id, content); `lines` > 0 keeps only the last lines
+    pub async fn get_deploy_log(
         &self,
-        deployer_filter: Option<String>,
-    ) -> Vec<AppMeasurement> {
-        let measurements = self.app_measurements.lock().await;
+        app_id: &str,
+        task_id: Option<&str>,
+        lines: i32,
+    ) -> TappResult<(String, String)> {
+        let app_dir = self.backend.app_dir(app_id);
+        if !app_dir.exists() {
+            return Err(TappError::InvalidParameter {
+                field: "app_id".to_string(),
+                reason: format!("App {} not found", app_id),
+            });
+        }
+        let (task_id, content) = deploy_log::read(&app_dir, task_id).await?;
+        if lines > 0 {
+            return Ok((task_id, deploy_log::tail(&content, lines as usize)));
+        }
+        Ok((task_id, content))
+    }
 
-        let mut result: Vec<AppMeasurement> = measurements
-            .values()
-            .filter(|m| {
-                // Apply deployer filter if provided
-                if let Some(ref filter) = deployer_filter {
-                    // Filter can be with or without 0x prefix
-                    let filter_normalized = filter.trim_start_matches("0x").to_lowercase();
-                    m.deployer.to_lowercase().contains(&filter_normalized)
-                } else {
-                    true
+    /// Keep only the newest `deploy_logs_per_app` deploy logs of an app
+    async fn trim_deploy_logs(&self, app_id: &str) {
+        let app_dir = self.backend.app_dir(app_id);
+        match deploy_log::trim(&app_dir, self.config.deploy_logs_per_app).await {
+            Ok(removed) => {
+                for path in removed {
+                    debug!(app_id = %app_id, path = %path.display(), "Removed old deploy log");
                 }
-            })
-            .cloned()
-            .collect();
+            }
+            Err(e) => warn!(app_id = %app_id, error = %e, "Failed to trim deploy logs"),
+        }
+    }
+
+    /// Write an app's measurement to its directory, for `with_backend` to
+    /// load after a restart
+    /// A failure is only logged: the app keeps running with the measurement
+    /// in memory.
+    fn save_measurement(&self, measurement: &AppMeasurement) {
+        if let Err(e) = measurement.save(&self.backend.app_dir(&measurement.app_id)) {
+            warn!(
+                app_id = %measurement.app_id,
+                error = %e,
+                event = "MEASUREMENT_SAVE_FAILED",
+                "Cannot save the app measurement; it is lost on restart"
+            );
+        }
+    }
+
+    /// Apps restored at startup whose containers were all gone, left for an
+    /// operator to remove with ForceRemoveApp
+    pub async fn apps_missing_containers(&self) -> HashSet<String> {
+        self.containers_missing.lock().await.clone()
+    }
+
+    /// Apps restored at startup from directories without a measurement, left
+    /// by deployments from before measurements were saved
+    /// They can only be removed with ForceRemoveApp.
+    pub async fn unmeasured_apps(&self) -> HashSet<String> {
+        self.unmeasured_apps.lock().await.clone()
+    }
+
+    /// Get the measurement record of a single app
+    pub async fn get_app_measurement(&self, app_id: &str) -> Option<AppMeasurement> {
+        self.app_measurements.lock().await.get(app_id).cloned()
+    }
+
+    /// Registration of an app on the tapp contract, if one was submitted
+    pub fn app_registration(&self, app_id: &str) -> Option<RegistrationStatus> {
+        self.registrar.as_ref()?.status(app_id)
+    }
+
+    /// List all app measurements
+    /// A filter naming a whole deployer, in any accepted form, matches that
+    /// deployer's address; anything else matches hex substrings of the
+    /// stored deployer or address.
+    pub async fn list_app_measurements(
+        &self,
+        deployer_filter: Option<String>,
+    ) -> Vec<AppMeasurement> {
+        let filter = deployer_filter.map(|filter| {
+            // Filter can be with or without 0x prefix
+            let normalized = filter.trim_start_matches("0x").to_lowercase();
+            let address = DeployerIdentity::from_hex(&normalized)
+                .ok()
+                .map(|identity| identity.address_hex());
+            (normalized, address)
+        });
+        let measurements = self.app_measurements.lock().await;
+
+        let mut result: Vec<AppMeasurement> = measurements
+            .values()
+            .filter(|m| {
+                // Apply deployer filter if provided
+                match &filter {
+                    Some((_, Some(address))) => m.deployer_address == *address,
+                    Some((normalized, None)) => {
+                        m.deployer.contains(normalized) || m.deployer_address.contains(normalized)
+                    }
+                    None => true,
+                }
+            })
+            .cloned()
+            .collect();
 
         // Sort by timestamp descending (newest first)
         result.sort_by(|a, b| b.timestamp.cmp(&a.timestamp));
@@ -305,7 +1243,9 @@ enable_eventlog = true
 
         info!("report_data: {:?}", hex::encode(&report_data));
 
-        let evidence = self.aa.lock().await.get_evidence(&report_data).await?;
+        let evidence = async { self.aa.lock().await.get_evidence(&report_data).await }
+            .instrument(info_span!(target: SPAN_TARGET, "evidence"))
+            .await?;
         Ok(GetEvidenceResponse {
             success: true,
             message: "Evidence generated successfully".to_string(),
@@ -315,6 +1255,42 @@ enable_eventlog = true
         })
     }
 
+    /// Policy deployed images are checked against, if any
+    pub fn image_policy(&self) -> Option<&ImagePolicy> {
+        self.image_policy.as_ref()
+    }
+
+    /// Directory holding the app directories
+    pub fn data_dir(&self) -> &Path {
+        self.backend.data_dir()
+    }
+
+    /// Container engine the apps run on
+    pub fn runtime(&self) -> ContainerRuntime {
+        self.backend.runtime()
+    }
+
+    /// Check that the Docker daemon is reachable
+    pub async fn check_docker(&self) -> TappResult<()> {
+        self.backend.ping().await
+    }
+
+    /// Containers, start time and restarts of a deployed app
+    pub async fn app_status(&self, app_id: &str) -> TappResult<AppStatus> {
+        self.backend.status(app_id).await
+    }
+
+    /// Docker data root and running containers, for host resource reporting
+    pub async fn docker_host_info(&self) -> TappResult<DockerHostInfo> {
+        self.backend.host_info().await
+    }
+
+    /// Check that the attestation agent produces evidence
+    pub async fn check_attestation(&self) -> TappResult<()> {
+        self.aa.lock().await.get_evidence(&[0u8; 64]).await?;
+        Ok(())
+    }
+
     /// Validate start app request
     fn validate_request(&self, request: &StartAppRequest) -> TappResult<()> {
         if request.compose_content.is_empty() {
@@ -331,23 +1307,75 @@ enable_eventlog = true
             .into());
         }
 
-        if !crate::utils::validate_app_id(&request.app_id) {
+        if !crate::namespace::validate_qualified_app_id(&request.app_id) {
             return Err(DockerError::InvalidComposeContent {
                 reason: format!("Invalid app ID format: {}", request.app_id),
             }
             .into());
         }
 
-        if request.deployer.len() != 64 {
-            return Err(DockerError::InvalidComposeContent {
-                reason: "Deployer must be 64 bytes".to_string(),
+        if let Err(reason) = DeployerIdentity::from_bytes(&request.deployer) {
+            return Err(DockerError::InvalidComposeContent { reason }.into());
+        }
+
+        if let Some(policy) = &self.image_policy {
+            if let Err(e) = policy.check_compose(&request.compose_content) {
+                warn!(
+                    app_id = %request.app_id,
+                    error = %e,
+                    event = "IMAGE_POLICY_REJECTED",
+                    "Deployment rejected by the image policy"
+                );
+                return Err(e);
             }
-            .into());
         }
 
         Ok(())
     }
 
+    /// `request` with the requested platforms written to its compose file
+    /// (see `platform`), once the host is known to run them
+    /// The host is only asked when some service has a platform.
+    async fn apply_platforms(&self, request: StartAppRequest) -> TappResult<StartAppRequest> {
+        let compose_content = platform::inject(
+            &request.compose_content,
+            &request.platform,
+            &request.service_platforms,
+        )?;
+        let platforms = platform::service_platforms(&compose_content)?;
+        if platforms.is_empty() {
+            return Ok(request);
+        }
+
+        let info = self.backend.host_info().await?;
+        let (Some(os_type), Some(architecture)) = (info.os_type, info.architecture) else {
+            return Err(TappError::ServiceUnavailable {
+                service: "Docker (host platform unknown)".to_string(),
+            });
+        };
+        let host = platform::Platform::host(&os_type, &architecture);
+        if let Err(e) = platform::check(&platforms, &host, self.config.allow_emulation) {
+            warn!(
+                app_id = %request.app_id,
+                error = %e,
+                "Deployment rejected: platform mismatch"
+            );
+            return Err(e);
+        }
+        if !platforms.values().all(|platform| host.runs(platform)) {
+            warn!(
+                app_id = %request.app_id,
+                host = %host,
+                "Deploying images of a foreign platform under emulation"
+            );
+        }
+
+        Ok(StartAppRequest {
+            compose_content,
+            ..request
+        })
+    }
+
     /// Calculate application measurement
     async fn calculate_app_measurement(
         &self,
@@ -356,6 +1384,8 @@ enable_eventlog = true
         app_id: &str,
     ) -> TappResult<(AppMeasurement, String, String)> {
         let measurement = ComposeMeasurement::new();
+        let deployer = DeployerIdentity::from_bytes(&request.deployer)
+            .map_err(|reason| DockerError::InvalidComposeContent { reason })?;
 
         // Calculate compose file hash
         // println!("compose_content: {}", request.compose_content);
@@ -369,13 +1399,19 @@ enable_eventlog = true
             measurement.calculate_mount_files_hash(mount_files)?;
         // println!("volumes_hash: {:?}", volumes_hash);
 
+        // Placeholders are measured by name; their values never are
+        let secret_names = compose_secrets::scan(&request.compose_content)?;
+
         Ok((
             AppMeasurement {
                 app_id: app_id.to_string(),
                 compose_hash,
                 volumes_hash,
-                deployer: hex::encode(request.deployer.clone()),
+                deployer: deployer.canonical_hex(),
+                deployer_address: deployer.address_hex(),
                 timestamp: crate::utils::current_timestamp(),
+                namespace: crate::namespace::split(app_id).0.map(str::to_string),
+                secret_names,
             },
             request.compose_content.clone(),
             volumes_content,
@@ -383,19 +1419,22 @@ enable_eventlog = true
     }
 
     /// Stop application
-    pub async fn stop_app(&self, app_id: &str) -> TappResult<()> {
+    /// Once `cancel` is cancelled `docker compose down` is killed and the
+    /// app directory kept.
+    pub async fn stop_app(&self, app_id: &str, cancel: &CancellationToken) -> TappResult<()> {
         info!(app_id = %app_id, "Stopping application");
 
         // 1. Stop compose
-        DockerComposeManager::stop_compose(app_id).await?;
+        self.backend.stop(app_id, cancel).await?;
 
         // 2. Delete app directory
-        let app_dir = DockerComposeManager::get_app_dir(app_id);
+        let app_dir = self.backend.app_dir(app_id);
         if app_dir.exists() {
             tokio::fs::remove_dir_all(&app_dir).await.map_err(|e| {
                 TappError::Docker(DockerError::ContainerOperationFailed {
                     operation: "delete_app_dir".to_string(),
                     reason: format!("Failed to delete app directory: {}", e),
+                    cause: FailureCause::Internal,
                 })
             })?;
             info!(app_id = %app_id, "App directory deleted successfully");
@@ -406,24 +1445,304 @@ enable_eventlog = true
             info!(app_id = %app_id, "Extending runtime measurement for stop operation");
             let measurement_json = serde_json::to_string(&measurement)?;
 
-            self.aa
-                .lock()
-                .await
-                .extend_runtime_measurement(
-                    ZGEL_DOMAIN,
-                    OPERATION_NAME_STOP_APP,
-                    &measurement_json,
-                    None,
-                )
+            self.extend_rtmr(OPERATION_NAME_STOP_APP, &measurement_json)
                 .await?;
 
             info!(app_id = %app_id, "Runtime measurement extended for stop operation");
         }
 
+        self.record_app_event(app_id, OPERATION_NAME_STOP_APP, String::new())
+            .await;
+
         info!(app_id = %app_id, "Application stopped successfully");
         Ok(())
     }
 
+    /// Remove whatever is left of an app, whatever state it is in
+    /// Best effort: cancels an in-flight task holding the app ID, takes its
+    /// compose project down, deletes its directory and forgets its
+    /// measurement, compose content and mount files. A failed step does not
+    /// stop the others; it is listed in `errors`. Removing an app that is
+    /// already gone reports nothing cleaned. Cancelling `cancel` kills the
+    /// compose command; the steps after it still run.
+    pub async fn force_remove_app(
+        &self,
+        app_id: &str,
+        purge_volumes: bool,
+        purge_images: bool,
+        cancel: &CancellationToken,
+    ) -> TappResult<ForceRemoval> {
+        // The app ID names the directory that is deleted
+        if !crate::namespace::validate_qualified_app_id(app_id) {
+            return Err(TappError::InvalidParameter {
+                field: "app_id".to_string(),
+                reason: format!("Invalid app ID format: {}", app_id),
+            });
+        }
+        warn!(
+            app_id = %app_id,
+            purge_volumes,
+            purge_images,
+            event = "APP_FORCE_REMOVE_STARTED",
+            "Force-removing application"
+        );
+
+        let mut removal = ForceRemoval {
+            app_id: app_id.to_string(),
+            purge_volumes,
+            purge_images,
+            ..Default::default()
+        };
+
+        // 1. Release the app ID: a deployment in progress must not recreate
+        //    what is removed below
+        let in_flight = self.task_manager.in_flight_task(app_id).await;
+        if let Some(task) = in_flight.filter(|task| !matches!(task.status, TaskState::Cancelling)) {
+            self.cancel_task(&task.id).await;
+            warn!(app_id = %app_id, task_id = %task.id, "In-flight task cancelled by force removal");
+            removal.cancelled_task = Some(task.id);
+        }
+
+        // 2. Containers, found by compose project even without a directory
+        match self.backend.status(app_id).await {
+            Ok(status) => {
+                let count = status.container_count;
+                if count > 0 || purge_volumes || purge_images {
+                    match self
+                        .backend
+                        .remove_project(app_id, purge_volumes, purge_images, cancel)
+                        .await
+                    {
+                        Ok(()) => removal.containers_removed = count,
+                        Err(e) => removal.errors.push(format!("containers: {}", e)),
+                    }
+                }
+            }
+            Err(e) => removal.errors.push(format!("containers: {}", e)),
+        }
+
+        // 3. App directory
+        let app_dir = self.backend.app_dir(app_id);
+        if app_dir.exists() {
+            match tokio::fs::remove_dir_all(&app_dir).await {
+                Ok(()) => removal.app_dir_removed = true,
+                Err(e) => removal.errors.push(format!("app_dir: {}", e)),
+            }
+        }
+
+        // 4. In-memory state
+        let measurement = self.app_measurements.lock().await.remove(app_id);
+        removal.measurement_removed = measurement.is_some();
+        if let Some(registrar) = &self.registrar {
+            registrar.forget(app_id);
+        }
+        removal.compose_content_removed = self
+            .app_compose_content
+            .lock()
+            .await
+            .remove(app_id)
+            .is_some();
+        removal.mount_files_removed = self.app_mount_files.lock().await.remove(app_id).is_some();
+        self.containers_missing.lock().await.remove(app_id);
+        self.unmeasured_apps.lock().await.remove(app_id);
+
+        // 5. Attest the removal of a measured app, and keep it in the history
+        removal.timestamp = crate::utils::current_timestamp();
+        if removal.measurement_removed {
+            let removal_json = serde_json::to_string(&removal)?;
+            if let Err(e) = self
+                .extend_rtmr(OPERATION_NAME_FORCE_REMOVE_APP, &removal_json)
+                .await
+            {
+                removal.errors.push(format!("runtime_measurement: {}", e));
+            }
+        }
+        self.record_app_event(
+            app_id,
+            OPERATION_NAME_FORCE_REMOVE_APP,
+            serde_json::to_string(&removal)?,
+        )
+        .await;
+
+        warn!(
+            app_id = %app_id,
+            cleaned = ?removal.cleaned(),
+            cancelled_task = ?removal.cancelled_task,
+            containers_removed = removal.containers_removed,
+            errors = ?removal.errors,
+            event = "APP_FORCE_REMOVED",
+            "Application force-removed"
+        );
+        Ok(removal)
+    }
+
+    /// Transfer ownership of an application to a new deployer key
+    /// `expected_deployer` is the owner the caller verified; the transfer is
+    /// rejected if ownership changed in the meantime. The transfer record is
+    /// extended into the runtime measurement before the deployer is updated,
+    /// so every effective owner change is attestable.
+    pub async fn transfer_app_ownership(
+        &self,
+        app_id: &str,
+        expected_deployer: &str,
+        new_deployer: &[u8],
+    ) -> TappResult<OwnershipTransfer> {
+        let new_deployer = DeployerIdentity::from_bytes(new_deployer).map_err(|reason| {
+            TappError::InvalidParameter {
+                field: "new_deployer".to_string(),
+                reason,
+            }
+        })?;
+
+        let mut measurements = self.app_measurements.lock().await;
+        let measurement =
+            measurements
+                .get_mut(app_id)
+                .ok_or_else(|| TappError::InvalidParameter {
+                    field: "app_id".to_string(),
+                    reason: format!("App {} not found", app_id),
+                })?;
+
+        if measurement.deployer != expected_deployer {
+            return Err(TappError::InvalidParameter {
+                field: "app_id".to_string(),
+                reason: format!("Ownership of app {} changed concurrently", app_id),
+            });
+        }
+
+        let transfer = OwnershipTransfer {
+            app_id: app_id.to_string(),
+            previous_deployer: measurement.deployer.clone(),
+            new_deployer: new_deployer.canonical_hex(),
+            timestamp: crate::utils::current_timestamp(),
+        };
+        let transfer_json = serde_json::to_string(&transfer)?;
+
+        self.extend_rtmr(OPERATION_NAME_TRANSFER_OWNERSHIP, &transfer_json)
+            .await?;
+
+        measurement.deployer = transfer.new_deployer.clone();
+        measurement.deployer_address = new_deployer.address_hex();
+        self.save_measurement(measurement);
+        drop(measurements);
+
+        self.record_app_event(app_id, OPERATION_NAME_TRANSFER_OWNERSHIP, transfer_json)
+            .await;
+
+        info!(
+            app_id = %app_id,
+            previous_deployer = %transfer.previous_deployer,
+            new_deployer = %transfer.new_deployer,
+            "Application ownership transferred"
+        );
+        Ok(transfer)
+    }
+
+    /// Extend the runtime measurement with the record of a keystore backup
+    /// export or import (`OPERATION_NAME_*_KEYSTORE_BACKUP`)
+    /// The record names keys by fingerprint only, never their material.
+    pub async fn attest_keystore_operation(&self, operation: &str, record: &str) -> TappResult<()> {
+        self.extend_rtmr(operation, record).await?;
+        info!(operation = %operation, "Keystore operation extended into the runtime measurement");
+        Ok(())
+    }
+
+    /// Extend the runtime measurement with `content` and add it to the
+    /// extend history
+    /// The agent stays locked until the extend is recorded, so the history
+    /// is in the order of the register.
+    async fn extend_rtmr(&self, operation: &str, content: &str) -> TappResult<()> {
+        let aa = self.aa.lock().await;
+        aa.extend_runtime_measurement(ZGEL_DOMAIN, operation, content, None)
+            .await?;
+        if let Err(e) =
+            self.extend_history
+                .record(rtmr::TAPP_RTMR_INDEX, ZGEL_DOMAIN, operation, content)
+        {
+            error!(
+                operation = %operation,
+                error = %e,
+                event = "EXTEND_HISTORY_FAILED",
+                "Runtime measurement extended but not recorded; its replay will not match"
+            );
+        }
+        Ok(())
+    }
+
+    /// Replay of the extends of RTMR `rtmr_index` recorded since boot, and
+    /// the register's value in a fresh quote
+    /// No extend happens between the two.
+    pub async fn replay_rtmr(&self, rtmr_index: u32) -> TappResult<(Replay, Vec<u8>)> {
+        if rtmr_index >= rtmr::RTMR_COUNT {
+            return Err(TappError::InvalidParameter {
+                field: "rtmr_index".to_string(),
+                reason: format!(
+                    "RTMR index must be below {}, got {}",
+                    rtmr::RTMR_COUNT,
+                    rtmr_index
+                ),
+            });
+        }
+        let aa = self.aa.lock().await;
+        let replay = Replay::run(rtmr_index, self.extend_history.records(rtmr_index));
+        let evidence = aa.get_evidence(&[0u8; 64]).await?;
+        drop(aa);
+
+        let quote = quote::parse_quote(&quote::quote_from_evidence(&evidence)?)?;
+        let quoted = quote.report.rtmrs[rtmr_index as usize].clone();
+        Ok((replay, quoted))
+    }
+
+    /// Append an entry to an application's event history
+    async fn record_app_event(&self, app_id: &str, event: &str, detail: String) {
+        self.app_events
+            .lock()
+            .await
+            .entry(app_id.to_string())
+            .or_default()
+            .push(AppEvent {
+                event: event.to_string(),
+                detail,
+                timestamp: crate::utils::current_timestamp(),
+            });
+    }
+
+    /// Add the image scan of a task to the app's history, if it scanned any
+    async fn record_image_scan_event(&self, app_id: &str, task_id: &str) {
+        let Some(task) = self.task_manager.get_task(task_id).await else {
+            return;
+        };
+        if task.image_scans.is_empty() {
+            return;
+        }
+        match serde_json::to_string(&task.image_scans) {
+            Ok(detail) => {
+                self.record_app_event(app_id, EVENT_IMAGE_SCAN, detail)
+                    .await
+            }
+            Err(e) => warn!(app_id = %app_id, error = %e, "Cannot record image scan"),
+        }
+    }
+
+    /// Get the event history of an application (oldest first)
+    pub async fn get_app_events(&self, app_id: &str) -> Vec<AppEvent> {
+        self.app_events
+            .lock()
+            .await
+            .get(app_id)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// Record a measurement without deploying the app (tests only)
+    #[cfg(test)]
+    pub(crate) async fn insert_app_measurement(&self, measurement: AppMeasurement) {
+        self.app_measurements
+            .lock()
+            .await
+            .insert(measurement.app_id.clone(), measurement);
+    }
+
     pub async fn get_app_compose_content(&self, app_id: &str) -> TappResult<Option<String>> {
         let compose_content = self.app_compose_content.lock().await.get(app_id).cloned();
         Ok(compose_content)
@@ -437,10 +1756,18 @@ enable_eventlog = true
 
 #[cfg(test)]
 mod tests {
+    use super::backend::{FakeBackend, FakeFailure};
     use super::*;
+    use crate::config::{ContractConfig, ImagePolicyConfig, RetryConfig};
+    use crate::contract::registrar::RegistrationState;
+    use crate::contract::{ContractClient, TxStatus};
+    use futures_util::future::BoxFuture;
     use std::fs::File;
     use std::sync::Arc;
 
+    /// Address-only deployer of the test requests
+    const TEST_DEPLOYER: [u8; 20] = [0x11; 20];
+
     fn create_test_request() -> StartAppRequest {
         StartAppRequest {
             compose_content: r#"
@@ -457,10 +1784,14 @@ volumes:
             .to_string(),
             app_id: "test-nginx-app".to_string(),
             mount_files: vec![],
-            deployer: vec![0; 32],
+            deployer: TEST_DEPLOYER.to_vec(),
+            register_on_chain: None,
+            platform: String::new(),
+            service_platforms: Default::default(),
         }
     }
 
+    #[cfg(any(feature = "docker-tests", feature = "podman-tests"))]
     fn create_real_request() -> StartAppRequest {
         StartAppRequest {
             compose_content: r#"
@@ -475,7 +1806,10 @@ volumes:
             .to_string(),
             app_id: "test-hello-app".to_string(),
             mount_files: vec![],
-            deployer: vec![0; 32],
+            deployer: TEST_DEPLOYER.to_vec(),
+            register_on_chain: None,
+            platform: String::new(),
+            service_platforms: Default::default(),
         }
     }
 
@@ -499,28 +1833,744 @@ services:
                     source_path: "./nginx.conf".to_string(),
                     content: b"user nginx;\nworker_processes 1;\n".to_vec(),
                     mode: "0644".to_string(),
+                    encrypted: false,
                 },
                 ProtoMountFile {
                     source_path: "./config.json".to_string(),
                     content: b"{\"key\": \"value\", \"enabled\": true}".to_vec(),
                     mode: "0644".to_string(),
+                    encrypted: false,
                 },
             ],
-            deployer: vec![0; 32],
+            deployer: TEST_DEPLOYER.to_vec(),
+            register_on_chain: None,
+            platform: String::new(),
+            service_platforms: Default::default(),
         }
     }
 
-    #[test]
-    fn test_validate_request() {
-        let service = BootService {
+    type FakeService = BootService<FakeBackend>;
+
+    /// Steps seen by a backend, with the task stage at each
+    type StageLog = Arc<std::sync::Mutex<Vec<(&'static str, &'static str)>>>;
+
+    /// Fake backend recording the stage of `task_id` at each operation, and
+    /// cancelling the token of `cancel_at` when that operation runs
+    fn stage_recorder(
+        task_manager: &Arc<TaskManager>,
+        task_id: &str,
+        data_dir: &Path,
+        cancel_at: Option<(&'static str, CancellationToken)>,
+    ) -> (FakeBackend, StageLog) {
+        let steps = StageLog::default();
+        let (task_manager, task_id, log) =
+            (task_manager.clone(), task_id.to_string(), steps.clone());
+        let backend = FakeBackend::new(data_dir).observe(move |operation| {
+            let (task_manager, task_id, log) = (task_manager.clone(), task_id.clone(), log.clone());
+            let cancel_at = cancel_at.clone();
+            Box::pin(async move {
+                let task = task_manager.get_task(&task_id).await.unwrap();
+                log.lock().unwrap().push((operation, task.stage_name()));
+                if let Some((at, cancel)) = cancel_at {
+                    if at == operation {
+                        cancel.cancel();
+                    }
+                }
+            })
+        });
+        (backend, steps)
+    }
+
+    #[tokio::test]
+    async fn test_deploy_stage_sequence() {
+        let data_dir = tempfile::tempdir().unwrap();
+        let task_manager = Arc::new(TaskManager::new());
+        let request = create_test_request();
+        let task = task_manager
+            .create_task(TaskKind::StartApp, &request.app_id, &request.deployer, None)
+            .await;
+        task_manager
+            .enter_stage(&task.id, TaskStage::Validating)
+            .await;
+
+        let (backend, steps) = stage_recorder(&task_manager, &task.id, data_dir.path(), None);
+        FakeService::deploy_steps(
+            &task_manager,
+            &backend,
+            &task.id,
+            &request,
+            &[],
+            &BootServiceConfig::default(),
+            &CancellationToken::new(),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(
+            *steps.lock().unwrap(),
+            vec![
+                ("write_files", "writing-files"),
+                ("pull", "pulling"),
+                ("up", "starting"),
+                ("wait_healthy", "health-wait"),
+            ]
+        );
+        let task = task_manager.get_task(&task.id).await.unwrap();
+        assert_eq!(task.stage, Some(TaskStage::HealthWait));
+        assert_eq!(task.progress_percent, TaskStage::HealthWait.percent());
+        assert!(backend
+            .app_dir(&request.app_id)
+            .join("docker-compose.yml")
+            .exists());
+        assert_eq!(backend.containers(&request.app_id), 1);
+    }
+
+    #[tokio::test]
+    async fn test_deploy_stage_kept_after_cancel() {
+        let data_dir = tempfile::tempdir().unwrap();
+        let task_manager = Arc::new(TaskManager::new());
+        let request = create_test_request();
+        let task = task_manager
+            .create_task(TaskKind::StartApp, &request.app_id, &request.deployer, None)
+            .await;
+        let cancel = task_manager.cancellation_token(&task.id).await;
+
+        let (backend, steps) = stage_recorder(
+            &task_manager,
+            &task.id,
+            data_dir.path(),
+            Some(("pull", cancel.clone())),
+        );
+        let result = FakeService::deploy_steps(
+            &task_manager,
+            &backend,
+            &task.id,
+            &request,
+            &[],
+            &BootServiceConfig::default(),
+            &cancel,
+        )
+        .await;
+        assert!(matches!(result, Err((_, TappError::Cancelled))));
+        assert_eq!(steps.lock().unwrap().len(), 2);
+
+        // The stage where the deployment stopped stays visible
+        task_manager.mark_cancelled(&task.id).await;
+        let task = task_manager.get_task(&task.id).await.unwrap();
+        assert_eq!(task.stage_name(), "pulling");
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_deploy_retries_transient_failures() {
+        let data_dir = tempfile::tempdir().unwrap();
+        let task_manager = TaskManager::new();
+        let request = create_test_request();
+        let config = BootServiceConfig {
+            auto_retry_attempts: 2,
+            retry: no_step_retries(),
+            ..Default::default()
+        };
+
+        for (failures, succeeds) in [(2, true), (3, false)] {
+            let task = task_manager
+                .create_task(TaskKind::StartApp, &request.app_id, &request.deployer, None)
+                .await;
+            let backend = FakeBackend::new(data_dir.path());
+            backend.fail("pull", FakeFailure::Unavailable, failures);
+            let result = FakeService::deploy_steps(
+                &task_manager,
+                &backend,
+                &task.id,
+                &request,
+                &[],
+                &config,
+                &CancellationToken::new(),
+            )
+            .await;
+
+            assert_eq!(backend.count("pull"), 3);
+            assert_eq!(result.is_ok(), succeeds);
+            if !succeeds {
+                assert!(matches!(
+                    result,
+                    Err((
+                        TaskErrorCode::DockerUnavailable,
+                        TappError::Docker(DockerError::ConnectionFailed { .. })
+                    ))
+                ));
+            }
+            task_manager
+                .mark_failed(&task.id, TaskErrorCode::Internal, String::new())
+                .await;
+        }
+    }
+
+    fn no_step_retries() -> RetryConfig {
+        RetryConfig {
+            max_retries: 0,
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_step_retries() {
+        let data_dir = tempfile::tempdir().unwrap();
+        let task_manager = TaskManager::new();
+        let request = create_test_request();
+        let config = BootServiceConfig {
+            retry: RetryConfig {
+                max_retries: 2,
+                initial_delay_ms: 100,
+                max_delay_ms: 1000,
+            },
+            ..Default::default()
+        };
+
+        // A flaky pull is retried within the attempt; the last failure is kept
+        for (failures, succeeds) in [(2, true), (3, false)] {
+            let task = task_manager
+                .create_task(TaskKind::StartApp, &request.app_id, &request.deployer, None)
+                .await;
+            let backend = FakeBackend::new(data_dir.path());
+            backend.fail("pull", FakeFailure::Unavailable, failures);
+            let result = FakeService::deploy_steps(
+                &task_manager,
+                &backend,
+                &task.id,
+                &request,
+                &[],
+                &config,
+                &CancellationToken::new(),
+            )
+            .await;
+
+            assert_eq!(backend.count("pull"), 3);
+            assert_eq!(result.is_ok(), succeeds);
+            if !succeeds {
+                let (code, _) = result.unwrap_err();
+                assert_eq!(code, TaskErrorCode::DockerUnavailable);
+                assert_eq!(backend.count("up"), 0);
+            }
+            task_manager
+                .mark_failed(&task.id, TaskErrorCode::Internal, String::new())
+                .await;
+        }
+
+        // `up` is not idempotent and fails on the first error
+        let task = task_manager
+            .create_task(TaskKind::StartApp, &request.app_id, &request.deployer, None)
+            .await;
+        let backend = FakeBackend::new(data_dir.path());
+        backend.fail(
+            "up",
+            FakeFailure::Error("Error response from daemon: connection reset".to_string()),
+            1,
+        );
+        let result = FakeService::deploy_steps(
+            &task_manager,
+            &backend,
+            &task.id,
+            &request,
+            &[],
+            &config,
+            &CancellationToken::new(),
+        )
+        .await;
+        assert!(result.is_err());
+        assert_eq!(backend.count("up"), 1);
+    }
+
+    #[tokio::test]
+    async fn test_deploy_failure_codes() {
+        let data_dir = tempfile::tempdir().unwrap();
+        let task_manager = TaskManager::new();
+        let request = create_test_request();
+        let error = |output: &str| FakeFailure::Error(output.to_string());
+        let cases = [
+            (
+                "write_files",
+                error("No space left on device"),
+                TaskErrorCode::Internal,
+            ),
+            (
+                "pull",
+                FakeFailure::Unavailable,
+                TaskErrorCode::DockerUnavailable,
+            ),
+            (
+                "pull",
+                error("Cannot connect to the Docker daemon at unix:///var/run/docker.sock"),
+                TaskErrorCode::DockerUnavailable,
+            ),
+            (
+                "up",
+                error("Error response from daemon: manifest unknown"),
+                TaskErrorCode::StartFailed,
+            ),
+            (
+                "up",
+                error("Bind for 0.0.0.0:80 failed: port is already allocated"),
+                TaskErrorCode::PortConflict,
+            ),
+            ("wait_healthy", FakeFailure::Timeout, TaskErrorCode::Timeout),
+        ];
+
+        for (operation, failure, expected) in cases {
+            let task = task_manager
+                .create_task(TaskKind::StartApp, &request.app_id, &request.deployer, None)
+                .await;
+            let backend = FakeBackend::new(data_dir.path());
+            backend.fail(operation, failure, 1);
+            let config = BootServiceConfig {
+                retry: no_step_retries(),
+                ..Default::default()
+            };
+            let result = FakeService::deploy_steps(
+                &task_manager,
+                &backend,
+                &task.id,
+                &request,
+                &[],
+                &config,
+                &CancellationToken::new(),
+            )
+            .await;
+
+            let (code, _) = result.unwrap_err();
+            assert_eq!(code, expected, "{}", operation);
+        }
+
+        // Errors carrying their own cause override the step's code
+        let timeout = DockerError::Timeout {
+            operation: "docker_compose_up".to_string(),
+            seconds: 300,
+        };
+        let (code, _) = FakeService::at_step(TaskErrorCode::StartFailed)(timeout.into());
+        assert_eq!(code, TaskErrorCode::Timeout);
+        let pull = DockerError::ContainerOperationFailed {
+            operation: "docker_compose_pull".to_string(),
+            reason: "pull access denied for private/image".to_string(),
+            cause: FailureCause::Internal,
+        };
+        let (code, _) = FakeService::at_step(TaskErrorCode::PullFailed)(pull.into());
+        assert_eq!(code, TaskErrorCode::PullFailed);
+    }
+
+    #[tokio::test]
+    async fn test_podman_backend() {
+        let data_dir = tempfile::tempdir().unwrap();
+        let mut service = create_fake_service(data_dir.path());
+        assert_eq!(service.runtime(), ContainerRuntime::Docker);
+        service.backend = FakeBackend::new(data_dir.path()).with_runtime(ContainerRuntime::Podman);
+        assert_eq!(service.runtime(), ContainerRuntime::Podman);
+
+        // podman's CLI words its failures differently
+        let task_manager = TaskManager::new();
+        let request = create_test_request();
+        let error = |output: &str| FakeFailure::Error(output.to_string());
+        let cases = [
+            (
+                "pull",
+                FakeFailure::Unavailable,
+                TaskErrorCode::DockerUnavailable,
+            ),
+            (
+                "pull",
+                error(
+                    "Cannot connect to Podman. Please verify your connection to the Linux \
+                     system using `podman system connection list`",
+                ),
+                TaskErrorCode::DockerUnavailable,
+            ),
+            (
+                "up",
+                error(
+                    "Error: unable to connect to Podman socket: dial unix /run/podman/podman.sock",
+                ),
+                TaskErrorCode::DockerUnavailable,
+            ),
+            (
+                "up",
+                error("rootlessport listen tcp 0.0.0.0:80: bind: address already in use"),
+                TaskErrorCode::PortConflict,
+            ),
+        ];
+        for (operation, failure, expected) in cases {
+            let task = task_manager
+                .create_task(TaskKind::StartApp, &request.app_id, &request.deployer, None)
+                .await;
+            let backend = FakeBackend::new(data_dir.path()).with_runtime(ContainerRuntime::Podman);
+            backend.fail(operation, failure, 1);
+            let config = BootServiceConfig {
+                runtime: ContainerRuntime::Podman,
+                retry: no_step_retries(),
+                ..Default::default()
+            };
+            let result = FakeService::deploy_steps(
+                &task_manager,
+                &backend,
+                &task.id,
+                &request,
+                &[],
+                &config,
+                &CancellationToken::new(),
+            )
+            .await;
+
+            let (code, _) = result.unwrap_err();
+            assert_eq!(code, expected, "{}", operation);
+        }
+    }
+
+    /// Service on a fake backend keeping apps in `data_dir`, with in-memory
+    /// tasks
+    fn create_fake_service(data_dir: &Path) -> FakeService {
+        BootService {
             config: BootServiceConfig::default(),
-            manager: Mutex::new(DockerComposeManager::mock()),
+            backend: FakeBackend::new(data_dir),
+            image_policy: None,
             app_measurements: Mutex::new(HashMap::new()),
             aa: Mutex::new(AttestationAgent::new(None).unwrap()),
+            extend_history: ExtendHistory::in_memory(),
             task_manager: TaskManager::new(),
+            deploy_queue: DeployQueue::from_config(&BootServiceConfig::default()),
             app_compose_content: Mutex::new(HashMap::new()),
             app_mount_files: Mutex::new(HashMap::new()),
+            containers_missing: Mutex::new(HashSet::new()),
+            unmeasured_apps: Mutex::new(HashSet::new()),
+            app_events: Mutex::new(HashMap::new()),
+            notifier: Notifier::disabled(),
+            app_keys: None,
+            registrar: None,
+            shutdown: CancellationToken::new(),
+        }
+    }
+
+    /// Poll a task until it finishes
+    async fn wait_finished<B: ContainerBackend>(service: &BootService<B>, task_id: &str) -> Task {
+        for _ in 0..2400 {
+            if let TaskLookup::Found(task) = service.get_task_status(task_id).await {
+                if task.is_finished() {
+                    return task;
+                }
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+        panic!("task {} did not finish", task_id);
+    }
+
+    #[tokio::test]
+    async fn test_open_mount_files() {
+        let data_dir = tempfile::tempdir().unwrap();
+        let app_keys = AppKeyService::new(&crate::config::KbsConfig::default(), true)
+            .await
+            .unwrap();
+        let public_key = app_keys
+            .get_app_key("app-1", "ethereum")
+            .await
+            .unwrap()
+            .public_key;
+        let file = |content: Vec<u8>, encrypted: bool| MountFile {
+            source_path: "./secret.env".to_string(),
+            content,
+            mode: "0600".to_string(),
+            encrypted,
         };
+        let sealed = envelope::seal(&public_key, "app-1", b"TOKEN=abc\n").unwrap();
+        let mount_files = vec![file(sealed.clone(), true), file(b"A=1\n".to_vec(), false)];
+
+        // Without the key service, encrypted files are rejected
+        let service = create_fake_service(data_dir.path());
+        let err = service
+            .open_mount_files("app-1", &mount_files)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("not supported"));
+        assert!(service
+            .open_mount_files("app-1", &mount_files[1..])
+            .await
+            .is_ok());
+
+        let service = create_fake_service(data_dir.path()).with_app_keys(Arc::new(app_keys));
+        let opened = service
+            .open_mount_files("app-1", &mount_files)
+            .await
+            .unwrap();
+        assert_eq!(opened[0].content, b"TOKEN=abc\n");
+        assert!(!opened[0].encrypted);
+        assert_eq!(opened[0].mode, "0600");
+        assert_eq!(opened[1].content, b"A=1\n");
+
+        // The measurement covers the envelope, not the plaintext
+        let measurement = ComposeMeasurement::new();
+        let (sealed_hash, _) = measurement
+            .calculate_mount_files_hash(&mount_files[..1])
+            .unwrap();
+        assert_eq!(sealed_hash, crate::utils::sha384_hex(&sealed));
+
+        // A file sealed for another app does not open
+        let other = vec![file(
+            envelope::seal(&public_key, "app-2", b"x").unwrap(),
+            true,
+        )];
+        let err = service.open_mount_files("app-1", &other).await.unwrap_err();
+        assert!(err.to_string().contains("Cannot open ./secret.env"));
+    }
+
+    #[tokio::test]
+    async fn test_list_app_measurements_deployer_filter() {
+        use k256::ecdsa::SigningKey;
+        use k256::elliptic_curve::{rand_core::OsRng, sec1::ToEncodedPoint};
+
+        let data_dir = tempfile::tempdir().unwrap();
+        let service = create_fake_service(data_dir.path());
+        let key = *SigningKey::random(&mut OsRng).verifying_key();
+        let compressed = key.to_encoded_point(true).as_bytes().to_vec();
+        let uncompressed = key.to_encoded_point(false).as_bytes().to_vec();
+        for (app_id, deployer) in [
+            ("by-key", &uncompressed[..]),
+            ("by-address", &TEST_DEPLOYER[..]),
+        ] {
+            let identity = DeployerIdentity::from_bytes(deployer).unwrap();
+            service
+                .insert_app_measurement(AppMeasurement {
+                    app_id: app_id.to_string(),
+                    compose_hash: String::new(),
+                    volumes_hash: String::new(),
+                    deployer: identity.canonical_hex(),
+                    deployer_address: identity.address_hex(),
+                    timestamp: crate::utils::current_timestamp(),
+                    namespace: None,
+                    secret_names: Vec::new(),
+                })
+                .await;
+        }
+        let listed = |filter: String| {
+            let service = &service;
+            async move {
+                let measurements = service.list_app_measurements(Some(filter)).await;
+                measurements
+                    .into_iter()
+                    .map(|m| m.app_id)
+                    .collect::<Vec<_>>()
+            }
+        };
+
+        // Every form of the key finds the app, whatever form it was deployed with
+        let identity = DeployerIdentity::from_bytes(&compressed).unwrap();
+        for filter in [
+            hex::encode(&compressed),
+            hex::encode(&uncompressed),
+            format!("0x{}", hex::encode(&uncompressed[1..])),
+            format!("0x{}", identity.address_hex().to_uppercase()),
+        ] {
+            assert_eq!(listed(filter).await, vec!["by-key"]);
+        }
+        assert_eq!(listed(hex::encode(TEST_DEPLOYER)).await, vec!["by-address"]);
+
+        // Partial hex still matches substrings
+        assert_eq!(
+            listed(identity.address_hex()[..8].to_string()).await,
+            vec!["by-key"]
+        );
+        assert!(listed("ff".repeat(20)).await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_force_remove_orphans() {
+        let data_dir = tempfile::tempdir().unwrap();
+        let service = create_fake_service(data_dir.path());
+        let cancel = CancellationToken::new();
+        let remove = |app_id: &'static str| {
+            let (service, cancel) = (&service, &cancel);
+            async move {
+                service
+                    .force_remove_app(app_id, false, false, cancel)
+                    .await
+                    .unwrap()
+            }
+        };
+
+        // An app directory with no containers
+        std::fs::create_dir_all(data_dir.path().join("dir-only").join("deploy")).unwrap();
+        let removal = remove("dir-only").await;
+        assert_eq!(removal.cleaned(), vec!["app_dir"]);
+        assert!(!data_dir.path().join("dir-only").exists());
+        assert_eq!(service.backend.count("remove_project"), 0);
+
+        // Containers with no measurement or directory
+        service.backend.add_containers("containers-only", 2);
+        let removal = remove("containers-only").await;
+        assert_eq!(removal.cleaned(), vec!["containers"]);
+        assert_eq!(removal.containers_removed, 2);
+        assert_eq!(service.backend.containers("containers-only"), 0);
+        assert!(service
+            .backend
+            .calls()
+            .contains(&"remove_project containers-only".to_string()));
+
+        // A measurement whose directory was deleted
+        service
+            .insert_app_measurement(AppMeasurement {
+                app_id: "measured".to_string(),
+                compose_hash: String::new(),
+                volumes_hash: String::new(),
+                deployer: hex::encode(TEST_DEPLOYER),
+                deployer_address: hex::encode(TEST_DEPLOYER),
+                timestamp: crate::utils::current_timestamp(),
+                namespace: None,
+                secret_names: Vec::new(),
+            })
+            .await;
+        service
+            .app_compose_content
+            .lock()
+            .await
+            .insert("measured".to_string(), "services: {}".to_string());
+        let removal = remove("measured").await;
+        assert_eq!(removal.cleaned(), vec!["measurement", "compose_content"]);
+        assert!(service.get_app_measurement("measured").await.is_none());
+        // Only attesting the removal may fail without a TEE
+        assert!(removal
+            .errors
+            .iter()
+            .all(|e| e.starts_with("runtime_measurement")));
+
+        // Removing again finds nothing, and every call is in the history
+        for app_id in ["dir-only", "containers-only", "measured"] {
+            let removal = remove(app_id).await;
+            assert!(removal.cleaned().is_empty(), "{}", app_id);
+            assert!(removal.errors.is_empty());
+            let events = service.get_app_events(app_id).await;
+            assert_eq!(events.len(), 2);
+            assert!(events
+                .iter()
+                .all(|e| e.event == OPERATION_NAME_FORCE_REMOVE_APP));
+        }
+        assert_eq!(service.backend.count("remove_project"), 1);
+    }
+
+    #[tokio::test]
+    async fn test_force_remove_in_flight_and_failures() {
+        let data_dir = tempfile::tempdir().unwrap();
+        let service = create_fake_service(data_dir.path());
+
+        // A task holding the app ID is cancelled
+        let request = create_test_request();
+        let task = service
+            .task_manager
+            .create_task(TaskKind::StartApp, &request.app_id, &request.deployer, None)
+            .await;
+        let removal = service
+            .force_remove_app(&request.app_id, false, false, &cancel)
+            .await
+            .unwrap();
+        assert_eq!(removal.cleaned(), vec!["task"]);
+        assert_eq!(removal.cancelled_task.as_deref(), Some(task.id.as_str()));
+        assert!(service
+            .task_manager
+            .in_flight_task(&request.app_id)
+            .await
+            .map_or(true, |task| matches!(task.status, TaskState::Cancelling)));
+        let removal = service
+            .force_remove_app(&request.app_id, false, false, &cancel)
+            .await
+            .unwrap();
+        assert_eq!(removal.cancelled_task, None);
+
+        // Purging takes the project down even without containers
+        let removal = service
+            .force_remove_app("purged", true, true, &cancel)
+            .await
+            .unwrap();
+        assert!(removal.cleaned().is_empty());
+        assert!(service
+            .backend
+            .calls()
+            .contains(&"remove_project purged --volumes --rmi all".to_string()));
+
+        // Docker being down does not stop the other steps
+        std::fs::create_dir_all(data_dir.path().join("no-docker")).unwrap();
+        service.backend.fail("status", FakeFailure::Unavailable, 1);
+        let removal = service
+            .force_remove_app("no-docker", false, false, &cancel)
+            .await
+            .unwrap();
+        assert_eq!(removal.cleaned(), vec!["app_dir"]);
+        assert_eq!(removal.errors.len(), 1);
+        assert!(removal.errors[0].starts_with("containers: "));
+
+        // The app ID names a directory, so it must be a plain name
+        assert!(matches!(
+            service
+                .force_remove_app("../etc", false, false, &cancel)
+                .await,
+            Err(TappError::InvalidParameter { .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_stop_app_and_logs() {
+        let data_dir = tempfile::tempdir().unwrap();
+        let service = create_fake_service(data_dir.path());
+        let cancel = CancellationToken::new();
+        service
+            .backend
+            .write_files("app-1", "services: {}\n", &[])
+            .await
+            .unwrap();
+        service
+            .backend
+            .up("app-1", "task-1", Duration::from_secs(1), &cancel)
+            .await
+            .unwrap();
+
+        assert!(service.app_status("app-1").await.unwrap().running);
+        let logs = service
+            .get_app_logs("app-1", 2, None, &cancel)
+            .await
+            .unwrap();
+        assert_eq!(logs.lines().count(), 2);
+
+        // A stop cut short by its RPC going away keeps the app
+        service.backend.fail("stop", FakeFailure::Hang, 1);
+        let rpc = CancellationToken::new();
+        rpc.cancel();
+        assert!(matches!(
+            service.stop_app("app-1", &rpc).await,
+            Err(TappError::Cancelled)
+        ));
+        assert!(data_dir.path().join("app-1").exists());
+
+        service.stop_app("app-1", &cancel).await.unwrap();
+        assert_eq!(service.backend.containers("app-1"), 0);
+        assert!(!data_dir.path().join("app-1").exists());
+        let events = service.get_app_events("app-1").await;
+        assert_eq!(events.last().unwrap().event, OPERATION_NAME_STOP_APP);
+
+        // A stopped app is gone
+        assert!(service
+            .get_app_logs("app-1", 2, None, &cancel)
+            .await
+            .is_err());
+        assert!(matches!(
+            service.stop_app("app-1", &cancel).await,
+            Err(TappError::InvalidParameter { .. })
+        ));
+
+        // Docker failures are reported as they are
+        service.backend.fail("ping", FakeFailure::Unavailable, 1);
+        assert!(matches!(
+            service.check_docker().await,
+            Err(TappError::Docker(DockerError::ConnectionFailed { .. }))
+        ));
+        assert!(service.check_docker().await.is_ok());
+    }
+
+    #[test]
+    fn test_validate_request() {
+        let data_dir = tempfile::tempdir().unwrap();
+        let mut service = create_fake_service(data_dir.path());
 
         // Valid request
         let request = create_test_request();
@@ -535,45 +2585,770 @@ services:
         let mut invalid_request = create_test_request();
         invalid_request.app_id = "".to_string();
         assert!(service.validate_request(&invalid_request).is_err());
+
+        // Invalid - neither an address nor a public key
+        let mut invalid_request = create_test_request();
+        invalid_request.deployer = vec![0; 32];
+        assert!(service.validate_request(&invalid_request).is_err());
+
+        // Invalid - nginx is not from an allowed registry
+        service.image_policy = ImagePolicy::from_config(Some(&ImagePolicyConfig {
+            enabled: true,
+            allowed_registries: vec!["registry.0g.dev".to_string()],
+            ..Default::default()
+        }))
+        .unwrap();
+        assert!(matches!(
+            service.validate_request(&request),
+            Err(TappError::Docker(DockerError::ImagesNotAllowed { .. }))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_task_logs_carry_request_id() {
+        use std::io::Write;
+        use tracing_subscriber::layer::SubscriberExt;
+
+        /// In-memory sink so the test can read back what was logged
+        #[derive(Clone, Default)]
+        struct SharedBuffer(Arc<std::sync::Mutex<Vec<u8>>>);
+
+        impl Write for SharedBuffer {
+            fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+                self.0.lock().unwrap().extend_from_slice(buf);
+                Ok(buf.len())
+            }
+
+            fn flush(&mut self) -> std::io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let buffer = SharedBuffer::default();
+        let writer = buffer.clone();
+        let subscriber = tracing_subscriber::registry().with(
+            tracing_subscriber::fmt::layer()
+                .with_ansi(false)
+                .with_writer(move || writer.clone()),
+        );
+        let _default = tracing::subscriber::set_default(subscriber);
+
+        // An app that is already deployed makes the task fail right away
+        let data_dir = tempfile::tempdir().unwrap();
+        let service = Arc::new(create_fake_service(data_dir.path()));
+        let request = create_test_request();
+        service
+            .insert_app_measurement(AppMeasurement {
+                app_id: request.app_id.clone(),
+                compose_hash: String::new(),
+                volumes_hash: String::new(),
+                deployer: String::new(),
+                deployer_address: String::new(),
+                timestamp: crate::utils::current_timestamp(),
+                namespace: None,
+                secret_names: Vec::new(),
+            })
+            .await;
+
+        let response = service
+            .clone()
+            .start_app(request, Some("req-7f3a"))
+            .await
+            .unwrap();
+        for _ in 0..100 {
+            match service.get_task_status(&response.task_id).await {
+                TaskLookup::Found(task) if task.is_finished() => {
+                    assert_eq!(task.request_id.as_deref(), Some("req-7f3a"));
+                    break;
+                }
+                _ => tokio::time::sleep(Duration::from_millis(10)).await,
+            }
+        }
+
+        let logged = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+        let failure = logged
+            .lines()
+            .find(|line| line.contains("Deployment failed"))
+            .unwrap();
+        assert!(failure.contains("request_id=req-7f3a"));
+        assert!(failure.contains(&response.task_id));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_deploy_during_shutdown() {
+        let data_dir = tempfile::tempdir().unwrap();
+        let mut service = create_fake_service(data_dir.path());
+        service.deploy_queue = DeployQueue::new(1, 16);
+        let service = Arc::new(service);
+        let request = |app_id: &str| StartAppRequest {
+            app_id: app_id.to_string(),
+            ..create_test_request()
+        };
+
+        // A deployment stuck in Docker holds the only slot
+        let stuck = service
+            .task_manager
+            .create_task(TaskKind::StartApp, "stuck-app", &[], None)
+            .await;
+        service
+            .deploy_queue
+            .enqueue(stuck.id.clone(), request("stuck-app"))
+            .unwrap();
+        service.task_manager.mark_running(&stuck.id).await;
+        let queued = service
+            .clone()
+            .start_app(request("queued-app"), None)
+            .await
+            .unwrap();
+
+        service.shutdown(Duration::from_secs(30)).await;
+
+        for task_id in [&stuck.id, &queued.task_id] {
+            let TaskLookup::Found(task) = service.get_task_status(task_id).await else {
+                panic!("task {} is gone", task_id);
+            };
+            let TaskState::Failed(code, reason) = &task.status else {
+                panic!("task {} is not failed: {:?}", task_id, task.status);
+            };
+            assert_eq!(*code, TaskErrorCode::Interrupted);
+            assert_eq!(reason, SHUTDOWN_REASON);
+        }
+
+        // Deployments requested during shutdown are refused without a task
+        let err = service
+            .clone()
+            .start_app(request("late-app"), None)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, TappError::ServiceUnavailable { .. }));
+        assert!(service
+            .task_manager
+            .in_flight_task("late-app")
+            .await
+            .is_none());
+        assert!(service.task_manager.unfinished_task_ids().await.is_empty());
     }
 
     #[tokio::test]
     async fn test_start_app() {
+        let data_dir = tempfile::tempdir().unwrap();
+        let service = Arc::new(create_fake_service(data_dir.path()));
+        let request = create_request_with_mount_files();
+        let app_id = request.app_id.clone();
+        let response = service.clone().start_app(request, None).await.unwrap();
+        assert!(response.success);
+        let task = wait_finished(&service, &response.task_id).await;
+
+        assert_eq!(
+            service.backend.calls(),
+            ["write_files", "pull", "up", "wait_healthy"]
+                .map(|operation| format!("{} {}", operation, app_id))
+        );
+        let app_dir = data_dir.path().join(&app_id);
+        assert!(app_dir.join("docker-compose.yml").exists());
+        assert_eq!(
+            std::fs::read(app_dir.join("nginx.conf")).unwrap(),
+            b"user nginx;\nworker_processes 1;\n"
+        );
+        assert!(service
+            .get_app_compose_content(&app_id)
+            .await
+            .unwrap()
+            .is_some());
+        // Without a TEE the deployment stops at extending the measurement
+        match &task.status {
+            TaskState::Completed(result) => {
+                assert_eq!(result.app_id, app_id);
+                assert!(service.get_app_measurement(&app_id).await.is_some());
+            }
+            TaskState::Failed(code, _) => {
+                assert_eq!(*code, TaskErrorCode::MeasurementFailed);
+                // A measurement that never reached the RTMR is not kept
+                assert!(service.get_app_measurement(&app_id).await.is_none());
+                assert!(!app_dir.join(measurement::MEASUREMENT_FILE).exists());
+            }
+            status => panic!("unexpected task status {:?}", status),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_compose_secrets() {
+        let data_dir = tempfile::tempdir().unwrap();
+        let app_keys = AppKeyService::new(&crate::config::KbsConfig::default(), true)
+            .await
+            .unwrap();
+        let service =
+            Arc::new(create_fake_service(data_dir.path()).with_app_keys(Arc::new(app_keys)));
+        let compose = "services:\n  db:\n    image: postgres\n    environment:\n      POSTGRES_PASSWORD: ${TAPP_SECRET:db_password}\n      API_TOKEN: ${TAPP_SECRET:api_token}\n";
+        let request = StartAppRequest {
+            compose_content: compose.to_string(),
+            app_id: "secret-app".to_string(),
+            mount_files: vec![],
+            deployer: TEST_DEPLOYER.to_vec(),
+            register_on_chain: None,
+            platform: String::new(),
+            service_platforms: Default::default(),
+        };
+        let app_dir = data_dir.path().join("secret-app");
+
+        let mut deployments = Vec::new();
+        for _ in 0..2 {
+            let response = service
+                .clone()
+                .start_app(request.clone(), None)
+                .await
+                .unwrap();
+            wait_finished(&service, &response.task_id).await;
+            let measurement = service.get_app_measurement("secret-app").await.unwrap();
+            let env = std::fs::read_to_string(app_dir.join(".env")).unwrap();
+            deployments.push((measurement, env));
+            service
+                .force_remove_app("secret-app", false, false, &CancellationToken::new())
+                .await
+                .unwrap();
+        }
+
+        let (measurement, env) = &deployments[0];
+        assert_eq!(measurement.secret_names, ["api_token", "db_password"]);
+        assert_eq!(
+            measurement.compose_hash,
+            ComposeMeasurement::new()
+                .calculate_compose_hash(compose)
+                .unwrap()
+        );
+        // The values are in .env only, and the same on redeploy
+        let lines: Vec<&str> = env.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].starts_with("TAPP_SECRET_api_token="));
+        assert!(lines[1].starts_with("TAPP_SECRET_db_password="));
+        assert_eq!(deployments[1].0.compose_hash, measurement.compose_hash);
+        assert_eq!(deployments[1].0.secret_names, measurement.secret_names);
+        assert_eq!(&deployments[1].1, env);
+        let value = lines[0].split_once('=').unwrap().1;
+        assert!(!serde_json::to_string(measurement).unwrap().contains(value));
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::metadata(app_dir.join(".env"))
+                .unwrap()
+                .permissions()
+                .mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+
+        // Undefined and malformed placeholders fail before anything runs
+        let service = Arc::new(create_fake_service(data_dir.path()));
+        let malformed = StartAppRequest {
+            compose_content: "services:\n  db:\n    image: postgres\n    environment:\n      A: ${TAPP_SECRET_db_password}\n".to_string(),
+            app_id: "malformed-app".to_string(),
+            ..request.clone()
+        };
+        for (request, code) in [
+            (request, TaskErrorCode::InvalidRequest),
+            (malformed, TaskErrorCode::InvalidCompose),
+        ] {
+            let response = service.clone().start_app(request, None).await.unwrap();
+            let task = wait_finished(&service, &response.task_id).await;
+            assert!(
+                matches!(&task.status, TaskState::Failed(c, _) if *c == code),
+                "{:?}",
+                task.status
+            );
+        }
+        assert!(service.backend.calls().is_empty());
+    }
+
+    /// Contract accepting every registration, mined by the first check
+    struct ConfirmingContract;
+
+    impl ContractClient for ConfirmingContract {
+        fn register_app<'a>(
+            &'a self,
+            app: &'a AppRegistration,
+        ) -> BoxFuture<'a, TappResult<String>> {
+            let tx_hash = format!("0x{}", hex::encode(app.app_id.as_bytes()));
+            Box::pin(async move { Ok(tx_hash) })
+        }
+
+        fn transaction_status<'a>(
+            &'a self,
+            _tx_hash: &'a str,
+        ) -> BoxFuture<'a, TappResult<TxStatus>> {
+            Box::pin(async { Ok(TxStatus::Confirmed { block_number: 1 }) })
+        }
+
+        fn is_registered_attestor<'a>(
+            &'a self,
+            _attestor: &'a [u8; 20],
+        ) -> BoxFuture<'a, TappResult<bool>> {
+            Box::pin(async { Ok(false) })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_measurements_survive_restart() {
+        let dir = tempfile::tempdir().unwrap();
+        let data_dir = dir.path().join("apps");
         let config = BootServiceConfig {
-            socket_path: "/var/run/docker.sock".to_string(),
+            aa_config_path: Some(dir.path().join("aa.toml").to_string_lossy().to_string()),
+            data_dir: data_dir.clone(),
+            task_state_dir: String::new(),
+            extend_history_path: String::new(),
             ..Default::default()
         };
-        let service = Arc::new(BootService::new(&config).await.unwrap());
-        let request = create_real_request();
-        let response = service.start_app(request).await.unwrap();
-        assert!(response.success);
+        let service = Arc::new(
+            BootService::with_backend(&config, FakeBackend::new(&data_dir))
+                .await
+                .unwrap(),
+        );
+        for app_id in ["app-one", "team-a/app-two"] {
+            let request = StartAppRequest {
+                app_id: app_id.to_string(),
+                ..create_test_request()
+            };
+            let response = service.clone().start_app(request, None).await.unwrap();
+            let task = wait_finished(&service, &response.task_id).await;
+            assert!(
+                matches!(task.status, TaskState::Completed(_)),
+                "{:?}",
+                task.status
+            );
+        }
+        let deployer = service
+            .get_app_measurement("app-one")
+            .await
+            .unwrap()
+            .deployer;
+        service
+            .transfer_app_ownership("app-one", &deployer, &[0x22; 20])
+            .await
+            .unwrap();
+
+        // Broken files and files of another app are skipped
+        let broken = data_dir.join("broken-app");
+        std::fs::create_dir_all(&broken).unwrap();
+        std::fs::write(
+            broken.join(measurement::MEASUREMENT_FILE),
+            "{\"app_id\": \"broken-app\", ",
+        )
+        .unwrap();
+        let copied = data_dir.join("copied-app");
+        std::fs::create_dir_all(&copied).unwrap();
+        std::fs::copy(
+            data_dir.join("app-one").join(measurement::MEASUREMENT_FILE),
+            copied.join(measurement::MEASUREMENT_FILE),
+        )
+        .unwrap();
+        // Deployed before measurements were saved
+        let legacy = data_dir.join("legacy-app");
+        std::fs::create_dir_all(&legacy).unwrap();
+        let legacy_compose = "services:\n  web:\n    image: nginx:alpine\n";
+        std::fs::write(legacy.join("docker-compose.yml"), legacy_compose).unwrap();
+
+        let by_app_id = |mut measurements: Vec<AppMeasurement>| {
+            measurements.sort_by(|a, b| a.app_id.cmp(&b.app_id));
+            measurements
+        };
+        let before = by_app_id(service.list_app_measurements(None).await);
+        assert_eq!(before.len(), 2);
+        assert_eq!(before[0].deployer_address, hex::encode([0x22; 20]));
+
+        // Only app-one still has containers after the restart
+        let backend = FakeBackend::new(&data_dir);
+        backend.add_containers("app-one", 1);
+        let restarted = BootService::with_backend(&config, backend).await.unwrap();
+        let after = by_app_id(restarted.list_app_measurements(None).await);
+        assert_eq!(after, before);
+        assert_eq!(
+            restarted
+                .get_app_measurement("team-a/app-two")
+                .await
+                .unwrap()
+                .namespace
+                .as_deref(),
+            Some("team-a")
+        );
+        for app_id in ["app-one", "team-a/app-two"] {
+            assert_eq!(
+                restarted.get_app_compose_content(app_id).await.unwrap(),
+                service.get_app_compose_content(app_id).await.unwrap()
+            );
+            assert_eq!(
+                restarted.get_app_mount_files(app_id).await.unwrap(),
+                Some(String::new())
+            );
+        }
+        assert_eq!(
+            restarted.apps_missing_containers().await,
+            HashSet::from(["team-a/app-two".to_string(), "legacy-app".to_string()])
+        );
+        assert_eq!(
+            restarted.unmeasured_apps().await,
+            HashSet::from(["legacy-app".to_string()])
+        );
+        assert_eq!(
+            restarted
+                .get_app_compose_content("legacy-app")
+                .await
+                .unwrap()
+                .as_deref(),
+            Some(legacy_compose)
+        );
+        assert!(restarted.get_app_measurement("legacy-app").await.is_none());
+        for app_id in ["team-a/app-two", "legacy-app"] {
+            restarted
+                .force_remove_app(app_id, false, false, &CancellationToken::new())
+                .await
+                .unwrap();
+        }
+        assert!(restarted.apps_missing_containers().await.is_empty());
+        assert!(restarted.unmeasured_apps().await.is_empty());
+
+        // Nothing is restored with the scan disabled
+        let config = BootServiceConfig {
+            restore_apps: false,
+            ..config
+        };
+        let empty = BootService::with_backend(&config, FakeBackend::new(&data_dir))
+            .await
+            .unwrap();
+        assert!(empty.list_app_measurements(None).await.is_empty());
+        assert_eq!(
+            empty.get_app_compose_content("app-one").await.unwrap(),
+            None
+        );
     }
 
     #[tokio::test]
-    async fn test_start_app_with_mount_files() {
+    async fn test_start_app_platforms() {
+        let data_dir = tempfile::tempdir().unwrap();
+        let mut service = create_fake_service(data_dir.path());
+        let compose = "services:\n  web:\n    image: nginx\n  worker:\n    image: worker\n";
+        let request = |app_id: &str, platform: &str, overrides: &[(&str, &str)]| StartAppRequest {
+            compose_content: compose.to_string(),
+            app_id: app_id.to_string(),
+            platform: platform.to_string(),
+            service_platforms: overrides
+                .iter()
+                .map(|(name, platform)| (name.to_string(), platform.to_string()))
+                .collect(),
+            ..create_test_request()
+        };
+
+        // The fake host is linux/x86_64
+        let err = Arc::new(create_fake_service(data_dir.path()))
+            .start_app(request("arm-app", "linux/arm64", &[]), None)
+            .await
+            .unwrap_err();
+        assert!(
+            matches!(&err, TappError::InvalidParameter { field, reason }
+                if field == "platform" && reason.contains("web (linux/arm64)")),
+            "{}",
+            err
+        );
+
+        service.config.allow_emulation = true;
+        let service = Arc::new(service);
+        let err = service
+            .clone()
+            .start_app(request("typo-app", "", &[("wbe", "linux/amd64")]), None)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("wbe"), "{}", err);
+
+        let response = service
+            .clone()
+            .start_app(
+                request("mixed-app", "linux/x86_64", &[("worker", "linux/arm64")]),
+                None,
+            )
+            .await
+            .unwrap();
+        wait_finished(&service, &response.task_id).await;
+        assert_eq!(service.backend.count("host_info"), 1);
+
+        // The platforms are in the measured compose file
+        let stored = service
+            .get_app_compose_content("mixed-app")
+            .await
+            .unwrap()
+            .unwrap();
+        let platforms = platform::service_platforms(&stored).unwrap();
+        assert_eq!(platforms["web"].to_string(), "linux/amd64");
+        assert_eq!(platforms["worker"].to_string(), "linux/arm64");
+        let measurement = service.get_app_measurement("mixed-app").await.unwrap();
+        assert_eq!(
+            measurement.compose_hash,
+            ComposeMeasurement::new()
+                .calculate_compose_hash(&stored)
+                .unwrap()
+        );
+
+        // Without platforms the compose file is kept as sent
+        let response = service
+            .clone()
+            .start_app(request("plain-app", "", &[]), None)
+            .await
+            .unwrap();
+        wait_finished(&service, &response.task_id).await;
+        assert_eq!(
+            service.get_app_compose_content("plain-app").await.unwrap(),
+            Some(compose.to_string())
+        );
+        assert_eq!(service.backend.count("host_info"), 1);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_register_on_chain() {
+        let data_dir = tempfile::tempdir().unwrap();
+        let registrar = Registrar::new(Arc::new(ConfirmingContract), &ContractConfig::default());
+        let service = create_fake_service(data_dir.path()).with_registrar(registrar);
+        let measurement = |app_id: &str| AppMeasurement {
+            app_id: app_id.to_string(),
+            compose_hash: "ab".repeat(48),
+            volumes_hash: "cd".repeat(48),
+            deployer: hex::encode(TEST_DEPLOYER),
+            deployer_address: hex::encode(TEST_DEPLOYER),
+            timestamp: 0,
+            namespace: None,
+            secret_names: Vec::new(),
+        };
+
+        service.register_on_chain(&create_test_request(), &measurement("registered"));
+        let opted_out = StartAppRequest {
+            register_on_chain: Some(false),
+            ..create_test_request()
+        };
+        service.register_on_chain(&opted_out, &measurement("opted-out"));
+        assert!(service.app_registration("opted-out").is_none());
+        assert_eq!(
+            service.app_registration("registered").unwrap().state,
+            RegistrationState::Pending
+        );
+
+        tokio::time::sleep(Duration::from_secs(10)).await;
+        let status = service.app_registration("registered").unwrap();
+        assert_eq!(status.state, RegistrationState::Confirmed);
+        assert_eq!(status.block_number, Some(1));
+
+        // Removing the app drops its registration
+        service
+            .insert_app_measurement(measurement("registered"))
+            .await;
+        service
+            .force_remove_app("registered", false, false, &CancellationToken::new())
+            .await
+            .unwrap();
+        assert!(service.app_registration("registered").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_cancel_rolls_back_deployment() {
+        let data_dir = tempfile::tempdir().unwrap();
+        let service = Arc::new(create_fake_service(data_dir.path()));
+        service.backend.fail("pull", FakeFailure::Hang, 1);
+        let request = create_test_request();
+        let app_id = request.app_id.clone();
+        let response = service.clone().start_app(request, None).await.unwrap();
+
+        // Cancel once the pull is stuck
+        while service.backend.count("pull") == 0 {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        service.cancel_task(&response.task_id).await;
+        let task = wait_finished(&service, &response.task_id).await;
+
+        assert!(matches!(task.status, TaskState::Cancelled));
+        assert_eq!(task.stage_name(), "pulling");
+        assert_eq!(service.backend.count("up"), 0);
+        assert_eq!(service.backend.count("stop"), 1);
+        assert!(service
+            .get_app_compose_content(&app_id)
+            .await
+            .unwrap()
+            .is_none());
+        assert!(service.get_app_measurement(&app_id).await.is_none());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_image_scan() {
+        let data_dir = tempfile::tempdir().unwrap();
+        let scanner_dir = tempfile::tempdir().unwrap();
+        let mut service = create_fake_service(data_dir.path());
+        service.config.image_scan = Some(image_scan::stub_scanner_config(scanner_dir.path()));
+        let service = Arc::new(service);
+        let request = |app_id: &str, image: &str| StartAppRequest {
+            compose_content: format!(
+                "services:\n  web:\n    image: {}\n  cache:\n    image: redis:7\n",
+                image
+            ),
+            app_id: app_id.to_string(),
+            ..create_test_request()
+        };
+
+        // Clean images are scanned by digest between the pull and up
+        let response = service
+            .clone()
+            .start_app(request("clean-app", "nginx:alpine"), None)
+            .await
+            .unwrap();
+        let task = wait_finished(&service, &response.task_id).await;
+        assert_eq!(
+            service.backend.calls(),
+            ["write_files", "pull", "image_digests", "up", "wait_healthy"]
+                .map(|operation| format!("{} clean-app", operation))
+        );
+        assert_eq!(task.image_scans.len(), 2);
+        assert!(task
+            .image_scans
+            .iter()
+            .all(|scan| scan.passed && scan.image.contains("@sha256:")));
+        let events = service.get_app_events("clean-app").await;
+        assert_eq!(events[0].event, EVENT_IMAGE_SCAN);
+
+        // A vulnerable image stops the deployment before up
+        let response = service
+            .clone()
+            .start_app(
+                request("vulnerable-app", "registry.example.com/vulnerable:1"),
+                None,
+            )
+            .await
+            .unwrap();
+        let task = wait_finished(&service, &response.task_id).await;
+        assert_eq!(service.backend.count("up"), 1);
+        assert_eq!(task.stage_name(), "scanning");
+        match &task.status {
+            TaskState::Failed(code, error) => {
+                assert_eq!(*code, TaskErrorCode::ImageScanFailed);
+                assert!(
+                    error.contains("registry.example.com/vulnerable@sha256:"),
+                    "{}",
+                    error
+                );
+                assert!(
+                    error.contains("1 critical, 2 high, 1 low (threshold high)"),
+                    "{}",
+                    error
+                );
+            }
+            status => panic!("unexpected task status {:?}", status),
+        }
+        assert!(!task.image_scans[0].passed);
+        assert!(task.image_scans[1].passed);
+        assert!(service
+            .get_app_measurement("vulnerable-app")
+            .await
+            .is_none());
+        let events = service.get_app_events("vulnerable-app").await;
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].event, EVENT_IMAGE_SCAN);
+        assert!(events[0].detail.contains("\"critical\":1"));
+    }
+
+    #[tokio::test]
+    async fn test_deploy_panic_fails_task() {
+        crate::panic_layer::install_panic_hook();
+        let data_dir = tempfile::tempdir().unwrap();
+        let mut service = create_fake_service(data_dir.path());
+        service.deploy_queue = DeployQueue::new(1, 16);
+        service.backend.fail("up", FakeFailure::Panic, 1);
+        let service = Arc::new(service);
+        let request = |app_id: &str| StartAppRequest {
+            app_id: app_id.to_string(),
+            ..create_test_request()
+        };
+
+        let panicked = service
+            .clone()
+            .start_app(request("panic-app"), None)
+            .await
+            .unwrap();
+        let queued = service
+            .clone()
+            .start_app(request("queued-app"), None)
+            .await
+            .unwrap();
+
+        let task = wait_finished(&service, &panicked.task_id).await;
+        let TaskState::Failed(code, reason) = &task.status else {
+            panic!("task is not failed: {:?}", task.status);
+        };
+        assert_eq!(*code, TaskErrorCode::Panicked);
+        assert!(reason.contains("up of panic-app panicked"), "{}", reason);
+
+        // The deploy slot is released for the next deployment
+        let task = wait_finished(&service, &queued.task_id).await;
+        assert!(!matches!(
+            task.status,
+            TaskState::Failed(TaskErrorCode::Panicked, _)
+        ));
+        assert_eq!(service.backend.count("up"), 2);
+    }
+
+    /// Deploys through the Docker daemon and the compose CLI; run with
+    /// `cargo test --features docker-tests`
+    #[cfg(feature = "docker-tests")]
+    #[tokio::test]
+    async fn test_start_app_with_docker() {
+        let data_dir = tempfile::tempdir().unwrap();
         let config = BootServiceConfig {
             socket_path: "/var/run/docker.sock".to_string(),
+            data_dir: data_dir.path().join("apps"),
             ..Default::default()
         };
-        let service = Arc::new(BootService::new(&config).await.unwrap());
-        let request = create_request_with_mount_files();
-        let response = service.start_app(request).await.unwrap();
-        assert!(response.success);
+        start_and_remove_real_app(&config).await;
     }
 
+    /// Same through podman's socket and `podman compose`; run as root with
+    /// `cargo test --features podman-tests`
+    #[cfg(feature = "podman-tests")]
     #[tokio::test]
-    async fn test_get_evidence() {
+    async fn test_start_app_with_podman() {
+        let data_dir = tempfile::tempdir().unwrap();
         let config = BootServiceConfig {
-            socket_path: "/var/run/docker.sock".to_string(),
+            runtime: ContainerRuntime::Podman,
+            data_dir: data_dir.path().join("apps"),
             ..Default::default()
         };
-        let service = Arc::new(BootService::new(&config).await.unwrap());
+        start_and_remove_real_app(&config).await;
+    }
 
-        service
-            .clone()
-            .start_app(create_request_with_mount_files())
+    #[cfg(any(feature = "docker-tests", feature = "podman-tests"))]
+    async fn start_and_remove_real_app(config: &BootServiceConfig) {
+        let service = Arc::new(BootService::new(config).await.unwrap());
+        let request = create_real_request();
+        let app_id = request.app_id.clone();
+        let response = service.clone().start_app(request, None).await.unwrap();
+        assert!(response.success);
+
+        let task = wait_finished(&service, &response.task_id).await;
+        assert!(
+            matches!(
+                task.status,
+                TaskState::Completed(_) | TaskState::Failed(TaskErrorCode::MeasurementFailed, _)
+            ),
+            "{:?}",
+            task.status
+        );
+        assert!(service.app_status(&app_id).await.unwrap().container_count > 0);
+
+        let removal = service
+            .force_remove_app(&app_id, true, false, &CancellationToken::new())
             .await
             .unwrap();
+        assert!(removal.containers_removed > 0);
+        assert_eq!(
+            service.app_status(&app_id).await.unwrap().container_count,
+            0
+        );
+    }
+
+    #[tokio::test]
+    async fn test_get_evidence() {
+        let data_dir = tempfile::tempdir().unwrap();
+        let service = create_fake_service(data_dir.path());
 
         // Create custom report data (e.g., a nonce or hash)
         let custom_data = b"test-nonce-12345678";
@@ -0,0 +1,412 @@
+//! Image platforms of deployments
+//!
+//! StartApp may name a platform (`os/arch[/variant]`, e.g. `linux/arm64`) for
+//! all services of the compose file and override it per service. The chosen
+//! platforms are written to the compose file as the `platform:` key of each
+//! service before it is measured, so the compose hash covers them, and
+//! `docker compose pull` fetches the matching manifest.
+//!
+//! Architecture names are normalized the way the OCI image spec writes them
+//! (`x86_64` is `amd64`, `aarch64` is `arm64`, ...). A platform the host
+//! cannot run natively is rejected unless `boot.allow_emulation` is set.
+
+use crate::error::{DockerError, TappError, TappResult};
+use serde_yaml::{Mapping, Value};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+
+/// Compose key of a service's platform
+const PLATFORM_KEY: &str = "platform";
+
+/// Platform of an image, normalized
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Platform {
+    pub os: String,
+    pub architecture: String,
+    pub variant: Option<String>,
+}
+
+impl Platform {
+    /// Parse `os/arch[/variant]`
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let parts: Vec<&str> = text.trim().split('/').collect();
+        let valid = |part: &&str| {
+            !part.is_empty()
+                && part
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+        };
+        if !(2..=3).contains(&parts.len()) || !parts.iter().all(valid) {
+            return Err(format!(
+                "'{}' is not a platform of the form os/arch[/variant]",
+                text
+            ));
+        }
+        let (architecture, implied_variant) = normalize_arch(parts[1]);
+        let variant = parts
+            .get(2)
+            .map(|variant| variant.to_ascii_lowercase())
+            .or(implied_variant.map(str::to_string))
+            // The only arm64 variant; images leave it out
+            .filter(|variant| !(architecture == "arm64" && variant == "v8"));
+        Ok(Self {
+            os: parts[0].to_ascii_lowercase(),
+            architecture,
+            variant,
+        })
+    }
+
+    /// Platform of a host from the OS type and architecture the Docker
+    /// daemon reports
+    pub fn host(os_type: &str, architecture: &str) -> Self {
+        Self {
+            os: os_type.to_ascii_lowercase(),
+            architecture: normalize_arch(architecture).0,
+            variant: None,
+        }
+    }
+
+    /// Whether a host of this platform runs images of `image` natively
+    /// The host variant is not known, so variants are not compared.
+    pub fn runs(&self, image: &Platform) -> bool {
+        self.os == image.os && self.architecture == image.architecture
+    }
+}
+
+impl fmt::Display for Platform {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.os, self.architecture)?;
+        if let Some(variant) = &self.variant {
+            write!(f, "/{}", variant)?;
+        }
+        Ok(())
+    }
+}
+
+/// OCI name of an architecture, and the variant its alias implies
+fn normalize_arch(architecture: &str) -> (String, Option<&'static str>) {
+    let architecture = architecture.to_ascii_lowercase();
+    match architecture.as_str() {
+        "x86_64" | "x86-64" => ("amd64".to_string(), None),
+        "aarch64" => ("arm64".to_string(), None),
+        "armhf" | "armv7l" => ("arm".to_string(), Some("v7")),
+        "armel" | "armv6l" => ("arm".to_string(), Some("v6")),
+        "i386" | "i686" => ("386".to_string(), None),
+        _ => (architecture, None),
+    }
+}
+
+/// `compose_content` with the platform of every service set
+/// `overrides` (service name, platform) come first, then the `platform:` the
+/// compose file gives, then `platform`. The content is returned unchanged
+/// when neither is given; otherwise it is re-serialized with every platform
+/// in normalized form.
+pub fn inject(
+    compose_content: &str,
+    platform: &str,
+    overrides: &HashMap<String, String>,
+) -> TappResult<String> {
+    if platform.is_empty() && overrides.is_empty() {
+        return Ok(compose_content.to_string());
+    }
+    let default = if platform.is_empty() {
+        None
+    } else {
+        Some(parse_field("platform", platform)?)
+    };
+
+    let mut parsed = parse_compose(compose_content)?;
+    let services = parsed
+        .get_mut("services")
+        .and_then(Value::as_mapping_mut)
+        .ok_or_else(|| DockerError::InvalidComposeContent {
+            reason: "Compose file has no services".to_string(),
+        })?;
+
+    let mut unknown: Vec<&str> = overrides
+        .keys()
+        .filter(|name| !services.contains_key(name.as_str()))
+        .map(String::as_str)
+        .collect();
+    if !unknown.is_empty() {
+        unknown.sort();
+        return Err(TappError::InvalidParameter {
+            field: "service_platforms".to_string(),
+            reason: format!("Services not in the compose file: {}", unknown.join(", ")),
+        });
+    }
+
+    for (name, service) in services.iter_mut() {
+        let name = name.as_str().unwrap_or_default();
+        let Some(service) = service.as_mapping_mut() else {
+            return Err(DockerError::InvalidComposeContent {
+                reason: format!("Service '{}' is not a mapping", name),
+            }
+            .into());
+        };
+        let chosen = match overrides.get(name) {
+            Some(platform) => Some(parse_field("service_platforms", platform)?),
+            None => match service_platform(name, service)? {
+                Some(platform) => Some(platform),
+                None => default.clone(),
+            },
+        };
+        if let Some(platform) = chosen {
+            service.insert(PLATFORM_KEY.into(), platform.to_string().into());
+        }
+    }
+
+    serde_yaml::to_string(&parsed).map_err(|e| {
+        DockerError::InvalidComposeContent {
+            reason: format!("YAML serialization error: {}", e),
+        }
+        .into()
+    })
+}
+
+/// Platforms the services of `compose_content` set, by service name
+/// Services without one run the host's platform.
+pub fn service_platforms(compose_content: &str) -> TappResult<BTreeMap<String, Platform>> {
+    let parsed = parse_compose(compose_content)?;
+    let mut platforms = BTreeMap::new();
+    let Some(services) = parsed.get("services").and_then(Value::as_mapping) else {
+        return Ok(platforms);
+    };
+    for (name, service) in services {
+        let name = name.as_str().unwrap_or_default();
+        if let Some(platform) = service
+            .as_mapping()
+            .map(|service| service_platform(name, service))
+            .transpose()?
+            .flatten()
+        {
+            platforms.insert(name.to_string(), platform);
+        }
+    }
+    Ok(platforms)
+}
+
+/// Fail unless a host of platform `host` runs every platform of `platforms`
+/// natively, or `allow_emulation` is set
+pub fn check(
+    platforms: &BTreeMap<String, Platform>,
+    host: &Platform,
+    allow_emulation: bool,
+) -> TappResult<()> {
+    if allow_emulation {
+        return Ok(());
+    }
+    let foreign: Vec<String> = platforms
+        .iter()
+        .filter(|(_, platform)| !host.runs(platform))
+        .map(|(name, platform)| format!("{} ({})", name, platform))
+        .collect();
+    if foreign.is_empty() {
+        return Ok(());
+    }
+    Err(TappError::InvalidParameter {
+        field: "platform".to_string(),
+        reason: format!(
+            "Host platform is {}, services request {}; set boot.allow_emulation to run them emulated",
+            host,
+            foreign.join(", ")
+        ),
+    })
+}
+
+fn parse_compose(compose_content: &str) -> TappResult<Value> {
+    serde_yaml::from_str(compose_content).map_err(|e| {
+        DockerError::InvalidComposeContent {
+            reason: format!("YAML parse error: {}", e),
+        }
+        .into()
+    })
+}
+
+fn parse_field(field: &str, platform: &str) -> TappResult<Platform> {
+    Platform::parse(platform).map_err(|reason| TappError::InvalidParameter {
+        field: field.to_string(),
+        reason,
+    })
+}
+
+/// The `platform:` a service sets, if any
+fn service_platform(name: &str, service: &Mapping) -> TappResult<Option<Platform>> {
+    let Some(value) = service.get(PLATFORM_KEY) else {
+        return Ok(None);
+    };
+    let platform = value
+        .as_str()
+        .ok_or_else(|| format!("'{}' is not a string", PLATFORM_KEY))
+        .and_then(Platform::parse)
+        .map_err(|reason| DockerError::InvalidComposeContent {
+            reason: format!("Invalid platform of service '{}': {}", name, reason),
+        })?;
+    Ok(Some(platform))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::boot::measurement::ComposeMeasurement;
+
+    const COMPOSE: &str = r#"
+services:
+  web:
+    image: nginx:alpine
+    ports:
+      - "80:80"
+  worker:
+    image: worker
+"#;
+
+    fn overrides(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs
+            .iter()
+            .map(|(name, platform)| (name.to_string(), platform.to_string()))
+            .collect()
+    }
+
+    fn platforms(compose: &str) -> Vec<(String, String)> {
+        service_platforms(compose)
+            .unwrap()
+            .into_iter()
+            .map(|(name, platform)| (name, platform.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_parse() {
+        for (text, expected) in [
+            ("linux/amd64", "linux/amd64"),
+            ("linux/x86_64", "linux/amd64"),
+            ("Linux/AArch64", "linux/arm64"),
+            ("linux/arm64/v8", "linux/arm64"),
+            ("linux/armv7l", "linux/arm/v7"),
+            ("linux/arm/v6", "linux/arm/v6"),
+            ("linux/i686", "linux/386"),
+        ] {
+            assert_eq!(
+                Platform::parse(text).unwrap().to_string(),
+                expected,
+                "{}",
+                text
+            );
+        }
+        for text in ["", "amd64", "linux/", "linux/amd64/v1/x", "linux/amd 64"] {
+            assert!(Platform::parse(text).is_err(), "{}", text);
+        }
+        assert_eq!(Platform::host("linux", "x86_64").to_string(), "linux/amd64");
+    }
+
+    #[test]
+    fn test_inject() {
+        assert_eq!(inject(COMPOSE, "", &HashMap::new()).unwrap(), COMPOSE);
+
+        let injected = inject(COMPOSE, "linux/x86_64", &HashMap::new()).unwrap();
+        assert_eq!(
+            platforms(&injected),
+            [
+                ("web".to_string(), "linux/amd64".to_string()),
+                ("worker".to_string(), "linux/amd64".to_string()),
+            ]
+        );
+
+        let injected = inject(
+            COMPOSE,
+            "linux/amd64",
+            &overrides(&[("worker", "linux/arm64")]),
+        )
+        .unwrap();
+        assert_eq!(
+            platforms(&injected),
+            [
+                ("web".to_string(), "linux/amd64".to_string()),
+                ("worker".to_string(), "linux/arm64".to_string()),
+            ]
+        );
+
+        // An override alone leaves the other services on the host platform
+        let injected = inject(COMPOSE, "", &overrides(&[("web", "linux/aarch64")])).unwrap();
+        assert_eq!(
+            platforms(&injected),
+            [("web".to_string(), "linux/arm64".to_string())]
+        );
+
+        // The compose file's own platform wins over the request-wide one
+        let pinned = "services:\n  web:\n    image: nginx\n    platform: linux/arm64\n  db:\n    image: postgres\n";
+        let injected = inject(pinned, "linux/amd64", &HashMap::new()).unwrap();
+        assert_eq!(
+            platforms(&injected),
+            [
+                ("db".to_string(), "linux/amd64".to_string()),
+                ("web".to_string(), "linux/arm64".to_string()),
+            ]
+        );
+
+        let err = inject(COMPOSE, "", &overrides(&[("cache", "linux/amd64")])).unwrap_err();
+        assert!(err.to_string().contains("cache"), "{}", err);
+        assert!(inject(COMPOSE, "amd64", &HashMap::new()).is_err());
+        assert!(inject(COMPOSE, "", &overrides(&[("web", "arm")])).is_err());
+    }
+
+    #[test]
+    fn test_injection_is_measured_stably() {
+        let measurement = ComposeMeasurement::new();
+        let injected = inject(COMPOSE, "linux/amd64", &HashMap::new()).unwrap();
+        let hash = measurement.calculate_compose_hash(&injected).unwrap();
+
+        // Written by hand, in another order and alias
+        let by_hand = r#"
+services:
+  worker:
+    platform: linux/amd64
+    image: worker
+  web:
+    platform: linux/x86_64
+    ports: ["80:80"]
+    image: nginx:alpine
+"#;
+        let injected_by_hand = inject(by_hand, "linux/amd64", &HashMap::new()).unwrap();
+        assert_eq!(
+            measurement
+                .calculate_compose_hash(&injected_by_hand)
+                .unwrap(),
+            hash
+        );
+
+        // Injecting again changes nothing
+        let again = inject(&injected, "linux/amd64", &HashMap::new()).unwrap();
+        assert_eq!(measurement.calculate_compose_hash(&again).unwrap(), hash);
+        assert_ne!(measurement.calculate_compose_hash(COMPOSE).unwrap(), hash);
+    }
+
+    #[test]
+    fn test_check() {
+        let injected = inject(
+            COMPOSE,
+            "linux/amd64",
+            &overrides(&[("worker", "linux/arm64")]),
+        )
+        .unwrap();
+        let requested = service_platforms(&injected).unwrap();
+
+        let err = check(&requested, &Platform::host("linux", "x86_64"), false).unwrap_err();
+        match &err {
+            TappError::InvalidParameter { field, reason } => {
+                assert_eq!(field, "platform");
+                assert!(reason.contains("linux/amd64"), "{}", reason);
+                assert!(reason.contains("worker (linux/arm64)"), "{}", reason);
+                assert!(!reason.contains("web"), "{}", reason);
+            }
+            other => panic!("unexpected error: {}", other),
+        }
+        assert!(check(&requested, &Platform::host("linux", "x86_64"), true).is_ok());
+
+        let native =
+            service_platforms(&inject(COMPOSE, "linux/arm64/v8", &HashMap::new()).unwrap())
+                .unwrap();
+        assert!(check(&native, &Platform::host("linux", "aarch64"), false).is_ok());
+        assert!(check(&BTreeMap::new(), &Platform::host("linux", "aarch64"), false).is_ok());
+    }
+}
@@ -0,0 +1,210 @@
+use crate::error::{AttestationError, TappResult};
+use base64::Engine;
+use serde::Deserialize;
+
+/// `tee_type` of TDX quotes
+pub const TDX_TEE_TYPE: u32 = 0x81;
+
+/// Quote header, before the report body
+const HEADER_LEN: usize = 48;
+
+/// TDX 1.0 report body; TDX 1.5 bodies append fields after it
+const TD_REPORT_LEN: usize = 584;
+
+/// Size of each measurement register
+const MEASUREMENT_LEN: usize = 48;
+
+/// Evidence of the TDX attester: the quote with optional event logs
+#[derive(Deserialize)]
+struct TdxEvidence {
+    /// Base64 quote
+    quote: String,
+}
+
+/// Fields of a TDX quote's report body
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TdReport {
+    pub tee_tcb_svn: Vec<u8>,
+    pub mr_seam: Vec<u8>,
+    pub mr_signer_seam: Vec<u8>,
+    pub seam_attributes: Vec<u8>,
+    pub td_attributes: Vec<u8>,
+    pub xfam: Vec<u8>,
+    pub mr_td: Vec<u8>,
+    pub mr_config_id: Vec<u8>,
+    pub mr_owner: Vec<u8>,
+    pub mr_owner_config: Vec<u8>,
+    pub rtmrs: [Vec<u8>; 4],
+    pub report_data: Vec<u8>,
+}
+
+/// A parsed TDX quote
+/// Only the header and report body are read; the signature is not verified.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Quote {
+    pub version: u16,
+    pub tee_type: u32,
+    pub report: TdReport,
+}
+
+fn invalid(reason: impl Into<String>) -> AttestationError {
+    AttestationError::InvalidQuote {
+        reason: reason.into(),
+    }
+}
+
+/// The quote inside GetEvidence's `evidence`
+/// The TDX attester returns JSON with a base64 `quote`; anything else is
+/// taken to be the raw quote.
+pub fn quote_from_evidence(evidence: &[u8]) -> TappResult<Vec<u8>> {
+    if evidence.first() != Some(&b'{') {
+        return Ok(evidence.to_vec());
+    }
+    let evidence: TdxEvidence = serde_json::from_slice(evidence).map_err(|e| {
+        AttestationError::UnsupportedEvidenceFormat {
+            format: format!("JSON without a TDX quote ({})", e),
+        }
+    })?;
+    base64::engine::general_purpose::STANDARD
+        .decode(evidence.quote.trim())
+        .map_err(|e| invalid(format!("quote is not base64: {}", e)).into())
+}
+
+/// Parse the header and TD report of a version 4 or 5 TDX quote
+pub fn parse_quote(quote: &[u8]) -> TappResult<Quote> {
+    if quote.len() < HEADER_LEN {
+        return Err(invalid(format!(
+            "{} bytes is too short for a quote header",
+            quote.len()
+        ))
+        .into());
+    }
+
+    let version = u16::from_le_bytes([quote[0], quote[1]]);
+    let tee_type = u32::from_le_bytes(quote[4..8].try_into().unwrap());
+    if tee_type != TDX_TEE_TYPE {
+        return Err(invalid(format!("tee_type {:#x} is not TDX", tee_type)).into());
+    }
+
+    // Version 5 prefixes the body with its type and size
+    let body_offset = match version {
+        4 => HEADER_LEN,
+        5 => HEADER_LEN + 6,
+        _ => return Err(invalid(format!("unsupported quote version {}", version)).into()),
+    };
+    let body = quote
+        .get(body_offset..body_offset + TD_REPORT_LEN)
+        .ok_or_else(|| {
+            invalid(format!(
+                "{} bytes is too short for a TD report",
+                quote.len()
+            ))
+        })?;
+
+    let mut offset = 0;
+    let mut field = |len: usize| {
+        let value = body[offset..offset + len].to_vec();
+        offset += len;
+        value
+    };
+    let tee_tcb_svn = field(16);
+    let mr_seam = field(MEASUREMENT_LEN);
+    let mr_signer_seam = field(MEASUREMENT_LEN);
+    let seam_attributes = field(8);
+    let td_attributes = field(8);
+    let xfam = field(8);
+    let mr_td = field(MEASUREMENT_LEN);
+    let mr_config_id = field(MEASUREMENT_LEN);
+    let mr_owner = field(MEASUREMENT_LEN);
+    let mr_owner_config = field(MEASUREMENT_LEN);
+    let rtmrs = [
+        field(MEASUREMENT_LEN),
+        field(MEASUREMENT_LEN),
+        field(MEASUREMENT_LEN),
+        field(MEASUREMENT_LEN),
+    ];
+    let report_data = field(64);
+
+    Ok(Quote {
+        version,
+        tee_type,
+        report: TdReport {
+            tee_tcb_svn,
+            mr_seam,
+            mr_signer_seam,
+            seam_attributes,
+            td_attributes,
+            xfam,
+            mr_td,
+            mr_config_id,
+            mr_owner,
+            mr_owner_config,
+            rtmrs,
+            report_data,
+        },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FIXTURE: &str = concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/src/boot/testdata/tdx_quote_v4.hex"
+    );
+
+    /// Quote bytes of the fixture; `#` lines are comments
+    fn fixture() -> Vec<u8> {
+        let hex: String = std::fs::read_to_string(FIXTURE)
+            .unwrap()
+            .lines()
+            .filter(|line| !line.starts_with('#'))
+            .collect();
+        hex::decode(hex).unwrap()
+    }
+
+    #[test]
+    fn test_parse_quote() {
+        let quote = parse_quote(&fixture()).unwrap();
+        assert_eq!(quote.version, 4);
+        assert_eq!(quote.tee_type, TDX_TEE_TYPE);
+
+        let report = &quote.report;
+        assert_eq!(report.mr_td, vec![0x11; 48]);
+        for (i, rtmr) in report.rtmrs.iter().enumerate() {
+            assert_eq!(rtmr, &vec![0x20 + i as u8; 48], "rtmr{}", i);
+        }
+        let mut report_data = b"tapp-fixture".to_vec();
+        report_data.resize(64, 0);
+        assert_eq!(report.report_data, report_data);
+
+        // The same quote wrapped in the attester's JSON evidence
+        let evidence = serde_json::json!({
+            "cc_eventlog": null,
+            "quote": base64::engine::general_purpose::STANDARD.encode(fixture()),
+        });
+        let unwrapped = quote_from_evidence(evidence.to_string().as_bytes()).unwrap();
+        assert_eq!(unwrapped, fixture());
+        assert_eq!(quote_from_evidence(&fixture()).unwrap(), fixture());
+    }
+
+    #[test]
+    fn test_parse_quote_rejects_malformed() {
+        let quote = fixture();
+        let error = |bytes: &[u8]| parse_quote(bytes).unwrap_err().to_string();
+
+        assert!(error(&quote[..40]).contains("too short for a quote header"));
+        assert!(error(&quote[..HEADER_LEN + 100]).contains("too short for a TD report"));
+
+        let mut sgx = quote.clone();
+        sgx[4..8].copy_from_slice(&0u32.to_le_bytes());
+        assert!(error(&sgx).contains("is not TDX"));
+
+        let mut v3 = quote;
+        v3[0..2].copy_from_slice(&3u16.to_le_bytes());
+        assert!(error(&v3).contains("unsupported quote version 3"));
+
+        assert!(quote_from_evidence(b"{\"tee\": \"tdx\"}").is_err());
+    }
+}
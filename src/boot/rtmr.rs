@@ -0,0 +1,385 @@
+//! Replay of the runtime measurement extends made by the service
+//!
+//! Every extend goes through the attestation agent, which hashes the event
+//! `"<domain> <operation> <content>"` with SHA-384 and extends the register
+//! with the digest: `RTMR = SHA-384(RTMR || digest)`. The agent's default
+//! register, PCR 17, is RTMR3 on TDX; RTMRs are zero when the guest boots.
+//!
+//! The service keeps each extend it made since the guest booted in an
+//! append-only history, one JSON record per line with the exact content, so
+//! the register value can be recomputed and compared with a quote. Records
+//! of an earlier boot (another `/proc/sys/kernel/random/boot_id`) are dropped
+//! when the history is opened.
+
+use crate::error::TappResult;
+use serde::{Deserialize, Serialize};
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use tracing::{info, warn};
+
+/// Register the service's extends land in
+pub const TAPP_RTMR_INDEX: u32 = 3;
+
+/// Number of RTMRs of a TD
+pub const RTMR_COUNT: u32 = 4;
+
+/// Size of a register and of an event digest
+pub const DIGEST_LEN: usize = 48;
+
+/// Identifies the current boot of the guest
+const BOOT_ID_PATH: &str = "/proc/sys/kernel/random/boot_id";
+
+pub type Digest = [u8; DIGEST_LEN];
+
+/// One extend of the runtime measurement
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ExtendRecord {
+    /// Boot of the guest the extend was made in
+    pub boot_id: String,
+    pub rtmr_index: u32,
+    pub domain: String,
+    pub operation: String,
+    pub content: String,
+    pub timestamp: i64,
+}
+
+impl ExtendRecord {
+    /// Digest the attestation agent extends the register with
+    pub fn event_digest(&self) -> Digest {
+        event_digest(&self.domain, &self.operation, &self.content)
+    }
+}
+
+/// Digest of the event `domain operation content`
+pub fn event_digest(domain: &str, operation: &str, content: &str) -> Digest {
+    crate::utils::sha384(format!("{} {} {}", domain, operation, content).as_bytes())
+}
+
+/// Register value after extending `rtmr` with `digest`
+pub fn extend(rtmr: &Digest, digest: &Digest) -> Digest {
+    let mut data = Vec::with_capacity(2 * DIGEST_LEN);
+    data.extend_from_slice(rtmr);
+    data.extend_from_slice(digest);
+    crate::utils::sha384(&data)
+}
+
+/// Extends made since the guest booted, persisted unless in memory only
+pub struct ExtendHistory {
+    path: Option<PathBuf>,
+    boot_id: String,
+    records: Mutex<Vec<ExtendRecord>>,
+}
+
+impl ExtendHistory {
+    /// History kept in memory only
+    pub fn in_memory() -> Self {
+        Self {
+            path: None,
+            boot_id: current_boot_id(),
+            records: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// History at `path` (empty: in memory only)
+    pub fn from_path(path: &str) -> TappResult<Self> {
+        if path.is_empty() {
+            return Ok(Self::in_memory());
+        }
+        Self::open(Path::new(path), current_boot_id())
+    }
+
+    /// Load the history at `path`, dropping the records of other boots than
+    /// `boot_id`
+    /// Lines that do not parse are skipped; the last one may be cut short by
+    /// a crash while it was written.
+    pub fn open(path: &Path, boot_id: String) -> TappResult<Self> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let content = match std::fs::read_to_string(path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(e.into()),
+        };
+
+        let mut records = Vec::new();
+        let mut dropped = 0;
+        for line in content.lines().filter(|line| !line.trim().is_empty()) {
+            match serde_json::from_str::<ExtendRecord>(line) {
+                Ok(record) if record.boot_id == boot_id => records.push(record),
+                Ok(_) => dropped += 1,
+                Err(e) => {
+                    dropped += 1;
+                    warn!(path = %path.display(), error = %e, "Skipping unreadable extend record");
+                }
+            }
+        }
+        if dropped > 0 {
+            let tmp = path.with_extension("tmp");
+            let mut lines = String::new();
+            for record in &records {
+                lines.push_str(&serde_json::to_string(record)?);
+                lines.push('\n');
+            }
+            std::fs::write(&tmp, lines)?;
+            std::fs::rename(&tmp, path)?;
+        }
+        info!(
+            path = %path.display(),
+            loaded = records.len(),
+            dropped = dropped,
+            "Loaded runtime measurement extend history"
+        );
+
+        Ok(Self {
+            path: Some(path.to_path_buf()),
+            boot_id,
+            records: Mutex::new(records),
+        })
+    }
+
+    /// Add an extend of `rtmr_index` that the attestation agent made
+    pub fn record(
+        &self,
+        rtmr_index: u32,
+        domain: &str,
+        operation: &str,
+        content: &str,
+    ) -> TappResult<()> {
+        let record = ExtendRecord {
+            boot_id: self.boot_id.clone(),
+            rtmr_index,
+            domain: domain.to_string(),
+            operation: operation.to_string(),
+            content: content.to_string(),
+            timestamp: crate::utils::current_timestamp(),
+        };
+        let mut records = self.records.lock().unwrap();
+        if let Some(path) = &self.path {
+            let mut line = serde_json::to_string(&record)?;
+            line.push('\n');
+            OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)?
+                .write_all(line.as_bytes())?;
+        }
+        records.push(record);
+        Ok(())
+    }
+
+    /// Extends of `rtmr_index`, in order
+    pub fn records(&self, rtmr_index: u32) -> Vec<ExtendRecord> {
+        self.records
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|record| record.rtmr_index == rtmr_index)
+            .cloned()
+            .collect()
+    }
+}
+
+/// Boot ID of the guest, empty if unknown
+fn current_boot_id() -> String {
+    std::fs::read_to_string(BOOT_ID_PATH)
+        .map(|id| id.trim().to_string())
+        .unwrap_or_default()
+}
+
+/// One replayed extend and the register value after it
+#[derive(Debug, Clone)]
+pub struct ReplayStep {
+    pub record: ExtendRecord,
+    pub event_digest: Digest,
+    pub rtmr: Digest,
+}
+
+/// A register recomputed from its extend history
+#[derive(Debug, Clone)]
+pub struct Replay {
+    pub rtmr_index: u32,
+    pub initial: Digest,
+    pub steps: Vec<ReplayStep>,
+}
+
+impl Replay {
+    /// Replay `records` from the boot value of the register
+    pub fn run(rtmr_index: u32, records: Vec<ExtendRecord>) -> Self {
+        let initial = [0; DIGEST_LEN];
+        let mut rtmr = initial;
+        let steps = records
+            .into_iter()
+            .map(|record| {
+                let event_digest = record.event_digest();
+                rtmr = extend(&rtmr, &event_digest);
+                ReplayStep {
+                    record,
+                    event_digest,
+                    rtmr,
+                }
+            })
+            .collect();
+        Self {
+            rtmr_index,
+            initial,
+            steps,
+        }
+    }
+
+    /// Register value after the last step
+    pub fn value(&self) -> Digest {
+        self.steps.last().map_or(self.initial, |step| step.rtmr)
+    }
+
+    /// First step the register `quoted` does not account for, or None if it
+    /// holds the replayed value
+    /// When `quoted` is the value after some earlier step, the steps after it
+    /// are missing from the register. When it is none of the values, the
+    /// register was extended outside the history and the replay diverges
+    /// from the start.
+    pub fn first_divergent_step(&self, quoted: &[u8]) -> Option<usize> {
+        if quoted == self.value().as_slice() {
+            return None;
+        }
+        // Value after each number of steps
+        let values: Vec<&Digest> = std::iter::once(&self.initial)
+            .chain(self.steps.iter().map(|step| &step.rtmr))
+            .collect();
+        Some(
+            values
+                .iter()
+                .rposition(|value| quoted == value.as_slice())
+                .unwrap_or(0),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(operation: &str, content: &str) -> ExtendRecord {
+        ExtendRecord {
+            boot_id: "boot-1".to_string(),
+            rtmr_index: TAPP_RTMR_INDEX,
+            domain: "tapp.0g.com".to_string(),
+            operation: operation.to_string(),
+            content: content.to_string(),
+            timestamp: 0,
+        }
+    }
+
+    #[test]
+    fn test_extend_vectors() {
+        // SHA-384("tapp.0g.com start_app {}")
+        assert_eq!(
+            hex::encode(event_digest("tapp.0g.com", "start_app", "{}")),
+            "4e3b741f4dd94170c283826b38f518fead6880c298c75666d889c7177b4ee545dea4871ed832570ad9585df83beea2d1"
+        );
+
+        // Extending the zero register with the zero digest is SHA-384 of 96
+        // zero bytes
+        assert_eq!(
+            hex::encode(extend(&[0; DIGEST_LEN], &[0; DIGEST_LEN])),
+            "f57bb7ed82c6ae4a29e6c9879338c592c7d42a39135583e8ccbe3940f2344b0eb6eb8503db0ffd6a39ddd00cd07d8317"
+        );
+
+        let replay = Replay::run(
+            TAPP_RTMR_INDEX,
+            vec![record("start_app", "a"), record("stop_app", "b")],
+        );
+        let first = extend(
+            &[0; DIGEST_LEN],
+            &event_digest("tapp.0g.com", "start_app", "a"),
+        );
+        let second = extend(&first, &event_digest("tapp.0g.com", "stop_app", "b"));
+        assert_eq!(replay.steps[0].rtmr, first);
+        assert_eq!(replay.value(), second);
+        assert_eq!(Replay::run(0, vec![]).value(), [0; DIGEST_LEN]);
+    }
+
+    #[test]
+    fn test_first_divergent_step() {
+        let replay = Replay::run(
+            TAPP_RTMR_INDEX,
+            vec![record("a", "1"), record("b", "2"), record("c", "3")],
+        );
+        assert_eq!(replay.first_divergent_step(&replay.value()), None);
+        // The register stopped after the first step
+        assert_eq!(replay.first_divergent_step(&replay.steps[0].rtmr), Some(1));
+        assert_eq!(replay.first_divergent_step(&replay.initial), Some(0));
+        // Extended outside the history
+        assert_eq!(replay.first_divergent_step(&[7; DIGEST_LEN]), Some(0));
+    }
+
+    #[test]
+    fn test_history_persistence() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("state").join("rtmr-history.jsonl");
+
+        let history = ExtendHistory::open(&path, "boot-1".to_string()).unwrap();
+        history
+            .record(
+                TAPP_RTMR_INDEX,
+                "tapp.0g.com",
+                "start_app",
+                "{\"app_id\":\"a\"}\n",
+            )
+            .unwrap();
+        history.record(2, "other", "op", "x").unwrap();
+        let reopened = ExtendHistory::open(&path, "boot-1".to_string()).unwrap();
+        let records = reopened.records(TAPP_RTMR_INDEX);
+        assert_eq!(records, history.records(TAPP_RTMR_INDEX));
+        assert_eq!(records[0].content, "{\"app_id\":\"a\"}\n");
+        assert_eq!(reopened.records(2).len(), 1);
+
+        // A torn last line is skipped
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(b"{\"boot_id\":\"boot-1\",\"rt").unwrap();
+        let reopened = ExtendHistory::open(&path, "boot-1".to_string()).unwrap();
+        assert_eq!(reopened.records(TAPP_RTMR_INDEX).len(), 1);
+
+        // Another boot starts from an empty register
+        let rebooted = ExtendHistory::open(&path, "boot-2".to_string()).unwrap();
+        assert!(rebooted.records(TAPP_RTMR_INDEX).is_empty());
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "");
+    }
+
+    /// Extend RTMR3 of the TD the test runs in, and check the new value
+    /// against the one computed here
+    #[cfg(feature = "tdx-tests")]
+    #[tokio::test]
+    async fn test_extend_matches_tdx() {
+        use crate::boot::quote::{parse_quote, quote_from_evidence};
+        use attestation_agent::{AttestationAPIs, AttestationAgent};
+
+        let mut aa = AttestationAgent::new(None).unwrap();
+        aa.init().await.unwrap();
+        let quoted = |evidence: Vec<u8>| {
+            let quote = parse_quote(&quote_from_evidence(&evidence).unwrap()).unwrap();
+            let rtmr: Digest = quote.report.rtmrs[TAPP_RTMR_INDEX as usize]
+                .as_slice()
+                .try_into()
+                .unwrap();
+            rtmr
+        };
+
+        let before = quoted(aa.get_evidence(&[0; 64]).await.unwrap());
+        let content = format!("{{\"test\":{}}}", crate::utils::current_timestamp());
+        aa.extend_runtime_measurement("tapp.0g.com", "replay_test", &content, None)
+            .await
+            .unwrap();
+        let after = quoted(aa.get_evidence(&[0; 64]).await.unwrap());
+
+        assert_eq!(
+            hex::encode(after),
+            hex::encode(extend(
+                &before,
+                &event_digest("tapp.0g.com", "replay_test", &content)
+            ))
+        );
+    }
+}
@@ -1,48 +1,233 @@
-use crate::proto::{TaskResult, TaskStatus as ProtoTaskStatus};
+use super::image_scan::ImageScanResult;
+use crate::config::BootServiceConfig;
+use crate::error::TappResult;
+use crate::proto::{
+    StartAppRequest, TaskErrorCode as ProtoTaskErrorCode, TaskKind as ProtoTaskKind, TaskResult,
+    TaskStatus as ProtoTaskStatus,
+};
+use lru::LruCache;
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
-use std::sync::Arc;
-use tokio::sync::RwLock;
+use std::num::NonZeroUsize;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Weak};
+use std::time::Duration;
+use tokio::sync::{broadcast, RwLock};
+use tokio::task::JoinHandle;
+use tokio::time::Instant;
+use tokio_util::sync::CancellationToken;
+use tracing::{info, warn};
 use uuid::Uuid;
 
-#[derive(Debug, Clone)]
+/// Interval between sweeps of finished tasks
+const TASK_SWEEP_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Number of purged task IDs remembered to answer "expired" instead of "unknown"
+const MAX_TOMBSTONES: usize = 4096;
+
+/// Task changes buffered for slow watchers before they see `Lagged`
+const TASK_UPDATES_CAPACITY: usize = 256;
+
+/// Failure reason of tasks that were unfinished when the service stopped
+pub const INTERRUPTED_REASON: &str = "interrupted by service restart";
+
+/// Failure reason of tasks the service stopped while shutting down
+pub const SHUTDOWN_REASON: &str = "interrupted by service shutdown";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum TaskStatus {
     Pending,
+    /// Waiting for a deployment slot
+    Queued,
     Running,
     Completed(TaskSuccessResult),
-    Failed(String),
+    Failed(TaskErrorCode, String),
+    /// Cancellation requested; the worker is rolling back
+    Cancelling,
+    Cancelled,
 }
 
-#[derive(Debug, Clone)]
+/// Machine-readable reason a task failed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TaskErrorCode {
+    /// Rejected before deploying (bad app ID, app already deployed, ...)
+    InvalidRequest,
+    /// The compose file could not be parsed
+    InvalidCompose,
+    PullFailed,
+    StartFailed,
+    /// A published port is already in use on the host
+    PortConflict,
+    Timeout,
+    /// Recording the runtime measurement failed
+    MeasurementFailed,
+    DockerUnavailable,
+    /// The service stopped while the task was running
+    Interrupted,
+    /// The deployment panicked (a bug); see the service log for the backtrace
+    Panicked,
+    /// An image failed the vulnerability scan (`boot.image_scan`)
+    ImageScanFailed,
+    Internal,
+}
+
+impl TaskErrorCode {
+    pub fn to_proto(&self) -> ProtoTaskErrorCode {
+        match self {
+            TaskErrorCode::InvalidRequest => ProtoTaskErrorCode::InvalidRequest,
+            TaskErrorCode::InvalidCompose => ProtoTaskErrorCode::InvalidCompose,
+            TaskErrorCode::PullFailed => ProtoTaskErrorCode::PullFailed,
+            TaskErrorCode::StartFailed => ProtoTaskErrorCode::StartFailed,
+            TaskErrorCode::PortConflict => ProtoTaskErrorCode::PortConflict,
+            TaskErrorCode::Timeout => ProtoTaskErrorCode::Timeout,
+            TaskErrorCode::MeasurementFailed => ProtoTaskErrorCode::MeasurementFailed,
+            TaskErrorCode::DockerUnavailable => ProtoTaskErrorCode::DockerUnavailable,
+            TaskErrorCode::Interrupted => ProtoTaskErrorCode::Interrupted,
+            TaskErrorCode::Panicked => ProtoTaskErrorCode::Panicked,
+            TaskErrorCode::ImageScanFailed => ProtoTaskErrorCode::ImageScanFailed,
+            TaskErrorCode::Internal => ProtoTaskErrorCode::InternalError,
+        }
+    }
+}
+
+/// Operation a task performs
+/// Only StartApp runs as a background task today.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TaskKind {
+    #[default]
+    StartApp,
+}
+
+impl TaskKind {
+    pub fn to_proto(&self) -> ProtoTaskKind {
+        match self {
+            TaskKind::StartApp => ProtoTaskKind::StartApp,
+        }
+    }
+}
+
+/// Phase of a StartApp task, in execution order
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum TaskStage {
+    Validating,
+    WritingFiles,
+    Pulling,
+    /// Only with `boot.image_scan` enabled
+    Scanning,
+    Starting,
+    HealthWait,
+    Measuring,
+    Done,
+}
+
+impl TaskStage {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TaskStage::Validating => "validating",
+            TaskStage::WritingFiles => "writing-files",
+            TaskStage::Pulling => "pulling",
+            TaskStage::Scanning => "scanning",
+            TaskStage::Starting => "starting",
+            TaskStage::HealthWait => "health-wait",
+            TaskStage::Measuring => "measuring",
+            TaskStage::Done => "done",
+        }
+    }
+
+    /// Progress reported when the stage is entered
+    pub fn percent(&self) -> i32 {
+        match self {
+            TaskStage::Validating => 5,
+            TaskStage::WritingFiles => 10,
+            TaskStage::Pulling => 20,
+            TaskStage::Scanning => 50,
+            TaskStage::Starting => 60,
+            TaskStage::HealthWait => 75,
+            TaskStage::Measuring => 90,
+            TaskStage::Done => 100,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TaskSuccessResult {
     pub app_id: String,
     pub deployer: Vec<u8>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Task {
     pub id: String,
+    /// Operation the task performs (tasks stored before it was recorded are StartApp)
+    #[serde(default)]
+    pub kind: TaskKind,
+    /// App the task operates on
+    pub app_id: String,
+    /// Public key of the deployer that started the task
+    pub deployer: Vec<u8>,
+    /// Failed task this one re-runs (RetryTask)
+    #[serde(default)]
+    pub retried_from: Option<String>,
+    /// ID of the RPC that created the task, for finding its logs
+    #[serde(default)]
+    pub request_id: Option<String>,
     pub status: TaskStatus,
+    /// Last stage reached; kept after the task finishes
+    pub stage: Option<TaskStage>,
+    pub progress_percent: i32,
+    /// Vulnerability scan of the images, from the last deployment attempt
+    #[serde(default)]
+    pub image_scans: Vec<ImageScanResult>,
     pub created_at: i64,
     pub updated_at: i64,
+    /// When the task reached a terminal state (drives retention)
+    /// Not persisted; restored from `updated_at` on load.
+    #[serde(skip)]
+    pub finished_at: Option<Instant>,
 }
 
 impl Task {
-    pub fn new() -> Self {
+    pub fn new(kind: TaskKind, app_id: &str, deployer: &[u8]) -> Self {
         let now = crate::utils::current_timestamp();
         Self {
             id: format!("task-{}", Uuid::new_v4()),
+            kind,
+            app_id: app_id.to_string(),
+            deployer: deployer.to_vec(),
+            retried_from: None,
+            request_id: None,
             status: TaskStatus::Pending,
+            stage: None,
+            progress_percent: 0,
+            image_scans: Vec::new(),
             created_at: now,
             updated_at: now,
+            finished_at: None,
         }
     }
 
+    pub fn is_finished(&self) -> bool {
+        matches!(
+            self.status,
+            TaskStatus::Completed(_) | TaskStatus::Failed(..) | TaskStatus::Cancelled
+        )
+    }
+
+    /// Name of the last stage reached (empty before the worker starts)
+    pub fn stage_name(&self) -> &'static str {
+        self.stage.map(|stage| stage.as_str()).unwrap_or_default()
+    }
+
     pub fn to_proto_status(&self) -> ProtoTaskStatus {
         match &self.status {
             TaskStatus::Pending => ProtoTaskStatus::Pending,
+            TaskStatus::Queued => ProtoTaskStatus::Queued,
             TaskStatus::Running => ProtoTaskStatus::Running,
             TaskStatus::Completed(_) => ProtoTaskStatus::Completed,
-            TaskStatus::Failed(_) => ProtoTaskStatus::Failed,
+            TaskStatus::Failed(..) => ProtoTaskStatus::Failed,
+            TaskStatus::Cancelling => ProtoTaskStatus::Cancelling,
+            TaskStatus::Cancelled => ProtoTaskStatus::Cancelled,
         }
     }
 
@@ -52,46 +237,604 @@ impl Task {
                 app_id: result.app_id.clone(),
                 deployer: result.deployer.clone(),
                 error: String::new(),
+                error_code: ProtoTaskErrorCode::NoError as i32,
             }),
-            TaskStatus::Failed(error) => Some(TaskResult {
+            TaskStatus::Failed(code, error) => Some(TaskResult {
                 app_id: String::new(),
                 deployer: vec![],
                 error: error.clone(),
+                error_code: code.to_proto() as i32,
             }),
             _ => None,
         }
     }
 }
 
+/// Result of looking up a task by ID
+#[derive(Debug, Clone)]
+pub enum TaskLookup {
+    Found(Task),
+    /// The task finished and was purged after the retention period
+    Expired,
+    /// No such task was ever created (or it was purged long ago)
+    Unknown,
+}
+
+/// Tasks persisted as one JSON file per task
+struct TaskStore {
+    dir: PathBuf,
+}
+
+impl TaskStore {
+    fn open(dir: &Path) -> TappResult<Self> {
+        std::fs::create_dir_all(dir)?;
+        Ok(Self {
+            dir: dir.to_path_buf(),
+        })
+    }
+
+    fn path(&self, task_id: &str) -> PathBuf {
+        self.dir.join(format!("{}.json", task_id))
+    }
+
+    /// Write a task, replacing the previous file atomically
+    fn save(&self, task: &Task) -> TappResult<()> {
+        let tmp = self.dir.join(format!(".{}.json.tmp", task.id));
+        std::fs::write(&tmp, serde_json::to_vec(task)?)?;
+        std::fs::rename(&tmp, self.path(&task.id))?;
+        Ok(())
+    }
+
+    fn remove(&self, task_id: &str) -> TappResult<()> {
+        match std::fs::remove_file(self.path(task_id)) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Read every stored task; unreadable files are skipped
+    fn load_all(&self) -> TappResult<Vec<Task>> {
+        let mut tasks = Vec::new();
+        for entry in std::fs::read_dir(&self.dir)? {
+            let path = entry?.path();
+            if path.extension().and_then(|ext| ext.to_str()) != Some("json") {
+                continue;
+            }
+            let task = std::fs::read(&path)
+                .map_err(crate::error::TappError::from)
+                .and_then(|bytes| serde_json::from_slice::<Task>(&bytes).map_err(Into::into));
+            match task {
+                Ok(task) => tasks.push(task),
+                Err(e) => {
+                    warn!(path = %path.display(), error = %e, "Skipping unreadable task file")
+                }
+            }
+        }
+        Ok(tasks)
+    }
+}
+
+/// Tasks and the IDs of recently purged ones
+struct TaskTable {
+    tasks: HashMap<String, Task>,
+    // Cancellation signals of unfinished tasks
+    cancel_tokens: HashMap<String, CancellationToken>,
+    // Original StartApp requests of unfinished and failed tasks, for RetryTask
+    // (kept in memory only)
+    retry_inputs: HashMap<String, StartAppRequest>,
+    tombstones: LruCache<String, ()>,
+    // On-disk copy of `tasks`, if persistence is enabled
+    store: Option<TaskStore>,
+    // IDs of tasks as they change, for WatchTask
+    updates: broadcast::Sender<String>,
+}
+
+impl TaskTable {
+    /// Write a task to the store and tell watchers it changed; store failures
+    /// are logged, the in-memory state stays authoritative
+    fn persist(&self, task_id: &str) {
+        // Sending fails only when nobody watches
+        let _ = self.updates.send(task_id.to_string());
+        let (Some(store), Some(task)) = (&self.store, self.tasks.get(task_id)) else {
+            return;
+        };
+        if let Err(e) = store.save(task) {
+            warn!(task_id = %task_id, error = %e, "Failed to persist task");
+        }
+    }
+
+    /// Drop finished tasks older than `retention`, then the oldest finished
+    /// tasks beyond `max_finished`. Pending and running tasks are never removed.
+    /// Returns how many tasks were removed.
+    fn purge_finished(&mut self, retention: Duration, max_finished: usize, now: Instant) -> usize {
+        let mut finished: Vec<(Instant, String)> = self
+            .tasks
+            .values()
+            .filter_map(|task| task.finished_at.map(|at| (at, task.id.clone())))
+            .collect();
+        finished.sort();
+
+        let over_limit = finished.len().saturating_sub(max_finished);
+        let expired: Vec<String> = finished
+            .into_iter()
+            .enumerate()
+            .filter(|(i, (at, _))| *i < over_limit || now.duration_since(*at) >= retention)
+            .map(|(_, (_, id))| id)
+            .collect();
+
+        for id in &expired {
+            self.tasks.remove(id);
+            self.retry_inputs.remove(id);
+            self.tombstones.put(id.clone(), ());
+            if let Some(store) = &self.store {
+                if let Err(e) = store.remove(id) {
+                    warn!(task_id = %id, error = %e, "Failed to remove persisted task");
+                }
+            }
+        }
+        expired.len()
+    }
+}
+
 pub struct TaskManager {
-    tasks: Arc<RwLock<HashMap<String, Task>>>,
+    table: Arc<RwLock<TaskTable>>,
+    // How long finished tasks are kept
+    retention: Duration,
+    // Maximum number of finished tasks kept
+    max_finished: usize,
 }
 
 impl TaskManager {
+    /// Task manager with the default retention, keeping tasks in memory only
     pub fn new() -> Self {
+        let defaults = BootServiceConfig::default();
+        Self::with_retention(
+            Duration::from_secs(defaults.task_retention_seconds),
+            defaults.max_finished_tasks,
+        )
+    }
+
+    /// Task manager configured by the boot section, persisting tasks to
+    /// `task_state_dir` unless it is empty
+    pub fn from_config(config: &BootServiceConfig) -> TappResult<Self> {
+        let manager = Self::with_retention(
+            Duration::from_secs(config.task_retention_seconds),
+            config.max_finished_tasks,
+        );
+        if config.task_state_dir.is_empty() {
+            return Ok(manager);
+        }
+        manager.with_store(&config.task_state_dir)
+    }
+
+    /// Task manager keeping finished tasks for `retention`, and at most
+    /// `max_finished` of them
+    pub fn with_retention(retention: Duration, max_finished: usize) -> Self {
         Self {
-            tasks: Arc::new(RwLock::new(HashMap::new())),
+            table: Arc::new(RwLock::new(TaskTable {
+                tasks: HashMap::new(),
+                cancel_tokens: HashMap::new(),
+                retry_inputs: HashMap::new(),
+                tombstones: LruCache::new(NonZeroUsize::new(MAX_TOMBSTONES).unwrap()),
+                store: None,
+                updates: broadcast::channel(TASK_UPDATES_CAPACITY).0,
+            })),
+            retention,
+            max_finished,
+        }
+    }
+
+    /// Persist tasks under `dir`, loading the tasks stored there
+    /// Tasks that were unfinished when the service stopped are failed with
+    /// `INTERRUPTED_REASON`, unless they had reached the `done` stage: the
+    /// measurement was already recorded, so they are marked completed.
+    pub fn with_store(mut self, dir: impl AsRef<Path>) -> TappResult<Self> {
+        let store = TaskStore::open(dir.as_ref())?;
+        let loaded = store.load_all()?;
+        let table = Arc::get_mut(&mut self.table)
+            .expect("task table is not shared before the sweeper starts")
+            .get_mut();
+
+        let now = crate::utils::current_timestamp();
+        let mut interrupted = 0;
+        for mut task in loaded {
+            if task.is_finished() {
+                // Age finished tasks by their wall-clock finish time
+                let age = Duration::from_secs(now.saturating_sub(task.updated_at).max(0) as u64);
+                task.finished_at =
+                    Some(Instant::now().checked_sub(age).unwrap_or_else(Instant::now));
+            } else {
+                task.status = if task.stage == Some(TaskStage::Done) {
+                    TaskStatus::Completed(TaskSuccessResult {
+                        app_id: task.app_id.clone(),
+                        deployer: task.deployer.clone(),
+                    })
+                } else {
+                    interrupted += 1;
+                    warn!(
+                        task_id = %task.id,
+                        app_id = %task.app_id,
+                        stage = %task.stage_name(),
+                        event = "TASK_INTERRUPTED",
+                        "Task was unfinished when the service stopped"
+                    );
+                    TaskStatus::Failed(TaskErrorCode::Interrupted, INTERRUPTED_REASON.to_string())
+                };
+                task.updated_at = now;
+                task.finished_at = Some(Instant::now());
+                if let Err(e) = store.save(&task) {
+                    warn!(task_id = %task.id, error = %e, "Failed to persist task");
+                }
+            }
+            table.tasks.insert(task.id.clone(), task);
+        }
+
+        info!(
+            dir = %dir.as_ref().display(),
+            loaded = table.tasks.len(),
+            interrupted = interrupted,
+            "Loaded persisted tasks"
+        );
+        table.store = Some(store);
+        Ok(self)
+    }
+
+    /// Spawn the periodic sweep of finished tasks
+    /// The task holds a weak reference and ends when the manager is dropped.
+    pub fn start_sweeper(&self) -> JoinHandle<()> {
+        let table = Arc::downgrade(&self.table);
+        let retention = self.retention;
+        let max_finished = self.max_finished;
+        tokio::spawn(async move {
+            loop {
+                tokio::time::sleep(TASK_SWEEP_INTERVAL).await;
+                if !Self::sweep(&table, retention, max_finished).await {
+                    return;
+                }
+            }
+        })
+    }
+
+    /// Run one sweep; returns false once the manager is gone
+    async fn sweep(
+        table: &Weak<RwLock<TaskTable>>,
+        retention: Duration,
+        max_finished: usize,
+    ) -> bool {
+        let Some(table) = table.upgrade() else {
+            return false;
+        };
+        let mut table = table.write().await;
+        let removed = table.purge_finished(retention, max_finished, Instant::now());
+
+        if removed > 0 {
+            tracing::debug!(
+                removed = removed,
+                remaining = table.tasks.len(),
+                "Purged finished tasks"
+            );
         }
+        true
     }
 
-    pub async fn create_task(&self) -> Task {
-        let task = Task::new();
-        let mut tasks = self.tasks.write().await;
-        tasks.insert(task.id.clone(), task.clone());
+    pub async fn create_task(
+        &self,
+        kind: TaskKind,
+        app_id: &str,
+        deployer: &[u8],
+        request_id: Option<&str>,
+    ) -> Task {
+        let mut task = Task::new(kind, app_id, deployer);
+        task.request_id = request_id.map(str::to_string);
+        self.insert_task(task).await
+    }
+
+    /// Create a task for `app_id` unless the app already has one in flight
+    /// The check and the insertion happen under one lock, so concurrent
+    /// requests cannot both start a task. Returns the in-flight task
+    /// otherwise.
+    pub async fn create_task_if_idle(
+        &self,
+        kind: TaskKind,
+        app_id: &str,
+        deployer: &[u8],
+        request_id: Option<&str>,
+    ) -> Result<Task, Task> {
+        let mut task = Task::new(kind, app_id, deployer);
+        task.request_id = request_id.map(str::to_string);
+        self.insert_task_if_idle(task).await
+    }
+
+    /// Create a task re-running a failed one for the same app and deployer,
+    /// unless the app has a task in flight (returned instead)
+    pub async fn create_retry_task(
+        &self,
+        original: &Task,
+        request_id: Option<&str>,
+    ) -> Result<Task, Task> {
+        let mut task = Task::new(original.kind, &original.app_id, &original.deployer);
+        task.retried_from = Some(original.id.clone());
+        task.request_id = request_id.map(str::to_string);
+        self.insert_task_if_idle(task).await
+    }
+
+    async fn insert_task(&self, task: Task) -> Task {
+        let mut table = self.table.write().await;
+        Self::insert_into(&mut table, task)
+    }
+
+    async fn insert_task_if_idle(&self, task: Task) -> Result<Task, Task> {
+        let mut table = self.table.write().await;
+        if let Some(existing) = table
+            .tasks
+            .values()
+            .find(|existing| existing.app_id == task.app_id && !existing.is_finished())
+        {
+            return Err(existing.clone());
+        }
+        Ok(Self::insert_into(&mut table, task))
+    }
+
+    fn insert_into(table: &mut TaskTable, task: Task) -> Task {
+        table.tasks.insert(task.id.clone(), task.clone());
+        table
+            .cancel_tokens
+            .insert(task.id.clone(), CancellationToken::new());
+        table.persist(&task.id);
         task
     }
 
+    /// Remove a task that was never started, leaving no trace of it
+    pub async fn discard(&self, task_id: &str) {
+        let mut table = self.table.write().await;
+        table.tasks.remove(task_id);
+        table.cancel_tokens.remove(task_id);
+        table.retry_inputs.remove(task_id);
+        if let Some(store) = &table.store {
+            if let Err(e) = store.remove(task_id) {
+                warn!(task_id = %task_id, error = %e, "Failed to remove persisted task");
+            }
+        }
+    }
+
+    /// Keep the request a task runs, so it can be retried if the task fails
+    pub async fn store_inputs(&self, task_id: &str, request: StartAppRequest) {
+        let mut table = self.table.write().await;
+        if table.tasks.contains_key(task_id) {
+            table.retry_inputs.insert(task_id.to_string(), request);
+        }
+    }
+
+    /// Request a failed task ran, if it is still kept
+    pub async fn retry_inputs(&self, task_id: &str) -> Option<StartAppRequest> {
+        let table = self.table.read().await;
+        table.retry_inputs.get(task_id).cloned()
+    }
+
+    /// Cancellation signal the worker of a task checks between phases
+    /// Finished or unknown tasks get an already-cancelled token.
+    pub async fn cancellation_token(&self, task_id: &str) -> CancellationToken {
+        let table = self.table.read().await;
+        table
+            .cancel_tokens
+            .get(task_id)
+            .cloned()
+            .unwrap_or_else(|| {
+                let token = CancellationToken::new();
+                token.cancel();
+                token
+            })
+    }
+
+    /// Request cancellation of a task
+    /// Pending, queued and running tasks move to CANCELLING and their worker is
+    /// signalled; finished tasks are left as they are. Returns the task's
+    /// state after the request.
+    pub async fn request_cancel(&self, task_id: &str) -> TaskLookup {
+        let mut table = self.table.write().await;
+        let TaskTable {
+            tasks,
+            cancel_tokens,
+            tombstones,
+            ..
+        } = &mut *table;
+
+        let Some(task) = tasks.get_mut(task_id) else {
+            return if tombstones.contains(task_id) {
+                TaskLookup::Expired
+            } else {
+                TaskLookup::Unknown
+            };
+        };
+
+        if !matches!(
+            task.status,
+            TaskStatus::Pending | TaskStatus::Queued | TaskStatus::Running
+        ) {
+            return TaskLookup::Found(task.clone());
+        }
+
+        task.status = TaskStatus::Cancelling;
+        task.updated_at = crate::utils::current_timestamp();
+        if let Some(token) = cancel_tokens.get(task_id) {
+            token.cancel();
+        }
+        let task = task.clone();
+        table.persist(task_id);
+        TaskLookup::Found(task)
+    }
+
     pub async fn get_task(&self, task_id: &str) -> Option<Task> {
-        let tasks = self.tasks.read().await;
-        tasks.get(task_id).cloned()
+        let table = self.table.read().await;
+        table.tasks.get(task_id).cloned()
+    }
+
+    /// Look up a task, telling purged tasks apart from unknown IDs
+    pub async fn lookup_task(&self, task_id: &str) -> TaskLookup {
+        let table = self.table.read().await;
+        match table.tasks.get(task_id) {
+            Some(task) => TaskLookup::Found(task.clone()),
+            None if table.tombstones.contains(task_id) => TaskLookup::Expired,
+            None => TaskLookup::Unknown,
+        }
+    }
+
+    /// Unfinished task operating on an app, if any
+    pub async fn in_flight_task(&self, app_id: &str) -> Option<Task> {
+        let table = self.table.read().await;
+        table
+            .tasks
+            .values()
+            .find(|task| task.app_id == app_id && !task.is_finished())
+            .cloned()
+    }
+
+    /// IDs of the tasks pending, queued, running or cancelling
+    pub async fn unfinished_task_ids(&self) -> Vec<String> {
+        let table = self.table.read().await;
+        table
+            .tasks
+            .values()
+            .filter(|task| !task.is_finished())
+            .map(|task| task.id.clone())
+            .collect()
+    }
+
+    /// Finish every unfinished task for a shutdown, as a restart would: failed
+    /// with `reason`, or completed if it had reached the `done` stage
+    /// Returns how many tasks failed.
+    pub async fn interrupt_unfinished(&self, reason: &str) -> usize {
+        let mut table = self.table.write().await;
+        let unfinished: Vec<String> = table
+            .tasks
+            .values()
+            .filter(|task| !task.is_finished())
+            .map(|task| task.id.clone())
+            .collect();
+
+        let mut interrupted = 0;
+        for task_id in unfinished {
+            let task = table.tasks.get_mut(&task_id).unwrap();
+            task.status = if task.stage == Some(TaskStage::Done) {
+                TaskStatus::Completed(TaskSuccessResult {
+                    app_id: task.app_id.clone(),
+                    deployer: task.deployer.clone(),
+                })
+            } else {
+                interrupted += 1;
+                warn!(
+                    task_id = %task.id,
+                    app_id = %task.app_id,
+                    stage = %task.stage_name(),
+                    event = "TASK_INTERRUPTED",
+                    "Task was unfinished at shutdown"
+                );
+                TaskStatus::Failed(TaskErrorCode::Interrupted, reason.to_string())
+            };
+            task.updated_at = crate::utils::current_timestamp();
+            task.finished_at = Some(Instant::now());
+            if let Some(token) = table.cancel_tokens.remove(&task_id) {
+                token.cancel();
+            }
+            table.persist(&task_id);
+        }
+        interrupted
+    }
+
+    /// IDs of tasks as they are created or change state or progress
+    /// Receivers that fall behind get `Lagged` and should re-read the tasks
+    /// they follow.
+    pub async fn subscribe(&self) -> broadcast::Receiver<String> {
+        self.table.read().await.updates.subscribe()
+    }
+
+    /// How long finished tasks are kept
+    pub fn retention(&self) -> Duration {
+        self.retention
     }
 
     pub async fn update_task_status(&self, task_id: &str, status: TaskStatus) {
-        let mut tasks = self.tasks.write().await;
-        if let Some(task) = tasks.get_mut(task_id) {
+        let mut table = self.table.write().await;
+        let table = &mut *table;
+        if let Some(task) = table.tasks.get_mut(task_id) {
+            // An outcome is final, e.g. a task interrupted at shutdown stays
+            // so when its worker ends after all
+            if task.is_finished() {
+                return;
+            }
+            // A cancellation request is not undone by the worker starting,
+            // and a task only enters the queue before it starts
+            match (&task.status, &status) {
+                (TaskStatus::Cancelling, TaskStatus::Running) => return,
+                (current, TaskStatus::Queued) if !matches!(current, TaskStatus::Pending) => return,
+                _ => {}
+            }
             task.status = status;
             task.updated_at = crate::utils::current_timestamp();
+            if task.is_finished() {
+                task.finished_at.get_or_insert_with(Instant::now);
+                table.cancel_tokens.remove(task_id);
+                if !matches!(task.status, TaskStatus::Failed(..)) {
+                    table.retry_inputs.remove(task_id);
+                }
+            }
+            table.persist(task_id);
+        }
+    }
+
+    /// Record the progress of an unfinished task
+    /// Progress only moves forward: an earlier stage, or a lower percentage
+    /// than already reported, is ignored. Returns whether it was recorded.
+    pub async fn update_progress(&self, task_id: &str, stage: TaskStage, percent: i32) -> bool {
+        let mut table = self.table.write().await;
+        let Some(task) = table.tasks.get_mut(task_id) else {
+            return false;
+        };
+        if task.is_finished() {
+            return false;
+        }
+
+        let percent = percent.clamp(0, 100);
+        let forward = match task.stage {
+            None => true,
+            Some(current) if stage > current => percent >= task.progress_percent,
+            Some(current) => stage == current && percent > task.progress_percent,
+        };
+        if !forward {
+            return false;
+        }
+
+        task.stage = Some(stage);
+        task.progress_percent = percent;
+        task.updated_at = crate::utils::current_timestamp();
+        table.persist(task_id);
+        true
+    }
+
+    /// Record the image scan of a running task, replacing an earlier
+    /// attempt's
+    pub async fn record_image_scans(&self, task_id: &str, results: Vec<ImageScanResult>) {
+        let mut table = self.table.write().await;
+        let Some(task) = table.tasks.get_mut(task_id) else {
+            return;
+        };
+        if task.is_finished() {
+            return;
         }
+        task.image_scans = results;
+        task.updated_at = crate::utils::current_timestamp();
+        table.persist(task_id);
+    }
+
+    /// Record that a task entered a stage, at the stage's nominal progress
+    pub async fn enter_stage(&self, task_id: &str, stage: TaskStage) -> bool {
+        self.update_progress(task_id, stage, stage.percent()).await
+    }
+
+    /// Mark a pending task as waiting for a deployment slot
+    /// No-op if its worker already started.
+    pub async fn mark_queued(&self, task_id: &str) {
+        self.update_task_status(task_id, TaskStatus::Queued).await;
     }
 
     pub async fn mark_running(&self, task_id: &str) {
@@ -103,8 +846,464 @@ impl TaskManager {
             .await;
     }
 
-    pub async fn mark_failed(&self, task_id: &str, error: String) {
-        self.update_task_status(task_id, TaskStatus::Failed(error))
+    pub async fn mark_failed(&self, task_id: &str, code: TaskErrorCode, error: String) {
+        self.update_task_status(task_id, TaskStatus::Failed(code, error))
             .await;
     }
+
+    pub async fn mark_cancelled(&self, task_id: &str) {
+        self.update_task_status(task_id, TaskStatus::Cancelled)
+            .await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn result(app_id: &str) -> TaskSuccessResult {
+        TaskSuccessResult {
+            app_id: app_id.to_string(),
+            deployer: vec![],
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_sweeper_purges_finished_tasks_after_retention() {
+        let manager = TaskManager::with_retention(Duration::from_secs(90), 100);
+        let sweeper = manager.start_sweeper();
+
+        let completed = manager
+            .create_task(TaskKind::StartApp, "app", &[], None)
+            .await;
+        let failed = manager
+            .create_task(TaskKind::StartApp, "app", &[], None)
+            .await;
+        let running = manager
+            .create_task(TaskKind::StartApp, "app", &[], None)
+            .await;
+        manager.mark_completed(&completed.id, result("app")).await;
+        manager
+            .mark_failed(&failed.id, TaskErrorCode::Internal, "boom".to_string())
+            .await;
+        manager.mark_running(&running.id).await;
+
+        // First sweep (60s): still within the retention
+        tokio::time::sleep(Duration::from_secs(61)).await;
+        assert!(manager.get_task(&completed.id).await.is_some());
+
+        // Second sweep (120s): finished tasks are gone, running ones stay
+        tokio::time::sleep(Duration::from_secs(60)).await;
+        assert!(matches!(
+            manager.lookup_task(&completed.id).await,
+            TaskLookup::Expired
+        ));
+        assert!(matches!(
+            manager.lookup_task(&failed.id).await,
+            TaskLookup::Expired
+        ));
+        assert!(matches!(
+            manager.lookup_task(&running.id).await,
+            TaskLookup::Found(_)
+        ));
+        assert!(matches!(
+            manager.lookup_task("task-never-created").await,
+            TaskLookup::Unknown
+        ));
+
+        // The sweeper ends with the manager
+        drop(manager);
+        tokio::time::sleep(Duration::from_secs(61)).await;
+        assert!(sweeper.is_finished());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_max_finished_tasks() {
+        let manager = TaskManager::with_retention(Duration::from_secs(3600), 2);
+        let mut ids = Vec::new();
+        for i in 0..4 {
+            let task = manager
+                .create_task(TaskKind::StartApp, "app", &[], None)
+                .await;
+            manager
+                .mark_completed(&task.id, result(&format!("app-{}", i)))
+                .await;
+            ids.push(task.id);
+            tokio::time::sleep(Duration::from_secs(1)).await;
+        }
+        let pending = manager
+            .create_task(TaskKind::StartApp, "app", &[], None)
+            .await;
+
+        let mut table = manager.table.write().await;
+        let removed = table.purge_finished(manager.retention(), 2, Instant::now());
+        drop(table);
+        assert_eq!(removed, 2);
+
+        // The oldest finished tasks go first
+        assert!(manager.get_task(&ids[0]).await.is_none());
+        assert!(manager.get_task(&ids[1]).await.is_none());
+        assert!(manager.get_task(&ids[3]).await.is_some());
+        assert!(manager.get_task(&pending.id).await.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_progress_is_forward_only() {
+        let manager = TaskManager::new();
+        let task = manager
+            .create_task(TaskKind::StartApp, "app", &[], None)
+            .await;
+        assert_eq!(task.stage_name(), "");
+
+        assert!(
+            manager
+                .update_progress(&task.id, TaskStage::Pulling, 20)
+                .await
+        );
+        assert!(
+            manager
+                .update_progress(&task.id, TaskStage::Pulling, 40)
+                .await
+        );
+        // Going back a stage or a percentage is ignored
+        assert!(
+            !manager
+                .update_progress(&task.id, TaskStage::WritingFiles, 50)
+                .await
+        );
+        assert!(
+            !manager
+                .update_progress(&task.id, TaskStage::Pulling, 30)
+                .await
+        );
+        assert!(
+            !manager
+                .update_progress(&task.id, TaskStage::Starting, 35)
+                .await
+        );
+        let current = manager.get_task(&task.id).await.unwrap();
+        assert_eq!(current.stage, Some(TaskStage::Pulling));
+        assert_eq!(current.progress_percent, 40);
+
+        // The last stage stays visible after the task fails
+        assert!(
+            manager
+                .update_progress(&task.id, TaskStage::Starting, 60)
+                .await
+        );
+        manager
+            .mark_failed(&task.id, TaskErrorCode::Internal, "boom".to_string())
+            .await;
+        assert!(
+            !manager
+                .update_progress(&task.id, TaskStage::Done, 100)
+                .await
+        );
+        let failed = manager.get_task(&task.id).await.unwrap();
+        assert_eq!(failed.stage_name(), "starting");
+        assert_eq!(failed.progress_percent, 60);
+    }
+
+    #[tokio::test]
+    async fn test_tasks_survive_restart() {
+        let dir = tempfile::tempdir().unwrap();
+        let retention = Duration::from_secs(3600);
+
+        let manager = TaskManager::with_retention(retention, 100)
+            .with_store(dir.path())
+            .unwrap();
+        let completed = manager
+            .create_task(TaskKind::StartApp, "done-app", &[1; 64], Some("req-1"))
+            .await;
+        manager.mark_running(&completed.id).await;
+        manager
+            .mark_completed(&completed.id, result("done-app"))
+            .await;
+
+        let running = manager
+            .create_task(TaskKind::StartApp, "slow-app", &[2; 64], None)
+            .await;
+        manager.mark_running(&running.id).await;
+        manager.enter_stage(&running.id, TaskStage::Pulling).await;
+
+        // Crashed after the measurement was recorded but before completion
+        let measured = manager
+            .create_task(TaskKind::StartApp, "measured-app", &[3; 64], None)
+            .await;
+        manager.mark_running(&measured.id).await;
+        manager.enter_stage(&measured.id, TaskStage::Done).await;
+        drop(manager);
+
+        // A second manager over the same directory sees truthful states
+        let restarted = TaskManager::with_retention(retention, 100)
+            .with_store(dir.path())
+            .unwrap();
+        let task = restarted.get_task(&completed.id).await.unwrap();
+        assert!(matches!(task.status, TaskStatus::Completed(_)));
+        assert_eq!(task.deployer, vec![1; 64]);
+        assert_eq!(task.request_id.as_deref(), Some("req-1"));
+
+        let task = restarted.get_task(&running.id).await.unwrap();
+        assert_eq!(task.to_proto_status(), ProtoTaskStatus::Failed);
+        let failure = task.to_proto_result().unwrap();
+        assert_eq!(failure.error, INTERRUPTED_REASON);
+        assert_eq!(failure.error_code(), ProtoTaskErrorCode::Interrupted);
+        assert_eq!(task.stage_name(), "pulling");
+
+        let task = restarted.get_task(&measured.id).await.unwrap();
+        assert_eq!(task.to_proto_status(), ProtoTaskStatus::Completed);
+        assert_eq!(task.to_proto_result().unwrap().app_id, "measured-app");
+
+        // Recovered tasks are finished and age out like any other
+        let mut table = restarted.table.write().await;
+        let removed = table.purge_finished(retention, 100, Instant::now() + retention);
+        drop(table);
+        assert_eq!(removed, 3);
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_interrupt_unfinished() {
+        let manager = TaskManager::new();
+        let running = manager
+            .create_task(TaskKind::StartApp, "slow-app", &[], None)
+            .await;
+        manager.mark_running(&running.id).await;
+        let token = manager.cancellation_token(&running.id).await;
+        let measured = manager
+            .create_task(TaskKind::StartApp, "measured-app", &[], None)
+            .await;
+        manager.mark_running(&measured.id).await;
+        manager.enter_stage(&measured.id, TaskStage::Done).await;
+        let completed = manager
+            .create_task(TaskKind::StartApp, "done-app", &[], None)
+            .await;
+        manager
+            .mark_completed(&completed.id, result("done-app"))
+            .await;
+        assert_eq!(manager.unfinished_task_ids().await.len(), 2);
+
+        assert_eq!(manager.interrupt_unfinished(SHUTDOWN_REASON).await, 1);
+        assert!(manager.unfinished_task_ids().await.is_empty());
+        assert!(token.is_cancelled());
+
+        let task = manager.get_task(&running.id).await.unwrap();
+        let failure = task.to_proto_result().unwrap();
+        assert_eq!(failure.error, SHUTDOWN_REASON);
+        assert_eq!(failure.error_code(), ProtoTaskErrorCode::Interrupted);
+        let task = manager.get_task(&measured.id).await.unwrap();
+        assert_eq!(task.to_proto_status(), ProtoTaskStatus::Completed);
+
+        // The worker ending later does not change the outcome
+        manager.mark_cancelled(&running.id).await;
+        let task = manager.get_task(&running.id).await.unwrap();
+        assert_eq!(task.to_proto_status(), ProtoTaskStatus::Failed);
+    }
+
+    #[test]
+    fn test_unreadable_task_files_are_skipped() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("task-broken.json"), b"{not json").unwrap();
+        std::fs::write(dir.path().join("notes.txt"), b"ignored").unwrap();
+
+        let manager = TaskManager::new().with_store(dir.path()).unwrap();
+        assert!(manager.table.try_read().unwrap().tasks.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_in_flight_task() {
+        let manager = TaskManager::new();
+        let task = manager
+            .create_task(TaskKind::StartApp, "my-app", &[], None)
+            .await;
+        assert_eq!(task.kind.to_proto(), ProtoTaskKind::StartApp);
+
+        let in_flight = manager.in_flight_task("my-app").await.unwrap();
+        assert_eq!(in_flight.id, task.id);
+        assert!(manager.in_flight_task("other-app").await.is_none());
+
+        manager
+            .mark_failed(&task.id, TaskErrorCode::Internal, "boom".to_string())
+            .await;
+        assert!(manager.in_flight_task("my-app").await.is_none());
+
+        // Tasks persisted before the kind was recorded load as StartApp
+        let mut json = serde_json::to_value(&task).unwrap();
+        json.as_object_mut().unwrap().remove("kind");
+        let old: Task = serde_json::from_value(json).unwrap();
+        assert_eq!(old.kind, TaskKind::StartApp);
+    }
+
+    #[tokio::test]
+    async fn test_queued_tasks() {
+        let manager = TaskManager::new();
+        let queued = manager
+            .create_task(TaskKind::StartApp, "queued", &[], None)
+            .await;
+        let started = manager
+            .create_task(TaskKind::StartApp, "started", &[], None)
+            .await;
+
+        manager.mark_queued(&queued.id).await;
+        let task = manager.get_task(&queued.id).await.unwrap();
+        assert_eq!(task.to_proto_status(), ProtoTaskStatus::Queued);
+        assert!(manager.in_flight_task("queued").await.is_some());
+
+        // A worker that already picked the task up is not sent back to the queue
+        manager.mark_running(&started.id).await;
+        manager.mark_queued(&started.id).await;
+        let task = manager.get_task(&started.id).await.unwrap();
+        assert!(matches!(task.status, TaskStatus::Running));
+
+        // Queued tasks can be cancelled
+        let TaskLookup::Found(task) = manager.request_cancel(&queued.id).await else {
+            panic!("queued task not found");
+        };
+        assert!(matches!(task.status, TaskStatus::Cancelling));
+
+        // Tasks rejected by a full queue leave nothing behind
+        manager.discard(&started.id).await;
+        assert!(matches!(
+            manager.lookup_task(&started.id).await,
+            TaskLookup::Unknown
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_retry_inputs_and_link() {
+        let manager = TaskManager::new();
+        let request = StartAppRequest {
+            app_id: "my-app".to_string(),
+            compose_content: "services: {}".to_string(),
+            ..Default::default()
+        };
+
+        let failed = manager
+            .create_task(TaskKind::StartApp, "my-app", &[7; 64], None)
+            .await;
+        manager.store_inputs(&failed.id, request.clone()).await;
+        manager
+            .mark_failed(
+                &failed.id,
+                TaskErrorCode::PullFailed,
+                "pull failed".to_string(),
+            )
+            .await;
+        let inputs = manager.retry_inputs(&failed.id).await.unwrap();
+        assert_eq!(inputs.compose_content, request.compose_content);
+
+        let retry = manager.create_retry_task(&failed, None).await.unwrap();
+        assert_eq!(retry.retried_from.as_deref(), Some(failed.id.as_str()));
+        assert_eq!(retry.app_id, "my-app");
+        assert_eq!(retry.deployer, vec![7; 64]);
+
+        // Inputs of tasks that did not fail are released
+        manager.store_inputs(&retry.id, request).await;
+        manager.mark_completed(&retry.id, result("my-app")).await;
+        assert!(manager.retry_inputs(&retry.id).await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_create_task_if_idle() {
+        let manager = Arc::new(TaskManager::new());
+        let attempts: Vec<_> = (0..8)
+            .map(|_| {
+                let manager = manager.clone();
+                tokio::spawn(async move {
+                    manager
+                        .create_task_if_idle(TaskKind::StartApp, "my-app", &[], None)
+                        .await
+                })
+            })
+            .collect();
+        let mut created = Vec::new();
+        for attempt in attempts {
+            if let Ok(task) = attempt.await.unwrap() {
+                created.push(task);
+            }
+        }
+        // Exactly one concurrent request wins; the others see its task
+        assert_eq!(created.len(), 1);
+        let existing = manager
+            .create_task_if_idle(TaskKind::StartApp, "my-app", &[], None)
+            .await
+            .unwrap_err();
+        assert_eq!(existing.id, created[0].id);
+        assert!(manager
+            .create_task_if_idle(TaskKind::StartApp, "other-app", &[], None)
+            .await
+            .is_ok());
+
+        manager
+            .mark_completed(&created[0].id, result("my-app"))
+            .await;
+        assert!(manager
+            .create_task_if_idle(TaskKind::StartApp, "my-app", &[], None)
+            .await
+            .is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_request_cancel() {
+        let manager = TaskManager::new();
+        let task = manager
+            .create_task(TaskKind::StartApp, "my-app", &[7; 64], None)
+            .await;
+        manager.mark_running(&task.id).await;
+        let cancel = manager.cancellation_token(&task.id).await;
+        assert!(!cancel.is_cancelled());
+
+        let TaskLookup::Found(cancelling) = manager.request_cancel(&task.id).await else {
+            panic!("task not found");
+        };
+        assert!(matches!(cancelling.status, TaskStatus::Cancelling));
+        assert_eq!(cancelling.to_proto_status(), ProtoTaskStatus::Cancelling);
+        assert!(cancel.is_cancelled());
+
+        // The worker starting late does not undo the request
+        manager.mark_running(&task.id).await;
+        assert!(matches!(
+            manager.get_task(&task.id).await.unwrap().status,
+            TaskStatus::Cancelling
+        ));
+
+        manager.mark_cancelled(&task.id).await;
+        let cancelled = manager.get_task(&task.id).await.unwrap();
+        assert!(cancelled.is_finished());
+        assert_eq!(cancelled.app_id, "my-app");
+
+        // Cancelling a finished task is a no-op
+        let done = manager
+            .create_task(TaskKind::StartApp, "other-app", &[], None)
+            .await;
+        manager.mark_completed(&done.id, result("other-app")).await;
+        let TaskLookup::Found(done) = manager.request_cancel(&done.id).await else {
+            panic!("task not found");
+        };
+        assert!(matches!(done.status, TaskStatus::Completed(_)));
+        assert!(matches!(
+            manager.request_cancel("task-never-created").await,
+            TaskLookup::Unknown
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_subscribe_reports_changes() {
+        let manager = TaskManager::new();
+        let mut updates = manager.subscribe().await;
+
+        let task = manager
+            .create_task(TaskKind::StartApp, "app", &[], None)
+            .await;
+        manager.mark_running(&task.id).await;
+        manager.enter_stage(&task.id, TaskStage::Pulling).await;
+        // Ignored progress is not reported
+        manager.enter_stage(&task.id, TaskStage::Validating).await;
+        manager.mark_completed(&task.id, result("app")).await;
+
+        for _ in 0..4 {
+            assert_eq!(updates.try_recv().unwrap(), task.id);
+        }
+        assert!(updates.try_recv().is_err());
+    }
 }
@@ -1,23 +1,415 @@
-use clap::{Parser, Subcommand};
-use std::path::PathBuf;
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use hyper_util::rt::TokioIo;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256, Sha384};
+use std::collections::BTreeMap;
+use std::path::{Component, Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+use std::task::{Context, Poll};
+use tapp_service::app_key::deployer::{self, DeployerIdentity};
+use tapp_service::app_key::{
+    attested_key_report_data, checksum_address, envelope, eth_address, public_key_from_private,
+    MAX_ATTESTED_KEY_NONCE_LEN,
+};
+use tapp_service::auth_layer::API_KEY_HEADER;
+use tapp_service::boot::measurement::split_mount_files;
+use tapp_service::boot::quote::{parse_quote, quote_from_evidence, Quote};
 use tapp_service::proto::{
-    tapp_service_client::TappServiceClient, GetAppKeyRequest, GetAppSecretKeyRequest,
-    GetEvidenceRequest, MountFile, StartAppRequest,
+    tapp_service_client::TappServiceClient, CancelTaskRequest, GetAppInfoRequest,
+    GetAppInfoResponse, GetAppKeyRequest, GetAppKeyResponse, GetAppLogsRequest,
+    GetAppSecretKeyRequest, GetAppSecretKeyResponse, GetAttestedAppKeyRequest,
+    GetAttestedAppKeyResponse, GetEvidenceRequest, GetNonceRequest, GetTaskStatusRequest,
+    GetTaskStatusResponse, ImageScanResult, MountFile, RetryTaskRequest, StartAppRequest,
+    StopAppRequest, TaskErrorCode, TaskKind, TaskStatus, TransferAppOwnershipRequest,
+    WatchTaskRequest,
 };
+use tapp_service::request_id_layer::REQUEST_ID_HEADER;
+use tokio::net::UnixStream;
+use tonic::body::BoxBody;
+use tonic::codec::CompressionEncoding;
+use tonic::transport::{Certificate, Channel, ClientTlsConfig, Endpoint, Identity};
 use tonic::Request;
+use tonic_types::StatusExt;
+use tower::Service;
 
 #[derive(Parser)]
 #[command(name = "tapp-cli")]
 #[command(about = "TAPP Service CLI - Interact with TAPP gRPC server", long_about = None)]
 struct Cli {
-    /// gRPC server address
-    #[arg(short, long, default_value = "http://127.0.0.1:50051")]
-    server: String,
+    /// gRPC server address: http://HOST:PORT, https://HOST:PORT, or
+    /// unix:///PATH for the server's local unix socket (e.g.
+    /// unix:///run/tapp/tapp.sock) [default: http://127.0.0.1:50051]
+    #[arg(short, long)]
+    server: Option<String>,
+
+    /// Take the server, TLS, API key and deployer key settings not given as
+    /// options from this profile of ~/.config/tapp/cli.toml (default: the
+    /// TAPP_PROFILE environment variable)
+    #[arg(long, global = true)]
+    profile: Option<String>,
+
+    /// API key sent with every request (default: the TAPP_API_KEY
+    /// environment variable)
+    #[arg(long)]
+    api_key: Option<String>,
+
+    /// Sign every request with this HMAC key ID
+    /// The shared secret is read from the TAPP_HMAC_SECRET environment variable
+    #[arg(long)]
+    hmac_key_id: Option<String>,
+
+    /// Compress requests and ask the server to compress responses, for slow
+    /// or metered links
+    #[arg(long, value_enum, value_name = "CODEC")]
+    compress: Option<Compression>,
+
+    #[command(flatten)]
+    keepalive: KeepaliveArgs,
+
+    #[command(flatten)]
+    tls: TlsArgs,
+
+    /// Output format; with json every command prints one JSON object on
+    /// stdout, errors included
+    #[arg(short, long, global = true, value_enum, default_value_t = OutputFormat::Text)]
+    output: OutputFormat,
 
     #[command(subcommand)]
     command: Commands,
 }
 
+/// HTTP/2 keepalive of the connection, for long streams such as watch-task
+/// behind NATs and load balancers
+#[derive(Args, Clone, Copy)]
+struct KeepaliveArgs {
+    /// Send a PING every SECS seconds
+    #[arg(long, value_name = "SECS")]
+    keepalive_interval: Option<u64>,
+
+    /// Close the connection when a PING is not acknowledged within SECS
+    /// seconds [default: 20]
+    #[arg(long, value_name = "SECS", requires = "keepalive_interval")]
+    keepalive_timeout: Option<u64>,
+
+    /// Also ping while no call is in progress
+    #[arg(long, requires = "keepalive_interval")]
+    keepalive_while_idle: bool,
+}
+
+impl KeepaliveArgs {
+    fn apply(&self, endpoint: Endpoint) -> Endpoint {
+        let Some(interval) = self.keepalive_interval else {
+            return endpoint;
+        };
+        let mut endpoint = endpoint
+            .http2_keep_alive_interval(std::time::Duration::from_secs(interval))
+            .keep_alive_while_idle(self.keepalive_while_idle);
+        if let Some(timeout) = self.keepalive_timeout {
+            endpoint = endpoint.keep_alive_timeout(std::time::Duration::from_secs(timeout));
+        }
+        endpoint
+    }
+}
+
+/// TLS settings of the connection to the server
+#[derive(Args)]
+struct TlsArgs {
+    /// Connect over TLS; implied by an https:// server address and by the
+    /// other TLS options
+    #[arg(long)]
+    tls: bool,
+
+    /// CA certificate (PEM) to verify the server with instead of the system
+    /// roots
+    #[arg(long, value_name = "PEM")]
+    ca_cert: Option<PathBuf>,
+
+    /// Name to verify the server certificate against instead of the host in
+    /// --server
+    #[arg(long)]
+    domain: Option<String>,
+
+    /// Client certificate (PEM) for servers that require one
+    #[arg(long, value_name = "PEM", requires = "client_key")]
+    client_cert: Option<PathBuf>,
+
+    /// Private key (PEM) of --client-cert
+    #[arg(long, value_name = "PEM", requires = "client_cert")]
+    client_key: Option<PathBuf>,
+}
+
+impl TlsArgs {
+    fn enabled(&self, server: &str) -> bool {
+        self.tls
+            || server.starts_with("https://")
+            || self.ca_cert.is_some()
+            || self.domain.is_some()
+            || self.client_cert.is_some()
+    }
+
+    fn client_config(&self) -> Result<ClientTlsConfig, CliError> {
+        let mut config = ClientTlsConfig::new();
+        config = match &self.ca_cert {
+            Some(path) => config.ca_certificate(Certificate::from_pem(read_pem(
+                "--ca-cert",
+                path,
+                "CERTIFICATE",
+            )?)),
+            None => config.with_native_roots(),
+        };
+        if let Some(domain) = &self.domain {
+            config = config.domain_name(domain);
+        }
+        if let (Some(cert), Some(key)) = (&self.client_cert, &self.client_key) {
+            config = config.identity(Identity::from_pem(
+                read_pem("--client-cert", cert, "CERTIFICATE")?,
+                read_pem("--client-key", key, "PRIVATE KEY")?,
+            ));
+        }
+        Ok(config)
+    }
+}
+
+/// Read a PEM file given with `flag`, which must hold a `label` block
+/// tonic only parses PEM files on the first connection, where a bad one
+/// fails as an opaque transport error
+fn read_pem(flag: &str, path: &Path, label: &str) -> Result<Vec<u8>, CliError> {
+    let pem = std::fs::read(path).map_err(|e| {
+        CliError::new(format!(
+            "{} {} could not be read: {}",
+            flag,
+            path.display(),
+            e
+        ))
+    })?;
+    let text = String::from_utf8_lossy(&pem);
+    let has_block = text.lines().any(|line| {
+        line.strip_prefix("-----BEGIN ")
+            .and_then(|rest| rest.strip_suffix("-----"))
+            .is_some_and(|found| found.ends_with(label))
+    });
+    if !has_block {
+        return Err(CliError::new(format!(
+            "{} {} is not a PEM file with a {} block",
+            flag,
+            path.display(),
+            label
+        )));
+    }
+    Ok(pem)
+}
+
+/// Profiles file, `~/.config/tapp/cli.toml`:
+///
+/// ```toml
+/// [profiles.prod]
+/// server = "https://tapp.example.com:50051"
+/// ca_cert = "prod-ca.pem"
+/// api_key_env = "TAPP_PROD_API_KEY"
+/// deployer_key_file = "~/.tapp/prod-deployer.key"
+/// ```
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ProfilesFile {
+    #[serde(default)]
+    profiles: BTreeMap<String, Profile>,
+}
+
+/// Settings of one node, selected with --profile
+/// Relative paths are relative to the profiles file. Secrets are only
+/// referenced: an inline API key or deployer key is rejected.
+#[derive(Debug, Default, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Profile {
+    server: Option<String>,
+    #[serde(default)]
+    tls: bool,
+    ca_cert: Option<PathBuf>,
+    domain: Option<String>,
+    client_cert: Option<PathBuf>,
+    client_key: Option<PathBuf>,
+    /// Environment variable holding the API key
+    api_key_env: Option<String>,
+    /// File holding the API key, trailing whitespace trimmed
+    api_key_file: Option<PathBuf>,
+    /// File holding the default deployer private key (hex)
+    deployer_key_file: Option<PathBuf>,
+}
+
+impl ProfilesFile {
+    /// `~/.config/tapp/cli.toml`
+    fn default_path() -> Result<PathBuf, CliError> {
+        dirs::home_dir()
+            .map(|home| home.join(".config/tapp/cli.toml"))
+            .ok_or_else(|| CliError::new("No home directory to find the profiles file in"))
+    }
+
+    /// The default file and its profiles; none if it does not exist
+    fn load_default() -> Result<(PathBuf, Self), CliError> {
+        let path = Self::default_path()?;
+        let profiles = Self::load(&path)?;
+        Ok((path, profiles))
+    }
+
+    fn load(path: &Path) -> Result<Self, CliError> {
+        let content = match std::fs::read_to_string(path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(e) => {
+                return Err(CliError::new(format!(
+                    "{} could not be read: {}",
+                    path.display(),
+                    e
+                )))
+            }
+        };
+        Self::parse(&content, path.parent().unwrap_or(Path::new(".")))
+            .map_err(|e| CliError::new(format!("{}: {}", path.display(), e.message)))
+    }
+
+    /// Parse a profiles file in `dir`, resolving the profiles' paths
+    fn parse(content: &str, dir: &Path) -> Result<Self, CliError> {
+        let mut file: Self =
+            toml::from_str(content).map_err(|e| CliError::new(e.to_string().trim_end()))?;
+        for profile in file.profiles.values_mut() {
+            for path in [
+                &mut profile.ca_cert,
+                &mut profile.client_cert,
+                &mut profile.client_key,
+                &mut profile.api_key_file,
+                &mut profile.deployer_key_file,
+            ]
+            .into_iter()
+            .flatten()
+            {
+                *path = resolve_profile_path(path, dir);
+            }
+        }
+        Ok(file)
+    }
+
+    fn get(&self, name: &str, path: &Path) -> Result<&Profile, CliError> {
+        self.profiles.get(name).ok_or_else(|| {
+            let known: Vec<&str> = self.profiles.keys().map(String::as_str).collect();
+            CliError::new(format!(
+                "Profile {} not found in {} (profiles: {})",
+                name,
+                path.display(),
+                if known.is_empty() {
+                    "none".to_string()
+                } else {
+                    known.join(", ")
+                }
+            ))
+        })
+    }
+}
+
+/// `~/` expanded, relative paths taken from `dir`
+fn resolve_profile_path(path: &Path, dir: &Path) -> PathBuf {
+    match (path.strip_prefix("~"), dirs::home_dir()) {
+        (Ok(rest), Some(home)) => home.join(rest),
+        _ => dir.join(path),
+    }
+}
+
+impl Profile {
+    /// Fill the settings not given on the command line from profile `name`
+    /// Secrets are only read when the command needs them.
+    fn apply(&self, name: &str, cli: &mut Cli) -> Result<(), CliError> {
+        if cli.server.is_none() {
+            cli.server = self.server.clone();
+        }
+
+        let tls = &mut cli.tls;
+        tls.tls |= self.tls;
+        if tls.ca_cert.is_none() {
+            tls.ca_cert = self.ca_cert.clone();
+        }
+        if tls.domain.is_none() {
+            tls.domain = self.domain.clone();
+        }
+        if tls.client_cert.is_none() && tls.client_key.is_none() {
+            tls.client_cert = self.client_cert.clone();
+            tls.client_key = self.client_key.clone();
+        }
+
+        if cli.api_key.is_none() && cli.command.connects() {
+            cli.api_key = self.api_key(name)?;
+        }
+
+        let deployer_key = match &mut cli.command {
+            Commands::StartApp {
+                deployer_key,
+                deployer_pubkey: None,
+                ..
+            }
+            | Commands::StopApp { deployer_key, .. }
+            | Commands::CancelTask { deployer_key, .. }
+            | Commands::RetryTask { deployer_key, .. }
+            | Commands::TransferOwnership { deployer_key, .. }
+            | Commands::GetAppSecretKey {
+                deployer_private_key: deployer_key,
+                ..
+            } => deployer_key,
+            _ => return Ok(()),
+        };
+        if deployer_key.is_none() {
+            *deployer_key = self.deployer_key(name)?;
+        }
+        Ok(())
+    }
+
+    fn api_key(&self, name: &str) -> Result<Option<String>, CliError> {
+        if let Some(var) = &self.api_key_env {
+            return match std::env::var(var) {
+                Ok(key) => Ok(Some(key.trim_end().to_string())),
+                Err(_) => Err(CliError::new(format!(
+                    "Profile {}: API key variable {} is not set",
+                    name, var
+                ))),
+            };
+        }
+        self.api_key_file
+            .as_deref()
+            .map(|path| read_secret_file(name, "API key", path))
+            .transpose()
+    }
+
+    fn deployer_key(&self, name: &str) -> Result<Option<String>, CliError> {
+        self.deployer_key_file
+            .as_deref()
+            .map(|path| read_secret_file(name, "deployer key", path))
+            .transpose()
+    }
+}
+
+/// Secret a profile refers to, trailing whitespace trimmed
+fn read_secret_file(profile: &str, what: &str, path: &Path) -> Result<String, CliError> {
+    std::fs::read_to_string(path)
+        .map(|secret| secret.trim_end().to_string())
+        .map_err(|e| {
+            CliError::new(format!(
+                "Profile {}: {} file {} could not be read: {}",
+                profile,
+                what,
+                path.display(),
+                e
+            ))
+        })
+}
+
+/// Deployer key of commands that cannot run without one, given with `flag`
+fn required_deployer_key(flag: &str, key: Option<String>) -> Result<String, CliError> {
+    key.ok_or_else(|| {
+        CliError::new(format!(
+            "{} is required unless the profile has a deployer_key_file",
+            flag
+        ))
+    })
+}
+
 #[derive(Subcommand)]
 enum Commands {
     /// Start an application with Docker Compose
@@ -34,6 +426,142 @@ enum Commands {
         /// Example: ./nginx.conf:/path/to/nginx.conf:0644
         #[arg(short, long)]
         mount: Vec<String>,
+
+        /// Mount files encrypted with encrypt-mount, in the --mount format
+        /// (./secret.env:secret.env.enc:0600); the server decrypts them with
+        /// the app key, but measures them as sent
+        #[arg(long, value_name = "SPEC")]
+        mount_encrypted: Vec<String>,
+
+        /// Upload the files bound from relative paths (./nginx.conf,
+        /// ./config/) in the compose file's volumes, read from DIR (default:
+        /// the compose file's directory); --mount entries override them
+        #[arg(long, value_name = "DIR", num_args = 0..=1)]
+        mounts_from_compose: Option<Option<PathBuf>>,
+
+        /// Deployer's private key (32 bytes hex); its public key becomes the
+        /// app's deployer, who signs stop-app and get-app-secret-key
+        /// (default: the profile's deployer key)
+        #[arg(short = 'd', long)]
+        deployer_key: Option<String>,
+
+        /// Deployer's public key (33, 64 or 65 bytes hex) or Ethereum address
+        /// (20 bytes hex), when the private key is not on this machine. An
+        /// address-only deployer must sign with recoverable signatures
+        #[arg(long, conflicts_with = "deployer_key")]
+        deployer_pubkey: Option<String>,
+
+        /// Don't register the app on the tapp contract, when the server has
+        /// a [contract] section
+        #[arg(long)]
+        no_register_on_chain: bool,
+
+        /// Image platform of every service, os/arch[/variant] (e.g.
+        /// linux/arm64); the server rejects one its host cannot run natively
+        /// unless boot.allow_emulation is set
+        #[arg(long)]
+        platform: Option<String>,
+
+        /// Platform of one service, over --platform: SERVICE=PLATFORM
+        #[arg(long, value_name = "SERVICE=PLATFORM")]
+        service_platform: Vec<String>,
+    },
+
+    /// Stop an application (requires the deployer's signature)
+    /// Exits with code 3 if the application does not exist
+    StopApp {
+        /// Application ID
+        #[arg(short, long)]
+        app_id: String,
+
+        /// Deployer's private key (32 bytes hex) for signing the request.
+        /// Omit only when using an admin API key to force-stop; defaults to
+        /// the profile's deployer key
+        #[arg(short = 'd', long)]
+        deployer_key: Option<String>,
+    },
+
+    /// Show the status of a task
+    TaskStatus {
+        /// Task ID returned by start-app
+        #[arg(short, long)]
+        task_id: String,
+
+        /// Poll until the task completes, fails or is cancelled; exits
+        /// non-zero unless it completed
+        #[arg(short, long)]
+        wait: bool,
+
+        /// Seconds between polls with --wait
+        #[arg(long, default_value_t = 2, value_parser = clap::value_parser!(u64).range(1..))]
+        interval: u64,
+
+        /// Give up waiting after this many seconds
+        #[arg(long)]
+        timeout: Option<u64>,
+    },
+
+    /// Follow a task as it runs, printing each change of status or stage;
+    /// exits non-zero unless it completed
+    WatchTask {
+        /// Task ID returned by start-app
+        #[arg(short, long)]
+        task_id: String,
+
+        /// Seconds between polls against servers without WatchTask
+        #[arg(long, default_value_t = 2, value_parser = clap::value_parser!(u64).range(1..))]
+        interval: u64,
+    },
+
+    /// Cancel an in-flight StartApp task (requires the deployer's signature)
+    CancelTask {
+        /// Task ID returned by start-app
+        #[arg(short, long)]
+        task_id: String,
+
+        /// Application ID the task deploys
+        #[arg(short, long)]
+        app_id: String,
+
+        /// Deployer's private key (32 bytes hex) for signing the request.
+        /// Omit only when using an admin API key; defaults to the profile's
+        /// deployer key
+        #[arg(short = 'd', long)]
+        deployer_key: Option<String>,
+    },
+
+    /// Re-run a failed StartApp task with its original request
+    RetryTask {
+        /// ID of the failed task
+        #[arg(short, long)]
+        task_id: String,
+
+        /// Application ID the task deploys
+        #[arg(short, long)]
+        app_id: String,
+
+        /// Deployer's private key (32 bytes hex) for signing the request.
+        /// Omit only when using an admin API key; defaults to the profile's
+        /// deployer key
+        #[arg(short = 'd', long)]
+        deployer_key: Option<String>,
+    },
+
+    /// Transfer control of an application to a new deployer key
+    TransferOwnership {
+        /// Application ID
+        #[arg(short, long)]
+        app_id: String,
+
+        /// New deployer's public key (33, 64 or 65 bytes hex) or Ethereum
+        /// address (20 bytes hex)
+        #[arg(short, long)]
+        new_deployer: String,
+
+        /// Current deployer's private key (32 bytes hex) for signing the
+        /// request (default: the profile's deployer key)
+        #[arg(short = 'd', long)]
+        deployer_key: Option<String>,
     },
 
     /// Get attestation evidence with custom report data
@@ -42,6 +570,37 @@ enum Commands {
         /// If not provided, will use zero-filled 64 bytes
         #[arg(short, long, default_value = "")]
         report_data: String,
+
+        /// Write the evidence bytes, as returned by the attester, to this file
+        #[arg(long, value_name = "PATH")]
+        out: Option<PathBuf>,
+
+        /// Also show the TD report of the quote (MRTD, RTMRs)
+        #[arg(long)]
+        summary: bool,
+
+        /// Also print the whole evidence as hex and base64
+        #[arg(long)]
+        raw: bool,
+    },
+
+    /// Print the TD report of a TDX quote saved from get-evidence and check
+    /// it against expected values; exits 1 on a mismatch
+    /// The quote's signature is not verified.
+    VerifyEvidence {
+        /// Evidence as returned by get-evidence: the attester's JSON, the
+        /// raw quote, or either as hex
+        #[arg(short, long, value_name = "PATH")]
+        evidence_file: PathBuf,
+
+        /// Report data requested from get-evidence (hex, up to 64 bytes,
+        /// zero-padded like the server does)
+        #[arg(long, value_name = "HEX")]
+        expect_report_data: Option<String>,
+
+        /// Expected RTMR2 (48 bytes hex)
+        #[arg(long, value_name = "HEX")]
+        expect_rtmr2: Option<String>,
     },
 
     /// Get application public key (public interface)
@@ -55,6 +614,44 @@ enum Commands {
         key_type: String,
     },
 
+    /// Get an app's public key with evidence binding it to the app's compose
+    /// hash and a nonce, and check the binding; exits 1 if it does not hold
+    /// The quote's signature is not verified.
+    GetAttestedKey {
+        /// Application ID
+        #[arg(short, long)]
+        app_id: String,
+
+        /// Fresh nonce (hex, 1 to 64 bytes)
+        #[arg(short, long, value_name = "HEX")]
+        nonce: String,
+
+        /// Write the evidence bytes to this file, for an external verifier
+        #[arg(long, value_name = "PATH")]
+        save_evidence: Option<PathBuf>,
+    },
+
+    /// Encrypt a mount file to an application's key for start-app
+    /// --mount-encrypted; writes FILE.enc
+    EncryptMount {
+        /// Application ID
+        #[arg(short, long)]
+        app_id: String,
+
+        /// File to encrypt, or with --verify the encrypted file
+        #[arg(short, long)]
+        file: PathBuf,
+
+        /// Decrypt FILE locally with --private-key instead, to test it
+        #[arg(long, requires = "private_key")]
+        verify: bool,
+
+        /// Application private key (32 bytes hex, from get-app-secret-key)
+        /// for --verify
+        #[arg(long, requires = "verify")]
+        private_key: Option<String>,
+    },
+
     /// Get application secret key (private key - local access only)
     GetAppSecretKey {
         /// Application ID
@@ -62,15 +659,57 @@ enum Commands {
         app_id: String,
 
         /// Deployer's private key (32 bytes hex) for signing the request
+        /// (default: the profile's deployer key)
         #[arg(short = 'd', long)]
-        deployer_private_key: String,
+        deployer_private_key: Option<String>,
 
-        /// Output in JSON format (for programmatic use)
+        /// Same as --output json
         #[arg(long)]
         json: bool,
     },
 
+    /// Show what is recorded for an application: compose file, mount files,
+    /// event history and runtime status
+    AppInfo {
+        /// Application ID
+        #[arg(short, long)]
+        app_id: String,
+
+        /// Write the compose content to this file instead of printing it
+        #[arg(long, value_name = "PATH")]
+        compose_out: Option<PathBuf>,
+
+        /// Write the mount files under this directory, at their source paths
+        #[arg(long, value_name = "DIR")]
+        volumes_out: Option<PathBuf>,
+    },
+
+    /// Print an application's container logs, or its deployment output
+    AppLogs {
+        /// Application ID
+        #[arg(short, long)]
+        app_id: String,
+
+        /// Only this compose service (e.g. "web")
+        #[arg(long)]
+        service: Option<String>,
+
+        /// Number of lines, counted from the end
+        #[arg(short = 'n', long, default_value_t = 100)]
+        lines: i32,
+
+        /// Print the captured docker compose pull/up output of a deployment
+        /// instead of the container logs
+        #[arg(long, conflicts_with = "service")]
+        deploy: bool,
+
+        /// With --deploy: the deployment task (default: the latest)
+        #[arg(long, requires = "deploy")]
+        task_id: Option<String>,
+    },
+
     /// Sign a message using a private key
+    /// For signatures Ethereum tools accept, use --scheme eip191.
     SignMessage {
         /// Private key (32 bytes hex)
         #[arg(short, long)]
@@ -79,13 +718,19 @@ enum Commands {
         /// Message to sign (will be treated as UTF-8 string)
         #[arg(short, long)]
         message: String,
+
+        /// Signature scheme; eip191 matches personal_sign and ethers'
+        /// signMessage, the default raw is not accepted by Ethereum tools
+        #[arg(long, value_enum, default_value_t = SignatureScheme::Raw)]
+        scheme: SignatureScheme,
     },
 
     /// Verify a signature using a public key
+    /// keccak and eip191 signatures print the signer's address they recover.
     VerifySignature {
-        /// Public key (64 bytes hex)
+        /// Public key (64 bytes hex); optional with keccak and eip191
         #[arg(short, long)]
-        public_key: String,
+        public_key: Option<String>,
 
         /// Message that was signed
         #[arg(short, long)]
@@ -94,417 +739,3840 @@ enum Commands {
         /// Signature (hex)
         #[arg(short, long)]
         signature: String,
+
+        /// Signature scheme the message was signed with
+        #[arg(long, value_enum, default_value_t = SignatureScheme::Raw)]
+        scheme: SignatureScheme,
+    },
+
+    /// Show the profiles of ~/.config/tapp/cli.toml
+    Profile {
+        #[command(subcommand)]
+        command: ProfileCommand,
+    },
+}
+
+impl Commands {
+    /// Whether the command talks to the server
+    fn connects(&self) -> bool {
+        !matches!(
+            self,
+            Commands::VerifyEvidence { .. }
+                | Commands::EncryptMount { verify: true, .. }
+                | Commands::SignMessage { .. }
+                | Commands::VerifySignature { .. }
+                | Commands::Profile { .. }
+        )
+    }
+}
+
+#[derive(Subcommand)]
+enum ProfileCommand {
+    /// List the profiles
+    List,
+
+    /// Show a profile's settings, with secrets masked
+    Show {
+        /// Profile name (default: the selected profile)
+        name: Option<String>,
     },
 }
 
+/// How sign-message and verify-signature hash and encode signatures
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum, Serialize)]
+#[serde(rename_all = "lowercase")]
+enum SignatureScheme {
+    /// ECDSA over SHA-256(message), 64-byte r || s
+    Raw,
+    /// ECDSA over Keccak-256(message), 65-byte r || s || v
+    Keccak,
+    /// keccak over the personal_sign prefixed message (EIP-191)
+    Eip191,
+}
+
+impl SignatureScheme {
+    /// Bytes that are hashed and signed for `message`
+    fn signed_message(self, message: &str) -> Vec<u8> {
+        match self {
+            Self::Raw | Self::Keccak => message.as_bytes().to_vec(),
+            Self::Eip191 => tapp_service::app_key::eip191_message(message.as_bytes()),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum OutputFormat {
+    Text,
+    /// One JSON object per command, for scripts
+    Json,
+}
+
+/// Message compression codec of `--compress`
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Compression {
+    Gzip,
+    Zstd,
+}
+
+impl Compression {
+    fn encoding(self) -> CompressionEncoding {
+        match self {
+            Compression::Gzip => CompressionEncoding::Gzip,
+            Compression::Zstd => CompressionEncoding::Zstd,
+        }
+    }
+}
+
 #[tokio::main]
-async fn main() -> Result<(), Box<dyn std::error::Error>> {
+async fn main() {
     let cli = Cli::parse();
+    let output = match cli.command {
+        Commands::GetAppSecretKey { json: true, .. } => OutputFormat::Json,
+        _ => cli.output,
+    };
+
+    let exit_code = match run(cli, output).await {
+        Ok(exit_code) => exit_code,
+        Err(e) => {
+            e.print(output);
+            e.exit_code
+        }
+    };
+    std::process::exit(exit_code);
+}
+
+async fn run(mut cli: Cli, output: OutputFormat) -> Result<i32, CliError> {
+    let profile_name = cli
+        .profile
+        .clone()
+        .or_else(|| std::env::var("TAPP_PROFILE").ok())
+        .filter(|name| !name.is_empty());
+    match &profile_name {
+        Some(name) if !matches!(cli.command, Commands::Profile { .. }) => {
+            let (path, profiles) = ProfilesFile::load_default()?;
+            profiles.get(name, &path)?.apply(name, &mut cli)?;
+        }
+        _ => {}
+    }
+
+    let mut server = cli
+        .server
+        .clone()
+        .unwrap_or_else(|| DEFAULT_SERVER.to_string());
+    if cli.tls.enabled(&server) {
+        let _ = TLS_CONFIG.set(cli.tls.client_config()?);
+        // tonic only uses TLS for https:// addresses
+        if let Some(address) = server.strip_prefix("http://") {
+            server = format!("https://{}", address);
+        }
+    }
+
+    if let Some(key_id) = cli.hmac_key_id.clone() {
+        let Ok(secret) = std::env::var("TAPP_HMAC_SECRET") else {
+            return Err(CliError::new(
+                "--hmac-key-id requires the TAPP_HMAC_SECRET environment variable",
+            ));
+        };
+        let _ = HMAC_AUTH.set((key_id, secret));
+    }
+
+    if let Some(api_key) = cli
+        .api_key
+        .clone()
+        .or_else(|| std::env::var("TAPP_API_KEY").ok())
+        .filter(|key| !key.is_empty())
+    {
+        let _ = API_KEY.set(api_key);
+    }
+    if let Some(compress) = cli.compress {
+        let _ = COMPRESSION.set(compress.encoding());
+    }
+    let _ = KEEPALIVE.set(cli.keepalive);
 
     match cli.command {
         Commands::StartApp {
             compose_file,
             app_id,
             mount,
+            mount_encrypted,
+            mounts_from_compose,
+            deployer_key,
+            deployer_pubkey,
+            no_register_on_chain,
+            platform,
+            service_platform,
         } => {
-            start_app(&cli.server, compose_file, app_id, mount).await?;
+            let deployer = deployer_public_key(deployer_key, deployer_pubkey)?;
+            report(
+                output,
+                &start_app(
+                    &server,
+                    compose_file,
+                    app_id,
+                    mount,
+                    mount_encrypted,
+                    mounts_from_compose,
+                    deployer,
+                    !no_register_on_chain,
+                    platform.unwrap_or_default(),
+                    service_platform,
+                )
+                .await?,
+            )
         }
-        Commands::GetEvidence { report_data } => {
-            get_evidence(&cli.server, report_data).await?;
+        Commands::StopApp {
+            app_id,
+            deployer_key,
+        } => report(output, &stop_app(&server, app_id, deployer_key).await?),
+        Commands::TaskStatus {
+            task_id,
+            wait,
+            interval,
+            timeout,
+        } => {
+            let wait = wait.then_some(Wait { interval, timeout });
+            report(output, &task_status(&server, task_id, wait, output).await?)
         }
+        Commands::WatchTask { task_id, interval } => report(
+            output,
+            &watch_task(&server, task_id, interval, output).await?,
+        ),
+        Commands::CancelTask {
+            task_id,
+            app_id,
+            deployer_key,
+        } => report(
+            output,
+            &cancel_task(&server, task_id, app_id, deployer_key).await?,
+        ),
+        Commands::RetryTask {
+            task_id,
+            app_id,
+            deployer_key,
+        } => report(
+            output,
+            &retry_task(&server, task_id, app_id, deployer_key).await?,
+        ),
+        Commands::TransferOwnership {
+            app_id,
+            new_deployer,
+            deployer_key,
+        } => report(
+            output,
+            &transfer_ownership(
+                &server,
+                app_id,
+                new_deployer,
+                required_deployer_key("--deployer-key", deployer_key)?,
+            )
+            .await?,
+        ),
+        Commands::GetEvidence {
+            report_data,
+            out,
+            summary,
+            raw,
+        } => report(
+            output,
+            &get_evidence(&server, report_data, out, summary, raw).await?,
+        ),
+        Commands::VerifyEvidence {
+            evidence_file,
+            expect_report_data,
+            expect_rtmr2,
+        } => report(
+            output,
+            &verify_evidence(evidence_file, expect_report_data, expect_rtmr2)?,
+        ),
         Commands::GetAppKey { app_id, key_type } => {
-            get_app_key(&cli.server, app_id, key_type).await?;
+            report(output, &get_app_key(&server, app_id, key_type).await?)
         }
+        Commands::EncryptMount {
+            app_id,
+            file,
+            private_key,
+            ..
+        } => match private_key {
+            Some(private_key) => report(output, &decrypt_mount(app_id, file, private_key)?),
+            None => report(output, &encrypt_mount(&server, app_id, file).await?),
+        },
+        Commands::GetAttestedKey {
+            app_id,
+            nonce,
+            save_evidence,
+        } => report(
+            output,
+            &get_attested_key(&server, app_id, nonce, save_evidence).await?,
+        ),
         Commands::GetAppSecretKey {
             app_id,
             deployer_private_key,
-            json,
-        } => {
-            get_app_secret_key(&cli.server, app_id, deployer_private_key, json).await?;
-        }
+            ..
+        } => report(
+            output,
+            &get_app_secret_key(
+                &server,
+                app_id,
+                required_deployer_key("--deployer-private-key", deployer_private_key)?,
+            )
+            .await?,
+        ),
+        Commands::AppInfo {
+            app_id,
+            compose_out,
+            volumes_out,
+        } => report(
+            output,
+            &app_info(&server, app_id, compose_out, volumes_out).await?,
+        ),
+        Commands::AppLogs {
+            app_id,
+            service,
+            lines,
+            deploy,
+            task_id,
+        } => report(
+            output,
+            &app_logs(&server, app_id, service, lines, deploy, task_id).await?,
+        ),
         Commands::SignMessage {
             private_key,
             message,
-        } => {
-            sign_message(private_key, message)?;
-        }
+            scheme,
+        } => report(output, &sign_message(private_key, message, scheme)?),
         Commands::VerifySignature {
             public_key,
             message,
             signature,
-        } => {
-            verify_signature(public_key, message, signature)?;
+            scheme,
+        } => report(
+            output,
+            &verify_signature(public_key, message, signature, scheme)?,
+        ),
+        Commands::Profile { command } => {
+            let (path, profiles) = ProfilesFile::load_default()?;
+            match command {
+                ProfileCommand::List => report(
+                    output,
+                    &list_profiles(path, &profiles, profile_name.as_deref()),
+                ),
+                ProfileCommand::Show { name } => report(
+                    output,
+                    &show_profile(&path, &profiles, name.or(profile_name))?,
+                ),
+            }
         }
     }
+}
 
-    Ok(())
+/// Result of a command
+/// `--output json` prints it serialized as one JSON object; otherwise
+/// `print_text` prints it for people. Diagnostics go to stderr either way.
+trait Report: Serialize {
+    fn print_text(&self);
+
+    /// Exit code after printing, for results that count as a failure
+    fn exit_code(&self) -> i32 {
+        0
+    }
 }
 
-async fn start_app(
-    server: &str,
-    compose_file: PathBuf,
-    app_id: String,
-    mounts: Vec<String>,
-) -> Result<(), Box<dyn std::error::Error>> {
-    let mut client = TappServiceClient::connect(server.to_string()).await?;
+/// Print `result` in the chosen format and return the exit code
+fn report(output: OutputFormat, result: &impl Report) -> Result<i32, CliError> {
+    match output {
+        OutputFormat::Text => result.print_text(),
+        OutputFormat::Json => println!("{}", serde_json::to_string_pretty(result)?),
+    }
+    Ok(result.exit_code())
+}
 
-    // Read compose file
-    let compose_content = std::fs::read_to_string(&compose_file)?;
+/// Why a command failed
+/// `main` prints it to stderr, or as a JSON object on stdout with
+/// `--output json`, and exits with `exit_code`.
+#[derive(Debug)]
+struct CliError {
+    message: String,
+    /// gRPC status code, when the server rejected the call
+    code: Option<tonic::Code>,
+    /// Further lines shown below the message in text mode
+    details: Vec<String>,
+    /// Structured details the server attached to the status
+    status_details: Option<StatusDetails>,
+    exit_code: i32,
+}
 
-    // Parse mount files
-    let mut mount_files = Vec::new();
-    for mount_spec in mounts {
-        let parts: Vec<&str> = mount_spec.split(':').collect();
-        if parts.len() != 3 {
-            eprintln!(
-                "Invalid mount format: {}. Expected: source_path:file_path:mode",
-                mount_spec
-            );
-            std::process::exit(1);
+impl CliError {
+    fn new(message: impl Into<String>) -> Self {
+        Self {
+            message: message.into(),
+            code: None,
+            details: Vec::new(),
+            status_details: None,
+            exit_code: 1,
         }
+    }
 
-        let source_path = parts[0].to_string();
-        let file_path = parts[1];
-        let mode = parts[2].to_string();
-
-        // Read file content
-        let content = std::fs::read(file_path)?;
+    fn with_detail(mut self, line: impl Into<String>) -> Self {
+        self.details.push(line.into());
+        self
+    }
 
-        mount_files.push(MountFile {
-            source_path,
-            content,
-            mode,
-        });
+    fn with_exit_code(mut self, exit_code: i32) -> Self {
+        self.exit_code = exit_code;
+        self
     }
 
-    let request = Request::new(StartAppRequest {
-        compose_content,
-        app_id: app_id.clone(),
-        mount_files,
-        deployer: vec![0; 32],
-    });
+    fn print(&self, output: OutputFormat) {
+        // The server's ID of the last request, to quote when reporting a failure
+        let request_id = LAST_REQUEST_ID.lock().unwrap().clone();
+        match output {
+            OutputFormat::Text => {
+                match self.code {
+                    Some(code) => eprintln!("ERROR: {} ({:?})", self.message, code),
+                    None => eprintln!("ERROR: {}", self.message),
+                }
+                for line in &self.details {
+                    eprintln!("{}", line);
+                }
+                if let Some(request_id) = request_id {
+                    eprintln!("  Request ID: {}", request_id);
+                }
+            }
+            OutputFormat::Json => println!("{:#}", self.to_json(request_id.as_deref())),
+        }
+    }
 
-    let response = client.start_app(request).await?;
-    let result = response.into_inner();
+    /// The error object printed with `--output json`
+    fn to_json(&self, request_id: Option<&str>) -> serde_json::Value {
+        serde_json::json!({
+            "error": {
+                "message": self.message,
+                "code": self.code.map(|code| format!("{:?}", code)),
+                "details": self.status_details,
+                "request_id": request_id,
+            }
+        })
+    }
+}
 
-    println!("✓ Application started successfully");
-    println!("  Task ID: {}", result.task_id);
-    println!("  Message: {}", result.message);
-    println!("  Timestamp: {}", result.timestamp);
+impl From<tonic::Status> for CliError {
+    fn from(status: tonic::Status) -> Self {
+        let mut error = Self::new(status.message());
+        error.code = Some(status.code());
+        if let Some(details) = StatusDetails::decode(&status) {
+            error.details.extend(details.lines());
+            error.status_details = Some(details);
+        }
+        error.details.extend(auth_hint(&status).map(str::to_string));
+        error
+    }
+}
 
-    Ok(())
+/// The `google.rpc.Status` details of a failed call that the CLI understands
+#[derive(Debug, Serialize)]
+struct StatusDetails {
+    /// Stable reason code from `ErrorInfo`
+    reason: Option<String>,
+    metadata: BTreeMap<String, String>,
+    /// (field, description) pairs from `BadRequest`
+    field_violations: Vec<FieldViolation>,
+    /// From `RetryInfo`
+    retry_after_seconds: Option<u64>,
 }
 
-async fn get_evidence(
-    server: &str,
-    report_data_hex: String,
-) -> Result<(), Box<dyn std::error::Error>> {
-    let mut client = TappServiceClient::connect(server.to_string()).await?;
+#[derive(Debug, Serialize)]
+struct FieldViolation {
+    field: String,
+    description: String,
+}
 
-    // Decode report data if provided
-    let report_data_bytes = if report_data_hex.is_empty() {
-        vec![]
-    } else {
-        // Remove 0x prefix if present
-        let hex_str = report_data_hex
-            .trim_start_matches("0x")
-            .trim_start_matches("0X");
+impl StatusDetails {
+    /// None when the status carries no details, or none the CLI understands
+    fn decode(status: &tonic::Status) -> Option<Self> {
+        let details = status.check_error_details().ok()?;
+        let error_info = details.error_info();
+        let decoded = Self {
+            reason: error_info.map(|info| info.reason.clone()),
+            metadata: error_info
+                .map(|info| info.metadata.clone().into_iter().collect())
+                .unwrap_or_default(),
+            field_violations: details
+                .bad_request()
+                .map(|bad_request| {
+                    bad_request
+                        .field_violations
+                        .iter()
+                        .map(|v| FieldViolation {
+                            field: v.field.clone(),
+                            description: v.description.clone(),
+                        })
+                        .collect()
+                })
+                .unwrap_or_default(),
+            retry_after_seconds: details
+                .retry_info()
+                .and_then(|info| info.retry_delay)
+                .map(|delay| delay.as_secs()),
+        };
+        let empty = decoded.reason.is_none()
+            && decoded.field_violations.is_empty()
+            && decoded.retry_after_seconds.is_none();
+        (!empty).then_some(decoded)
+    }
 
-        // Validate and decode
-        if hex_str.len() > 128 {
-            eprintln!(
-                "ERROR: Report data must be at most 64 bytes (128 hex characters), got {}",
-                hex_str.len()
-            );
-            std::process::exit(1);
+    /// Lines shown below the message in text mode
+    fn lines(&self) -> Vec<String> {
+        let mut lines = Vec::new();
+        if let Some(reason) = &self.reason {
+            let metadata: Vec<String> = self
+                .metadata
+                .iter()
+                .map(|(key, value)| format!("{}={}", key, value))
+                .collect();
+            if metadata.is_empty() {
+                lines.push(format!("  Reason: {}", reason));
+            } else {
+                lines.push(format!("  Reason: {} ({})", reason, metadata.join(", ")));
+            }
+        }
+        for violation in &self.field_violations {
+            lines.push(format!(
+                "  Invalid field {}: {}",
+                violation.field, violation.description
+            ));
         }
+        if let Some(seconds) = self.retry_after_seconds {
+            lines.push(format!("  Retry after: {}s", seconds));
+        }
+        lines
+    }
+}
 
-        hex::decode(hex_str)?
+/// Other errors are reported with their causes
+macro_rules! cli_error_from {
+    ($($error:ty),* $(,)?) => {
+        $(
+            impl From<$error> for CliError {
+                fn from(e: $error) -> Self {
+                    let mut message = e.to_string();
+                    let mut source = std::error::Error::source(&e);
+                    while let Some(cause) = source {
+                        // Some errors already include their cause
+                        let cause_message = cause.to_string();
+                        if !message.contains(&cause_message) {
+                            message = format!("{}: {}", message, cause_message);
+                        }
+                        source = cause.source();
+                    }
+                    Self::new(message)
+                }
+            }
+        )*
     };
+}
 
-    let request = Request::new(GetEvidenceRequest {
-        report_data: report_data_bytes.clone(),
-    });
+cli_error_from!(
+    std::io::Error,
+    tonic::transport::Error,
+    http::uri::InvalidUri,
+    hex::FromHexError,
+    serde_json::Error,
+    serde_yaml::Error,
+    tapp_service::TappError,
+);
 
-    let response = client.get_evidence(request).await?;
-    let result = response.into_inner();
+/// Server used when neither --server nor the profile gives one
+const DEFAULT_SERVER: &str = "http://127.0.0.1:50051";
 
-    println!("✓ Evidence generated successfully");
-    println!("  TEE Type: {}", result.tee_type);
-    println!("  Timestamp: {}", result.timestamp);
-    println!("  Evidence (hex): {}", hex::encode(&result.evidence));
-    println!("  Evidence (base64): {}", base64::encode(&result.evidence));
+/// Exit code of commands whose application does not exist
+const EXIT_NOT_FOUND: i32 = 3;
 
-    if !report_data_bytes.is_empty() {
-        println!("\nReport data used: 0x{}", hex::encode(&report_data_bytes));
-    } else {
-        println!("\nReport data: (empty, will use zero-filled 64 bytes)");
+/// API key sent in every request, if configured
+static API_KEY: OnceLock<String> = OnceLock::new();
+
+/// TLS settings of the connection, if enabled
+static TLS_CONFIG: OnceLock<ClientTlsConfig> = OnceLock::new();
+
+/// HMAC key ID and secret used to sign requests, if configured
+static HMAC_AUTH: OnceLock<(String, String)> = OnceLock::new();
+
+/// Codec compressing requests and responses, if requested
+static COMPRESSION: OnceLock<CompressionEncoding> = OnceLock::new();
+
+/// HTTP/2 keepalive of every connection
+static KEEPALIVE: OnceLock<KeepaliveArgs> = OnceLock::new();
+
+/// Request ID the server returned for the last call
+static LAST_REQUEST_ID: Mutex<Option<String>> = Mutex::new(None);
+
+/// Hint pointing at --api-key when the server rejected the caller's credentials
+fn auth_hint(status: &tonic::Status) -> Option<&'static str> {
+    match (status.code(), API_KEY.get()) {
+        (tonic::Code::Unauthenticated | tonic::Code::PermissionDenied, None) => {
+            Some("  Hint: the server may require an API key; pass --api-key or set TAPP_API_KEY")
+        }
+        (tonic::Code::PermissionDenied, Some(_)) if status.message().contains("API key") => {
+            Some("  Hint: check the API key given with --api-key or TAPP_API_KEY")
+        }
+        _ => None,
     }
+}
 
-    Ok(())
+/// Channel that adds the API key or signed-request headers to every call and
+/// keeps the request ID of every response
+#[derive(Clone)]
+struct SignedChannel {
+    inner: Channel,
+    api_key: Option<http::HeaderValue>,
 }
 
-async fn get_app_key(
-    server: &str,
-    app_id: String,
-    key_type: String,
-) -> Result<(), Box<dyn std::error::Error>> {
-    let mut client = TappServiceClient::connect(server.to_string()).await?;
+impl Service<http::Request<BoxBody>> for SignedChannel {
+    type Response = <Channel as Service<http::Request<BoxBody>>>::Response;
+    type Error = <Channel as Service<http::Request<BoxBody>>>::Error;
+    type Future = futures_util::future::BoxFuture<'static, Result<Self::Response, Self::Error>>;
 
-    let request = Request::new(GetAppKeyRequest {
-        app_id: app_id.clone(),
-        key_type: key_type.clone(),
-        additional_data: vec![],
-        kbs_resource_uri: String::new(),
-    });
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
 
-    let response = client.get_app_key(request).await?;
-    let result = response.into_inner();
+    fn call(&mut self, mut req: http::Request<BoxBody>) -> Self::Future {
+        if let Some(api_key) = &self.api_key {
+            req.headers_mut().insert(API_KEY_HEADER, api_key.clone());
+        }
+        if let Some((key_id, secret)) = HMAC_AUTH.get() {
+            // The signature covers the method path, so sign per call
+            let headers =
+                tapp_service::auth_layer::hmac_auth_headers(key_id, secret, req.uri().path());
+            for (name, value) in headers {
+                if let Ok(value) = value.parse() {
+                    req.headers_mut().insert(name, value);
+                }
+            }
+        }
 
-    if !result.success {
-        eprintln!("ERROR: {}", result.message);
-        std::process::exit(1);
+        let call = self.inner.call(req);
+        Box::pin(async move {
+            let response = call.await?;
+            *LAST_REQUEST_ID.lock().unwrap() = response
+                .headers()
+                .get(REQUEST_ID_HEADER)
+                .and_then(|value| value.to_str().ok())
+                .map(str::to_string);
+            Ok(response)
+        })
     }
+}
 
-    println!("✓ Application key retrieved successfully");
-    println!("  App ID: {}", app_id);
-    println!("  Key Type: {}", key_type);
-    println!("  Key Source: {}", result.key_source);
-    println!("  Public Key (hex): 0x{}", hex::encode(&result.public_key));
+/// Connect to the server, authenticating when an API key or HMAC key is
+/// configured
+async fn connect(server: &str) -> Result<TappServiceClient<SignedChannel>, CliError> {
+    let inner = channel(server, TLS_CONFIG.get()).await?;
+    let client = client(inner, API_KEY.get().map(String::as_str))?;
+    Ok(match COMPRESSION.get() {
+        Some(&encoding) => compressed(client, encoding),
+        None => client,
+    })
+}
+
+/// `client` sending compressed requests and accepting compressed responses
+/// A server without the codec rejects the requests with UNIMPLEMENTED.
+fn compressed(
+    client: TappServiceClient<SignedChannel>,
+    encoding: CompressionEncoding,
+) -> TappServiceClient<SignedChannel> {
+    client.send_compressed(encoding).accept_compressed(encoding)
+}
 
-    if key_type == "ethereum" && !result.eth_address.is_empty() {
-        println!("  Ethereum Address: 0x{}", hex::encode(&result.eth_address));
+/// Open a channel to `server`, over TLS if `tls` is given
+/// `unix:///path` addresses connect to the server's unix socket listener.
+async fn channel(server: &str, tls: Option<&ClientTlsConfig>) -> Result<Channel, CliError> {
+    if let Some(path) = server.strip_prefix("unix://") {
+        if tls.is_some() {
+            return Err(CliError::new(
+                "TLS options cannot be used with a unix:// server address",
+            ));
+        }
+        let path = PathBuf::from(path);
+        let connector = tower::service_fn(move |_: http::Uri| {
+            let path = path.clone();
+            async move { Ok::<_, std::io::Error>(TokioIo::new(UnixStream::connect(path).await?)) }
+        });
+        // The URI only fills in the requests' authority; the connector ignores it
+        let endpoint = keepalive(Endpoint::from_static("http://localhost"));
+        return Ok(endpoint.connect_with_connector(connector).await?);
     }
 
-    Ok(())
+    let mut endpoint = keepalive(Channel::from_shared(server.to_string())?);
+    if let Some(tls) = tls {
+        endpoint = endpoint.tls_config(tls.clone())?;
+    }
+    Ok(endpoint.connect().await?)
 }
 
-async fn get_app_secret_key(
-    server: &str,
-    app_id: String,
-    deployer_private_key_hex: String,
-    json_output: bool,
-) -> Result<(), Box<dyn std::error::Error>> {
-    let mut client = TappServiceClient::connect(server.to_string()).await?;
+/// `endpoint` with the keepalive options, if any
+fn keepalive(endpoint: Endpoint) -> Endpoint {
+    match KEEPALIVE.get() {
+        Some(keepalive) => keepalive.apply(endpoint),
+        None => endpoint,
+    }
+}
 
-    // Remove 0x prefix if present
-    let deployer_private_key_hex = deployer_private_key_hex
-        .trim_start_matches("0x")
-        .trim_start_matches("0X");
+/// Client over `inner` sending `api_key` with every request
+fn client(
+    inner: Channel,
+    api_key: Option<&str>,
+) -> Result<TappServiceClient<SignedChannel>, CliError> {
+    let api_key = api_key
+        .map(|key| {
+            let mut value = http::HeaderValue::from_str(key).map_err(|_| {
+                CliError::new("API key contains characters not allowed in a header")
+            })?;
+            // Keeps the key out of debug output
+            value.set_sensitive(true);
+            Ok::<_, CliError>(value)
+        })
+        .transpose()?;
+    Ok(TappServiceClient::new(SignedChannel { inner, api_key }))
+}
 
-    if deployer_private_key_hex.len() != 64 {
-        eprintln!(
-            "ERROR: Deployer private key must be 32 bytes (64 hex characters), got {}",
-            deployer_private_key_hex.len()
-        );
-        std::process::exit(1);
+/// Remove a 0x prefix, if present
+fn strip_hex_prefix(hex: &str) -> &str {
+    hex.trim_start_matches("0x").trim_start_matches("0X")
+}
+
+/// Decode `hex` (with or without 0x) that must be exactly `len` bytes
+fn decode_hex_key(what: &str, hex: &str, len: usize) -> Result<Vec<u8>, CliError> {
+    let hex = strip_hex_prefix(hex);
+    if hex.len() != len * 2 {
+        return Err(CliError::new(format!(
+            "{} must be {} bytes ({} hex characters), got {}",
+            what,
+            len,
+            len * 2,
+            hex.len()
+        )));
     }
+    Ok(hex::decode(hex)?)
+}
 
-    let deployer_private_key = hex::decode(deployer_private_key_hex)?;
+#[derive(Serialize)]
+struct StartAppOutput {
+    app_id: String,
+    task_id: String,
+    message: String,
+    timestamp: i64,
+    deployer: String,
+    deployer_address: String,
+}
 
-    // Generate random nonce (16 bytes hex = 32 characters)
-    use rand::Rng;
-    let nonce: String = rand::thread_rng()
-        .sample_iter(&rand::distributions::Alphanumeric)
-        .take(32)
-        .map(char::from)
-        .collect();
+impl Report for StartAppOutput {
+    fn print_text(&self) {
+        println!("✓ Application started successfully");
+        println!("  Task ID: {}", self.task_id);
+        println!("  Message: {}", self.message);
+        println!("  Timestamp: {}", self.timestamp);
+        println!("  Deployer: {}", self.deployer_address);
+    }
+}
 
-    // Get current timestamp
-    let timestamp = chrono::Utc::now().timestamp();
+/// Hex-encoded deployer in any form the service accepts, as raw bytes
+fn decode_deployer(what: &str, hex: &str) -> Result<(Vec<u8>, DeployerIdentity), CliError> {
+    let bytes = hex::decode(strip_hex_prefix(hex))?;
+    let identity = DeployerIdentity::from_bytes(&bytes)
+        .map_err(|e| CliError::new(format!("{}: {}", what, e)))?;
+    Ok((bytes, identity))
+}
 
-    // Construct message: app_id || nonce || timestamp (as bytes)
-    let mut message = Vec::new();
-    message.extend_from_slice(app_id.as_bytes());
-    message.extend_from_slice(nonce.as_bytes());
-    message.extend_from_slice(&timestamp.to_le_bytes());
+/// The deployer of start-app: the 64-byte public key of the private key if
+/// given, or the public key or address as passed
+fn deployer_public_key(
+    deployer_key: Option<String>,
+    deployer_pubkey: Option<String>,
+) -> Result<Vec<u8>, CliError> {
+    match (deployer_key, deployer_pubkey) {
+        (Some(key), _) => {
+            let private_key = decode_hex_key("Deployer private key", &key, 32)?;
+            Ok(public_key_from_private(&private_key)?)
+        }
+        (None, Some(public_key)) => Ok(decode_deployer("Deployer", &public_key)?.0),
+        (None, None) => Err(CliError::new(
+            "start-app needs the deployer's identity: pass --deployer-key or \
+             --deployer-pubkey. Only the deployer can later stop the app or \
+             fetch its secret key",
+        )),
+    }
+}
 
-    // Sign the message with deployer's private key
-    let signature = tapp_service::app_key::sign_message(&deployer_private_key, &message)?;
+#[allow(clippy::too_many_arguments)]
+async fn start_app(
+    server: &str,
+    compose_file: PathBuf,
+    app_id: String,
+    mounts: Vec<String>,
+    mounts_encrypted: Vec<String>,
+    mounts_from_compose: Option<Option<PathBuf>>,
+    deployer: Vec<u8>,
+    register_on_chain: bool,
+    platform: String,
+    service_platforms: Vec<String>,
+) -> Result<StartAppOutput, CliError> {
+    let service_platforms = service_platforms
+        .iter()
+        .map(|spec| {
+            spec.split_once('=')
+                .map(|(service, platform)| (service.to_string(), platform.to_string()))
+                .ok_or_else(|| {
+                    CliError::new(format!(
+                        "Invalid service platform: {}. Expected: SERVICE=PLATFORM",
+                        spec
+                    ))
+                })
+        })
+        .collect::<Result<_, _>>()?;
 
-    let request = Request::new(GetAppSecretKeyRequest {
-        app_id: app_id.clone(),
-        nonce: nonce.clone(),
-        timestamp,
-        signature,
-    });
+    let mut client = connect(server).await?;
 
-    // Server will validate signature and that the connection is from localhost
-    let response = match client.get_app_secret_key(request).await {
-        Ok(resp) => resp,
-        Err(e) if e.code() == tonic::Code::PermissionDenied => {
-            eprintln!("╔════════════════════════════════════════════════════════════╗");
-            eprintln!("║              SECURITY RESTRICTION                          ║");
-            eprintln!("╠════════════════════════════════════════════════════════════╣");
-
-            if e.message().contains("Nonce") {
-                eprintln!("║ Nonce verification failed (replay attack detected)        ║");
-                eprintln!("║                                                            ║");
-                eprintln!("║ This could mean:                                           ║");
-                eprintln!("║ - The request was replayed                                 ║");
-                eprintln!("║ - The timestamp is outside the validity window             ║");
-                eprintln!("║ - The nonce was already used                               ║");
-            } else if e.message().contains("signature") {
-                eprintln!("║ Signature verification failed!                             ║");
-                eprintln!("║                                                            ║");
-                eprintln!("║ This means:                                                ║");
-                eprintln!("║ - The deployer private key is incorrect                    ║");
-                eprintln!("║ - You are not the deployer of this application             ║");
-                eprintln!("║                                                            ║");
-                eprintln!("║ Only the application deployer can access the private key.  ║");
-            } else {
-                eprintln!("║ GetAppSecretKey can ONLY be called from localhost or      ║");
-                eprintln!("║ same-host Docker containers!                               ║");
-                eprintln!("║                                                            ║");
-                eprintln!("║ Server: {:<51} ║", server);
-                eprintln!("║                                                            ║");
-                eprintln!("║ Private keys will NEVER be sent over the network.         ║");
-                eprintln!("║ This command must be run on the same machine as the       ║");
-                eprintln!("║ TAPP server (inside the TEE).                             ║");
-            }
+    // Read compose file
+    let compose_content = std::fs::read_to_string(&compose_file)?;
 
-            eprintln!("║                                                            ║");
-            eprintln!("║ Server says: {:<43} ║", e.message());
-            eprintln!("╚════════════════════════════════════════════════════════════╝");
-            std::process::exit(1);
-        }
-        Err(e) if e.code() == tonic::Code::NotFound => {
-            eprintln!("╔════════════════════════════════════════════════════════════╗");
-            eprintln!("║              APPLICATION NOT FOUND                         ║");
-            eprintln!("╠════════════════════════════════════════════════════════════╣");
-            eprintln!(
-                "║ The application '{}' was not found.{:<16} ║",
-                if app_id.len() <= 30 {
-                    &app_id
-                } else {
-                    &app_id[..30]
-                },
-                ""
-            );
-            eprintln!("║                                                            ║");
-            eprintln!("║ Make sure the application has been deployed using          ║");
-            eprintln!("║ the StartApp interface.                                    ║");
-            eprintln!("╚════════════════════════════════════════════════════════════╝");
-            std::process::exit(1);
+    let mut mount_files = match mounts_from_compose {
+        Some(dir) => {
+            let dir = dir.unwrap_or_else(|| {
+                compose_file
+                    .parent()
+                    .map(Path::to_path_buf)
+                    .unwrap_or_default()
+            });
+            let mount_files = mounts_from_compose_file(&compose_content, &dir)?;
+            // On stderr, so --output json stays parseable
+            for file in &mount_files {
+                eprintln!(
+                    "  Mount {} ({}, {} bytes)",
+                    file.source_path,
+                    file.mode,
+                    file.content.len()
+                );
+            }
+            mount_files
         }
-        Err(e) => return Err(e.into()),
+        None => Vec::new(),
     };
 
-    let result = response.into_inner();
+    // Parse mount files
+    let mounts = mounts.into_iter().map(|spec| (spec, false));
+    let mounts_encrypted = mounts_encrypted.into_iter().map(|spec| (spec, true));
+    for (mount_spec, encrypted) in mounts.chain(mounts_encrypted) {
+        let parts: Vec<&str> = mount_spec.split(':').collect();
+        if parts.len() != 3 {
+            return Err(CliError::new(format!(
+                "Invalid mount format: {}. Expected: source_path:file_path:mode",
+                mount_spec
+            )));
+        }
+
+        let source_path = parts[0].to_string();
+        let file_path = parts[1];
+        let mode = parts[2].to_string();
+
+        // Read file content
+        let content = std::fs::read(file_path)?;
+
+        // Replaces a file picked up from the compose file
+        mount_files.retain(|file| file.source_path != source_path);
+        mount_files.push(MountFile {
+            source_path,
+            content,
+            mode,
+            encrypted,
+        });
+    }
+
+    let request = Request::new(StartAppRequest {
+        compose_content,
+        app_id: app_id.clone(),
+        mount_files,
+        deployer: deployer.clone(),
+        register_on_chain: Some(register_on_chain),
+        platform,
+        service_platforms,
+    });
 
+    let result = client.start_app(request).await?.into_inner();
     if !result.success {
-        eprintln!("ERROR: {}", result.message);
-        std::process::exit(1);
+        return Err(CliError::new(result.message));
     }
+    let identity = DeployerIdentity::from_bytes(&deployer).map_err(CliError::new)?;
 
-    if json_output {
-        let output = serde_json::json!({
-            "private_key": format!("0x{}", hex::encode(&result.private_key)),
-            "public_key": format!("0x{}", hex::encode(&result.public_key)),
-            "evm_address": if !result.eth_address.is_empty() {
-                format!("0x{}", hex::encode(&result.eth_address))
-            } else {
-                String::new()
-            },
-        });
-        println!("{}", serde_json::to_string_pretty(&output)?);
-    } else {
-        println!("╔════════════════════════════════════════════════════════════╗");
-        println!("║           APPLICATION SECRET KEY (SENSITIVE!)              ║");
-        println!("╠════════════════════════════════════════════════════════════╣");
-        println!("║ WARNING: This is highly sensitive information!            ║");
-        println!("║ Keep this private key secure and never share it.          ║");
-        println!("╚════════════════════════════════════════════════════════════╝");
-        println!();
-        println!("  App ID: {}", app_id);
-        println!(
-            "  Private Key (hex): 0x{}",
-            hex::encode(&result.private_key)
-        );
-        println!("  Public Key (hex):  0x{}", hex::encode(&result.public_key));
+    Ok(StartAppOutput {
+        app_id,
+        task_id: result.task_id,
+        message: result.message,
+        timestamp: result.timestamp,
+        deployer: format!("0x{}", identity.canonical_hex()),
+        deployer_address: checksum_address(&identity.address),
+    })
+}
 
-        if !result.eth_address.is_empty() {
-            println!(
-                "  Ethereum Address:  0x{}",
-                hex::encode(&result.eth_address)
-            );
+/// Relative bind mount sources (`./nginx.conf`) of every service's volumes
+/// Named volumes and absolute host paths are not files to upload.
+fn compose_bind_sources(compose_content: &str) -> Result<Vec<String>, CliError> {
+    let compose: serde_yaml::Value = serde_yaml::from_str(compose_content)?;
+    let mut sources = Vec::new();
+    let services = compose
+        .get("services")
+        .and_then(|services| services.as_mapping());
+    for service in services.into_iter().flat_map(|services| services.values()) {
+        let volumes = service
+            .get("volumes")
+            .and_then(|volumes| volumes.as_sequence());
+        for volume in volumes.into_iter().flatten() {
+            // Short syntax is SOURCE:TARGET[:MODE], long syntax a mapping
+            let source = match volume {
+                serde_yaml::Value::String(spec) => spec.split(':').next(),
+                volume => volume.get("source").and_then(|source| source.as_str()),
+            };
+            if let Some(source) = source.filter(|source| source.starts_with("./")) {
+                if !sources.iter().any(|known| known == source) {
+                    sources.push(source.to_string());
+                }
+            }
         }
+    }
+    Ok(sources)
+}
 
-        println!();
-        println!("💡 You can use this private key with:");
-        println!("   tapp-cli sign-message --private-key <KEY> --message <MSG>");
+/// Mount files for the bind sources of `compose_content`, read from `dir`
+/// Directories are uploaded file by file, each with its own source path.
+fn mounts_from_compose_file(compose_content: &str, dir: &Path) -> Result<Vec<MountFile>, CliError> {
+    let mut mount_files = Vec::new();
+    for source in compose_bind_sources(compose_content)? {
+        let path = dir.join(&source);
+        if !path.exists() {
+            return Err(CliError::new(format!(
+                "The compose file binds {}, but {} does not exist",
+                source,
+                path.display()
+            )));
+        }
+        collect_mount_files(&path, source.trim_end_matches('/'), &mut mount_files)?;
+    }
+    Ok(mount_files)
+}
+
+fn collect_mount_files(
+    path: &Path,
+    source_path: &str,
+    mount_files: &mut Vec<MountFile>,
+) -> Result<(), CliError> {
+    if path.is_dir() {
+        let mut entries = std::fs::read_dir(path)?
+            .map(|entry| entry.map(|entry| entry.file_name()))
+            .collect::<Result<Vec<_>, _>>()?;
+        entries.sort();
+        for name in entries {
+            let source_path = format!("{}/{}", source_path, name.to_string_lossy());
+            collect_mount_files(&path.join(&name), &source_path, mount_files)?;
+        }
+        return Ok(());
     }
 
+    #[cfg(unix)]
+    let mode = {
+        use std::os::unix::fs::PermissionsExt;
+        format!(
+            "{:04o}",
+            std::fs::metadata(path)?.permissions().mode() & 0o777
+        )
+    };
+    #[cfg(not(unix))]
+    let mode = "0644".to_string();
+
+    mount_files.push(MountFile {
+        source_path: source_path.to_string(),
+        content: std::fs::read(path)?,
+        mode,
+        encrypted: false,
+    });
     Ok(())
 }
 
-fn sign_message(
-    private_key_hex: String,
-    message: String,
-) -> Result<(), Box<dyn std::error::Error>> {
-    // Remove 0x prefix if present
-    let private_key_hex = private_key_hex
-        .trim_start_matches("0x")
-        .trim_start_matches("0X");
+/// Sign a deployer-gated request for `operation` on `app_id`, with the
+/// legacy 64-byte scheme if `legacy`, with the v2 scheme otherwise
+/// Returns (nonce, timestamp, signature). With a server-issued challenge the
+/// challenge is the nonce and the timestamp is 0.
+fn sign_deployer_request(
+    app_id: &str,
+    operation: &str,
+    action: &[u8],
+    legacy: bool,
+    deployer_private_key_hex: &str,
+    challenge: Option<String>,
+) -> Result<(String, i64, Vec<u8>), CliError> {
+    let deployer_private_key =
+        decode_hex_key("Deployer private key", deployer_private_key_hex, 32)?;
+
+    let (nonce, timestamp) = match challenge {
+        Some(challenge) => (challenge, 0),
+        None => (
+            tapp_service::nonce_manager::generate_nonce(),
+            chrono::Utc::now().timestamp(),
+        ),
+    };
 
-    if private_key_hex.len() != 64 {
-        eprintln!(
-            "ERROR: Private key must be 32 bytes (64 hex characters), got {}",
-            private_key_hex.len()
+    let signature = if legacy {
+        let message = tapp_service::app_key::deployer_request_message(
+            operation, app_id, action, &nonce, timestamp,
         );
-        std::process::exit(1);
+        tapp_service::app_key::sign_message(&deployer_private_key, &message)?
+    } else {
+        let message = deployer::request_message_v2(app_id, &nonce, timestamp, operation, action);
+        deployer::sign_request_v2(&deployer_private_key, &message)?
+    };
+
+    Ok((nonce, timestamp, signature))
+}
+
+#[derive(Serialize)]
+struct StopAppOutput {
+    app_id: String,
+    message: String,
+    timestamp: i64,
+}
+
+impl Report for StopAppOutput {
+    fn print_text(&self) {
+        println!("✓ Application stopped successfully");
+        println!("  App ID: {}", self.app_id);
+        println!("  Message: {}", self.message);
+        println!("  Timestamp: {}", self.timestamp);
     }
+}
+
+async fn stop_app(
+    server: &str,
+    app_id: String,
+    deployer_key: Option<String>,
+) -> Result<StopAppOutput, CliError> {
+    let mut client = connect(server).await?;
 
-    let private_key = hex::decode(private_key_hex)?;
-    let message_bytes = message.as_bytes();
+    // Without a deployer key the request is sent unsigned; the server only
+    // accepts that from an admin API key
+    let request = match deployer_key {
+        Some(key) => {
+            let (nonce, timestamp, signature) =
+                sign_deployer_request(&app_id, "stop_app", b"stop", true, &key, None)?;
+            StopAppRequest {
+                app_id: app_id.clone(),
+                nonce,
+                timestamp,
+                signature,
+            }
+        }
+        None => StopAppRequest {
+            app_id: app_id.clone(),
+            ..Default::default()
+        },
+    };
 
-    let signature = tapp_service::app_key::sign_message(&private_key, message_bytes)?;
+    let result = match client.stop_app(Request::new(request)).await {
+        Ok(response) => response.into_inner(),
+        // A separate exit code lets teardown scripts treat an app that is
+        // already gone as done
+        Err(e) if e.code() == tonic::Code::NotFound => {
+            let server_says = format!("  Server says: {}", e.message());
+            let mut error = CliError::from(e)
+                .with_detail(server_says)
+                .with_exit_code(EXIT_NOT_FOUND);
+            error.message = format!("Application '{}' not found", app_id);
+            return Err(error);
+        }
+        Err(e) => return Err(e.into()),
+    };
 
-    println!("✓ Message signed successfully");
-    println!("  Message: {}", message);
-    println!("  Signature (hex): 0x{}", hex::encode(&signature));
-    println!("  Signature (base64): {}", base64::encode(&signature));
+    if !result.success {
+        return Err(CliError::new(result.message));
+    }
 
-    Ok(())
+    Ok(StopAppOutput {
+        app_id,
+        message: result.message,
+        timestamp: result.timestamp,
+    })
 }
 
-fn verify_signature(
-    public_key_hex: String,
-    message: String,
-    signature_hex: String,
-) -> Result<(), Box<dyn std::error::Error>> {
-    // Remove 0x prefix if present
-    let public_key_hex = public_key_hex
-        .trim_start_matches("0x")
-        .trim_start_matches("0X");
-    let signature_hex = signature_hex
-        .trim_start_matches("0x")
-        .trim_start_matches("0X");
+/// Polling settings of `task-status --wait`
+struct Wait {
+    interval: u64,
+    timeout: Option<u64>,
+}
 
-    if public_key_hex.len() != 128 {
-        eprintln!(
-            "ERROR: Public key must be 64 bytes (128 hex characters), got {}",
-            public_key_hex.len()
-        );
-        std::process::exit(1);
+#[derive(Serialize)]
+struct TaskStatusOutput {
+    task_id: String,
+    app_id: String,
+    kind: String,
+    status: String,
+    stage: String,
+    progress_percent: i32,
+    queue_position: i32,
+    retried_from: String,
+    created_at: i64,
+    updated_at: i64,
+    /// Set once the task failed
+    error_code: Option<String>,
+    error: Option<String>,
+    /// Vulnerability scan of each image, when the service scans them
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    image_scans: Vec<ImageScanOutput>,
+    #[serde(skip)]
+    task_status: TaskStatus,
+    /// Whether the command waited for the outcome
+    #[serde(skip)]
+    waited: bool,
+}
+
+impl TaskStatusOutput {
+    fn new(task_id: String, response: GetTaskStatusResponse, waited: bool) -> Self {
+        let task_status = TaskStatus::try_from(response.status).unwrap_or(TaskStatus::Pending);
+        let (error_code, error) = match response.result {
+            Some(result) if task_status == TaskStatus::Failed => (
+                Some(
+                    TaskErrorCode::try_from(result.error_code)
+                        .unwrap_or(TaskErrorCode::InternalError)
+                        .as_str_name()
+                        .to_string(),
+                ),
+                Some(result.error),
+            ),
+            _ => (None, None),
+        };
+        Self {
+            task_id,
+            app_id: response.app_id,
+            kind: TaskKind::try_from(response.kind)
+                .unwrap_or(TaskKind::StartApp)
+                .as_str_name()
+                .to_string(),
+            status: task_status.as_str_name().to_string(),
+            stage: response.stage,
+            progress_percent: response.progress_percent,
+            queue_position: response.queue_position,
+            retried_from: response.retried_from,
+            created_at: response.created_at,
+            updated_at: response.updated_at,
+            error_code,
+            error,
+            image_scans: response
+                .image_scans
+                .into_iter()
+                .map(ImageScanOutput::from)
+                .collect(),
+            task_status,
+            waited,
+        }
     }
+}
 
-    let public_key = hex::decode(public_key_hex)?;
-    let signature = hex::decode(signature_hex)?;
-    let message_bytes = message.as_bytes();
+#[derive(Serialize)]
+struct ImageScanOutput {
+    image: String,
+    critical: i32,
+    high: i32,
+    medium: i32,
+    low: i32,
+    unknown: i32,
+    passed: bool,
+    /// Why the image could not be scanned
+    error: Option<String>,
+}
 
-    let is_valid = tapp_service::app_key::verify_signature(&public_key, message_bytes, &signature)?;
+impl From<ImageScanResult> for ImageScanOutput {
+    fn from(scan: ImageScanResult) -> Self {
+        Self {
+            image: scan.image,
+            critical: scan.critical,
+            high: scan.high,
+            medium: scan.medium,
+            low: scan.low,
+            unknown: scan.unknown,
+            passed: scan.passed,
+            error: (!scan.error.is_empty()).then_some(scan.error),
+        }
+    }
+}
 
-    if is_valid {
-        println!("✓ Signature is VALID");
-        println!("  Message: {}", message);
-        println!("  Public Key: 0x{}", public_key_hex);
-    } else {
-        println!("✗ Signature is INVALID");
-        println!("  Message: {}", message);
-        println!("  Public Key: 0x{}", public_key_hex);
-        std::process::exit(1);
+impl Report for TaskStatusOutput {
+    fn print_text(&self) {
+        println!("Task ID: {}", self.task_id);
+        println!("  App ID: {}", self.app_id);
+        println!("  Status: {:?}", self.task_status);
+        if self.task_status == TaskStatus::Queued {
+            println!("  Queue position: {}", self.queue_position);
+        }
+        println!("  Stage: {} ({}%)", self.stage, self.progress_percent);
+        if !self.retried_from.is_empty() {
+            println!("  Retried from: {}", self.retried_from);
+        }
+        if !self.image_scans.is_empty() {
+            println!("  Image scans:");
+        }
+        for scan in &self.image_scans {
+            let verdict = if scan.passed { "passed" } else { "failed" };
+            println!(
+                "    {} {}: {} critical, {} high, {} medium, {} low, {} unknown",
+                verdict, scan.image, scan.critical, scan.high, scan.medium, scan.low, scan.unknown
+            );
+            if let Some(error) = &scan.error {
+                println!("      Scanner error: {}", error);
+            }
+        }
+        if let (Some(error_code), Some(error)) = (&self.error_code, &self.error) {
+            println!("  Error code: {}", error_code);
+            println!("  Error: {}", error);
+        }
     }
 
-    Ok(())
+    /// Waiting succeeds only if the task completed
+    fn exit_code(&self) -> i32 {
+        match self.task_status {
+            TaskStatus::Failed | TaskStatus::Cancelled if self.waited => 1,
+            _ => 0,
+        }
+    }
+}
+
+async fn task_status(
+    server: &str,
+    task_id: String,
+    wait: Option<Wait>,
+    output: OutputFormat,
+) -> Result<TaskStatusOutput, CliError> {
+    let mut client = connect(server).await?;
+    let started = std::time::Instant::now();
+    let mut last = None;
+
+    loop {
+        let request = GetTaskStatusRequest {
+            task_id: task_id.clone(),
+        };
+        let result = client
+            .get_task_status(Request::new(request))
+            .await?
+            .into_inner();
+        if !result.success {
+            return Err(CliError::new(result.message));
+        }
+
+        let Some(wait) = &wait else {
+            return Ok(TaskStatusOutput::new(task_id, result, false));
+        };
+
+        // Print every change while waiting; on stderr when stdout is for JSON
+        let status = TaskStatus::try_from(result.status).unwrap_or(TaskStatus::Pending);
+        let state = (status, result.stage.clone(), result.progress_percent);
+        if last.as_ref() != Some(&state) {
+            let line = format!(
+                "[{}] {:?}: {} ({}%)",
+                chrono::Local::now().format("%H:%M:%S"),
+                status,
+                result.stage,
+                result.progress_percent
+            );
+            match output {
+                OutputFormat::Text => println!("{}", line),
+                OutputFormat::Json => eprintln!("{}", line),
+            }
+            last = Some(state);
+        }
+
+        if task_finished(status) {
+            return Ok(TaskStatusOutput::new(task_id, result, true));
+        }
+        if let Some(timeout) = wait.timeout {
+            if started.elapsed() >= std::time::Duration::from_secs(timeout) {
+                return Err(CliError::new(format!(
+                    "Task {} still {:?} after {}s",
+                    task_id, status, timeout
+                )));
+            }
+        }
+        tokio::time::sleep(std::time::Duration::from_secs(wait.interval)).await;
+    }
+}
+
+/// Whether a task reached its final status
+fn task_finished(status: TaskStatus) -> bool {
+    matches!(
+        status,
+        TaskStatus::Completed | TaskStatus::Failed | TaskStatus::Cancelled
+    )
+}
+
+/// Consecutive failed reconnects after which watch-task gives up
+const MAX_WATCH_RECONNECTS: u32 = 5;
+
+/// Change of a task's state, printed by watch-task
+/// With `--output json` each one is a line of JSON on stdout.
+#[derive(Serialize)]
+struct TaskTransition<'a> {
+    time: String,
+    task_id: &'a str,
+    status: &'static str,
+    stage: &'a str,
+    progress_percent: i32,
+    queue_position: i32,
+}
+
+/// Print `update` unless its status, stage, progress and queue position are
+/// those of `last`
+fn print_transition(
+    output: OutputFormat,
+    last: Option<&GetTaskStatusResponse>,
+    update: &GetTaskStatusResponse,
+) -> Result<(), CliError> {
+    let state = |r: &GetTaskStatusResponse| {
+        (
+            r.status,
+            r.stage.clone(),
+            r.progress_percent,
+            r.queue_position,
+        )
+    };
+    if last.map(state) == Some(state(update)) {
+        return Ok(());
+    }
+
+    let status = TaskStatus::try_from(update.status).unwrap_or(TaskStatus::Pending);
+    let now = chrono::Local::now();
+    match output {
+        OutputFormat::Text => {
+            let queued = if status == TaskStatus::Queued {
+                format!(", queue position {}", update.queue_position)
+            } else {
+                String::new()
+            };
+            println!(
+                "[{}] {:?}: {} ({}%){}",
+                now.format("%H:%M:%S"),
+                status,
+                update.stage,
+                update.progress_percent,
+                queued
+            );
+        }
+        OutputFormat::Json => {
+            let transition = TaskTransition {
+                time: now.to_rfc3339(),
+                task_id: &update.task_id,
+                status: status.as_str_name(),
+                stage: &update.stage,
+                progress_percent: update.progress_percent,
+                queue_position: update.queue_position,
+            };
+            println!("{}", serde_json::to_string(&transition)?);
+        }
+    }
+    Ok(())
+}
+
+/// Whether a failed watch stream is worth reopening: the connection or the
+/// stream was reset rather than the request refused
+fn stream_reset(status: &tonic::Status) -> bool {
+    matches!(
+        status.code(),
+        tonic::Code::Unavailable | tonic::Code::Unknown | tonic::Code::Cancelled
+    )
+}
+
+async fn watch_task(
+    server: &str,
+    task_id: String,
+    interval: u64,
+    output: OutputFormat,
+) -> Result<TaskStatusOutput, CliError> {
+    let mut client = connect(server).await?;
+    let mut last: Option<GetTaskStatusResponse> = None;
+    let mut reconnects = 0;
+
+    loop {
+        let request = WatchTaskRequest {
+            task_id: task_id.clone(),
+        };
+        let mut stream = match client.watch_task(Request::new(request)).await {
+            Ok(response) => response.into_inner(),
+            Err(e) if e.code() == tonic::Code::Unimplemented && last.is_none() => {
+                eprintln!(
+                    "Warning: the server does not support WatchTask; polling every {}s instead",
+                    interval
+                );
+                return poll_task(&mut client, task_id, interval, output).await;
+            }
+            Err(e) if last.is_some() && stream_reset(&e) && reconnects < MAX_WATCH_RECONNECTS => {
+                reconnects += 1;
+                tokio::time::sleep(std::time::Duration::from_secs(interval)).await;
+                continue;
+            }
+            Err(e) => return Err(e.into()),
+        };
+
+        // The stream starts with the current state, so after a reconnect
+        // unchanged states are skipped and the watch resumes where it was
+        loop {
+            match stream.message().await {
+                Ok(Some(update)) => {
+                    reconnects = 0;
+                    print_transition(output, last.as_ref(), &update)?;
+                    let status = TaskStatus::try_from(update.status).unwrap_or(TaskStatus::Pending);
+                    if task_finished(status) {
+                        return Ok(TaskStatusOutput::new(task_id, update, true));
+                    }
+                    last = Some(update);
+                }
+                // Ended or reset before the task finished
+                Ok(None) => break,
+                Err(e) if stream_reset(&e) => break,
+                Err(e) => return Err(e.into()),
+            }
+        }
+        eprintln!("Watch stream of task {} interrupted; reconnecting", task_id);
+    }
+}
+
+/// watch-task for servers without WatchTask: poll GetTaskStatus
+async fn poll_task(
+    client: &mut TappServiceClient<SignedChannel>,
+    task_id: String,
+    interval: u64,
+    output: OutputFormat,
+) -> Result<TaskStatusOutput, CliError> {
+    let mut last = None;
+    loop {
+        let request = GetTaskStatusRequest {
+            task_id: task_id.clone(),
+        };
+        let result = client
+            .get_task_status(Request::new(request))
+            .await?
+            .into_inner();
+        if !result.success {
+            return Err(CliError::new(result.message));
+        }
+
+        print_transition(output, last.as_ref(), &result)?;
+        let status = TaskStatus::try_from(result.status).unwrap_or(TaskStatus::Pending);
+        if task_finished(status) {
+            return Ok(TaskStatusOutput::new(task_id, result, true));
+        }
+        last = Some(result);
+        tokio::time::sleep(std::time::Duration::from_secs(interval)).await;
+    }
+}
+
+#[derive(Serialize)]
+struct CancelTaskOutput {
+    task_id: String,
+    message: String,
+    status: String,
+}
+
+impl Report for CancelTaskOutput {
+    fn print_text(&self) {
+        println!("✓ {}", self.message);
+        println!("  Task ID: {}", self.task_id);
+        println!("  Status: {}", self.status);
+    }
+}
+
+async fn cancel_task(
+    server: &str,
+    task_id: String,
+    app_id: String,
+    deployer_key: Option<String>,
+) -> Result<CancelTaskOutput, CliError> {
+    let mut client = connect(server).await?;
+
+    let request = match deployer_key {
+        Some(key) => {
+            let action = format!("cancel:{}", task_id);
+            let (nonce, timestamp, signature) =
+                sign_deployer_request(&app_id, "cancel_task", action.as_bytes(), true, &key, None)?;
+            CancelTaskRequest {
+                task_id: task_id.clone(),
+                nonce,
+                timestamp,
+                signature,
+            }
+        }
+        None => CancelTaskRequest {
+            task_id: task_id.clone(),
+            ..Default::default()
+        },
+    };
+
+    let result = client
+        .cancel_task(Request::new(request))
+        .await?
+        .into_inner();
+    if !result.success {
+        return Err(CliError::new(result.message));
+    }
+
+    Ok(CancelTaskOutput {
+        task_id,
+        message: result.message,
+        status: TaskStatus::try_from(result.status)
+            .unwrap_or(TaskStatus::Pending)
+            .as_str_name()
+            .to_string(),
+    })
+}
+
+#[derive(Serialize)]
+struct RetryTaskOutput {
+    task_id: String,
+    new_task_id: String,
+    message: String,
+}
+
+impl Report for RetryTaskOutput {
+    fn print_text(&self) {
+        println!("✓ {}", self.message);
+        println!("  New Task ID: {}", self.new_task_id);
+    }
+}
+
+async fn retry_task(
+    server: &str,
+    task_id: String,
+    app_id: String,
+    deployer_key: Option<String>,
+) -> Result<RetryTaskOutput, CliError> {
+    let mut client = connect(server).await?;
+
+    let request = match deployer_key {
+        Some(key) => {
+            let action = format!("retry:{}", task_id);
+            let (nonce, timestamp, signature) =
+                sign_deployer_request(&app_id, "retry_task", action.as_bytes(), true, &key, None)?;
+            RetryTaskRequest {
+                task_id: task_id.clone(),
+                nonce,
+                timestamp,
+                signature,
+            }
+        }
+        None => RetryTaskRequest {
+            task_id: task_id.clone(),
+            ..Default::default()
+        },
+    };
+
+    let result = client.retry_task(Request::new(request)).await?.into_inner();
+    if !result.success {
+        return Err(CliError::new(result.message));
+    }
+
+    Ok(RetryTaskOutput {
+        task_id,
+        new_task_id: result.new_task_id,
+        message: result.message,
+    })
+}
+
+#[derive(Serialize)]
+struct TransferOwnershipOutput {
+    app_id: String,
+    previous_deployer: String,
+    new_deployer: String,
+    timestamp: i64,
+}
+
+impl Report for TransferOwnershipOutput {
+    fn print_text(&self) {
+        println!("✓ Application ownership transferred successfully");
+        println!("  App ID: {}", self.app_id);
+        println!("  Previous Deployer: {}", self.previous_deployer);
+        println!("  New Deployer: {}", self.new_deployer);
+        println!("  Timestamp: {}", self.timestamp);
+    }
+}
+
+async fn transfer_ownership(
+    server: &str,
+    app_id: String,
+    new_deployer_hex: String,
+    deployer_key: String,
+) -> Result<TransferOwnershipOutput, CliError> {
+    let mut client = connect(server).await?;
+
+    let (new_deployer, _) = decode_deployer("New deployer", &new_deployer_hex)?;
+
+    // The current deployer signs the new deployer
+    let (nonce, timestamp, signature) = sign_deployer_request(
+        &app_id,
+        "transfer_ownership",
+        &new_deployer,
+        true,
+        &deployer_key,
+        None,
+    )?;
+
+    let request = Request::new(TransferAppOwnershipRequest {
+        app_id: app_id.clone(),
+        new_deployer,
+        nonce,
+        timestamp,
+        signature,
+    });
+
+    let result = client.transfer_app_ownership(request).await?.into_inner();
+    if !result.success {
+        return Err(CliError::new(result.message));
+    }
+
+    Ok(TransferOwnershipOutput {
+        app_id,
+        previous_deployer: format!("0x{}", result.previous_deployer),
+        new_deployer: format!("0x{}", result.new_deployer),
+        timestamp: result.timestamp,
+    })
+}
+
+#[derive(Serialize)]
+struct EvidenceOutput {
+    tee_type: String,
+    timestamp: i64,
+    size: usize,
+    sha256: String,
+    /// Report data sent, 0x-prefixed hex (None: zero-filled 64 bytes)
+    report_data: Option<String>,
+    /// File the evidence was written to
+    out: Option<PathBuf>,
+    /// With --summary: the quote's TD report
+    td_report: Option<TdReportOutput>,
+    evidence_hex: String,
+    evidence_base64: String,
+    /// With --raw: print the whole evidence in text mode too
+    #[serde(skip)]
+    raw: bool,
+}
+
+impl Report for EvidenceOutput {
+    fn print_text(&self) {
+        println!("✓ Evidence generated successfully");
+        println!("  TEE Type: {}", self.tee_type);
+        println!("  Timestamp: {}", self.timestamp);
+        println!("  Size: {} bytes", self.size);
+        println!("  SHA-256: {}", self.sha256);
+        if let Some(path) = &self.out {
+            println!("  Written to: {}", path.display());
+        }
+
+        match &self.report_data {
+            Some(report_data) => println!("  Report data used: {}", report_data),
+            None => println!("  Report data: (empty, will use zero-filled 64 bytes)"),
+        }
+
+        if let Some(td_report) = &self.td_report {
+            println!();
+            td_report.print_text();
+        }
+
+        if self.raw {
+            println!();
+            println!("Evidence (hex): {}", self.evidence_hex);
+            println!("Evidence (base64): {}", self.evidence_base64);
+        }
+    }
+}
+
+async fn get_evidence(
+    server: &str,
+    report_data_hex: String,
+    out: Option<PathBuf>,
+    summary: bool,
+    raw: bool,
+) -> Result<EvidenceOutput, CliError> {
+    let mut client = connect(server).await?;
+
+    // Decode report data if provided
+    let report_data_bytes = if report_data_hex.is_empty() {
+        vec![]
+    } else {
+        let hex_str = strip_hex_prefix(&report_data_hex);
+
+        // Validate and decode
+        if hex_str.len() > 128 {
+            return Err(CliError::new(format!(
+                "Report data must be at most 64 bytes (128 hex characters), got {}",
+                hex_str.len()
+            )));
+        }
+
+        hex::decode(hex_str)?
+    };
+
+    let request = Request::new(GetEvidenceRequest {
+        report_data: report_data_bytes.clone(),
+    });
+
+    let result = client.get_evidence(request).await?.into_inner();
+
+    if let Some(path) = &out {
+        std::fs::write(path, &result.evidence)?;
+    }
+    let td_report = if summary {
+        let quote = parse_quote(&quote_from_evidence(&result.evidence)?)?;
+        Some(TdReportOutput::new(quote))
+    } else {
+        None
+    };
+
+    Ok(EvidenceOutput {
+        tee_type: result.tee_type,
+        timestamp: result.timestamp,
+        size: result.evidence.len(),
+        sha256: format!("0x{}", hex::encode(Sha256::digest(&result.evidence))),
+        report_data: (!report_data_bytes.is_empty())
+            .then(|| format!("0x{}", hex::encode(&report_data_bytes))),
+        out,
+        td_report,
+        evidence_hex: hex::encode(&result.evidence),
+        evidence_base64: BASE64.encode(&result.evidence),
+        raw,
+    })
+}
+
+/// Fields of a TDX quote's TD report, 0x-prefixed hex
+#[derive(Serialize)]
+struct TdReportOutput {
+    version: u16,
+    tee_tcb_svn: String,
+    mr_seam: String,
+    mr_signer_seam: String,
+    td_attributes: String,
+    xfam: String,
+    mr_td: String,
+    mr_config_id: String,
+    mr_owner: String,
+    mr_owner_config: String,
+    rtmrs: Vec<String>,
+    report_data: String,
+}
+
+impl TdReportOutput {
+    fn new(quote: Quote) -> Self {
+        let report = quote.report;
+        let hex = |bytes: &[u8]| format!("0x{}", hex::encode(bytes));
+        Self {
+            version: quote.version,
+            tee_tcb_svn: hex(&report.tee_tcb_svn),
+            mr_seam: hex(&report.mr_seam),
+            mr_signer_seam: hex(&report.mr_signer_seam),
+            td_attributes: hex(&report.td_attributes),
+            xfam: hex(&report.xfam),
+            mr_td: hex(&report.mr_td),
+            mr_config_id: hex(&report.mr_config_id),
+            mr_owner: hex(&report.mr_owner),
+            mr_owner_config: hex(&report.mr_owner_config),
+            rtmrs: report.rtmrs.iter().map(|rtmr| hex(rtmr)).collect(),
+            report_data: hex(&report.report_data),
+        }
+    }
+
+    fn print_text(&self) {
+        println!("TDX quote (version {})", self.version);
+        println!("  TEE TCB SVN:     {}", self.tee_tcb_svn);
+        println!("  MRSEAM:          {}", self.mr_seam);
+        println!("  MRSIGNERSEAM:    {}", self.mr_signer_seam);
+        println!("  TD attributes:   {}", self.td_attributes);
+        println!("  XFAM:            {}", self.xfam);
+        println!("  MRTD:            {}", self.mr_td);
+        println!("  MRCONFIGID:      {}", self.mr_config_id);
+        println!("  MROWNER:         {}", self.mr_owner);
+        println!("  MROWNERCONFIG:   {}", self.mr_owner_config);
+        for (i, rtmr) in self.rtmrs.iter().enumerate() {
+            println!("  RTMR{}:           {}", i, rtmr);
+        }
+        println!("  Report data:     {}", self.report_data);
+    }
+}
+
+#[derive(Serialize)]
+struct VerifyEvidenceOutput {
+    #[serde(flatten)]
+    td_report: TdReportOutput,
+    checks: Vec<EvidenceCheck>,
+}
+
+/// An expected value compared with the quote
+#[derive(Serialize)]
+struct EvidenceCheck {
+    field: &'static str,
+    expected: String,
+    matches: bool,
+}
+
+impl VerifyEvidenceOutput {
+    fn check(&mut self, field: &'static str, expected: &[u8], actual: &[u8]) {
+        self.checks.push(EvidenceCheck {
+            field,
+            expected: format!("0x{}", hex::encode(expected)),
+            matches: expected == actual,
+        });
+    }
+}
+
+impl Report for VerifyEvidenceOutput {
+    fn print_text(&self) {
+        self.td_report.print_text();
+
+        for check in &self.checks {
+            if check.matches {
+                println!("✓ {} matches", check.field);
+            } else {
+                println!("✗ {} does NOT match", check.field);
+                println!("  Expected: {}", check.expected);
+            }
+        }
+    }
+
+    fn exit_code(&self) -> i32 {
+        if self.checks.iter().all(|check| check.matches) {
+            0
+        } else {
+            1
+        }
+    }
+}
+
+/// Read an evidence file; get-evidence prints hex, so hex text is decoded
+fn read_evidence(path: &Path) -> Result<Vec<u8>, CliError> {
+    let bytes = std::fs::read(path)?;
+    if let Ok(text) = std::str::from_utf8(&bytes) {
+        let text = strip_hex_prefix(text.trim());
+        if !text.is_empty() && text.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return Ok(hex::decode(text)?);
+        }
+    }
+    Ok(bytes)
+}
+
+fn verify_evidence(
+    evidence_file: PathBuf,
+    expect_report_data: Option<String>,
+    expect_rtmr2: Option<String>,
+) -> Result<VerifyEvidenceOutput, CliError> {
+    let quote = quote_from_evidence(&read_evidence(&evidence_file)?)?;
+    let quote = parse_quote(&quote)?;
+
+    let mut output = VerifyEvidenceOutput {
+        td_report: TdReportOutput::new(quote.clone()),
+        checks: Vec::new(),
+    };
+    if let Some(report_data) = expect_report_data {
+        let mut expected = hex::decode(strip_hex_prefix(&report_data))?;
+        if expected.len() > 64 {
+            return Err(CliError::new(format!(
+                "Expected report data must be at most 64 bytes, got {}",
+                expected.len()
+            )));
+        }
+        expected.resize(64, 0);
+        output.check("report_data", &expected, &quote.report.report_data);
+    }
+    if let Some(rtmr2) = expect_rtmr2 {
+        let expected = decode_hex_key("Expected RTMR2", &rtmr2, 48)?;
+        output.check("rtmr2", &expected, &quote.report.rtmrs[2]);
+    }
+    Ok(output)
+}
+
+#[derive(Serialize)]
+struct AppKeyOutput {
+    app_id: String,
+    key_type: String,
+    key_source: String,
+    public_key: String,
+    /// EIP-55 checksummed, for ethereum keys
+    eth_address: Option<String>,
+}
+
+impl AppKeyOutput {
+    fn new(app_id: String, key_type: String, response: GetAppKeyResponse) -> Self {
+        Self {
+            app_id,
+            key_source: response.key_source,
+            public_key: format!("0x{}", hex::encode(&response.public_key)),
+            eth_address: (key_type == "ethereum" && !response.eth_address.is_empty())
+                .then(|| checksum_address(&response.eth_address)),
+            key_type,
+        }
+    }
+}
+
+impl Report for AppKeyOutput {
+    fn print_text(&self) {
+        println!("✓ Application key retrieved successfully");
+        println!("  App ID: {}", self.app_id);
+        println!("  Key Type: {}", self.key_type);
+        println!("  Key Source: {}", self.key_source);
+        println!("  Public Key (hex): {}", self.public_key);
+
+        if let Some(eth_address) = &self.eth_address {
+            println!("  Ethereum Address: {}", eth_address);
+        }
+    }
+}
+
+async fn get_app_key(
+    server: &str,
+    app_id: String,
+    key_type: String,
+) -> Result<AppKeyOutput, CliError> {
+    let mut client = connect(server).await?;
+
+    let request = Request::new(GetAppKeyRequest {
+        app_id: app_id.clone(),
+        key_type: key_type.clone(),
+        additional_data: vec![],
+        kbs_resource_uri: String::new(),
+    });
+
+    let result = client.get_app_key(request).await?.into_inner();
+    if !result.success {
+        return Err(CliError::new(result.message));
+    }
+
+    Ok(AppKeyOutput::new(app_id, key_type, result))
+}
+
+#[derive(Serialize)]
+struct EncryptMountOutput {
+    app_id: String,
+    file: PathBuf,
+    encrypted_file: PathBuf,
+    size: usize,
+    /// Hashes of the encrypted file; the server measures it with its hash
+    /// algorithm (sha384 by default)
+    sha256: String,
+    sha384: String,
+}
+
+impl Report for EncryptMountOutput {
+    fn print_text(&self) {
+        println!("✓ Mount file encrypted for app {}", self.app_id);
+        println!("  Written to: {}", self.encrypted_file.display());
+        println!("  Size: {} bytes", self.size);
+        println!("  SHA-256: {}", self.sha256);
+        println!("  SHA-384: {}", self.sha384);
+        println!(
+            "  Deploy with: --mount-encrypted <SOURCE_PATH>:{}:<MODE>",
+            self.encrypted_file.display()
+        );
+    }
+}
+
+/// Encrypt `file` to the app key `public_key`, next to it as FILE.enc
+fn seal_mount_file(
+    public_key: &[u8],
+    app_id: String,
+    file: PathBuf,
+) -> Result<EncryptMountOutput, CliError> {
+    let sealed = envelope::seal(public_key, &app_id, &std::fs::read(&file)?)?;
+    let mut encrypted_file = file.clone().into_os_string();
+    encrypted_file.push(".enc");
+    let encrypted_file = PathBuf::from(encrypted_file);
+    std::fs::write(&encrypted_file, &sealed)?;
+
+    Ok(EncryptMountOutput {
+        app_id,
+        file,
+        encrypted_file,
+        size: sealed.len(),
+        sha256: format!("0x{}", hex::encode(Sha256::digest(&sealed))),
+        sha384: format!("0x{}", hex::encode(Sha384::digest(&sealed))),
+    })
+}
+
+async fn encrypt_mount(
+    server: &str,
+    app_id: String,
+    file: PathBuf,
+) -> Result<EncryptMountOutput, CliError> {
+    let mut client = connect(server).await?;
+
+    let request = Request::new(GetAppKeyRequest {
+        app_id: app_id.clone(),
+        key_type: "ethereum".to_string(),
+        additional_data: vec![],
+        kbs_resource_uri: String::new(),
+    });
+    let result = client.get_app_key(request).await?.into_inner();
+    if !result.success {
+        return Err(CliError::new(result.message));
+    }
+
+    seal_mount_file(&result.public_key, app_id, file)
+}
+
+#[derive(Serialize)]
+struct DecryptMountOutput {
+    app_id: String,
+    file: PathBuf,
+    size: usize,
+    /// SHA-256 of the decrypted content
+    sha256: String,
+}
+
+impl Report for DecryptMountOutput {
+    fn print_text(&self) {
+        println!("✓ {} decrypts with the given key", self.file.display());
+        println!("  App ID: {}", self.app_id);
+        println!("  Size: {} bytes", self.size);
+        println!("  SHA-256: {}", self.sha256);
+    }
+}
+
+fn decrypt_mount(
+    app_id: String,
+    file: PathBuf,
+    private_key: String,
+) -> Result<DecryptMountOutput, CliError> {
+    let private_key = decode_hex_key("Private key", &private_key, 32)?;
+    let plaintext = envelope::open(&private_key, &app_id, &std::fs::read(&file)?)?;
+    Ok(DecryptMountOutput {
+        app_id,
+        file,
+        size: plaintext.len(),
+        sha256: format!("0x{}", hex::encode(Sha256::digest(&plaintext))),
+    })
+}
+
+#[derive(Serialize)]
+struct AttestedKeyOutput {
+    app_id: String,
+    public_key: String,
+    /// EIP-55 checksummed
+    eth_address: String,
+    compose_hash: String,
+    nonce: String,
+    tee_type: String,
+    timestamp: i64,
+    /// Report data recomputed from the response
+    expected_report_data: String,
+    /// File the evidence was written to
+    evidence_file: Option<PathBuf>,
+    verified: bool,
+    /// Why the binding does not hold
+    reason: Option<String>,
+}
+
+impl AttestedKeyOutput {
+    /// Check `response` against the binding for `nonce`
+    fn new(app_id: String, nonce: &[u8], response: &GetAttestedAppKeyResponse) -> Self {
+        let hex = |bytes: &[u8]| format!("0x{}", hex::encode(bytes));
+        let expected = attested_key_report_data(
+            &response.nonce,
+            &response.public_key,
+            &response.compose_hash,
+        );
+        let reason = check_attested_key(nonce, response, &expected).err();
+        Self {
+            app_id,
+            public_key: hex(&response.public_key),
+            eth_address: checksum_address(&response.eth_address),
+            compose_hash: hex(&response.compose_hash),
+            nonce: hex(&response.nonce),
+            tee_type: response.tee_type.clone(),
+            timestamp: response.timestamp,
+            expected_report_data: hex(&expected),
+            evidence_file: None,
+            verified: reason.is_none(),
+            reason,
+        }
+    }
+}
+
+/// Check that the response answers `nonce` and that its quote carries
+/// `expected` as report data
+fn check_attested_key(
+    nonce: &[u8],
+    response: &GetAttestedAppKeyResponse,
+    expected: &[u8],
+) -> Result<(), String> {
+    if response.nonce != nonce {
+        return Err("the response is for another nonce".to_string());
+    }
+    if response.public_key.len() != 64 {
+        return Err(format!(
+            "the public key is {} bytes, expected 64",
+            response.public_key.len()
+        ));
+    }
+    if response.eth_address != eth_address(&response.public_key) {
+        return Err("the Ethereum address does not belong to the public key".to_string());
+    }
+    let quote = quote_from_evidence(&response.evidence)
+        .and_then(|quote| parse_quote(&quote))
+        .map_err(|e| format!("the evidence is not a TDX quote: {}", e))?;
+    if quote.report.report_data != expected {
+        return Err(format!(
+            "the quote's report data 0x{} does not match the binding",
+            hex::encode(&quote.report.report_data)
+        ));
+    }
+    Ok(())
+}
+
+impl Report for AttestedKeyOutput {
+    fn print_text(&self) {
+        println!("App ID: {}", self.app_id);
+        println!("  Public Key (hex): {}", self.public_key);
+        println!("  Ethereum Address: {}", self.eth_address);
+        println!("  Compose Hash: {}", self.compose_hash);
+        println!("  Nonce: {}", self.nonce);
+        println!("  TEE Type: {}", self.tee_type);
+        println!("  Report data expected: {}", self.expected_report_data);
+        if let Some(path) = &self.evidence_file {
+            println!("  Evidence written to: {}", path.display());
+        }
+
+        match &self.reason {
+            None => println!("✓ VERIFIED: the quote binds this key to the compose hash"),
+            Some(reason) => println!("✗ NOT VERIFIED: {}", reason),
+        }
+    }
+
+    fn exit_code(&self) -> i32 {
+        if self.verified {
+            0
+        } else {
+            1
+        }
+    }
+}
+
+async fn get_attested_key(
+    server: &str,
+    app_id: String,
+    nonce: String,
+    save_evidence: Option<PathBuf>,
+) -> Result<AttestedKeyOutput, CliError> {
+    let nonce = hex::decode(strip_hex_prefix(&nonce))?;
+    if nonce.is_empty() || nonce.len() > MAX_ATTESTED_KEY_NONCE_LEN {
+        return Err(CliError::new(format!(
+            "Nonce must be 1 to {} bytes, got {}",
+            MAX_ATTESTED_KEY_NONCE_LEN,
+            nonce.len()
+        )));
+    }
+
+    let mut client = connect(server).await?;
+    let request = Request::new(GetAttestedAppKeyRequest {
+        app_id: app_id.clone(),
+        nonce: nonce.clone(),
+    });
+    let result = client.get_attested_app_key(request).await?.into_inner();
+    if !result.success {
+        return Err(CliError::new(result.message));
+    }
+
+    if let Some(path) = &save_evidence {
+        std::fs::write(path, &result.evidence)?;
+    }
+    let mut output = AttestedKeyOutput::new(app_id, &nonce, &result);
+    output.evidence_file = save_evidence;
+    Ok(output)
+}
+
+#[derive(Serialize)]
+struct AppSecretKeyOutput {
+    app_id: String,
+    private_key: String,
+    public_key: String,
+    /// EIP-55 checksummed, empty without an address
+    evm_address: String,
+}
+
+impl AppSecretKeyOutput {
+    fn new(app_id: String, response: GetAppSecretKeyResponse) -> Self {
+        Self {
+            app_id,
+            private_key: format!("0x{}", hex::encode(&response.private_key)),
+            public_key: format!("0x{}", hex::encode(&response.public_key)),
+            evm_address: if response.eth_address.is_empty() {
+                String::new()
+            } else {
+                checksum_address(&response.eth_address)
+            },
+        }
+    }
+}
+
+impl Report for AppSecretKeyOutput {
+    fn print_text(&self) {
+        println!("╔════════════════════════════════════════════════════════════╗");
+        println!("║           APPLICATION SECRET KEY (SENSITIVE!)              ║");
+        println!("╠════════════════════════════════════════════════════════════╣");
+        println!("║ WARNING: This is highly sensitive information!            ║");
+        println!("║ Keep this private key secure and never share it.          ║");
+        println!("╚════════════════════════════════════════════════════════════╝");
+        println!();
+        println!("  App ID: {}", self.app_id);
+        println!("  Private Key (hex): {}", self.private_key);
+        println!("  Public Key (hex):  {}", self.public_key);
+
+        if !self.evm_address.is_empty() {
+            println!("  Ethereum Address:  {}", self.evm_address);
+        }
+
+        println!();
+        println!("💡 You can use this private key with:");
+        println!("   tapp-cli sign-message --private-key <KEY> --message <MSG>");
+    }
+}
+
+async fn get_app_secret_key(
+    server: &str,
+    app_id: String,
+    deployer_private_key_hex: String,
+) -> Result<AppSecretKeyOutput, CliError> {
+    let mut client = connect(server).await?;
+
+    // Prefer a server-issued challenge; older servers without GetNonce get a
+    // client-generated nonce and the current timestamp instead
+    let challenge = match client.get_nonce(Request::new(GetNonceRequest {})).await {
+        Ok(resp) => Some(resp.into_inner().nonce),
+        Err(e) if e.code() == tonic::Code::Unimplemented => None,
+        Err(e) => return Err(e.into()),
+    };
+
+    let (nonce, timestamp, signature) = sign_deployer_request(
+        &app_id,
+        "get_secret_key",
+        b"",
+        false,
+        &deployer_private_key_hex,
+        challenge,
+    )?;
+
+    let request = Request::new(GetAppSecretKeyRequest {
+        app_id: app_id.clone(),
+        nonce: nonce.clone(),
+        timestamp,
+        signature,
+    });
+
+    // Server will validate signature and that the connection is local
+    let response = match client.get_app_secret_key(request).await {
+        Ok(resp) => resp,
+        Err(e) if e.code() == tonic::Code::PermissionDenied => {
+            let explanation = if e.message().contains("Nonce") {
+                vec![
+                    "║ Nonce verification failed (replay attack detected)        ║".to_string(),
+                    "║                                                            ║".to_string(),
+                    "║ This could mean:                                           ║".to_string(),
+                    "║ - The request was replayed                                 ║".to_string(),
+                    "║ - The timestamp is outside the validity window             ║".to_string(),
+                    "║ - The nonce was already used                               ║".to_string(),
+                ]
+            } else if e.message().contains("signature") {
+                vec![
+                    "║ Signature verification failed!                             ║".to_string(),
+                    "║                                                            ║".to_string(),
+                    "║ This means:                                                ║".to_string(),
+                    "║ - The deployer private key is incorrect                    ║".to_string(),
+                    "║ - You are not the deployer of this application             ║".to_string(),
+                    "║                                                            ║".to_string(),
+                    "║ Only the application deployer can access the private key.  ║".to_string(),
+                ]
+            } else {
+                vec![
+                    "║ GetAppSecretKey is only served to callers on the TAPP      ║".to_string(),
+                    "║ host, preferably over the server's unix socket:            ║".to_string(),
+                    "║                                                            ║".to_string(),
+                    "║   tapp-cli --server unix:///run/tapp/tapp.sock ...         ║".to_string(),
+                    "║                                                            ║".to_string(),
+                    format!("║ Server: {:<51} ║", server),
+                    "║                                                            ║".to_string(),
+                    "║ (localhost and same-host Docker containers are also        ║".to_string(),
+                    "║ accepted). Private keys will NEVER be sent over the        ║".to_string(),
+                    "║ network.                                                   ║".to_string(),
+                ]
+            };
+            let server_says = format!("║ Server says: {:<43} ║", e.message());
+
+            let mut error = CliError::from(e)
+                .with_detail("╔════════════════════════════════════════════════════════════╗")
+                .with_detail("║              SECURITY RESTRICTION                          ║")
+                .with_detail("╠════════════════════════════════════════════════════════════╣");
+            for line in explanation {
+                error = error.with_detail(line);
+            }
+            return Err(error
+                .with_detail("║                                                            ║")
+                .with_detail(server_says)
+                .with_detail("╚════════════════════════════════════════════════════════════╝"));
+        }
+        Err(e) if e.code() == tonic::Code::NotFound => {
+            let shown = if app_id.len() <= 30 {
+                &app_id
+            } else {
+                &app_id[..30]
+            };
+            return Err(CliError::from(e)
+                .with_detail("╔════════════════════════════════════════════════════════════╗")
+                .with_detail("║              APPLICATION NOT FOUND                         ║")
+                .with_detail("╠════════════════════════════════════════════════════════════╣")
+                .with_detail(format!(
+                    "║ The application '{}' was not found.{:<16} ║",
+                    shown, ""
+                ))
+                .with_detail("║                                                            ║")
+                .with_detail("║ Make sure the application has been deployed using          ║")
+                .with_detail("║ the StartApp interface.                                    ║")
+                .with_detail("╚════════════════════════════════════════════════════════════╝"));
+        }
+        Err(e) => return Err(e.into()),
+    };
+
+    let result = response.into_inner();
+    if !result.success {
+        return Err(CliError::new(result.message));
+    }
+
+    Ok(AppSecretKeyOutput::new(app_id, result))
+}
+
+#[derive(Serialize)]
+struct AppInfoOutput {
+    app_id: String,
+    running: bool,
+    deployed_at: i64,
+    current_started_at: i64,
+    uptime_seconds: i64,
+    restart_count: i64,
+    /// Platforms of the services that set one
+    platforms: BTreeMap<String, String>,
+    mount_files: Vec<MountFileOutput>,
+    events: Vec<AppEventOutput>,
+    compose_content: String,
+    volumes_content: String,
+    #[serde(skip)]
+    compose_out: Option<PathBuf>,
+    #[serde(skip)]
+    volumes_out: Option<PathBuf>,
+}
+
+#[derive(Serialize)]
+struct MountFileOutput {
+    source_path: String,
+    size: usize,
+}
+
+#[derive(Serialize)]
+struct AppEventOutput {
+    event: String,
+    detail: String,
+    timestamp: i64,
+}
+
+impl AppInfoOutput {
+    fn new(response: GetAppInfoResponse) -> Self {
+        Self {
+            running: response.running,
+            deployed_at: response.deployed_at,
+            current_started_at: response.current_started_at,
+            uptime_seconds: response.uptime_seconds,
+            restart_count: response.restart_count,
+            platforms: response.platforms.into_iter().collect(),
+            mount_files: split_mount_files(&response.volumes_content)
+                .into_iter()
+                .map(|(source_path, content)| MountFileOutput {
+                    source_path: source_path.to_string(),
+                    size: content.len(),
+                })
+                .collect(),
+            events: response
+                .events
+                .into_iter()
+                .map(|event| AppEventOutput {
+                    event: event.event,
+                    detail: event.detail,
+                    timestamp: event.timestamp,
+                })
+                .collect(),
+            app_id: response.app_id,
+            compose_content: response.compose_content,
+            volumes_content: response.volumes_content,
+            compose_out: None,
+            volumes_out: None,
+        }
+    }
+}
+
+impl Report for AppInfoOutput {
+    fn print_text(&self) {
+        println!("App ID: {}", self.app_id);
+        println!("  Running: {}", self.running);
+        println!("  Deployed at: {}", self.deployed_at);
+        if self.current_started_at > 0 {
+            println!("  Started at: {}", self.current_started_at);
+            println!("  Uptime: {}s", self.uptime_seconds);
+        }
+        println!("  Restart count: {}", self.restart_count);
+        for (service, platform) in &self.platforms {
+            println!("  Platform of {}: {}", service, platform);
+        }
+
+        println!("\nMount files ({}):", self.mount_files.len());
+        for file in &self.mount_files {
+            println!("  {} ({} bytes)", file.source_path, file.size);
+        }
+        if let Some(dir) = &self.volumes_out {
+            println!("  Written to: {}", dir.display());
+        }
+
+        println!("\nEvents ({}):", self.events.len());
+        for event in &self.events {
+            println!("  {} {}", event.timestamp, event.event);
+        }
+
+        match &self.compose_out {
+            Some(path) => println!("\nCompose content written to: {}", path.display()),
+            None => println!("\nCompose content:\n{}", self.compose_content),
+        }
+    }
+}
+
+async fn app_info(
+    server: &str,
+    app_id: String,
+    compose_out: Option<PathBuf>,
+    volumes_out: Option<PathBuf>,
+) -> Result<AppInfoOutput, CliError> {
+    let mut client = connect(server).await?;
+
+    let request = Request::new(GetAppInfoRequest {
+        app_id: app_id.clone(),
+    });
+    let result = match client.get_app_info(request).await {
+        Ok(response) => response.into_inner(),
+        Err(e) if e.code() == tonic::Code::NotFound => {
+            let mut error = CliError::from(e);
+            error.message = format!("App '{}' is not deployed", app_id);
+            return Err(error);
+        }
+        Err(e) => return Err(e.into()),
+    };
+    if !result.success {
+        return Err(CliError::new(result.message));
+    }
+
+    if let Some(path) = &compose_out {
+        std::fs::write(path, &result.compose_content)?;
+    }
+    if let Some(dir) = &volumes_out {
+        for (source_path, content) in split_mount_files(&result.volumes_content) {
+            // Source paths come from the server; never write outside `dir`
+            let relative = Path::new(source_path.trim_start_matches('/'));
+            if relative
+                .components()
+                .any(|c| !matches!(c, Component::Normal(_) | Component::CurDir))
+            {
+                return Err(CliError::new(format!(
+                    "Refusing to write mount file {}",
+                    source_path
+                )));
+            }
+            let path = dir.join(relative);
+            if let Some(parent) = path.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            std::fs::write(&path, content)?;
+        }
+    }
+
+    Ok(AppInfoOutput {
+        compose_out,
+        volumes_out,
+        ..AppInfoOutput::new(result)
+    })
+}
+
+#[derive(Serialize)]
+struct AppLogsOutput {
+    app_id: String,
+    /// With --deploy: task the output belongs to
+    task_id: Option<String>,
+    total_lines: i32,
+    content: String,
+}
+
+impl Report for AppLogsOutput {
+    /// Logs go to stdout untouched so they can be piped; anything else to stderr
+    fn print_text(&self) {
+        if let Some(task_id) = &self.task_id {
+            eprintln!("Deployment output of task {}", task_id);
+        }
+        print!("{}", self.content);
+    }
+}
+
+async fn app_logs(
+    server: &str,
+    app_id: String,
+    service: Option<String>,
+    lines: i32,
+    deploy: bool,
+    task_id: Option<String>,
+) -> Result<AppLogsOutput, CliError> {
+    let mut client = connect(server).await?;
+
+    let request = Request::new(GetAppLogsRequest {
+        app_id: app_id.clone(),
+        lines,
+        service_name: service.unwrap_or_default(),
+        source: if deploy { "deploy" } else { "containers" }.to_string(),
+        task_id: task_id.unwrap_or_default(),
+    });
+    let result = client.get_app_logs(request).await?.into_inner();
+    if !result.success {
+        return Err(CliError::new(result.message));
+    }
+
+    Ok(AppLogsOutput {
+        app_id,
+        task_id: (!result.task_id.is_empty()).then_some(result.task_id),
+        total_lines: result.total_lines,
+        content: result.content,
+    })
+}
+
+#[derive(Serialize)]
+struct SignMessageOutput {
+    message: String,
+    scheme: SignatureScheme,
+    signature: String,
+    signature_base64: String,
+    /// Signer's Ethereum address
+    address: String,
+}
+
+impl Report for SignMessageOutput {
+    fn print_text(&self) {
+        println!("✓ Message signed successfully");
+        println!("  Message: {}", self.message);
+        println!("  Signature (hex): {}", self.signature);
+        println!("  Signature (base64): {}", self.signature_base64);
+        println!("  Signer Address: {}", self.address);
+        if self.scheme == SignatureScheme::Raw {
+            println!(
+                "  Note: raw signatures are not accepted by Ethereum tools; use --scheme eip191"
+            );
+        }
+    }
+}
+
+fn sign_message(
+    private_key_hex: String,
+    message: String,
+    scheme: SignatureScheme,
+) -> Result<SignMessageOutput, CliError> {
+    let private_key = decode_hex_key("Private key", &private_key_hex, 32)?;
+
+    let signed = scheme.signed_message(&message);
+    let signature = match scheme {
+        SignatureScheme::Raw => tapp_service::app_key::sign_message(&private_key, &signed)?,
+        SignatureScheme::Keccak | SignatureScheme::Eip191 => {
+            tapp_service::app_key::sign_message_recoverable(&private_key, &signed)?
+        }
+    };
+    let public_key = public_key_from_private(&private_key)?;
+
+    Ok(SignMessageOutput {
+        message,
+        scheme,
+        signature: format!("0x{}", hex::encode(&signature)),
+        signature_base64: BASE64.encode(&signature),
+        address: checksum_address(&eth_address(&public_key)),
+    })
+}
+
+#[derive(Serialize)]
+struct VerifySignatureOutput {
+    message: String,
+    scheme: SignatureScheme,
+    public_key: Option<String>,
+    /// Signer recovered from a keccak or eip191 signature
+    recovered_address: Option<String>,
+    /// Whether the signature is by `public_key`; unknown without one
+    valid: Option<bool>,
+}
+
+impl Report for VerifySignatureOutput {
+    fn print_text(&self) {
+        match self.valid {
+            Some(true) => println!("✓ Signature is VALID"),
+            Some(false) => println!("✗ Signature is INVALID"),
+            None => println!("Signature recovered; compare the address with get-app-key"),
+        }
+        println!("  Message: {}", self.message);
+        if let Some(public_key) = &self.public_key {
+            println!("  Public Key: {}", public_key);
+        }
+        if let Some(address) = &self.recovered_address {
+            println!("  Recovered Address: {}", address);
+        }
+    }
+
+    fn exit_code(&self) -> i32 {
+        if self.valid == Some(false) {
+            1
+        } else {
+            0
+        }
+    }
+}
+
+fn verify_signature(
+    public_key_hex: Option<String>,
+    message: String,
+    signature_hex: String,
+    scheme: SignatureScheme,
+) -> Result<VerifySignatureOutput, CliError> {
+    let public_key = public_key_hex
+        .map(|hex| decode_hex_key("Public key", &hex, 64))
+        .transpose()?;
+    let signature = hex::decode(strip_hex_prefix(&signature_hex))?;
+    let signed = scheme.signed_message(&message);
+
+    let (valid, recovered_address) = match (scheme, &public_key) {
+        (SignatureScheme::Raw, None) => {
+            return Err(CliError::new(
+                "--public-key is required with --scheme raw; raw signatures do not identify their signer",
+            ))
+        }
+        (SignatureScheme::Raw, Some(public_key)) => (
+            Some(tapp_service::app_key::verify_signature(
+                public_key, &signed, &signature,
+            )?),
+            None,
+        ),
+        (SignatureScheme::Keccak | SignatureScheme::Eip191, _) => {
+            let recovered = tapp_service::app_key::recover_public_key(&signed, &signature)?;
+            (
+                public_key.as_ref().map(|key| *key == recovered),
+                Some(checksum_address(&eth_address(&recovered))),
+            )
+        }
+    };
+
+    Ok(VerifySignatureOutput {
+        message,
+        scheme,
+        public_key: public_key.map(|key| format!("0x{}", hex::encode(key))),
+        recovered_address,
+        valid,
+    })
+}
+
+#[derive(Serialize)]
+struct ProfileOutput {
+    name: String,
+    /// Selected with --profile or TAPP_PROFILE
+    selected: bool,
+    server: Option<String>,
+    tls: bool,
+    ca_cert: Option<PathBuf>,
+    domain: Option<String>,
+    client_cert: Option<PathBuf>,
+    client_key: Option<PathBuf>,
+    /// Where the API key comes from; the key itself is never shown
+    api_key: Option<String>,
+    deployer_key_file: Option<PathBuf>,
+    /// Address of the deployer key, if the file holds a valid one
+    deployer_address: Option<String>,
+}
+
+impl ProfileOutput {
+    fn new(name: &str, profile: &Profile, selected: bool) -> Self {
+        let api_key = match (&profile.api_key_env, &profile.api_key_file) {
+            (Some(var), _) => Some(format!(
+                "env {} ({})",
+                var,
+                if std::env::var_os(var).is_some() {
+                    "set"
+                } else {
+                    "not set"
+                }
+            )),
+            (None, Some(path)) => Some(format!(
+                "file {} ({})",
+                path.display(),
+                if path.is_file() { "present" } else { "missing" }
+            )),
+            (None, None) => None,
+        };
+        let deployer_address = profile.deployer_key(name).ok().flatten().and_then(|key| {
+            let key = hex::decode(strip_hex_prefix(&key)).ok()?;
+            let public_key = public_key_from_private(&key).ok()?;
+            Some(checksum_address(&eth_address(&public_key)))
+        });
+
+        Self {
+            name: name.to_string(),
+            selected,
+            server: profile.server.clone(),
+            tls: profile.tls,
+            ca_cert: profile.ca_cert.clone(),
+            domain: profile.domain.clone(),
+            client_cert: profile.client_cert.clone(),
+            client_key: profile.client_key.clone(),
+            api_key,
+            deployer_key_file: profile.deployer_key_file.clone(),
+            deployer_address,
+        }
+    }
+}
+
+impl Report for ProfileOutput {
+    fn print_text(&self) {
+        if self.selected {
+            println!("Profile: {} (selected)", self.name);
+        } else {
+            println!("Profile: {}", self.name);
+        }
+        println!(
+            "  Server: {}",
+            self.server.as_deref().unwrap_or(DEFAULT_SERVER)
+        );
+        if self.tls {
+            println!("  TLS: yes");
+        }
+        let paths = [
+            ("CA Certificate", &self.ca_cert),
+            ("Client Certificate", &self.client_cert),
+            ("Client Key", &self.client_key),
+        ];
+        for (label, path) in paths {
+            if let Some(path) = path {
+                println!("  {}: {}", label, path.display());
+            }
+        }
+        if let Some(domain) = &self.domain {
+            println!("  Domain: {}", domain);
+        }
+        if let Some(api_key) = &self.api_key {
+            println!("  API Key: {}", api_key);
+        }
+        if let Some(path) = &self.deployer_key_file {
+            match &self.deployer_address {
+                Some(address) => println!("  Deployer Key: {} ({})", path.display(), address),
+                None => println!("  Deployer Key: {} (unreadable)", path.display()),
+            }
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct ProfileListOutput {
+    path: PathBuf,
+    profiles: Vec<ProfileOutput>,
+}
+
+impl Report for ProfileListOutput {
+    fn print_text(&self) {
+        println!("Profiles in {}:", self.path.display());
+        if self.profiles.is_empty() {
+            println!("  (none)");
+        }
+        for profile in &self.profiles {
+            println!(
+                "{} {:<16} {}",
+                if profile.selected { "*" } else { " " },
+                profile.name,
+                profile.server.as_deref().unwrap_or(DEFAULT_SERVER)
+            );
+        }
+    }
+}
+
+fn list_profiles(
+    path: PathBuf,
+    profiles: &ProfilesFile,
+    selected: Option<&str>,
+) -> ProfileListOutput {
+    ProfileListOutput {
+        path,
+        profiles: profiles
+            .profiles
+            .iter()
+            .map(|(name, profile)| {
+                ProfileOutput::new(name, profile, Some(name.as_str()) == selected)
+            })
+            .collect(),
+    }
+}
+
+fn show_profile(
+    path: &Path,
+    profiles: &ProfilesFile,
+    name: Option<String>,
+) -> Result<ProfileOutput, CliError> {
+    let Some(name) = name else {
+        return Err(CliError::new(
+            "No profile selected; name one, or pass --profile or set TAPP_PROFILE",
+        ));
+    };
+    let profile = profiles.get(&name, path)?;
+    Ok(ProfileOutput::new(&name, profile, true))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use tapp_service::auth_layer::ApiKeyLayer;
+    use tapp_service::boot::TaskManager;
+    use tapp_service::config::{ApiKeyConfig, ApiKeyEntry};
+    use tapp_service::proto::{
+        AppEventInfo, GetAppLogsResponse, GetServiceStatusRequest, TaskResult,
+    };
+    use tapp_service::{TappConfig, TappServiceImpl, TappServiceServer};
+    use tokio_stream::wrappers::{TcpListenerStream, UnixListenerStream};
+    use tonic::transport::{Server, ServerTlsConfig};
+
+    const SNAPSHOT: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/src/snapshots/cli_output.txt");
+
+    #[tokio::test]
+    async fn test_api_key_is_sent_with_every_request() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = TappConfig::default();
+        config.boot.aa_config_path = Some(dir.path().join("aa.toml").to_string_lossy().to_string());
+        config.boot.data_dir = dir.path().join("apps");
+        let service = Arc::new(TappServiceImpl::new(config).await.unwrap());
+
+        // Every method requires a key
+        let layer = ApiKeyLayer::new(Some(ApiKeyConfig {
+            enabled: true,
+            keys: vec![ApiKeyEntry::Inline("cli-test-key".to_string())],
+            ..Default::default()
+        }));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(
+            Server::builder()
+                .layer(layer)
+                .add_service(TappServiceServer::from_arc(service))
+                .serve_with_incoming(TcpListenerStream::new(listener)),
+        );
+
+        let channel = Channel::from_shared(format!("http://{}", addr))
+            .unwrap()
+            .connect()
+            .await
+            .unwrap();
+        let status = |api_key: Option<&'static str>| {
+            let channel = channel.clone();
+            async move {
+                client(channel, api_key)
+                    .unwrap()
+                    .get_service_status(GetServiceStatusRequest::default())
+                    .await
+            }
+        };
+
+        assert!(
+            status(Some("cli-test-key"))
+                .await
+                .unwrap()
+                .into_inner()
+                .success
+        );
+        assert_eq!(
+            status(None).await.unwrap_err().code(),
+            tonic::Code::Unauthenticated
+        );
+        assert_eq!(
+            status(Some("wrong-key")).await.unwrap_err().code(),
+            tonic::Code::PermissionDenied
+        );
+
+        assert!(client(channel.clone(), Some("bad\nkey")).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_compressed_app_logs() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = TappConfig::default();
+        config.boot.aa_config_path = Some(dir.path().join("aa.toml").to_string_lossy().to_string());
+        config.boot.data_dir = dir.path().join("apps");
+
+        // A 3 MB deploy log, under the client's 4 MB decoding limit
+        let app_dir = config.boot.data_dir.join("chatty-app");
+        std::fs::create_dir_all(&app_dir).unwrap();
+        let log: String = (0..40_000)
+            .map(|i| format!("#{} [web 3/7] RUN npm ci --omit=dev  {:>8}ms\n", i, i * 7))
+            .collect();
+        assert!(log.len() > 2 * 1024 * 1024);
+        std::fs::write(
+            tapp_service::boot::deploy_log::log_path(&app_dir, "task-1"),
+            &log,
+        )
+        .unwrap();
+
+        // Compression goes through the API key layer like any other call
+        let serve = |compression: Vec<String>| {
+            let mut config = config.clone();
+            config.server.compression = compression;
+            async move {
+                let encodings = config.server.compression_encodings();
+                let service = Arc::new(TappServiceImpl::new(config).await.unwrap());
+                let mut server = TappServiceServer::from_arc(service);
+                for encoding in encodings {
+                    server = server.accept_compressed(encoding).send_compressed(encoding);
+                }
+                let layer = ApiKeyLayer::new(Some(ApiKeyConfig {
+                    enabled: true,
+                    keys: vec![ApiKeyEntry::Inline("cli-test-key".to_string())],
+                    ..Default::default()
+                }));
+                let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+                let addr = listener.local_addr().unwrap();
+                tokio::spawn(
+                    Server::builder()
+                        .layer(layer)
+                        .add_service(server)
+                        .serve_with_incoming(TcpListenerStream::new(listener)),
+                );
+                Channel::from_shared(format!("http://{}", addr))
+                    .unwrap()
+                    .connect()
+                    .await
+                    .unwrap()
+            }
+        };
+        let logs = |channel: Channel, encoding: Option<CompressionEncoding>| async move {
+            let mut client = client(channel, Some("cli-test-key")).unwrap();
+            if let Some(encoding) = encoding {
+                client = compressed(client, encoding);
+            }
+            client
+                .get_app_logs(GetAppLogsRequest {
+                    app_id: "chatty-app".to_string(),
+                    source: "deploy".to_string(),
+                    ..Default::default()
+                })
+                .await
+                .map(|response| response.into_inner().content)
+        };
+
+        let channel = serve(vec!["gzip".to_string(), "zstd".to_string()]).await;
+        for encoding in [
+            None,
+            Some(CompressionEncoding::Gzip),
+            Some(CompressionEncoding::Zstd),
+        ] {
+            assert_eq!(logs(channel.clone(), encoding).await.unwrap(), log);
+        }
+
+        // A server without the codec refuses compressed requests
+        let channel = serve(Vec::new()).await;
+        assert_eq!(logs(channel.clone(), None).await.unwrap(), log);
+        let status = logs(channel, Some(CompressionEncoding::Gzip))
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), tonic::Code::Unimplemented);
+    }
+
+    /// A fresh CA and a "localhost" certificate it signed: (CA, cert, key) PEM
+    fn test_pki() -> (String, String, String) {
+        use rcgen::{BasicConstraints, CertificateParams, IsCa, KeyPair};
+
+        let ca_key = KeyPair::generate().unwrap();
+        let mut ca_params = CertificateParams::new(Vec::new()).unwrap();
+        ca_params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+        let ca = ca_params.self_signed(&ca_key).unwrap();
+
+        let key = KeyPair::generate().unwrap();
+        let cert = CertificateParams::new(vec!["localhost".to_string()])
+            .unwrap()
+            .signed_by(&key, &ca, &ca_key)
+            .unwrap();
+        (ca.pem(), cert.pem(), key.serialize_pem())
+    }
+
+    #[tokio::test]
+    async fn test_tls_connection() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = TappConfig::default();
+        config.boot.aa_config_path = Some(dir.path().join("aa.toml").to_string_lossy().to_string());
+        config.boot.data_dir = dir.path().join("apps");
+        let service = Arc::new(TappServiceImpl::new(config).await.unwrap());
+
+        let (ca_pem, cert_pem, key_pem) = test_pki();
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let server = format!("https://{}", listener.local_addr().unwrap());
+        tokio::spawn(
+            Server::builder()
+                .tls_config(
+                    ServerTlsConfig::new().identity(Identity::from_pem(&cert_pem, &key_pem)),
+                )
+                .unwrap()
+                .add_service(TappServiceServer::from_arc(service))
+                .serve_with_incoming(TcpListenerStream::new(listener)),
+        );
+
+        let write = |name: &str, pem: &str| {
+            let path = dir.path().join(name);
+            std::fs::write(&path, pem).unwrap();
+            path
+        };
+        // The certificate names localhost, the address is an IP
+        let tls = |ca_cert: PathBuf| TlsArgs {
+            tls: false,
+            ca_cert: Some(ca_cert),
+            domain: Some("localhost".to_string()),
+            client_cert: None,
+            client_key: None,
+        };
+
+        let config = tls(write("ca.pem", &ca_pem)).client_config().unwrap();
+        let inner = channel(&server, Some(&config)).await.unwrap();
+        let response = client(inner, None)
+            .unwrap()
+            .get_service_status(GetServiceStatusRequest::default())
+            .await
+            .unwrap();
+        assert!(response.into_inner().success);
+
+        // A server signed by another CA is refused
+        let (other_ca_pem, _, _) = test_pki();
+        let config = tls(write("other-ca.pem", &other_ca_pem))
+            .client_config()
+            .unwrap();
+        assert!(channel(&server, Some(&config)).await.is_err());
+
+        // Bad PEM files fail before connecting, naming the option
+        let err = tls(dir.path().join("missing.pem"))
+            .client_config()
+            .unwrap_err();
+        assert!(
+            err.message.starts_with("--ca-cert ") && err.message.contains("could not be read"),
+            "{}",
+            err.message
+        );
+        let err = tls(write("key.pem", &key_pem)).client_config().unwrap_err();
+        assert!(
+            err.message
+                .ends_with("is not a PEM file with a CERTIFICATE block"),
+            "{}",
+            err.message
+        );
+
+        // Plain http stays plain unless TLS is asked for
+        let plain = TlsArgs {
+            tls: false,
+            ca_cert: None,
+            domain: None,
+            client_cert: None,
+            client_key: None,
+        };
+        assert!(!plain.enabled("http://127.0.0.1:50051"));
+        assert!(plain.enabled("https://127.0.0.1:50051"));
+        assert!(tls(PathBuf::from("ca.pem")).enabled("http://127.0.0.1:50051"));
+    }
+
+    #[tokio::test]
+    async fn test_unix_socket_connection() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = TappConfig::default();
+        config.boot.aa_config_path = Some(dir.path().join("aa.toml").to_string_lossy().to_string());
+        config.boot.data_dir = dir.path().join("apps");
+        let service = Arc::new(TappServiceImpl::new(config).await.unwrap());
+
+        let path = dir.path().join("tapp.sock");
+        let listener = tokio::net::UnixListener::bind(&path).unwrap();
+        tokio::spawn(
+            Server::builder()
+                .add_service(TappServiceServer::from_arc(service))
+                .serve_with_incoming(UnixListenerStream::new(listener)),
+        );
+
+        let server = format!("unix://{}", path.display());
+        let response = connect(&server)
+            .await
+            .unwrap()
+            .get_service_status(GetServiceStatusRequest::default())
+            .await
+            .unwrap();
+        assert!(response.into_inner().success);
+
+        let err = channel(
+            &format!("unix://{}", dir.path().join("missing.sock").display()),
+            None,
+        )
+        .await
+        .unwrap_err();
+        assert!(err.message.contains("No such file"), "{}", err.message);
+
+        let tls = ClientTlsConfig::new();
+        assert!(channel(&server, Some(&tls)).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_encrypt_mount() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = TappConfig::default();
+        config.boot.aa_config_path = Some(dir.path().join("aa.toml").to_string_lossy().to_string());
+        config.boot.data_dir = dir.path().join("apps");
+        let service = Arc::new(TappServiceImpl::new(config).await.unwrap());
+        let app_keys = service.app_key_service.clone();
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let server = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(
+            Server::builder()
+                .add_service(TappServiceServer::from_arc(service))
+                .serve_with_incoming(TcpListenerStream::new(listener)),
+        );
+
+        let file = dir.path().join("secret.env");
+        std::fs::write(&file, "TOKEN=abc\n").unwrap();
+        let output = encrypt_mount(&server, "sealed-app".to_string(), file)
+            .await
+            .unwrap();
+        assert_eq!(output.encrypted_file, dir.path().join("secret.env.enc"));
+        let sealed = std::fs::read(&output.encrypted_file).unwrap();
+        assert_eq!(output.size, sealed.len());
+        assert_eq!(
+            output.sha384,
+            format!("0x{}", tapp_service::utils::sha384_hex(&sealed))
+        );
+
+        // The server's key opens it, for this app only
+        let private_key = app_keys.get_private_key("sealed-app").await.unwrap();
+        assert_eq!(
+            envelope::open(&private_key, "sealed-app", &sealed).unwrap(),
+            b"TOKEN=abc\n"
+        );
+        assert!(envelope::open(&private_key, "other-app", &sealed).is_err());
+
+        // --verify decrypts locally with the same key
+        let private_key = hex::encode(&private_key);
+        let verified = decrypt_mount(
+            "sealed-app".to_string(),
+            output.encrypted_file.clone(),
+            private_key.clone(),
+        )
+        .unwrap();
+        assert_eq!(verified.size, 10);
+        assert_eq!(
+            verified.sha256,
+            format!("0x{}", hex::encode(Sha256::digest(b"TOKEN=abc\n")))
+        );
+        let err = decrypt_mount("other-app".to_string(), output.encrypted_file, private_key)
+            .err()
+            .unwrap();
+        assert!(err.message.contains("Decryption failed"), "{}", err.message);
+    }
+
+    #[tokio::test]
+    async fn test_watch_task() {
+        let dir = tempfile::tempdir().unwrap();
+        let state_dir = dir.path().join("tasks");
+        // A deployment that was running when the service last stopped
+        let task = {
+            let manager = TaskManager::with_retention(std::time::Duration::from_secs(60), 10)
+                .with_store(&state_dir)
+                .unwrap();
+            let task = manager
+                .create_task(
+                    tapp_service::boot::TaskKind::StartApp,
+                    "watched-app",
+                    &[],
+                    None,
+                )
+                .await;
+            manager.mark_running(&task.id).await;
+            task
+        };
+        let mut config = TappConfig::default();
+        config.boot.aa_config_path = Some(dir.path().join("aa.toml").to_string_lossy().to_string());
+        config.boot.data_dir = dir.path().join("apps");
+        config.boot.task_state_dir = state_dir.to_string_lossy().to_string();
+        let service = Arc::new(TappServiceImpl::new(config).await.unwrap());
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let server = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(
+            Server::builder()
+                .add_service(TappServiceServer::from_arc(service))
+                .serve_with_incoming(TcpListenerStream::new(listener)),
+        );
+
+        // The stream ends at the terminal status, which sets the exit code
+        let output = watch_task(&server, task.id.clone(), 1, OutputFormat::Json)
+            .await
+            .unwrap();
+        assert_eq!(output.task_status, TaskStatus::Failed);
+        assert_eq!(output.error_code.as_deref(), Some("INTERRUPTED"));
+        assert_eq!(output.exit_code(), 1);
+
+        // Polling, for servers without WatchTask, ends the same way
+        let mut client = connect(&server).await.unwrap();
+        let polled = poll_task(&mut client, task.id.clone(), 1, OutputFormat::Json)
+            .await
+            .unwrap();
+        assert_eq!(polled.task_status, TaskStatus::Failed);
+        assert_eq!(polled.exit_code(), 1);
+
+        let err = watch_task(&server, "no-such-task".to_string(), 1, OutputFormat::Json)
+            .await
+            .err()
+            .unwrap();
+        assert_eq!(err.code, Some(tonic::Code::NotFound));
+    }
+
+    #[test]
+    fn test_mounts_from_compose() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let write = |name: &str, content: &str, mode: u32| {
+            let path = dir.path().join(name);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(&path, content).unwrap();
+            std::fs::set_permissions(&path, std::fs::Permissions::from_mode(mode)).unwrap();
+        };
+        write("nginx.conf", "worker_processes 1;\n", 0o600);
+        write("config/app.env", "A=1\n", 0o644);
+        write("config/tls/cert.pem", "cert", 0o640);
+
+        let compose = r#"
+services:
+  web:
+    image: nginx
+    volumes:
+      - ./nginx.conf:/etc/nginx/nginx.conf:ro
+      - data:/var/lib/data
+      - /var/run/docker.sock:/var/run/docker.sock
+      - type: bind
+        source: ./config/
+        target: /config
+  worker:
+    image: busybox
+    volumes:
+      - ./nginx.conf:/nginx.conf
+      - type: volume
+        source: cache
+        target: /cache
+volumes:
+  data:
+  cache:
+"#;
+        // Named volumes and absolute paths are skipped, duplicates merged
+        assert_eq!(
+            compose_bind_sources(compose).unwrap(),
+            vec!["./nginx.conf", "./config/"]
+        );
+
+        let mounts = mounts_from_compose_file(compose, dir.path()).unwrap();
+        let files: Vec<_> = mounts
+            .iter()
+            .map(|file| (file.source_path.as_str(), file.mode.as_str()))
+            .collect();
+        assert_eq!(
+            files,
+            vec![
+                ("./nginx.conf", "0600"),
+                ("./config/app.env", "0644"),
+                ("./config/tls/cert.pem", "0640"),
+            ]
+        );
+        assert_eq!(mounts[0].content, b"worker_processes 1;\n");
+
+        // A bound file that is missing locally is an error
+        std::fs::remove_file(dir.path().join("nginx.conf")).unwrap();
+        let err = mounts_from_compose_file(compose, dir.path()).unwrap_err();
+        assert!(
+            err.message
+                .starts_with("The compose file binds ./nginx.conf, but "),
+            "{}",
+            err.message
+        );
+    }
+
+    /// The synthetic TDX quote of the quote parser's tests, saved as hex the
+    /// way get-evidence prints it
+    fn quote_file(dir: &Path) -> PathBuf {
+        let fixture = concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/src/boot/testdata/tdx_quote_v4.hex"
+        );
+        let hex: String = std::fs::read_to_string(fixture)
+            .unwrap()
+            .lines()
+            .filter(|line| !line.starts_with('#'))
+            .collect();
+        let path = dir.join("evidence.hex");
+        std::fs::write(&path, format!("0x{}\n", hex)).unwrap();
+        path
+    }
+
+    #[test]
+    fn test_verify_evidence() {
+        let dir = tempfile::tempdir().unwrap();
+        let hex_file = quote_file(dir.path());
+        let rtmr2 = "22".repeat(48);
+        let report_data = hex::encode("tapp-fixture");
+
+        // Expectations met
+        let output = verify_evidence(
+            hex_file.clone(),
+            Some(report_data.clone()),
+            Some(rtmr2.clone()),
+        )
+        .unwrap();
+        assert_eq!(output.td_report.mr_td, format!("0x{}", "11".repeat(48)));
+        assert_eq!(output.checks.len(), 2);
+        assert_eq!(output.exit_code(), 0);
+
+        // The raw quote and the attester's JSON give the same result
+        let quote = read_evidence(&hex_file).unwrap();
+        let raw = dir.path().join("quote.bin");
+        std::fs::write(&raw, &quote).unwrap();
+        let json = dir.path().join("evidence.json");
+        let evidence = serde_json::json!({ "quote": BASE64.encode(&quote) });
+        std::fs::write(&json, evidence.to_string()).unwrap();
+        for path in [raw, json] {
+            let output = verify_evidence(path, Some(report_data.clone()), None).unwrap();
+            assert_eq!(output.exit_code(), 0);
+        }
+
+        // A mismatch exits 1
+        let output =
+            verify_evidence(hex_file.clone(), Some(hex::encode("other")), Some(rtmr2)).unwrap();
+        assert!(!output.checks[0].matches);
+        assert!(output.checks[1].matches);
+        assert_eq!(output.exit_code(), 1);
+
+        let err = verify_evidence(hex_file, None, Some("22".to_string()))
+            .err()
+            .unwrap();
+        assert!(err.message.starts_with("Expected RTMR2 must be 48 bytes"));
+    }
+
+    #[test]
+    fn test_attested_key_binding() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut quote = read_evidence(&quote_file(dir.path())).unwrap();
+        let private_key = [0x42; 32];
+        let public_key = public_key_from_private(&private_key).unwrap();
+        let nonce = vec![0x5a; 32];
+        let compose_hash = vec![0xc0; 48];
+
+        // Fixture response: the parser's quote with the binding as report data
+        // (header, then 520 bytes of TD report before report_data)
+        let report_data = attested_key_report_data(&nonce, &public_key, &compose_hash);
+        let offset = 48 + 520;
+        assert!(quote[offset..].starts_with(b"tapp-fixture"));
+        quote[offset..offset + 64].copy_from_slice(&report_data);
+        let response = GetAttestedAppKeyResponse {
+            success: true,
+            eth_address: eth_address(&public_key),
+            public_key,
+            compose_hash,
+            nonce: nonce.clone(),
+            evidence: serde_json::json!({ "quote": BASE64.encode(&quote) })
+                .to_string()
+                .into_bytes(),
+            tee_type: "Tdx".to_string(),
+            ..Default::default()
+        };
+
+        let output = AttestedKeyOutput::new("demo".to_string(), &nonce, &response);
+        assert!(output.verified, "{:?}", output.reason);
+        assert_eq!(output.exit_code(), 0);
+        assert_eq!(
+            output.expected_report_data,
+            format!("0x{}", hex::encode(&report_data))
+        );
+        assert_eq!(output.compose_hash, format!("0x{}", "c0".repeat(48)));
+
+        // Anything the quote does not bind fails the check
+        let not_verified = |response: GetAttestedAppKeyResponse, nonce: &[u8]| {
+            let output = AttestedKeyOutput::new("demo".to_string(), nonce, &response);
+            assert_eq!(output.exit_code(), 1);
+            output.reason.unwrap()
+        };
+        let mut other_compose = response.clone();
+        other_compose.compose_hash[0] ^= 1;
+        assert!(not_verified(other_compose, &nonce).contains("does not match the binding"));
+
+        let mut other_key = response.clone();
+        other_key.public_key = public_key_from_private(&[0x43; 32]).unwrap();
+        other_key.eth_address = eth_address(&other_key.public_key);
+        assert!(not_verified(other_key, &nonce).contains("does not match the binding"));
+
+        let mut other_address = response.clone();
+        other_address.eth_address = vec![0; 20];
+        assert!(not_verified(other_address, &nonce).contains("Ethereum address"));
+
+        assert!(not_verified(response.clone(), &[0x5b; 32]).contains("another nonce"));
+
+        let mut no_quote = response;
+        no_quote.evidence = b"{\"tee\": \"sample\"}".to_vec();
+        assert!(not_verified(no_quote, &nonce).contains("not a TDX quote"));
+    }
+
+    #[test]
+    fn test_signature_schemes() {
+        let private_key = "0x4c0883a69102937d6231471b5dbb6204fe5129617082792ae468d01a3f362318";
+        let address = "0x2c7536E3605D9C16a7a3D7b1898e529396a65c23";
+        // ethers' Wallet.signMessage("Some data") with the key above
+        let ethers_signature = "0xb91467e570a6466aa9e9876cbcd013baba02900b8979d43fe208a4a4f339f5fd\
+                                6007e74cd82e037b800186422fc2da167c747ef045e5d18a5f5d4300f8e1a0291c";
+        let public_key =
+            hex::encode(public_key_from_private(&hex::decode(&private_key[2..]).unwrap()).unwrap());
+        let message = || "Some data".to_string();
+
+        let signed =
+            sign_message(private_key.to_string(), message(), SignatureScheme::Eip191).unwrap();
+        assert_eq!(signed.signature, ethers_signature);
+        assert_eq!(signed.address, address);
+
+        // Without a public key the signer is only recovered
+        let verified = verify_signature(
+            None,
+            message(),
+            ethers_signature.to_string(),
+            SignatureScheme::Eip191,
+        )
+        .unwrap();
+        assert_eq!(verified.recovered_address.as_deref(), Some(address));
+        assert_eq!(verified.valid, None);
+        assert_eq!(verified.exit_code(), 0);
+
+        // The same signature is not a keccak signature of the bare message
+        let verified = verify_signature(
+            Some(public_key.clone()),
+            message(),
+            ethers_signature.to_string(),
+            SignatureScheme::Keccak,
+        );
+        assert!(verified.map_or(true, |output| output.exit_code() == 1));
+
+        for scheme in [
+            SignatureScheme::Raw,
+            SignatureScheme::Keccak,
+            SignatureScheme::Eip191,
+        ] {
+            let signed = sign_message(private_key.to_string(), message(), scheme).unwrap();
+            let verified = verify_signature(
+                Some(public_key.clone()),
+                message(),
+                signed.signature,
+                scheme,
+            )
+            .unwrap();
+            assert_eq!(verified.valid, Some(true));
+        }
+
+        let err = verify_signature(
+            None,
+            message(),
+            ethers_signature.to_string(),
+            SignatureScheme::Raw,
+        )
+        .err()
+        .unwrap();
+        assert!(err.message.starts_with("--public-key is required"));
+    }
+
+    #[test]
+    fn test_deployer_request_signatures() {
+        let private_key = "0x4c0883a69102937d6231471b5dbb6204fe5129617082792ae468d01a3f362318";
+        let public_key = public_key_from_private(&hex::decode(&private_key[2..]).unwrap()).unwrap();
+        let by_key = DeployerIdentity::from_bytes(&public_key).unwrap();
+        let by_address = DeployerIdentity::from_bytes(&by_key.address).unwrap();
+        // get-app-secret-key's v2 signature is accepted with or without the key
+        let (nonce, timestamp, signature) =
+            sign_deployer_request("app", "get_secret_key", b"", false, private_key, None).unwrap();
+        let v2 = deployer::request_message_v2("app", &nonce, timestamp, "get_secret_key", b"");
+        assert_eq!(signature[0], deployer::SIGNATURE_VERSION_2);
+        assert!(by_key.verify_request(None, &v2, &signature).unwrap());
+        assert!(by_address.verify_request(None, &v2, &signature).unwrap());
+
+        // stop-app signs with the legacy 64-byte scheme
+        let challenge = Some("c0ffee".to_string());
+        let (nonce, timestamp, signature) =
+            sign_deployer_request("app", "stop_app", b"stop", true, private_key, challenge)
+                .unwrap();
+        assert_eq!((nonce.as_str(), timestamp), ("c0ffee", 0));
+        let legacy = tapp_service::app_key::deployer_request_message(
+            "stop_app", "app", b"stop", &nonce, timestamp,
+        );
+        let v2 = deployer::request_message_v2("app", &nonce, timestamp, "stop_app", b"stop");
+        assert_eq!(signature.len(), 64);
+        assert!(by_key
+            .verify_request(Some(&legacy), &v2, &signature)
+            .unwrap());
+        assert!(tapp_service::app_key::verify_signature(&public_key, &legacy, &signature).unwrap());
+    }
+
+    #[test]
+    fn test_profiles_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("cli.toml");
+        std::fs::write(
+            &path,
+            r#"
+[profiles.dev]
+server = "http://127.0.0.1:50051"
+
+[profiles.prod]
+server = "https://tapp.example.com:50051"
+tls = true
+ca_cert = "prod-ca.pem"
+domain = "tapp.internal"
+api_key_file = "/run/secrets/tapp-api-key"
+deployer_key_file = "~/keys/prod.key"
+"#,
+        )
+        .unwrap();
+
+        let profiles = ProfilesFile::load(&path).unwrap();
+        assert_eq!(profiles.profiles.len(), 2);
+        let prod = profiles.get("prod", &path).unwrap();
+        assert!(prod.tls);
+        assert_eq!(prod.domain.as_deref(), Some("tapp.internal"));
+        // Relative to the file, absolute, and under the home directory
+        assert_eq!(prod.ca_cert, Some(dir.path().join("prod-ca.pem")));
+        assert_eq!(
+            prod.api_key_file,
+            Some(PathBuf::from("/run/secrets/tapp-api-key"))
+        );
+        assert_eq!(
+            prod.deployer_key_file,
+            Some(dirs::home_dir().unwrap().join("keys/prod.key"))
+        );
+
+        let err = profiles.get("staging", &path).unwrap_err();
+        assert_eq!(
+            err.message,
+            format!(
+                "Profile staging not found in {} (profiles: dev, prod)",
+                path.display()
+            )
+        );
+
+        // Secrets can only be referenced
+        let err =
+            ProfilesFile::parse("[profiles.dev]\napi_key = \"secret\"\n", dir.path()).unwrap_err();
+        assert!(
+            err.message.contains("unknown field `api_key`"),
+            "{}",
+            err.message
+        );
+
+        // Without a file there are no profiles
+        let missing = ProfilesFile::load(&dir.path().join("missing.toml")).unwrap();
+        assert!(missing.profiles.is_empty());
+        assert!(missing.get("dev", &path).is_err());
+    }
+
+    #[test]
+    fn test_profile_precedence() {
+        let dir = tempfile::tempdir().unwrap();
+        let private_key = "0x4c0883a69102937d6231471b5dbb6204fe5129617082792ae468d01a3f362318";
+        std::fs::write(dir.path().join("api.key"), "profile-api-key\n").unwrap();
+        std::fs::write(
+            dir.path().join("deployer.key"),
+            format!("{}\n", private_key),
+        )
+        .unwrap();
+        let profiles = ProfilesFile::parse(
+            r#"
+[profiles.prod]
+server = "https://tapp.example.com:50051"
+ca_cert = "ca.pem"
+api_key_file = "api.key"
+deployer_key_file = "deployer.key"
+"#,
+            dir.path(),
+        )
+        .unwrap();
+        let prod = &profiles.profiles["prod"];
+        let apply = |profile: &Profile, args: &[&str]| {
+            let mut cli = Cli::try_parse_from([&["tapp-cli"][..], args].concat()).unwrap();
+            profile.apply("prod", &mut cli).map(|()| cli)
+        };
+
+        // Options not given come from the profile
+        let cli = apply(prod, &["stop-app", "-a", "app"]).unwrap();
+        assert_eq!(
+            cli.server.as_deref(),
+            Some("https://tapp.example.com:50051")
+        );
+        assert_eq!(cli.tls.ca_cert, Some(dir.path().join("ca.pem")));
+        assert_eq!(cli.api_key.as_deref(), Some("profile-api-key"));
+        let Commands::StopApp { deployer_key, .. } = cli.command else {
+            panic!("not stop-app");
+        };
+        assert_eq!(deployer_key.as_deref(), Some(private_key));
+
+        // Options given explicitly win
+        let cli = apply(
+            prod,
+            &[
+                "--server",
+                "http://127.0.0.1:50051",
+                "--api-key",
+                "flag-key",
+                "--ca-cert",
+                "other-ca.pem",
+                "get-app-secret-key",
+                "-a",
+                "app",
+                "-d",
+                "0x01",
+            ],
+        )
+        .unwrap();
+        assert_eq!(cli.server.as_deref(), Some("http://127.0.0.1:50051"));
+        assert_eq!(cli.tls.ca_cert, Some(PathBuf::from("other-ca.pem")));
+        assert_eq!(cli.api_key.as_deref(), Some("flag-key"));
+        let Commands::GetAppSecretKey {
+            deployer_private_key,
+            ..
+        } = cli.command
+        else {
+            panic!("not get-app-secret-key");
+        };
+        assert_eq!(deployer_private_key.as_deref(), Some("0x01"));
+
+        // A deployer public key is not replaced by the profile's private key
+        let cli = apply(
+            prod,
+            &[
+                "start-app",
+                "-c",
+                "compose.yml",
+                "-a",
+                "app",
+                "--deployer-pubkey",
+                "0xab",
+            ],
+        )
+        .unwrap();
+        let Commands::StartApp { deployer_key, .. } = cli.command else {
+            panic!("not start-app");
+        };
+        assert_eq!(deployer_key, None);
+
+        // Secrets are read only by commands that use them
+        let unset = Profile {
+            api_key_env: Some("TAPP_TEST_PROFILE_UNSET_KEY".to_string()),
+            deployer_key_file: Some(dir.path().join("missing.key")),
+            ..prod.clone()
+        };
+        assert!(apply(&unset, &["sign-message", "-p", "0x01", "-m", "hi"]).is_ok());
+        let err = apply(&unset, &["get-evidence"]).err().unwrap();
+        assert_eq!(
+            err.message,
+            "Profile prod: API key variable TAPP_TEST_PROFILE_UNSET_KEY is not set"
+        );
+        let err = apply(&unset, &["--api-key", "k", "stop-app", "-a", "app"])
+            .err()
+            .unwrap();
+        assert!(
+            err.message.starts_with("Profile prod: deployer key file"),
+            "{}",
+            err.message
+        );
+    }
+
+    #[test]
+    fn test_status_details() {
+        let status: tonic::Status = tapp_service::TappError::InvalidParameter {
+            field: "app_id".to_string(),
+            reason: "cannot be empty".to_string(),
+        }
+        .into();
+        let err = CliError::from(status);
+        assert_eq!(
+            err.details,
+            vec![
+                "  Reason: INVALID_PARAMETER (field=app_id)",
+                "  Invalid field app_id: cannot be empty",
+            ]
+        );
+        let json = err.to_json(None);
+        assert_eq!(json["error"]["details"]["reason"], "INVALID_PARAMETER");
+        assert_eq!(
+            json["error"]["details"]["field_violations"][0]["field"],
+            "app_id"
+        );
+
+        let status = tapp_service::error::throttled_status(
+            "Rate limit exceeded",
+            tapp_service::error::ErrorReason::RateLimited,
+            Default::default(),
+            std::time::Duration::from_secs(3),
+        );
+        let err = CliError::from(status);
+        assert_eq!(
+            err.details,
+            vec!["  Reason: RATE_LIMITED", "  Retry after: 3s"]
+        );
+        assert_eq!(
+            err.to_json(None)["error"]["details"]["retry_after_seconds"],
+            3
+        );
+
+        // Statuses without details, e.g. from older servers, are shown as before
+        let err = CliError::from(tonic::Status::not_found("App demo not found"));
+        assert!(err.details.is_empty());
+        assert!(err.status_details.is_none());
+    }
+
+    #[test]
+    fn test_json_output_snapshot() {
+        let dir = tempfile::tempdir().unwrap();
+        let address = hex::decode("5aaeb6053f3e94c9b9a09f33669435e7ef1beaed").unwrap();
+        let mut generated = String::new();
+        let mut section = |name: &str, json: String| {
+            generated.push_str(&format!("# {}\n{}\n", name, json));
+        };
+
+        section(
+            "start-app",
+            serde_json::to_string_pretty(&StartAppOutput {
+                app_id: "demo".to_string(),
+                task_id: "task-1".to_string(),
+                message: "Deployment queued".to_string(),
+                timestamp: 1700000000,
+                deployer: "0xabababab".to_string(),
+                deployer_address: "0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed".to_string(),
+            })
+            .unwrap(),
+        );
+        let task = TaskStatusOutput::new(
+            "task-1".to_string(),
+            GetTaskStatusResponse {
+                success: true,
+                task_id: "task-1".to_string(),
+                status: TaskStatus::Failed as i32,
+                result: Some(TaskResult {
+                    error: "pull access denied".to_string(),
+                    error_code: TaskErrorCode::PullFailed as i32,
+                    ..Default::default()
+                }),
+                created_at: 1700000000,
+                updated_at: 1700000060,
+                stage: "pulling".to_string(),
+                progress_percent: 40,
+                app_id: "demo".to_string(),
+                ..Default::default()
+            },
+            true,
+        );
+        assert_eq!(task.exit_code(), 1);
+        section("task-status", serde_json::to_string_pretty(&task).unwrap());
+        section(
+            "get-app-key",
+            serde_json::to_string_pretty(&AppKeyOutput::new(
+                "demo".to_string(),
+                "ethereum".to_string(),
+                GetAppKeyResponse {
+                    success: true,
+                    public_key: vec![0xab; 4],
+                    eth_address: address.clone(),
+                    key_source: "kbs".to_string(),
+                    ..Default::default()
+                },
+            ))
+            .unwrap(),
+        );
+        section(
+            "get-app-secret-key",
+            serde_json::to_string_pretty(&AppSecretKeyOutput::new(
+                "demo".to_string(),
+                GetAppSecretKeyResponse {
+                    success: true,
+                    private_key: vec![0x01; 4],
+                    public_key: vec![0xab; 4],
+                    eth_address: address,
+                    ..Default::default()
+                },
+            ))
+            .unwrap(),
+        );
+        section(
+            "app-info",
+            serde_json::to_string_pretty(&AppInfoOutput::new(GetAppInfoResponse {
+                success: true,
+                app_id: "demo".to_string(),
+                compose_content: "services: {}\n".to_string(),
+                volumes_content: "--- FILE: ./nginx.conf ---\nworker_processes 1;\n".to_string(),
+                events: vec![AppEventInfo {
+                    event: "start_app".to_string(),
+                    detail: "{}".to_string(),
+                    timestamp: 1700000000,
+                }],
+                deployed_at: 1700000000,
+                current_started_at: 1700000010,
+                uptime_seconds: 50,
+                running: true,
+                platforms: [("web".to_string(), "linux/amd64".to_string())].into(),
+                ..Default::default()
+            }))
+            .unwrap(),
+        );
+        let logs = GetAppLogsResponse {
+            success: true,
+            content: "ready\n".to_string(),
+            total_lines: 1,
+            ..Default::default()
+        };
+        section(
+            "app-logs",
+            serde_json::to_string_pretty(&AppLogsOutput {
+                app_id: "demo".to_string(),
+                task_id: None,
+                total_lines: logs.total_lines,
+                content: logs.content,
+            })
+            .unwrap(),
+        );
+        section(
+            "verify-evidence",
+            serde_json::to_string_pretty(
+                &verify_evidence(
+                    quote_file(dir.path()),
+                    Some(hex::encode("tapp-fixture")),
+                    None,
+                )
+                .unwrap(),
+            )
+            .unwrap(),
+        );
+        section(
+            "error",
+            serde_json::to_string_pretty(
+                &CliError::from(tonic::Status::not_found("App demo not found"))
+                    .to_json(Some("req-1")),
+            )
+            .unwrap(),
+        );
+
+        // UPDATE_SNAPSHOTS=1 cargo test rewrites the snapshot after a change
+        if std::env::var_os("UPDATE_SNAPSHOTS").is_some() {
+            std::fs::write(SNAPSHOT, &generated).unwrap();
+        }
+        assert_eq!(generated, std::fs::read_to_string(SNAPSHOT).unwrap());
+    }
 }
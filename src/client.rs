@@ -0,0 +1,736 @@
+//! Client for apps running on a TAPP host
+//!
+//! Apps reach the tapp-server that deployed them over the Docker bridge.
+//! `TappClient` wraps the calls an app makes about itself: its app key, its
+//! secret key, transactions signed with it and its sealed data. It builds the challenge nonce and the v2
+//! deployer signature of the signed requests, retries once when the nonce
+//! was rejected, and turns failed calls back into `TappError`s (see
+//! `error_from_status`). Built with the `client` cargo feature.
+
+use crate::app_key::deployer::{request_message_v2, sign_request_v2};
+use crate::app_key::{public_key_from_private, sign_message_recoverable};
+use crate::auth_layer::API_KEY_HEADER;
+use crate::cluster::sss;
+use crate::contract::tx::{SignedTransaction, Transaction};
+use crate::error::{TappError, TappResult};
+use crate::nonce_manager::NONCE_REJECTED_MESSAGE;
+use crate::proto::{
+    tapp_service_client::TappServiceClient, GetAppKeyRequest, GetAppSecretKeyRequest,
+    GetNonceRequest, SealBinding, SealDataRequest, SealedBlobInfo, SignTransactionRequest,
+    UnsealDataRequest,
+};
+use futures_util::future::BoxFuture;
+use hyper_util::rt::TokioIo;
+use std::net::Ipv4Addr;
+use std::path::PathBuf;
+use std::sync::Arc;
+use tokio::net::UnixStream;
+use tonic::metadata::AsciiMetadataValue;
+use tonic::service::interceptor::InterceptedService;
+use tonic::service::Interceptor;
+use tonic::transport::{Channel, Endpoint};
+use tonic::{Code, Request, Response, Status};
+use tonic_types::StatusExt;
+
+/// Environment variable naming the server: `http://HOST:PORT`, or
+/// `unix:///PATH` when the server's socket is mounted into the container
+pub const SERVER_ENV: &str = "TAPP_SERVER";
+
+/// Environment variable holding the API key sent with every request
+pub const API_KEY_ENV: &str = "TAPP_API_KEY";
+
+/// Port of the server on the container's default gateway
+pub const DEFAULT_PORT: u16 = 50051;
+
+/// Routing table the default gateway is read from
+const ROUTE_TABLE: &str = "/proc/net/route";
+
+/// Generated client with the API key interceptor
+pub type RawClient = TappServiceClient<InterceptedService<Channel, ApiKey>>;
+
+/// Adds the API key, if any, to every request
+#[derive(Clone, Default)]
+pub struct ApiKey(Option<AsciiMetadataValue>);
+
+impl Interceptor for ApiKey {
+    fn call(&mut self, mut request: Request<()>) -> Result<Request<()>, Status> {
+        if let Some(api_key) = &self.0 {
+            request
+                .metadata_mut()
+                .insert(API_KEY_HEADER, api_key.clone());
+        }
+        Ok(request)
+    }
+}
+
+/// Private key bytes, wiped when dropped
+struct SecretBytes(Vec<u8>);
+
+impl Drop for SecretBytes {
+    fn drop(&mut self) {
+        sss::wipe(&mut self.0);
+    }
+}
+
+/// An app's public key (GetAppKey)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AppKey {
+    pub public_key: Vec<u8>,
+    /// 20-byte Ethereum address
+    pub eth_address: Vec<u8>,
+    /// Where the server got the key: "kbs" or "in-memory"
+    pub key_source: String,
+}
+
+/// An app's key pair (GetAppSecretKey), the private key wiped when dropped
+pub struct AppSecretKey {
+    private_key: SecretBytes,
+    pub public_key: Vec<u8>,
+    /// 20-byte Ethereum address
+    pub eth_address: Vec<u8>,
+}
+
+impl AppSecretKey {
+    /// 32-byte private key
+    pub fn private_key(&self) -> &[u8] {
+        &self.private_key.0
+    }
+
+    /// Sign Keccak-256(message) the way Ethereum does: r || s || v
+    pub fn sign(&self, message: &[u8]) -> TappResult<Vec<u8>> {
+        sign_message_recoverable(self.private_key(), message)
+    }
+}
+
+impl std::fmt::Debug for AppSecretKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AppSecretKey")
+            .field("eth_address", &hex::encode(&self.eth_address))
+            .finish_non_exhaustive()
+    }
+}
+
+/// Nonce, timestamp and signature of a deployer-signed request
+#[derive(Default)]
+struct Signed {
+    nonce: String,
+    timestamp: i64,
+    signature: Vec<u8>,
+}
+
+/// Connection to tapp-server for an app
+#[derive(Clone)]
+pub struct TappClient {
+    channel: Channel,
+    api_key: ApiKey,
+    /// Signs the sealed data requests, when set
+    deployer_key: Option<Arc<SecretBytes>>,
+}
+
+impl TappClient {
+    /// Connect to `server`: `http://HOST:PORT`, `https://HOST:PORT` or
+    /// `unix:///PATH`
+    pub async fn connect(server: &str) -> TappResult<Self> {
+        let unreachable = |e: tonic::transport::Error| TappError::ServiceUnavailable {
+            service: format!("tapp-server at {} ({})", server, e),
+        };
+        let channel = match server.strip_prefix("unix://") {
+            Some(path) => {
+                let path = PathBuf::from(path);
+                let connector = tower::service_fn(move |_: http::Uri| {
+                    let path = path.clone();
+                    async move {
+                        Ok::<_, std::io::Error>(TokioIo::new(UnixStream::connect(path).await?))
+                    }
+                });
+                // The URI only fills in the requests' authority
+                Endpoint::from_static("http://localhost")
+                    .connect_with_connector(connector)
+                    .await
+            }
+            None => {
+                Endpoint::from_shared(server.to_string())
+                    .map_err(unreachable)?
+                    .connect()
+                    .await
+            }
+        }
+        .map_err(unreachable)?;
+        Ok(Self {
+            channel,
+            api_key: ApiKey::default(),
+            deployer_key: None,
+        })
+    }
+
+    /// Connect from inside an app container: to `TAPP_SERVER` if it is set,
+    /// otherwise to port 50051 of the default gateway, which is the host on
+    /// the Docker bridge. `TAPP_API_KEY`, if set, is sent with every request.
+    pub async fn connect_local() -> TappResult<Self> {
+        let server = match std::env::var(SERVER_ENV) {
+            Ok(server) if !server.is_empty() => server,
+            _ => {
+                let routes = std::fs::read_to_string(ROUTE_TABLE)?;
+                let gateway =
+                    default_gateway(&routes).ok_or_else(|| TappError::ServiceUnavailable {
+                        service: format!(
+                            "tapp-server ({} is not set, no default route)",
+                            SERVER_ENV
+                        ),
+                    })?;
+                format!("http://{}:{}", gateway, DEFAULT_PORT)
+            }
+        };
+        let client = Self::connect(&server).await?;
+        match std::env::var(API_KEY_ENV) {
+            Ok(api_key) if !api_key.is_empty() => client.with_api_key(&api_key),
+            _ => Ok(client),
+        }
+    }
+
+    /// Send `api_key` with every request
+    pub fn with_api_key(mut self, api_key: &str) -> TappResult<Self> {
+        let mut value: AsciiMetadataValue =
+            api_key.parse().map_err(|_| TappError::InvalidParameter {
+                field: "api_key".to_string(),
+                reason: "contains characters not allowed in a header".to_string(),
+            })?;
+        // Keeps the key out of debug output
+        value.set_sensitive(true);
+        self.api_key = ApiKey(Some(value));
+        Ok(self)
+    }
+
+    /// Sign sealed data requests with the deployer's 32-byte private key
+    /// Without it they are only accepted from the app's own containers.
+    pub fn with_deployer_key(mut self, private_key: Vec<u8>) -> TappResult<Self> {
+        let private_key = SecretBytes(private_key);
+        public_key_from_private(&private_key.0)?;
+        self.deployer_key = Some(Arc::new(private_key));
+        Ok(self)
+    }
+
+    /// Generated client, for the calls this module doesn't wrap
+    pub fn raw(&self) -> RawClient {
+        TappServiceClient::with_interceptor(self.channel.clone(), self.api_key.clone())
+    }
+
+    /// Ethereum public key and address of `app_id`
+    pub async fn get_app_key(&self, app_id: &str) -> TappResult<AppKey> {
+        let response = self
+            .raw()
+            .get_app_key(GetAppKeyRequest {
+                app_id: app_id.to_string(),
+                key_type: "ethereum".to_string(),
+                ..Default::default()
+            })
+            .await
+            .map_err(error_from_status)?
+            .into_inner();
+        check_success(response.success, response.message)?;
+        Ok(AppKey {
+            public_key: response.public_key,
+            eth_address: response.eth_address,
+            key_source: response.key_source,
+        })
+    }
+
+    /// Key pair of `app_id`, requested with a signature of its deployer's
+    /// 32-byte private key. The server only answers local callers.
+    pub async fn get_secret_key(
+        &self,
+        app_id: &str,
+        deployer_key: &[u8],
+    ) -> TappResult<AppSecretKey> {
+        let response = self
+            .signed(deployer_key, app_id, "get_secret_key", b"", |signed| {
+                let mut client = self.raw();
+                let request = GetAppSecretKeyRequest {
+                    app_id: app_id.to_string(),
+                    nonce: signed.nonce,
+                    timestamp: signed.timestamp,
+                    signature: signed.signature,
+                };
+                Box::pin(async move { client.get_app_secret_key(request).await })
+            })
+            .await?;
+        check_success(response.success, response.message)?;
+        Ok(AppSecretKey {
+            private_key: SecretBytes(response.private_key),
+            public_key: response.public_key,
+            eth_address: response.eth_address,
+        })
+    }
+
+    /// Sign `message` with the key of `app_id`: Ethereum-style r || s || v
+    /// over its Keccak-256. The server only signs transactions (see
+    /// `sign_transaction`), so the key is fetched with `get_secret_key` and
+    /// used here.
+    pub async fn sign_with_app_key(
+        &self,
+        app_id: &str,
+        deployer_key: &[u8],
+        message: &[u8],
+    ) -> TappResult<Vec<u8>> {
+        self.get_secret_key(app_id, deployer_key)
+            .await?
+            .sign(message)
+    }
+
+    /// Sign `transaction` with the key of `app_id`, requested with a
+    /// signature of its deployer's 32-byte private key over the
+    /// transaction's signing hash. The server does not broadcast it.
+    pub async fn sign_transaction(
+        &self,
+        app_id: &str,
+        deployer_key: &[u8],
+        transaction: &Transaction,
+    ) -> TappResult<SignedTransaction> {
+        let unsigned = transaction_request(app_id, transaction)?;
+        let signing_hash = transaction.signing_hash();
+        let response = self
+            .signed(
+                deployer_key,
+                app_id,
+                "sign_transaction",
+                &signing_hash,
+                |signed| {
+                    let mut client = self.raw();
+                    let request = SignTransactionRequest {
+                        nonce: signed.nonce,
+                        timestamp: signed.timestamp,
+                        signature: signed.signature,
+                        ..unsigned.clone()
+                    };
+                    Box::pin(async move { client.sign_transaction(request).await })
+                },
+            )
+            .await?;
+        check_success(response.success, response.message)?;
+        let hash = <[u8; 32]>::try_from(response.tx_hash.as_slice()).map_err(|_| {
+            TappError::Internal("SignTransaction answered without the hash".to_string())
+        })?;
+        Ok(SignedTransaction {
+            raw: response.raw_transaction,
+            hash,
+        })
+    }
+
+    /// Keep `data` of `app_id` sealed under `label`
+    pub async fn seal(
+        &self,
+        app_id: &str,
+        label: &str,
+        data: &[u8],
+        binding: SealBinding,
+    ) -> TappResult<SealedBlobInfo> {
+        let response = self
+            .sealed_data("seal_data", app_id, label, |signed| {
+                let mut client = self.raw();
+                let request = SealDataRequest {
+                    app_id: app_id.to_string(),
+                    label: label.to_string(),
+                    data: data.to_vec(),
+                    binding: binding as i32,
+                    nonce: signed.nonce,
+                    timestamp: signed.timestamp,
+                    signature: signed.signature,
+                };
+                Box::pin(async move { client.seal_data(request).await })
+            })
+            .await?;
+        check_success(response.success, response.message)?;
+        response
+            .blob
+            .ok_or_else(|| TappError::Internal("SealData answered without the blob".to_string()))
+    }
+
+    /// Data of `app_id` sealed under `label`
+    pub async fn unseal(&self, app_id: &str, label: &str) -> TappResult<Vec<u8>> {
+        let response = self
+            .sealed_data("unseal_data", app_id, label, |signed| {
+                let mut client = self.raw();
+                let request = UnsealDataRequest {
+                    app_id: app_id.to_string(),
+                    label: label.to_string(),
+                    nonce: signed.nonce,
+                    timestamp: signed.timestamp,
+                    signature: signed.signature,
+                };
+                Box::pin(async move { client.unseal_data(request).await })
+            })
+            .await?;
+        check_success(response.success, response.message)?;
+        Ok(response.data)
+    }
+
+    /// A sealed data call, signed with the deployer key if there is one
+    async fn sealed_data<T>(
+        &self,
+        operation: &str,
+        app_id: &str,
+        label: &str,
+        call: impl Fn(Signed) -> BoxFuture<'static, Result<Response<T>, Status>>,
+    ) -> TappResult<T> {
+        match &self.deployer_key {
+            Some(deployer_key) => {
+                let action = format!("{}:{}", operation, label);
+                self.signed(&deployer_key.0, app_id, operation, action.as_bytes(), call)
+                    .await
+            }
+            None => call(Signed::default())
+                .await
+                .map(Response::into_inner)
+                .map_err(error_from_status),
+        }
+    }
+
+    /// A deployer-signed call, made once more with a new challenge if its
+    /// nonce was rejected (expired, or taken by another request)
+    async fn signed<T>(
+        &self,
+        deployer_key: &[u8],
+        app_id: &str,
+        operation: &str,
+        action: &[u8],
+        call: impl Fn(Signed) -> BoxFuture<'static, Result<Response<T>, Status>>,
+    ) -> TappResult<T> {
+        let mut retried = false;
+        loop {
+            let (nonce, timestamp) = self.challenge().await?;
+            let message = request_message_v2(app_id, &nonce, timestamp, operation, action);
+            let signed = Signed {
+                nonce,
+                timestamp,
+                signature: sign_request_v2(deployer_key, &message)?,
+            };
+            match call(signed).await.map_err(error_from_status) {
+                Ok(response) => return Ok(response.into_inner()),
+                Err(TappError::PermissionDenied(message))
+                    if !retried && message.starts_with(NONCE_REJECTED_MESSAGE) =>
+                {
+                    retried = true;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    /// Nonce and timestamp to sign: a GetNonce challenge with timestamp 0,
+    /// or a random nonce and the current time from servers without GetNonce
+    async fn challenge(&self) -> TappResult<(String, i64)> {
+        match self.raw().get_nonce(GetNonceRequest {}).await {
+            Ok(response) => Ok((response.into_inner().nonce, 0)),
+            Err(status) if status.code() == Code::Unimplemented => Ok((
+                crate::nonce_manager::generate_nonce(),
+                crate::utils::current_timestamp(),
+            )),
+            Err(status) => Err(error_from_status(status)),
+        }
+    }
+}
+
+/// SignTransaction request for `transaction`, without the deployer signature
+fn transaction_request(
+    app_id: &str,
+    transaction: &Transaction,
+) -> TappResult<SignTransactionRequest> {
+    let fee = |field: &str, fee: u128| {
+        u64::try_from(fee).map_err(|_| TappError::InvalidParameter {
+            field: field.to_string(),
+            reason: "must fit in 64 bits".to_string(),
+        })
+    };
+    let to = |to: &Option<[u8; 20]>| to.map(|to| to.to_vec()).unwrap_or_default();
+    let value = |value: u128| -> Vec<u8> {
+        value
+            .to_be_bytes()
+            .into_iter()
+            .skip_while(|byte| *byte == 0)
+            .collect()
+    };
+    Ok(match transaction {
+        Transaction::Eip1559(tx) => SignTransactionRequest {
+            app_id: app_id.to_string(),
+            chain_id: tx.chain_id,
+            tx_nonce: tx.nonce,
+            max_priority_fee_per_gas: fee("max_priority_fee_per_gas", tx.max_priority_fee_per_gas)?,
+            max_fee_per_gas: fee("max_fee_per_gas", tx.max_fee_per_gas)?,
+            gas_limit: tx.gas_limit,
+            to: to(&tx.to),
+            value: value(tx.value),
+            data: tx.data.clone(),
+            access_list: tx
+                .access_list
+                .iter()
+                .map(|item| crate::proto::AccessListItem {
+                    address: item.address.to_vec(),
+                    storage_keys: item.storage_keys.iter().map(|key| key.to_vec()).collect(),
+                })
+                .collect(),
+            ..Default::default()
+        },
+        Transaction::Legacy(tx) => SignTransactionRequest {
+            app_id: app_id.to_string(),
+            chain_id: tx.chain_id,
+            tx_nonce: tx.nonce,
+            gas_price: fee("gas_price", tx.gas_price)?,
+            gas_limit: tx.gas_limit,
+            to: to(&tx.to),
+            value: value(tx.value),
+            data: tx.data.clone(),
+            legacy: true,
+            ..Default::default()
+        },
+    })
+}
+
+/// Error of a call answered with `success = false`
+fn check_success(success: bool, message: String) -> TappResult<()> {
+    if success {
+        Ok(())
+    } else {
+        Err(TappError::Internal(message))
+    }
+}
+
+/// The crate error a failed call stands for
+/// Statuses whose `ErrorInfo` reason names an error the caller can act on
+/// come back as that variant, and PERMISSION_DENIED as
+/// `TappError::PermissionDenied`; anything else stays a `TappError::Grpc`.
+pub fn error_from_status(status: Status) -> TappError {
+    let info = status.get_details_error_info();
+    let metadata = |key: &str| {
+        info.as_ref()
+            .and_then(|info| info.metadata.get(key))
+            .cloned()
+            .unwrap_or_default()
+    };
+    let message = status.message().to_string();
+    match info.as_ref().map(|info| info.reason.as_str()) {
+        Some("APP_NOT_FOUND") => TappError::AppNotFound {
+            app_id: metadata("app_id"),
+        },
+        Some("SEALED_BLOB_NOT_FOUND") => TappError::SealedBlobNotFound {
+            app_id: metadata("app_id"),
+            label: metadata("label"),
+        },
+        Some("SEAL_BINDING_MISMATCH") => TappError::SealBindingMismatch {
+            app_id: metadata("app_id"),
+            label: metadata("label"),
+        },
+        Some("INVALID_PARAMETER") => TappError::InvalidParameter {
+            field: metadata("field"),
+            reason: status
+                .get_details_bad_request()
+                .and_then(|bad_request| bad_request.field_violations.into_iter().next())
+                .map(|violation| violation.description)
+                .unwrap_or(message),
+        },
+        Some("SERVICE_UNAVAILABLE") => TappError::ServiceUnavailable {
+            service: metadata("service"),
+        },
+        _ if status.code() == Code::PermissionDenied => TappError::PermissionDenied(message),
+        _ => TappError::Grpc(status),
+    }
+}
+
+/// Gateway of the default route in a `/proc/net/route` table, whose
+/// addresses are hex in host (little-endian) byte order
+fn default_gateway(routes: &str) -> Option<Ipv4Addr> {
+    routes.lines().skip(1).find_map(|line| {
+        match line.split_whitespace().collect::<Vec<_>>().as_slice() {
+            [_, "00000000", gateway, ..] => u32::from_str_radix(gateway, 16)
+                .ok()
+                .map(|gateway| Ipv4Addr::from(gateway.to_le_bytes()))
+                .filter(|gateway| !gateway.is_unspecified()),
+            _ => None,
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app_key::{self, recover_public_key};
+    use crate::boot::measurement::AppMeasurement;
+    use crate::{utils, TappConfig, TappServiceImpl, TappServiceServer};
+    use k256::ecdsa::SigningKey;
+    use k256::elliptic_curve::{rand_core::OsRng, sec1::ToEncodedPoint};
+    use tokio_stream::wrappers::TcpListenerStream;
+    use tonic::transport::Server;
+
+    #[test]
+    fn test_default_gateway() {
+        let routes = "\
+Iface\tDestination\tGateway \tFlags\tRefCnt\tUse\tMetric\tMask\t\tMTU\tWindow\tIRTT
+eth0\t0012A8C0\t00000000\t0001\t0\t0\t0\t00FFFFFF\t0\t0\t0
+eth0\t00000000\t0100A8C0\t0003\t0\t0\t0\t00000000\t0\t0\t0
+";
+        assert_eq!(default_gateway(routes), Some(Ipv4Addr::new(192, 168, 0, 1)));
+        let no_default_route = routes.lines().take(2).collect::<Vec<_>>().join("\n");
+        assert_eq!(default_gateway(&no_default_route), None);
+    }
+
+    #[test]
+    fn test_error_from_status() {
+        let round_trip = |err: TappError| error_from_status(err.into());
+        assert!(matches!(
+            round_trip(TappError::AppNotFound { app_id: "a".to_string() }),
+            TappError::AppNotFound { app_id } if app_id == "a"
+        ));
+        assert!(matches!(
+            round_trip(TappError::SealedBlobNotFound {
+                app_id: "a".to_string(),
+                label: "db".to_string(),
+            }),
+            TappError::SealedBlobNotFound { app_id, label } if app_id == "a" && label == "db"
+        ));
+        assert!(matches!(
+            round_trip(TappError::InvalidParameter {
+                field: "label".to_string(),
+                reason: "too long".to_string(),
+            }),
+            TappError::InvalidParameter { field, reason } if field == "label" && reason == "too long"
+        ));
+        // Statuses built by hand carry no ErrorInfo
+        assert!(matches!(
+            error_from_status(Status::permission_denied("not local")),
+            TappError::PermissionDenied(message) if message == "not local"
+        ));
+        assert!(matches!(
+            error_from_status(Status::internal("boom")),
+            TappError::Grpc(status) if status.code() == Code::Internal
+        ));
+    }
+
+    /// Serve `app_id`, deployed by `deployer_key`, on a free local port;
+    /// returns its endpoint
+    async fn serve(dir: &std::path::Path, app_id: &str, deployer_key: &SigningKey) -> String {
+        let mut config = TappConfig::default();
+        config.boot.aa_config_path = Some(dir.join("aa.toml").to_string_lossy().to_string());
+        config.boot.data_dir = dir.join("apps");
+        config.cluster.state_dir = dir.join("cluster").to_string_lossy().to_string();
+        let service = TappServiceImpl::new(config).await.unwrap();
+
+        let public_key = deployer_key.verifying_key().to_encoded_point(false);
+        let public_key = &public_key.as_bytes()[1..];
+        service
+            .boot_service
+            .insert_app_measurement(AppMeasurement {
+                app_id: app_id.to_string(),
+                compose_hash: "ab".repeat(32),
+                volumes_hash: String::new(),
+                deployer: hex::encode(public_key),
+                deployer_address: hex::encode(app_key::eth_address(public_key)),
+                timestamp: utils::current_timestamp(),
+                namespace: None,
+                secret_names: Vec::new(),
+            })
+            .await;
+        service
+            .app_key_service
+            .get_app_key(app_id, "ethereum")
+            .await
+            .unwrap();
+        std::fs::create_dir_all(dir.join("apps").join(app_id)).unwrap();
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let endpoint = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(
+            Server::builder()
+                .add_service(TappServiceServer::new(service))
+                .serve_with_incoming(TcpListenerStream::new(listener)),
+        );
+        endpoint
+    }
+
+    #[tokio::test]
+    async fn test_app_keys() {
+        let dir = tempfile::tempdir().unwrap();
+        let deployer_key = SigningKey::random(&mut OsRng);
+        let endpoint = serve(dir.path(), "wallet-app", &deployer_key).await;
+        let client = TappClient::connect(&endpoint).await.unwrap();
+
+        let app_key = client.get_app_key("wallet-app").await.unwrap();
+        assert_eq!(app_key.eth_address.len(), 20);
+
+        // The deployer gets the key pair behind that address
+        let secret_key = client
+            .get_secret_key("wallet-app", &deployer_key.to_bytes())
+            .await
+            .unwrap();
+        assert_eq!(secret_key.eth_address, app_key.eth_address);
+        let signature = client
+            .sign_with_app_key("wallet-app", &deployer_key.to_bytes(), b"transfer")
+            .await
+            .unwrap();
+        let signer = recover_public_key(b"transfer", &signature).unwrap();
+        assert_eq!(app_key::eth_address(&signer), app_key.eth_address);
+
+        // Transactions are signed by the server, as they would be locally
+        let transaction = Transaction::Eip1559(crate::contract::tx::Eip1559Transaction {
+            chain_id: 16601,
+            nonce: 1,
+            max_priority_fee_per_gas: 1_000_000_000,
+            max_fee_per_gas: 20_000_000_000,
+            gas_limit: 21_000,
+            to: Some([0x35; 20]),
+            value: 1_000_000_000_000_000_000,
+            ..Default::default()
+        });
+        let signed = client
+            .sign_transaction("wallet-app", &deployer_key.to_bytes(), &transaction)
+            .await
+            .unwrap();
+        assert_eq!(signed, transaction.sign(secret_key.private_key()).unwrap());
+
+        // Anyone else is refused
+        let other_key = SigningKey::random(&mut OsRng);
+        let err = client
+            .get_secret_key("wallet-app", &other_key.to_bytes())
+            .await
+            .unwrap_err();
+        assert!(matches!(err, TappError::PermissionDenied(_)), "{:?}", err);
+    }
+
+    #[tokio::test]
+    async fn test_sealed_data() {
+        let dir = tempfile::tempdir().unwrap();
+        let deployer_key = SigningKey::random(&mut OsRng);
+        let endpoint = serve(dir.path(), "vault-app", &deployer_key).await;
+        let client = TappClient::connect(&endpoint).await.unwrap();
+
+        // Unsigned requests are for the app's containers, not localhost
+        let err = client
+            .seal("vault-app", "db-key", b"secret", SealBinding::SealToAppId)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, TappError::PermissionDenied(_)), "{:?}", err);
+
+        let client = client
+            .with_deployer_key(deployer_key.to_bytes().to_vec())
+            .unwrap();
+        let blob = client
+            .seal("vault-app", "db-key", b"secret", SealBinding::SealToAppId)
+            .await
+            .unwrap();
+        assert_eq!(blob.label, "db-key");
+        assert_eq!(blob.size, 6);
+        assert_eq!(
+            client.unseal("vault-app", "db-key").await.unwrap(),
+            b"secret"
+        );
+
+        let err = client.unseal("vault-app", "missing").await.unwrap_err();
+        assert!(
+            matches!(
+                &err,
+                TappError::SealedBlobNotFound { app_id, label }
+                    if app_id == "vault-app" && label == "missing"
+            ),
+            "{:?}",
+            err
+        );
+    }
+}
@@ -0,0 +1,409 @@
+//! Key share exchange between nodes (RequestKeyShare, JoinCluster)
+//!
+//! Every node has a secp256k1 node key, kept sealed in `cluster.state_dir`;
+//! its Ethereum address is the node's signer address. A joining node asks
+//! for its share with evidence over
+//! SHA-512("tapp-key-share-request:v1" || len(cluster_id) as u8 || cluster_id
+//! || len(peer) as u8 || peer || node key || timestamp)
+//! with the 64-byte node key and the timestamp as little-endian i64.
+//!
+//! The serving node checks that evidence and whether the requester's signer
+//! address and code hash are whitelisted, then seals the share to the
+//! requester's node key (ECIES as in `app_key::envelope`, info
+//! "tapp-key-share:v1", associated data cluster_id || 0x00 || peer). It
+//! answers with evidence of its own over
+//! SHA-512("tapp-key-share-response:v1" || request report data || its node
+//! key || SHA-256(sealed share)), which the requester checks against its own
+//! whitelist before it opens the share.
+//!
+//! Share recovery (RecoverShare, ContributeShare) uses the same checks and
+//! response format. Requests are bound by
+//! SHA-512(domain || len(cluster_id) as u8 || cluster_id || share index ||
+//! node key || timestamp), with domain "tapp-share-recovery-request:v1" for
+//! the replacement node and "tapp-share-contribution-request:v1" for the
+//! coordinator, and shares are sealed for "recovery:<index>" and
+//! "contribution:<index>" in place of a peer name.
+
+use super::sss::{self, Share};
+use super::verifier::{AttestedNode, EvidenceVerifier, Whitelist};
+use crate::app_key::envelope::{self, EnvelopeError};
+use crate::app_key::{eth_address, public_key_from_private};
+use crate::error::{TappError, TappResult};
+use k256::elliptic_curve::rand_core::OsRng;
+use k256::SecretKey;
+use sha2::{Digest, Sha256, Sha512};
+
+const REQUEST_DOMAIN: &[u8] = b"tapp-key-share-request:v1";
+const RESPONSE_DOMAIN: &[u8] = b"tapp-key-share-response:v1";
+const RECOVERY_DOMAIN: &[u8] = b"tapp-share-recovery-request:v1";
+const CONTRIBUTION_DOMAIN: &[u8] = b"tapp-share-contribution-request:v1";
+
+/// HKDF info of the key a share is sealed under
+const SHARE_KEY_INFO: &[u8] = b"tapp-key-share:v1";
+
+/// Length of a node key without the 0x04 prefix
+pub const NODE_KEY_LEN: usize = 64;
+
+/// Longest cluster ID or peer name accepted in an exchange
+pub const MAX_NAME_LEN: usize = 255;
+
+/// Key a node proves itself with and receives its share under
+/// The private key is wiped when it is dropped.
+pub struct NodeKey {
+    private_key: Vec<u8>,
+    public_key: Vec<u8>,
+}
+
+impl NodeKey {
+    pub fn generate() -> TappResult<Self> {
+        let secret = SecretKey::random(&mut OsRng);
+        Self::from_private_key(secret.to_bytes().to_vec())
+    }
+
+    pub fn from_private_key(private_key: Vec<u8>) -> TappResult<Self> {
+        let public_key = public_key_from_private(&private_key)?;
+        Ok(Self {
+            private_key,
+            public_key,
+        })
+    }
+
+    pub(crate) fn private_key(&self) -> &[u8] {
+        &self.private_key
+    }
+
+    /// 64-byte public key, without the 0x04 prefix
+    pub fn public_key(&self) -> &[u8] {
+        &self.public_key
+    }
+
+    pub fn signer_address(&self) -> String {
+        signer_address(&self.public_key)
+    }
+}
+
+impl Drop for NodeKey {
+    fn drop(&mut self) {
+        sss::wipe(&mut self.private_key);
+    }
+}
+
+/// Signer address of a node key: 0x-prefixed lowercase Ethereum address
+pub fn signer_address(public_key: &[u8]) -> String {
+    format!("0x{}", hex::encode(eth_address(public_key)))
+}
+
+fn update_with_name(hasher: &mut Sha512, name: &str) {
+    hasher.update([name.len() as u8]);
+    hasher.update(name.as_bytes());
+}
+
+/// Report data of the requester's evidence
+pub fn request_report_data(
+    cluster_id: &str,
+    peer: &str,
+    public_key: &[u8],
+    timestamp: i64,
+) -> Vec<u8> {
+    let mut hasher = Sha512::new();
+    hasher.update(REQUEST_DOMAIN);
+    update_with_name(&mut hasher, cluster_id);
+    update_with_name(&mut hasher, peer);
+    hasher.update(public_key);
+    hasher.update(timestamp.to_le_bytes());
+    hasher.finalize().to_vec()
+}
+
+/// Report data of the replacement node's evidence in RecoverShare
+pub fn recovery_report_data(
+    cluster_id: &str,
+    share_index: u8,
+    public_key: &[u8],
+    timestamp: i64,
+) -> Vec<u8> {
+    indexed_report_data(
+        RECOVERY_DOMAIN,
+        cluster_id,
+        share_index,
+        public_key,
+        timestamp,
+    )
+}
+
+/// Report data of the coordinator's evidence in ContributeShare
+pub fn contribution_report_data(
+    cluster_id: &str,
+    share_index: u8,
+    public_key: &[u8],
+    timestamp: i64,
+) -> Vec<u8> {
+    indexed_report_data(
+        CONTRIBUTION_DOMAIN,
+        cluster_id,
+        share_index,
+        public_key,
+        timestamp,
+    )
+}
+
+fn indexed_report_data(
+    domain: &[u8],
+    cluster_id: &str,
+    share_index: u8,
+    public_key: &[u8],
+    timestamp: i64,
+) -> Vec<u8> {
+    let mut hasher = Sha512::new();
+    hasher.update(domain);
+    update_with_name(&mut hasher, cluster_id);
+    hasher.update([share_index]);
+    hasher.update(public_key);
+    hasher.update(timestamp.to_le_bytes());
+    hasher.finalize().to_vec()
+}
+
+/// Name a recovered share is sealed for
+pub fn recovery_label(share_index: u8) -> String {
+    format!("recovery:{}", share_index)
+}
+
+/// Name a contributed share is sealed for, by the share being recovered
+pub fn contribution_label(share_index: u8) -> String {
+    format!("contribution:{}", share_index)
+}
+
+/// Report data of the serving node's evidence
+pub fn response_report_data(
+    request_report_data: &[u8],
+    public_key: &[u8],
+    sealed_share: &[u8],
+) -> Vec<u8> {
+    let mut hasher = Sha512::new();
+    hasher.update(RESPONSE_DOMAIN);
+    hasher.update(request_report_data);
+    hasher.update(public_key);
+    hasher.update(Sha256::digest(sealed_share));
+    hasher.finalize().to_vec()
+}
+
+/// Check that names fit the report data encoding
+pub fn check_names(cluster_id: &str, peer: &str) -> TappResult<()> {
+    for (field, name) in [("cluster_id", cluster_id), ("peer", peer)] {
+        if name.is_empty() || name.len() > MAX_NAME_LEN {
+            return Err(TappError::InvalidParameter {
+                field: field.to_string(),
+                reason: format!("must be 1 to {} bytes", MAX_NAME_LEN),
+            });
+        }
+    }
+    Ok(())
+}
+
+fn share_aad(cluster_id: &str, peer: &str) -> Vec<u8> {
+    [cluster_id.as_bytes(), &[0], peer.as_bytes()].concat()
+}
+
+/// Seal `share` of `peer` (or a recovery label) to its node key
+pub fn seal_share(
+    public_key: &[u8],
+    cluster_id: &str,
+    peer: &str,
+    share: &Share,
+) -> TappResult<Vec<u8>> {
+    let mut plaintext = [&[share.index][..], &share.value].concat();
+    let sealed = envelope::seal_to(
+        public_key,
+        SHARE_KEY_INFO,
+        &share_aad(cluster_id, peer),
+        &plaintext,
+    );
+    sss::wipe(&mut plaintext);
+    sealed.map_err(|e| TappError::Crypto(format!("Sealing the share failed: {}", e)))
+}
+
+/// Open a share sealed to `node_key`
+pub fn open_share(
+    node_key: &NodeKey,
+    cluster_id: &str,
+    peer: &str,
+    sealed_share: &[u8],
+) -> TappResult<Share> {
+    let mut plaintext = envelope::open_with(
+        node_key.private_key(),
+        SHARE_KEY_INFO,
+        &share_aad(cluster_id, peer),
+        sealed_share,
+    )
+    .map_err(|e| match e {
+        EnvelopeError::Decryption => TappError::Crypto(format!(
+            "Share is not sealed to this node as {} of cluster {}",
+            peer, cluster_id
+        )),
+        e => TappError::Crypto(format!("Opening the share failed: {}", e)),
+    })?;
+    let share = match plaintext.split_first() {
+        Some((&index, value)) if index != 0 && !value.is_empty() => Ok(Share {
+            index,
+            value: value.to_vec(),
+        }),
+        _ => Err(TappError::Crypto("Sealed share is malformed".to_string())),
+    };
+    sss::wipe(&mut plaintext);
+    share
+}
+
+/// Check the other side of an exchange: its evidence must attest
+/// `report_data`, and its signer address (of `public_key`) and code hash
+/// must be whitelisted. `peer` names it in errors.
+pub async fn check_node(
+    verifier: &dyn EvidenceVerifier,
+    whitelist: &dyn Whitelist,
+    peer: &str,
+    evidence: &[u8],
+    report_data: &[u8],
+    public_key: &[u8],
+) -> TappResult<(String, AttestedNode)> {
+    let rejected = |reason: String| TappError::ClusterPeerRejected {
+        peer: peer.to_string(),
+        reason,
+    };
+    if public_key.len() != NODE_KEY_LEN {
+        return Err(rejected(format!(
+            "node key must be {} bytes, got {}",
+            NODE_KEY_LEN,
+            public_key.len()
+        )));
+    }
+    let node = verifier
+        .verify(evidence)
+        .map_err(|e| rejected(format!("evidence does not verify: {}", e)))?;
+    if node.report_data.get(..report_data.len()) != Some(report_data) {
+        return Err(rejected(
+            "evidence does not attest this exchange".to_string(),
+        ));
+    }
+
+    let signer = signer_address(public_key);
+    if !whitelist.is_allowed(&signer, &node.code_hash).await? {
+        return Err(rejected(format!(
+            "signer {} with code hash {} is not whitelisted",
+            signer, node.code_hash
+        )));
+    }
+    Ok((signer, node))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cluster::verifier::StaticWhitelist;
+    use crate::config::ClusterConfig;
+
+    /// Evidence that attests whatever it carries
+    struct EchoVerifier;
+
+    impl EvidenceVerifier for EchoVerifier {
+        fn verify(&self, evidence: &[u8]) -> TappResult<AttestedNode> {
+            Ok(AttestedNode {
+                report_data: evidence.to_vec(),
+                code_hash: "ab".repeat(32),
+            })
+        }
+    }
+
+    #[test]
+    fn test_share_is_sealed_to_the_node_key() {
+        let node_key = NodeKey::generate().unwrap();
+        let share = Share {
+            index: 3,
+            value: vec![1, 2, 3],
+        };
+        let sealed = seal_share(node_key.public_key(), "c1", "node-b", &share).unwrap();
+        assert_eq!(
+            open_share(&node_key, "c1", "node-b", &sealed).unwrap(),
+            share
+        );
+
+        // Another node, cluster or peer name does not open it
+        let other = NodeKey::generate().unwrap();
+        assert!(open_share(&other, "c1", "node-b", &sealed).is_err());
+        assert!(open_share(&node_key, "c2", "node-b", &sealed).is_err());
+        assert!(open_share(&node_key, "c1", "node-c", &sealed).is_err());
+    }
+
+    #[test]
+    fn test_report_data_binds_every_field() {
+        let key = [4u8; 64];
+        let base = request_report_data("c1", "node-b", &key, 100);
+        assert_eq!(base.len(), 64);
+        for other in [
+            request_report_data("c1", "node-c", &key, 100),
+            request_report_data("c1n", "ode-b", &key, 100),
+            request_report_data("c1", "node-b", &[5; 64], 100),
+            request_report_data("c1", "node-b", &key, 101),
+        ] {
+            assert_ne!(other, base);
+        }
+        assert_ne!(
+            response_report_data(&base, &key, b"share"),
+            response_report_data(&base, &key, b"other")
+        );
+        assert_ne!(
+            recovery_report_data("c1", 2, &key, 100),
+            contribution_report_data("c1", 2, &key, 100)
+        );
+        assert_ne!(
+            recovery_report_data("c1", 2, &key, 100),
+            recovery_report_data("c1", 3, &key, 100)
+        );
+        assert!(check_names("c1", &"p".repeat(MAX_NAME_LEN)).is_ok());
+        assert!(check_names("", "node-b").is_err());
+        assert!(check_names("c1", &"p".repeat(MAX_NAME_LEN + 1)).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_check_node() {
+        let node_key = NodeKey::generate().unwrap();
+        let whitelist = StaticWhitelist::new(&ClusterConfig {
+            allowed_signers: vec![node_key.signer_address()],
+            allowed_code_hashes: vec!["ab".repeat(32)],
+            ..Default::default()
+        });
+        let report_data = request_report_data("c1", "node-b", node_key.public_key(), 1);
+        let check = |evidence: Vec<u8>, public_key: Vec<u8>| {
+            let whitelist = &whitelist;
+            let report_data = &report_data;
+            async move {
+                check_node(
+                    &EchoVerifier,
+                    whitelist,
+                    "node-b",
+                    &evidence,
+                    report_data,
+                    &public_key,
+                )
+                .await
+            }
+        };
+
+        let (signer, node) = check(report_data.clone(), node_key.public_key().to_vec())
+            .await
+            .unwrap();
+        assert_eq!(signer, node_key.signer_address());
+        assert_eq!(node.code_hash, "ab".repeat(32));
+
+        // Evidence of another exchange, and keys that are not whitelisted
+        let stale = request_report_data("c1", "node-b", node_key.public_key(), 2);
+        let other = NodeKey::generate().unwrap();
+        for (evidence, public_key) in [
+            (stale, node_key.public_key().to_vec()),
+            (report_data.clone(), other.public_key().to_vec()),
+            (report_data.clone(), vec![4; 33]),
+        ] {
+            assert!(matches!(
+                check(evidence, public_key).await,
+                Err(TappError::ClusterPeerRejected { .. })
+            ));
+        }
+    }
+}
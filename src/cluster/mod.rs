@@ -0,0 +1,897 @@
+//! Key cluster membership
+//!
+//! A designated start node creates the cluster with InitCluster: it
+//! generates the cluster master key and splits it into `total` shares (see
+//! `sss`), any `threshold` of which recover it. Share 1 is the start node's
+//! own and is kept sealed in `cluster.state_dir`; share i + 2 waits in memory
+//! until `peers[i]` collects it. Once every peer has its share, the master
+//! key and the remaining buffer are wiped and the node keeps its own share
+//! only.
+//!
+//! Peers collect their shares with RequestKeyShare, other nodes join with
+//! JoinCluster (see `exchange`). A node replacing a failed member gets that
+//! member's share with RecoverCluster, derived by another member from the
+//! shares of `threshold` members (see `recovery`).
+//!
+//! Distribution state lives in memory. After a restart a node reloads its
+//! own share and reports READY; shares that were not collected before the
+//! restart are gone.
+
+pub mod exchange;
+pub mod recovery;
+pub mod sss;
+pub mod verifier;
+
+use crate::app_key::sealed::{self, Binding};
+use crate::config::ClusterConfig;
+use crate::error::{TappError, TappResult};
+use ring::rand::{SecureRandom, SystemRandom};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::future::Future;
+use std::path::PathBuf;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use tracing::{info, warn};
+
+/// Length of the cluster master key in bytes
+pub const MASTER_KEY_LEN: usize = 32;
+
+/// ID the own share is sealed for; not a valid app ID, so no app's blob key
+/// can open it
+const SEAL_ID: &str = "tapp:cluster";
+
+/// Label of the own share's envelope
+const SHARE_LABEL: &str = "own-share";
+
+/// File of the own share in `cluster.state_dir`
+const SHARE_FILE: &str = "share.sealed";
+
+/// Label and file of the node key
+const NODE_KEY_LABEL: &str = "node-key";
+const NODE_KEY_FILE: &str = "node_key.sealed";
+
+/// Where this node is in the life of a cluster
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClusterState {
+    /// No cluster was initialized on this node
+    Uninitialized,
+    /// Start node: peers are still collecting their shares
+    Distributing,
+    /// The node holds its own share only
+    Ready,
+}
+
+impl ClusterState {
+    pub fn to_proto(self) -> crate::proto::ClusterState {
+        match self {
+            ClusterState::Uninitialized => crate::proto::ClusterState::ClusterUninitialized,
+            ClusterState::Distributing => crate::proto::ClusterState::ClusterDistributing,
+            ClusterState::Ready => crate::proto::ClusterState::ClusterReady,
+        }
+    }
+}
+
+/// A peer of the start node and its share
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PeerStatus {
+    pub peer: String,
+    pub share_index: u8,
+    /// When the peer collected its share (unix seconds)
+    pub delivered_at: Option<i64>,
+    /// Signer address of the node that collected it
+    pub collected_by: Option<String>,
+}
+
+/// What GetClusterStatus reports
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClusterStatus {
+    pub state: ClusterState,
+    pub cluster_id: String,
+    pub total_shares: u8,
+    pub threshold: u8,
+    pub own_share_index: u8,
+    /// Peers the start node distributes to (empty on other nodes and after
+    /// a restart)
+    pub peers: Vec<PeerStatus>,
+    pub initialized_at: i64,
+}
+
+impl ClusterStatus {
+    fn uninitialized() -> Self {
+        Self {
+            state: ClusterState::Uninitialized,
+            cluster_id: String::new(),
+            total_shares: 0,
+            threshold: 0,
+            own_share_index: 0,
+            peers: Vec::new(),
+            initialized_at: 0,
+        }
+    }
+
+    pub fn to_proto(&self) -> crate::proto::ClusterStatus {
+        crate::proto::ClusterStatus {
+            state: self.state.to_proto() as i32,
+            cluster_id: self.cluster_id.clone(),
+            total_shares: self.total_shares as u32,
+            threshold: self.threshold as u32,
+            own_share_index: self.own_share_index as u32,
+            peers: self
+                .peers
+                .iter()
+                .map(|peer| crate::proto::ClusterPeerStatus {
+                    peer: peer.peer.clone(),
+                    share_index: peer.share_index as u32,
+                    delivered: peer.delivered_at.is_some(),
+                    delivered_at: peer.delivered_at.unwrap_or_default(),
+                    collected_by: peer.collected_by.clone().unwrap_or_default(),
+                })
+                .collect(),
+            initialized_at: self.initialized_at,
+            signer_address: String::new(),
+            recovery: None,
+        }
+    }
+}
+
+/// Hands a share to the peer collecting it
+/// The share stays pending when delivery fails, so the peer can try again.
+pub trait ShareDelivery: Sync {
+    fn deliver(
+        &self,
+        peer: &str,
+        cluster_id: &str,
+        share: &sss::Share,
+    ) -> impl Future<Output = TappResult<()>> + Send;
+}
+
+/// The own share as sealed to disk
+#[derive(Serialize, Deserialize)]
+struct StoredShare {
+    cluster_id: String,
+    total_shares: u8,
+    threshold: u8,
+    index: u8,
+    /// Hex of the share value
+    value: String,
+    initialized_at: i64,
+}
+
+/// What only the start node holds while peers collect their shares
+struct Distribution {
+    master_key: Vec<u8>,
+    /// Shares not collected yet, by peer
+    pending: HashMap<String, sss::Share>,
+}
+
+impl Drop for Distribution {
+    fn drop(&mut self) {
+        sss::wipe(&mut self.master_key);
+    }
+}
+
+struct Membership {
+    status: ClusterStatus,
+    own_share: sss::Share,
+    distribution: Option<Distribution>,
+}
+
+impl Membership {
+    /// Why no other cluster can be initialized or joined
+    fn conflict(&self) -> TappError {
+        let status = &self.status;
+        TappError::ClusterState(match &self.distribution {
+            Some(distribution) => format!(
+                "Cluster {} is still distributing shares ({} of {} peers have not collected theirs)",
+                status.cluster_id,
+                distribution.pending.len(),
+                status.peers.len()
+            ),
+            None => format!(
+                "This node already holds share {} of cluster {}",
+                status.own_share_index, status.cluster_id
+            ),
+        })
+    }
+}
+
+/// This node's membership in a key cluster
+pub struct ClusterManager {
+    state_dir: PathBuf,
+    membership: Mutex<Option<Membership>>,
+    /// Loaded or generated on first use
+    node_key: Mutex<Option<Arc<exchange::NodeKey>>>,
+    /// Last recovery this node coordinated
+    recovery: std::sync::Mutex<Option<recovery::RecoveryStatus>>,
+}
+
+impl ClusterManager {
+    pub fn new(config: &ClusterConfig) -> Self {
+        Self {
+            state_dir: PathBuf::from(&config.state_dir),
+            membership: Mutex::new(None),
+            node_key: Mutex::new(None),
+            recovery: std::sync::Mutex::new(None),
+        }
+    }
+
+    /// Whether a share was sealed to disk by an earlier run
+    pub fn has_stored_share(&self) -> bool {
+        self.state_dir.join(SHARE_FILE).exists()
+    }
+
+    /// Reload the own share sealed by an earlier run; false if there is none
+    pub async fn load(&self, sealing_key: &[u8]) -> TappResult<bool> {
+        let Some(mut plaintext) = self
+            .read_sealed(SHARE_FILE, SHARE_LABEL, sealing_key)
+            .await?
+        else {
+            return Ok(false);
+        };
+        let stored: Result<StoredShare, _> = serde_json::from_slice(&plaintext);
+        sss::wipe(&mut plaintext);
+        let stored = stored?;
+        let own_share = sss::Share {
+            index: stored.index,
+            value: hex::decode(&stored.value).map_err(|e| {
+                TappError::Crypto(format!("Stored cluster share is not hex: {}", e))
+            })?,
+        };
+
+        info!(
+            cluster_id = %stored.cluster_id,
+            share_index = stored.index,
+            event = "CLUSTER_SHARE_LOADED",
+            "Loaded this node's cluster share"
+        );
+        *self.membership.lock().await = Some(Membership {
+            status: ClusterStatus {
+                state: ClusterState::Ready,
+                cluster_id: stored.cluster_id,
+                total_shares: stored.total_shares,
+                threshold: stored.threshold,
+                own_share_index: stored.index,
+                peers: Vec::new(),
+                initialized_at: stored.initialized_at,
+            },
+            own_share,
+            distribution: None,
+        });
+        Ok(true)
+    }
+
+    /// Create a cluster as its start node
+    /// Generates the master key, splits it into `total_shares` shares of
+    /// which `threshold` recover it, seals share 1 to disk and keeps share
+    /// i + 2 for `peers[i]`. Rejected once this node holds a share.
+    pub async fn init(
+        &self,
+        total_shares: u8,
+        threshold: u8,
+        peers: Vec<String>,
+        sealing_key: &[u8],
+    ) -> TappResult<ClusterStatus> {
+        let mut membership = self.membership.lock().await;
+        if let Some(existing) = membership.as_ref() {
+            return Err(existing.conflict());
+        }
+        validate_peers(total_shares, &peers)?;
+
+        let rng = SystemRandom::new();
+        let mut master_key = vec![0u8; MASTER_KEY_LEN];
+        let mut cluster_id = [0u8; 16];
+        rng.fill(&mut master_key)
+            .and_then(|_| rng.fill(&mut cluster_id))
+            .map_err(|_| TappError::Crypto("No randomness for the master key".to_string()))?;
+        let cluster_id = hex::encode(cluster_id);
+        let mut distribution = Distribution {
+            master_key,
+            pending: HashMap::new(),
+        };
+        let mut shares = sss::split(&distribution.master_key, total_shares, threshold)?;
+        let peer_shares = shares.split_off(1);
+        let own_share = shares.remove(0);
+
+        let initialized_at = crate::utils::current_timestamp();
+        let status = ClusterStatus {
+            state: ClusterState::Distributing,
+            cluster_id,
+            total_shares,
+            threshold,
+            own_share_index: own_share.index,
+            peers: peers
+                .iter()
+                .zip(&peer_shares)
+                .map(|(peer, share)| PeerStatus {
+                    peer: peer.clone(),
+                    share_index: share.index,
+                    delivered_at: None,
+                    collected_by: None,
+                })
+                .collect(),
+            initialized_at,
+        };
+        self.store_share(&status, &own_share, sealing_key).await?;
+
+        info!(
+            cluster_id = %status.cluster_id,
+            total_shares,
+            threshold,
+            peers = ?peers,
+            event = "CLUSTER_INITIALIZED",
+            "Generated the cluster master key; shares await their peers"
+        );
+        distribution.pending = peers.into_iter().zip(peer_shares).collect();
+        *membership = Some(Membership {
+            status: status.clone(),
+            own_share,
+            distribution: Some(distribution),
+        });
+        Ok(status)
+    }
+
+    /// Become a member holding `share`, collected from the start node with
+    /// JoinCluster. Rejected once this node holds a share.
+    pub async fn join(
+        &self,
+        cluster_id: &str,
+        total_shares: u8,
+        threshold: u8,
+        initialized_at: i64,
+        share: sss::Share,
+        sealing_key: &[u8],
+    ) -> TappResult<ClusterStatus> {
+        let mut membership = self.membership.lock().await;
+        if let Some(existing) = membership.as_ref() {
+            return Err(existing.conflict());
+        }
+        if threshold < 2 || threshold > total_shares || share.index > total_shares {
+            return Err(TappError::InvalidParameter {
+                field: "share".to_string(),
+                reason: format!(
+                    "share {} of a {}-of-{} cluster is impossible",
+                    share.index, threshold, total_shares
+                ),
+            });
+        }
+
+        let status = ClusterStatus {
+            state: ClusterState::Ready,
+            cluster_id: cluster_id.to_string(),
+            total_shares,
+            threshold,
+            own_share_index: share.index,
+            peers: Vec::new(),
+            initialized_at,
+        };
+        self.store_share(&status, &share, sealing_key).await?;
+        info!(
+            cluster_id = %cluster_id,
+            share_index = share.index,
+            event = "CLUSTER_JOINED",
+            "Joined the cluster with a share from its start node"
+        );
+        *membership = Some(Membership {
+            status: status.clone(),
+            own_share: share,
+            distribution: None,
+        });
+        Ok(status)
+    }
+
+    /// Seal the own share to disk
+    async fn store_share(
+        &self,
+        status: &ClusterStatus,
+        own_share: &sss::Share,
+        sealing_key: &[u8],
+    ) -> TappResult<()> {
+        let stored = StoredShare {
+            cluster_id: status.cluster_id.clone(),
+            total_shares: status.total_shares,
+            threshold: status.threshold,
+            index: own_share.index,
+            value: hex::encode(&own_share.value),
+            initialized_at: status.initialized_at,
+        };
+        let mut plaintext = serde_json::to_vec(&stored)?;
+        let written = self
+            .write_sealed(SHARE_FILE, SHARE_LABEL, &plaintext, sealing_key)
+            .await;
+        sss::wipe(&mut plaintext);
+        written
+    }
+
+    /// This node's key, generated and sealed to disk on first use
+    pub async fn node_key(&self, sealing_key: &[u8]) -> TappResult<Arc<exchange::NodeKey>> {
+        let mut node_key = self.node_key.lock().await;
+        if let Some(node_key) = node_key.as_ref() {
+            return Ok(node_key.clone());
+        }
+        let key = match self
+            .read_sealed(NODE_KEY_FILE, NODE_KEY_LABEL, sealing_key)
+            .await?
+        {
+            Some(private_key) => exchange::NodeKey::from_private_key(private_key)?,
+            None => {
+                let key = exchange::NodeKey::generate()?;
+                self.write_sealed(
+                    NODE_KEY_FILE,
+                    NODE_KEY_LABEL,
+                    key.private_key(),
+                    sealing_key,
+                )
+                .await?;
+                info!(
+                    signer_address = %key.signer_address(),
+                    event = "CLUSTER_NODE_KEY_GENERATED",
+                    "Generated this node's cluster key"
+                );
+                key
+            }
+        };
+        Ok(node_key.insert(Arc::new(key)).clone())
+    }
+
+    /// Seal `plaintext` into `file` of the state directory, replacing the
+    /// file at once
+    async fn write_sealed(
+        &self,
+        file: &str,
+        label: &str,
+        plaintext: &[u8],
+        sealing_key: &[u8],
+    ) -> TappResult<()> {
+        let envelope = sealed::seal(
+            sealing_key,
+            SEAL_ID,
+            "",
+            label,
+            Binding::AppId,
+            crate::utils::current_timestamp(),
+            plaintext,
+        )?;
+        tokio::fs::create_dir_all(&self.state_dir).await?;
+        let partial = self.state_dir.join(format!(".{}.partial", file));
+        tokio::fs::write(&partial, &envelope).await?;
+        tokio::fs::rename(&partial, self.state_dir.join(file)).await?;
+        Ok(())
+    }
+
+    /// Open `file` of the state directory; None if there is none
+    async fn read_sealed(
+        &self,
+        file: &str,
+        label: &str,
+        sealing_key: &[u8],
+    ) -> TappResult<Option<Vec<u8>>> {
+        let envelope = match tokio::fs::read(self.state_dir.join(file)).await {
+            Ok(envelope) => envelope,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        let (_, plaintext) = sealed::open(sealing_key, SEAL_ID, "", label, &envelope)?;
+        Ok(Some(plaintext))
+    }
+
+    /// Hand `peer` its share with `delivery`, to the node with signer
+    /// address `collector`
+    /// Once the last peer has its share, the master key and the share
+    /// buffer are wiped and the node becomes READY.
+    pub async fn deliver_share<D: ShareDelivery>(
+        &self,
+        peer: &str,
+        collector: &str,
+        delivery: &D,
+    ) -> TappResult<ClusterStatus> {
+        let mut guard = self.membership.lock().await;
+        let Some(membership) = guard.as_mut() else {
+            return Err(TappError::ClusterState(
+                "No cluster was initialized on this node".to_string(),
+            ));
+        };
+        let Some(distribution) = membership.distribution.as_mut() else {
+            return Err(TappError::ClusterState(format!(
+                "This node has no shares of cluster {} to hand out",
+                membership.status.cluster_id
+            )));
+        };
+        let Some(share) = distribution.pending.get(peer) else {
+            if membership.status.peers.iter().any(|p| p.peer == peer) {
+                return Err(TappError::ClusterState(format!(
+                    "Peer {} already collected its share",
+                    peer
+                )));
+            }
+            return Err(TappError::InvalidParameter {
+                field: "peer".to_string(),
+                reason: format!(
+                    "{} is not a peer of cluster {}",
+                    peer, membership.status.cluster_id
+                ),
+            });
+        };
+
+        delivery
+            .deliver(peer, &membership.status.cluster_id, share)
+            .await
+            .inspect_err(|e| {
+                warn!(
+                    cluster_id = %membership.status.cluster_id,
+                    peer = %peer,
+                    error = %e,
+                    event = "CLUSTER_SHARE_DELIVERY_FAILED",
+                    "Share delivery failed; the share stays pending"
+                );
+            })?;
+
+        distribution.pending.remove(peer);
+        let now = crate::utils::current_timestamp();
+        if let Some(status) = membership.status.peers.iter_mut().find(|p| p.peer == peer) {
+            status.delivered_at = Some(now);
+            status.collected_by = Some(collector.to_string());
+        }
+        info!(
+            cluster_id = %membership.status.cluster_id,
+            peer = %peer,
+            collector = %collector,
+            remaining = distribution.pending.len(),
+            event = "CLUSTER_SHARE_DELIVERED",
+            "Peer collected its share"
+        );
+
+        if distribution.pending.is_empty() {
+            // Dropping the distribution wipes the master key
+            membership.distribution = None;
+            membership.status.state = ClusterState::Ready;
+            info!(
+                cluster_id = %membership.status.cluster_id,
+                event = "CLUSTER_DISTRIBUTED",
+                "Every peer has its share; discarded the master key"
+            );
+        }
+        Ok(membership.status.clone())
+    }
+
+    /// State of this node's membership
+    pub async fn status(&self) -> ClusterStatus {
+        match self.membership.lock().await.as_ref() {
+            Some(membership) => membership.status.clone(),
+            None => ClusterStatus::uninitialized(),
+        }
+    }
+
+    /// This node's share of `cluster_id`, to contribute to a recovery
+    pub async fn own_share(&self, cluster_id: &str) -> TappResult<sss::Share> {
+        match self.membership.lock().await.as_ref() {
+            Some(membership) if membership.status.cluster_id == cluster_id => {
+                Ok(membership.own_share.clone())
+            }
+            _ => Err(TappError::ClusterState(format!(
+                "This node is not a member of cluster {}",
+                cluster_id
+            ))),
+        }
+    }
+
+    /// Coordinate the recovery of share `share_index` of `cluster_id` for
+    /// the replacement node with signer address `requested_by`
+    pub async fn begin_recovery(
+        &self,
+        cluster_id: &str,
+        share_index: u8,
+        requested_by: &str,
+    ) -> TappResult<recovery::Recovery<'_>> {
+        let membership = self.membership.lock().await;
+        let Some(membership) = membership
+            .as_ref()
+            .filter(|membership| membership.status.cluster_id == cluster_id)
+        else {
+            return Err(TappError::ClusterState(format!(
+                "This node is not a member of cluster {}",
+                cluster_id
+            )));
+        };
+        let status = &membership.status;
+        if share_index == 0 || share_index > status.total_shares {
+            return Err(TappError::InvalidParameter {
+                field: "share_index".to_string(),
+                reason: format!(
+                    "cluster {} has shares 1 to {}",
+                    cluster_id, status.total_shares
+                ),
+            });
+        }
+        if share_index == status.own_share_index {
+            return Err(TappError::InvalidParameter {
+                field: "share_index".to_string(),
+                reason: format!("share {} is this node's own", share_index),
+            });
+        }
+        recovery::Recovery::begin(
+            &self.recovery,
+            cluster_id,
+            share_index,
+            status.threshold,
+            membership.own_share.clone(),
+            requested_by,
+        )
+    }
+
+    /// Last recovery this node coordinated
+    pub fn recovery_status(&self) -> Option<recovery::RecoveryStatus> {
+        self.recovery.lock().unwrap().clone()
+    }
+}
+
+/// Check the peer list of a cluster of `total_shares` shares
+fn validate_peers(total_shares: u8, peers: &[String]) -> TappResult<()> {
+    let invalid = |reason: String| TappError::InvalidParameter {
+        field: "peers".to_string(),
+        reason,
+    };
+    if peers.len() + 1 != total_shares as usize {
+        return Err(invalid(format!(
+            "{} shares need {} peers besides this node, got {}",
+            total_shares,
+            (total_shares as usize).saturating_sub(1),
+            peers.len()
+        )));
+    }
+    for (i, peer) in peers.iter().enumerate() {
+        if peer.trim().is_empty() || peer.len() > exchange::MAX_NAME_LEN {
+            return Err(invalid(format!(
+                "peer names must be 1 to {} bytes",
+                exchange::MAX_NAME_LEN
+            )));
+        }
+        if peers[..i].contains(peer) {
+            return Err(invalid(format!("{} is listed twice", peer)));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex as StdMutex;
+
+    const SEALING_KEY: &[u8] = &[9; 32];
+    const COLLECTOR: &str = "0x00112233445566778899aabbccddeeff00112233";
+
+    /// Records delivered shares; fails for the peers in `unreachable`
+    #[derive(Default)]
+    struct MockDelivery {
+        unreachable: Vec<String>,
+        delivered: StdMutex<Vec<(String, sss::Share)>>,
+    }
+
+    impl ShareDelivery for MockDelivery {
+        async fn deliver(
+            &self,
+            peer: &str,
+            _cluster_id: &str,
+            share: &sss::Share,
+        ) -> TappResult<()> {
+            if self.unreachable.iter().any(|p| p == peer) {
+                return Err(TappError::ServiceUnavailable {
+                    service: peer.to_string(),
+                });
+            }
+            self.delivered
+                .lock()
+                .unwrap()
+                .push((peer.to_string(), share.clone()));
+            Ok(())
+        }
+    }
+
+    fn manager(dir: &std::path::Path) -> ClusterManager {
+        ClusterManager::new(&ClusterConfig {
+            state_dir: dir.to_string_lossy().to_string(),
+            ..Default::default()
+        })
+    }
+
+    fn peers(names: &[&str]) -> Vec<String> {
+        names.iter().map(|name| name.to_string()).collect()
+    }
+
+    #[tokio::test]
+    async fn test_start_node_distributes_and_discards_the_master_key() {
+        let dir = tempfile::tempdir().unwrap();
+        let cluster = manager(dir.path());
+        assert_eq!(cluster.status().await.state, ClusterState::Uninitialized);
+
+        let status = cluster
+            .init(5, 3, peers(&["b", "c", "d", "e"]), SEALING_KEY)
+            .await
+            .unwrap();
+        assert_eq!(status.state, ClusterState::Distributing);
+        assert_eq!(status.own_share_index, 1);
+        assert_eq!(
+            status
+                .peers
+                .iter()
+                .map(|p| (p.peer.as_str(), p.share_index))
+                .collect::<Vec<_>>(),
+            vec![("b", 2), ("c", 3), ("d", 4), ("e", 5)]
+        );
+        assert!(cluster.has_stored_share());
+        let master_key = {
+            let membership = cluster.membership.lock().await;
+            membership
+                .as_ref()
+                .unwrap()
+                .distribution
+                .as_ref()
+                .unwrap()
+                .master_key
+                .clone()
+        };
+
+        // Re-running init while shares are outstanding is rejected
+        assert!(matches!(
+            cluster
+                .init(5, 3, peers(&["b", "c", "d", "e"]), SEALING_KEY)
+                .await,
+            Err(TappError::ClusterState(_))
+        ));
+
+        // A failed delivery keeps the share pending
+        let delivery = MockDelivery {
+            unreachable: peers(&["c"]),
+            ..Default::default()
+        };
+        cluster
+            .deliver_share("b", COLLECTOR, &delivery)
+            .await
+            .unwrap();
+        assert!(cluster
+            .deliver_share("c", COLLECTOR, &delivery)
+            .await
+            .is_err());
+        assert!(matches!(
+            cluster.deliver_share("b", COLLECTOR, &delivery).await,
+            Err(TappError::ClusterState(_))
+        ));
+        assert!(matches!(
+            cluster.deliver_share("z", COLLECTOR, &delivery).await,
+            Err(TappError::InvalidParameter { .. })
+        ));
+        let status = cluster.status().await;
+        assert_eq!(status.state, ClusterState::Distributing);
+        assert!(status.peers[0].delivered_at.is_some());
+        assert!(status.peers[1].delivered_at.is_none());
+
+        let delivery = MockDelivery {
+            delivered: StdMutex::new(delivery.delivered.into_inner().unwrap()),
+            ..Default::default()
+        };
+        for peer in ["c", "d", "e"] {
+            cluster
+                .deliver_share(peer, COLLECTOR, &delivery)
+                .await
+                .unwrap();
+        }
+
+        // Only the own share is left
+        let status = cluster.status().await;
+        assert_eq!(status.state, ClusterState::Ready);
+        assert!(status.peers.iter().all(|p| p.delivered_at.is_some()));
+        assert_eq!(status.peers[1].collected_by.as_deref(), Some(COLLECTOR));
+        let own_share = {
+            let membership = cluster.membership.lock().await;
+            let membership = membership.as_ref().unwrap();
+            assert!(membership.distribution.is_none());
+            membership.own_share.clone()
+        };
+        assert!(matches!(
+            cluster.deliver_share("b", COLLECTOR, &delivery).await,
+            Err(TappError::ClusterState(_))
+        ));
+        assert!(matches!(
+            cluster
+                .init(5, 3, peers(&["b", "c", "d", "e"]), SEALING_KEY)
+                .await,
+            Err(TappError::ClusterState(_))
+        ));
+
+        // Any three shares recover the master key
+        let delivered = delivery.delivered.lock().unwrap();
+        assert_eq!(delivered.len(), 4);
+        let shares = [own_share, delivered[1].1.clone(), delivered[3].1.clone()];
+        assert_eq!(sss::combine(&shares).unwrap(), master_key);
+    }
+
+    #[tokio::test]
+    async fn test_own_share_survives_a_restart() {
+        let dir = tempfile::tempdir().unwrap();
+        let cluster = manager(dir.path());
+        let status = cluster
+            .init(3, 2, peers(&["b", "c"]), SEALING_KEY)
+            .await
+            .unwrap();
+
+        let restarted = manager(dir.path());
+        assert!(restarted.load(SEALING_KEY).await.unwrap());
+        let reloaded = restarted.status().await;
+        assert_eq!(reloaded.state, ClusterState::Ready);
+        assert_eq!(reloaded.cluster_id, status.cluster_id);
+        assert_eq!(reloaded.own_share_index, 1);
+        assert_eq!((reloaded.total_shares, reloaded.threshold), (3, 2));
+        assert!(reloaded.peers.is_empty());
+
+        // Only this node's sealing key opens it
+        assert!(matches!(
+            manager(dir.path()).load(&[1; 32]).await,
+            Err(TappError::SealBindingMismatch { .. })
+        ));
+        let empty = tempfile::tempdir().unwrap();
+        assert!(!manager(empty.path()).load(SEALING_KEY).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_init_rejects_bad_parameters() {
+        let dir = tempfile::tempdir().unwrap();
+        let cluster = manager(dir.path());
+        for (total, threshold, names) in [
+            (3, 2, vec!["b"]),
+            (3, 2, vec!["b", "b"]),
+            (3, 2, vec!["b", " "]),
+            (3, 1, vec!["b", "c"]),
+            (3, 4, vec!["b", "c"]),
+        ] {
+            assert!(matches!(
+                cluster
+                    .init(total, threshold, peers(&names), SEALING_KEY)
+                    .await,
+                Err(TappError::InvalidParameter { .. })
+            ));
+        }
+        assert_eq!(cluster.status().await.state, ClusterState::Uninitialized);
+        assert!(!cluster.has_stored_share());
+    }
+
+    #[tokio::test]
+    async fn test_join_and_node_key() {
+        let dir = tempfile::tempdir().unwrap();
+        let cluster = manager(dir.path());
+
+        // The node key survives a restart
+        let node_key = cluster.node_key(SEALING_KEY).await.unwrap();
+        let restarted = manager(dir.path());
+        assert_eq!(
+            restarted.node_key(SEALING_KEY).await.unwrap().public_key(),
+            node_key.public_key()
+        );
+
+        let share = || sss::Share {
+            index: 2,
+            value: vec![1; MASTER_KEY_LEN],
+        };
+        assert!(matches!(
+            cluster.join("c1", 3, 4, 100, share(), SEALING_KEY).await,
+            Err(TappError::InvalidParameter { .. })
+        ));
+        let status = cluster
+            .join("c1", 3, 2, 100, share(), SEALING_KEY)
+            .await
+            .unwrap();
+        assert_eq!(status.state, ClusterState::Ready);
+        assert_eq!(status.own_share_index, 2);
+        assert!(matches!(
+            cluster.join("c1", 3, 2, 100, share(), SEALING_KEY).await,
+            Err(TappError::ClusterState(_))
+        ));
+        assert!(matches!(
+            cluster.init(3, 2, peers(&["b", "c"]), SEALING_KEY).await,
+            Err(TappError::ClusterState(_))
+        ));
+
+        assert!(restarted.load(SEALING_KEY).await.unwrap());
+        assert_eq!(restarted.status().await, status);
+    }
+}
@@ -0,0 +1,326 @@
+//! Recovery of a failed member's share
+//!
+//! A replacement node asks a member of the cluster, the coordinator, for the
+//! share of the member it replaces (RecoverShare). The coordinator collects
+//! `threshold - 1` shares of other members (ContributeShare), derives the
+//! requested share from them and its own, and seals it to the replacement
+//! node (see `exchange`). The share is interpolated directly: the same as
+//! recovering the master key and splitting it again with the same
+//! polynomials, but the key is never assembled. Collected shares are wiped
+//! as soon as the recovery ends, delivered or aborted.
+//!
+//! A node coordinates one recovery at a time. GetClusterStatus reports the
+//! last one; the report is kept in memory only.
+
+use super::sss::{self, Share};
+use crate::error::{TappError, TappResult};
+use std::sync::Mutex;
+use tracing::{info, warn};
+
+/// Where a recovery is
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecoveryState {
+    /// Collecting shares of other members
+    Collecting,
+    /// Deriving and sealing the requested share
+    Reconstructing,
+    /// Sent to the replacement node
+    Delivered,
+    /// Failed or interrupted; collected shares were wiped
+    Aborted,
+}
+
+impl RecoveryState {
+    pub fn to_proto(self) -> crate::proto::RecoveryState {
+        match self {
+            RecoveryState::Collecting => crate::proto::RecoveryState::RecoveryCollecting,
+            RecoveryState::Reconstructing => crate::proto::RecoveryState::RecoveryReconstructing,
+            RecoveryState::Delivered => crate::proto::RecoveryState::RecoveryDelivered,
+            RecoveryState::Aborted => crate::proto::RecoveryState::RecoveryAborted,
+        }
+    }
+
+    fn is_active(self) -> bool {
+        matches!(
+            self,
+            RecoveryState::Collecting | RecoveryState::Reconstructing
+        )
+    }
+}
+
+/// What GetClusterStatus reports about the last recovery
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecoveryStatus {
+    pub state: RecoveryState,
+    pub share_index: u8,
+    /// Shares of other members collected so far
+    pub collected: u8,
+    /// Shares of other members needed (threshold - 1)
+    pub needed: u8,
+    /// Signer address of the replacement node
+    pub requested_by: String,
+    /// Signer addresses of the members that contributed
+    pub contributors: Vec<String>,
+    pub started_at: i64,
+    pub updated_at: i64,
+    /// Why it was aborted
+    pub error: Option<String>,
+}
+
+impl RecoveryStatus {
+    pub fn to_proto(&self) -> crate::proto::ClusterRecovery {
+        crate::proto::ClusterRecovery {
+            state: self.state.to_proto() as i32,
+            share_index: self.share_index as u32,
+            collected: self.collected as u32,
+            needed: self.needed as u32,
+            requested_by: self.requested_by.clone(),
+            contributors: self.contributors.clone(),
+            started_at: self.started_at,
+            updated_at: self.updated_at,
+            error: self.error.clone().unwrap_or_default(),
+        }
+    }
+}
+
+/// A recovery this node coordinates
+/// Dropping it before `delivered` or `abort`, e.g. when the RPC is
+/// cancelled, aborts it.
+pub struct Recovery<'a> {
+    report: &'a Mutex<Option<RecoveryStatus>>,
+    cluster_id: String,
+    share_index: u8,
+    threshold: u8,
+    /// The own share, then the collected ones
+    shares: Vec<Share>,
+    finished: bool,
+}
+
+impl<'a> Recovery<'a> {
+    /// Start recovering `share_index` with `own_share`, reporting to `report`
+    /// Rejected while another recovery is running.
+    pub(super) fn begin(
+        report: &'a Mutex<Option<RecoveryStatus>>,
+        cluster_id: &str,
+        share_index: u8,
+        threshold: u8,
+        own_share: Share,
+        requested_by: &str,
+    ) -> TappResult<Self> {
+        let mut current = report.lock().unwrap();
+        if let Some(active) = current.as_ref().filter(|r| r.state.is_active()) {
+            return Err(TappError::ClusterState(format!(
+                "This node is already recovering share {} for {}",
+                active.share_index, active.requested_by
+            )));
+        }
+        let now = crate::utils::current_timestamp();
+        *current = Some(RecoveryStatus {
+            state: RecoveryState::Collecting,
+            share_index,
+            collected: 0,
+            needed: threshold - 1,
+            requested_by: requested_by.to_string(),
+            contributors: Vec::new(),
+            started_at: now,
+            updated_at: now,
+            error: None,
+        });
+        info!(
+            cluster_id = %cluster_id,
+            share_index,
+            requested_by = %requested_by,
+            event = "CLUSTER_RECOVERY_STARTED",
+            "Recovering a member's share as coordinator"
+        );
+        Ok(Self {
+            report,
+            cluster_id: cluster_id.to_string(),
+            share_index,
+            threshold,
+            shares: vec![own_share],
+            finished: false,
+        })
+    }
+
+    /// Share being recovered
+    pub fn share_index(&self) -> u8 {
+        self.share_index
+    }
+
+    /// Shares of other members still to collect
+    pub fn missing(&self) -> usize {
+        (self.threshold as usize).saturating_sub(self.shares.len())
+    }
+
+    /// Add the share `contributor` handed over
+    pub fn add(&mut self, share: Share, contributor: &str) -> TappResult<()> {
+        let reason = if share.index == self.share_index {
+            Some("is the share being recovered")
+        } else if self.shares.iter().any(|s| s.index == share.index) {
+            Some("was already collected")
+        } else if self.missing() == 0 {
+            Some("is not needed any more")
+        } else {
+            None
+        };
+        if let Some(reason) = reason {
+            return Err(TappError::InvalidParameter {
+                field: "share".to_string(),
+                reason: format!("share {} {}", share.index, reason),
+            });
+        }
+
+        self.shares.push(share);
+        let collected = self.shares.len() as u8 - 1;
+        self.update(|report| {
+            report.collected = collected;
+            report.contributors.push(contributor.to_string());
+        });
+        info!(
+            cluster_id = %self.cluster_id,
+            share_index = self.share_index,
+            contributor = %contributor,
+            missing = self.missing(),
+            "Collected a share for the recovery"
+        );
+        Ok(())
+    }
+
+    /// Derive the share being recovered; the collected shares are wiped
+    pub fn reconstruct(&mut self) -> TappResult<Share> {
+        if self.missing() > 0 {
+            return Err(TappError::ClusterState(format!(
+                "Collected {} of the {} shares of other members needed to recover share {}",
+                self.shares.len() - 1,
+                self.threshold - 1,
+                self.share_index
+            )));
+        }
+        self.update(|report| report.state = RecoveryState::Reconstructing);
+        let share = sss::share_at(&self.shares, self.share_index);
+        // Dropping the shares wipes them
+        self.shares.clear();
+        share
+    }
+
+    /// The share reached the replacement node
+    pub fn delivered(mut self) {
+        self.finish(RecoveryState::Delivered, None);
+        info!(
+            cluster_id = %self.cluster_id,
+            share_index = self.share_index,
+            event = "CLUSTER_RECOVERY_DELIVERED",
+            "Recovered share sent to the replacement node"
+        );
+    }
+
+    /// Give up because of `error`
+    pub fn abort(mut self, error: &TappError) {
+        self.finish(RecoveryState::Aborted, Some(error.to_string()));
+        warn!(
+            cluster_id = %self.cluster_id,
+            share_index = self.share_index,
+            error = %error,
+            event = "CLUSTER_RECOVERY_ABORTED",
+            "Recovery aborted; collected shares were wiped"
+        );
+    }
+
+    fn finish(&mut self, state: RecoveryState, error: Option<String>) {
+        self.finished = true;
+        self.shares.clear();
+        self.update(|report| {
+            report.state = state;
+            report.error = error;
+        });
+    }
+
+    fn update(&self, change: impl FnOnce(&mut RecoveryStatus)) {
+        if let Some(report) = self.report.lock().unwrap().as_mut() {
+            change(report);
+            report.updated_at = crate::utils::current_timestamp();
+        }
+    }
+}
+
+impl Drop for Recovery<'_> {
+    fn drop(&mut self) {
+        if !self.finished {
+            self.finish(
+                RecoveryState::Aborted,
+                Some("The recovery was interrupted".to_string()),
+            );
+            warn!(
+                cluster_id = %self.cluster_id,
+                share_index = self.share_index,
+                event = "CLUSTER_RECOVERY_ABORTED",
+                "Recovery interrupted; collected shares were wiped"
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_recovery_lifecycle() {
+        let shares = sss::split(&[7; 32], 5, 3).unwrap();
+        let report = Mutex::new(None);
+        let state = || report.lock().unwrap().as_ref().unwrap().state;
+
+        let mut recovery =
+            Recovery::begin(&report, "c1", 5, 3, shares[0].clone(), "0xnew").unwrap();
+        assert!(matches!(
+            Recovery::begin(&report, "c1", 4, 3, shares[0].clone(), "0xother"),
+            Err(TappError::ClusterState(_))
+        ));
+        assert_eq!(recovery.missing(), 2);
+        for rejected in [&shares[0], &shares[4]] {
+            assert!(recovery.add(rejected.clone(), "0xpeer").is_err());
+        }
+        recovery.add(shares[1].clone(), "0xb").unwrap();
+        assert!(recovery.add(shares[1].clone(), "0xb").is_err());
+        assert!(matches!(
+            recovery.reconstruct(),
+            Err(TappError::ClusterState(_))
+        ));
+        recovery.add(shares[2].clone(), "0xc").unwrap();
+        assert_eq!(recovery.missing(), 0);
+        assert!(recovery.add(shares[3].clone(), "0xd").is_err());
+
+        assert_eq!(recovery.reconstruct().unwrap(), shares[4]);
+        assert_eq!(state(), RecoveryState::Reconstructing);
+        recovery.delivered();
+        let delivered = report.lock().unwrap().clone().unwrap();
+        assert_eq!(delivered.state, RecoveryState::Delivered);
+        assert_eq!((delivered.collected, delivered.needed), (2, 2));
+        assert_eq!(delivered.contributors, vec!["0xb", "0xc"]);
+    }
+
+    #[test]
+    fn test_dropped_recovery_is_aborted() {
+        let shares = sss::split(&[7; 32], 5, 3).unwrap();
+        let report = Mutex::new(None);
+        let mut recovery =
+            Recovery::begin(&report, "c1", 5, 3, shares[0].clone(), "0xnew").unwrap();
+        recovery.add(shares[1].clone(), "0xb").unwrap();
+        drop(recovery);
+
+        let aborted = report.lock().unwrap().clone().unwrap();
+        assert_eq!(aborted.state, RecoveryState::Aborted);
+        assert_eq!(aborted.collected, 1);
+        assert!(aborted.error.is_some());
+
+        // Another recovery can start
+        let recovery = Recovery::begin(&report, "c1", 5, 3, shares[0].clone(), "0xnew").unwrap();
+        recovery.abort(&TappError::ClusterState("gave up".to_string()));
+        let aborted = report.lock().unwrap().clone().unwrap();
+        assert_eq!(
+            aborted.error.as_deref(),
+            Some("Cluster state conflict: gave up")
+        );
+    }
+}
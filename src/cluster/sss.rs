@@ -0,0 +1,257 @@
+//! Shamir secret sharing over GF(2^8)
+//!
+//! Every byte of the secret is the constant term of its own random
+//! polynomial of degree `threshold - 1`; share `x` holds the polynomials'
+//! values at `x` (1 to 255). Any `threshold` shares give the secret back by
+//! Lagrange interpolation at 0, fewer reveal nothing about it. Field
+//! arithmetic uses the AES polynomial (x^8 + x^4 + x^3 + x + 1) without
+//! lookup tables, so it takes the same time whatever the values.
+
+use crate::error::{TappError, TappResult};
+use ring::rand::{SecureRandom, SystemRandom};
+
+/// Most shares a secret can be split into (share indices are 1 to 255)
+pub const MAX_SHARES: u8 = 255;
+
+/// One share of a secret
+/// Its value is wiped when it is dropped.
+#[derive(Clone, PartialEq, Eq)]
+pub struct Share {
+    /// Where the polynomials were evaluated, 1 to 255
+    pub index: u8,
+    pub value: Vec<u8>,
+}
+
+impl std::fmt::Debug for Share {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Share")
+            .field("index", &self.index)
+            .field("len", &self.value.len())
+            .finish()
+    }
+}
+
+impl Drop for Share {
+    fn drop(&mut self) {
+        wipe(&mut self.value);
+    }
+}
+
+/// Overwrite key material before its memory is released
+pub fn wipe(bytes: &mut [u8]) {
+    bytes.fill(0);
+    // Keep the writes from being optimized away as dead stores
+    std::hint::black_box(bytes);
+}
+
+fn gf_mul(mut a: u8, mut b: u8) -> u8 {
+    let mut product = 0;
+    for _ in 0..8 {
+        product ^= a & 0u8.wrapping_sub(b & 1);
+        let carry = a >> 7;
+        a = (a << 1) ^ (0x1b & 0u8.wrapping_sub(carry));
+        b >>= 1;
+    }
+    product
+}
+
+/// Multiplicative inverse (a^254); 0 has none and maps to 0
+fn gf_inv(a: u8) -> u8 {
+    let mut result = 1;
+    let mut base = a;
+    let mut exponent = 254u8;
+    while exponent > 0 {
+        if exponent & 1 == 1 {
+            result = gf_mul(result, base);
+        }
+        base = gf_mul(base, base);
+        exponent >>= 1;
+    }
+    result
+}
+
+/// Split `secret` into `total` shares, any `threshold` of which recover it
+/// Shares get indices 1 to `total`.
+pub fn split(secret: &[u8], total: u8, threshold: u8) -> TappResult<Vec<Share>> {
+    if threshold < 2 || threshold > total {
+        return Err(TappError::InvalidParameter {
+            field: "threshold".to_string(),
+            reason: format!("must be between 2 and the number of shares ({})", total),
+        });
+    }
+    if secret.is_empty() {
+        return Err(TappError::InvalidParameter {
+            field: "secret".to_string(),
+            reason: "must not be empty".to_string(),
+        });
+    }
+
+    // Coefficients of x^1 to x^(threshold - 1) of every byte's polynomial
+    let degree = threshold as usize - 1;
+    let mut coefficients = vec![0u8; secret.len() * degree];
+    SystemRandom::new()
+        .fill(&mut coefficients)
+        .map_err(|_| TappError::Crypto("No randomness for secret sharing".to_string()))?;
+
+    let shares = (1..=total)
+        .map(|x| {
+            let value = secret
+                .iter()
+                .zip(coefficients.chunks(degree))
+                .map(|(&constant, higher)| {
+                    // Horner's rule, from the highest coefficient down
+                    higher
+                        .iter()
+                        .rev()
+                        .chain([&constant])
+                        .fold(0, |acc, &c| gf_mul(acc, x) ^ c)
+                })
+                .collect();
+            Share { index: x, value }
+        })
+        .collect();
+    wipe(&mut coefficients);
+    Ok(shares)
+}
+
+/// Recover the secret from at least `threshold` shares of it
+/// With fewer shares the result is unrelated to the secret; the caller has
+/// to know the threshold.
+pub fn combine(shares: &[Share]) -> TappResult<Vec<u8>> {
+    interpolate(shares, 0)
+}
+
+/// Share `index` of the secret, from at least `threshold` other shares
+/// Same as splitting the secret recovered by `combine` again with the same
+/// polynomials, without the secret ever being assembled.
+pub fn share_at(shares: &[Share], index: u8) -> TappResult<Share> {
+    if index == 0 {
+        return Err(TappError::InvalidParameter {
+            field: "index".to_string(),
+            reason: "share index 0 is the secret".to_string(),
+        });
+    }
+    Ok(Share {
+        index,
+        value: interpolate(shares, index)?,
+    })
+}
+
+/// Values of the shares' polynomials at `x`
+fn interpolate(shares: &[Share], x: u8) -> TappResult<Vec<u8>> {
+    let Some(first) = shares.first() else {
+        return Err(TappError::InvalidParameter {
+            field: "shares".to_string(),
+            reason: "no shares given".to_string(),
+        });
+    };
+    for (i, share) in shares.iter().enumerate() {
+        if share.index == 0 {
+            return Err(TappError::InvalidParameter {
+                field: "shares".to_string(),
+                reason: "share index 0 is not a share".to_string(),
+            });
+        }
+        if share.value.len() != first.value.len() {
+            return Err(TappError::InvalidParameter {
+                field: "shares".to_string(),
+                reason: "shares have different lengths".to_string(),
+            });
+        }
+        if shares[..i].iter().any(|other| other.index == share.index) {
+            return Err(TappError::InvalidParameter {
+                field: "shares".to_string(),
+                reason: format!("share {} is given twice", share.index),
+            });
+        }
+    }
+
+    let mut values = vec![0u8; first.value.len()];
+    for share in shares {
+        // Lagrange basis polynomial of this share, at x
+        let basis = shares
+            .iter()
+            .filter(|other| other.index != share.index)
+            .fold(1, |acc, other| {
+                gf_mul(
+                    acc,
+                    gf_mul(x ^ other.index, gf_inv(other.index ^ share.index)),
+                )
+            });
+        for (value, &y) in values.iter_mut().zip(&share.value) {
+            *value ^= gf_mul(y, basis);
+        }
+    }
+    Ok(values)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_field_arithmetic() {
+        assert_eq!(gf_mul(0x57, 0x83), 0xc1);
+        assert_eq!(gf_mul(0x57, 0x13), 0xfe);
+        for a in 1..=255u8 {
+            assert_eq!(gf_mul(a, gf_inv(a)), 1, "{}", a);
+        }
+    }
+
+    #[test]
+    fn test_any_threshold_shares_recover_the_secret() {
+        let secret: Vec<u8> = (0..32).collect();
+        let shares = split(&secret, 5, 3).unwrap();
+        assert_eq!(
+            shares.iter().map(|share| share.index).collect::<Vec<_>>(),
+            vec![1, 2, 3, 4, 5]
+        );
+
+        for a in 0..5 {
+            for b in a + 1..5 {
+                for c in b + 1..5 {
+                    let subset = [shares[a].clone(), shares[b].clone(), shares[c].clone()];
+                    assert_eq!(combine(&subset).unwrap(), secret);
+                }
+            }
+        }
+        assert_eq!(combine(&shares).unwrap(), secret);
+
+        // Two shares are not enough
+        assert_ne!(combine(&shares[..2]).unwrap(), secret);
+    }
+
+    #[test]
+    fn test_lost_shares_are_derived_from_threshold_others() {
+        let secret: Vec<u8> = (0..32).rev().collect();
+        let shares = split(&secret, 5, 3).unwrap();
+        let others = [shares[0].clone(), shares[2].clone(), shares[3].clone()];
+        for lost in [1, 4] {
+            assert_eq!(share_at(&others, lost + 1).unwrap(), shares[lost]);
+        }
+        assert_eq!(share_at(&others, 3).unwrap(), shares[2]);
+
+        // The derived share recovers the secret like the lost one
+        let derived = share_at(&others, 5).unwrap();
+        assert_eq!(
+            combine(&[shares[1].clone(), shares[2].clone(), derived]).unwrap(),
+            secret
+        );
+        assert!(share_at(&others, 0).is_err());
+        assert!(share_at(&[], 5).is_err());
+    }
+
+    #[test]
+    fn test_rejects_bad_parameters() {
+        assert!(split(b"secret", 3, 1).is_err());
+        assert!(split(b"secret", 3, 4).is_err());
+        assert!(split(b"", 3, 2).is_err());
+
+        let shares = split(b"secret", 3, 2).unwrap();
+        assert!(combine(&[]).is_err());
+        assert!(combine(&[shares[0].clone(), shares[0].clone()]).is_err());
+        let mut short = shares[1].clone();
+        short.value.pop();
+        assert!(combine(&[shares[0].clone(), short]).is_err());
+    }
+}
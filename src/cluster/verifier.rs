@@ -0,0 +1,198 @@
+//! Checks of the other side of a key share exchange
+//!
+//! Both sides of RequestKeyShare present evidence whose report data binds
+//! their node key (see `exchange`). An `EvidenceVerifier` reads what the
+//! evidence attests: its report data and the code hash of the node, here
+//! SHA-256(MRTD || RTMR0 || RTMR1 || RTMR2), the firmware and OS image the
+//! node booted. RTMR3 is left out since app deployments extend it. A
+//! `Whitelist` then decides whether a node with that signer address and code
+//! hash may take part.
+
+use crate::boot::quote::{parse_quote, quote_from_evidence, TDX_TEE_TYPE};
+use crate::config::{ClusterConfig, EvidenceVerifierKind};
+use crate::error::{AttestationError, TappResult};
+use base64::Engine;
+use futures_util::future::BoxFuture;
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+
+/// Size of each measurement register
+const MEASUREMENT_LEN: usize = 48;
+
+/// What a verifier read from evidence
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AttestedNode {
+    /// Report data, as attested (64 bytes)
+    pub report_data: Vec<u8>,
+    /// Hex code hash of the node
+    pub code_hash: String,
+}
+
+/// Reads evidence of a node
+pub trait EvidenceVerifier: Send + Sync {
+    fn verify(&self, evidence: &[u8]) -> TappResult<AttestedNode>;
+}
+
+/// Verifier selected by `cluster.evidence_verifier`
+pub fn from_config(config: &ClusterConfig) -> Box<dyn EvidenceVerifier> {
+    match config.evidence_verifier {
+        EvidenceVerifierKind::Tdx => Box::new(TdxVerifier),
+        EvidenceVerifierKind::Sample => Box::new(SampleVerifier),
+    }
+}
+
+/// Code hash of a node with the given measurement registers
+pub fn code_hash(mr_td: &[u8], rtmrs: &[Vec<u8>]) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(mr_td);
+    for rtmr in &rtmrs[..3] {
+        hasher.update(rtmr);
+    }
+    hex::encode(hasher.finalize())
+}
+
+/// TDX quotes, raw or in the attester's JSON
+/// The quote's header and TD report are checked; its signature is not, so
+/// the quote must reach this node over a channel it trusts not to forge
+/// quotes (DCAP collateral checks are not available in this build).
+pub struct TdxVerifier;
+
+impl EvidenceVerifier for TdxVerifier {
+    fn verify(&self, evidence: &[u8]) -> TappResult<AttestedNode> {
+        let quote = parse_quote(&quote_from_evidence(evidence)?)?;
+        if quote.tee_type != TDX_TEE_TYPE {
+            return Err(AttestationError::InvalidQuote {
+                reason: format!("TEE type {:#x} is not TDX", quote.tee_type),
+            }
+            .into());
+        }
+        Ok(AttestedNode {
+            code_hash: code_hash(&quote.report.mr_td, &quote.report.rtmrs),
+            report_data: quote.report.report_data,
+        })
+    }
+}
+
+/// Evidence of the sample attester: `{"svn": "1", "report_data": "<base64>"}`
+#[derive(Deserialize)]
+struct SampleEvidence {
+    report_data: String,
+}
+
+/// The sample attester's evidence, attesting a node whose registers are all
+/// zero. Anyone can produce it; for testing without a TEE only.
+pub struct SampleVerifier;
+
+impl EvidenceVerifier for SampleVerifier {
+    fn verify(&self, evidence: &[u8]) -> TappResult<AttestedNode> {
+        let evidence: SampleEvidence = serde_json::from_slice(evidence).map_err(|e| {
+            AttestationError::UnsupportedEvidenceFormat {
+                format: format!("not sample evidence ({})", e),
+            }
+        })?;
+        let report_data = base64::engine::general_purpose::STANDARD
+            .decode(evidence.report_data.trim())
+            .map_err(|e| AttestationError::InvalidQuote {
+                reason: format!("sample report data is not base64: {}", e),
+            })?;
+        let zero = vec![0u8; MEASUREMENT_LEN];
+        Ok(AttestedNode {
+            report_data,
+            code_hash: code_hash(&zero, &[zero.clone(), zero.clone(), zero.clone()]),
+        })
+    }
+}
+
+/// Decides which nodes take part in share exchanges
+/// `signer_address` is 0x-prefixed and lowercase, `code_hash` lowercase hex.
+pub trait Whitelist: Send + Sync {
+    fn is_allowed<'a>(
+        &'a self,
+        signer_address: &'a str,
+        code_hash: &'a str,
+    ) -> BoxFuture<'a, TappResult<bool>>;
+}
+
+/// `cluster.allowed_signers` and `cluster.allowed_code_hashes`: both the
+/// signer and the code hash must be listed
+pub struct StaticWhitelist {
+    signers: Vec<String>,
+    code_hashes: Vec<String>,
+}
+
+impl StaticWhitelist {
+    pub fn new(config: &ClusterConfig) -> Self {
+        Self {
+            signers: config
+                .allowed_signers
+                .iter()
+                .map(|signer| signer.to_lowercase())
+                .collect(),
+            code_hashes: config
+                .allowed_code_hashes
+                .iter()
+                .map(|hash| hash.to_lowercase())
+                .collect(),
+        }
+    }
+}
+
+impl Whitelist for StaticWhitelist {
+    fn is_allowed<'a>(
+        &'a self,
+        signer_address: &'a str,
+        code_hash: &'a str,
+    ) -> BoxFuture<'a, TappResult<bool>> {
+        let allowed = self.signers.iter().any(|signer| signer == signer_address)
+            && self.code_hashes.iter().any(|hash| hash == code_hash);
+        Box::pin(async move { Ok(allowed) })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sample_evidence() {
+        let report_data = vec![7u8; 64];
+        let evidence = serde_json::json!({
+            "svn": "1",
+            "report_data": base64::engine::general_purpose::STANDARD.encode(&report_data),
+        });
+        let node = SampleVerifier
+            .verify(evidence.to_string().as_bytes())
+            .unwrap();
+        assert_eq!(node.report_data, report_data);
+        let zero = vec![0u8; MEASUREMENT_LEN];
+        assert_eq!(node.code_hash, code_hash(&zero, &vec![zero.clone(); 4]));
+
+        assert!(SampleVerifier.verify(b"raw quote").is_err());
+        assert!(TdxVerifier.verify(evidence.to_string().as_bytes()).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_static_whitelist() {
+        let signer = "0x00112233445566778899AABBCCDDEEFF00112233";
+        let hash = "ab".repeat(32);
+        let whitelist = StaticWhitelist::new(&ClusterConfig {
+            allowed_signers: vec![signer.to_string()],
+            allowed_code_hashes: vec![hash.to_uppercase()],
+            ..Default::default()
+        });
+        let signer = signer.to_lowercase();
+        assert!(whitelist.is_allowed(&signer, &hash).await.unwrap());
+        assert!(!whitelist
+            .is_allowed(&signer, &"cd".repeat(32))
+            .await
+            .unwrap());
+        assert!(!whitelist
+            .is_allowed("0x0000000000000000000000000000000000000000", &hash)
+            .await
+            .unwrap());
+        assert!(!StaticWhitelist::new(&ClusterConfig::default())
+            .is_allowed(&signer, &hash)
+            .await
+            .unwrap());
+    }
+}
@@ -1,6 +1,9 @@
 use crate::error::{ConfigError, TappResult};
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 
 #[derive(Debug, Clone, Serialize, Default)]
 pub struct EvidenceServiceConfig {
@@ -16,111 +19,2342 @@ pub struct BootServiceConfig {
     #[serde(default)]
     pub aa_config_path: Option<String>,
 
-    /// Docker socket path
+    /// Rewrite an existing `aa_config_path` file from `[boot.aa]` at startup
+    /// (a missing file is always written; an existing one is left alone
+    /// otherwise)
+    #[serde(default)]
+    pub manage_aa_config: bool,
+
+    /// Contents of the attestation agent configuration file
+    #[serde(default)]
+    pub aa: AaConfig,
+
+    /// Container engine deployments run on
+    #[serde(default)]
+    pub runtime: ContainerRuntime,
+
+    /// Socket of the Docker or Podman API; with runtime "podman", the Docker
+    /// default stands for the first podman socket found (see `runtime_socket`)
     #[serde(default = "default_docker_socket")]
     pub socket_path: String,
 
-    /// Container startup timeout in seconds
-    #[serde(default = "default_container_timeout")]
-    pub container_timeout_seconds: u64,
+    /// Compose CLI, as a program and its leading arguments (default: "docker
+    /// compose", or "podman compose" with runtime "podman");
+    /// "podman-compose" runs the standalone tool, whose options differ
+    #[serde(default)]
+    pub compose_command: Option<String>,
+
+    /// Networks (CIDR) of same-host containers, which may call the local
+    /// access RPCs; empty for the runtime's default networks (see
+    /// `ContainerRuntime::default_networks`)
+    #[serde(default)]
+    pub container_networks: Vec<String>,
+
+    /// Deploy images whose platform (StartApp `platform`, or a service's
+    /// `platform:`) the host cannot run natively, relying on emulation
+    #[serde(default)]
+    pub allow_emulation: bool,
+
+    /// Directory holding one directory per deployed app (compose file, mount
+    /// files, deploy logs); created owner-only at startup if missing
+    #[serde(default = "default_data_dir")]
+    pub data_dir: PathBuf,
+
+    /// Container startup timeout in seconds
+    #[serde(default = "default_container_timeout")]
+    pub container_timeout_seconds: u64,
+
+    /// How long COMPLETED/FAILED tasks stay queryable via GetTaskStatus
+    #[serde(default = "default_task_retention")]
+    pub task_retention_seconds: u64,
+
+    /// Maximum number of finished tasks kept (oldest are dropped first)
+    #[serde(default = "default_max_finished_tasks")]
+    pub max_finished_tasks: usize,
+
+    /// Automatic retries of a deployment failing in a Docker step, with
+    /// exponential backoff, before the task is marked FAILED
+    #[serde(default)]
+    pub auto_retry_attempts: u32,
+
+    /// Retries of idempotent Docker daemon calls and of `docker compose
+    /// pull` on transient failures (`docker compose up` is never retried here)
+    #[serde(default)]
+    pub retry: RetryConfig,
+
+    /// Deployments running at once (at least 1); later ones are queued
+    #[serde(default = "default_max_concurrent_deployments")]
+    pub max_concurrent_deployments: usize,
+
+    /// Deployments waiting for a slot; StartApp gets RESOURCE_EXHAUSTED beyond it
+    #[serde(default = "default_max_queued_deployments")]
+    pub max_queued_deployments: usize,
+
+    /// Directory where tasks are persisted across restarts (empty keeps them in memory only)
+    #[serde(default = "default_task_state_dir")]
+    pub task_state_dir: String,
+
+    /// File keeping the runtime measurement extends made since the guest
+    /// booted, for VerifyRtmrReplay (empty keeps them in memory only)
+    #[serde(default = "default_extend_history_path")]
+    pub extend_history_path: String,
+
+    /// Rebuild the apps deployed before a restart (measurements, compose and
+    /// mount file content) from `data_dir` at startup; off for test
+    /// environments that should start empty
+    #[serde(default = "default_restore_apps")]
+    pub restore_apps: bool,
+
+    /// Deploy output files (`deploy-<task_id>.log`) kept per app, oldest deleted first
+    #[serde(default = "default_deploy_logs_per_app")]
+    pub deploy_logs_per_app: usize,
+
+    /// Caller container verification for app-scoped secret operations
+    #[serde(default)]
+    pub caller_identity: CallerIdentityConfig,
+
+    /// Images StartApp may deploy (any image if absent or disabled)
+    #[serde(default)]
+    pub image_policy: Option<ImagePolicyConfig>,
+
+    /// Vulnerability scan of each deployment's images before they start
+    /// (no scan if absent or disabled)
+    #[serde(default)]
+    pub image_scan: Option<ImageScanConfig>,
+
+    /// Limits of the blobs apps keep with SealData
+    #[serde(default)]
+    pub sealed_data: SealedDataConfig,
+
+    /// What apps may sign with their keys (SignTransaction)
+    #[serde(default)]
+    pub key_policy: KeyPolicyConfig,
+}
+
+/// Container engine of the boot service
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ContainerRuntime {
+    #[default]
+    Docker,
+    /// Podman through its Docker-compatible API socket
+    Podman,
+}
+
+impl ContainerRuntime {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ContainerRuntime::Docker => "docker",
+            ContainerRuntime::Podman => "podman",
+        }
+    }
+
+    /// Compose CLI used when `boot.compose_command` is not set
+    pub fn default_compose_command(self) -> &'static str {
+        match self {
+            ContainerRuntime::Docker => "docker compose",
+            ContainerRuntime::Podman => "podman compose",
+        }
+    }
+
+    /// Sockets the runtime's API is served on, rootful first
+    pub fn default_sockets(self) -> Vec<PathBuf> {
+        match self {
+            ContainerRuntime::Docker => vec![PathBuf::from(default_docker_socket())],
+            ContainerRuntime::Podman => {
+                let mut sockets = vec![PathBuf::from("/run/podman/podman.sock")];
+                if let Some(runtime_dir) = std::env::var_os("XDG_RUNTIME_DIR") {
+                    sockets.push(PathBuf::from(runtime_dir).join("podman/podman.sock"));
+                }
+                sockets
+            }
+        }
+    }
+
+    /// Networks the runtime gives containers by default: Docker's default
+    /// bridge and the pool of its user-defined networks (172.17.0.0 to
+    /// 172.31.255.255), or podman's default network and the first subnets
+    /// of its pool
+    pub fn default_networks(self) -> &'static [&'static str] {
+        match self {
+            ContainerRuntime::Docker => &[
+                "172.17.0.0/16",
+                "172.18.0.0/15",
+                "172.20.0.0/14",
+                "172.24.0.0/13",
+            ],
+            ContainerRuntime::Podman => &["10.88.0.0/16", "10.89.0.0/16"],
+        }
+    }
+}
+
+impl BootServiceConfig {
+    /// Socket of the container runtime's API
+    /// `socket_path` as set, except that with runtime "podman" the Docker
+    /// default is replaced by the first podman socket that exists (the
+    /// rootful one if none does).
+    pub fn runtime_socket(&self) -> String {
+        if self.runtime == ContainerRuntime::Docker || self.socket_path != default_docker_socket() {
+            return self.socket_path.clone();
+        }
+        let sockets = self.runtime.default_sockets();
+        sockets
+            .iter()
+            .find(|socket| socket.exists())
+            .unwrap_or(&sockets[0])
+            .to_string_lossy()
+            .to_string()
+    }
+
+    /// `container_networks`, or the runtime's default networks
+    pub fn effective_container_networks(&self) -> Vec<String> {
+        if self.container_networks.is_empty() {
+            self.runtime
+                .default_networks()
+                .iter()
+                .map(|network| network.to_string())
+                .collect()
+        } else {
+            self.container_networks.clone()
+        }
+    }
+}
+
+/// Attestation agent settings rendered into `aa_config_path`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AaConfig {
+    /// Record runtime measurements in the AA event log
+    #[serde(default = "default_eventlog_enabled")]
+    pub eventlog_enabled: bool,
+
+    /// Event log file (`eventlog_config.eventlog_path`; the AA's own default
+    /// if unset)
+    pub eventlog_path: Option<String>,
+
+    /// `[token_configs]` of the AA (attestation service and KBS token
+    /// settings), copied as is
+    /// Example: { coco_as = { url = "http://127.0.0.1:8080" } }
+    #[serde(default)]
+    pub token_configs: toml::Table,
+}
+
+/// Caller container verification
+/// Requests for app X arriving from a Docker network address must come from
+/// a container of X's compose project, or from a trusted helper.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CallerIdentityConfig {
+    /// Verify the container behind Docker network callers
+    #[serde(default = "default_caller_identity_enabled")]
+    pub enabled: bool,
+
+    /// Compose projects or container names allowed to act for any app
+    #[serde(default)]
+    pub trusted_helpers: Vec<String>,
+
+    /// How long the address to container mapping is cached, in seconds
+    #[serde(default = "default_caller_identity_cache_ttl")]
+    pub cache_ttl_seconds: u64,
+}
+
+/// Limits of sealed data (SealData), per app
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SealedDataConfig {
+    /// Largest plaintext a blob may hold, in bytes
+    #[serde(default = "default_max_sealed_blob_bytes")]
+    pub max_blob_bytes: usize,
+
+    /// Blobs an app may keep; replacing a label does not count
+    #[serde(default = "default_max_sealed_blobs_per_app")]
+    pub max_blobs_per_app: usize,
+}
+
+/// What apps may sign with their keys
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct KeyPolicyConfig {
+    /// Chain IDs SignTransaction signs for, per internal app ID
+    /// ("namespace/app" for namespaced apps). Apps not listed may sign for
+    /// any chain; an empty list allows none.
+    #[serde(default)]
+    pub allowed_chain_ids: HashMap<String, Vec<u64>>,
+}
+
+impl KeyPolicyConfig {
+    /// Whether `app_id` may sign transactions for `chain_id`
+    pub fn allows_chain(&self, app_id: &str, chain_id: u64) -> bool {
+        self.allowed_chain_ids
+            .get(app_id)
+            .is_none_or(|chain_ids| chain_ids.contains(&chain_id))
+    }
+}
+
+/// Image allowlist for deployments
+/// Every `image:` of a compose file is normalized (`nginx` is
+/// `docker.io/library/nginx:latest`) and checked: deny entries win, then the
+/// image must come from an allowed registry or be in `allow`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ImagePolicyConfig {
+    /// Enforce the policy
+    #[serde(default)]
+    pub enabled: bool,
+
+    /// Registries or repository prefixes whose images are allowed
+    /// Examples: "registry.0g.dev", "ghcr.io/0glabs/*", "localhost:5000"
+    #[serde(default)]
+    pub allowed_registries: Vec<String>,
+
+    /// Images allowed from any registry; without a tag or digest every tag
+    /// matches. Examples: "nginx:1.27", "redis@sha256:<digest>"
+    /// (if `allowed_registries` and this are empty, every image not denied
+    /// is allowed)
+    #[serde(default)]
+    pub allow: Vec<String>,
+
+    /// Images that are always rejected, matched like `allow`
+    #[serde(default)]
+    pub deny: Vec<String>,
+}
+
+impl ImagePolicyConfig {
+    /// Validate the policy section
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        crate::boot::image_policy::ImagePolicy::new(self).map(|_| ())
+    }
+}
+
+/// Vulnerability scan of the images of a deployment, between the pull and
+/// `up`
+/// The scanner is `command` or `endpoint`, given one image at a time as
+/// `<repository>@<digest>` (or the image ID of an image without a digest).
+/// Trivy (`--format json`) and grype (`-o json`) reports are understood.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImageScanConfig {
+    /// Scan images before deploying them
+    #[serde(default)]
+    pub enabled: bool,
+
+    /// Scanner program (an absolute path) and its arguments, run without a
+    /// shell; `{image}` in an argument is replaced by the image
+    /// Example: ["/usr/local/bin/trivy", "image", "--quiet", "--format", "json", "{image}"]
+    #[serde(default)]
+    pub command: Vec<String>,
+
+    /// HTTP scanner instead of `command`: `{"image": "<image>"}` is POSTed
+    /// to it and the answer is the scanner's JSON report
+    pub endpoint: Option<String>,
+
+    /// Lowest severity that fails a deployment
+    #[serde(default)]
+    pub severity_threshold: ScanSeverity,
+
+    /// How long the scan of one image may take, in seconds
+    #[serde(default = "default_image_scan_timeout")]
+    pub timeout_seconds: u64,
+
+    /// Deploy anyway, with a warning, when the scanner fails or times out
+    /// (by default the deployment fails)
+    #[serde(default)]
+    pub fail_open: bool,
+}
+
+impl ImageScanConfig {
+    /// Validate the scan section
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        let invalid = |field: &str, reason: &str| ConfigError::InvalidValue {
+            field: format!("boot.image_scan.{}", field),
+            reason: reason.to_string(),
+        };
+        match (self.command.first(), &self.endpoint) {
+            (Some(_), Some(_)) => {
+                return Err(invalid("endpoint", "cannot be set together with command"))
+            }
+            (None, None) if self.enabled => {
+                return Err(invalid("command", "a command or an endpoint is required"))
+            }
+            (Some(program), None) if !Path::new(program).is_absolute() => {
+                return Err(invalid("command", "the program must be an absolute path"))
+            }
+            (None, Some(endpoint))
+                if !endpoint.starts_with("https://") && !endpoint.starts_with("http://") =>
+            {
+                return Err(invalid("endpoint", "must be an http:// or https:// URL"))
+            }
+            _ => {}
+        }
+        if self.timeout_seconds == 0 {
+            return Err(invalid("timeout_seconds", "must be at least 1"));
+        }
+        Ok(())
+    }
+}
+
+/// Severity of a vulnerability finding, lowest first
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ScanSeverity {
+    Low,
+    Medium,
+    #[default]
+    High,
+    Critical,
+}
+
+impl ScanSeverity {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ScanSeverity::Low => "low",
+            ScanSeverity::Medium => "medium",
+            ScanSeverity::High => "high",
+            ScanSeverity::Critical => "critical",
+        }
+    }
+}
+
+/// Logging configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LoggingConfig {
+    /// Log level
+    #[serde(default = "default_log_level")]
+    pub level: String,
+
+    /// Log format (json or pretty)
+    #[serde(default = "default_log_format")]
+    pub format: String,
+
+    /// Log file path (if None, logs to stdout)
+    pub file_path: Option<PathBuf>,
+
+    /// Maximum log file size in MB
+    #[serde(default = "default_max_log_size")]
+    pub max_file_size_mb: u64,
+
+    /// Maximum number of log files to keep
+    #[serde(default = "default_max_log_files")]
+    pub max_files: usize,
+
+    /// Seconds after which a filter set with SetLogLevel reverts to `level`
+    /// (0 = keep it until changed again)
+    #[serde(default = "default_level_revert_seconds")]
+    pub level_revert_seconds: u64,
+}
+
+/// Prefix of the environment variables overriding configuration values
+pub const ENV_PREFIX: &str = "TAPP_";
+
+/// Separator between the sections of an override's name
+const ENV_SEPARATOR: &str = "__";
+
+/// Shown in place of secrets when a configuration is printed
+pub const REDACTED: &str = "********";
+
+/// Main configuration structure for TAPP service
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TappConfig {
+    #[serde(default)]
+    pub logging: LoggingConfig,
+    #[serde(default)]
+    pub boot: BootServiceConfig,
+    #[serde(default)]
+    pub server: ServerConfig,
+    #[serde(default)]
+    pub kbs: Option<KbsConfig>,
+    #[serde(default)]
+    pub audit: Option<AuditConfig>,
+    #[serde(default)]
+    pub security: SecurityConfig,
+    #[serde(default)]
+    pub monitoring: MonitoringConfig,
+    #[serde(default)]
+    pub notifications: Option<NotificationConfig>,
+    #[serde(default)]
+    pub telemetry: Option<TelemetryConfig>,
+    #[serde(default)]
+    pub cluster: ClusterConfig,
+    #[serde(default)]
+    pub contract: Option<ContractConfig>,
+}
+
+impl TappConfig {
+    /// Load the configuration file with the `TAPP_*` environment overrides
+    /// Precedence, highest first: command-line arguments (applied by the
+    /// caller), environment variables, the file, built-in defaults.
+    /// `config_path` may also be a directory, whose `*.toml` files are merged
+    /// in lexical order (see `merge_dir`).
+    pub fn load(config_path: String) -> TappResult<Self> {
+        let table = if Path::new(&config_path).is_dir() {
+            merge_dir(Path::new(&config_path))?
+        } else {
+            let content =
+                std::fs::read_to_string(&config_path).map_err(|_| ConfigError::FileNotFound {
+                    path: config_path.clone(),
+                })?;
+            toml::from_str(&content).map_err(|e: toml::de::Error| ConfigError::ParseFailed {
+                reason: e.to_string(),
+            })?
+        };
+        let mut config = Self::from_table(table, std::env::vars())?;
+        config.resolve_api_keys(|name| std::env::var(name).ok())?;
+        Ok(config)
+    }
+
+    /// Built-in defaults with the `TAPP_*` environment overrides, for running
+    /// without a configuration file
+    pub fn from_env() -> TappResult<Self> {
+        let mut config = Self::parse("", std::env::vars())?;
+        config.resolve_api_keys(|name| std::env::var(name).ok())?;
+        Ok(config)
+    }
+
+    /// Read the API keys given as `key_file` or `key_env`, looking variables
+    /// up with `var`
+    pub fn resolve_api_keys(
+        &mut self,
+        var: impl Fn(&str) -> Option<String>,
+    ) -> Result<(), ConfigError> {
+        match &mut self.server.api_key {
+            Some(api_key) => api_key.resolve_keys(var),
+            None => Ok(()),
+        }
+    }
+
+    /// Parse TOML `content` and apply the overrides found in `vars`
+    /// `TAPP_SERVER__BIND_ADDRESS` sets `bind_address` in `[server]`: after
+    /// the prefix, `__` separates sections and names match case-insensitively.
+    /// A value is read as the type the setting has in the file or by default
+    /// (a TOML array or inline table for those), and a new one as a TOML
+    /// number, boolean or array, else as a string. Errors name the variable.
+    pub fn parse(
+        content: &str,
+        vars: impl IntoIterator<Item = (String, String)>,
+    ) -> Result<Self, ConfigError> {
+        let table =
+            toml::from_str(content).map_err(|e: toml::de::Error| ConfigError::ParseFailed {
+                reason: e.to_string(),
+            })?;
+        Self::from_table(table, vars)
+    }
+
+    /// Like `parse`, for an already parsed (or merged) file
+    pub fn from_table(
+        table: toml::Table,
+        vars: impl IntoIterator<Item = (String, String)>,
+    ) -> Result<Self, ConfigError> {
+        let parse_failed = |e: toml::de::Error| ConfigError::ParseFailed {
+            reason: e.to_string(),
+        };
+        // The file alone must be valid before variables are blamed
+        let config: Self = toml::Value::Table(table.clone())
+            .try_into()
+            .map_err(parse_failed)?;
+
+        let mut overrides: Vec<(String, Vec<String>, String)> = vars
+            .into_iter()
+            .filter_map(|(name, value)| {
+                let path = env_path(&name)?;
+                Some((name, path, value))
+            })
+            .collect();
+        if overrides.is_empty() {
+            return Ok(config);
+        }
+        overrides.sort();
+
+        // Current values tell the type each variable is read as
+        let current = match toml::Value::try_from(&config) {
+            Ok(toml::Value::Table(current)) => current,
+            _ => toml::Table::new(),
+        };
+        let mut values = Vec::with_capacity(overrides.len());
+        for (name, path, raw) in &overrides {
+            let value = env_value(name, raw, lookup(&current, path))?;
+            values.push((name.as_str(), path.as_slice(), value));
+        }
+
+        let merged = |skip: Option<&str>| -> Result<Self, ConfigError> {
+            let mut table = table.clone();
+            for (name, path, value) in &values {
+                if Some(*name) != skip {
+                    set_path(&mut table, name, path, value.clone())?;
+                }
+            }
+            toml::Value::Table(table)
+                .try_into()
+                .map_err(|e: toml::de::Error| ConfigError::ParseFailed {
+                    reason: e.message().to_string(),
+                })
+        };
+        match merged(None) {
+            Ok(config) => Ok(config),
+            // Blame the variable without which the configuration parses,
+            // or all of them when no single one is at fault
+            Err(ConfigError::ParseFailed { reason }) => {
+                let field = values
+                    .iter()
+                    .map(|(name, _, _)| *name)
+                    .find(|name| merged(Some(*name)).is_ok())
+                    .map(str::to_string)
+                    .unwrap_or_else(|| {
+                        let names: Vec<&str> = values.iter().map(|(name, _, _)| *name).collect();
+                        names.join(", ")
+                    });
+                Err(ConfigError::InvalidValue { field, reason })
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Copy that is safe to print: API keys, HMAC secrets and the webhook
+    /// signing secret are replaced with `REDACTED`
+    pub fn redacted(&self) -> Self {
+        let mut config = self.clone();
+        if let Some(api_key) = &mut config.server.api_key {
+            for entry in &mut api_key.keys {
+                match entry {
+                    // Keys read from a file or variable print as their source
+                    ApiKeyEntry::Named {
+                        key,
+                        key_file: Some(_),
+                        ..
+                    }
+                    | ApiKeyEntry::Named {
+                        key,
+                        key_env: Some(_),
+                        ..
+                    } => key.clear(),
+                    ApiKeyEntry::Inline(key) | ApiKeyEntry::Named { key, .. } => {
+                        *key = REDACTED.to_string();
+                    }
+                }
+            }
+        }
+        if let Some(secret) = config
+            .notifications
+            .as_mut()
+            .and_then(|notifications| notifications.secret.as_mut())
+        {
+            *secret = REDACTED.to_string();
+        }
+        config
+    }
+
+    /// Check the whole configuration, reporting every problem at once
+    /// Run after the environment and command-line overrides, so the values
+    /// checked are the ones the service will use.
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        let mut problems = Vec::new();
+        let mut invalid = |field: &str, reason: String| {
+            problems.push(ConfigError::InvalidValue {
+                field: field.to_string(),
+                reason,
+            })
+        };
+
+        // [server]
+        let server = &self.server;
+        if server.bind_address.0.is_empty() {
+            invalid(
+                "server.bind_address",
+                "at least one address is required".to_string(),
+            );
+        }
+        for (i, address) in server.bind_address.iter().enumerate() {
+            check_address(&mut invalid, "server.bind_address", address);
+            if server.bind_address.iter().take(i).any(|a| a == address) {
+                invalid(
+                    "server.bind_address",
+                    format!("'{}' is listed more than once", address),
+                );
+            }
+        }
+        if let Some(admin) = &server.admin_bind_address {
+            check_address(&mut invalid, "server.admin_bind_address", admin);
+            if server.bind_address.contains(admin) {
+                invalid(
+                    "server.admin_bind_address",
+                    "must differ from server.bind_address".to_string(),
+                );
+            }
+        }
+        for (field, value) in [
+            ("server.max_connections", server.max_connections as u64),
+            (
+                "server.max_tracked_nonces",
+                server.max_tracked_nonces as u64,
+            ),
+            (
+                "server.request_timeout_seconds",
+                server.request_timeout_seconds,
+            ),
+            (
+                "server.max_recv_message_mb",
+                server.max_recv_message_mb as u64,
+            ),
+            (
+                "server.max_send_message_mb",
+                server.max_send_message_mb as u64,
+            ),
+            (
+                "server.health.check_interval_seconds",
+                server.health.check_interval_seconds,
+            ),
+            (
+                "server.health.docker_failure_threshold",
+                server.health.docker_failure_threshold as u64,
+            ),
+            (
+                "server.health.attestation_failure_threshold",
+                server.health.attestation_failure_threshold as u64,
+            ),
+            (
+                "server.http2.keepalive_timeout_seconds",
+                server.http2.keepalive_timeout_seconds,
+            ),
+        ] {
+            if value == 0 {
+                invalid(field, "must be greater than 0".to_string());
+            }
+        }
+        let mut methods: Vec<_> = server.method_max_recv_message_mb.iter().collect();
+        methods.sort();
+        for (method, mb) in methods {
+            if *mb == 0 {
+                invalid(
+                    &format!("server.method_max_recv_message_mb.{}", method),
+                    "must be greater than 0".to_string(),
+                );
+            }
+        }
+        for codec in &server.compression {
+            if !COMPRESSION_CODECS.contains(&codec.as_str()) {
+                invalid(
+                    "server.compression",
+                    format!(
+                        "'{}' is not supported, expected one of: {}",
+                        codec,
+                        COMPRESSION_CODECS.join(", ")
+                    ),
+                );
+            }
+        }
+        if server.tls_enabled {
+            for (field, path) in [
+                ("server.tls_cert_path", &server.tls_cert_path),
+                ("server.tls_key_path", &server.tls_key_path),
+            ] {
+                match path {
+                    Some(path) => check_file(&mut invalid, field, path),
+                    None => invalid(
+                        field,
+                        "required when server.tls_enabled is true".to_string(),
+                    ),
+                }
+            }
+        }
+        if let Some(unix_socket) = &server.unix_socket {
+            if !matches!(u32::from_str_radix(&unix_socket.mode, 8), Ok(mode) if mode <= 0o777) {
+                invalid(
+                    "server.unix_socket.mode",
+                    format!(
+                        "'{}' is not an octal permission mode such as 0660",
+                        unix_socket.mode
+                    ),
+                );
+            }
+        }
+        if let Some(web) = &server.web {
+            if !cfg!(feature = "grpc-web") {
+                invalid(
+                    "server.web",
+                    "this build has no gRPC-Web support (cargo feature grpc-web)".to_string(),
+                );
+            }
+            for origin in &web.allowed_origins {
+                check_origin(&mut invalid, "server.web.allowed_origins", origin);
+            }
+        }
+
+        // [logging]
+        if let Err(e) = tracing_subscriber::EnvFilter::try_new(&self.logging.level) {
+            invalid(
+                "logging.level",
+                format!("'{}' is not a log filter: {}", self.logging.level, e),
+            );
+        }
+        if !LOG_FORMATS.contains(&self.logging.format.as_str()) {
+            invalid(
+                "logging.format",
+                format!(
+                    "'{}' is not supported, expected one of: {}",
+                    self.logging.format,
+                    LOG_FORMATS.join(", ")
+                ),
+            );
+        }
+        if self.logging.max_file_size_mb == 0 {
+            invalid(
+                "logging.max_file_size_mb",
+                "must be greater than 0".to_string(),
+            );
+        }
+
+        // [boot]
+        if self.boot.socket_path.trim().is_empty() {
+            invalid("boot.socket_path", "must not be empty".to_string());
+        }
+        if matches!(&self.boot.compose_command, Some(command) if command.trim().is_empty()) {
+            invalid("boot.compose_command", "must not be empty".to_string());
+        }
+        if self.boot.data_dir.as_os_str().is_empty() {
+            invalid("boot.data_dir", "must not be empty".to_string());
+        }
+        if self.boot.container_timeout_seconds == 0 {
+            invalid(
+                "boot.container_timeout_seconds",
+                "must be greater than 0".to_string(),
+            );
+        }
+        if self.boot.max_concurrent_deployments == 0 {
+            invalid(
+                "boot.max_concurrent_deployments",
+                "must be at least 1".to_string(),
+            );
+        }
+        if self.boot.manage_aa_config && self.boot.aa_config_path.is_none() {
+            invalid(
+                "boot.manage_aa_config",
+                "requires boot.aa_config_path".to_string(),
+            );
+        }
+        if matches!(&self.boot.aa.eventlog_path, Some(path) if path.trim().is_empty()) {
+            invalid("boot.aa.eventlog_path", "must not be empty".to_string());
+        }
+        if self.boot.retry.initial_delay_ms > self.boot.retry.max_delay_ms {
+            invalid(
+                "boot.retry.initial_delay_ms",
+                "cannot exceed boot.retry.max_delay_ms".to_string(),
+            );
+        }
+        if self.boot.sealed_data.max_blob_bytes == 0 {
+            invalid(
+                "boot.sealed_data.max_blob_bytes",
+                "must be greater than 0".to_string(),
+            );
+        }
+
+        // [kbs]
+        if let Some(kbs) = &self.kbs {
+            if !kbs.endpoint.starts_with("https://") && !kbs.endpoint.starts_with("http://") {
+                invalid(
+                    "kbs.endpoint",
+                    format!("'{}' must be an http:// or https:// URL", kbs.endpoint),
+                );
+            }
+            if let Some(cert_path) = &kbs.cert_path {
+                check_file(&mut invalid, "kbs.cert_path", cert_path);
+            }
+            if kbs.retry.initial_delay_ms > kbs.retry.max_delay_ms {
+                invalid(
+                    "kbs.retry.initial_delay_ms",
+                    "cannot exceed kbs.retry.max_delay_ms".to_string(),
+                );
+            }
+        }
+
+        // [cluster]
+        for signer in &self.cluster.allowed_signers {
+            let hex_address = signer.strip_prefix("0x").unwrap_or_default();
+            if hex_address.len() != 40 || hex::decode(hex_address).is_err() {
+                invalid(
+                    "cluster.allowed_signers",
+                    format!("'{}' is not a 0x-prefixed 20-byte address", signer),
+                );
+            }
+        }
+        for code_hash in &self.cluster.allowed_code_hashes {
+            if code_hash.len() != 64 || hex::decode(code_hash).is_err() {
+                invalid(
+                    "cluster.allowed_code_hashes",
+                    format!("'{}' is not a 32-byte hex hash", code_hash),
+                );
+            }
+        }
+
+        // [contract]
+        if let Some(contract) = &self.contract {
+            if !contract.rpc_url.starts_with("https://") && !contract.rpc_url.starts_with("http://")
+            {
+                invalid(
+                    "contract.rpc_url",
+                    format!("'{}' must be an http:// or https:// URL", contract.rpc_url),
+                );
+            }
+            let hex_address = contract.address.strip_prefix("0x").unwrap_or_default();
+            if hex_address.len() != 40 || hex::decode(hex_address).is_err() {
+                invalid(
+                    "contract.address",
+                    format!(
+                        "'{}' is not a 0x-prefixed 20-byte address",
+                        contract.address
+                    ),
+                );
+            }
+            if contract.chain_id == 0 {
+                invalid("contract.chain_id", "must be set".to_string());
+            }
+            if let Some(funder_key_file) = &contract.funder_key_file {
+                check_file(&mut invalid, "contract.funder_key_file", funder_key_file);
+            }
+            if contract.poll_interval_seconds == 0 {
+                invalid(
+                    "contract.poll_interval_seconds",
+                    "must be at least 1".to_string(),
+                );
+            }
+            if contract.retry.initial_delay_ms > contract.retry.max_delay_ms {
+                invalid(
+                    "contract.retry.initial_delay_ms",
+                    "cannot exceed contract.retry.max_delay_ms".to_string(),
+                );
+            }
+        }
+
+        // [monitoring]
+        for (field, value) in [
+            (
+                "monitoring.disk_warning_percent",
+                self.monitoring.disk_warning_percent,
+            ),
+            (
+                "monitoring.memory_warning_percent",
+                self.monitoring.memory_warning_percent,
+            ),
+        ] {
+            if !(0.0..=100.0).contains(&value) {
+                invalid(field, "must be between 0 and 100".to_string());
+            }
+        }
+
+        // Sections with their own checks, which stop at their first problem
+        let sections = [
+            self.security.validate(),
+            server
+                .api_key
+                .as_ref()
+                .map_or(Ok(()), ApiKeyConfig::validate),
+            server
+                .rate_limit
+                .as_ref()
+                .map_or(Ok(()), RateLimitConfig::validate),
+            server
+                .ip_filter
+                .as_ref()
+                .map_or(Ok(()), IpFilterConfig::validate),
+            self.telemetry
+                .as_ref()
+                .map_or(Ok(()), TelemetryConfig::validate),
+            self.notifications
+                .as_ref()
+                .map_or(Ok(()), NotificationConfig::validate),
+            self.boot
+                .image_policy
+                .as_ref()
+                .map_or(Ok(()), ImagePolicyConfig::validate),
+            self.boot
+                .image_scan
+                .as_ref()
+                .map_or(Ok(()), ImageScanConfig::validate),
+            crate::ip_filter_layer::parse_networks(
+                "boot.container_networks",
+                &self.boot.container_networks,
+            )
+            .map(drop),
+        ];
+        problems.extend(sections.into_iter().filter_map(Result::err));
+
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(ConfigError::Invalid { problems })
+        }
+    }
+}
+
+/// Supported values of `logging.format`
+pub const LOG_FORMATS: &[&str] = &["json", "pretty"];
+
+/// Supported values of `server.compression`
+pub const COMPRESSION_CODECS: &[&str] = &["gzip", "zstd"];
+
+/// Report `value` unless it is a `host:port` socket address
+fn check_address(invalid: &mut impl FnMut(&str, String), field: &str, value: &str) {
+    if let Err(e) = value.parse::<std::net::SocketAddr>() {
+        invalid(
+            field,
+            format!("'{}' is not an address such as 0.0.0.0:50051: {}", value, e),
+        );
+    }
+}
+
+/// Report `path` unless it is an existing file
+fn check_file(invalid: &mut impl FnMut(&str, String), field: &str, path: &Path) {
+    if !path.is_file() {
+        invalid(field, format!("file {} does not exist", path.display()));
+    }
+}
+
+/// Report `value` unless it is "*" or an origin as browsers send it:
+/// scheme://host[:port], without path
+fn check_origin(invalid: &mut impl FnMut(&str, String), field: &str, value: &str) {
+    let authority = value
+        .strip_prefix("https://")
+        .or_else(|| value.strip_prefix("http://"));
+    let valid = match authority {
+        Some(authority) => {
+            !authority.is_empty()
+                && authority
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | ':' | '[' | ']'))
+        }
+        None => value == "*",
+    };
+    if !valid {
+        invalid(
+            field,
+            format!(
+                "'{}' is not an origin such as https://dashboard.example.com, or \"*\"",
+                value
+            ),
+        );
+    }
+}
+
+/// Lowercase key path named by an override variable, if `name` is one
+/// Merge the `*.toml` files of `dir` in lexical order
+/// Later files override values, tables are merged key by key, and arrays are
+/// replaced as a whole. A key that is a table in one file and a value in
+/// another is an error naming both files.
+pub fn merge_dir(dir: &Path) -> Result<toml::Table, ConfigError> {
+    let read_failed = |e: std::io::Error| ConfigError::ParseFailed {
+        reason: format!("Failed to read {}: {}", dir.display(), e),
+    };
+    let mut files: Vec<PathBuf> = std::fs::read_dir(dir)
+        .map_err(read_failed)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<Result<_, _>>()
+        .map_err(read_failed)?;
+    files.retain(|path| path.is_file() && path.extension().is_some_and(|ext| ext == "toml"));
+    files.sort();
+    if files.is_empty() {
+        return Err(ConfigError::FileNotFound {
+            path: dir.join("*.toml").display().to_string(),
+        });
+    }
+
+    let mut merged = toml::Table::new();
+    // File that last set each key path, for conflict errors
+    let mut origins: HashMap<String, String> = HashMap::new();
+    for path in &files {
+        let file = path.display().to_string();
+        let content = std::fs::read_to_string(path).map_err(|e| ConfigError::ParseFailed {
+            reason: format!("Failed to read {}: {}", file, e),
+        })?;
+        let table: toml::Table =
+            toml::from_str(&content).map_err(|e: toml::de::Error| ConfigError::ParseFailed {
+                reason: format!("{}: {}", file, e),
+            })?;
+        merge_table(&mut merged, table, "", &file, &mut origins)?;
+    }
+    Ok(merged)
+}
+
+fn merge_table(
+    base: &mut toml::Table,
+    overlay: toml::Table,
+    prefix: &str,
+    file: &str,
+    origins: &mut HashMap<String, String>,
+) -> Result<(), ConfigError> {
+    for (key, value) in overlay {
+        let path = if prefix.is_empty() {
+            key.clone()
+        } else {
+            format!("{}.{}", prefix, key)
+        };
+        match (base.get_mut(&key), value) {
+            (Some(toml::Value::Table(existing)), toml::Value::Table(table)) => {
+                merge_table(existing, table, &path, file, origins)?;
+            }
+            (Some(existing), value) if existing.is_table() || value.is_table() => {
+                // The file that set this key, or the table containing it
+                let mut earlier = path.as_str();
+                let origin = loop {
+                    if let Some(origin) = origins.get(earlier) {
+                        break origin.clone();
+                    }
+                    match earlier.rsplit_once('.') {
+                        Some((parent, _)) => earlier = parent,
+                        None => break "an earlier file".to_string(),
+                    }
+                };
+                return Err(ConfigError::InvalidValue {
+                    field: path.clone(),
+                    reason: format!(
+                        "{} in {} conflicts with {} in {}",
+                        value.type_str(),
+                        file,
+                        existing.type_str(),
+                        origin
+                    ),
+                });
+            }
+            (_, value) => {
+                origins.insert(path, file.to_string());
+                base.insert(key, value);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Path of the setting an override variable names
+/// Every setting is in a section, so a name without `ENV_SEPARATOR` is not
+/// an override; this leaves `TAPP_SERVER` and the other client variables
+/// alone.
+fn env_path(name: &str) -> Option<Vec<String>> {
+    let path: Vec<String> = name
+        .strip_prefix(ENV_PREFIX)?
+        .split(ENV_SEPARATOR)
+        .map(str::to_lowercase)
+        .collect();
+    (path.len() > 1 && path.iter().all(|key| !key.is_empty())).then_some(path)
+}
+
+/// Existing key of `table` matching `key` case-insensitively, or `key`
+fn resolve_key(table: &toml::Table, key: &str) -> String {
+    table
+        .keys()
+        .find(|existing| existing.eq_ignore_ascii_case(key))
+        .cloned()
+        .unwrap_or_else(|| key.to_string())
+}
+
+fn lookup<'a>(table: &'a toml::Table, path: &[String]) -> Option<&'a toml::Value> {
+    let (last, sections) = path.split_last()?;
+    let mut table = table;
+    for key in sections {
+        table = table.get(&resolve_key(table, key))?.as_table()?;
+    }
+    table.get(&resolve_key(table, last))
+}
+
+fn set_path(
+    table: &mut toml::Table,
+    name: &str,
+    path: &[String],
+    value: toml::Value,
+) -> Result<(), ConfigError> {
+    let Some((last, sections)) = path.split_last() else {
+        return Ok(());
+    };
+    let mut table = table;
+    for key in sections {
+        let key = resolve_key(table, key);
+        table = table
+            .entry(key.clone())
+            .or_insert_with(|| toml::Value::Table(toml::Table::new()))
+            .as_table_mut()
+            .ok_or_else(|| ConfigError::InvalidValue {
+                field: name.to_string(),
+                reason: format!("{} is not a section", key),
+            })?;
+    }
+    let key = resolve_key(table, last);
+    table.insert(key, value);
+    Ok(())
+}
+
+/// Read the value of variable `name` as the type of `current`
+fn env_value(
+    name: &str,
+    raw: &str,
+    current: Option<&toml::Value>,
+) -> Result<toml::Value, ConfigError> {
+    let invalid = |expected: &str| ConfigError::InvalidValue {
+        field: name.to_string(),
+        reason: format!("expected {}, got '{}'", expected, raw),
+    };
+
+    match current {
+        Some(toml::Value::String(_)) => Ok(toml::Value::String(raw.to_string())),
+        Some(toml::Value::Integer(_)) => raw
+            .trim()
+            .parse()
+            .map(toml::Value::Integer)
+            .map_err(|_| invalid("an integer")),
+        Some(toml::Value::Float(_)) => raw
+            .trim()
+            .parse()
+            .map(toml::Value::Float)
+            .map_err(|_| invalid("a number")),
+        Some(toml::Value::Boolean(_)) => raw
+            .trim()
+            .parse()
+            .map(toml::Value::Boolean)
+            .map_err(|_| invalid("true or false")),
+        Some(toml::Value::Array(_)) => match toml_literal(raw) {
+            Some(value @ toml::Value::Array(_)) => Ok(value),
+            _ => Err(invalid("an array such as [\"a\", \"b\"]")),
+        },
+        Some(toml::Value::Table(_)) => match toml_literal(raw) {
+            Some(value @ toml::Value::Table(_)) => Ok(value),
+            _ => Err(invalid("an inline table such as { key = \"value\" }")),
+        },
+        Some(toml::Value::Datetime(_)) | None => {
+            Ok(toml_literal(raw).unwrap_or_else(|| toml::Value::String(raw.to_string())))
+        }
+    }
+}
+
+/// `raw` read as a TOML value, if it is one
+fn toml_literal(raw: &str) -> Option<toml::Value> {
+    let mut table: toml::Table = toml::from_str(&format!("value = {}", raw)).ok()?;
+    table.remove("value")
+}
+
+/// Replay protection settings for deployer-signed requests
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SecurityConfig {
+    /// How far a request timestamp may be from the server clock, and how long
+    /// its nonce is remembered
+    #[serde(default = "default_nonce_validity")]
+    pub nonce_validity_seconds: u64,
+
+    /// How often expired nonces are dropped
+    #[serde(default = "default_nonce_cleanup_interval")]
+    pub nonce_cleanup_interval_seconds: u64,
+
+    /// How many seconds a request may be older than the newest one accepted
+    /// from the same deployer for the same operation (0 = strictly newer)
+    #[serde(default = "default_timestamp_tolerance")]
+    pub timestamp_tolerance_seconds: u64,
+}
+
+/// Accepted range for the nonce settings
+const NONCE_SECONDS_RANGE: std::ops::RangeInclusive<u64> = 5..=3600;
+
+impl SecurityConfig {
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        for (field, value) in [
+            (
+                "security.nonce_validity_seconds",
+                self.nonce_validity_seconds,
+            ),
+            (
+                "security.nonce_cleanup_interval_seconds",
+                self.nonce_cleanup_interval_seconds,
+            ),
+        ] {
+            if !NONCE_SECONDS_RANGE.contains(&value) {
+                return Err(ConfigError::InvalidValue {
+                    field: field.to_string(),
+                    reason: format!(
+                        "must be between {} and {} seconds",
+                        NONCE_SECONDS_RANGE.start(),
+                        NONCE_SECONDS_RANGE.end()
+                    ),
+                });
+            }
+        }
+
+        if self.timestamp_tolerance_seconds > self.nonce_validity_seconds {
+            return Err(ConfigError::InvalidValue {
+                field: "security.timestamp_tolerance_seconds".to_string(),
+                reason: "must not exceed security.nonce_validity_seconds".to_string(),
+            });
+        }
+
+        Ok(())
+    }
+}
+
+/// Addresses the gRPC server listens on
+/// Written as one address, a list (`["0.0.0.0:50051", "[::]:50051"]`) or a
+/// comma-separated string, as in `--bind` and `TAPP_SERVER__BIND_ADDRESS`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BindAddresses(pub Vec<String>);
+
+impl BindAddresses {
+    pub fn iter(&self) -> impl Iterator<Item = &str> {
+        self.0.iter().map(String::as_str)
+    }
+
+    pub fn contains(&self, address: &str) -> bool {
+        self.iter().any(|a| a == address)
+    }
+}
+
+impl From<&str> for BindAddresses {
+    fn from(s: &str) -> Self {
+        Self(
+            s.split(',')
+                .map(str::trim)
+                .filter(|a| !a.is_empty())
+                .map(str::to_string)
+                .collect(),
+        )
+    }
+}
+
+impl PartialEq<&str> for BindAddresses {
+    fn eq(&self, other: &&str) -> bool {
+        *self == Self::from(*other)
+    }
+}
+
+impl std::fmt::Display for BindAddresses {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0.join(", "))
+    }
+}
+
+impl Serialize for BindAddresses {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        // A single address stays a plain string, as in existing files
+        match self.0.as_slice() {
+            [address] => serializer.serialize_str(address),
+            addresses => addresses.serialize(serializer),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for BindAddresses {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Addresses {
+            One(String),
+            Many(Vec<String>),
+        }
+
+        Ok(match Addresses::deserialize(deserializer)? {
+            Addresses::One(s) => Self::from(s.as_str()),
+            Addresses::Many(list) => Self(list.iter().map(|a| a.trim().to_string()).collect()),
+        })
+    }
+}
+
+/// Server configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ServerConfig {
+    /// Bind address(es) for the gRPC server, each served with the same
+    /// service and layers
+    #[serde(default = "default_bind_address")]
+    pub bind_address: BindAddresses,
+
+    /// Optional second listener for admin-only methods (e.g. 127.0.0.1:50052)
+    /// When set, admin-only methods are rejected on `bind_address`
+    #[serde(default)]
+    pub admin_bind_address: Option<String>,
+
+    /// Maximum number of concurrent connections
+    #[serde(default = "default_max_connections")]
+    pub max_connections: usize,
+
+    /// Maximum number of deployer request nonces remembered at once
+    /// At capacity, new deployer-signed requests get RESOURCE_EXHAUSTED until
+    /// older nonces expire
+    #[serde(default = "default_max_tracked_nonces")]
+    pub max_tracked_nonces: usize,
+
+    /// Time an RPC may take before it is abandoned with DEADLINE_EXCEEDED
+    #[serde(default = "default_request_timeout")]
+    pub request_timeout_seconds: u64,
+
+    /// Time in-flight RPCs and deployments get to finish on SIGINT/SIGTERM
+    #[serde(default = "default_shutdown_grace")]
+    pub shutdown_grace_seconds: u64,
+
+    /// Per-method timeout overrides in seconds (0 = no timeout)
+    /// Streaming methods have no timeout unless listed here
+    /// Examples: StopApp = 120
+    #[serde(default)]
+    pub method_timeout_seconds: HashMap<String, u64>,
+
+    /// Serve gRPC reflection, for grpcurl and grpcui without the .proto files
+    /// It requires an API key whenever API key authentication is enabled.
+    #[serde(default = "default_enable_reflection")]
+    pub enable_reflection: bool,
+
+    /// Enable TLS
+    #[serde(default)]
+    pub tls_enabled: bool,
+
+    /// TLS certificate path (if TLS enabled)
+    pub tls_cert_path: Option<PathBuf>,
+
+    /// TLS private key path (if TLS enabled)
+    pub tls_key_path: Option<PathBuf>,
+
+    /// API Key configuration for authentication
+    #[serde(default)]
+    pub api_key: Option<ApiKeyConfig>,
+
+    /// Request rate limiting configuration
+    #[serde(default)]
+    pub rate_limit: Option<RateLimitConfig>,
+
+    /// Source address allow/deny lists for the TCP listener
+    #[serde(default)]
+    pub ip_filter: Option<IpFilterConfig>,
+
+    /// Optional Unix domain socket listener for local-only operations
+    #[serde(default)]
+    pub unix_socket: Option<UnixSocketConfig>,
+
+    /// gRPC-Web for browser clients (needs the `grpc-web` cargo feature)
+    #[serde(default)]
+    pub web: Option<WebConfig>,
+
+    /// Maximum size of a decoded request message in MB
+    #[serde(default = "default_max_message_mb")]
+    pub max_recv_message_mb: usize,
+
+    /// Maximum size of an encoded response message in MB
+    #[serde(default = "default_max_message_mb")]
+    pub max_send_message_mb: usize,
+
+    /// Per-method request size overrides in MB
+    /// Examples: StartApp = 64
+    #[serde(default)]
+    pub method_max_recv_message_mb: HashMap<String, usize>,
+
+    /// Message compression codecs (gzip, zstd): accepted on requests, and
+    /// used for responses to clients that accept them. Empty disables it.
+    #[serde(default = "default_compression")]
+    pub compression: Vec<String>,
+
+    /// grpc.health.v1.Health status updates
+    #[serde(default)]
+    pub health: HealthCheckConfig,
+
+    /// HTTP/2 keepalive and connection limits of the gRPC listeners
+    #[serde(default)]
+    pub http2: Http2Config,
+}
+
+/// Headroom added on top of the largest request limit when configuring the
+/// gRPC codec, so oversized requests reach the application-level checks and
+/// get a descriptive error instead of the codec's generic one
+pub const RECV_MESSAGE_HEADROOM_BYTES: usize = 1024 * 1024;
+
+/// Methods sending a stream for as long as the client reads it, so they are
+/// not bound by `request_timeout_seconds`
+pub const STREAMING_METHODS: &[&str] = &["StreamServiceLogs", "DownloadLogFile", "WatchTask"];
+
+impl ServerConfig {
+    /// Time limit of a method (override, none for streaming methods, or
+    /// global); None if it may run as long as it needs
+    pub fn request_timeout(&self, method: &str) -> Option<Duration> {
+        let seconds = match self.method_timeout_seconds.get(method) {
+            Some(seconds) => *seconds,
+            None if STREAMING_METHODS.contains(&method) => 0,
+            None => self.request_timeout_seconds,
+        };
+        (seconds > 0).then_some(Duration::from_secs(seconds))
+    }
+
+    /// Request size limit in bytes for a method (override or global)
+    pub fn max_recv_message_bytes(&self, method: &str) -> usize {
+        self.method_max_recv_message_mb
+            .get(method)
+            .copied()
+            .unwrap_or(self.max_recv_message_mb)
+            * 1024
+            * 1024
+    }
+
+    /// Decoding limit in bytes for the gRPC service: the largest per-method
+    /// limit plus headroom. Hard memory bound for every request.
+    pub fn codec_max_recv_message_bytes(&self) -> usize {
+        let largest_mb = self
+            .method_max_recv_message_mb
+            .values()
+            .copied()
+            .chain(std::iter::once(self.max_recv_message_mb))
+            .max()
+            .unwrap_or(self.max_recv_message_mb);
+        largest_mb * 1024 * 1024 + RECV_MESSAGE_HEADROOM_BYTES
+    }
+
+    /// Encoding limit in bytes for responses
+    pub fn max_send_message_bytes(&self) -> usize {
+        self.max_send_message_mb * 1024 * 1024
+    }
+
+    /// Codecs listed in `compression`; unknown names are reported by
+    /// `TappConfig::validate`
+    pub fn compression_encodings(&self) -> Vec<tonic::codec::CompressionEncoding> {
+        use tonic::codec::CompressionEncoding;
+
+        self.compression
+            .iter()
+            .filter_map(|codec| match codec.as_str() {
+                "gzip" => Some(CompressionEncoding::Gzip),
+                "zstd" => Some(CompressionEncoding::Zstd),
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+/// Unix domain socket listener configuration
+/// Requests arriving over the socket are inherently local
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UnixSocketConfig {
+    /// Socket path
+    #[serde(default = "default_unix_socket_path")]
+    pub path: PathBuf,
+
+    /// Socket file permissions in octal
+    #[serde(default = "default_unix_socket_mode")]
+    pub mode: String,
+
+    /// Owner uid of the socket file (unchanged if not set)
+    #[serde(default)]
+    pub uid: Option<u32>,
+
+    /// Owner gid of the socket file (unchanged if not set)
+    #[serde(default)]
+    pub gid: Option<u32>,
+}
+
+/// gRPC-Web translation on the listeners, with the CORS answers browsers
+/// need to call the API from another origin
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WebConfig {
+    /// Origins allowed to call cross-origin, e.g. "https://dashboard.example.com",
+    /// or "*" for any; empty allows same-origin pages only
+    #[serde(default)]
+    pub allowed_origins: Vec<String>,
+
+    /// Seconds browsers may cache a preflight answer
+    #[serde(default = "default_web_max_age")]
+    pub max_age_seconds: u64,
+}
+
+/// Membership of this node in a key cluster (InitCluster)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ClusterConfig {
+    /// Directory keeping this node's share of the cluster master key, sealed
+    #[serde(default = "default_cluster_state_dir")]
+    pub state_dir: String,
+
+    /// How the evidence of the other side of a share exchange is checked
+    #[serde(default)]
+    pub evidence_verifier: EvidenceVerifierKind,
+
+    /// Signer addresses (0x-prefixed) of the nodes shares are exchanged with
+    #[serde(default)]
+    pub allowed_signers: Vec<String>,
+
+    /// Code hashes (hex) of the nodes shares are exchanged with; see
+    /// `cluster::verifier` for how they are computed
+    #[serde(default)]
+    pub allowed_code_hashes: Vec<String>,
+}
+
+/// Evidence accepted in the key share exchange
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum EvidenceVerifierKind {
+    /// TDX quotes
+    #[default]
+    Tdx,
+    /// Evidence of the sample attester, which proves nothing; for testing
+    /// without a TEE only
+    Sample,
+}
+
+/// The tapp contract: app registrations after each deployment, and the
+/// attestors allowed to request app keys
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ContractConfig {
+    /// Ethereum JSON-RPC endpoint of the chain the contract is deployed on
+    #[serde(default = "default_contract_rpc_url")]
+    pub rpc_url: String,
+
+    /// Address (0x-prefixed) of the tapp contract
+    #[serde(default)]
+    pub address: String,
+
+    /// Chain ID registration transactions are signed for
+    #[serde(default)]
+    pub chain_id: u64,
+
+    /// File holding the hex private key of the account paying for
+    /// registrations; without it this node's identity key, the cluster
+    /// signer key, pays
+    #[serde(default)]
+    pub funder_key_file: Option<PathBuf>,
+
+    /// Timeout of one call to the RPC endpoint
+    #[serde(default = "default_contract_timeout")]
+    pub timeout_seconds: u64,
+
+    /// Seconds between checks of a submitted registration
+    #[serde(default = "default_contract_poll_interval")]
+    pub poll_interval_seconds: u64,
+
+    /// Seconds after which a registration still not mined is reported failed
+    #[serde(default = "default_contract_confirmation_timeout")]
+    pub confirmation_timeout_seconds: u64,
+
+    /// Retries of calls that failed because the endpoint was unreachable
+    #[serde(default)]
+    pub retry: RetryConfig,
+
+    /// Checks of GetAppKey and GetAttestedAppKey callers against the
+    /// contract's registered attestors
+    #[serde(default)]
+    pub attestor_auth: AttestorAuthConfig,
+}
+
+/// Attestor authorization of app key requests (`isRegisteredAttestor`)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AttestorAuthConfig {
+    /// Require GetAppKey and GetAttestedAppKey callers to sign a GetNonce
+    /// challenge with a registered attestor's key
+    #[serde(default)]
+    pub enabled: bool,
+
+    /// Seconds a contract answer is reused for the same attestor
+    #[serde(default = "default_attestor_cache_ttl")]
+    pub cache_ttl_seconds: u64,
+
+    /// Seconds an expired answer may still be used while the contract
+    /// cannot be reached
+    #[serde(default = "default_attestor_max_stale")]
+    pub max_stale_seconds: u64,
+
+    /// Decision when the contract cannot be asked and no usable answer is
+    /// cached
+    #[serde(default)]
+    pub failure_policy: AttestorFailurePolicy,
+}
+
+/// What an attestor check does when the contract cannot be reached
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum AttestorFailurePolicy {
+    /// Reject the request
+    #[default]
+    Closed,
+    /// Let the request through with a valid signature
+    Open,
+}
+
+/// Host resource reporting (GetHostResources)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MonitoringConfig {
+    /// Filesystem usage (percent) at or above which `disk_warning` is set
+    #[serde(default = "default_resource_warning_percent")]
+    pub disk_warning_percent: f64,
+
+    /// Memory usage (percent) at or above which `memory_warning` is set
+    #[serde(default = "default_resource_warning_percent")]
+    pub memory_warning_percent: f64,
+
+    /// How long a reading is reused before the host is queried again
+    #[serde(default = "default_resource_cache_seconds")]
+    pub cache_seconds: u64,
+}
+
+/// Dependency checks behind the gRPC health service
+/// TappService is reported NOT_SERVING after the given number of consecutive
+/// failed checks, and SERVING again after the first successful round.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HealthCheckConfig {
+    /// Seconds between dependency checks
+    #[serde(default = "default_health_check_interval")]
+    pub check_interval_seconds: u64,
+
+    /// Consecutive failed Docker daemon pings before NOT_SERVING
+    #[serde(default = "default_health_failure_threshold")]
+    pub docker_failure_threshold: u32,
+
+    /// Consecutive failed attestation agent probes before NOT_SERVING
+    #[serde(default = "default_health_failure_threshold")]
+    pub attestation_failure_threshold: u32,
+}
+
+/// HTTP/2 connection settings of the gRPC listeners
+/// The defaults send no pings and limit neither streams nor connection age.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Http2Config {
+    /// Seconds between PING frames keeping connections open behind NATs and
+    /// load balancers (0 = no pings)
+    #[serde(default)]
+    pub keepalive_interval_seconds: u64,
+
+    /// Seconds to wait for a PING acknowledgement before closing the connection
+    #[serde(default = "default_keepalive_timeout")]
+    pub keepalive_timeout_seconds: u64,
+
+    /// Concurrent streams per connection (0 = unlimited)
+    #[serde(default)]
+    pub max_concurrent_streams: u32,
+
+    /// Seconds after which a connection is sent GOAWAY; calls in flight finish
+    /// and the client reconnects for new ones (0 = unlimited)
+    #[serde(default)]
+    pub max_connection_age_seconds: u64,
+}
+
+impl Http2Config {
+    pub fn keepalive_interval(&self) -> Option<Duration> {
+        (self.keepalive_interval_seconds > 0)
+            .then(|| Duration::from_secs(self.keepalive_interval_seconds))
+    }
+
+    pub fn keepalive_timeout(&self) -> Duration {
+        Duration::from_secs(self.keepalive_timeout_seconds)
+    }
+
+    pub fn max_concurrent_streams(&self) -> Option<u32> {
+        (self.max_concurrent_streams > 0).then_some(self.max_concurrent_streams)
+    }
+
+    pub fn max_connection_age(&self) -> Option<Duration> {
+        (self.max_connection_age_seconds > 0)
+            .then(|| Duration::from_secs(self.max_connection_age_seconds))
+    }
+}
+
+/// API Key authentication configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ApiKeyConfig {
+    /// Enable API key authentication
+    #[serde(default)]
+    pub enabled: bool,
+
+    /// List of valid API keys
+    /// Each entry is either a bare key string or a table with a name:
+    /// `{ name = "ci", key = "..." }`. Instead of `key`, a table may name a
+    /// file (`key_file`) or an environment variable (`key_env`) holding it,
+    /// read when the configuration is loaded. Tables with `type = "hmac"`
+    /// hold a shared secret for signed requests; `name` is the key ID.
+    pub keys: Vec<ApiKeyEntry>,
+
+    /// Methods that require authentication (if empty, all methods require auth)
+    /// Examples: "StartApp", "GetAppSecretKey"
+    #[serde(default)]
+    pub protected_methods: Vec<String>,
+
+    /// Lockout after repeated authentication failures
+    #[serde(default)]
+    pub lockout: AuthLockoutConfig,
+
+    /// Freshness checks for HMAC-signed requests
+    #[serde(default)]
+    pub hmac: HmacAuthConfig,
+
+    /// Keys expiring within this many days are reported by GetServiceStatus
+    #[serde(default = "default_expiry_horizon_days")]
+    pub expiry_horizon_days: u64,
+
+    /// Scope apps to the `namespace` of the calling key, so teams sharing a
+    /// node cannot see or address each other's apps. Client keys must name
+    /// a namespace; admin keys see every app. Read at startup only, since it
+    /// changes where apps live.
+    #[serde(default)]
+    pub namespaces: bool,
+}
+
+/// Freshness checks for HMAC-signed requests
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HmacAuthConfig {
+    /// Maximum difference between the request timestamp and server time in seconds
+    #[serde(default = "default_hmac_max_clock_skew")]
+    pub max_clock_skew_seconds: i64,
+
+    /// How long used nonces are remembered in seconds
+    /// Must cover the clock skew, or replays become possible once a nonce expires
+    #[serde(default = "default_hmac_nonce_window")]
+    pub nonce_window_seconds: i64,
+
+    /// Maximum number of nonces remembered at once (applied at startup)
+    #[serde(default = "default_max_tracked_nonces")]
+    pub max_tracked_nonces: usize,
+}
+
+/// Lockout thresholds for repeated authentication failures
+/// Failures are counted per source IP and per key name (when the presented
+/// key shares a prefix with a configured key)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuthLockoutConfig {
+    /// Failures within the window that trigger a lockout (0 = disabled)
+    #[serde(default = "default_lockout_max_failures")]
+    pub max_failures: u32,
+
+    /// Window in seconds in which failures are counted
+    #[serde(default = "default_lockout_failure_window")]
+    pub failure_window_seconds: u64,
+
+    /// Duration of the first lockout in seconds; doubles with each further lockout
+    #[serde(default = "default_lockout_base_seconds")]
+    pub base_lockout_seconds: u64,
+
+    /// Upper bound for the lockout duration in seconds
+    #[serde(default = "default_lockout_max_seconds")]
+    pub max_lockout_seconds: u64,
+
+    /// Maximum number of sources tracked in memory (least recently used are evicted)
+    #[serde(default = "default_max_tracked_clients")]
+    pub max_tracked_sources: usize,
+}
+
+impl ApiKeyConfig {
+    /// Fill in the keys given as `key_file` or `key_env`
+    pub fn resolve_keys(
+        &mut self,
+        var: impl Fn(&str) -> Option<String>,
+    ) -> Result<(), ConfigError> {
+        for entry in &mut self.keys {
+            entry.resolve(&var)?;
+        }
+        Ok(())
+    }
+
+    /// Validate the API key section (also used before applying a hot reload)
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        if self.enabled && self.keys.is_empty() {
+            return Err(ConfigError::InvalidValue {
+                field: "server.api_key.keys".to_string(),
+                reason: "at least one key is required when API key auth is enabled".to_string(),
+            });
+        }
+
+        if self.keys.iter().any(|k| k.key().trim().is_empty()) {
+            return Err(ConfigError::InvalidValue {
+                field: "server.api_key.keys".to_string(),
+                reason: "API keys cannot be empty".to_string(),
+            });
+        }
+
+        if self.lockout.max_tracked_sources == 0 {
+            return Err(ConfigError::InvalidValue {
+                field: "server.api_key.lockout.max_tracked_sources".to_string(),
+                reason: "must be greater than 0".to_string(),
+            });
+        }
+
+        for entry in &self.keys {
+            entry.validate()?;
+        }
+
+        if self.namespaces {
+            if !self.enabled {
+                return Err(ConfigError::InvalidValue {
+                    field: "server.api_key.namespaces".to_string(),
+                    reason: "requires API key auth to be enabled".to_string(),
+                });
+            }
+            if let Some(entry) = self
+                .keys
+                .iter()
+                .find(|k| k.role() == ApiKeyRole::Client && k.namespace().is_none())
+            {
+                return Err(ConfigError::InvalidValue {
+                    field: "server.api_key.keys".to_string(),
+                    reason: format!(
+                        "key '{}' needs a namespace when namespaces are enabled",
+                        entry.name()
+                    ),
+                });
+            }
+        }
+
+        if self.hmac.max_tracked_nonces == 0 {
+            return Err(ConfigError::InvalidValue {
+                field: "server.api_key.hmac.max_tracked_nonces".to_string(),
+                reason: "must be greater than 0".to_string(),
+            });
+        }
+
+        if self.hmac.max_clock_skew_seconds <= 0 {
+            return Err(ConfigError::InvalidValue {
+                field: "server.api_key.hmac.max_clock_skew_seconds".to_string(),
+                reason: "must be greater than 0".to_string(),
+            });
+        }
+
+        if self.hmac.nonce_window_seconds < self.hmac.max_clock_skew_seconds {
+            return Err(ConfigError::InvalidValue {
+                field: "server.api_key.hmac.nonce_window_seconds".to_string(),
+                reason: "cannot be shorter than max_clock_skew_seconds".to_string(),
+            });
+        }
+
+        if self.lockout.base_lockout_seconds > self.lockout.max_lockout_seconds {
+            return Err(ConfigError::InvalidValue {
+                field: "server.api_key.lockout.base_lockout_seconds".to_string(),
+                reason: "cannot exceed max_lockout_seconds".to_string(),
+            });
+        }
+
+        Ok(())
+    }
 }
 
-/// Logging configuration
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct LoggingConfig {
-    /// Log level
-    #[serde(default = "default_log_level")]
-    pub level: String,
+/// A single API key entry
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(untagged)]
+pub enum ApiKeyEntry {
+    /// Bare key string (identified by a fingerprint of the key)
+    Inline(String),
+    /// Named key, optionally with a role, a type and a validity period
+    Named {
+        name: String,
+        /// Key value; empty in the file when `key_file` or `key_env` is set
+        #[serde(default, skip_serializing_if = "String::is_empty")]
+        key: String,
+        /// File holding the key, trailing whitespace trimmed
+        #[serde(default)]
+        key_file: Option<PathBuf>,
+        /// Environment variable holding the key, trailing whitespace trimmed
+        #[serde(default)]
+        key_env: Option<String>,
+        #[serde(default)]
+        role: ApiKeyRole,
+        #[serde(default, rename = "type")]
+        key_type: ApiKeyType,
+        /// RFC 3339 time before which the key is rejected
+        #[serde(default)]
+        not_before: Option<String>,
+        /// RFC 3339 time from which the key is rejected
+        #[serde(default)]
+        expires_at: Option<String>,
+        /// Still accepted, but every use is logged as a warning
+        #[serde(default)]
+        deprecated: bool,
+        /// Namespace of the apps this key works with, when
+        /// `server.api_key.namespaces` is enabled
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        namespace: Option<String>,
+    },
+}
 
-    /// Log format (json or pretty)
-    #[serde(default = "default_log_format")]
-    pub format: String,
+/// Whether a key may be used at a given time
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyValidity {
+    Valid,
+    NotYetValid,
+    Expired,
+}
 
-    /// Log file path (if None, logs to stdout)
-    pub file_path: Option<PathBuf>,
+/// How a key is presented
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ApiKeyType {
+    /// Sent as is in the `x-api-key` header
+    #[default]
+    Plain,
+    /// Shared secret for HMAC-signed requests; never sent over the wire
+    Hmac,
+}
 
-    /// Maximum log file size in MB
-    #[serde(default = "default_max_log_size")]
-    pub max_file_size_mb: u64,
+/// Role granted to an API key
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ApiKeyRole {
+    /// Regular client; deployer-gated RPCs still require a deployer signature
+    #[default]
+    Client,
+    /// Operator; may force deployer-gated operations such as StopApp
+    Admin,
+}
 
-    /// Maximum number of log files to keep
-    #[serde(default = "default_max_log_files")]
-    pub max_files: usize,
+impl ApiKeyEntry {
+    /// Secret key value
+    pub fn key(&self) -> &str {
+        match self {
+            ApiKeyEntry::Inline(key) => key,
+            ApiKeyEntry::Named { key, .. } => key,
+        }
+    }
+
+    /// Identity used in logs; never contains the key itself
+    pub fn name(&self) -> String {
+        match self {
+            ApiKeyEntry::Inline(key) => {
+                format!("key-{}", &crate::utils::sha256_hex(key.as_bytes())[..8])
+            }
+            ApiKeyEntry::Named { name, .. } => name.clone(),
+        }
+    }
+
+    /// Where the key was read from, for logs: `config`, `file <path>` or
+    /// `env <variable>`
+    pub fn source(&self) -> String {
+        match self {
+            ApiKeyEntry::Named {
+                key_file: Some(path),
+                ..
+            } => format!("file {}", path.display()),
+            ApiKeyEntry::Named {
+                key_env: Some(var), ..
+            } => format!("env {}", var),
+            _ => "config".to_string(),
+        }
+    }
+
+    /// Read the key from `key_file` or `key_env` into `key`
+    /// Exactly one of `key`, `key_file` and `key_env` must be set, and the
+    /// file or variable must exist and hold a non-empty key.
+    fn resolve(&mut self, var: &impl Fn(&str) -> Option<String>) -> Result<(), ConfigError> {
+        let ApiKeyEntry::Named {
+            name,
+            key,
+            key_file,
+            key_env,
+            ..
+        } = self
+        else {
+            return Ok(());
+        };
+        let invalid = |reason: String| ConfigError::InvalidValue {
+            field: "server.api_key.keys".to_string(),
+            reason: format!("key '{}': {}", name, reason),
+        };
+
+        let (source, value) = match (key_file.as_ref(), key_env.as_deref()) {
+            (None, None) => return Ok(()),
+            (Some(_), Some(_)) => {
+                return Err(invalid("set only one of key_file and key_env".to_string()))
+            }
+            _ if !key.is_empty() => {
+                return Err(invalid(
+                    "set either key or a key_file/key_env source, not both".to_string(),
+                ))
+            }
+            (Some(path), None) => {
+                let source = format!("key_file {}", path.display());
+                let value = std::fs::read_to_string(path)
+                    .map_err(|e| invalid(format!("{} could not be read: {}", source, e)))?;
+                (source, value)
+            }
+            (None, Some(env)) => {
+                let source = format!("key_env {}", env);
+                let value = var(env).ok_or_else(|| invalid(format!("{} is not set", source)))?;
+                (source, value)
+            }
+        };
+
+        let value = value.trim_end();
+        if value.is_empty() {
+            return Err(invalid(format!("{} is empty", source)));
+        }
+        *key = value.to_string();
+        Ok(())
+    }
+
+    /// Role of this key (bare keys are always clients)
+    pub fn role(&self) -> ApiKeyRole {
+        match self {
+            ApiKeyEntry::Inline(_) => ApiKeyRole::Client,
+            ApiKeyEntry::Named { role, .. } => *role,
+        }
+    }
+
+    /// Type of this key (bare keys are always plain)
+    pub fn key_type(&self) -> ApiKeyType {
+        match self {
+            ApiKeyEntry::Inline(_) => ApiKeyType::Plain,
+            ApiKeyEntry::Named { key_type, .. } => *key_type,
+        }
+    }
+
+    /// Whether every use of this key is logged as deprecated
+    pub fn deprecated(&self) -> bool {
+        match self {
+            ApiKeyEntry::Inline(_) => false,
+            ApiKeyEntry::Named { deprecated, .. } => *deprecated,
+        }
+    }
+
+    /// Namespace of this key's apps (bare keys have none)
+    pub fn namespace(&self) -> Option<&str> {
+        match self {
+            ApiKeyEntry::Inline(_) => None,
+            ApiKeyEntry::Named { namespace, .. } => namespace.as_deref(),
+        }
+    }
+
+    fn validity_bounds(
+        &self,
+    ) -> Result<(Option<DateTime<Utc>>, Option<DateTime<Utc>>), ConfigError> {
+        let ApiKeyEntry::Named {
+            name,
+            not_before,
+            expires_at,
+            ..
+        } = self
+        else {
+            return Ok((None, None));
+        };
+
+        let parse = |field: &str, value: &Option<String>| {
+            value
+                .as_deref()
+                .map(|value| {
+                    DateTime::parse_from_rfc3339(value)
+                        .map(|time| time.with_timezone(&Utc))
+                        .map_err(|e| ConfigError::InvalidValue {
+                            field: "server.api_key.keys".to_string(),
+                            reason: format!(
+                                "key '{}': {} '{}' is not an RFC 3339 timestamp: {}",
+                                name, field, value, e
+                            ),
+                        })
+                })
+                .transpose()
+        };
+
+        Ok((
+            parse("not_before", not_before)?,
+            parse("expires_at", expires_at)?,
+        ))
+    }
+
+    /// Expiry time of this key, if it has one
+    pub fn expires_at(&self) -> Option<DateTime<Utc>> {
+        self.validity_bounds()
+            .ok()
+            .and_then(|(_, expires_at)| expires_at)
+    }
+
+    /// Check the validity period at `now`
+    /// Keys with unparsable timestamps are treated as expired
+    pub fn validity_at(&self, now: DateTime<Utc>) -> KeyValidity {
+        match self.validity_bounds() {
+            Ok((Some(not_before), _)) if now < not_before => KeyValidity::NotYetValid,
+            Ok((_, Some(expires_at))) if now >= expires_at => KeyValidity::Expired,
+            Ok(_) => KeyValidity::Valid,
+            Err(_) => KeyValidity::Expired,
+        }
+    }
+
+    /// Validate the validity period and namespace of this key
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        if let Some(namespace) = self.namespace() {
+            if !crate::namespace::validate_namespace(namespace) {
+                return Err(ConfigError::InvalidValue {
+                    field: "server.api_key.keys".to_string(),
+                    reason: format!(
+                        "key '{}': namespace '{}' must be 1 to {} lowercase letters, digits or '-'",
+                        self.name(),
+                        namespace,
+                        crate::namespace::MAX_NAMESPACE_LEN
+                    ),
+                });
+            }
+        }
+        if let (Some(not_before), Some(expires_at)) = self.validity_bounds()? {
+            if not_before >= expires_at {
+                return Err(ConfigError::InvalidValue {
+                    field: "server.api_key.keys".to_string(),
+                    reason: format!(
+                        "key '{}': not_before must be before expires_at",
+                        self.name()
+                    ),
+                });
+            }
+        }
+        Ok(())
+    }
 }
 
-/// Main configuration structure for TAPP service
-#[derive(Debug, Clone, Default, Deserialize)]
-pub struct TappConfig {
+/// Rate limiting configuration (token bucket per client and method)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RateLimitConfig {
+    /// Enable request rate limiting
     #[serde(default)]
-    pub logging: LoggingConfig,
+    pub enabled: bool,
+
+    /// Requests per minute allowed for methods without an override (0 = unlimited)
+    #[serde(default = "default_requests_per_minute")]
+    pub default_requests_per_minute: u32,
+
+    /// Per-method overrides in requests per minute (0 = unlimited)
+    /// Examples: GetEvidence = 5, GetAppLogs = 60
     #[serde(default)]
-    pub boot: BootServiceConfig,
+    pub method_limits: HashMap<String, u32>,
+
+    /// Maximum number of client buckets kept in memory (least recently used are evicted)
+    #[serde(default = "default_max_tracked_clients")]
+    pub max_tracked_clients: usize,
+}
+
+impl RateLimitConfig {
+    /// Validate the rate limit section (also used before applying a hot reload)
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        if self.max_tracked_clients == 0 {
+            return Err(ConfigError::InvalidValue {
+                field: "server.rate_limit.max_tracked_clients".to_string(),
+                reason: "must be greater than 0".to_string(),
+            });
+        }
+
+        Ok(())
+    }
+}
+
+/// Source address filter for the TCP listener
+/// Checked before authentication; deny entries win over allow entries
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct IpFilterConfig {
+    /// Enable the filter
     #[serde(default)]
-    pub server: ServerConfig,
+    pub enabled: bool,
+
+    /// CIDRs allowed to connect (if empty, every source not denied is allowed)
+    /// Examples: "10.0.0.0/8", "2001:db8::/32", "192.168.1.7"
     #[serde(default)]
-    pub kbs: Option<KbsConfig>,
+    pub allow: Vec<String>,
+
+    /// CIDRs that are always rejected
+    #[serde(default)]
+    pub deny: Vec<String>,
 }
 
-impl TappConfig {
-    pub fn load(config_path: String) -> TappResult<Self> {
-        toml::from_str(&std::fs::read_to_string(&config_path).map_err(|_| {
-            ConfigError::FileNotFound {
-                path: config_path.clone(),
-            }
-        })?)
-        .map_err(|e| {
-            ConfigError::ParseFailed {
-                reason: e.to_string(),
-            }
-            .into()
-        })
+impl IpFilterConfig {
+    /// Validate the filter section (also used before applying a hot reload)
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        crate::ip_filter_layer::parse_networks("server.ip_filter.allow", &self.allow)?;
+        crate::ip_filter_layer::parse_networks("server.ip_filter.deny", &self.deny)?;
+        Ok(())
     }
 }
 
-/// Server configuration
+/// RPC audit log configuration
 #[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct ServerConfig {
-    /// Bind address for gRPC server
-    #[serde(default = "default_bind_address")]
-    pub bind_address: String,
+pub struct AuditConfig {
+    /// Enable the audit log
+    #[serde(default)]
+    pub enabled: bool,
 
-    /// Maximum number of concurrent connections
-    #[serde(default = "default_max_connections")]
-    pub max_connections: usize,
+    /// Audit log file (separate from the application log)
+    /// Rotated using logging.max_file_size_mb and logging.max_files
+    #[serde(default = "default_audit_file_path")]
+    pub file_path: PathBuf,
 
-    /// Request timeout in seconds
-    #[serde(default = "default_request_timeout")]
-    pub request_timeout_seconds: u64,
+    /// Methods to audit (if empty, all methods are audited)
+    #[serde(default = "default_audited_methods")]
+    pub methods: Vec<String>,
+}
 
-    /// Enable TLS
-    #[serde(default)]
-    pub tls_enabled: bool,
+/// OpenTelemetry trace export (OTLP over gRPC)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TelemetryConfig {
+    /// Collector endpoint, e.g. http://localhost:4317
+    pub endpoint: String,
 
-    /// TLS certificate path (if TLS enabled)
-    pub tls_cert_path: Option<PathBuf>,
+    /// `service.name` of the exported spans
+    #[serde(default = "default_telemetry_service_name")]
+    pub service_name: String,
 
-    /// TLS private key path (if TLS enabled)
-    pub tls_key_path: Option<PathBuf>,
+    /// Share of traces exported, from 0.0 to 1.0 (requests carrying a
+    /// sampled parent context follow the parent's decision)
+    #[serde(default = "default_telemetry_sampling_ratio")]
+    pub sampling_ratio: f64,
+}
 
-    /// API Key configuration for authentication
-    #[serde(default)]
-    pub api_key: Option<ApiKeyConfig>,
+impl TelemetryConfig {
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        if self.endpoint.is_empty() {
+            return Err(ConfigError::MissingField {
+                field: "telemetry.endpoint".to_string(),
+            });
+        }
+        if !(0.0..=1.0).contains(&self.sampling_ratio) {
+            return Err(ConfigError::InvalidValue {
+                field: "telemetry.sampling_ratio".to_string(),
+                reason: "must be between 0.0 and 1.0".to_string(),
+            });
+        }
+        Ok(())
+    }
 }
 
-/// API Key authentication configuration
+/// Event types that can be sent to webhooks
+pub const NOTIFICATION_EVENTS: &[&str] = &["task_failed", "attestation_failed", "disk_threshold"];
+
+/// Webhook notifications of app and service events
 #[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct ApiKeyConfig {
-    /// Enable API key authentication
+pub struct NotificationConfig {
+    /// Enable notifications
     #[serde(default)]
     pub enabled: bool,
 
-    /// List of valid API keys (in production, use env vars or secrets management)
-    pub keys: Vec<String>,
+    /// URLs every event is POSTed to as JSON
+    #[serde(default)]
+    pub webhooks: Vec<String>,
 
-    /// Methods that require authentication (if empty, all methods require auth)
-    /// Examples: "StartApp", "GetAppSecretKey"
+    /// Event types to send (if empty, all events are sent)
     #[serde(default)]
-    pub protected_methods: Vec<String>,
+    pub events: Vec<String>,
+
+    /// Secret signing each body with HMAC-SHA256 (`x-tapp-signature` header)
+    #[serde(default)]
+    pub secret: Option<String>,
+
+    /// Retries of a failed delivery, with exponential backoff
+    #[serde(default = "default_notification_retries")]
+    pub max_retries: u32,
+
+    /// Delay before the first retry, doubled for each further one
+    #[serde(default = "default_notification_retry_delay")]
+    pub retry_delay_ms: u64,
+
+    /// Timeout of one delivery attempt
+    #[serde(default = "default_notification_timeout")]
+    pub timeout_seconds: u64,
+}
+
+impl NotificationConfig {
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        if self.enabled && self.webhooks.is_empty() {
+            return Err(ConfigError::InvalidValue {
+                field: "notifications.webhooks".to_string(),
+                reason: "at least one webhook URL is required".to_string(),
+            });
+        }
+        for url in &self.webhooks {
+            if !url.starts_with("https://") && !url.starts_with("http://") {
+                return Err(ConfigError::InvalidValue {
+                    field: "notifications.webhooks".to_string(),
+                    reason: "webhook URLs must be http:// or https://".to_string(),
+                });
+            }
+        }
+        for event in &self.events {
+            if !NOTIFICATION_EVENTS.contains(&event.as_str()) {
+                return Err(ConfigError::InvalidValue {
+                    field: "notifications.events".to_string(),
+                    reason: format!(
+                        "unknown event {}, expected one of: {}",
+                        event,
+                        NOTIFICATION_EVENTS.join(", ")
+                    ),
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether events of this type are sent
+    pub fn wants(&self, event: &str) -> bool {
+        self.events.is_empty() || self.events.iter().any(|e| e == event)
+    }
 }
 
 /// KBS configuration
@@ -145,7 +2379,7 @@ pub struct KbsConfig {
     pub supported_key_types: Vec<String>,
 }
 
-/// Retry configuration for KBS operations
+/// Retries with exponential backoff (KBS operations, Docker and contract calls)
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RetryConfig {
     /// Maximum number of retry attempts
@@ -162,20 +2396,179 @@ pub struct RetryConfig {
 }
 
 // Default value functions
-fn default_bind_address() -> String {
-    "0.0.0.0:50051".to_string()
+fn default_bind_address() -> BindAddresses {
+    BindAddresses::from("0.0.0.0:50051")
 }
 
 fn default_max_connections() -> usize {
     1000
 }
 
-fn default_request_timeout() -> u64 {
+fn default_request_timeout() -> u64 {
+    30
+}
+
+fn default_shutdown_grace() -> u64 {
+    30
+}
+
+fn default_enable_reflection() -> bool {
+    true
+}
+
+fn default_keepalive_timeout() -> u64 {
+    20
+}
+
+fn default_compression() -> Vec<String> {
+    COMPRESSION_CODECS
+        .iter()
+        .map(|codec| codec.to_string())
+        .collect()
+}
+
+fn default_unix_socket_path() -> PathBuf {
+    PathBuf::from("/run/tapp/tapp.sock")
+}
+
+fn default_unix_socket_mode() -> String {
+    "0660".to_string()
+}
+
+fn default_web_max_age() -> u64 {
+    3600
+}
+
+fn default_max_message_mb() -> usize {
+    4
+}
+
+fn default_requests_per_minute() -> u32 {
+    120
+}
+
+fn default_max_tracked_clients() -> usize {
+    10000
+}
+
+fn default_lockout_max_failures() -> u32 {
+    5
+}
+
+fn default_lockout_failure_window() -> u64 {
+    300
+}
+
+fn default_lockout_base_seconds() -> u64 {
+    30
+}
+
+fn default_lockout_max_seconds() -> u64 {
+    3600
+}
+
+fn default_expiry_horizon_days() -> u64 {
+    14
+}
+
+fn default_nonce_validity() -> u64 {
+    300
+}
+
+fn default_nonce_cleanup_interval() -> u64 {
+    60
+}
+
+fn default_timestamp_tolerance() -> u64 {
+    crate::nonce_manager::DEFAULT_TIMESTAMP_TOLERANCE as u64
+}
+
+fn default_max_tracked_nonces() -> usize {
+    crate::nonce_manager::DEFAULT_MAX_NONCES
+}
+
+fn default_hmac_max_clock_skew() -> i64 {
+    60
+}
+
+fn default_hmac_nonce_window() -> i64 {
+    300
+}
+
+fn default_audit_file_path() -> PathBuf {
+    PathBuf::from("/var/log/tapp/audit.log")
+}
+
+fn default_telemetry_endpoint() -> String {
+    "http://localhost:4317".to_string()
+}
+
+fn default_telemetry_service_name() -> String {
+    "tapp-service".to_string()
+}
+
+fn default_telemetry_sampling_ratio() -> f64 {
+    1.0
+}
+
+fn default_notification_retries() -> u32 {
+    3
+}
+
+fn default_notification_retry_delay() -> u64 {
+    1000
+}
+
+fn default_notification_timeout() -> u64 {
+    10
+}
+
+fn default_audited_methods() -> Vec<String> {
+    vec![
+        "StartApp".to_string(),
+        "StopApp".to_string(),
+        "GetAppKey".to_string(),
+        "GetAttestedAppKey".to_string(),
+        "GetAppSecretKey".to_string(),
+        "SignTransaction".to_string(),
+        "ForceRemoveApp".to_string(),
+        "InitCluster".to_string(),
+        "RequestKeyShare".to_string(),
+        "JoinCluster".to_string(),
+        "RecoverShare".to_string(),
+        "ContributeShare".to_string(),
+        "RecoverCluster".to_string(),
+        "ExportKeystoreBackup".to_string(),
+        "ImportKeystoreBackup".to_string(),
+    ]
+}
+
+fn default_kbs_timeout() -> u64 {
+    30
+}
+
+fn default_contract_rpc_url() -> String {
+    "http://127.0.0.1:8545".to_string()
+}
+
+fn default_contract_timeout() -> u64 {
+    10
+}
+
+fn default_contract_poll_interval() -> u64 {
+    5
+}
+
+fn default_contract_confirmation_timeout() -> u64 {
+    600
+}
+
+fn default_attestor_cache_ttl() -> u64 {
     30
 }
 
-fn default_kbs_timeout() -> u64 {
-    30
+fn default_attestor_max_stale() -> u64 {
+    300
 }
 
 fn default_supported_key_types() -> Vec<String> {
@@ -198,10 +2591,90 @@ fn default_docker_socket() -> String {
     "/var/run/docker.sock".to_string()
 }
 
+fn default_data_dir() -> PathBuf {
+    PathBuf::from("/var/lib/tapp/apps")
+}
+
 fn default_container_timeout() -> u64 {
     300
 }
 
+fn default_task_retention() -> u64 {
+    3600
+}
+
+fn default_max_finished_tasks() -> usize {
+    1000
+}
+
+fn default_max_concurrent_deployments() -> usize {
+    2
+}
+
+fn default_max_queued_deployments() -> usize {
+    16
+}
+
+fn default_task_state_dir() -> String {
+    "/var/lib/tapp/tasks".to_string()
+}
+
+fn default_extend_history_path() -> String {
+    "/var/lib/tapp/rtmr-history.jsonl".to_string()
+}
+
+fn default_restore_apps() -> bool {
+    true
+}
+
+fn default_cluster_state_dir() -> String {
+    "/var/lib/tapp/cluster".to_string()
+}
+
+fn default_deploy_logs_per_app() -> usize {
+    5
+}
+
+fn default_health_check_interval() -> u64 {
+    10
+}
+
+fn default_health_failure_threshold() -> u32 {
+    3
+}
+
+fn default_resource_warning_percent() -> f64 {
+    90.0
+}
+
+fn default_resource_cache_seconds() -> u64 {
+    5
+}
+
+fn default_eventlog_enabled() -> bool {
+    true
+}
+
+fn default_caller_identity_enabled() -> bool {
+    true
+}
+
+fn default_caller_identity_cache_ttl() -> u64 {
+    5
+}
+
+fn default_image_scan_timeout() -> u64 {
+    300
+}
+
+fn default_max_sealed_blob_bytes() -> usize {
+    64 * 1024
+}
+
+fn default_max_sealed_blobs_per_app() -> usize {
+    64
+}
+
 fn default_log_level() -> String {
     "info".to_string()
 }
@@ -218,6 +2691,10 @@ fn default_max_log_files() -> usize {
     10
 }
 
+fn default_level_revert_seconds() -> u64 {
+    3600
+}
+
 fn default_kbs_endpoint() -> String {
     "http://localhost:8080".to_string()
 }
@@ -234,16 +2711,211 @@ impl Default for KbsConfig {
     }
 }
 
+impl Default for ContractConfig {
+    fn default() -> Self {
+        Self {
+            rpc_url: default_contract_rpc_url(),
+            address: String::new(),
+            chain_id: 0,
+            funder_key_file: None,
+            timeout_seconds: default_contract_timeout(),
+            poll_interval_seconds: default_contract_poll_interval(),
+            confirmation_timeout_seconds: default_contract_confirmation_timeout(),
+            retry: RetryConfig::default(),
+            attestor_auth: AttestorAuthConfig::default(),
+        }
+    }
+}
+
+impl Default for AttestorAuthConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            cache_ttl_seconds: default_attestor_cache_ttl(),
+            max_stale_seconds: default_attestor_max_stale(),
+            failure_policy: AttestorFailurePolicy::default(),
+        }
+    }
+}
+
+impl Default for SecurityConfig {
+    fn default() -> Self {
+        Self {
+            nonce_validity_seconds: default_nonce_validity(),
+            nonce_cleanup_interval_seconds: default_nonce_cleanup_interval(),
+            timestamp_tolerance_seconds: default_timestamp_tolerance(),
+        }
+    }
+}
+
 impl Default for ServerConfig {
     fn default() -> Self {
         Self {
             bind_address: default_bind_address(),
+            admin_bind_address: None,
             max_connections: default_max_connections(),
+            max_tracked_nonces: default_max_tracked_nonces(),
             request_timeout_seconds: default_request_timeout(),
+            shutdown_grace_seconds: default_shutdown_grace(),
+            method_timeout_seconds: HashMap::new(),
+            enable_reflection: default_enable_reflection(),
             tls_enabled: false,
             tls_cert_path: None,
             tls_key_path: None,
             api_key: None,
+            rate_limit: None,
+            ip_filter: None,
+            unix_socket: None,
+            web: None,
+            max_recv_message_mb: default_max_message_mb(),
+            max_send_message_mb: default_max_message_mb(),
+            compression: default_compression(),
+            method_max_recv_message_mb: HashMap::new(),
+            health: HealthCheckConfig::default(),
+            http2: Http2Config::default(),
+        }
+    }
+}
+
+impl Default for Http2Config {
+    fn default() -> Self {
+        Self {
+            keepalive_interval_seconds: 0,
+            keepalive_timeout_seconds: default_keepalive_timeout(),
+            max_concurrent_streams: 0,
+            max_connection_age_seconds: 0,
+        }
+    }
+}
+
+impl Default for HealthCheckConfig {
+    fn default() -> Self {
+        Self {
+            check_interval_seconds: default_health_check_interval(),
+            docker_failure_threshold: default_health_failure_threshold(),
+            attestation_failure_threshold: default_health_failure_threshold(),
+        }
+    }
+}
+
+impl Default for ClusterConfig {
+    fn default() -> Self {
+        Self {
+            state_dir: default_cluster_state_dir(),
+            evidence_verifier: EvidenceVerifierKind::default(),
+            allowed_signers: Vec::new(),
+            allowed_code_hashes: Vec::new(),
+        }
+    }
+}
+
+impl Default for MonitoringConfig {
+    fn default() -> Self {
+        Self {
+            disk_warning_percent: default_resource_warning_percent(),
+            memory_warning_percent: default_resource_warning_percent(),
+            cache_seconds: default_resource_cache_seconds(),
+        }
+    }
+}
+
+impl Default for UnixSocketConfig {
+    fn default() -> Self {
+        Self {
+            path: default_unix_socket_path(),
+            mode: default_unix_socket_mode(),
+            uid: None,
+            gid: None,
+        }
+    }
+}
+
+impl Default for WebConfig {
+    fn default() -> Self {
+        Self {
+            allowed_origins: Vec::new(),
+            max_age_seconds: default_web_max_age(),
+        }
+    }
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            default_requests_per_minute: default_requests_per_minute(),
+            method_limits: HashMap::new(),
+            max_tracked_clients: default_max_tracked_clients(),
+        }
+    }
+}
+
+impl Default for AuthLockoutConfig {
+    fn default() -> Self {
+        Self {
+            max_failures: default_lockout_max_failures(),
+            failure_window_seconds: default_lockout_failure_window(),
+            base_lockout_seconds: default_lockout_base_seconds(),
+            max_lockout_seconds: default_lockout_max_seconds(),
+            max_tracked_sources: default_max_tracked_clients(),
+        }
+    }
+}
+
+impl Default for HmacAuthConfig {
+    fn default() -> Self {
+        Self {
+            max_clock_skew_seconds: default_hmac_max_clock_skew(),
+            nonce_window_seconds: default_hmac_nonce_window(),
+            max_tracked_nonces: default_max_tracked_nonces(),
+        }
+    }
+}
+
+impl Default for AuditConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            file_path: default_audit_file_path(),
+            methods: default_audited_methods(),
+        }
+    }
+}
+
+impl Default for ApiKeyConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            keys: Vec::new(),
+            protected_methods: Vec::new(),
+            lockout: AuthLockoutConfig::default(),
+            hmac: HmacAuthConfig::default(),
+            expiry_horizon_days: default_expiry_horizon_days(),
+            namespaces: false,
+        }
+    }
+}
+
+impl Default for TelemetryConfig {
+    fn default() -> Self {
+        Self {
+            endpoint: default_telemetry_endpoint(),
+            service_name: default_telemetry_service_name(),
+            sampling_ratio: default_telemetry_sampling_ratio(),
+        }
+    }
+}
+
+impl Default for NotificationConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            webhooks: Vec::new(),
+            events: Vec::new(),
+            secret: None,
+            max_retries: default_notification_retries(),
+            retry_delay_ms: default_notification_retry_delay(),
+            timeout_seconds: default_notification_timeout(),
         }
     }
 }
@@ -262,8 +2934,72 @@ impl Default for BootServiceConfig {
     fn default() -> Self {
         Self {
             aa_config_path: Some("config/attestation-agent.toml".to_string()),
+            manage_aa_config: false,
+            aa: AaConfig::default(),
+            runtime: ContainerRuntime::default(),
             socket_path: default_docker_socket(),
+            compose_command: None,
+            container_networks: Vec::new(),
+            allow_emulation: false,
+            data_dir: default_data_dir(),
             container_timeout_seconds: default_container_timeout(),
+            task_retention_seconds: default_task_retention(),
+            max_finished_tasks: default_max_finished_tasks(),
+            auto_retry_attempts: 0,
+            retry: RetryConfig::default(),
+            max_concurrent_deployments: default_max_concurrent_deployments(),
+            max_queued_deployments: default_max_queued_deployments(),
+            task_state_dir: default_task_state_dir(),
+            extend_history_path: default_extend_history_path(),
+            restore_apps: default_restore_apps(),
+            deploy_logs_per_app: default_deploy_logs_per_app(),
+            caller_identity: CallerIdentityConfig::default(),
+            image_policy: None,
+            image_scan: None,
+            sealed_data: SealedDataConfig::default(),
+            key_policy: KeyPolicyConfig::default(),
+        }
+    }
+}
+
+impl Default for AaConfig {
+    fn default() -> Self {
+        Self {
+            eventlog_enabled: default_eventlog_enabled(),
+            eventlog_path: None,
+            token_configs: toml::Table::new(),
+        }
+    }
+}
+
+impl Default for CallerIdentityConfig {
+    fn default() -> Self {
+        Self {
+            enabled: default_caller_identity_enabled(),
+            trusted_helpers: Vec::new(),
+            cache_ttl_seconds: default_caller_identity_cache_ttl(),
+        }
+    }
+}
+
+impl Default for SealedDataConfig {
+    fn default() -> Self {
+        Self {
+            max_blob_bytes: default_max_sealed_blob_bytes(),
+            max_blobs_per_app: default_max_sealed_blobs_per_app(),
+        }
+    }
+}
+
+impl Default for ImageScanConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            command: Vec::new(),
+            endpoint: None,
+            severity_threshold: ScanSeverity::default(),
+            timeout_seconds: default_image_scan_timeout(),
+            fail_open: false,
         }
     }
 }
@@ -276,8 +3012,660 @@ impl Default for LoggingConfig {
             file_path: None,
             max_file_size_mb: default_max_log_size(),
             max_files: default_max_log_files(),
+            level_revert_seconds: default_level_revert_seconds(),
         }
     }
 }
 
 impl TappConfig {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FILE: &str = r#"
+[server]
+bind_address = "127.0.0.1:50051"
+max_connections = 200
+
+[server.method_max_recv_message_mb]
+StartApp = 8
+
+[logging]
+level = "info"
+format = "json"
+"#;
+
+    fn vars(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+        pairs
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_env_overrides_file() {
+        let config = TappConfig::parse(
+            FILE,
+            vars(&[
+                ("TAPP_SERVER__BIND_ADDRESS", "0.0.0.0:6000"),
+                ("TAPP_LOGGING__LEVEL", "info,tapp_service::boot=debug"),
+                ("TAPP_BOOT__SOCKET_PATH", "/run/docker.sock"),
+                ("TAPP_BOOT__DEPLOY_LOGS_PER_APP", "9"),
+                ("TAPP_SERVER__METHOD_MAX_RECV_MESSAGE_MB__STARTAPP", "16"),
+                ("TAPP_SERVER__API_KEY__ENABLED", "true"),
+                ("TAPP_SERVER__API_KEY__KEYS", r#"["key-from-env"]"#),
+                ("TAPP_KBS__ENDPOINT", "https://kbs.example:8080"),
+                ("TAPP_TELEMETRY__ENDPOINT", "http://collector:4317"),
+                ("TAPP_TELEMETRY__SAMPLING_RATIO", "0.25"),
+                // Not settings: other TAPP_ variables and the rest of the environment
+                ("TAPP_HMAC_SECRET", "client secret"),
+                ("TAPP_SERVER", "http://10.0.0.1:50051"),
+                ("TAPP_API_KEY", "client key"),
+                ("PATH", "/usr/bin"),
+            ]),
+        )
+        .unwrap();
+
+        assert_eq!(config.server.bind_address, "0.0.0.0:6000");
+        assert_eq!(config.logging.level, "info,tapp_service::boot=debug");
+        assert_eq!(config.boot.socket_path, "/run/docker.sock");
+        assert_eq!(config.boot.deploy_logs_per_app, 9);
+        // Map keys keep the case they have in the file
+        assert_eq!(config.server.method_max_recv_message_mb["StartApp"], 16);
+        let api_key = config.server.api_key.unwrap();
+        assert!(api_key.enabled);
+        assert_eq!(api_key.keys[0].key(), "key-from-env");
+        let kbs = config.kbs.unwrap();
+        assert_eq!(kbs.endpoint, "https://kbs.example:8080");
+        assert_eq!(kbs.timeout_seconds, default_kbs_timeout());
+        assert_eq!(config.telemetry.unwrap().sampling_ratio, 0.25);
+
+        // Values without a variable come from the file, then the defaults
+        assert_eq!(config.server.max_connections, 200);
+        assert_eq!(config.logging.format, "json");
+        assert_eq!(
+            config.security.nonce_validity_seconds,
+            default_nonce_validity()
+        );
+    }
+
+    #[test]
+    fn test_env_errors_name_the_variable() {
+        let field = |pairs: &[(&str, &str)]| match TappConfig::parse(FILE, vars(pairs)) {
+            Err(ConfigError::InvalidValue { field, reason }) => (field, reason),
+            other => panic!("expected InvalidValue, got {:?}", other),
+        };
+
+        let (name, reason) = field(&[("TAPP_BOOT__DEPLOY_LOGS_PER_APP", "many")]);
+        assert_eq!(name, "TAPP_BOOT__DEPLOY_LOGS_PER_APP");
+        assert_eq!(reason, "expected an integer, got 'many'");
+
+        let (name, reason) = field(&[("TAPP_SERVER__TLS_ENABLED", "yes")]);
+        assert_eq!(name, "TAPP_SERVER__TLS_ENABLED");
+        assert!(reason.contains("true or false"));
+
+        let (name, _) = field(&[("TAPP_SERVER__BIND_ADDRESS__PORT", "1")]);
+        assert_eq!(name, "TAPP_SERVER__BIND_ADDRESS__PORT");
+
+        // Settings outside the file and defaults fail when deserialized
+        let (name, reason) = field(&[
+            ("TAPP_TELEMETRY__ENDPOINT", "http://collector:4317"),
+            ("TAPP_TELEMETRY__SAMPLING_RATIO", "half"),
+        ]);
+        assert_eq!(name, "TAPP_TELEMETRY__SAMPLING_RATIO");
+        assert!(reason.contains("half"), "{}", reason);
+
+        let (name, reason) = field(&[("TAPP_TELEMETRY__SAMPLING_RATIO", "0.5")]);
+        assert_eq!(name, "TAPP_TELEMETRY__SAMPLING_RATIO");
+        assert!(reason.contains("endpoint"), "{}", reason);
+
+        // A broken file is reported as such
+        assert!(matches!(
+            TappConfig::parse("[server", vars(&[])),
+            Err(ConfigError::ParseFailed { .. })
+        ));
+    }
+
+    #[test]
+    fn test_load_applies_environment() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        std::fs::write(&path, FILE).unwrap();
+
+        std::env::set_var("TAPP_SECURITY__NONCE_VALIDITY_SECONDS", "120");
+        let config = TappConfig::load(path.to_string_lossy().to_string());
+        let defaults = TappConfig::from_env();
+        std::env::remove_var("TAPP_SECURITY__NONCE_VALIDITY_SECONDS");
+
+        let config = config.unwrap();
+        assert_eq!(config.security.nonce_validity_seconds, 120);
+        assert_eq!(config.server.bind_address, "127.0.0.1:50051");
+        let defaults = defaults.unwrap();
+        assert_eq!(defaults.security.nonce_validity_seconds, 120);
+        assert_eq!(defaults.server.bind_address, default_bind_address());
+    }
+
+    #[test]
+    fn test_config_dir_merges_fragments() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join("00-base.toml"),
+            r#"
+[server]
+bind_address = "0.0.0.0:50051"
+max_connections = 500
+
+[server.api_key]
+enabled = true
+keys = ["base-key", "shared-key"]
+protected_methods = ["StartApp"]
+
+[logging]
+level = "info"
+format = "json"
+"#,
+        )
+        .unwrap();
+        std::fs::write(
+            dir.path().join("10-node.toml"),
+            r#"
+[server]
+bind_address = ["10.0.0.5:50051", "[::1]:50051"]
+
+[server.api_key]
+keys = ["node-key"]
+
+[logging]
+level = "debug"
+"#,
+        )
+        .unwrap();
+        // Only *.toml files are read
+        std::fs::write(dir.path().join("20-notes.txt"), "not toml").unwrap();
+
+        let merged = merge_dir(dir.path()).unwrap();
+        let config = TappConfig::from_table(
+            merged,
+            vars(&[
+                ("TAPP_LOGGING__LEVEL", "warn"),
+                ("TAPP_SERVER__MAX_CONNECTIONS", "700"),
+            ]),
+        )
+        .unwrap();
+        // Later files override values and replace arrays; tables merge
+        assert_eq!(
+            config.server.bind_address.0,
+            vec!["10.0.0.5:50051", "[::1]:50051"]
+        );
+        let api_key = config.server.api_key.unwrap();
+        assert!(api_key.enabled);
+        assert_eq!(
+            api_key.keys,
+            vec![ApiKeyEntry::Inline("node-key".to_string())]
+        );
+        assert_eq!(api_key.protected_methods, vec!["StartApp"]);
+        assert_eq!(config.logging.format, "json");
+        // The environment still overrides every file
+        assert_eq!(config.logging.level, "warn");
+        assert_eq!(config.server.max_connections, 700);
+
+        // load() takes the directory in place of a file
+        let loaded = TappConfig::load(dir.path().to_string_lossy().to_string()).unwrap();
+        assert_eq!(loaded.server.max_connections, 500);
+
+        // A table in one file and a value in another names both files
+        std::fs::write(dir.path().join("30-bad.toml"), "logging = \"debug\"\n").unwrap();
+        match merge_dir(dir.path()).unwrap_err() {
+            ConfigError::InvalidValue { field, reason } => {
+                assert_eq!(field, "logging");
+                assert_eq!(
+                    reason,
+                    format!(
+                        "string in {} conflicts with table in {}",
+                        dir.path().join("30-bad.toml").display(),
+                        dir.path().join("00-base.toml").display()
+                    )
+                );
+            }
+            other => panic!("expected InvalidValue, got {:?}", other),
+        }
+
+        let empty = tempfile::tempdir().unwrap();
+        assert!(matches!(
+            merge_dir(empty.path()),
+            Err(ConfigError::FileNotFound { .. })
+        ));
+    }
+
+    #[test]
+    fn test_bind_address_list() {
+        let parse = |content: &str| TappConfig::parse(content, vars(&[])).unwrap();
+
+        // A single address, a list, or a comma-separated string
+        let config = parse("[server]\nbind_address = \"127.0.0.1:50051\"\n");
+        assert_eq!(config.server.bind_address.0, vec!["127.0.0.1:50051"]);
+        let config = parse("[server]\nbind_address = [\"0.0.0.0:50051\", \"[::]:50051\"]\n");
+        assert_eq!(
+            config.server.bind_address.0,
+            vec!["0.0.0.0:50051", "[::]:50051"]
+        );
+        assert_eq!(
+            config.server.bind_address.to_string(),
+            "0.0.0.0:50051, [::]:50051"
+        );
+        let config = TappConfig::parse(
+            "",
+            vars(&[("TAPP_SERVER__BIND_ADDRESS", "0.0.0.0:6000, [::1]:6000")]),
+        )
+        .unwrap();
+        assert_eq!(
+            config.server.bind_address.0,
+            vec!["0.0.0.0:6000", "[::1]:6000"]
+        );
+
+        // One address is still written as a string
+        let rendered = toml::to_string(&config.server).unwrap();
+        assert!(rendered.contains("bind_address = [\"0.0.0.0:6000\", \"[::1]:6000\"]"));
+        let rendered = toml::to_string(&ServerConfig::default()).unwrap();
+        assert!(rendered.contains("bind_address = \"0.0.0.0:50051\""));
+
+        assert_eq!(
+            problems("[server]\nbind_address = []\n"),
+            vec!["server.bind_address: at least one address is required"]
+        );
+        assert_eq!(
+            problems(
+                "[server]\nbind_address = [\"127.0.0.1:50051\", \"[::1]:50051\", \"127.0.0.1:50051\"]\nadmin_bind_address = \"[::1]:50051\"\n"
+            ),
+            vec![
+                "server.bind_address: '127.0.0.1:50051' is listed more than once",
+                "server.admin_bind_address: must differ from server.bind_address",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_api_key_sources() {
+        let dir = tempfile::tempdir().unwrap();
+        let key_path = dir.path().join("ci-key");
+        std::fs::write(&key_path, "file-secret \n\n").unwrap();
+        let env = |name: &str| match name {
+            "TAPP_API_KEY_DEPLOY" => Some("env-secret\n".to_string()),
+            "TAPP_API_KEY_BLANK" => Some("  \n".to_string()),
+            _ => None,
+        };
+        let resolve = |keys: &str| {
+            let mut config = TappConfig::parse(
+                &format!("[server.api_key]\nenabled = true\nkeys = [{}]\n", keys),
+                vars(&[]),
+            )
+            .unwrap();
+            config
+                .resolve_api_keys(env)
+                .map(|()| config.server.api_key.unwrap().keys)
+        };
+
+        let keys = resolve(&format!(
+            r#""inline-secret", {{ name = "ci", key_file = {:?} }}, {{ name = "deploy", key_env = "TAPP_API_KEY_DEPLOY" }}"#,
+            key_path
+        ))
+        .unwrap();
+        let resolved: Vec<(&str, String)> = keys.iter().map(|k| (k.key(), k.source())).collect();
+        assert_eq!(
+            resolved,
+            vec![
+                ("inline-secret", "config".to_string()),
+                ("file-secret", format!("file {}", key_path.display())),
+                ("env-secret", "env TAPP_API_KEY_DEPLOY".to_string()),
+            ]
+        );
+
+        // Printed configurations show the source, not the key
+        let mut config = TappConfig::default();
+        config.server.api_key = Some(ApiKeyConfig {
+            enabled: true,
+            keys,
+            ..Default::default()
+        });
+        let printed = toml::to_string(&config.redacted()).unwrap();
+        assert!(!printed.contains("secret"), "{}", printed);
+        assert!(printed.contains("key_env = \"TAPP_API_KEY_DEPLOY\""));
+
+        let error = |keys: &str| match resolve(keys).unwrap_err() {
+            ConfigError::InvalidValue { field, reason } => {
+                assert_eq!(field, "server.api_key.keys");
+                reason
+            }
+            other => panic!("expected InvalidValue, got {:?}", other),
+        };
+        let missing = dir.path().join("missing");
+        let reason = error(&format!(r#"{{ name = "ci", key_file = {:?} }}"#, missing));
+        let expected = format!(
+            "key 'ci': key_file {} could not be read: ",
+            missing.display()
+        );
+        assert!(reason.starts_with(&expected), "{}", reason);
+        std::fs::write(&key_path, "\n").unwrap();
+        assert_eq!(
+            error(&format!(r#"{{ name = "ci", key_file = {:?} }}"#, key_path)),
+            format!("key 'ci': key_file {} is empty", key_path.display())
+        );
+        assert_eq!(
+            error(r#"{ name = "ci", key_env = "TAPP_API_KEY_UNSET" }"#),
+            "key 'ci': key_env TAPP_API_KEY_UNSET is not set"
+        );
+        assert_eq!(
+            error(r#"{ name = "ci", key_env = "TAPP_API_KEY_BLANK" }"#),
+            "key 'ci': key_env TAPP_API_KEY_BLANK is empty"
+        );
+        assert_eq!(
+            error(r#"{ name = "ci", key = "x", key_env = "TAPP_API_KEY_DEPLOY" }"#),
+            "key 'ci': set either key or a key_file/key_env source, not both"
+        );
+    }
+
+    /// Problems reported for `content` as "field: reason"
+    fn problems(content: &str) -> Vec<String> {
+        let config = TappConfig::parse(content, vars(&[])).unwrap();
+        match config.validate() {
+            Ok(()) => Vec::new(),
+            Err(ConfigError::Invalid { problems }) => problems
+                .into_iter()
+                .map(|problem| match problem {
+                    ConfigError::InvalidValue { field, reason } => format!("{}: {}", field, reason),
+                    other => other.to_string(),
+                })
+                .collect(),
+            Err(other) => panic!("expected Invalid, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_validate_reports_every_problem() {
+        assert!(problems("").is_empty());
+        assert!(problems(FILE).is_empty());
+
+        let found = problems(
+            r#"
+[server]
+bind_address = "hello"
+max_connections = 0
+tls_enabled = true
+tls_cert_path = "/nonexistent/server.crt"
+
+[server.api_key]
+enabled = true
+keys = []
+
+[logging]
+format = "yaml"
+
+[boot.image_policy]
+enabled = true
+allowed_registries = ["nginx"]
+"#,
+        );
+        assert_eq!(
+            found,
+            vec![
+                "server.bind_address: 'hello' is not an address such as 0.0.0.0:50051: invalid socket address syntax",
+                "server.max_connections: must be greater than 0",
+                "server.tls_cert_path: file /nonexistent/server.crt does not exist",
+                "server.tls_key_path: required when server.tls_enabled is true",
+                "logging.format: 'yaml' is not supported, expected one of: json, pretty",
+                "server.api_key.keys: at least one key is required when API key auth is enabled",
+                "boot.image_policy.allowed_registries: 'nginx' is not a registry such as registry.0g.dev or ghcr.io/0glabs",
+            ]
+        );
+
+        // The error lists them all in one message
+        let err = TappConfig::parse(
+            r#"logging = { format = "yaml", level = "info,tapp_service=loud" }"#,
+            vars(&[]),
+        )
+        .unwrap()
+        .validate()
+        .unwrap_err();
+        assert!(
+            err.to_string().starts_with("2 configuration problem(s): "),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn test_api_key_namespaces() {
+        let keys = |keys: &str| {
+            problems(&format!(
+                "[server.api_key]\nenabled = true\nnamespaces = true\nkeys = [{}]\n",
+                keys
+            ))
+        };
+        assert!(keys(
+            r#"{ name = "a", key = "a-key", namespace = "team-a" }, { name = "ops", key = "ops-key", role = "admin" }"#
+        )
+        .is_empty());
+        assert_eq!(
+            keys(r#"{ name = "a", key = "a-key" }"#),
+            vec!["server.api_key.keys: key 'a' needs a namespace when namespaces are enabled"]
+        );
+        assert_eq!(
+            keys(r#"{ name = "a", key = "a-key", namespace = "Team_A" }"#),
+            vec!["server.api_key.keys: key 'a': namespace 'Team_A' must be 1 to 32 lowercase letters, digits or '-'"]
+        );
+        assert_eq!(
+            problems("[server.api_key]\nnamespaces = true\nkeys = []\n"),
+            vec!["server.api_key.namespaces: requires API key auth to be enabled"]
+        );
+
+        // Without the switch a namespace is only validated
+        let config = TappConfig::parse(
+            "[server.api_key]\nenabled = true\nkeys = [{ name = \"a\", key = \"a-key\", namespace = \"team-a\" }]\n",
+            vars(&[]),
+        )
+        .unwrap();
+        let api_key = config.server.api_key.unwrap();
+        assert!(!api_key.namespaces);
+        assert_eq!(api_key.keys[0].namespace(), Some("team-a"));
+    }
+
+    #[test]
+    fn test_validate_ranges_and_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let cert = dir.path().join("server.crt");
+        let key = dir.path().join("server.key");
+        std::fs::write(&cert, "cert").unwrap();
+        std::fs::write(&key, "key").unwrap();
+
+        // Existing files pass
+        assert!(problems(&format!(
+            "[server]\ntls_enabled = true\ntls_cert_path = {:?}\ntls_key_path = {:?}\n",
+            cert, key
+        ))
+        .is_empty());
+
+        let found = problems(&format!(
+            r#"
+[server]
+bind_address = "127.0.0.1:50051"
+admin_bind_address = "127.0.0.1:50051"
+compression = ["gzip", "brotli"]
+
+[server.unix_socket]
+mode = "rw-rw----"
+
+[server.method_max_recv_message_mb]
+StartApp = 0
+
+[boot]
+max_concurrent_deployments = 0
+
+[boot.retry]
+initial_delay_ms = 5000
+max_delay_ms = 1000
+
+[kbs]
+endpoint = "kbs.example:8080"
+cert_path = {:?}
+
+[cluster]
+allowed_signers = ["0x00112233445566778899aabbccddeeff00112233", "00112233445566778899aabbccddeeff00112233"]
+allowed_code_hashes = ["abc"]
+
+[contract]
+address = "0x1234"
+
+[monitoring]
+disk_warning_percent = 150.0
+
+[telemetry]
+endpoint = "http://collector:4317"
+sampling_ratio = 2.0
+"#,
+            dir.path()
+        ));
+        assert_eq!(
+            found,
+            vec![
+                "server.admin_bind_address: must differ from server.bind_address".to_string(),
+                "server.method_max_recv_message_mb.StartApp: must be greater than 0".to_string(),
+                "server.compression: 'brotli' is not supported, expected one of: gzip, zstd"
+                    .to_string(),
+                "server.unix_socket.mode: 'rw-rw----' is not an octal permission mode such as 0660"
+                    .to_string(),
+                "boot.max_concurrent_deployments: must be at least 1".to_string(),
+                "boot.retry.initial_delay_ms: cannot exceed boot.retry.max_delay_ms".to_string(),
+                "kbs.endpoint: 'kbs.example:8080' must be an http:// or https:// URL".to_string(),
+                format!(
+                    "kbs.cert_path: file {} does not exist",
+                    dir.path().display()
+                ),
+                "cluster.allowed_signers: '00112233445566778899aabbccddeeff00112233' is not a \
+                 0x-prefixed 20-byte address"
+                    .to_string(),
+                "cluster.allowed_code_hashes: 'abc' is not a 32-byte hex hash".to_string(),
+                "contract.address: '0x1234' is not a 0x-prefixed 20-byte address".to_string(),
+                "contract.chain_id: must be set".to_string(),
+                "monitoring.disk_warning_percent: must be between 0 and 100".to_string(),
+                "telemetry.sampling_ratio: must be between 0.0 and 1.0".to_string(),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_web_origins() {
+        let found = problems(
+            r#"
+[server.web]
+allowed_origins = [
+    "*",
+    "https://dashboard.example.com",
+    "http://[::1]:8080",
+    "dashboard.example.com",
+    "https://dashboard.example.com/app",
+]
+"#,
+        );
+        let mut expected = Vec::new();
+        if !cfg!(feature = "grpc-web") {
+            expected.push(
+                "server.web: this build has no gRPC-Web support (cargo feature grpc-web)"
+                    .to_string(),
+            );
+        }
+        for origin in ["dashboard.example.com", "https://dashboard.example.com/app"] {
+            expected.push(format!(
+                "server.web.allowed_origins: '{}' is not an origin such as \
+                 https://dashboard.example.com, or \"*\"",
+                origin
+            ));
+        }
+        assert_eq!(found, expected);
+    }
+
+    #[test]
+    fn test_container_runtime() {
+        let config = TappConfig::parse("[boot]\nruntime = \"podman\"\n", vars(&[])).unwrap();
+        assert_eq!(config.boot.runtime, ContainerRuntime::Podman);
+        // The Docker default stands for a podman socket
+        let socket = PathBuf::from(config.boot.runtime_socket());
+        assert!(ContainerRuntime::Podman.default_sockets().contains(&socket));
+        assert_eq!(
+            config.boot.effective_container_networks(),
+            ["10.88.0.0/16", "10.89.0.0/16"]
+        );
+
+        let config = TappConfig::parse(
+            r#"
+[boot]
+runtime = "podman"
+socket_path = "/var/run/podman-docker.sock"
+container_networks = ["10.90.0.0/16"]
+"#,
+            vars(&[]),
+        )
+        .unwrap();
+        assert_eq!(config.boot.runtime_socket(), "/var/run/podman-docker.sock");
+        assert_eq!(config.boot.effective_container_networks(), ["10.90.0.0/16"]);
+        assert_eq!(
+            TappConfig::default().boot.runtime_socket(),
+            "/var/run/docker.sock"
+        );
+
+        assert_eq!(
+            problems("[boot]\ncompose_command = \" \"\ncontainer_networks = [\"10.88.0.0/33\"]\n"),
+            [
+                "boot.compose_command: must not be empty",
+                "boot.container_networks: invalid prefix length in '10.88.0.0/33'",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_image_scan_config() {
+        let config = TappConfig::parse(
+            r#"
+[boot.image_scan]
+enabled = true
+command = ["/usr/local/bin/trivy", "image", "--quiet", "--format", "json", "{image}"]
+severity_threshold = "critical"
+"#,
+            vars(&[]),
+        )
+        .unwrap();
+        let scan = config.boot.image_scan.unwrap();
+        assert_eq!(scan.severity_threshold, ScanSeverity::Critical);
+        assert_eq!(scan.timeout_seconds, 300);
+        assert!(!scan.fail_open);
+        assert!(ScanSeverity::Critical > ScanSeverity::High);
+
+        for (section, problem) in [
+            (
+                "enabled = true",
+                "boot.image_scan.command: a command or an endpoint is required",
+            ),
+            (
+                "command = [\"trivy\", \"image\", \"{image}\"]",
+                "boot.image_scan.command: the program must be an absolute path",
+            ),
+            (
+                "command = [\"/bin/scan\"]\nendpoint = \"http://scanner\"",
+                "boot.image_scan.endpoint: cannot be set together with command",
+            ),
+            (
+                "endpoint = \"scanner:8080\"",
+                "boot.image_scan.endpoint: must be an http:// or https:// URL",
+            ),
+            (
+                "endpoint = \"http://scanner:8080\"\ntimeout_seconds = 0",
+                "boot.image_scan.timeout_seconds: must be at least 1",
+            ),
+        ] {
+            assert_eq!(
+                problems(&format!("[boot.image_scan]\n{}\n", section)),
+                [problem]
+            );
+        }
+    }
+}
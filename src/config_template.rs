@@ -0,0 +1,307 @@
+use crate::config::{
+    ApiKeyConfig, AuditConfig, ContractConfig, ImagePolicyConfig, ImageScanConfig, IpFilterConfig,
+    KbsConfig, NotificationConfig, RateLimitConfig, TappConfig, TelemetryConfig, UnixSocketConfig,
+    WebConfig,
+};
+use std::fmt::Write;
+
+/// Opening lines of a generated configuration file
+const HEADER: &str = "\
+# tapp-server configuration
+# Generated by `tapp-server --generate-config` from the built-in defaults.
+# Any setting can be overridden with a TAPP_<SECTION>__<KEY> environment
+# variable. Commented-out sections are off until uncommented.
+";
+
+/// Note written above each section of a generated file
+const SECTION_NOTES: &[(&str, &str)] = &[
+    ("audit", "RPC audit log, separate from the application log"),
+    (
+        "boot",
+        "Deployments: Docker access, app files and task bookkeeping",
+    ),
+    (
+        "boot.aa",
+        "Attestation agent configuration written to aa_config_path",
+    ),
+    (
+        "boot.aa.token_configs",
+        "AA token settings copied as is, e.g. coco_as = { url = \"http://127.0.0.1:8080\" }",
+    ),
+    (
+        "boot.caller_identity",
+        "Caller container verification for app-scoped secret operations",
+    ),
+    (
+        "boot.image_policy",
+        "Images StartApp may deploy; deny wins, e.g. allowed_registries = [\"registry.0g.dev\"]",
+    ),
+    (
+        "boot.image_scan",
+        "Vulnerability scan of deployment images before up, e.g. command = [\"/usr/local/bin/trivy\", \"image\", \"--format\", \"json\", \"{image}\"]",
+    ),
+    (
+        "boot.key_policy",
+        "What apps may sign with their keys (SignTransaction)",
+    ),
+    (
+        "boot.key_policy.allowed_chain_ids",
+        "Chain IDs an app may sign transactions for (any if not listed), e.g. \"my-app\" = [16661]",
+    ),
+    (
+        "boot.retry",
+        "Retries of transient Docker failures within one deployment attempt",
+    ),
+    (
+        "boot.sealed_data",
+        "Limits of the blobs apps keep with SealData",
+    ),
+    (
+        "cluster",
+        "Key cluster membership and share exchange; this node's share is kept sealed in state_dir",
+    ),
+    (
+        "contract",
+        "Registration of deployed apps on the tapp contract; set address and chain_id",
+    ),
+    (
+        "contract.attestor_auth",
+        "Require GetAppKey and GetAttestedAppKey callers to be registered attestors",
+    ),
+    (
+        "contract.retry",
+        "Retries of contract calls while the RPC endpoint is unreachable",
+    ),
+    ("kbs", "Key broker service"),
+    ("kbs.retry", "Retries of KBS operations"),
+    ("logging", "Application log"),
+    ("monitoring", "Host resource reporting (GetHostResources)"),
+    (
+        "notifications",
+        "Webhook notifications of app and service events",
+    ),
+    ("security", "Replay protection for deployer-signed requests"),
+    ("server", "gRPC listener, limits and authentication"),
+    ("server.api_key", "API key authentication"),
+    (
+        "server.api_key.hmac",
+        "Freshness checks for HMAC-signed requests",
+    ),
+    (
+        "server.api_key.lockout",
+        "Lockout after repeated authentication failures",
+    ),
+    (
+        "server.health",
+        "Dependency checks behind grpc.health.v1.Health",
+    ),
+    (
+        "server.http2",
+        "HTTP/2 keepalive pings and connection limits; 0 disables a ping or limit",
+    ),
+    (
+        "server.ip_filter",
+        "Source address allow and deny lists for the TCP listener",
+    ),
+    (
+        "server.method_max_recv_message_mb",
+        "Per-method request size limits in MB, e.g. StartApp = 64",
+    ),
+    (
+        "server.method_timeout_seconds",
+        "Per-method timeouts in seconds (0 = none), e.g. StopApp = 120",
+    ),
+    (
+        "server.rate_limit",
+        "Request rate limiting per client and method",
+    ),
+    (
+        "server.rate_limit.method_limits",
+        "Per-method requests per minute (0 = unlimited), e.g. GetEvidence = 5",
+    ),
+    ("server.unix_socket", "Local Unix domain socket listener"),
+    (
+        "server.web",
+        "gRPC-Web for browser clients (needs the grpc-web cargo feature)",
+    ),
+    ("telemetry", "OpenTelemetry trace export (OTLP over gRPC)"),
+];
+
+/// Commented configuration file holding every setting with its default
+/// The values come from the `Default` impls, so the file always matches the
+/// code. Sections that are off unless configured are commented out.
+pub fn default_config() -> String {
+    let defaults = TappConfig::default();
+    let mut template = defaults.clone();
+    template.boot.image_policy = Some(ImagePolicyConfig::default());
+    template.boot.image_scan = Some(ImageScanConfig::default());
+    template.server.api_key = Some(ApiKeyConfig::default());
+    template.server.rate_limit = Some(RateLimitConfig::default());
+    template.server.ip_filter = Some(IpFilterConfig::default());
+    template.server.unix_socket = Some(UnixSocketConfig::default());
+    template.server.web = Some(WebConfig::default());
+    template.kbs = Some(KbsConfig::default());
+    template.contract = Some(ContractConfig::default());
+    template.audit = Some(AuditConfig::default());
+    template.notifications = Some(NotificationConfig::default());
+    template.telemetry = Some(TelemetryConfig::default());
+
+    let active = to_table(&defaults);
+    let mut out = HEADER.to_string();
+    write_table(&mut out, "", &to_table(&template), Some(&active), true);
+    out
+}
+
+/// `config` as TOML, e.g. to show the effective configuration
+pub fn render(config: &TappConfig) -> String {
+    render_table(&to_table(config)).trim_start().to_string()
+}
+
+/// `table` as TOML: its values, then its sections in order of name, each
+/// after a blank line
+pub fn render_table(table: &toml::Table) -> String {
+    let mut out = String::new();
+    write_table(&mut out, "", table, Some(table), false);
+    out
+}
+
+fn to_table(config: &TappConfig) -> toml::Table {
+    match toml::Value::try_from(config) {
+        Ok(toml::Value::Table(table)) => table,
+        _ => toml::Table::new(),
+    }
+}
+
+/// Write the values of `table`, then its sections
+/// Sections missing from `active` are commented out.
+fn write_table(
+    out: &mut String,
+    path: &str,
+    table: &toml::Table,
+    active: Option<&toml::Table>,
+    notes: bool,
+) {
+    let prefix = if active.is_some() { "" } else { "# " };
+    if !path.is_empty() {
+        out.push('\n');
+        if let Some((_, note)) = SECTION_NOTES
+            .iter()
+            .find(|(section, _)| notes && *section == path)
+        {
+            let _ = writeln!(out, "# {}", note);
+        }
+        let _ = writeln!(out, "{}[{}]", prefix, path);
+    }
+
+    for (key, value) in table {
+        if !value.is_table() {
+            let _ = writeln!(out, "{}{} = {}", prefix, toml_key(key), value);
+        }
+    }
+    for (key, value) in table {
+        if let toml::Value::Table(section) = value {
+            let section_path = if path.is_empty() {
+                toml_key(key)
+            } else {
+                format!("{}.{}", path, toml_key(key))
+            };
+            let section_active = active
+                .and_then(|active| active.get(key))
+                .and_then(toml::Value::as_table);
+            write_table(out, &section_path, section, section_active, notes);
+        }
+    }
+}
+
+/// `key` as written in TOML: bare if possible, quoted otherwise
+fn toml_key(key: &str) -> String {
+    let bare = !key.is_empty()
+        && key
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+    if bare {
+        key.to_string()
+    } else {
+        toml::Value::String(key.to_string()).to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{ApiKeyEntry, REDACTED};
+
+    const SNAPSHOT: &str = concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/src/snapshots/default_config.toml"
+    );
+
+    #[test]
+    fn test_default_config_snapshot() {
+        let generated = default_config();
+        // UPDATE_SNAPSHOTS=1 cargo test rewrites the snapshot after a change
+        if std::env::var_os("UPDATE_SNAPSHOTS").is_some() {
+            std::fs::write(SNAPSHOT, &generated).unwrap();
+        }
+        assert_eq!(generated, std::fs::read_to_string(SNAPSHOT).unwrap());
+
+        // It loads back as the defaults and passes validation
+        let parsed = TappConfig::parse(&generated, Vec::new()).unwrap();
+        assert_eq!(to_table(&parsed), to_table(&TappConfig::default()));
+        parsed.validate().unwrap();
+
+        // Every section is explained
+        for line in generated.lines() {
+            let header = line.trim_start_matches("# ");
+            if let Some(section) = header.strip_prefix('[') {
+                let section = section.trim_end_matches(']');
+                assert!(
+                    SECTION_NOTES.iter().any(|(name, _)| *name == section),
+                    "no note for [{}]",
+                    section
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_render_masks_secrets() {
+        let mut config = TappConfig::default();
+        let mut api_key = ApiKeyConfig::default();
+        api_key.keys = vec![
+            ApiKeyEntry::Inline("plain-secret".to_string()),
+            ApiKeyEntry::Named {
+                name: "ci".to_string(),
+                key: "named-secret".to_string(),
+                key_file: None,
+                key_env: None,
+                role: Default::default(),
+                key_type: Default::default(),
+                not_before: None,
+                expires_at: None,
+                deprecated: false,
+                namespace: None,
+            },
+        ];
+        config.server.api_key = Some(api_key);
+        config.notifications = Some(NotificationConfig {
+            secret: Some("webhook-secret".to_string()),
+            ..Default::default()
+        });
+        config
+            .server
+            .method_max_recv_message_mb
+            .insert("StartApp".to_string(), 64);
+
+        let rendered = render(&config.redacted());
+        assert!(!rendered.contains("secret\""), "{}", rendered);
+        assert!(rendered.contains(REDACTED));
+        assert!(rendered.contains("name = \"ci\""));
+        assert!(rendered.contains("[server.method_max_recv_message_mb]\nStartApp = 64\n"));
+        // The printed configuration is the configuration
+        let parsed = TappConfig::parse(&rendered, Vec::new()).unwrap();
+        assert_eq!(to_table(&parsed), to_table(&config.redacted()));
+        // The original is untouched
+        assert_eq!(config.server.api_key.unwrap().keys[0].key(), "plain-secret");
+    }
+}
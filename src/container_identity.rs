@@ -0,0 +1,246 @@
+use crate::boot::manager::docker_client;
+use crate::boot::DockerComposeManager;
+use crate::config::CallerIdentityConfig;
+use crate::error::{DockerError, TappResult};
+use bollard::container::ListContainersOptions;
+use bollard::Docker;
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
+
+/// Label docker compose puts on every container of a project
+pub const COMPOSE_PROJECT_LABEL: &str = "com.docker.compose.project";
+
+/// Label podman-compose puts on every container of a project (versions
+/// before 1.0 set only this one)
+pub const PODMAN_COMPOSE_PROJECT_LABEL: &str = "io.podman.compose.project";
+
+/// Misses on a fresh snapshot trigger a refresh at most this often, so a
+/// container started right after the last lookup is still found quickly
+const MIN_REFRESH_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Container owning a Docker network address
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ContainerIdentity {
+    pub id: String,
+    pub name: String,
+    pub compose_project: Option<String>,
+}
+
+/// Why a caller container may act for an app
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CallerMatch {
+    /// The container belongs to the app's compose project
+    AppContainer,
+    /// The container is listed in `trusted_helpers`
+    TrustedHelper,
+}
+
+/// Check whether a caller container may act for an app
+pub fn match_caller(
+    config: &CallerIdentityConfig,
+    caller: &ContainerIdentity,
+    app_id: &str,
+) -> Option<CallerMatch> {
+    let project = caller.compose_project.as_deref();
+
+    if project == Some(DockerComposeManager::compose_project_name(app_id).as_str()) {
+        return Some(CallerMatch::AppContainer);
+    }
+
+    config
+        .trusted_helpers
+        .iter()
+        .any(|helper| project == Some(helper.as_str()) || caller.name == *helper)
+        .then_some(CallerMatch::TrustedHelper)
+}
+
+/// Address to container mapping taken from a single container listing
+struct Snapshot {
+    taken_at: Instant,
+    by_ip: HashMap<IpAddr, ContainerIdentity>,
+}
+
+/// Resolves Docker network addresses to the containers owning them
+/// One container listing maps every running container, and the result is
+/// cached for `cache_ttl_seconds`. Concurrent lookups share a refresh.
+pub struct ContainerResolver {
+    /// The client, or why it could not be created
+    docker: Result<Docker, String>,
+    ttl: Duration,
+    snapshot: Mutex<Option<Snapshot>>,
+}
+
+impl ContainerResolver {
+    /// Resolver asking the container runtime at `socket`
+    pub fn new(config: &CallerIdentityConfig, socket: &str) -> Self {
+        Self {
+            docker: docker_client(socket).map_err(|e| e.to_string()),
+            ttl: Duration::from_secs(config.cache_ttl_seconds),
+            snapshot: Mutex::new(None),
+        }
+    }
+
+    /// Find the running container that owns an address
+    /// Returns None if no container on any Docker network has it
+    pub async fn resolve(&self, ip: IpAddr) -> TappResult<Option<ContainerIdentity>> {
+        let ip = ip.to_canonical();
+        let mut snapshot = self.snapshot.lock().await;
+        let now = Instant::now();
+
+        if let Some(cached) = snapshot.as_ref() {
+            let age = now.duration_since(cached.taken_at);
+            if age < self.ttl {
+                if let Some(container) = cached.by_ip.get(&ip) {
+                    return Ok(Some(container.clone()));
+                }
+                if age < MIN_REFRESH_INTERVAL {
+                    return Ok(None);
+                }
+            }
+        }
+
+        let by_ip = self.list_container_addresses().await?;
+        let found = by_ip.get(&ip).cloned();
+        *snapshot = Some(Snapshot {
+            taken_at: now,
+            by_ip,
+        });
+
+        Ok(found)
+    }
+
+    async fn list_container_addresses(&self) -> TappResult<HashMap<IpAddr, ContainerIdentity>> {
+        let docker = self
+            .docker
+            .as_ref()
+            .map_err(|reason| DockerError::ConnectionFailed {
+                endpoint: String::new(),
+                reason: reason.clone(),
+            })?;
+
+        let containers = docker
+            .list_containers(Some(ListContainersOptions::<String> {
+                all: false,
+                ..Default::default()
+            }))
+            .await?;
+
+        let mut by_ip = HashMap::new();
+        for container in containers {
+            let identity = ContainerIdentity {
+                id: container.id.unwrap_or_default(),
+                name: container
+                    .names
+                    .and_then(|names| names.into_iter().next())
+                    .map(|name| name.trim_start_matches('/').to_string())
+                    .unwrap_or_default(),
+                compose_project: container.labels.and_then(|mut labels| {
+                    labels
+                        .remove(COMPOSE_PROJECT_LABEL)
+                        .or_else(|| labels.remove(PODMAN_COMPOSE_PROJECT_LABEL))
+                }),
+            };
+
+            let endpoints = container
+                .network_settings
+                .and_then(|settings| settings.networks)
+                .unwrap_or_default();
+            for endpoint in endpoints.into_values() {
+                let addresses = [endpoint.ip_address, endpoint.global_ipv6_address];
+                for ip in addresses.into_iter().flatten() {
+                    if let Ok(ip) = ip.parse::<IpAddr>() {
+                        by_ip.insert(ip, identity.clone());
+                    }
+                }
+            }
+        }
+
+        Ok(by_ip)
+    }
+
+    /// Resolver with a fixed mapping and no Docker daemon
+    #[cfg(test)]
+    pub(crate) fn with_containers(containers: Vec<(IpAddr, ContainerIdentity)>) -> Self {
+        Self {
+            docker: Err("no Docker daemon in tests".to_string()),
+            ttl: Duration::from_secs(60),
+            snapshot: Mutex::new(Some(Snapshot {
+                taken_at: Instant::now(),
+                by_ip: containers.into_iter().collect(),
+            })),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn container(name: &str, project: Option<&str>) -> ContainerIdentity {
+        ContainerIdentity {
+            id: format!("{}-id", name),
+            name: name.to_string(),
+            compose_project: project.map(str::to_string),
+        }
+    }
+
+    fn config(trusted_helpers: &[&str]) -> CallerIdentityConfig {
+        CallerIdentityConfig {
+            trusted_helpers: trusted_helpers.iter().map(|s| s.to_string()).collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_match_caller() {
+        let config = config(&["monitoring", "backup-agent"]);
+
+        let own = container("my-app-web-1", Some("my-app"));
+        assert_eq!(
+            match_caller(&config, &own, "my-app"),
+            Some(CallerMatch::AppContainer)
+        );
+        // Compose lowercases the directory name
+        assert_eq!(
+            match_caller(&config, &own, "My-App"),
+            Some(CallerMatch::AppContainer)
+        );
+        assert_eq!(match_caller(&config, &own, "other-app"), None);
+
+        // Helpers match by compose project or container name
+        let helper = container("monitoring-agent-1", Some("monitoring"));
+        assert_eq!(
+            match_caller(&config, &helper, "my-app"),
+            Some(CallerMatch::TrustedHelper)
+        );
+        let standalone = container("backup-agent", None);
+        assert_eq!(
+            match_caller(&config, &standalone, "my-app"),
+            Some(CallerMatch::TrustedHelper)
+        );
+        assert_eq!(
+            match_caller(&config, &container("stray", None), "my-app"),
+            None
+        );
+    }
+
+    #[tokio::test]
+    async fn test_resolve_uses_cached_mapping() {
+        let ip: IpAddr = "172.18.0.5".parse().unwrap();
+        let resolver =
+            ContainerResolver::with_containers(vec![(ip, container("web", Some("my-app")))]);
+
+        let found = resolver.resolve(ip).await.unwrap().unwrap();
+        assert_eq!(found.compose_project.as_deref(), Some("my-app"));
+
+        // IPv4-mapped sources resolve to the IPv4 entry
+        let mapped: IpAddr = "::ffff:172.18.0.5".parse().unwrap();
+        assert!(resolver.resolve(mapped).await.unwrap().is_some());
+
+        // A miss on a fresh snapshot does not hit the daemon
+        let unknown: IpAddr = "172.18.0.9".parse().unwrap();
+        assert!(resolver.resolve(unknown).await.unwrap().is_none());
+    }
+}
@@ -0,0 +1,242 @@
+//! The tapp contract
+//!
+//! Deployed apps are registered on the tapp contract (`[contract]`) with
+//! `registerApp(string appId, bytes composeHash, bytes volumesHash, address
+//! deployer)`, once their measurement is extended into the RTMR, and app key
+//! requests may be limited to the attestors the contract reports with
+//! `isRegisteredAttestor(address)` (see `crate::attestor_auth_layer`).
+//! Contract access goes through the thin `ContractClient` trait; `rpc`
+//! implements it over Ethereum JSON-RPC, signing EIP-1559 transactions
+//! itself (see `tx`), and tests use mocks. `registrar` submits registrations
+//! in the background and follows them until they are mined.
+
+pub mod registrar;
+pub mod rpc;
+pub mod tx;
+
+use crate::app_key::{eth_address, public_key_from_private};
+use crate::cluster::sss;
+use crate::error::{TappError, TappResult};
+use futures_util::future::BoxFuture;
+use sha3::{Digest, Keccak256};
+use std::path::Path;
+
+/// Function registering an app
+pub const REGISTER_APP_SIGNATURE: &str = "registerApp(string,bytes,bytes,address)";
+
+/// View telling whether an address is a registered attestor
+pub const IS_REGISTERED_ATTESTOR_SIGNATURE: &str = "isRegisteredAttestor(address)";
+
+/// Length of an ABI word
+const WORD_LEN: usize = 32;
+
+/// An app as registered on the contract
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AppRegistration {
+    pub app_id: String,
+    /// Hex compose hash of the measurement
+    pub compose_hash: String,
+    /// Hex volumes hash of the measurement
+    pub volumes_hash: String,
+    /// Hex of the deployer's 20-byte address, with or without 0x
+    pub deployer: String,
+}
+
+impl AppRegistration {
+    /// ABI-encoded call of `registerApp`
+    pub fn call_data(&self) -> TappResult<Vec<u8>> {
+        let deployer = decode_hex("deployer", &self.deployer)?;
+        if deployer.len() != 20 {
+            return Err(TappError::InvalidParameter {
+                field: "deployer".to_string(),
+                reason: format!("must be a 20-byte address, got {} bytes", deployer.len()),
+            });
+        }
+        let dynamic = [
+            self.app_id.as_bytes().to_vec(),
+            decode_hex("compose_hash", &self.compose_hash)?,
+            decode_hex("volumes_hash", &self.volumes_hash)?,
+        ];
+
+        let mut data = selector(REGISTER_APP_SIGNATURE).to_vec();
+        // Heads: offsets of the string and both byte arrays, then the address
+        let mut offset = 4 * WORD_LEN;
+        for value in &dynamic {
+            data.extend_from_slice(&uint_word(offset as u128));
+            offset += WORD_LEN + padded_len(value.len());
+        }
+        data.extend_from_slice(&[0; WORD_LEN - 20]);
+        data.extend_from_slice(&deployer);
+        // Tails: length, then the bytes padded to whole words
+        for value in &dynamic {
+            data.extend_from_slice(&uint_word(value.len() as u128));
+            data.extend_from_slice(value);
+            data.resize(data.len() + padded_len(value.len()) - value.len(), 0);
+        }
+        Ok(data)
+    }
+}
+
+/// ABI-encoded call of `isRegisteredAttestor`
+pub fn is_registered_attestor_call_data(attestor: &[u8; 20]) -> Vec<u8> {
+    let mut data = selector(IS_REGISTERED_ATTESTOR_SIGNATURE).to_vec();
+    data.extend_from_slice(&[0; WORD_LEN - 20]);
+    data.extend_from_slice(attestor);
+    data
+}
+
+/// Where a submitted transaction is
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TxStatus {
+    /// Not mined yet
+    Pending,
+    /// Mined and succeeded
+    Confirmed { block_number: u64 },
+    /// Mined but reverted
+    Reverted { block_number: u64 },
+}
+
+/// Calls of the tapp contract
+pub trait ContractClient: Send + Sync {
+    /// Submit a registration of `app`, returning the 0x-prefixed hash of its
+    /// transaction
+    fn register_app<'a>(&'a self, app: &'a AppRegistration) -> BoxFuture<'a, TappResult<String>>;
+
+    /// Where the transaction `tx_hash` is
+    fn transaction_status<'a>(&'a self, tx_hash: &'a str) -> BoxFuture<'a, TappResult<TxStatus>>;
+
+    /// Whether the contract lists `attestor` as a registered attestor
+    fn is_registered_attestor<'a>(
+        &'a self,
+        attestor: &'a [u8; 20],
+    ) -> BoxFuture<'a, TappResult<bool>>;
+}
+
+/// Key paying for contract transactions, wiped when dropped
+pub struct FunderKey {
+    private_key: Vec<u8>,
+    address: [u8; 20],
+}
+
+impl FunderKey {
+    pub fn from_private_key(private_key: Vec<u8>) -> TappResult<Self> {
+        let public_key = public_key_from_private(&private_key)?;
+        let mut address = [0; 20];
+        address.copy_from_slice(&eth_address(&public_key));
+        Ok(Self {
+            private_key,
+            address,
+        })
+    }
+
+    /// Read `contract.funder_key_file`: a hex private key, with or without 0x
+    pub fn from_file(path: &Path) -> TappResult<Self> {
+        let mut content = std::fs::read(path)?;
+        let private_key = decode_hex(
+            "contract.funder_key_file",
+            std::str::from_utf8(&content).unwrap_or_default().trim(),
+        );
+        sss::wipe(&mut content);
+        Self::from_private_key(private_key?)
+    }
+
+    pub(crate) fn private_key(&self) -> &[u8] {
+        &self.private_key
+    }
+
+    pub fn address(&self) -> [u8; 20] {
+        self.address
+    }
+}
+
+impl Drop for FunderKey {
+    fn drop(&mut self) {
+        sss::wipe(&mut self.private_key);
+    }
+}
+
+/// First 4 bytes of the Keccak-256 of a function signature
+pub fn selector(signature: &str) -> [u8; 4] {
+    let hash = Keccak256::digest(signature.as_bytes());
+    [hash[0], hash[1], hash[2], hash[3]]
+}
+
+fn uint_word(value: u128) -> [u8; WORD_LEN] {
+    let mut word = [0; WORD_LEN];
+    word[WORD_LEN - 16..].copy_from_slice(&value.to_be_bytes());
+    word
+}
+
+fn padded_len(len: usize) -> usize {
+    len.div_ceil(WORD_LEN) * WORD_LEN
+}
+
+fn decode_hex(field: &str, value: &str) -> TappResult<Vec<u8>> {
+    hex::decode(value.strip_prefix("0x").unwrap_or(value)).map_err(|e| {
+        TappError::InvalidParameter {
+            field: field.to_string(),
+            reason: format!("not hex: {}", e),
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_register_app_call_data() {
+        let registration = AppRegistration {
+            app_id: "my-app".to_string(),
+            compose_hash: "ab".repeat(48),
+            volumes_hash: "0xcd".to_string(),
+            deployer: "11".repeat(20),
+        };
+        let data = registration.call_data().unwrap();
+        let word = |i: usize| &data[4 + i * WORD_LEN..4 + (i + 1) * WORD_LEN];
+
+        assert_eq!(data[..4], selector(REGISTER_APP_SIGNATURE));
+        // Offsets: the string after the 4 heads, each tail a length word and
+        // its padded bytes
+        assert_eq!(word(0), uint_word(128));
+        assert_eq!(word(1), uint_word(128 + 64));
+        assert_eq!(word(2), uint_word(128 + 64 + 96));
+        assert_eq!(word(3)[12..], [0x11; 20]);
+        assert_eq!(word(4), uint_word(6));
+        assert_eq!(word(5)[..6], *b"my-app");
+        assert_eq!(word(6), uint_word(48));
+        assert_eq!(word(7), [0xab; 32]);
+        assert_eq!(word(8)[..16], [0xab; 16]);
+        assert_eq!(word(9), uint_word(1));
+        assert_eq!(word(10)[0], 0xcd);
+        assert_eq!(data.len(), 4 + 11 * WORD_LEN);
+
+        let by_public_key = AppRegistration {
+            deployer: "11".repeat(64),
+            ..registration
+        };
+        assert!(by_public_key.call_data().is_err());
+    }
+
+    #[test]
+    fn test_is_registered_attestor_call_data() {
+        let data = is_registered_attestor_call_data(&[0x22; 20]);
+        assert_eq!(data[..4], selector(IS_REGISTERED_ATTESTOR_SIGNATURE));
+        assert_eq!(data[4..16], [0; 12]);
+        assert_eq!(data[16..], [0x22; 20]);
+    }
+
+    #[test]
+    fn test_funder_key_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("funder.key");
+        std::fs::write(&path, format!("0x{}\n", "01".repeat(32))).unwrap();
+        let key = FunderKey::from_file(&path).unwrap();
+        assert_eq!(key.private_key(), [1; 32]);
+        let public_key = public_key_from_private(&[1; 32]).unwrap();
+        assert_eq!(key.address().to_vec(), eth_address(&public_key));
+
+        std::fs::write(&path, "not a key").unwrap();
+        assert!(FunderKey::from_file(&path).is_err());
+    }
+}
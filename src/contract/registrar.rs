@@ -0,0 +1,404 @@
+//! Registration of deployed apps on the tapp contract
+//!
+//! `Registrar::submit` records an app's registration as pending and sends it
+//! in the background. Submissions that fail because the RPC endpoint is
+//! unreachable are retried with backoff (`contract.retry`); the transaction
+//! is then checked every `poll_interval_seconds` until it is mined or
+//! `confirmation_timeout_seconds` have passed. Statuses are kept in memory
+//! and dropped with the app.
+
+use super::{AppRegistration, ContractClient, TxStatus};
+use crate::boot::manager::retry_transient;
+use crate::config::{ContractConfig, RetryConfig};
+use crate::error::{ContractError, TappError};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tracing::{info, warn};
+
+/// Where an app's registration is
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RegistrationState {
+    /// Being submitted, or submitted and not mined yet
+    Pending,
+    /// Mined
+    Confirmed,
+    /// Not submitted, reverted or never mined
+    Failed,
+}
+
+impl RegistrationState {
+    pub fn to_proto(self) -> crate::proto::RegistrationState {
+        match self {
+            RegistrationState::Pending => crate::proto::RegistrationState::RegistrationPending,
+            RegistrationState::Confirmed => crate::proto::RegistrationState::RegistrationConfirmed,
+            RegistrationState::Failed => crate::proto::RegistrationState::RegistrationFailed,
+        }
+    }
+}
+
+/// What ListAppMeasurements reports about an app's registration
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RegistrationStatus {
+    pub state: RegistrationState,
+    /// 0x-prefixed hash of the transaction, once submitted
+    pub tx_hash: Option<String>,
+    /// Block the transaction was mined in
+    pub block_number: Option<u64>,
+    /// Why it failed
+    pub error: Option<String>,
+    /// Submissions tried
+    pub attempts: u32,
+    pub updated_at: i64,
+}
+
+impl RegistrationStatus {
+    pub fn to_proto(&self) -> crate::proto::ContractRegistration {
+        crate::proto::ContractRegistration {
+            state: self.state.to_proto() as i32,
+            tx_hash: self.tx_hash.clone().unwrap_or_default(),
+            block_number: self.block_number.unwrap_or_default(),
+            error: self.error.clone().unwrap_or_default(),
+            attempts: self.attempts,
+            updated_at: self.updated_at,
+        }
+    }
+}
+
+/// Sends app registrations and follows them until they are mined
+#[derive(Clone)]
+pub struct Registrar {
+    client: Arc<dyn ContractClient>,
+    retry: RetryConfig,
+    poll_interval: Duration,
+    confirmation_timeout: Duration,
+    statuses: Arc<Mutex<HashMap<String, RegistrationStatus>>>,
+}
+
+impl Registrar {
+    pub fn new(client: Arc<dyn ContractClient>, config: &ContractConfig) -> Self {
+        Self {
+            client,
+            retry: config.retry.clone(),
+            poll_interval: Duration::from_secs(config.poll_interval_seconds),
+            confirmation_timeout: Duration::from_secs(config.confirmation_timeout_seconds),
+            statuses: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Register an app in the background, replacing any earlier status of it
+    pub fn submit(&self, registration: AppRegistration) {
+        self.statuses.lock().unwrap().insert(
+            registration.app_id.clone(),
+            RegistrationStatus {
+                state: RegistrationState::Pending,
+                tx_hash: None,
+                block_number: None,
+                error: None,
+                attempts: 0,
+                updated_at: crate::utils::current_timestamp(),
+            },
+        );
+        let registrar = self.clone();
+        tokio::spawn(async move { registrar.run(registration).await });
+    }
+
+    /// Registration of `app_id`, if one was submitted
+    pub fn status(&self, app_id: &str) -> Option<RegistrationStatus> {
+        self.statuses.lock().unwrap().get(app_id).cloned()
+    }
+
+    /// Stop following the registration of a removed app
+    pub fn forget(&self, app_id: &str) {
+        self.statuses.lock().unwrap().remove(app_id);
+    }
+
+    async fn run(&self, registration: AppRegistration) {
+        let app_id = registration.app_id.as_str();
+        let submitted = retry_transient(
+            &self.retry,
+            "contract_register_app",
+            |e| matches!(e, TappError::Contract(e) if e.is_transient()),
+            None,
+            || {
+                self.update(app_id, |status| status.attempts += 1);
+                self.client.register_app(&registration)
+            },
+        )
+        .await;
+        let tx_hash = match submitted {
+            Ok(tx_hash) => tx_hash,
+            Err(e) => return self.fail(app_id, e.to_string()),
+        };
+        if !self.update(app_id, |status| status.tx_hash = Some(tx_hash.clone())) {
+            return;
+        }
+        info!(
+            app_id = %app_id,
+            tx_hash = %tx_hash,
+            event = "CONTRACT_REGISTRATION_SUBMITTED",
+            "App registration submitted"
+        );
+
+        let deadline = tokio::time::Instant::now() + self.confirmation_timeout;
+        loop {
+            tokio::time::sleep(self.poll_interval).await;
+            match self.client.transaction_status(&tx_hash).await {
+                Ok(TxStatus::Pending) => {}
+                Ok(TxStatus::Confirmed { block_number }) => {
+                    self.update(app_id, |status| {
+                        status.state = RegistrationState::Confirmed;
+                        status.block_number = Some(block_number);
+                    });
+                    info!(
+                        app_id = %app_id,
+                        tx_hash = %tx_hash,
+                        block_number,
+                        event = "CONTRACT_REGISTRATION_CONFIRMED",
+                        "App registered on the contract"
+                    );
+                    return;
+                }
+                Ok(TxStatus::Reverted { block_number }) => {
+                    self.update(app_id, |status| status.block_number = Some(block_number));
+                    let reverted = ContractError::TransactionReverted {
+                        tx_hash: tx_hash.clone(),
+                    };
+                    return self.fail(app_id, reverted.to_string());
+                }
+                // Checked again at the next poll
+                Err(e) => warn!(
+                    app_id = %app_id,
+                    tx_hash = %tx_hash,
+                    error = %e,
+                    "Checking the app registration failed"
+                ),
+            }
+            if !self.statuses.lock().unwrap().contains_key(app_id) {
+                return;
+            }
+            if tokio::time::Instant::now() >= deadline {
+                return self.fail(
+                    app_id,
+                    format!(
+                        "Transaction {} was not mined within {} seconds",
+                        tx_hash,
+                        self.confirmation_timeout.as_secs()
+                    ),
+                );
+            }
+        }
+    }
+
+    fn fail(&self, app_id: &str, error: String) {
+        warn!(
+            app_id = %app_id,
+            error = %error,
+            event = "CONTRACT_REGISTRATION_FAILED",
+            "App registration failed"
+        );
+        self.update(app_id, |status| {
+            status.state = RegistrationState::Failed;
+            status.error = Some(error);
+        });
+    }
+
+    /// Change the status of `app_id`; false if it was forgotten
+    fn update(&self, app_id: &str, change: impl FnOnce(&mut RegistrationStatus)) -> bool {
+        match self.statuses.lock().unwrap().get_mut(app_id) {
+            Some(status) => {
+                change(status);
+                status.updated_at = crate::utils::current_timestamp();
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::TappResult;
+    use futures_util::future::BoxFuture;
+    use std::collections::VecDeque;
+
+    /// Contract answering from scripted results
+    #[derive(Default)]
+    struct MockContract {
+        /// Results of the next submissions; then they succeed
+        submissions: Mutex<VecDeque<TappResult<()>>>,
+        /// Results of the next status checks; then the transaction is pending
+        statuses: Mutex<VecDeque<TappResult<TxStatus>>>,
+        registered: Mutex<Vec<AppRegistration>>,
+    }
+
+    impl ContractClient for MockContract {
+        fn register_app<'a>(
+            &'a self,
+            app: &'a AppRegistration,
+        ) -> BoxFuture<'a, TappResult<String>> {
+            let result = self
+                .submissions
+                .lock()
+                .unwrap()
+                .pop_front()
+                .unwrap_or(Ok(()));
+            if result.is_ok() {
+                self.registered.lock().unwrap().push(app.clone());
+            }
+            let tx_hash = format!("0x{}", hex::encode(app.app_id.as_bytes()));
+            Box::pin(async move { result.map(|()| tx_hash) })
+        }
+
+        fn transaction_status<'a>(
+            &'a self,
+            _tx_hash: &'a str,
+        ) -> BoxFuture<'a, TappResult<TxStatus>> {
+            let status = self
+                .statuses
+                .lock()
+                .unwrap()
+                .pop_front()
+                .unwrap_or(Ok(TxStatus::Pending));
+            Box::pin(async move { status })
+        }
+
+        fn is_registered_attestor<'a>(
+            &'a self,
+            _attestor: &'a [u8; 20],
+        ) -> BoxFuture<'a, TappResult<bool>> {
+            Box::pin(async { Ok(false) })
+        }
+    }
+
+    fn unreachable() -> TappError {
+        ContractError::ConnectionFailed {
+            endpoint: "http://127.0.0.1:8545".to_string(),
+            reason: "connection refused".to_string(),
+        }
+        .into()
+    }
+
+    fn registration(app_id: &str) -> AppRegistration {
+        AppRegistration {
+            app_id: app_id.to_string(),
+            compose_hash: "ab".repeat(48),
+            volumes_hash: "cd".repeat(48),
+            deployer: "11".repeat(20),
+        }
+    }
+
+    /// Status of `app_id` once it is no longer pending
+    async fn settled(registrar: &Registrar, app_id: &str) -> RegistrationStatus {
+        loop {
+            let status = registrar.status(app_id).unwrap();
+            if status.state != RegistrationState::Pending {
+                return status;
+            }
+            tokio::time::sleep(Duration::from_secs(1)).await;
+        }
+    }
+
+    fn registrar(contract: Arc<MockContract>) -> Registrar {
+        Registrar::new(
+            contract,
+            &ContractConfig {
+                confirmation_timeout_seconds: 60,
+                ..Default::default()
+            },
+        )
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_registration_confirmed_after_transient_failures() {
+        let contract = Arc::new(MockContract::default());
+        contract
+            .submissions
+            .lock()
+            .unwrap()
+            .extend([Err(unreachable()), Err(unreachable())]);
+        contract.statuses.lock().unwrap().extend([
+            Ok(TxStatus::Pending),
+            Err(unreachable()),
+            Ok(TxStatus::Confirmed { block_number: 42 }),
+        ]);
+        let registrar = registrar(contract.clone());
+
+        registrar.submit(registration("app-1"));
+        let status = registrar.status("app-1").unwrap();
+        assert_eq!(status.state, RegistrationState::Pending);
+
+        let status = settled(&registrar, "app-1").await;
+        assert_eq!(status.state, RegistrationState::Confirmed);
+        assert_eq!(status.attempts, 3);
+        assert_eq!(status.block_number, Some(42));
+        assert_eq!(
+            status.tx_hash.as_deref(),
+            Some(format!("0x{}", hex::encode("app-1")).as_str())
+        );
+        assert_eq!(
+            *contract.registered.lock().unwrap(),
+            [registration("app-1")]
+        );
+
+        registrar.forget("app-1");
+        assert!(registrar.status("app-1").is_none());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_registration_failures() {
+        let contract = Arc::new(MockContract::default());
+        let registrar = registrar(contract.clone());
+
+        // Rejected by the node: not retried
+        contract
+            .submissions
+            .lock()
+            .unwrap()
+            .push_back(Err(ContractError::RpcError {
+                method: "eth_sendRawTransaction".to_string(),
+                code: -32000,
+                message: "insufficient funds".to_string(),
+            }
+            .into()));
+        registrar.submit(registration("rejected"));
+        let status = settled(&registrar, "rejected").await;
+        assert_eq!(status.state, RegistrationState::Failed);
+        assert_eq!(status.attempts, 1);
+        assert!(status.tx_hash.is_none());
+        assert!(status.error.unwrap().contains("insufficient funds"));
+
+        // Unreachable beyond the retries
+        contract
+            .submissions
+            .lock()
+            .unwrap()
+            .extend((0..4).map(|_| Err(unreachable())));
+        registrar.submit(registration("unreachable"));
+        let status = settled(&registrar, "unreachable").await;
+        assert_eq!(status.state, RegistrationState::Failed);
+        assert_eq!(status.attempts, 4);
+
+        contract
+            .statuses
+            .lock()
+            .unwrap()
+            .push_back(Ok(TxStatus::Reverted { block_number: 7 }));
+        registrar.submit(registration("reverted"));
+        let status = settled(&registrar, "reverted").await;
+        assert_eq!(status.state, RegistrationState::Failed);
+        assert_eq!(status.block_number, Some(7));
+        assert!(status.error.unwrap().contains("reverted"));
+
+        // Never mined
+        registrar.submit(registration("stuck"));
+        let status = settled(&registrar, "stuck").await;
+        assert_eq!(status.state, RegistrationState::Failed);
+        assert!(status.tx_hash.is_some());
+        assert!(status
+            .error
+            .unwrap()
+            .contains("not mined within 60 seconds"));
+    }
+}
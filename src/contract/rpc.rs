@@ -0,0 +1,281 @@
+//! `ContractClient` over Ethereum JSON-RPC
+//!
+//! Transactions are signed here with the funder key and sent raw, so the
+//! RPC endpoint needs no unlocked account: any node works, anvil included.
+//! The nonce comes from the endpoint's pending count, fees from
+//! `eth_gasPrice` and `eth_maxPriorityFeePerGas`, and the gas limit from
+//! `eth_estimateGas` with a margin.
+
+use super::tx::Eip1559Transaction;
+use super::{
+    is_registered_attestor_call_data, AppRegistration, ContractClient, FunderKey, TxStatus,
+};
+use crate::config::ContractConfig;
+use crate::error::{ContractError, TappError, TappResult};
+use futures_util::future::BoxFuture;
+use serde_json::{json, Value};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+use tokio::sync::Mutex;
+use tracing::debug;
+
+/// Percent added to the estimated gas
+const GAS_MARGIN_PERCENT: u64 = 20;
+
+/// Contract client talking to `contract.rpc_url`
+pub struct JsonRpcContract {
+    client: reqwest::Client,
+    endpoint: String,
+    address: [u8; 20],
+    chain_id: u64,
+    funder: FunderKey,
+    /// Held while a transaction is prepared and sent, so that concurrent
+    /// registrations don't take the same nonce
+    submitting: Mutex<()>,
+    next_id: AtomicU64,
+}
+
+impl JsonRpcContract {
+    pub fn new(config: &ContractConfig, funder: FunderKey) -> TappResult<Self> {
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(config.timeout_seconds))
+            .build()
+            .map_err(|e| ContractError::ConnectionFailed {
+                endpoint: config.rpc_url.clone(),
+                reason: format!("HTTP client setup failed: {}", e),
+            })?;
+        let address = hex::decode(config.address.trim_start_matches("0x"))
+            .ok()
+            .and_then(|address| <[u8; 20]>::try_from(address).ok())
+            .ok_or_else(|| TappError::InvalidParameter {
+                field: "contract.address".to_string(),
+                reason: format!("'{}' is not a 20-byte address", config.address),
+            })?;
+        Ok(Self {
+            client,
+            endpoint: config.rpc_url.clone(),
+            address,
+            chain_id: config.chain_id,
+            funder,
+            submitting: Mutex::new(()),
+            next_id: AtomicU64::new(1),
+        })
+    }
+
+    /// 0x-prefixed address paying for transactions
+    pub fn funder_address(&self) -> String {
+        format!("0x{}", hex::encode(self.funder.address()))
+    }
+
+    /// Result of one JSON-RPC call
+    async fn call(&self, method: &str, params: Value) -> TappResult<Value> {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let body = json!({"jsonrpc": "2.0", "id": id, "method": method, "params": params});
+        let unreachable = |reason: String| ContractError::ConnectionFailed {
+            endpoint: self.endpoint.clone(),
+            reason,
+        };
+        let invalid = |reason: String| ContractError::InvalidResponse {
+            method: method.to_string(),
+            reason,
+        };
+
+        let response = self
+            .client
+            .post(&self.endpoint)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .body(serde_json::to_vec(&body)?)
+            .send()
+            .await
+            .map_err(|e| unreachable(e.to_string()))?;
+        let status = response.status();
+        if status.is_server_error() || status == reqwest::StatusCode::TOO_MANY_REQUESTS {
+            return Err(unreachable(format!("HTTP {}", status)).into());
+        }
+        let bytes = response
+            .bytes()
+            .await
+            .map_err(|e| unreachable(e.to_string()))?;
+        let mut answer: Value = serde_json::from_slice(&bytes)
+            .map_err(|e| invalid(format!("HTTP {}, not JSON: {}", status, e)))?;
+
+        if let Some(error) = answer.get("error") {
+            return Err(ContractError::RpcError {
+                method: method.to_string(),
+                code: error["code"].as_i64().unwrap_or_default(),
+                message: error["message"].as_str().unwrap_or_default().to_string(),
+            }
+            .into());
+        }
+        debug!(method, "Contract RPC call answered");
+        match answer.get_mut("result") {
+            Some(result) => Ok(result.take()),
+            None => Err(invalid("no result".to_string()).into()),
+        }
+    }
+
+    /// A hex quantity answered by `method`
+    async fn quantity(&self, method: &str, params: Value) -> TappResult<u128> {
+        let result = self.call(method, params).await?;
+        parse_quantity(method, &result)
+    }
+
+    async fn submit(&self, data: Vec<u8>) -> TappResult<String> {
+        let _submitting = self.submitting.lock().await;
+        let from = self.funder_address();
+        let to = format!("0x{}", hex::encode(self.address));
+
+        let nonce = self
+            .quantity("eth_getTransactionCount", json!([from, "pending"]))
+            .await?;
+        let gas_price = self.quantity("eth_gasPrice", json!([])).await?;
+        let priority_fee = self.quantity("eth_maxPriorityFeePerGas", json!([])).await?;
+        let estimate = self
+            .quantity(
+                "eth_estimateGas",
+                json!([{"from": from, "to": to, "data": format!("0x{}", hex::encode(&data))}]),
+            )
+            .await?;
+
+        let tx = Eip1559Transaction {
+            chain_id: self.chain_id,
+            nonce: nonce as u64,
+            max_priority_fee_per_gas: priority_fee.min(gas_price),
+            // Room for the base fee to double before the transaction is mined
+            max_fee_per_gas: gas_price.saturating_mul(2),
+            gas_limit: (estimate as u64).saturating_mul(100 + GAS_MARGIN_PERCENT) / 100,
+            to: Some(self.address),
+            value: 0,
+            data,
+            access_list: Vec::new(),
+        };
+        let signed = tx.sign(self.funder.private_key())?;
+        self.call(
+            "eth_sendRawTransaction",
+            json!([format!("0x{}", hex::encode(&signed.raw))]),
+        )
+        .await?;
+        Ok(signed.hash_hex())
+    }
+
+    /// Answer of the `isRegisteredAttestor` view at the latest block
+    async fn registered_attestor(&self, attestor: &[u8; 20]) -> TappResult<bool> {
+        let method = "eth_call";
+        let to = format!("0x{}", hex::encode(self.address));
+        let data = format!(
+            "0x{}",
+            hex::encode(is_registered_attestor_call_data(attestor))
+        );
+        let result = self
+            .call(method, json!([{"to": to, "data": data}, "latest"]))
+            .await?;
+        // An ABI bool: one word, 0 or 1
+        let word = result
+            .as_str()
+            .and_then(|word| hex::decode(word.trim_start_matches("0x")).ok())
+            .filter(|word| word.len() == 32 && word[..31].iter().all(|b| *b == 0))
+            .ok_or_else(|| ContractError::InvalidResponse {
+                method: method.to_string(),
+                reason: format!("{} is not an ABI bool", result),
+            })?;
+        Ok(word[31] != 0)
+    }
+
+    async fn receipt(&self, tx_hash: &str) -> TappResult<TxStatus> {
+        let method = "eth_getTransactionReceipt";
+        let receipt = self.call(method, json!([tx_hash])).await?;
+        if receipt.is_null() {
+            return Ok(TxStatus::Pending);
+        }
+        let block_number = parse_quantity(method, &receipt["blockNumber"])? as u64;
+        match parse_quantity(method, &receipt["status"])? {
+            1 => Ok(TxStatus::Confirmed { block_number }),
+            _ => Ok(TxStatus::Reverted { block_number }),
+        }
+    }
+}
+
+impl ContractClient for JsonRpcContract {
+    fn register_app<'a>(&'a self, app: &'a AppRegistration) -> BoxFuture<'a, TappResult<String>> {
+        Box::pin(async move { self.submit(app.call_data()?).await })
+    }
+
+    fn transaction_status<'a>(&'a self, tx_hash: &'a str) -> BoxFuture<'a, TappResult<TxStatus>> {
+        Box::pin(self.receipt(tx_hash))
+    }
+
+    fn is_registered_attestor<'a>(
+        &'a self,
+        attestor: &'a [u8; 20],
+    ) -> BoxFuture<'a, TappResult<bool>> {
+        Box::pin(self.registered_attestor(attestor))
+    }
+}
+
+/// A JSON-RPC quantity: 0x-prefixed hex without leading zeros
+fn parse_quantity(method: &str, value: &Value) -> TappResult<u128> {
+    value
+        .as_str()
+        .and_then(|value| value.strip_prefix("0x"))
+        .and_then(|digits| u128::from_str_radix(digits, 16).ok())
+        .ok_or_else(|| {
+            ContractError::InvalidResponse {
+                method: method.to_string(),
+                reason: format!("{} is not a hex quantity", value),
+            }
+            .into()
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_quantity() {
+        assert_eq!(parse_quantity("m", &json!("0x0")).unwrap(), 0);
+        assert_eq!(parse_quantity("m", &json!("0x5208")).unwrap(), 21000);
+        for invalid in [json!("5208"), json!(21000), json!("0xzz"), Value::Null] {
+            assert!(parse_quantity("m", &invalid).is_err());
+        }
+    }
+
+    /// Registers on a running `anvil` with its first prefunded account; run
+    /// with `cargo test --features anvil-tests`. The registration goes to an
+    /// address without code, which accepts any call, so this checks the
+    /// signing and the RPC flow rather than the contract.
+    #[cfg(feature = "anvil-tests")]
+    #[tokio::test]
+    async fn test_register_on_anvil() {
+        const ANVIL_KEY: &str = "ac0974bec39a17e36ba4a6b4d238ff944bacb478cbed5efcae784d7bf4f2ff80";
+        let config = ContractConfig {
+            address: format!("0x{}", "77".repeat(20)),
+            chain_id: 31337,
+            ..Default::default()
+        };
+        let funder = FunderKey::from_private_key(hex::decode(ANVIL_KEY).unwrap()).unwrap();
+        let contract = JsonRpcContract::new(&config, funder).unwrap();
+        assert_eq!(
+            contract.funder_address(),
+            "0xf39fd6e51aad88f6f4ce6ab8827279cfffb92266"
+        );
+
+        let registration = AppRegistration {
+            app_id: "anvil-app".to_string(),
+            compose_hash: "ab".repeat(48),
+            volumes_hash: "cd".repeat(48),
+            deployer: "11".repeat(20),
+        };
+        let tx_hash = contract.register_app(&registration).await.unwrap();
+        // anvil mines each transaction right away
+        let mut status = TxStatus::Pending;
+        for _ in 0..50 {
+            status = contract.transaction_status(&tx_hash).await.unwrap();
+            if status != TxStatus::Pending {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+        assert!(matches!(status, TxStatus::Confirmed { .. }), "{:?}", status);
+    }
+}
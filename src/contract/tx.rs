@@ -0,0 +1,432 @@
+//! EIP-1559 (type 2) and legacy (type 0) transactions, RLP-encoded and
+//! signed without an Ethereum library
+//!
+//! A type 2 transaction is signed over Keccak-256(0x02 || rlp([chain_id,
+//! nonce, max_priority_fee_per_gas, max_fee_per_gas, gas_limit, to, value,
+//! data, access_list])) and sent as 0x02 || rlp([...the same fields,
+//! y_parity, r, s]). A legacy one is signed over Keccak-256(rlp([nonce,
+//! gas_price, gas_limit, to, value, data, chain_id, 0, 0])) (EIP-155) and
+//! sent as rlp([...the first six fields, chain_id * 2 + 35 + y_parity, r,
+//! s]). Either way the Keccak-256 of the raw bytes is the transaction hash.
+
+use crate::error::{TappError, TappResult};
+use k256::ecdsa::SigningKey;
+use sha3::{Digest, Keccak256};
+
+/// Type byte of EIP-1559 transactions
+pub const EIP1559_TX_TYPE: u8 = 0x02;
+
+/// An address and storage slots a transaction declares it will access
+/// (EIP-2930)
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AccessListItem {
+    pub address: [u8; 20],
+    pub storage_keys: Vec<[u8; 32]>,
+}
+
+/// An unsigned EIP-1559 transaction
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Eip1559Transaction {
+    pub chain_id: u64,
+    pub nonce: u64,
+    pub max_priority_fee_per_gas: u128,
+    pub max_fee_per_gas: u128,
+    pub gas_limit: u64,
+    /// `None` creates a contract
+    pub to: Option<[u8; 20]>,
+    pub value: u128,
+    pub data: Vec<u8>,
+    pub access_list: Vec<AccessListItem>,
+}
+
+/// An unsigned legacy transaction, signed with EIP-155 replay protection
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LegacyTransaction {
+    pub chain_id: u64,
+    pub nonce: u64,
+    pub gas_price: u128,
+    pub gas_limit: u64,
+    /// `None` creates a contract
+    pub to: Option<[u8; 20]>,
+    pub value: u128,
+    pub data: Vec<u8>,
+}
+
+/// An unsigned transaction of either type
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Transaction {
+    Eip1559(Eip1559Transaction),
+    Legacy(LegacyTransaction),
+}
+
+/// A signed transaction, ready for `eth_sendRawTransaction`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SignedTransaction {
+    pub raw: Vec<u8>,
+    pub hash: [u8; 32],
+}
+
+impl SignedTransaction {
+    /// 0x-prefixed transaction hash
+    pub fn hash_hex(&self) -> String {
+        format!("0x{}", hex::encode(self.hash))
+    }
+}
+
+impl Eip1559Transaction {
+    fn fields(&self) -> Vec<Vec<u8>> {
+        vec![
+            rlp_uint(self.chain_id as u128),
+            rlp_uint(self.nonce as u128),
+            rlp_uint(self.max_priority_fee_per_gas),
+            rlp_uint(self.max_fee_per_gas),
+            rlp_uint(self.gas_limit as u128),
+            rlp_to(&self.to),
+            rlp_uint(self.value),
+            rlp_bytes(&self.data),
+            rlp_access_list(&self.access_list),
+        ]
+    }
+
+    /// Hash the signature is made over
+    pub fn signing_hash(&self) -> [u8; 32] {
+        typed_hash(&rlp_list(&self.fields()))
+    }
+
+    /// Sign with a 32-byte secp256k1 private key
+    pub fn sign(&self, private_key: &[u8]) -> TappResult<SignedTransaction> {
+        let (y_odd, r, s) = sign_hash(private_key, &self.signing_hash())?;
+
+        let mut fields = self.fields();
+        fields.push(rlp_uint(y_odd as u128));
+        fields.push(r);
+        fields.push(s);
+        let mut raw = vec![EIP1559_TX_TYPE];
+        raw.extend_from_slice(&rlp_list(&fields));
+        Ok(SignedTransaction::new(raw))
+    }
+}
+
+impl LegacyTransaction {
+    fn fields(&self) -> Vec<Vec<u8>> {
+        vec![
+            rlp_uint(self.nonce as u128),
+            rlp_uint(self.gas_price),
+            rlp_uint(self.gas_limit as u128),
+            rlp_to(&self.to),
+            rlp_uint(self.value),
+            rlp_bytes(&self.data),
+        ]
+    }
+
+    /// Hash the signature is made over, which commits to the chain ID
+    pub fn signing_hash(&self) -> [u8; 32] {
+        let mut fields = self.fields();
+        fields.push(rlp_uint(self.chain_id as u128));
+        fields.push(rlp_uint(0));
+        fields.push(rlp_uint(0));
+        Keccak256::digest(rlp_list(&fields)).into()
+    }
+
+    /// Sign with a 32-byte secp256k1 private key
+    pub fn sign(&self, private_key: &[u8]) -> TappResult<SignedTransaction> {
+        let (y_odd, r, s) = sign_hash(private_key, &self.signing_hash())?;
+
+        let mut fields = self.fields();
+        fields.push(rlp_uint(self.chain_id as u128 * 2 + 35 + y_odd as u128));
+        fields.push(r);
+        fields.push(s);
+        Ok(SignedTransaction::new(rlp_list(&fields)))
+    }
+}
+
+impl Transaction {
+    pub fn chain_id(&self) -> u64 {
+        match self {
+            Transaction::Eip1559(tx) => tx.chain_id,
+            Transaction::Legacy(tx) => tx.chain_id,
+        }
+    }
+
+    /// Hash the signature is made over
+    pub fn signing_hash(&self) -> [u8; 32] {
+        match self {
+            Transaction::Eip1559(tx) => tx.signing_hash(),
+            Transaction::Legacy(tx) => tx.signing_hash(),
+        }
+    }
+
+    /// Sign with a 32-byte secp256k1 private key
+    pub fn sign(&self, private_key: &[u8]) -> TappResult<SignedTransaction> {
+        match self {
+            Transaction::Eip1559(tx) => tx.sign(private_key),
+            Transaction::Legacy(tx) => tx.sign(private_key),
+        }
+    }
+}
+
+impl SignedTransaction {
+    fn new(raw: Vec<u8>) -> Self {
+        SignedTransaction {
+            hash: Keccak256::digest(&raw).into(),
+            raw,
+        }
+    }
+}
+
+/// Sign a transaction hash: the y parity and the RLP-encoded r and s
+fn sign_hash(private_key: &[u8], hash: &[u8; 32]) -> TappResult<(bool, Vec<u8>, Vec<u8>)> {
+    let signing_key = SigningKey::from_slice(private_key)
+        .map_err(|e| TappError::Crypto(format!("Invalid transaction key: {}", e)))?;
+    let (signature, recovery_id) = signing_key
+        .sign_prehash_recoverable(hash)
+        .map_err(|e| TappError::Crypto(format!("Transaction signing failed: {}", e)))?;
+    let (r, s) = signature.split_bytes();
+    Ok((
+        recovery_id.is_y_odd(),
+        rlp_bytes(trim_zeros(&r)),
+        rlp_bytes(trim_zeros(&s)),
+    ))
+}
+
+/// RLP encoding of a recipient, the empty string for contract creation
+fn rlp_to(to: &Option<[u8; 20]>) -> Vec<u8> {
+    match to {
+        Some(to) => rlp_bytes(to),
+        None => rlp_bytes(&[]),
+    }
+}
+
+fn rlp_access_list(access_list: &[AccessListItem]) -> Vec<u8> {
+    let items: Vec<Vec<u8>> = access_list
+        .iter()
+        .map(|item| {
+            let storage_keys: Vec<Vec<u8>> =
+                item.storage_keys.iter().map(|key| rlp_bytes(key)).collect();
+            rlp_list(&[rlp_bytes(&item.address), rlp_list(&storage_keys)])
+        })
+        .collect();
+    rlp_list(&items)
+}
+
+fn typed_hash(payload: &[u8]) -> [u8; 32] {
+    let mut hasher = Keccak256::new();
+    hasher.update([EIP1559_TX_TYPE]);
+    hasher.update(payload);
+    hasher.finalize().into()
+}
+
+fn trim_zeros(bytes: &[u8]) -> &[u8] {
+    let start = bytes.iter().position(|b| *b != 0).unwrap_or(bytes.len());
+    &bytes[start..]
+}
+
+/// RLP prefix of a string or list of `len` bytes
+fn rlp_prefix(short_base: u8, len: usize) -> Vec<u8> {
+    if len < 56 {
+        return vec![short_base + len as u8];
+    }
+    let len_bytes = trim_zeros(&len.to_be_bytes()).to_vec();
+    let mut prefix = vec![short_base + 55 + len_bytes.len() as u8];
+    prefix.extend_from_slice(&len_bytes);
+    prefix
+}
+
+/// RLP encoding of a byte string
+pub fn rlp_bytes(bytes: &[u8]) -> Vec<u8> {
+    if bytes.len() == 1 && bytes[0] < 0x80 {
+        return bytes.to_vec();
+    }
+    let mut encoded = rlp_prefix(0x80, bytes.len());
+    encoded.extend_from_slice(bytes);
+    encoded
+}
+
+/// RLP encoding of an integer: big-endian without leading zeros
+pub fn rlp_uint(value: u128) -> Vec<u8> {
+    rlp_bytes(trim_zeros(&value.to_be_bytes()))
+}
+
+/// RLP encoding of a list of encoded items
+pub fn rlp_list(items: &[Vec<u8>]) -> Vec<u8> {
+    let payload = items.concat();
+    let mut encoded = rlp_prefix(0xc0, payload.len());
+    encoded.extend_from_slice(&payload);
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app_key::{eth_address, public_key_from_private};
+    use k256::ecdsa::{RecoveryId, Signature, VerifyingKey};
+
+    #[test]
+    fn test_rlp() {
+        // Examples of the RLP specification
+        assert_eq!(rlp_bytes(b"dog"), hex::decode("83646f67").unwrap());
+        assert_eq!(
+            rlp_list(&[rlp_bytes(b"cat"), rlp_bytes(b"dog")]),
+            hex::decode("c88363617483646f67").unwrap()
+        );
+        assert_eq!(rlp_bytes(b""), [0x80]);
+        assert_eq!(rlp_list(&[]), [0xc0]);
+        assert_eq!(rlp_uint(0), [0x80]);
+        assert_eq!(rlp_uint(15), [0x0f]);
+        assert_eq!(rlp_uint(1024), [0x82, 0x04, 0x00]);
+        let lorem = b"Lorem ipsum dolor sit amet, consectetur adipisicing elit";
+        let mut expected = vec![0xb8, 0x38];
+        expected.extend_from_slice(lorem);
+        assert_eq!(rlp_bytes(lorem), expected);
+        // [ [], [[]], [ [], [[]] ] ]
+        let empty = rlp_list(&[]);
+        let nested = rlp_list(&[empty.clone()]);
+        assert_eq!(
+            rlp_list(&[empty.clone(), nested.clone(), rlp_list(&[empty, nested])]),
+            hex::decode("c7c0c1c0c3c0c1c0").unwrap()
+        );
+    }
+
+    #[test]
+    fn test_signed_transaction() {
+        let private_key = [0x46; 32];
+        let tx = Eip1559Transaction {
+            chain_id: 16601,
+            nonce: 9,
+            max_priority_fee_per_gas: 1_000_000_000,
+            max_fee_per_gas: 20_000_000_000,
+            gas_limit: 21_000,
+            to: Some([0x35; 20]),
+            value: 1_000_000_000_000_000_000,
+            data: vec![],
+            access_list: vec![],
+        };
+        let signed = tx.sign(&private_key).unwrap();
+        assert_eq!(signed.raw[0], EIP1559_TX_TYPE);
+        assert_eq!(
+            signed.hash,
+            <[u8; 32]>::from(Keccak256::digest(&signed.raw))
+        );
+        assert!(signed.hash_hex().starts_with("0x"));
+
+        // The unsigned fields lead the signed list, which ends with
+        // y_parity, r and s
+        let unsigned = rlp_list(&tx.fields());
+        let fields_len = unsigned.len() - 1;
+        assert_eq!(signed.raw[3..3 + fields_len], unsigned[1..]);
+        let rest = &signed.raw[3 + fields_len..];
+        let y_parity = match rest[0] {
+            0x80 => 0,
+            byte => byte,
+        };
+        // r and s as 32-byte scalars
+        let scalar = |item: &[u8]| {
+            let len = (item[0] - 0x80) as usize;
+            let mut scalar = [0u8; 32];
+            scalar[32 - len..].copy_from_slice(&item[1..1 + len]);
+            (scalar, 1 + len)
+        };
+        let (r, r_len) = scalar(&rest[1..]);
+        let (s, s_len) = scalar(&rest[1 + r_len..]);
+        assert_eq!(rest.len(), 1 + r_len + s_len);
+
+        // The signature recovers to the key's address
+        let signature = Signature::from_slice(&[r, s].concat()).unwrap();
+        let recovery_id = RecoveryId::from_byte(y_parity).unwrap();
+        let recovered =
+            VerifyingKey::recover_from_prehash(&tx.signing_hash(), &signature, recovery_id)
+                .unwrap();
+        let public_key = public_key_from_private(&private_key).unwrap();
+        assert_eq!(
+            eth_address(&recovered.to_encoded_point(false).as_bytes()[1..]),
+            eth_address(&public_key)
+        );
+    }
+
+    #[test]
+    fn test_transaction_vectors() {
+        // Signatures are deterministic (RFC 6979), so the raw bytes are
+        // fixed. The legacy vector is the example of EIP-155, which ethers
+        // reproduces; the type 2 ones were computed independently of this
+        // module.
+        let private_key = [0x46; 32];
+
+        let legacy = LegacyTransaction {
+            chain_id: 1,
+            nonce: 9,
+            gas_price: 20_000_000_000,
+            gas_limit: 21_000,
+            to: Some([0x35; 20]),
+            value: 1_000_000_000_000_000_000,
+            data: vec![],
+        };
+        let signed = legacy.sign(&private_key).unwrap();
+        assert_eq!(
+            hex::encode(&signed.raw),
+            "f86c098504a817c800825208943535353535353535353535353535353535353535880de0b6b3a76400008025a028ef61340bd939bc2195fe537567866003e1a15d3c71ff63e1590620aa636276a067cbe9d8997f761aecb703304b3800ccf555c9f3dc64214b297fb1966a3b6d83"
+        );
+        assert_eq!(
+            signed.hash_hex(),
+            "0x33469b22e9f636356c4160a87eb19df52b7412e8eac32a4a55ffe88ea8350788"
+        );
+
+        let transfer = Eip1559Transaction {
+            chain_id: 16601,
+            nonce: 9,
+            max_priority_fee_per_gas: 1_000_000_000,
+            max_fee_per_gas: 20_000_000_000,
+            gas_limit: 21_000,
+            to: Some([0x35; 20]),
+            value: 1_000_000_000_000_000_000,
+            data: vec![],
+            access_list: vec![],
+        };
+        let signed = transfer.sign(&private_key).unwrap();
+        assert_eq!(
+            hex::encode(&signed.raw),
+            "02f8758240d909843b9aca008504a817c800825208943535353535353535353535353535353535353535880de0b6b3a764000080c001a0f34e14f33e90f84bff2072dc15ef114be4bc72462f3ae1f7044732976fff63b8a0653344b2506d783957c4e6573e8e1ebc73c26130ddd78407ae7ba8115f4eb251"
+        );
+        assert_eq!(
+            signed.hash_hex(),
+            "0x2389540b762908e84911943cb3df16fc8bc24d2fc8442d17b07768c513e8f3a2"
+        );
+
+        let mut token = [0u8; 20];
+        token[19] = 0xaa;
+        let mut slot = [0u8; 32];
+        slot[31] = 1;
+        let with_access_list = Eip1559Transaction {
+            chain_id: 1,
+            nonce: 3,
+            max_priority_fee_per_gas: 2_000_000_000,
+            max_fee_per_gas: 30_000_000_000,
+            gas_limit: 60_000,
+            to: Some(token),
+            value: 0,
+            data: hex::decode("a9059cbb").unwrap(),
+            access_list: vec![
+                AccessListItem {
+                    address: token,
+                    storage_keys: vec![slot, [0; 32]],
+                },
+                AccessListItem {
+                    address: [0x35; 20],
+                    storage_keys: vec![],
+                },
+            ],
+        };
+        let signed = with_access_list.sign(&private_key).unwrap();
+        assert_eq!(
+            hex::encode(&signed.raw),
+            "02f8e2010384773594008506fc23ac0082ea609400000000000000000000000000000000000000aa8084a9059cbbf872f8599400000000000000000000000000000000000000aaf842a00000000000000000000000000000000000000000000000000000000000000001a00000000000000000000000000000000000000000000000000000000000000000d6943535353535353535353535353535353535353535c001a0c843b7f841bbbe8c0f1465c1786dffcf308ecffa714521f7dcd4ac25180a0a06a00dade99eeb75edadf3fb7277048c2462e367d68e63080e270d53571f245cbccf"
+        );
+        assert_eq!(
+            signed.hash_hex(),
+            "0xdbeb0d2ecde24480e85319430c1e44004818b27cbd283fc34f0cf688d6b6a5b8"
+        );
+
+        // Contract creation leaves `to` empty
+        let create = LegacyTransaction { to: None, ..legacy };
+        assert_eq!(create.fields()[3], [0x80]);
+    }
+}
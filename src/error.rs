@@ -1,4 +1,7 @@
+use std::collections::HashMap;
+use std::time::Duration;
 use thiserror::Error;
+use tonic_types::{ErrorDetails, StatusExt};
 
 /// Result type for TAPP operations
 pub type TappResult<T> = Result<T, TappError>;
@@ -18,6 +21,10 @@ pub enum TappError {
     #[error("Docker error: {0}")]
     Docker(#[from] DockerError),
 
+    /// Tapp contract related errors
+    #[error("Contract error: {0}")]
+    Contract(#[from] ContractError),
+
     /// Configuration errors
     #[error("Configuration error: {0}")]
     Config(#[from] ConfigError),
@@ -42,10 +49,70 @@ pub enum TappError {
     #[error("Invalid parameter: {field} - {reason}")]
     InvalidParameter { field: String, reason: String },
 
+    /// An app with this ID is already deployed
+    #[error("Application {app_id} already exists")]
+    AppAlreadyExists { app_id: String },
+
+    /// No app with this ID is deployed
+    #[error("App {app_id} not found")]
+    AppNotFound { app_id: String },
+
+    /// No task with this ID was ever created (or it predates a restart)
+    #[error("Task not found: {task_id}")]
+    TaskNotFound { task_id: String },
+
+    /// The task finished longer ago than the retention period and was purged
+    #[error("Task expired: {task_id} (finished tasks are kept for {retention_seconds} seconds)")]
+    TaskExpired {
+        task_id: String,
+        retention_seconds: u64,
+    },
+
+    /// The app has no sealed blob with this label
+    #[error("Sealed blob {label} of app {app_id} not found")]
+    SealedBlobNotFound { app_id: String, label: String },
+
+    /// A sealed blob does not open for this app, or its compose hash when
+    /// bound to it
+    #[error("Sealed blob {label} is bound to another app or measurement than app {app_id}")]
+    SealBindingMismatch { app_id: String, label: String },
+
+    /// The key cluster is not in a state that allows the operation
+    #[error("Cluster state conflict: {0}")]
+    ClusterState(String),
+
+    /// The other side of a key share exchange did not prove it is a
+    /// whitelisted node
+    #[error("Cluster peer {peer} rejected: {reason}")]
+    ClusterPeerRejected { peer: String, reason: String },
+
+    /// A keystore backup is malformed, altered, or does not open with the
+    /// recovery key
+    #[error("Invalid keystore backup: {0}")]
+    InvalidBackup(String),
+
+    /// Keys of a keystore backup are held by this node with other values
+    /// (app IDs, or the key type of the sealing master key)
+    #[error("Keystore conflict: {} held with other keys", keys.join(", "))]
+    KeystoreConflict { keys: Vec<String> },
+
+    /// The caller may not make the request: not local, not the deployer, or
+    /// a rejected nonce
+    #[error("Permission denied: {0}")]
+    PermissionDenied(String),
+
     /// Service unavailable
     #[error("Service unavailable: {service}")]
     ServiceUnavailable { service: String },
 
+    /// A bounded resource (e.g. the deployment queue) is full
+    #[error("Resource exhausted: {0}")]
+    ResourceExhausted(String),
+
+    /// Operation cancelled on request
+    #[error("Operation cancelled")]
+    Cancelled,
+
     /// Internal error
     #[error("Internal error: {0}")]
     Internal(String),
@@ -68,6 +135,9 @@ pub enum AttestationError {
 
     #[error("RTMR extension failed: {reason}")]
     RtmrExtensionFailed { reason: String },
+
+    #[error("Invalid quote: {reason}")]
+    InvalidQuote { reason: String },
 }
 
 /// KBS specific errors
@@ -92,23 +162,220 @@ pub enum KbsError {
     UnsupportedKeyType { key_type: String },
 }
 
+/// Errors talking to the tapp contract through an Ethereum JSON-RPC node
+#[derive(Error, Debug)]
+pub enum ContractError {
+    /// The node could not be reached or answered with an HTTP error
+    #[error("RPC node {endpoint} unreachable: {reason}")]
+    ConnectionFailed { endpoint: String, reason: String },
+
+    /// The node answered the call with a JSON-RPC error
+    #[error("{method} failed with RPC error {code}: {message}")]
+    RpcError {
+        method: String,
+        code: i64,
+        message: String,
+    },
+
+    /// The node's answer could not be understood
+    #[error("Unexpected answer to {method}: {reason}")]
+    InvalidResponse { method: String, reason: String },
+
+    /// The transaction was mined but reverted
+    #[error("Transaction {tx_hash} reverted")]
+    TransactionReverted { tx_hash: String },
+}
+
+impl ContractError {
+    /// Failures that may go away on their own: the node is unreachable,
+    /// overloaded or restarting
+    pub fn is_transient(&self) -> bool {
+        matches!(self, ContractError::ConnectionFailed { .. })
+    }
+}
+
 /// Docker specific errors
 #[derive(Error, Debug)]
 pub enum DockerError {
-    #[error("Docker daemon connection failed")]
-    ConnectionFailed,
+    /// The daemon could not be reached; `endpoint` is empty when unknown
+    #[error("Docker daemon connection failed{}: {reason}", endpoint_suffix(.endpoint))]
+    ConnectionFailed { endpoint: String, reason: String },
+
+    /// The daemon does not speak the client's API version
+    #[error("Docker API version not supported: {reason}")]
+    ApiVersionMismatch { reason: String },
+
+    /// The daemon answered with an error
+    #[error("Docker daemon returned {status_code}: {message}")]
+    DaemonError { status_code: u16, message: String },
+
+    /// The request failed in the client before reaching the daemon
+    #[error("Docker client error: {reason}")]
+    ClientError { reason: String },
 
     #[error("Invalid compose content: {reason}")]
     InvalidComposeContent { reason: String },
 
     #[error("Container operation failed: {operation} - {reason}")]
-    ContainerOperationFailed { operation: String, reason: String },
+    ContainerOperationFailed {
+        operation: String,
+        reason: String,
+        cause: FailureCause,
+    },
+
+    #[error("Container operation timed out: {operation} after {seconds}s")]
+    Timeout { operation: String, seconds: u64 },
 
     #[error("Volume measurement failed: {path}")]
     VolumeMeasurementFailed { path: String },
 
     #[error("Service not found: {service_name}")]
     ServiceNotFound { service_name: String },
+
+    #[error("Images not allowed by the image policy: {}", .violations.join("; "))]
+    ImagesNotAllowed { violations: Vec<String> },
+
+    /// Images failed the vulnerability scan, or could not be scanned
+    #[error("Image scan failed: {}", .findings.join("; "))]
+    ImageScanFailed { findings: Vec<String> },
+}
+
+fn endpoint_suffix(endpoint: &str) -> String {
+    if endpoint.is_empty() {
+        String::new()
+    } else {
+        format!(" at {}", endpoint)
+    }
+}
+
+impl DockerError {
+    /// Name the endpoint of a connection failure that doesn't know it yet
+    pub fn at_endpoint(self, attempted: &str) -> Self {
+        match self {
+            DockerError::ConnectionFailed { endpoint, reason } if endpoint.is_empty() => {
+                DockerError::ConnectionFailed {
+                    endpoint: attempted.to_string(),
+                    reason,
+                }
+            }
+            other => other,
+        }
+    }
+}
+
+/// Why talking to the daemon failed, from the I/O error underneath
+fn io_reason(err: &std::io::Error) -> String {
+    match err.kind() {
+        std::io::ErrorKind::NotFound => format!("socket not found ({})", err),
+        std::io::ErrorKind::PermissionDenied => format!(
+            "permission denied, check that the service may use the Docker socket ({})",
+            err
+        ),
+        std::io::ErrorKind::ConnectionRefused => format!("connection refused ({})", err),
+        _ => err.to_string(),
+    }
+}
+
+/// The I/O error a bollard error was caused by, if any
+fn io_source(err: &(dyn std::error::Error + 'static)) -> Option<&std::io::Error> {
+    let mut source = err.source();
+    while let Some(cause) = source {
+        if let Some(io) = cause.downcast_ref::<std::io::Error>() {
+            return Some(io);
+        }
+        source = cause.source();
+    }
+    None
+}
+
+impl From<bollard::errors::Error> for DockerError {
+    fn from(err: bollard::errors::Error) -> Self {
+        use bollard::errors::Error;
+
+        let connection_failed =
+            |endpoint: String, reason: String| DockerError::ConnectionFailed { endpoint, reason };
+        match err {
+            Error::DockerResponseServerError {
+                status_code,
+                message,
+            } => {
+                // e.g. "client version 1.45 is too new. Maximum supported API version is 1.41"
+                if status_code == 400 && message.contains("API version") {
+                    DockerError::ApiVersionMismatch { reason: message }
+                } else {
+                    DockerError::DaemonError {
+                        status_code,
+                        message,
+                    }
+                }
+            }
+            Error::APIVersionParseError {} => DockerError::ApiVersionMismatch {
+                reason: err.to_string(),
+            },
+            Error::SocketNotFoundError(path) => connection_failed(path, "socket not found".into()),
+            Error::UnsupportedURISchemeError { uri } => {
+                connection_failed(uri, "unsupported URI scheme".into())
+            }
+            Error::RequestTimeoutError => {
+                connection_failed(String::new(), "request timed out".into())
+            }
+            Error::IOError { err } => connection_failed(String::new(), io_reason(&err)),
+            err => match io_source(&err) {
+                Some(io) => connection_failed(String::new(), io_reason(io)),
+                None => DockerError::ClientError {
+                    reason: err.to_string(),
+                },
+            },
+        }
+    }
+}
+
+impl From<bollard::errors::Error> for TappError {
+    fn from(err: bollard::errors::Error) -> Self {
+        TappError::Docker(err.into())
+    }
+}
+
+/// Why a container operation failed, which decides the gRPC code clients see
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FailureCause {
+    /// The operation isn't possible in the current state or configuration,
+    /// e.g. key retrieval outside in-memory mode (FAILED_PRECONDITION)
+    Precondition,
+    /// Docker, the filesystem or a crypto primitive failed (INTERNAL)
+    Internal,
+}
+
+/// Nonce verification errors
+#[derive(Error, Debug)]
+pub enum NonceError {
+    #[error("Nonce must be between 1 and {max} bytes")]
+    InvalidLength { max: usize },
+
+    #[error("Nonce must be hex digits")]
+    InvalidFormat,
+
+    #[error("Timestamp outside validity window. Diff: {diff}s, Max: {max}s")]
+    OutsideWindow { diff: i64, max: i64 },
+
+    #[error("Nonce already used (replay attack detected)")]
+    Replayed,
+
+    #[error("Challenge nonce expired, request a new one with GetNonce")]
+    ChallengeExpired,
+
+    #[error("Timestamp {timestamp} is older than the last accepted request ({last_accepted}, tolerance {tolerance}s)")]
+    OutOfOrder {
+        timestamp: i64,
+        last_accepted: i64,
+        tolerance: i64,
+    },
+
+    #[error("Too many outstanding nonces (capacity {capacity}), retry later")]
+    CapacityExceeded { capacity: usize },
+
+    #[error("Too many unused challenges for this caller (max {max}), retry later")]
+    TooManyChallenges { max: usize },
 }
 
 /// Configuration specific errors
@@ -125,6 +392,10 @@ pub enum ConfigError {
 
     #[error("Configuration parsing failed: {reason}")]
     ParseFailed { reason: String },
+
+    /// Every problem found by `TappConfig::validate`
+    #[error("{} configuration problem(s): {}", .problems.len(), .problems.iter().map(ToString::to_string).collect::<Vec<_>>().join("; "))]
+    Invalid { problems: Vec<ConfigError> },
 }
 
 // Implement conversion from guest-components errors
@@ -134,33 +405,429 @@ impl From<anyhow::Error> for TappError {
     }
 }
 
+/// Domain of the `google.rpc.ErrorInfo` attached to failed calls
+pub const ERROR_DOMAIN: &str = "tapp.0g.ai";
+
+/// Stable reason codes sent in `google.rpc.ErrorInfo`
+/// Clients match on these rather than on messages, so existing codes must
+/// never be renamed or reused; add new ones instead.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ErrorReason {
+    InvalidParameter,
+    AppAlreadyExists,
+    AppNotFound,
+    TaskNotFound,
+    TaskExpired,
+    SealedBlobNotFound,
+    SealBindingMismatch,
+    ClusterState,
+    ClusterPeerRejected,
+    InvalidBackup,
+    KeystoreConflict,
+    PermissionDenied,
+    ServiceUnavailable,
+    ResourceExhausted,
+    RateLimited,
+    Cancelled,
+    Internal,
+    Io,
+    Serialization,
+    Crypto,
+    Config,
+    Upstream,
+    TeeNotSupported,
+    EvidenceGenerationFailed,
+    InvalidRuntimeData,
+    UnsupportedEvidenceFormat,
+    RtmrExtensionFailed,
+    InvalidQuote,
+    KbsConnectionFailed,
+    KbsAuthenticationFailed,
+    KbsResourceNotFound,
+    KbsInvalidResourceUri,
+    KbsKeyDerivationFailed,
+    KbsUnsupportedKeyType,
+    DockerConnectionFailed,
+    DockerApiVersionMismatch,
+    DockerDaemonError,
+    DockerClientError,
+    InvalidCompose,
+    ContainerOperationFailed,
+    ContainerTimeout,
+    VolumeMeasurementFailed,
+    ServiceNotFound,
+    ImagesNotAllowed,
+    ImageScanFailed,
+    ContractConnectionFailed,
+    ContractRpcError,
+    ContractInvalidResponse,
+    ContractTransactionReverted,
+}
+
+impl ErrorReason {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ErrorReason::InvalidParameter => "INVALID_PARAMETER",
+            ErrorReason::AppAlreadyExists => "APP_ALREADY_EXISTS",
+            ErrorReason::AppNotFound => "APP_NOT_FOUND",
+            ErrorReason::TaskNotFound => "TASK_NOT_FOUND",
+            ErrorReason::TaskExpired => "TASK_EXPIRED",
+            ErrorReason::SealedBlobNotFound => "SEALED_BLOB_NOT_FOUND",
+            ErrorReason::SealBindingMismatch => "SEAL_BINDING_MISMATCH",
+            ErrorReason::ClusterState => "CLUSTER_STATE_CONFLICT",
+            ErrorReason::ClusterPeerRejected => "CLUSTER_PEER_REJECTED",
+            ErrorReason::InvalidBackup => "INVALID_KEYSTORE_BACKUP",
+            ErrorReason::KeystoreConflict => "KEYSTORE_CONFLICT",
+            ErrorReason::PermissionDenied => "PERMISSION_DENIED",
+            ErrorReason::ServiceUnavailable => "SERVICE_UNAVAILABLE",
+            ErrorReason::ResourceExhausted => "RESOURCE_EXHAUSTED",
+            ErrorReason::RateLimited => "RATE_LIMITED",
+            ErrorReason::Cancelled => "CANCELLED",
+            ErrorReason::Internal => "INTERNAL",
+            ErrorReason::Io => "IO_ERROR",
+            ErrorReason::Serialization => "SERIALIZATION_ERROR",
+            ErrorReason::Crypto => "CRYPTO_ERROR",
+            ErrorReason::Config => "CONFIG_ERROR",
+            ErrorReason::Upstream => "UPSTREAM_ERROR",
+            ErrorReason::TeeNotSupported => "TEE_NOT_SUPPORTED",
+            ErrorReason::EvidenceGenerationFailed => "EVIDENCE_GENERATION_FAILED",
+            ErrorReason::InvalidRuntimeData => "INVALID_RUNTIME_DATA",
+            ErrorReason::UnsupportedEvidenceFormat => "UNSUPPORTED_EVIDENCE_FORMAT",
+            ErrorReason::RtmrExtensionFailed => "RTMR_EXTENSION_FAILED",
+            ErrorReason::InvalidQuote => "INVALID_QUOTE",
+            ErrorReason::KbsConnectionFailed => "KBS_CONNECTION_FAILED",
+            ErrorReason::KbsAuthenticationFailed => "KBS_AUTHENTICATION_FAILED",
+            ErrorReason::KbsResourceNotFound => "KBS_RESOURCE_NOT_FOUND",
+            ErrorReason::KbsInvalidResourceUri => "KBS_INVALID_RESOURCE_URI",
+            ErrorReason::KbsKeyDerivationFailed => "KBS_KEY_DERIVATION_FAILED",
+            ErrorReason::KbsUnsupportedKeyType => "KBS_UNSUPPORTED_KEY_TYPE",
+            ErrorReason::DockerConnectionFailed => "DOCKER_CONNECTION_FAILED",
+            ErrorReason::DockerApiVersionMismatch => "DOCKER_API_VERSION_MISMATCH",
+            ErrorReason::DockerDaemonError => "DOCKER_DAEMON_ERROR",
+            ErrorReason::DockerClientError => "DOCKER_CLIENT_ERROR",
+            ErrorReason::InvalidCompose => "INVALID_COMPOSE",
+            ErrorReason::ContainerOperationFailed => "CONTAINER_OPERATION_FAILED",
+            ErrorReason::ContainerTimeout => "CONTAINER_TIMEOUT",
+            ErrorReason::VolumeMeasurementFailed => "VOLUME_MEASUREMENT_FAILED",
+            ErrorReason::ServiceNotFound => "SERVICE_NOT_FOUND",
+            ErrorReason::ImagesNotAllowed => "IMAGES_NOT_ALLOWED",
+            ErrorReason::ImageScanFailed => "IMAGE_SCAN_FAILED",
+            ErrorReason::ContractConnectionFailed => "CONTRACT_CONNECTION_FAILED",
+            ErrorReason::ContractRpcError => "CONTRACT_RPC_ERROR",
+            ErrorReason::ContractInvalidResponse => "CONTRACT_INVALID_RESPONSE",
+            ErrorReason::ContractTransactionReverted => "CONTRACT_TRANSACTION_REVERTED",
+        }
+    }
+}
+
+impl std::fmt::Display for ErrorReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl AttestationError {
+    pub fn reason(&self) -> ErrorReason {
+        match self {
+            AttestationError::TeeNotSupported => ErrorReason::TeeNotSupported,
+            AttestationError::EvidenceGenerationFailed { .. } => {
+                ErrorReason::EvidenceGenerationFailed
+            }
+            AttestationError::InvalidRuntimeData { .. } => ErrorReason::InvalidRuntimeData,
+            AttestationError::UnsupportedEvidenceFormat { .. } => {
+                ErrorReason::UnsupportedEvidenceFormat
+            }
+            AttestationError::RtmrExtensionFailed { .. } => ErrorReason::RtmrExtensionFailed,
+            AttestationError::InvalidQuote { .. } => ErrorReason::InvalidQuote,
+        }
+    }
+}
+
+impl KbsError {
+    pub fn reason(&self) -> ErrorReason {
+        match self {
+            KbsError::ConnectionFailed { .. } => ErrorReason::KbsConnectionFailed,
+            KbsError::AuthenticationFailed => ErrorReason::KbsAuthenticationFailed,
+            KbsError::ResourceNotFound { .. } => ErrorReason::KbsResourceNotFound,
+            KbsError::InvalidResourceUri { .. } => ErrorReason::KbsInvalidResourceUri,
+            KbsError::KeyDerivationFailed { .. } => ErrorReason::KbsKeyDerivationFailed,
+            KbsError::UnsupportedKeyType { .. } => ErrorReason::KbsUnsupportedKeyType,
+        }
+    }
+}
+
+impl DockerError {
+    pub fn reason(&self) -> ErrorReason {
+        match self {
+            DockerError::ConnectionFailed { .. } => ErrorReason::DockerConnectionFailed,
+            DockerError::ApiVersionMismatch { .. } => ErrorReason::DockerApiVersionMismatch,
+            DockerError::DaemonError { .. } => ErrorReason::DockerDaemonError,
+            DockerError::ClientError { .. } => ErrorReason::DockerClientError,
+            DockerError::InvalidComposeContent { .. } => ErrorReason::InvalidCompose,
+            DockerError::ContainerOperationFailed { .. } => ErrorReason::ContainerOperationFailed,
+            DockerError::Timeout { .. } => ErrorReason::ContainerTimeout,
+            DockerError::VolumeMeasurementFailed { .. } => ErrorReason::VolumeMeasurementFailed,
+            DockerError::ServiceNotFound { .. } => ErrorReason::ServiceNotFound,
+            DockerError::ImagesNotAllowed { .. } => ErrorReason::ImagesNotAllowed,
+            DockerError::ImageScanFailed { .. } => ErrorReason::ImageScanFailed,
+        }
+    }
+}
+
+impl ContractError {
+    pub fn reason(&self) -> ErrorReason {
+        match self {
+            ContractError::ConnectionFailed { .. } => ErrorReason::ContractConnectionFailed,
+            ContractError::RpcError { .. } => ErrorReason::ContractRpcError,
+            ContractError::InvalidResponse { .. } => ErrorReason::ContractInvalidResponse,
+            ContractError::TransactionReverted { .. } => ErrorReason::ContractTransactionReverted,
+        }
+    }
+}
+
+impl TappError {
+    /// Stable reason code reported to clients
+    pub fn reason(&self) -> ErrorReason {
+        match self {
+            TappError::Attestation(e) => e.reason(),
+            TappError::Kbs(e) => e.reason(),
+            TappError::Docker(e) => e.reason(),
+            TappError::Contract(e) => e.reason(),
+            TappError::Config(_) => ErrorReason::Config,
+            TappError::Grpc(_) => ErrorReason::Upstream,
+            TappError::Io(_) => ErrorReason::Io,
+            TappError::Serialization(_) => ErrorReason::Serialization,
+            TappError::Crypto(_) => ErrorReason::Crypto,
+            TappError::InvalidParameter { .. } => ErrorReason::InvalidParameter,
+            TappError::AppAlreadyExists { .. } => ErrorReason::AppAlreadyExists,
+            TappError::AppNotFound { .. } => ErrorReason::AppNotFound,
+            TappError::TaskNotFound { .. } => ErrorReason::TaskNotFound,
+            TappError::TaskExpired { .. } => ErrorReason::TaskExpired,
+            TappError::SealedBlobNotFound { .. } => ErrorReason::SealedBlobNotFound,
+            TappError::SealBindingMismatch { .. } => ErrorReason::SealBindingMismatch,
+            TappError::ClusterState(_) => ErrorReason::ClusterState,
+            TappError::ClusterPeerRejected { .. } => ErrorReason::ClusterPeerRejected,
+            TappError::InvalidBackup(_) => ErrorReason::InvalidBackup,
+            TappError::KeystoreConflict { .. } => ErrorReason::KeystoreConflict,
+            TappError::PermissionDenied(_) => ErrorReason::PermissionDenied,
+            TappError::ServiceUnavailable { .. } => ErrorReason::ServiceUnavailable,
+            TappError::ResourceExhausted(_) => ErrorReason::ResourceExhausted,
+            TappError::Cancelled => ErrorReason::Cancelled,
+            TappError::Internal(_) => ErrorReason::Internal,
+        }
+    }
+
+    /// Values identifying what failed, sent as `ErrorInfo` metadata
+    fn metadata(&self) -> HashMap<String, String> {
+        let pairs: Vec<(&str, String)> = match self {
+            TappError::InvalidParameter { field, .. } => vec![("field", field.clone())],
+            TappError::AppAlreadyExists { app_id } | TappError::AppNotFound { app_id } => {
+                vec![("app_id", app_id.clone())]
+            }
+            TappError::TaskNotFound { task_id } => vec![("task_id", task_id.clone())],
+            TappError::TaskExpired {
+                task_id,
+                retention_seconds,
+            } => vec![
+                ("task_id", task_id.clone()),
+                ("retention_seconds", retention_seconds.to_string()),
+            ],
+            TappError::SealedBlobNotFound { app_id, label }
+            | TappError::SealBindingMismatch { app_id, label } => {
+                vec![("app_id", app_id.clone()), ("label", label.clone())]
+            }
+            TappError::ClusterPeerRejected { peer, .. } => vec![("peer", peer.clone())],
+            TappError::KeystoreConflict { keys } => vec![("keys", keys.join(","))],
+            TappError::ServiceUnavailable { service } => vec![("service", service.clone())],
+            TappError::Attestation(AttestationError::UnsupportedEvidenceFormat { format }) => {
+                vec![("format", format.clone())]
+            }
+            TappError::Kbs(KbsError::ConnectionFailed { endpoint }) => {
+                vec![("endpoint", endpoint.clone())]
+            }
+            TappError::Kbs(KbsError::ResourceNotFound { resource_uri }) => {
+                vec![("resource_uri", resource_uri.clone())]
+            }
+            TappError::Kbs(KbsError::InvalidResourceUri { uri }) => {
+                vec![("resource_uri", uri.clone())]
+            }
+            TappError::Kbs(KbsError::UnsupportedKeyType { key_type }) => {
+                vec![("key_type", key_type.clone())]
+            }
+            TappError::Docker(DockerError::ConnectionFailed { endpoint, .. })
+                if !endpoint.is_empty() =>
+            {
+                vec![("endpoint", endpoint.clone())]
+            }
+            TappError::Docker(DockerError::DaemonError { status_code, .. }) => {
+                vec![("status_code", status_code.to_string())]
+            }
+            TappError::Docker(DockerError::ContainerOperationFailed { operation, .. }) => {
+                vec![("operation", operation.clone())]
+            }
+            TappError::Docker(DockerError::Timeout { operation, seconds }) => vec![
+                ("operation", operation.clone()),
+                ("timeout_seconds", seconds.to_string()),
+            ],
+            TappError::Docker(DockerError::VolumeMeasurementFailed { path }) => {
+                vec![("path", path.clone())]
+            }
+            TappError::Docker(DockerError::ServiceNotFound { service_name }) => {
+                vec![("service_name", service_name.clone())]
+            }
+            TappError::Docker(DockerError::ImagesNotAllowed { violations }) => {
+                vec![("violations", violations.len().to_string())]
+            }
+            TappError::Docker(DockerError::ImageScanFailed { findings }) => {
+                vec![("images", findings.len().to_string())]
+            }
+            TappError::Contract(ContractError::ConnectionFailed { endpoint, .. }) => {
+                vec![("endpoint", endpoint.clone())]
+            }
+            TappError::Contract(ContractError::RpcError { method, code, .. }) => {
+                vec![("method", method.clone()), ("rpc_code", code.to_string())]
+            }
+            TappError::Contract(ContractError::InvalidResponse { method, .. }) => {
+                vec![("method", method.clone())]
+            }
+            TappError::Contract(ContractError::TransactionReverted { tx_hash }) => {
+                vec![("tx_hash", tx_hash.clone())]
+            }
+            TappError::Grpc(status) => vec![("upstream_code", format!("{:?}", status.code()))],
+            _ => Vec::new(),
+        };
+        pairs
+            .into_iter()
+            .map(|(key, value)| (key.to_string(), value))
+            .collect()
+    }
+}
+
+/// RESOURCE_EXHAUSTED status telling the client when to retry, both as a
+/// `RetryInfo` detail and as a `retry-after` header in seconds
+pub fn throttled_status(
+    message: impl Into<String>,
+    reason: ErrorReason,
+    metadata: HashMap<String, String>,
+    retry_after: Duration,
+) -> tonic::Status {
+    let mut details = ErrorDetails::with_error_info(reason.as_str(), ERROR_DOMAIN, metadata);
+    details.set_retry_info(Some(retry_after));
+    let mut status =
+        tonic::Status::with_error_details(tonic::Code::ResourceExhausted, message, details);
+    if let Ok(value) = retry_after.as_secs().max(1).to_string().parse() {
+        status.metadata_mut().insert("retry-after", value);
+    }
+    status
+}
+
 // Convert to gRPC Status for network responses
 impl From<TappError> for tonic::Status {
     fn from(err: TappError) -> Self {
-        use tonic::Status;
+        use tonic::Code;
 
-        match err {
+        let code = match &err {
+            TappError::InvalidParameter { .. } | TappError::InvalidBackup(_) => {
+                Code::InvalidArgument
+            }
+            TappError::AppAlreadyExists { .. } | TappError::KeystoreConflict { .. } => {
+                Code::AlreadyExists
+            }
+            TappError::AppNotFound { .. }
+            | TappError::TaskNotFound { .. }
+            | TappError::TaskExpired { .. }
+            | TappError::SealedBlobNotFound { .. } => Code::NotFound,
+            TappError::SealBindingMismatch { .. } => Code::PermissionDenied,
+            TappError::ClusterState(_) => Code::FailedPrecondition,
+            TappError::ClusterPeerRejected { .. } | TappError::PermissionDenied(_) => {
+                Code::PermissionDenied
+            }
+            TappError::ServiceUnavailable { .. } => Code::Unavailable,
+            TappError::ResourceExhausted(_) => Code::ResourceExhausted,
+            TappError::Cancelled => Code::Cancelled,
+            TappError::Config(_) => Code::FailedPrecondition,
+            TappError::Attestation(e) => match e {
+                AttestationError::TeeNotSupported => Code::FailedPrecondition,
+                AttestationError::InvalidRuntimeData { .. }
+                | AttestationError::UnsupportedEvidenceFormat { .. }
+                | AttestationError::InvalidQuote { .. } => Code::InvalidArgument,
+                AttestationError::EvidenceGenerationFailed { .. }
+                | AttestationError::RtmrExtensionFailed { .. } => Code::Internal,
+            },
+            TappError::Kbs(e) => match e {
+                KbsError::ConnectionFailed { .. } => Code::Unavailable,
+                KbsError::AuthenticationFailed => Code::Unauthenticated,
+                KbsError::ResourceNotFound { .. } => Code::NotFound,
+                KbsError::InvalidResourceUri { .. } | KbsError::UnsupportedKeyType { .. } => {
+                    Code::InvalidArgument
+                }
+                KbsError::KeyDerivationFailed { .. } => Code::Internal,
+            },
+            TappError::Docker(e) => match e {
+                DockerError::ConnectionFailed { .. } => Code::Unavailable,
+                DockerError::ApiVersionMismatch { .. } => Code::FailedPrecondition,
+                DockerError::DaemonError { .. } | DockerError::ClientError { .. } => Code::Internal,
+                DockerError::InvalidComposeContent { .. }
+                | DockerError::VolumeMeasurementFailed { .. } => Code::InvalidArgument,
+                DockerError::ContainerOperationFailed { cause, .. } => match cause {
+                    FailureCause::Precondition => Code::FailedPrecondition,
+                    FailureCause::Internal => Code::Internal,
+                },
+                DockerError::Timeout { .. } => Code::DeadlineExceeded,
+                DockerError::ServiceNotFound { .. } => Code::NotFound,
+                DockerError::ImagesNotAllowed { .. } | DockerError::ImageScanFailed { .. } => {
+                    Code::PermissionDenied
+                }
+            },
+            TappError::Contract(e) => match e {
+                ContractError::ConnectionFailed { .. } => Code::Unavailable,
+                ContractError::RpcError { .. } | ContractError::InvalidResponse { .. } => {
+                    Code::Internal
+                }
+                ContractError::TransactionReverted { .. } => Code::FailedPrecondition,
+            },
+            TappError::Grpc(_)
+            | TappError::Io(_)
+            | TappError::Serialization(_)
+            | TappError::Crypto(_)
+            | TappError::Internal(_) => Code::Internal,
+        };
+        let message = match &err {
             TappError::InvalidParameter { field, reason } => {
-                Status::invalid_argument(format!("Invalid {}: {}", field, reason))
+                format!("Invalid {}: {}", field, reason)
             }
             TappError::ServiceUnavailable { service } => {
-                Status::unavailable(format!("Service {} is unavailable", service))
+                format!("Service {} is unavailable", service)
             }
             TappError::Attestation(AttestationError::TeeNotSupported) => {
-                Status::failed_precondition("TEE not supported on this platform")
+                "TEE not supported on this platform".to_string()
             }
             TappError::Kbs(KbsError::AuthenticationFailed) => {
-                Status::unauthenticated("KBS authentication failed")
+                "KBS authentication failed".to_string()
             }
             TappError::Kbs(KbsError::ResourceNotFound { resource_uri }) => {
-                Status::not_found(format!("Resource not found: {}", resource_uri))
+                format!("Resource not found: {}", resource_uri)
             }
             TappError::Docker(DockerError::ServiceNotFound { service_name }) => {
-                Status::not_found(format!("Service not found: {}", service_name))
+                format!("Service not found: {}", service_name)
             }
-            TappError::Config(_) => Status::failed_precondition("Service configuration error"),
-            _ => Status::internal(err.to_string()),
+            TappError::Docker(
+                e @ (DockerError::ImagesNotAllowed { .. } | DockerError::ImageScanFailed { .. }),
+            ) => e.to_string(),
+            TappError::Config(_) => "Service configuration error".to_string(),
+            TappError::ResourceExhausted(reason)
+            | TappError::ClusterState(reason)
+            | TappError::PermissionDenied(reason) => reason.clone(),
+            TappError::Cancelled => "Operation cancelled".to_string(),
+            _ => err.to_string(),
+        };
+
+        let mut details =
+            ErrorDetails::with_error_info(err.reason().as_str(), ERROR_DOMAIN, err.metadata());
+        if let TappError::InvalidParameter { field, reason } = &err {
+            details.add_bad_request_violation(field.clone(), reason.clone());
         }
+        tonic::Status::with_error_details(code, message, details)
     }
 }
 
@@ -206,6 +873,429 @@ mod tests {
         };
         let status: tonic::Status = err.into();
         assert_eq!(status.code(), tonic::Code::InvalidArgument);
+
+        let details = status.check_error_details().unwrap();
+        let info = details.error_info().unwrap();
+        assert_eq!(info.reason, "INVALID_PARAMETER");
+        assert_eq!(info.domain, ERROR_DOMAIN);
+        assert_eq!(
+            info.metadata.get("field").map(String::as_str),
+            Some("app_id")
+        );
+        let violations = &details.bad_request().unwrap().field_violations;
+        assert_eq!(violations.len(), 1);
+        assert_eq!(violations[0].field, "app_id");
+        assert_eq!(violations[0].description, "cannot be empty");
+    }
+
+    /// Every variant, with the code and reason clients see
+    fn status_table() -> Vec<(TappError, tonic::Code, &'static str)> {
+        use tonic::Code;
+
+        let s = |value: &str| value.to_string();
+        let operation_failed = |cause| DockerError::ContainerOperationFailed {
+            operation: s("get_private_key"),
+            reason: s("failed"),
+            cause,
+        };
+        vec![
+            (
+                TappError::Attestation(AttestationError::TeeNotSupported),
+                Code::FailedPrecondition,
+                "TEE_NOT_SUPPORTED",
+            ),
+            (
+                AttestationError::EvidenceGenerationFailed { reason: s("x") }.into(),
+                Code::Internal,
+                "EVIDENCE_GENERATION_FAILED",
+            ),
+            (
+                AttestationError::InvalidRuntimeData { reason: s("x") }.into(),
+                Code::InvalidArgument,
+                "INVALID_RUNTIME_DATA",
+            ),
+            (
+                AttestationError::UnsupportedEvidenceFormat { format: s("x") }.into(),
+                Code::InvalidArgument,
+                "UNSUPPORTED_EVIDENCE_FORMAT",
+            ),
+            (
+                AttestationError::RtmrExtensionFailed { reason: s("x") }.into(),
+                Code::Internal,
+                "RTMR_EXTENSION_FAILED",
+            ),
+            (
+                AttestationError::InvalidQuote { reason: s("x") }.into(),
+                Code::InvalidArgument,
+                "INVALID_QUOTE",
+            ),
+            (
+                KbsError::ConnectionFailed { endpoint: s("x") }.into(),
+                Code::Unavailable,
+                "KBS_CONNECTION_FAILED",
+            ),
+            (
+                KbsError::AuthenticationFailed.into(),
+                Code::Unauthenticated,
+                "KBS_AUTHENTICATION_FAILED",
+            ),
+            (
+                KbsError::ResourceNotFound {
+                    resource_uri: s("kbs:///default/key/1"),
+                }
+                .into(),
+                Code::NotFound,
+                "KBS_RESOURCE_NOT_FOUND",
+            ),
+            (
+                KbsError::InvalidResourceUri { uri: s("x") }.into(),
+                Code::InvalidArgument,
+                "KBS_INVALID_RESOURCE_URI",
+            ),
+            (
+                KbsError::KeyDerivationFailed { reason: s("x") }.into(),
+                Code::Internal,
+                "KBS_KEY_DERIVATION_FAILED",
+            ),
+            (
+                KbsError::UnsupportedKeyType { key_type: s("x") }.into(),
+                Code::InvalidArgument,
+                "KBS_UNSUPPORTED_KEY_TYPE",
+            ),
+            (
+                DockerError::ConnectionFailed {
+                    endpoint: s("unix:///var/run/docker.sock"),
+                    reason: s("x"),
+                }
+                .into(),
+                Code::Unavailable,
+                "DOCKER_CONNECTION_FAILED",
+            ),
+            (
+                DockerError::ApiVersionMismatch { reason: s("x") }.into(),
+                Code::FailedPrecondition,
+                "DOCKER_API_VERSION_MISMATCH",
+            ),
+            (
+                DockerError::DaemonError {
+                    status_code: 500,
+                    message: s("x"),
+                }
+                .into(),
+                Code::Internal,
+                "DOCKER_DAEMON_ERROR",
+            ),
+            (
+                DockerError::ClientError { reason: s("x") }.into(),
+                Code::Internal,
+                "DOCKER_CLIENT_ERROR",
+            ),
+            (
+                DockerError::InvalidComposeContent { reason: s("x") }.into(),
+                Code::InvalidArgument,
+                "INVALID_COMPOSE",
+            ),
+            (
+                operation_failed(FailureCause::Precondition).into(),
+                Code::FailedPrecondition,
+                "CONTAINER_OPERATION_FAILED",
+            ),
+            (
+                operation_failed(FailureCause::Internal).into(),
+                Code::Internal,
+                "CONTAINER_OPERATION_FAILED",
+            ),
+            (
+                DockerError::Timeout {
+                    operation: s("docker_compose_up"),
+                    seconds: 300,
+                }
+                .into(),
+                Code::DeadlineExceeded,
+                "CONTAINER_TIMEOUT",
+            ),
+            (
+                DockerError::VolumeMeasurementFailed { path: s("x") }.into(),
+                Code::InvalidArgument,
+                "VOLUME_MEASUREMENT_FAILED",
+            ),
+            (
+                DockerError::ServiceNotFound {
+                    service_name: s("x"),
+                }
+                .into(),
+                Code::NotFound,
+                "SERVICE_NOT_FOUND",
+            ),
+            (
+                DockerError::ImagesNotAllowed {
+                    violations: vec![s("nginx:latest is not pinned")],
+                }
+                .into(),
+                Code::PermissionDenied,
+                "IMAGES_NOT_ALLOWED",
+            ),
+            (
+                DockerError::ImageScanFailed {
+                    findings: vec![s("nginx: 1 critical")],
+                }
+                .into(),
+                Code::PermissionDenied,
+                "IMAGE_SCAN_FAILED",
+            ),
+            (
+                ContractError::ConnectionFailed {
+                    endpoint: s("http://127.0.0.1:8545"),
+                    reason: s("x"),
+                }
+                .into(),
+                Code::Unavailable,
+                "CONTRACT_CONNECTION_FAILED",
+            ),
+            (
+                ContractError::RpcError {
+                    method: s("eth_sendRawTransaction"),
+                    code: -32000,
+                    message: s("x"),
+                }
+                .into(),
+                Code::Internal,
+                "CONTRACT_RPC_ERROR",
+            ),
+            (
+                ContractError::InvalidResponse {
+                    method: s("eth_call"),
+                    reason: s("x"),
+                }
+                .into(),
+                Code::Internal,
+                "CONTRACT_INVALID_RESPONSE",
+            ),
+            (
+                ContractError::TransactionReverted { tx_hash: s("0x00") }.into(),
+                Code::FailedPrecondition,
+                "CONTRACT_TRANSACTION_REVERTED",
+            ),
+            (
+                ConfigError::MissingField { field: s("x") }.into(),
+                Code::FailedPrecondition,
+                "CONFIG_ERROR",
+            ),
+            (
+                tonic::Status::unavailable("x").into(),
+                Code::Internal,
+                "UPSTREAM_ERROR",
+            ),
+            (
+                std::io::Error::other("x").into(),
+                Code::Internal,
+                "IO_ERROR",
+            ),
+            (
+                serde_json::from_str::<u8>("x").unwrap_err().into(),
+                Code::Internal,
+                "SERIALIZATION_ERROR",
+            ),
+            (TappError::Crypto(s("x")), Code::Internal, "CRYPTO_ERROR"),
+            (
+                TappError::InvalidParameter {
+                    field: s("app_id"),
+                    reason: s("x"),
+                },
+                Code::InvalidArgument,
+                "INVALID_PARAMETER",
+            ),
+            (
+                TappError::AppAlreadyExists { app_id: s("x") },
+                Code::AlreadyExists,
+                "APP_ALREADY_EXISTS",
+            ),
+            (
+                TappError::AppNotFound { app_id: s("x") },
+                Code::NotFound,
+                "APP_NOT_FOUND",
+            ),
+            (
+                TappError::TaskNotFound { task_id: s("x") },
+                Code::NotFound,
+                "TASK_NOT_FOUND",
+            ),
+            (
+                TappError::TaskExpired {
+                    task_id: s("x"),
+                    retention_seconds: 3600,
+                },
+                Code::NotFound,
+                "TASK_EXPIRED",
+            ),
+            (
+                TappError::SealedBlobNotFound {
+                    app_id: s("x"),
+                    label: s("token"),
+                },
+                Code::NotFound,
+                "SEALED_BLOB_NOT_FOUND",
+            ),
+            (
+                TappError::SealBindingMismatch {
+                    app_id: s("x"),
+                    label: s("token"),
+                },
+                Code::PermissionDenied,
+                "SEAL_BINDING_MISMATCH",
+            ),
+            (
+                TappError::ClusterState(s("x")),
+                Code::FailedPrecondition,
+                "CLUSTER_STATE_CONFLICT",
+            ),
+            (
+                TappError::ClusterPeerRejected {
+                    peer: s("node-b"),
+                    reason: s("x"),
+                },
+                Code::PermissionDenied,
+                "CLUSTER_PEER_REJECTED",
+            ),
+            (
+                TappError::InvalidBackup(s("x")),
+                Code::InvalidArgument,
+                "INVALID_KEYSTORE_BACKUP",
+            ),
+            (
+                TappError::KeystoreConflict {
+                    keys: vec![s("a"), s("b")],
+                },
+                Code::AlreadyExists,
+                "KEYSTORE_CONFLICT",
+            ),
+            (
+                TappError::PermissionDenied(s("x")),
+                Code::PermissionDenied,
+                "PERMISSION_DENIED",
+            ),
+            (
+                TappError::ServiceUnavailable { service: s("x") },
+                Code::Unavailable,
+                "SERVICE_UNAVAILABLE",
+            ),
+            (
+                TappError::ResourceExhausted(s("x")),
+                Code::ResourceExhausted,
+                "RESOURCE_EXHAUSTED",
+            ),
+            (TappError::Cancelled, Code::Cancelled, "CANCELLED"),
+            (TappError::Internal(s("x")), Code::Internal, "INTERNAL"),
+        ]
+    }
+
+    #[test]
+    fn test_status_codes_and_reasons() {
+        for (err, code, reason) in status_table() {
+            let description = format!("{:?}", err);
+            let status: tonic::Status = err.into();
+            assert_eq!(status.code(), code, "{}", description);
+            let details = status.check_error_details().unwrap();
+            assert_eq!(
+                details.error_info().unwrap().reason,
+                reason,
+                "{}",
+                description
+            );
+        }
+    }
+
+    #[test]
+    fn test_bollard_error_mapping() {
+        use bollard::errors::Error;
+        use std::io::ErrorKind;
+
+        let err = DockerError::from(Error::SocketNotFoundError(
+            "/var/run/docker.sock".to_string(),
+        ));
+        assert_eq!(
+            err.to_string(),
+            "Docker daemon connection failed at /var/run/docker.sock: socket not found"
+        );
+
+        let err = DockerError::from(Error::IOError {
+            err: std::io::Error::new(ErrorKind::PermissionDenied, "Permission denied"),
+        })
+        .at_endpoint("/var/run/docker.sock");
+        match &err {
+            DockerError::ConnectionFailed { endpoint, reason } => {
+                assert_eq!(endpoint, "/var/run/docker.sock");
+                assert!(reason.starts_with("permission denied"), "{}", reason);
+            }
+            other => panic!("unexpected error: {:?}", other),
+        }
+        let status: tonic::Status = TappError::Docker(err).into();
+        assert_eq!(status.code(), tonic::Code::Unavailable);
+        let details = status.check_error_details().unwrap();
+        assert_eq!(
+            details
+                .error_info()
+                .unwrap()
+                .metadata
+                .get("endpoint")
+                .unwrap(),
+            "/var/run/docker.sock"
+        );
+
+        let err = DockerError::from(Error::DockerResponseServerError {
+            status_code: 400,
+            message: "client version 1.47 is too new. Maximum supported API version is 1.41"
+                .to_string(),
+        });
+        assert!(matches!(err, DockerError::ApiVersionMismatch { .. }));
+
+        let err = DockerError::from(Error::DockerResponseServerError {
+            status_code: 404,
+            message: "No such container: abc".to_string(),
+        });
+        assert!(matches!(
+            err,
+            DockerError::DaemonError {
+                status_code: 404,
+                ..
+            }
+        ));
+
+        // An endpoint given by the error itself is kept
+        let err = DockerError::from(Error::SocketNotFoundError("/run/docker.sock".to_string()))
+            .at_endpoint("/var/run/docker.sock");
+        assert!(err.to_string().contains("at /run/docker.sock"));
+    }
+
+    #[test]
+    fn test_already_exists_message() {
+        let status: tonic::Status = TappError::AppAlreadyExists {
+            app_id: "demo".to_string(),
+        }
+        .into();
+        assert_eq!(status.message(), "Application demo already exists");
+        let details = status.check_error_details().unwrap();
+        let metadata = &details.error_info().unwrap().metadata;
+        assert_eq!(metadata.get("app_id").map(String::as_str), Some("demo"));
+    }
+
+    #[test]
+    fn test_throttled_status_retry_info() {
+        let status = throttled_status(
+            "Rate limit exceeded",
+            ErrorReason::RateLimited,
+            HashMap::from([("method".to_string(), "GetEvidence".to_string())]),
+            Duration::from_secs(3),
+        );
+        assert_eq!(status.code(), tonic::Code::ResourceExhausted);
+        assert_eq!(status.metadata().get("retry-after").unwrap(), "3");
+
+        let details = status.check_error_details().unwrap();
+        assert_eq!(details.error_info().unwrap().reason, "RATE_LIMITED");
+        assert_eq!(
+            details.retry_info().unwrap().retry_delay,
+            Some(Duration::from_secs(3))
+        );
     }
 
     #[test]
@@ -0,0 +1,343 @@
+use crate::config::HealthCheckConfig;
+use crate::notifications::{NotificationEvent, Notifier};
+use crate::proto::v2::tapp_service_server::TappServiceServer as TappServiceV2Server;
+use crate::{BootService, TappServiceImpl, TappServiceServer};
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::Duration;
+use tonic::server::NamedService;
+use tonic_health::server::HealthReporter;
+use tonic_health::ServingStatus;
+use tower::{Layer, Service};
+use tracing::{info, warn};
+
+/// Fully qualified name TappService health is reported under
+pub const TAPP_SERVICE_HEALTH_NAME: &str =
+    <TappServiceServer<TappServiceImpl> as NamedService>::NAME;
+
+/// Name of TappService v2, which reports the same status as v1
+pub const TAPP_SERVICE_V2_HEALTH_NAME: &str =
+    <TappServiceV2Server<TappServiceImpl> as NamedService>::NAME;
+
+/// Name of the overall (process) status
+pub const OVERALL_HEALTH_NAME: &str = "";
+
+/// Path prefix of the health service's methods
+const HEALTH_PATH_PREFIX: &str = "/grpc.health.v1.Health/";
+
+/// Consecutive failed checks of the dependencies TappService needs
+#[derive(Debug)]
+pub struct DependencyHealth {
+    docker_threshold: u32,
+    attestation_threshold: u32,
+    docker_failures: u32,
+    attestation_failures: u32,
+}
+
+impl DependencyHealth {
+    pub fn new(config: &HealthCheckConfig) -> Self {
+        Self {
+            docker_threshold: config.docker_failure_threshold.max(1),
+            attestation_threshold: config.attestation_failure_threshold.max(1),
+            docker_failures: 0,
+            attestation_failures: 0,
+        }
+    }
+
+    /// Record one round of checks and return the resulting TappService status
+    /// A successful check resets its failure count, so the status recovers
+    /// as soon as the dependency does.
+    pub fn record(&mut self, docker_ok: bool, attestation_ok: bool) -> ServingStatus {
+        self.docker_failures = if docker_ok {
+            0
+        } else {
+            self.docker_failures.saturating_add(1)
+        };
+        self.attestation_failures = if attestation_ok {
+            0
+        } else {
+            self.attestation_failures.saturating_add(1)
+        };
+        self.status()
+    }
+
+    pub fn status(&self) -> ServingStatus {
+        if self.docker_failures >= self.docker_threshold
+            || self.attestation_failures >= self.attestation_threshold
+        {
+            ServingStatus::NotServing
+        } else {
+            ServingStatus::Serving
+        }
+    }
+}
+
+/// Report every service NOT_SERVING while the server is starting
+pub async fn report_starting(reporter: &mut HealthReporter) {
+    for name in [
+        OVERALL_HEALTH_NAME,
+        TAPP_SERVICE_HEALTH_NAME,
+        TAPP_SERVICE_V2_HEALTH_NAME,
+    ] {
+        reporter
+            .set_service_status(name, ServingStatus::NotServing)
+            .await;
+    }
+}
+
+/// Keeps the health statuses in line with the service's dependencies
+/// The overall status turns SERVING once the service is initialized; the
+/// TappService status follows the Docker daemon and attestation agent checks.
+pub struct HealthMonitor {
+    reporter: HealthReporter,
+    interval: Duration,
+    dependencies: DependencyHealth,
+    current: ServingStatus,
+    notifier: Notifier,
+}
+
+impl HealthMonitor {
+    pub fn new(reporter: HealthReporter, config: &HealthCheckConfig) -> Self {
+        Self {
+            reporter,
+            interval: Duration::from_secs(config.check_interval_seconds.max(1)),
+            dependencies: DependencyHealth::new(config),
+            current: ServingStatus::NotServing,
+            notifier: Notifier::disabled(),
+        }
+    }
+
+    /// Report attestation failures through `notifier`
+    pub fn with_notifier(mut self, notifier: Notifier) -> Self {
+        self.notifier = notifier;
+        self
+    }
+
+    /// Apply the result of one round of checks, logging status changes
+    pub async fn update(&mut self, docker_ok: bool, attestation_ok: bool) -> ServingStatus {
+        let status = self.dependencies.record(docker_ok, attestation_ok);
+        if status != self.current {
+            match status {
+                ServingStatus::Serving => info!(
+                    service = TAPP_SERVICE_HEALTH_NAME,
+                    event = "HEALTH_SERVING",
+                    "Service dependencies healthy, reporting SERVING"
+                ),
+                _ => warn!(
+                    service = TAPP_SERVICE_HEALTH_NAME,
+                    docker_failures = self.dependencies.docker_failures,
+                    attestation_failures = self.dependencies.attestation_failures,
+                    event = "HEALTH_NOT_SERVING",
+                    "Service dependencies failing, reporting NOT_SERVING"
+                ),
+            }
+            for name in [TAPP_SERVICE_HEALTH_NAME, TAPP_SERVICE_V2_HEALTH_NAME] {
+                self.reporter.set_service_status(name, status).await;
+            }
+            self.current = status;
+        }
+        status
+    }
+
+    /// Mark the service initialized and check its dependencies until the
+    /// process exits; the first round runs immediately
+    pub async fn run(mut self, boot_service: Arc<BootService>) {
+        self.reporter
+            .set_service_status(OVERALL_HEALTH_NAME, ServingStatus::Serving)
+            .await;
+
+        let mut ticker = tokio::time::interval(self.interval);
+        ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        loop {
+            ticker.tick().await;
+
+            let docker = boot_service.check_docker().await;
+            if let Err(e) = &docker {
+                warn!(error = %e, "Health check: Docker daemon unreachable");
+            }
+            let attestation = boot_service.check_attestation().await;
+            if let Err(e) = &attestation {
+                warn!(error = %e, "Health check: attestation agent failed");
+            }
+
+            self.update(docker.is_ok(), attestation.is_ok()).await;
+
+            // Once per run of failures: when it reaches the threshold
+            let failures = self.dependencies.attestation_failures;
+            if let Err(e) = attestation {
+                if failures == self.dependencies.attestation_threshold {
+                    self.notifier.notify(NotificationEvent::AttestationFailed {
+                        consecutive_failures: failures,
+                        error: e.to_string(),
+                    });
+                }
+            }
+        }
+    }
+}
+
+/// Layer applying `layer` to every service except the health service
+/// Health checks come from orchestrators and load balancers, which hold no
+/// API key and may not be in the IP allow list, so they bypass the
+/// authentication, filtering, rate limiting and audit layers.
+#[derive(Clone)]
+pub struct HealthBypassLayer<L> {
+    layer: L,
+}
+
+impl<L> HealthBypassLayer<L> {
+    pub fn new(layer: L) -> Self {
+        Self { layer }
+    }
+}
+
+impl<S: Clone, L: Layer<S>> Layer<S> for HealthBypassLayer<L> {
+    type Service = HealthBypass<L::Service, S>;
+
+    fn layer(&self, service: S) -> Self::Service {
+        HealthBypass {
+            layered: self.layer.layer(service.clone()),
+            direct: service,
+        }
+    }
+}
+
+/// Routes health checks around the wrapped layers
+#[derive(Clone)]
+pub struct HealthBypass<A, S> {
+    layered: A,
+    direct: S,
+}
+
+impl<A, S, ReqBody> Service<http::Request<ReqBody>> for HealthBypass<A, S>
+where
+    A: Service<http::Request<ReqBody>>,
+    S: Service<http::Request<ReqBody>, Response = A::Response, Error = A::Error>,
+{
+    type Response = A::Response;
+    type Error = A::Error;
+    type Future = futures_util::future::Either<A::Future, S::Future>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        match self.layered.poll_ready(cx)? {
+            Poll::Ready(()) => self.direct.poll_ready(cx),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+
+    fn call(&mut self, req: http::Request<ReqBody>) -> Self::Future {
+        use futures_util::future::Either;
+
+        if req.uri().path().starts_with(HEALTH_PATH_PREFIX) {
+            Either::Right(self.direct.call(req))
+        } else {
+            Either::Left(self.layered.call(req))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio_stream::wrappers::TcpListenerStream;
+    use tonic_health::pb::health_check_response::ServingStatus as ProtoServingStatus;
+    use tonic_health::pb::health_client::HealthClient;
+    use tonic_health::pb::HealthCheckRequest;
+
+    fn config(threshold: u32) -> HealthCheckConfig {
+        HealthCheckConfig {
+            check_interval_seconds: 1,
+            docker_failure_threshold: threshold,
+            attestation_failure_threshold: threshold,
+        }
+    }
+
+    #[test]
+    fn test_dependency_thresholds_and_recovery() {
+        let mut health = DependencyHealth::new(&config(2));
+        assert_eq!(health.record(true, true), ServingStatus::Serving);
+
+        // One failure is tolerated, the second in a row is not
+        assert_eq!(health.record(false, true), ServingStatus::Serving);
+        assert_eq!(health.record(false, true), ServingStatus::NotServing);
+
+        // Recovers as soon as the dependency answers again
+        assert_eq!(health.record(true, true), ServingStatus::Serving);
+
+        // Interleaved successes keep the attestation count below the threshold
+        assert_eq!(health.record(true, false), ServingStatus::Serving);
+        assert_eq!(health.record(true, true), ServingStatus::Serving);
+        assert_eq!(health.record(true, false), ServingStatus::Serving);
+        assert_eq!(health.record(true, false), ServingStatus::NotServing);
+    }
+
+    async fn check(client: &mut HealthClient<tonic::transport::Channel>, service: &str) -> i32 {
+        client
+            .check(HealthCheckRequest {
+                service: service.to_string(),
+            })
+            .await
+            .unwrap()
+            .into_inner()
+            .status
+    }
+
+    #[tokio::test]
+    async fn test_health_service_reports_transitions() {
+        let (mut reporter, health_service) = tonic_health::server::health_reporter();
+        report_starting(&mut reporter).await;
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(
+            tonic::transport::Server::builder()
+                .add_service(health_service)
+                .serve_with_incoming(TcpListenerStream::new(listener)),
+        );
+
+        let mut client = HealthClient::connect(format!("http://{}", addr))
+            .await
+            .unwrap();
+        for name in [OVERALL_HEALTH_NAME, TAPP_SERVICE_HEALTH_NAME] {
+            assert_eq!(
+                check(&mut client, name).await,
+                ProtoServingStatus::NotServing as i32
+            );
+        }
+        assert_eq!(TAPP_SERVICE_HEALTH_NAME, "tapp_service.TappService");
+        assert_eq!(TAPP_SERVICE_V2_HEALTH_NAME, "tapp_service.v2.TappService");
+
+        let mut monitor = HealthMonitor::new(reporter.clone(), &config(2));
+        reporter
+            .set_service_status(OVERALL_HEALTH_NAME, ServingStatus::Serving)
+            .await;
+        monitor.update(true, true).await;
+        assert_eq!(
+            check(&mut client, OVERALL_HEALTH_NAME).await,
+            ProtoServingStatus::Serving as i32
+        );
+        assert_eq!(
+            check(&mut client, TAPP_SERVICE_HEALTH_NAME).await,
+            ProtoServingStatus::Serving as i32
+        );
+
+        // Docker lost: NOT_SERVING once the threshold is reached, then back
+        monitor.update(false, true).await;
+        monitor.update(false, true).await;
+        for name in [TAPP_SERVICE_HEALTH_NAME, TAPP_SERVICE_V2_HEALTH_NAME] {
+            assert_eq!(
+                check(&mut client, name).await,
+                ProtoServingStatus::NotServing as i32
+            );
+        }
+        assert_eq!(
+            check(&mut client, OVERALL_HEALTH_NAME).await,
+            ProtoServingStatus::Serving as i32
+        );
+        monitor.update(true, true).await;
+        assert_eq!(
+            check(&mut client, TAPP_SERVICE_HEALTH_NAME).await,
+            ProtoServingStatus::Serving as i32
+        );
+    }
+}
@@ -0,0 +1,314 @@
+use crate::config::IpFilterConfig;
+use crate::error::ConfigError;
+use std::net::IpAddr;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, RwLock};
+use std::task::{Context, Poll};
+use tonic::body::BoxBody;
+use tonic::Status;
+use tower::{Layer, Service};
+use tracing::debug;
+
+/// An IPv4 or IPv6 network in CIDR notation
+/// A bare address is a single-host network (/32 or /128)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IpNetwork {
+    addr: IpAddr,
+    prefix_len: u8,
+}
+
+impl IpNetwork {
+    /// Check whether an address belongs to this network
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        match (self.addr, ip) {
+            (IpAddr::V4(net), IpAddr::V4(ip)) => {
+                let mask = u32::MAX
+                    .checked_shl(32 - self.prefix_len as u32)
+                    .unwrap_or(0);
+                u32::from(net) & mask == u32::from(ip) & mask
+            }
+            (IpAddr::V6(net), IpAddr::V6(ip)) => {
+                let mask = u128::MAX
+                    .checked_shl(128 - self.prefix_len as u32)
+                    .unwrap_or(0);
+                u128::from(net) & mask == u128::from(ip) & mask
+            }
+            _ => false,
+        }
+    }
+}
+
+impl FromStr for IpNetwork {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (addr, prefix_len) = match s.split_once('/') {
+            Some((addr, prefix_len)) => (addr, Some(prefix_len)),
+            None => (s, None),
+        };
+
+        let addr: IpAddr = addr
+            .trim()
+            .parse()
+            .map_err(|e| format!("invalid address in '{}': {}", s, e))?;
+        let max_prefix_len = if addr.is_ipv4() { 32 } else { 128 };
+
+        let prefix_len = match prefix_len {
+            Some(prefix_len) => prefix_len
+                .trim()
+                .parse::<u8>()
+                .ok()
+                .filter(|p| *p <= max_prefix_len)
+                .ok_or_else(|| format!("invalid prefix length in '{}'", s))?,
+            None => max_prefix_len,
+        };
+
+        Ok(Self { addr, prefix_len })
+    }
+}
+
+/// Parsed allow and deny lists
+struct IpFilterRules {
+    allow: Vec<IpNetwork>,
+    deny: Vec<IpNetwork>,
+}
+
+impl IpFilterRules {
+    fn from_config(config: &IpFilterConfig) -> Result<Self, ConfigError> {
+        Ok(Self {
+            allow: parse_networks("server.ip_filter.allow", &config.allow)?,
+            deny: parse_networks("server.ip_filter.deny", &config.deny)?,
+        })
+    }
+}
+
+/// Parse a list of CIDR strings, naming the config field on error
+pub fn parse_networks(field: &str, entries: &[String]) -> Result<Vec<IpNetwork>, ConfigError> {
+    entries
+        .iter()
+        .map(|entry| {
+            entry.parse().map_err(|reason| ConfigError::InvalidValue {
+                field: field.to_string(),
+                reason,
+            })
+        })
+        .collect()
+}
+
+/// Shared source address filter state
+pub struct IpFilter {
+    rules: RwLock<Option<IpFilterRules>>,
+    dropped_total: AtomicU64,
+}
+
+impl IpFilter {
+    pub fn new(config: Option<IpFilterConfig>) -> Result<Self, ConfigError> {
+        Ok(Self {
+            rules: RwLock::new(Self::compile(config.as_ref())?),
+            dropped_total: AtomicU64::new(0),
+        })
+    }
+
+    fn compile(config: Option<&IpFilterConfig>) -> Result<Option<IpFilterRules>, ConfigError> {
+        config
+            .filter(|c| c.enabled)
+            .map(IpFilterRules::from_config)
+            .transpose()
+    }
+
+    /// Replace the active allow and deny lists (used by hot reload)
+    /// Nothing changes if any entry fails to parse
+    pub fn update_config(&self, config: Option<IpFilterConfig>) -> Result<(), ConfigError> {
+        let rules = Self::compile(config.as_ref())?;
+        *self.rules.write().unwrap() = rules;
+        Ok(())
+    }
+
+    /// Check whether a source address may reach the service
+    /// Deny wins over allow; an empty allow list allows every source not denied
+    pub fn is_allowed(&self, ip: IpAddr) -> bool {
+        let rules = self.rules.read().unwrap();
+        let Some(rules) = rules.as_ref() else {
+            return true;
+        };
+
+        // Match IPv4-mapped IPv6 sources (::ffff:a.b.c.d) against IPv4 rules
+        let ip = ip.to_canonical();
+
+        let allowed = !rules.deny.iter().any(|net| net.contains(ip))
+            && (rules.allow.is_empty() || rules.allow.iter().any(|net| net.contains(ip)));
+
+        if !allowed {
+            self.dropped_total.fetch_add(1, Ordering::Relaxed);
+        }
+        allowed
+    }
+
+    /// Number of requests rejected since startup
+    pub fn dropped_total(&self) -> u64 {
+        self.dropped_total.load(Ordering::Relaxed)
+    }
+}
+
+/// Tower Layer filtering requests by source address
+/// Applies to TCP connections only; Unix socket requests carry no source
+/// address and always pass.
+#[derive(Clone)]
+pub struct IpFilterLayer {
+    filter: Arc<IpFilter>,
+}
+
+impl IpFilterLayer {
+    pub fn new(filter: Arc<IpFilter>) -> Self {
+        Self { filter }
+    }
+}
+
+impl<S> Layer<S> for IpFilterLayer {
+    type Service = IpFilterMiddleware<S>;
+
+    fn layer(&self, service: S) -> Self::Service {
+        IpFilterMiddleware {
+            inner: service,
+            filter: self.filter.clone(),
+        }
+    }
+}
+
+/// Middleware that rejects requests from sources outside the allow list
+#[derive(Clone)]
+pub struct IpFilterMiddleware<S> {
+    inner: S,
+    filter: Arc<IpFilter>,
+}
+
+impl<S> Service<http::Request<BoxBody>> for IpFilterMiddleware<S>
+where
+    S: Service<http::Request<BoxBody>, Response = http::Response<BoxBody>>
+        + Clone
+        + Send
+        + 'static,
+    S::Error: Into<Box<dyn std::error::Error + Send + Sync>> + Send,
+    S::Future: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = futures_util::future::BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: http::Request<BoxBody>) -> Self::Future {
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+        let filter = self.filter.clone();
+
+        Box::pin(async move {
+            if let Some(ip) = crate::utils::remote_ip(&req) {
+                if !filter.is_allowed(ip) {
+                    debug!(
+                        method = %crate::utils::grpc_method_name(req.uri().path()),
+                        remote_addr = %ip,
+                        event = "IP_FILTER_DENIED",
+                        "Request from blocked source address"
+                    );
+                    return Ok(Status::permission_denied("Source address not allowed").into_http());
+                }
+            }
+
+            inner.call(req).await
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(allow: &[&str], deny: &[&str]) -> IpFilterConfig {
+        IpFilterConfig {
+            enabled: true,
+            allow: allow.iter().map(|s| s.to_string()).collect(),
+            deny: deny.iter().map(|s| s.to_string()).collect(),
+        }
+    }
+
+    fn ip(s: &str) -> IpAddr {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn test_network_contains() {
+        let net: IpNetwork = "10.1.0.0/16".parse().unwrap();
+        assert!(net.contains(ip("10.1.255.3")));
+        assert!(!net.contains(ip("10.2.0.1")));
+        assert!(!net.contains(ip("::1")));
+
+        let host: IpNetwork = "192.168.1.7".parse().unwrap();
+        assert!(host.contains(ip("192.168.1.7")));
+        assert!(!host.contains(ip("192.168.1.8")));
+
+        let any: IpNetwork = "0.0.0.0/0".parse().unwrap();
+        assert!(any.contains(ip("8.8.8.8")));
+
+        let v6: IpNetwork = "2001:db8::/32".parse().unwrap();
+        assert!(v6.contains(ip("2001:db8:1::42")));
+        assert!(!v6.contains(ip("2001:db9::1")));
+
+        assert!("10.0.0.0/33".parse::<IpNetwork>().is_err());
+        assert!("2001:db8::/129".parse::<IpNetwork>().is_err());
+        assert!("not-an-ip/8".parse::<IpNetwork>().is_err());
+    }
+
+    #[test]
+    fn test_deny_wins_over_allow() {
+        let filter = IpFilter::new(Some(config(&["10.0.0.0/8"], &["10.0.0.5"]))).unwrap();
+
+        assert!(filter.is_allowed(ip("10.3.4.5")));
+        assert!(!filter.is_allowed(ip("10.0.0.5")));
+        assert!(!filter.is_allowed(ip("172.16.0.1")));
+        assert_eq!(filter.dropped_total(), 2);
+    }
+
+    #[test]
+    fn test_empty_allow_list_and_ipv6() {
+        let filter =
+            IpFilter::new(Some(config(&[], &["2001:db8::/32", "203.0.113.0/24"]))).unwrap();
+
+        assert!(filter.is_allowed(ip("198.51.100.1")));
+        assert!(!filter.is_allowed(ip("2001:db8::1")));
+        assert!(filter.is_allowed(ip("2001:db9::1")));
+        // IPv4-mapped IPv6 sources match IPv4 rules
+        assert!(!filter.is_allowed(ip("::ffff:203.0.113.9")));
+    }
+
+    #[test]
+    fn test_disabled_allows_all() {
+        let filter = IpFilter::new(None).unwrap();
+        assert!(filter.is_allowed(ip("203.0.113.9")));
+
+        let mut disabled = config(&["10.0.0.0/8"], &[]);
+        disabled.enabled = false;
+        let filter = IpFilter::new(Some(disabled)).unwrap();
+        assert!(filter.is_allowed(ip("203.0.113.9")));
+    }
+
+    #[test]
+    fn test_update_config() {
+        let filter = IpFilter::new(Some(config(&["10.0.0.0/8"], &[]))).unwrap();
+        assert!(!filter.is_allowed(ip("192.168.0.1")));
+
+        // Invalid lists are rejected and the previous rules stay active
+        assert!(filter
+            .update_config(Some(config(&["192.168.0.0/40"], &[])))
+            .is_err());
+        assert!(!filter.is_allowed(ip("192.168.0.1")));
+
+        filter
+            .update_config(Some(config(&["192.168.0.0/16"], &[])))
+            .unwrap();
+        assert!(filter.is_allowed(ip("192.168.0.1")));
+    }
+}
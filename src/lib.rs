@@ -1,21 +1,65 @@
 pub mod app_key;
+pub mod attestor_auth_layer;
+pub mod audit_layer;
 pub mod auth_layer;
+pub mod auth_lockout;
 pub mod boot;
+#[cfg(feature = "client")]
+pub mod client;
+pub mod cluster;
 pub mod config;
+pub mod config_template;
+pub mod container_identity;
+pub mod contract;
 pub mod error;
+pub mod health;
+pub mod ip_filter_layer;
+pub mod listeners;
+pub mod log_level;
+pub mod method_filter_layer;
+pub mod namespace;
 pub mod nonce_manager;
+pub mod notifications;
+pub mod panic_layer;
+pub mod rate_limit_layer;
+pub mod reflection;
+pub mod reload;
+pub mod request_id_layer;
+pub mod rolling_file;
 pub mod service_monitor;
+pub mod service_v2;
+pub mod telemetry;
+pub mod timeout_layer;
 pub mod utils;
+pub mod web_layer;
+use app_key::deployer::DeployerIdentity;
+use audit_layer::AuditAnnotation;
+use auth_layer::AuthIdentity;
+use boot::measurement::AppMeasurement;
 pub use boot::BootService;
+use config::ApiKeyRole;
 pub use config::TappConfig;
 pub use error::{TappError, TappResult};
+use namespace::AppScope;
+use request_id_layer::RequestId;
 use std::sync::Arc;
+use tokio_util::sync::{CancellationToken, DropGuard};
 use tonic::{Request, Response, Status};
 use tracing::info;
 
 // Re-export generated protobuf types
 pub mod proto {
     tonic::include_proto!("tapp_service");
+
+    /// Version 2 of the API (tapp_service_v2.proto), implemented in `service_v2`
+    pub mod v2 {
+        tonic::include_proto!("tapp_service.v2");
+    }
+
+    /// Encoded descriptors of tapp_service.proto and tapp_service_v2.proto,
+    /// served by gRPC reflection
+    pub const FILE_DESCRIPTOR_SET: &[u8] =
+        tonic::include_file_descriptor_set!("tapp_service_descriptor");
 }
 
 // // Re-export common types
@@ -32,571 +76,5334 @@ pub const NAME: &str = env!("CARGO_PKG_NAME");
 pub struct TappServiceImpl {
     pub config: TappConfig,
     pub boot_service: Arc<BootService>,
-    pub app_key_service: app_key::AppKeyService,
-    pub nonce_manager: nonce_manager::NonceManager,
+    pub app_key_service: Arc<app_key::AppKeyService>,
+    pub nonce_manager: Arc<nonce_manager::NonceManager>,
     pub logs_service: service_monitor::logs::LogsService,
+    pub host_monitor: service_monitor::system_info::HostMonitor,
+    /// Webhook notifications of app and service events
+    pub notifier: notifications::Notifier,
+    pub container_resolver: container_identity::ContainerResolver,
+    /// Networks of same-host containers (`boot.container_networks`)
+    pub container_networks: Vec<ip_filter_layer::IpNetwork>,
+    /// Active API key configuration, shared with the ApiKeyLayer and swapped on reload
+    pub api_key_config: auth_layer::SharedApiKeyConfig,
+    /// Runtime log filter control; SetLogLevel is unavailable without it
+    pub log_level: Option<log_level::LogLevelControl>,
+    /// Blobs kept with SealData
+    pub sealed_data: app_key::sealed::SealedDataStore,
+    /// Key cluster membership (InitCluster, JoinCluster)
+    pub cluster: cluster::ClusterManager,
+    /// Reads the evidence of the other side of a key share exchange
+    pub cluster_verifier: Box<dyn cluster::verifier::EvidenceVerifier>,
+    /// Nodes that may take part in key share exchanges
+    pub cluster_whitelist: Box<dyn cluster::verifier::Whitelist>,
+    /// Checks of app key callers against the tapp contract, shared with the
+    /// AttestorAuthLayer; `None` unless `contract.attestor_auth` is enabled
+    pub attestor_auth: Option<Arc<attestor_auth_layer::AttestorAuth>>,
 }
 
-impl TappServiceImpl {
-    /// Check if an IP address is allowed to access sensitive operations
-    /// Allows: localhost (IPv4/IPv6) and Docker bridge networks
-    fn is_allowed_local_access(ip: std::net::IpAddr) -> bool {
-        // 1. Check if it's loopback
-        if ip.is_loopback() {
-            return true;
+/// Operation a deployer signature authorizes
+/// The action tag is part of the signed message so a signature for one
+/// operation cannot be replayed against another.
+enum DeployerAction<'a> {
+    GetSecretKey,
+    StopApp,
+    /// The signature also binds the new owner's public key
+    TransferOwnership {
+        new_deployer: &'a [u8],
+    },
+    /// Signed by the deployer that started the task
+    CancelTask {
+        task_id: &'a str,
+    },
+    /// Signed by the deployer that started the task
+    RetryTask {
+        task_id: &'a str,
+    },
+    /// One of the sealed data RPCs, on the blob stored under `label`
+    SealedData {
+        operation: &'static str,
+        label: &'a str,
+    },
+    /// The signature also binds the transaction's signing hash
+    SignTransaction {
+        signing_hash: [u8; 32],
+    },
+}
+
+impl DeployerAction<'_> {
+    fn message_tag(&self) -> Vec<u8> {
+        match self {
+            DeployerAction::GetSecretKey => Vec::new(),
+            DeployerAction::StopApp => b"stop".to_vec(),
+            DeployerAction::TransferOwnership { new_deployer } => new_deployer.to_vec(),
+            DeployerAction::CancelTask { task_id } => format!("cancel:{}", task_id).into_bytes(),
+            DeployerAction::RetryTask { task_id } => format!("retry:{}", task_id).into_bytes(),
+            DeployerAction::SealedData { operation, label } => {
+                format!("{}:{}", operation, label).into_bytes()
+            }
+            DeployerAction::SignTransaction { signing_hash } => signing_hash.to_vec(),
         }
+    }
 
-        // 2. Check if it's in Docker network ranges
-        if let std::net::IpAddr::V4(ipv4) = ip {
-            let octets = ipv4.octets();
+    fn denied_event(&self) -> &'static str {
+        match self {
+            DeployerAction::GetSecretKey => "SECRET_KEY_ACCESS_DENIED",
+            DeployerAction::StopApp => "STOP_APP_DENIED",
+            DeployerAction::TransferOwnership { .. } => "OWNERSHIP_TRANSFER_DENIED",
+            DeployerAction::CancelTask { .. } => "CANCEL_TASK_DENIED",
+            DeployerAction::RetryTask { .. } => "RETRY_TASK_DENIED",
+            DeployerAction::SealedData { .. } => "SEALED_DATA_DENIED",
+            DeployerAction::SignTransaction { .. } => "SIGN_TRANSACTION_DENIED",
+        }
+    }
 
-            // Docker default bridge network: 172.17.0.0/16
-            if octets[0] == 172 && octets[1] == 17 {
-                return true;
-            }
+    fn admin_override_event(&self) -> &'static str {
+        match self {
+            DeployerAction::GetSecretKey => "SECRET_KEY_ADMIN_OVERRIDE",
+            DeployerAction::StopApp => "STOP_APP_ADMIN_OVERRIDE",
+            DeployerAction::TransferOwnership { .. } => "OWNERSHIP_TRANSFER_ADMIN_OVERRIDE",
+            DeployerAction::CancelTask { .. } => "CANCEL_TASK_ADMIN_OVERRIDE",
+            DeployerAction::RetryTask { .. } => "RETRY_TASK_ADMIN_OVERRIDE",
+            DeployerAction::SealedData { .. } => "SEALED_DATA_ADMIN_OVERRIDE",
+            DeployerAction::SignTransaction { .. } => "SIGN_TRANSACTION_ADMIN_OVERRIDE",
+        }
+    }
 
-            // Docker custom bridge networks: 172.18.0.0/16 - 172.31.0.0/16
-            if octets[0] == 172 && octets[1] >= 18 && octets[1] <= 31 {
-                return true;
-            }
+    fn authorized_event(&self) -> &'static str {
+        match self {
+            DeployerAction::GetSecretKey => "SECRET_KEY_ACCESS_AUTHORIZED",
+            DeployerAction::StopApp => "STOP_APP_AUTHORIZED",
+            DeployerAction::TransferOwnership { .. } => "OWNERSHIP_TRANSFER_AUTHORIZED",
+            DeployerAction::CancelTask { .. } => "CANCEL_TASK_AUTHORIZED",
+            DeployerAction::RetryTask { .. } => "RETRY_TASK_AUTHORIZED",
+            DeployerAction::SealedData { .. } => "SEALED_DATA_AUTHORIZED",
+            DeployerAction::SignTransaction { .. } => "SIGN_TRANSACTION_AUTHORIZED",
         }
+    }
 
-        false
+    /// Whether the request releases or uses the app key, so that only v2
+    /// deployer signatures are accepted for it
+    fn requires_v2(&self) -> bool {
+        matches!(
+            self,
+            DeployerAction::GetSecretKey | DeployerAction::SignTransaction { .. }
+        )
     }
 
-    /// Determine the source type for logging
-    fn get_source_type(ip: std::net::IpAddr) -> &'static str {
-        if ip.is_loopback() {
-            "localhost"
-        } else if let std::net::IpAddr::V4(ipv4) = ip {
-            let octets = ipv4.octets();
-            if octets[0] == 172 && (octets[1] >= 17 && octets[1] <= 31) {
-                "docker-network"
-            } else {
-                "unknown"
-            }
-        } else {
-            "unknown"
+    /// Operation name the deployer's timestamp ordering is tracked under
+    /// It is also part of v2 signed messages, so it must not change.
+    fn operation(&self) -> &'static str {
+        match self {
+            DeployerAction::GetSecretKey => "get_secret_key",
+            DeployerAction::StopApp => "stop_app",
+            DeployerAction::TransferOwnership { .. } => "transfer_ownership",
+            DeployerAction::CancelTask { .. } => "cancel_task",
+            DeployerAction::RetryTask { .. } => "retry_task",
+            DeployerAction::SealedData { operation, .. } => operation,
+            DeployerAction::SignTransaction { .. } => "sign_transaction",
         }
     }
 
-    pub async fn new(config: TappConfig) -> TappResult<Self> {
-        info!("Initializing TAPP service components");
-        let boot_service = Arc::new(BootService::new(&config.boot).await?);
+    fn description(&self) -> &'static str {
+        match self {
+            DeployerAction::GetSecretKey => "access the private key",
+            DeployerAction::StopApp => "stop the application",
+            DeployerAction::TransferOwnership { .. } => "transfer ownership",
+            DeployerAction::CancelTask { .. } => "cancel the task",
+            DeployerAction::RetryTask { .. } => "retry the task",
+            DeployerAction::SealedData { .. } => "use sealed data",
+            DeployerAction::SignTransaction { .. } => "sign transactions with the app key",
+        }
+    }
+}
 
-        // Initialize AppKeyService
-        // If KBS config is not provided, use in-memory mode
-        let (kbs_config, use_in_memory) = if let Some(ref kbs) = config.kbs {
-            (kbs.clone(), false)
-        } else {
-            info!("KBS config not provided, using in-memory key generation");
-            (config::KbsConfig::default(), true)
-        };
+/// GetTaskStatus and WatchTask message describing a task to a caller in `scope`
+fn task_status_response(
+    boot_service: &BootService,
+    scope: &AppScope,
+    task: &boot::Task,
+) -> GetTaskStatusResponse {
+    GetTaskStatusResponse {
+        success: true,
+        message: "Task found".to_string(),
+        task_id: task.id.clone(),
+        status: task.to_proto_status() as i32,
+        result: task.to_proto_result(),
+        created_at: task.created_at,
+        updated_at: task.updated_at,
+        stage: task.stage_name().to_string(),
+        progress_percent: task.progress_percent,
+        app_id: scope.localize(&task.app_id).unwrap_or_default().to_string(),
+        kind: task.kind.to_proto() as i32,
+        retried_from: task.retried_from.clone().unwrap_or_default(),
+        queue_position: boot_service
+            .queue_position(&task.id)
+            .map_or(0, |position| position as i32),
+        image_scans: task
+            .image_scans
+            .iter()
+            .map(|scan| scan.to_proto())
+            .collect(),
+    }
+}
 
-        let app_key_service = app_key::AppKeyService::new(&kbs_config, use_in_memory).await?;
+/// Token for the docker commands of one RPC, cancelled when the guard drops
+/// Tonic drops a handler's future when the client's deadline passes or it
+/// disconnects; a handler holding the guard takes its commands down with it.
+fn rpc_cancellation() -> (CancellationToken, DropGuard) {
+    let cancel = CancellationToken::new();
+    let guard = cancel.clone().drop_guard();
+    (cancel, guard)
+}
 
-        // Initialize NonceManager for replay attack prevention
-        let nonce_manager = nonce_manager::NonceManager::new();
+/// Seals a share to the node collecting it and attests the response
+/// The sealed share and evidence are kept for the RequestKeyShare response;
+/// when either step fails, the share stays pending.
+struct AttestedShareDelivery<'a> {
+    boot_service: &'a BootService,
+    node_key: &'a cluster::exchange::NodeKey,
+    requester_key: &'a [u8],
+    request_report_data: &'a [u8],
+    /// Share index, sealed share and evidence once delivered
+    delivered: std::sync::Mutex<Option<(u8, Vec<u8>, Vec<u8>)>>,
+}
 
-        // Initialize LogsService
-        let logs_service =
-            service_monitor::logs::LogsService::new(config.logging.file_path.clone());
+impl cluster::ShareDelivery for AttestedShareDelivery<'_> {
+    async fn deliver(
+        &self,
+        peer: &str,
+        cluster_id: &str,
+        share: &cluster::sss::Share,
+    ) -> TappResult<()> {
+        let sealed_share =
+            cluster::exchange::seal_share(self.requester_key, cluster_id, peer, share)?;
+        let report_data = cluster::exchange::response_report_data(
+            self.request_report_data,
+            self.node_key.public_key(),
+            &sealed_share,
+        );
+        let evidence = self
+            .boot_service
+            .get_evidence(GetEvidenceRequest { report_data })
+            .await?;
+        *self.delivered.lock().unwrap() = Some((share.index, sealed_share, evidence.evidence));
+        Ok(())
+    }
+}
 
-        info!("All TAPP service components initialized successfully");
+/// Time limit of connecting to another cluster node
+const CLUSTER_CONNECT_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(10);
 
-        Ok(Self {
-            boot_service,
-            app_key_service,
-            nonce_manager,
-            logs_service,
-            config,
-        })
+/// Time limit of a call to another cluster node
+const CLUSTER_CALL_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(30);
+
+/// Time limit of RecoverShare, which waits for the coordinator to collect
+/// shares from other members
+const CLUSTER_RECOVERY_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(120);
+
+/// Endpoint of another cluster node at `url`, an http:// or https:// URL
+/// given in `field`
+fn cluster_endpoint(
+    field: &str,
+    url: &str,
+    timeout: std::time::Duration,
+) -> TappResult<tonic::transport::Endpoint> {
+    let invalid = |reason: String| TappError::InvalidParameter {
+        field: field.to_string(),
+        reason,
+    };
+    if !url.starts_with("http://") && !url.starts_with("https://") {
+        return Err(invalid(format!(
+            "{} is not an http:// or https:// URL",
+            url
+        )));
+    }
+    let endpoint = tonic::transport::Endpoint::from_shared(url.to_string())
+        .map_err(|e| invalid(format!("{}: {}", url, e)))?
+        .connect_timeout(CLUSTER_CONNECT_TIMEOUT)
+        .timeout(timeout);
+    if url.starts_with("https://") {
+        return endpoint
+            .tls_config(tonic::transport::ClientTlsConfig::new().with_native_roots())
+            .map_err(|e| invalid(format!("{}: {}", url, e)));
     }
+    Ok(endpoint)
 }
 
-#[tonic::async_trait]
-impl TappService for TappServiceImpl {
-    /// Get attestation evidence from TEE platform
-    async fn get_evidence(
-        &self,
-        request: Request<GetEvidenceRequest>,
-    ) -> Result<Response<GetEvidenceResponse>, Status> {
-        let req = request.into_inner();
-        let evidence = self.boot_service.get_evidence(req).await?;
-        Ok(Response::new(evidence))
+/// Connect to the cluster node at `endpoint`
+async fn connect_cluster_node(
+    endpoint: &tonic::transport::Endpoint,
+) -> TappResult<TappServiceClient<tonic::transport::Channel>> {
+    let channel = endpoint.connect().await.map_err(|e| {
+        tracing::warn!(
+            endpoint = %endpoint.uri(),
+            error = %e,
+            "Failed to connect to a cluster node"
+        );
+        TappError::ServiceUnavailable {
+            service: endpoint.uri().to_string(),
+        }
+    })?;
+    Ok(TappServiceClient::new(channel))
+}
+
+/// Share count or index sent as `field`
+fn share_count(field: &str, value: u32) -> TappResult<u8> {
+    u8::try_from(value).map_err(|_| TappError::InvalidParameter {
+        field: field.to_string(),
+        reason: format!("must be at most {}", cluster::sss::MAX_SHARES),
+    })
+}
+
+/// Transaction of a SignTransaction request
+fn unsigned_transaction(req: &SignTransactionRequest) -> TappResult<contract::tx::Transaction> {
+    use contract::tx::{AccessListItem, Eip1559Transaction, LegacyTransaction, Transaction};
+
+    let invalid = |field: &str, reason: &str| TappError::InvalidParameter {
+        field: field.to_string(),
+        reason: reason.to_string(),
+    };
+    if req.chain_id == 0 {
+        return Err(invalid("chain_id", "must be set"));
+    }
+    let to = if req.to.is_empty() {
+        None
+    } else {
+        Some(
+            <[u8; 20]>::try_from(req.to.as_slice())
+                .map_err(|_| invalid("to", "must be 20 bytes, or empty to create a contract"))?,
+        )
+    };
+    if req.value.len() > 16 {
+        return Err(invalid("value", "must be at most 16 bytes"));
     }
+    let mut value = [0u8; 16];
+    value[16 - req.value.len()..].copy_from_slice(&req.value);
+    let value = u128::from_be_bytes(value);
 
-    async fn start_app(
-        &self,
-        request: Request<StartAppRequest>,
-    ) -> Result<Response<StartAppResponse>, Status> {
-        // API key validation is handled by ApiKeyLayer - no code needed here!
-        let req = request.into_inner();
-        let response = self.boot_service.clone().start_app(req).await?;
-        Ok(Response::new(response))
+    if req.legacy {
+        if req.max_priority_fee_per_gas != 0 || req.max_fee_per_gas != 0 {
+            return Err(invalid(
+                "max_fee_per_gas",
+                "legacy transactions set gas_price instead",
+            ));
+        }
+        if !req.access_list.is_empty() {
+            return Err(invalid("access_list", "legacy transactions have none"));
+        }
+        return Ok(Transaction::Legacy(LegacyTransaction {
+            chain_id: req.chain_id,
+            nonce: req.tx_nonce,
+            gas_price: req.gas_price as u128,
+            gas_limit: req.gas_limit,
+            to,
+            value,
+            data: req.data.clone(),
+        }));
     }
 
-    async fn stop_app(
-        &self,
-        request: Request<StopAppRequest>,
-    ) -> Result<Response<StopAppResponse>, Status> {
-        let req = request.into_inner();
-        self.boot_service.stop_app(&req.app_id).await?;
-        Ok(Response::new(StopAppResponse {
-            success: true,
-            message: format!("Application {} stopped successfully", req.app_id),
-            timestamp: utils::current_timestamp(),
-        }))
+    if req.gas_price != 0 {
+        return Err(invalid(
+            "gas_price",
+            "EIP-1559 transactions set max_fee_per_gas instead (legacy = true for gas_price)",
+        ));
+    }
+    if req.max_priority_fee_per_gas > req.max_fee_per_gas {
+        return Err(invalid(
+            "max_priority_fee_per_gas",
+            "cannot exceed max_fee_per_gas",
+        ));
     }
+    let access_list = req
+        .access_list
+        .iter()
+        .map(|item| -> TappResult<AccessListItem> {
+            let address = <[u8; 20]>::try_from(item.address.as_slice())
+                .map_err(|_| invalid("access_list", "addresses must be 20 bytes"))?;
+            let storage_keys = item
+                .storage_keys
+                .iter()
+                .map(|key| {
+                    <[u8; 32]>::try_from(key.as_slice())
+                        .map_err(|_| invalid("access_list", "storage keys must be 32 bytes"))
+                })
+                .collect::<TappResult<_>>()?;
+            Ok(AccessListItem {
+                address,
+                storage_keys,
+            })
+        })
+        .collect::<TappResult<_>>()?;
+    Ok(Transaction::Eip1559(Eip1559Transaction {
+        chain_id: req.chain_id,
+        nonce: req.tx_nonce,
+        max_priority_fee_per_gas: req.max_priority_fee_per_gas as u128,
+        max_fee_per_gas: req.max_fee_per_gas as u128,
+        gas_limit: req.gas_limit,
+        to,
+        value,
+        data: req.data.clone(),
+        access_list,
+    }))
+}
 
-    async fn get_task_status(
-        &self,
-        request: Request<GetTaskStatusRequest>,
-    ) -> Result<Response<GetTaskStatusResponse>, Status> {
-        let req = request.into_inner();
+/// Client of the `[contract]`, paying with `funder_key_file` or else this
+/// node's identity key, its cluster signer key
+async fn contract_client(
+    config: &config::ContractConfig,
+    cluster: &cluster::ClusterManager,
+    app_keys: &app_key::AppKeyService,
+) -> TappResult<Arc<contract::rpc::JsonRpcContract>> {
+    let funder = match &config.funder_key_file {
+        Some(path) => contract::FunderKey::from_file(path)?,
+        None => {
+            let sealing_key = app_keys.sealing_master_key().await?;
+            let node_key = cluster.node_key(&sealing_key).await?;
+            contract::FunderKey::from_private_key(node_key.private_key().to_vec())?
+        }
+    };
+    let client = contract::rpc::JsonRpcContract::new(config, funder)?;
+    info!(
+        contract = %config.address,
+        chain_id = config.chain_id,
+        funder = %client.funder_address(),
+        "Deployed apps are registered on the tapp contract"
+    );
+    Ok(Arc::new(client))
+}
 
-        match self.boot_service.get_task_status(&req.task_id).await {
-            Some(task) => Ok(Response::new(GetTaskStatusResponse {
-                success: true,
-                message: "Task found".to_string(),
-                task_id: task.id.clone(),
-                status: task.to_proto_status() as i32,
-                result: task.to_proto_result(),
-                created_at: task.created_at,
-                updated_at: task.updated_at,
-            })),
-            None => Ok(Response::new(GetTaskStatusResponse {
-                success: false,
-                message: format!("Task not found: {}", req.task_id),
-                task_id: req.task_id,
-                status: 0,
-                result: None,
-                created_at: 0,
-                updated_at: 0,
-            })),
+impl TappServiceImpl {
+    /// Apps the caller of `request` can see, and how it names them
+    fn app_scope<T>(&self, request: &Request<T>) -> AppScope {
+        let enabled = self
+            .config
+            .server
+            .api_key
+            .as_ref()
+            .is_some_and(|api_key| api_key.namespaces);
+        AppScope::of(enabled, request.extensions().get::<AuthIdentity>())
+    }
+
+    /// Look up a task a request refers to
+    /// Tasks of apps outside the caller's scope are reported as not found.
+    async fn find_task(&self, scope: &AppScope, task_id: &str) -> TappResult<boot::Task> {
+        match self.boot_service.get_task_status(task_id).await {
+            boot::TaskLookup::Found(task) if scope.localize(&task.app_id).is_some() => Ok(task),
+            boot::TaskLookup::Found(_) => Err(TappError::TaskNotFound {
+                task_id: task_id.to_string(),
+            }),
+            boot::TaskLookup::Expired => Err(TappError::TaskExpired {
+                task_id: task_id.to_string(),
+                retention_seconds: self.boot_service.task_retention().as_secs(),
+            }),
+            boot::TaskLookup::Unknown => Err(TappError::TaskNotFound {
+                task_id: task_id.to_string(),
+            }),
         }
     }
 
-    async fn list_app_measurements(
+    /// Measurements of the apps in `scope`, newest first
+    /// An empty `deployer_filter` matches every deployer, and also lists the
+    /// unmeasured apps, last.
+    async fn measurement_infos(
         &self,
-        request: Request<ListAppMeasurementsRequest>,
-    ) -> Result<Response<ListAppMeasurementsResponse>, Status> {
-        let req = request.into_inner();
-
-        let deployer_filter = if req.deployer_filter.is_empty() {
-            None
-        } else {
-            Some(req.deployer_filter)
+        scope: &AppScope,
+        deployer_filter: String,
+    ) -> Vec<AppMeasurementInfo> {
+        let deployer_filter = (!deployer_filter.is_empty()).then_some(deployer_filter);
+        let containers_missing = self.boot_service.apps_missing_containers().await;
+        let mut unmeasured: Vec<String> = match deployer_filter {
+            Some(_) => Vec::new(),
+            None => self
+                .boot_service
+                .unmeasured_apps()
+                .await
+                .into_iter()
+                .collect(),
         };
-
-        let measurements = self
-            .boot_service
+        unmeasured.sort();
+        let unmeasured = unmeasured.into_iter().filter_map(|app_id| {
+            Some(AppMeasurementInfo {
+                app_id: scope.localize(&app_id)?.to_string(),
+                namespace: crate::namespace::split(&app_id)
+                    .0
+                    .unwrap_or_default()
+                    .to_string(),
+                containers_missing: containers_missing.contains(&app_id),
+                unmeasured: true,
+                ..Default::default()
+            })
+        });
+        self.boot_service
             .list_app_measurements(deployer_filter)
-            .await;
-
-        let measurement_infos: Vec<AppMeasurementInfo> = measurements
-            .iter()
-            .map(|m| AppMeasurementInfo {
-                app_id: m.app_id.clone(),
-                compose_hash: m.compose_hash.clone(),
-                volumes_hash: m.volumes_hash.clone(),
-                deployer: m.deployer.clone(),
-                timestamp: m.timestamp,
+            .await
+            .into_iter()
+            .filter_map(|m| {
+                Some(AppMeasurementInfo {
+                    app_id: scope.localize(&m.app_id)?.to_string(),
+                    compose_hash: m.compose_hash,
+                    volumes_hash: m.volumes_hash,
+                    deployer: m.deployer,
+                    timestamp: m.timestamp,
+                    deployer_address: m.deployer_address,
+                    namespace: m.namespace.unwrap_or_default(),
+                    secret_names: m.secret_names,
+                    containers_missing: containers_missing.contains(&m.app_id),
+                    unmeasured: false,
+                    registration: self
+                        .boot_service
+                        .app_registration(&m.app_id)
+                        .map(|status| status.to_proto()),
+                })
             })
-            .collect();
-
-        let total_count = measurement_infos.len() as i32;
-
-        Ok(Response::new(ListAppMeasurementsResponse {
-            success: true,
-            message: format!("Found {} measurements", total_count),
-            measurements: measurement_infos,
-            total_count,
-            hash_algorithm: self.boot_service.get_hash_algorithm(),
-        }))
+            .chain(unmeasured)
+            .collect()
     }
 
-    async fn get_app_key(
+    /// Authorize a request on a task
+    /// It must be signed by the deployer that started the task, over the app
+    /// ID as the caller in `scope` knows it; an admin API key may omit the
+    /// signature. Returns whether that override was used.
+    async fn authorize_task_request(
         &self,
-        request: Request<GetAppKeyRequest>,
-    ) -> Result<Response<GetAppKeyResponse>, Status> {
-        let req = request.into_inner();
-
-        // Default to "ethereum" if key_type is not specified
-        let key_type = if req.key_type.is_empty() {
-            "ethereum"
-        } else {
-            &req.key_type
-        };
+        identity: Option<&AuthIdentity>,
+        scope: &AppScope,
+        task: &boot::Task,
+        action: DeployerAction<'_>,
+        nonce: &str,
+        timestamp: i64,
+        signature: &[u8],
+    ) -> Result<bool, Status> {
+        if let Some(identity) = identity {
+            if identity.role == ApiKeyRole::Admin && signature.is_empty() {
+                tracing::warn!(
+                    task_id = %task.id,
+                    app_id = %task.app_id,
+                    key_name = %identity.key_name,
+                    event = action.admin_override_event(),
+                    "Task request by operator without deployer signature"
+                );
+                return Ok(true);
+            }
+        }
 
-        let response = self
-            .app_key_service
-            .get_app_key(&req.app_id, key_type)
-            .await?;
-        Ok(Response::new(response))
+        // The task keeps the deployer as sent; compare it in canonical form
+        let deployer = DeployerIdentity::from_bytes(&task.deployer)
+            .map(|identity| identity.canonical_hex())
+            .unwrap_or_else(|_| hex::encode(&task.deployer));
+        self.verify_deployer_signature(
+            &action,
+            scope.localize(&task.app_id).unwrap_or_default(),
+            &deployer,
+            nonce,
+            timestamp,
+            signature,
+        )
+        .await?;
+        info!(
+            task_id = %task.id,
+            app_id = %task.app_id,
+            deployer = %deployer,
+            event = action.authorized_event(),
+            "Task request signed by task deployer"
+        );
+        Ok(false)
     }
 
-    async fn get_app_secret_key(
+    /// Verify a request signed by the app's deployer
+    /// Checks the nonce/timestamp (consuming the nonce) and the signature over
+    /// (signed_app_id || action || nonce || timestamp) against the current
+    /// deployer (after any ownership transfer) of the app stored as `app_id`.
+    /// `signed_app_id` is the ID the caller used, without its namespace.
+    /// Returns that measurement on success.
+    async fn verify_deployer_request(
         &self,
-        request: Request<GetAppSecretKeyRequest>,
-    ) -> Result<Response<GetAppSecretKeyResponse>, Status> {
-        // Extract remote address BEFORE consuming request
-        let remote_addr = request.remote_addr();
-
-        // Validate that the request is from localhost or Docker network
-        let (is_allowed, source_type) = if let Some(addr) = remote_addr {
-            let ip = addr.ip();
-            let allowed = Self::is_allowed_local_access(ip);
-            let source = Self::get_source_type(ip);
-            (allowed, source)
-        } else {
-            // No remote address (e.g., Unix socket) - allow
-            (true, "unix-socket")
-        };
-
-        if !is_allowed {
-            // SECURITY: Log rejected attempts with full details
-            tracing::error!(
-                remote_addr = ?remote_addr,
-                event = "SECRET_KEY_ACCESS_DENIED",
-                reason = "not in allowed network range",
-                "Rejected GetAppSecretKey request from non-allowed address"
-            );
+        action: DeployerAction<'_>,
+        app_id: &str,
+        signed_app_id: &str,
+        nonce: &str,
+        timestamp: i64,
+        signature: &[u8],
+    ) -> Result<AppMeasurement, Status> {
+        // SECURITY: Get deployer public key from app measurements
+        let app_measurement = self
+            .boot_service
+            .get_app_measurement(app_id)
+            .await
+            .ok_or_else(|| {
+                tracing::error!(
+                    app_id = %app_id,
+                    event = action.denied_event(),
+                    reason = "app not found",
+                    "App not found in measurements"
+                );
+                Status::from(TappError::AppNotFound {
+                    app_id: app_id.to_string(),
+                })
+            })?;
 
-            return Err(Status::permission_denied(
-                "GetAppSecretKey can only be called from localhost or same-host Docker containers",
-            ));
-        }
+        self.verify_deployer_signature(
+            &action,
+            signed_app_id,
+            &app_measurement.deployer,
+            nonce,
+            timestamp,
+            signature,
+        )
+        .await?;
 
-        let req = request.into_inner();
+        Ok(app_measurement)
+    }
 
-        // SECURITY: Verify nonce and timestamp to prevent replay attacks
-        if let Err(e) = self
+    /// Verify and consume the nonce of a deployer-signed request
+    async fn consume_deployer_nonce(
+        &self,
+        action: &DeployerAction<'_>,
+        app_id: &str,
+        nonce: &str,
+        timestamp: i64,
+    ) -> Result<nonce_manager::NonceKind, Status> {
+        match self
             .nonce_manager
-            .verify_and_consume(&req.nonce, req.timestamp)
+            .verify_and_consume(nonce, timestamp)
             .await
         {
-            tracing::error!(
-                app_id = %req.app_id,
-                remote_addr = ?remote_addr,
-                source_type = source_type,
-                event = "SECRET_KEY_ACCESS_DENIED",
-                reason = "nonce verification failed",
-                error = %e,
-                "Nonce verification failed"
-            );
+            Ok(kind) => Ok(kind),
+            Err(e) => {
+                tracing::error!(
+                    app_id = %app_id,
+                    event = action.denied_event(),
+                    reason = "nonce verification failed",
+                    error = %e,
+                    "Nonce verification failed"
+                );
 
-            return Err(Status::permission_denied(format!(
-                "Nonce verification failed: {}",
-                e
-            )));
+                let message = format!("{}: {}", nonce_manager::NONCE_REJECTED_MESSAGE, e);
+                Err(match e {
+                    error::NonceError::CapacityExceeded { .. } => {
+                        Status::resource_exhausted(message)
+                    }
+                    _ => Status::permission_denied(message),
+                })
+            }
         }
+    }
 
-        // SECURITY: Get deployer public key from app measurements
-        let app_measurements = self.boot_service.list_app_measurements(None).await;
-        let app_measurement = app_measurements
-            .iter()
-            .find(|m| m.app_id == req.app_id)
-            .ok_or_else(|| {
-                tracing::error!(
-                    app_id = %req.app_id,
-                    remote_addr = ?remote_addr,
-                    event = "SECRET_KEY_ACCESS_DENIED",
-                    reason = "app not found",
-                    "App not found in measurements"
-                );
-                Status::not_found(format!("App {} not found", req.app_id))
-            })?;
+    /// Verify a request signed by the given deployer
+    /// Consumes the nonce, then checks the signature (v2 or legacy, see
+    /// `app_key::deployer`) against `deployer` (canonical hex: public key, or
+    /// address for address-only deployers) and the deployer's timestamp order.
+    async fn verify_deployer_signature(
+        &self,
+        action: &DeployerAction<'_>,
+        app_id: &str,
+        deployer: &str,
+        nonce: &str,
+        timestamp: i64,
+        signature: &[u8],
+    ) -> Result<(), Status> {
+        let denied_event = action.denied_event();
 
-        // Decode deployer public key from hex
-        let deployer_pubkey = hex::decode(&app_measurement.deployer).map_err(|e| {
+        // SECURITY: Verify nonce and timestamp to prevent replay attacks
+        let nonce_kind = self
+            .consume_deployer_nonce(action, app_id, nonce, timestamp)
+            .await?;
+
+        // Decode deployer public key or address from hex
+        let deployer_identity = DeployerIdentity::from_hex(deployer).map_err(|e| {
             tracing::error!(
-                app_id = %req.app_id,
+                app_id = %app_id,
                 error = %e,
-                "Failed to decode deployer public key"
+                "Failed to decode deployer"
             );
-            Status::internal("Failed to decode deployer public key")
+            Status::internal("Failed to decode deployer")
         })?;
 
-        // SECURITY: Verify deployer signature
-        // Message format: app_id || nonce || timestamp (as bytes)
-        let mut message = Vec::new();
-        message.extend_from_slice(req.app_id.as_bytes());
-        message.extend_from_slice(req.nonce.as_bytes());
-        message.extend_from_slice(&req.timestamp.to_le_bytes());
+        // SECURITY: Verify deployer signature, v2 (recovered signer address)
+        // or legacy where the operation still accepts it
+        let action_tag = action.message_tag();
+        let legacy_message = (!action.requires_v2()).then(|| {
+            app_key::deployer_request_message(
+                action.operation(),
+                app_id,
+                &action_tag,
+                nonce,
+                timestamp,
+            )
+        });
+        let message_v2 = app_key::deployer::request_message_v2(
+            app_id,
+            nonce,
+            timestamp,
+            action.operation(),
+            &action_tag,
+        );
 
-        let signature_valid = app_key::verify_signature(&deployer_pubkey, &message, &req.signature)
+        let signature_valid = deployer_identity
+            .verify_request(legacy_message.as_deref(), &message_v2, signature)
             .map_err(|e| {
                 tracing::error!(
-                    app_id = %req.app_id,
-                    remote_addr = ?remote_addr,
-                    event = "SECRET_KEY_ACCESS_DENIED",
+                    app_id = %app_id,
+                    event = denied_event,
                     reason = "signature verification error",
                     error = %e,
                     "Signature verification error"
                 );
-                Status::internal(format!("Signature verification error: {}", e))
+                match e {
+                    TappError::InvalidParameter { .. } => Status::from(e),
+                    e => Status::internal(format!("Signature verification error: {}", e)),
+                }
             })?;
 
         if !signature_valid {
             tracing::error!(
-                app_id = %req.app_id,
-                remote_addr = ?remote_addr,
-                source_type = source_type,
-                event = "SECRET_KEY_ACCESS_DENIED",
+                app_id = %app_id,
+                event = denied_event,
                 reason = "invalid deployer signature",
                 "Invalid deployer signature"
             );
 
-            return Err(Status::permission_denied(
-                "Invalid deployer signature. Only the app deployer can access the private key.",
-            ));
+            return Err(Status::permission_denied(format!(
+                "Invalid deployer signature. Only the app deployer can {}.",
+                action.description()
+            )));
         }
 
-        // SECURITY: Log all successful private key access attempts
-        tracing::warn!(
-            app_id = %req.app_id,
-            remote_addr = ?remote_addr,
-            source_type = source_type,
-            deployer = %app_measurement.deployer,
-            event = "SECRET_KEY_ACCESS",
-            timestamp = %chrono::Utc::now(),
-            "Private key access attempt from allowed source with valid signature"
-        );
-
-        // Also get public key and address for response
-        let key_response = self
-            .app_key_service
-            .get_app_key(&req.app_id, "ethereum")
+        // SECURITY: Reject requests older than the deployer's last accepted one
+        // (challenges are server-issued and single-use, so they carry no order)
+        if nonce_kind == nonce_manager::NonceKind::Client {
+            if let Err(e) = self
+                .nonce_manager
+                .check_timestamp_order(deployer, action.operation(), timestamp)
+                .await
+            {
+                tracing::error!(
+                    app_id = %app_id,
+                    deployer = %deployer,
+                    event = denied_event,
+                    reason = "timestamp out of order",
+                    error = %e,
+                    "Request is older than the deployer's last accepted request"
+                );
+                return Err(Status::permission_denied(format!(
+                    "Nonce verification failed: {}",
+                    e
+                )));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Whether a request comes from this host, and the source type to log
+    /// Requests over the Unix socket are inherently local; otherwise the
+    /// caller must be on localhost or a same-host container network.
+    fn local_source<T>(&self, request: &Request<T>) -> (bool, &'static str) {
+        if request
+            .extensions()
+            .get::<tonic::transport::server::UdsConnectInfo>()
+            .is_some()
+        {
+            (true, "unix-socket")
+        } else if let Some(addr) = request.remote_addr() {
+            let ip = addr.ip();
+            (self.is_allowed_local_access(ip), self.get_source_type(ip))
+        } else {
+            // Unknown transport - cannot prove locality
+            (false, "unknown")
+        }
+    }
+
+    /// Authorize a sealed data request on the app stored as `app_id`
+    /// The caller must be on this host. A container of the app (or a trusted
+    /// helper, see `boot.caller_identity`) acts as the app itself; any other
+    /// caller needs the deployer's signature. Returns the app's measurement.
+    async fn authorize_sealed_data<T>(
+        &self,
+        request: &Request<T>,
+        action: DeployerAction<'_>,
+        app_id: &str,
+        nonce: &str,
+        timestamp: i64,
+        signature: &[u8],
+    ) -> Result<AppMeasurement, Status> {
+        let denied_event = action.denied_event();
+        let authorized_event = action.authorized_event();
+        let (is_allowed, source_type) = self.local_source(request);
+        if !is_allowed {
+            tracing::error!(
+                app_id = %app_id,
+                remote_addr = ?request.remote_addr(),
+                event = denied_event,
+                reason = "not in allowed network range",
+                "Rejected sealed data request from non-allowed address"
+            );
+            return Err(Status::permission_denied(
+                "Sealed data can only be used over the local unix socket, from localhost or same-host Docker containers",
+            ));
+        }
+
+        let caller = match request.remote_addr() {
+            Some(addr) => {
+                self.verify_caller_container(app_id, addr.ip(), denied_event)
+                    .await?
+            }
+            None => None,
+        };
+        if let Some(matched) = caller {
+            let measurement = self
+                .boot_service
+                .get_app_measurement(app_id)
+                .await
+                .ok_or_else(|| TappError::AppNotFound {
+                    app_id: app_id.to_string(),
+                })?;
+            info!(
+                app_id = %app_id,
+                matched = ?matched,
+                event = authorized_event,
+                "Sealed data request from the app's container"
+            );
+            return Ok(measurement);
+        }
+
+        let signed_app_id = self
+            .app_scope(request)
+            .localize(app_id)
+            .unwrap_or_default()
+            .to_string();
+        let measurement = self
+            .verify_deployer_request(action, app_id, &signed_app_id, nonce, timestamp, signature)
+            .await?;
+        info!(
+            app_id = %app_id,
+            source_type = source_type,
+            deployer = %measurement.deployer,
+            event = authorized_event,
+            "Sealed data request signed by the app deployer"
+        );
+        Ok(measurement)
+    }
+
+    /// Check if an IP address is allowed to access sensitive operations
+    /// Allows: localhost (IPv4/IPv6) and the container networks
+    fn is_allowed_local_access(&self, ip: std::net::IpAddr) -> bool {
+        self.get_source_type(ip) != "unknown"
+    }
+
+    /// Check that a Docker network caller belongs to the app it acts for
+    /// The owning container must be part of the app's compose project or a
+    /// trusted helper. Callers on other addresses (localhost, Unix socket)
+    /// are not container-attributable and pass. Used by every app-scoped
+    /// secret operation. Returns how a verified container matched, None if
+    /// the caller was not checked.
+    async fn verify_caller_container(
+        &self,
+        app_id: &str,
+        ip: std::net::IpAddr,
+        denied_event: &'static str,
+    ) -> Result<Option<container_identity::CallerMatch>, Status> {
+        let config = &self.config.boot.caller_identity;
+        if !config.enabled || self.get_source_type(ip) != "docker-network" {
+            return Ok(None);
+        }
+
+        let caller = self.container_resolver.resolve(ip).await.map_err(|e| {
+            tracing::error!(
+                app_id = %app_id,
+                remote_ip = %ip,
+                event = denied_event,
+                reason = "container lookup failed",
+                error = %e,
+                "Failed to resolve caller container"
+            );
+            Status::unavailable("Unable to verify the calling container")
+        })?;
+
+        let Some(caller) = caller else {
+            tracing::error!(
+                app_id = %app_id,
+                remote_ip = %ip,
+                event = denied_event,
+                reason = "no container owns the source address",
+                "Rejected request from unattributable Docker network address"
+            );
+            return Err(Status::permission_denied(
+                "Caller is not a running container on this host",
+            ));
+        };
+
+        match container_identity::match_caller(config, &caller, app_id) {
+            Some(matched) => {
+                tracing::info!(
+                    app_id = %app_id,
+                    remote_ip = %ip,
+                    caller_container = %caller.name,
+                    caller_project = ?caller.compose_project,
+                    matched = ?matched,
+                    "Caller container verified"
+                );
+                Ok(Some(matched))
+            }
+            None => {
+                tracing::error!(
+                    app_id = %app_id,
+                    remote_ip = %ip,
+                    caller_container = %caller.name,
+                    caller_container_id = %caller.id,
+                    caller_project = ?caller.compose_project,
+                    event = denied_event,
+                    reason = "caller container belongs to another app",
+                    "Caller container does not belong to the requested app"
+                );
+                Err(Status::permission_denied(format!(
+                    "Caller container does not belong to app {}",
+                    app_id
+                )))
+            }
+        }
+    }
+
+    /// API keys that expire within the configured horizon (or already have)
+    fn expiring_api_keys(&self, now: chrono::DateTime<chrono::Utc>) -> Vec<ApiKeyExpiryInfo> {
+        let config = self.api_key_config.read().unwrap();
+        let Some(api_config) = config.as_ref() else {
+            return Vec::new();
+        };
+
+        let horizon = now + chrono::Duration::days(api_config.expiry_horizon_days as i64);
+        let mut keys: Vec<ApiKeyExpiryInfo> = api_config
+            .keys
+            .iter()
+            .filter_map(|entry| {
+                let expires_at = entry.expires_at().filter(|t| *t <= horizon)?;
+                Some(ApiKeyExpiryInfo {
+                    name: entry.name(),
+                    expires_at: expires_at.timestamp(),
+                    expired: expires_at <= now,
+                    deprecated: entry.deprecated(),
+                })
+            })
+            .collect();
+        keys.sort_by_key(|key| key.expires_at);
+        keys
+    }
+
+    /// This node's key for key share exchanges
+    async fn cluster_node_key(&self) -> TappResult<Arc<cluster::exchange::NodeKey>> {
+        let sealing_key = self.app_key_service.sealing_master_key().await?;
+        self.cluster.node_key(&sealing_key).await
+    }
+
+    /// Reject key share exchange requests made more than
+    /// `security.nonce_validity_seconds` away from this node's clock
+    fn check_exchange_timestamp(&self, timestamp: i64) -> TappResult<()> {
+        let max_age = self.config.security.nonce_validity_seconds;
+        let age = utils::current_timestamp().abs_diff(timestamp);
+        if age > max_age {
+            return Err(TappError::InvalidParameter {
+                field: "timestamp".to_string(),
+                reason: format!(
+                    "is {} seconds off this node's clock; at most {} are accepted",
+                    age, max_age
+                ),
+            });
+        }
+        Ok(())
+    }
+
+    /// Check that this node can take a share: collecting one it could not
+    /// keep would lose it
+    async fn check_no_cluster(&self) -> TappResult<()> {
+        let status = self.cluster.status().await;
+        if status.state != cluster::ClusterState::Uninitialized {
+            return Err(TappError::ClusterState(format!(
+                "This node is already a member of cluster {}",
+                status.cluster_id
+            )));
+        }
+        Ok(())
+    }
+
+    /// `status` as reported to callers, with this node's signer address and
+    /// the last recovery it coordinated
+    async fn cluster_status_proto(&self, status: &cluster::ClusterStatus) -> ClusterStatus {
+        let mut proto = status.to_proto();
+        proto.recovery = self
+            .cluster
+            .recovery_status()
+            .map(|recovery| recovery.to_proto());
+        match self.cluster_node_key().await {
+            Ok(node_key) => proto.signer_address = node_key.signer_address(),
+            Err(e) => {
+                tracing::warn!(error = %e, "Node key unavailable; reporting no signer address")
+            }
+        }
+        proto
+    }
+
+    /// Collect shares for `recovery` from the members at `peers`, derive the
+    /// recovered share and seal it to the replacement node's `requester_key`
+    /// Returns the sealed share and this node's evidence over it.
+    async fn coordinate_recovery(
+        &self,
+        recovery: &mut cluster::recovery::Recovery<'_>,
+        cluster_id: &str,
+        peers: &[tonic::transport::Endpoint],
+        requester_key: &[u8],
+        request_report_data: &[u8],
+    ) -> TappResult<(Vec<u8>, Vec<u8>)> {
+        let node_key = self.cluster_node_key().await?;
+        for endpoint in peers {
+            if recovery.missing() == 0 {
+                break;
+            }
+            let contribution = self
+                .collect_contribution(endpoint, &node_key, cluster_id, recovery.share_index())
+                .await;
+            let added = contribution.and_then(|(contributor, share)| {
+                recovery.add(share, &contributor).map_err(Status::from)
+            });
+            if let Err(status) = added {
+                tracing::warn!(
+                    endpoint = %endpoint.uri(),
+                    cluster_id = %cluster_id,
+                    error = %status.message(),
+                    event = "CLUSTER_CONTRIBUTION_FAILED",
+                    "No share from this member; trying the next"
+                );
+            }
+        }
+
+        let share = recovery.reconstruct()?;
+        let sealed_share = cluster::exchange::seal_share(
+            requester_key,
+            cluster_id,
+            &cluster::exchange::recovery_label(share.index),
+            &share,
+        )?;
+        drop(share);
+        let report_data = cluster::exchange::response_report_data(
+            request_report_data,
+            node_key.public_key(),
+            &sealed_share,
+        );
+        let evidence = self
+            .boot_service
+            .get_evidence(GetEvidenceRequest { report_data })
+            .await?;
+        Ok((sealed_share, evidence.evidence))
+    }
+
+    /// Ask the member at `endpoint` for its share, to recover `share_index`
+    /// Returns the member's signer address and share.
+    async fn collect_contribution(
+        &self,
+        endpoint: &tonic::transport::Endpoint,
+        node_key: &cluster::exchange::NodeKey,
+        cluster_id: &str,
+        share_index: u8,
+    ) -> Result<(String, cluster::sss::Share), Status> {
+        let timestamp = utils::current_timestamp();
+        let request_report_data = cluster::exchange::contribution_report_data(
+            cluster_id,
+            share_index,
+            node_key.public_key(),
+            timestamp,
+        );
+        let evidence = self
+            .boot_service
+            .get_evidence(GetEvidenceRequest {
+                report_data: request_report_data.clone(),
+            })
+            .await?;
+        let response = connect_cluster_node(endpoint)
+            .await?
+            .contribute_share(ContributeShareRequest {
+                cluster_id: cluster_id.to_string(),
+                share_index: share_index as u32,
+                public_key: node_key.public_key().to_vec(),
+                timestamp,
+                evidence: evidence.evidence,
+            })
+            .await?
+            .into_inner();
+
+        let response_report_data = cluster::exchange::response_report_data(
+            &request_report_data,
+            &response.public_key,
+            &response.sealed_share,
+        );
+        let (contributor, _) = cluster::exchange::check_node(
+            self.cluster_verifier.as_ref(),
+            self.cluster_whitelist.as_ref(),
+            &endpoint.uri().to_string(),
+            &response.evidence,
+            &response_report_data,
+            &response.public_key,
+        )
+        .await?;
+        let share = cluster::exchange::open_share(
+            node_key,
+            cluster_id,
+            &cluster::exchange::contribution_label(share_index),
+            &response.sealed_share,
+        )?;
+        if share.index as u32 != response.share_index {
+            return Err(TappError::Crypto(format!(
+                "Sealed share {} does not match share index {}",
+                share.index, response.share_index
+            ))
+            .into());
+        }
+        Ok((contributor, share))
+    }
+
+    /// Determine the source type for logging
+    fn get_source_type(&self, ip: std::net::IpAddr) -> &'static str {
+        let ip = ip.to_canonical();
+        if ip.is_loopback() {
+            "localhost"
+        } else if self
+            .container_networks
+            .iter()
+            .any(|network| network.contains(ip))
+        {
+            // Podman's networks too; the name is kept for log queries
+            "docker-network"
+        } else {
+            "unknown"
+        }
+    }
+
+    pub async fn new(config: TappConfig) -> TappResult<Self> {
+        info!("Initializing TAPP service components");
+        config.security.validate()?;
+        if let Some(notifications) = &config.notifications {
+            notifications.validate()?;
+        }
+
+        let notifier = notifications::Notifier::start(config.notifications.as_ref());
+        if notifier.stats().enabled {
+            info!("Webhook notifications enabled");
+        }
+
+        // Initialize AppKeyService
+        // If KBS config is not provided, use in-memory mode
+        let (kbs_config, use_in_memory) = if let Some(ref kbs) = config.kbs {
+            (kbs.clone(), false)
+        } else {
+            info!("KBS config not provided, using in-memory key generation");
+            (config::KbsConfig::default(), true)
+        };
+
+        let app_key_service =
+            Arc::new(app_key::AppKeyService::new(&kbs_config, use_in_memory).await?);
+
+        // A share sealed by an earlier run keeps this node in its cluster
+        let cluster = cluster::ClusterManager::new(&config.cluster);
+        if cluster.has_stored_share() {
+            let loaded = match app_key_service.sealing_master_key().await {
+                Ok(sealing_key) => cluster.load(&sealing_key).await,
+                Err(e) => Err(e),
+            };
+            if let Err(e) = loaded {
+                tracing::warn!(
+                    state_dir = %config.cluster.state_dir,
+                    error = %e,
+                    "Failed to load the cluster share; the node reports no cluster"
+                );
+            }
+        }
+
+        let mut boot_service = BootService::new(&config.boot)
+            .await?
+            .with_notifier(notifier.clone())
+            .with_app_keys(app_key_service.clone());
+        let contract_access = match &config.contract {
+            Some(contract_config) => {
+                let client = contract_client(contract_config, &cluster, &app_key_service).await?;
+                boot_service = boot_service.with_registrar(contract::registrar::Registrar::new(
+                    client.clone(),
+                    contract_config,
+                ));
+                Some((contract_config, client))
+            }
+            None => None,
+        };
+        let boot_service = Arc::new(boot_service);
+
+        // Initialize NonceManager for replay attack prevention
+        let nonce_manager = Arc::new(nonce_manager::NonceManager::from_config(
+            &config.security,
+            config.server.max_tracked_nonces,
+        ));
+        info!(
+            validity_seconds = config.security.nonce_validity_seconds,
+            cleanup_interval_seconds = config.security.nonce_cleanup_interval_seconds,
+            "Nonce replay protection configured"
+        );
+
+        // Initialize LogsService
+        let logs_service =
+            service_monitor::logs::LogsService::new(config.logging.file_path.clone());
+
+        // Host memory, load and disk readings for GetHostResources
+        let host_monitor = service_monitor::system_info::HostMonitor::new(
+            &config.monitoring,
+            logs_service.log_dir().map(|dir| dir.to_path_buf()),
+            notifier.clone(),
+        );
+
+        // Resolves Docker network callers to their containers
+        let container_resolver = container_identity::ContainerResolver::new(
+            &config.boot.caller_identity,
+            &config.boot.runtime_socket(),
+        );
+        let container_networks = ip_filter_layer::parse_networks(
+            "boot.container_networks",
+            &config.boot.effective_container_networks(),
+        )?;
+
+        let api_key_config = Arc::new(std::sync::RwLock::new(config.server.api_key.clone()));
+
+        let sealed_data = app_key::sealed::SealedDataStore::new(
+            &config.boot.data_dir,
+            config.boot.sealed_data.clone(),
+        );
+
+        let cluster_verifier = cluster::verifier::from_config(&config.cluster);
+        if config.cluster.evidence_verifier == config::EvidenceVerifierKind::Sample {
+            tracing::warn!(
+                "Key share exchanges accept sample evidence, which anyone can produce; \
+                 use this for testing only"
+            );
+        }
+        let cluster_whitelist = Box::new(cluster::verifier::StaticWhitelist::new(&config.cluster));
+
+        let attestor_auth = contract_access
+            .filter(|(contract_config, _)| contract_config.attestor_auth.enabled)
+            .map(|(contract_config, client)| {
+                info!(
+                    failure_policy = ?contract_config.attestor_auth.failure_policy,
+                    cache_ttl_seconds = contract_config.attestor_auth.cache_ttl_seconds,
+                    "App key callers must be attestors registered on the tapp contract"
+                );
+                Arc::new(attestor_auth_layer::AttestorAuth::new(
+                    client,
+                    nonce_manager.clone(),
+                    &contract_config.attestor_auth,
+                ))
+            });
+
+        info!("All TAPP service components initialized successfully");
+
+        Ok(Self {
+            boot_service,
+            app_key_service,
+            nonce_manager,
+            logs_service,
+            host_monitor,
+            notifier,
+            container_resolver,
+            container_networks,
+            api_key_config,
+            log_level: None,
+            sealed_data,
+            cluster,
+            cluster_verifier,
+            cluster_whitelist,
+            attestor_auth,
+            config,
+        })
+    }
+
+    /// Let SetLogLevel swap the filter installed by `init_tracing`
+    pub fn with_log_level(mut self, control: log_level::LogLevelControl) -> Self {
+        self.log_level = Some(control);
+        self
+    }
+
+    /// Decide with `whitelist` instead of `cluster.allowed_signers` and
+    /// `cluster.allowed_code_hashes` which nodes exchange key shares
+    pub fn with_cluster_whitelist(
+        mut self,
+        whitelist: Box<dyn cluster::verifier::Whitelist>,
+    ) -> Self {
+        self.cluster_whitelist = whitelist;
+        self
+    }
+
+    /// Stop deployments and background work before the process exits
+    /// See `BootService::shutdown` for what happens to unfinished tasks.
+    pub async fn shutdown(&self, grace: std::time::Duration) {
+        self.boot_service.shutdown(grace).await;
+        self.nonce_manager.shutdown();
+    }
+}
+
+#[tonic::async_trait]
+impl TappService for TappServiceImpl {
+    /// Get attestation evidence from TEE platform
+    async fn get_evidence(
+        &self,
+        request: Request<GetEvidenceRequest>,
+    ) -> Result<Response<GetEvidenceResponse>, Status> {
+        let req = request.into_inner();
+        let evidence = self.boot_service.get_evidence(req).await?;
+        Ok(Response::new(evidence))
+    }
+
+    async fn start_app(
+        &self,
+        request: Request<StartAppRequest>,
+    ) -> Result<Response<StartAppResponse>, Status> {
+        // API key validation is handled by ApiKeyLayer - no code needed here!
+        let request_id = request.extensions().get::<RequestId>().cloned();
+        let scope = self.app_scope(&request);
+        let mut req = request.into_inner();
+        req.app_id = scope.qualify(&req.app_id)?;
+
+        // StartApp is unary: the compose file and every mount file travel in
+        // this one message. Reject uploads over the configured limit here with
+        // a descriptive error; the codec limit only adds headroom above it.
+        let limit = self.config.server.max_recv_message_bytes("StartApp");
+        let mount_total: usize = req.mount_files.iter().map(|f| f.content.len()).sum();
+        if mount_total + req.compose_content.len() > limit {
+            return Err(Status::invalid_argument(format!(
+                "StartApp upload too large: {} mount file(s) totalling {} plus a {} compose file exceed the {} limit (server.method_max_recv_message_mb.StartApp)",
+                req.mount_files.len(),
+                utils::format_bytes(mount_total as u64),
+                utils::format_bytes(req.compose_content.len() as u64),
+                utils::format_bytes(limit as u64)
+            )));
+        }
+
+        let response = self
+            .boot_service
+            .clone()
+            .start_app(req, request_id.as_ref().map(|id| id.0.as_str()))
+            .await?;
+        Ok(Response::new(response))
+    }
+
+    async fn stop_app(
+        &self,
+        request: Request<StopAppRequest>,
+    ) -> Result<Response<StopAppResponse>, Status> {
+        let identity = request.extensions().get::<AuthIdentity>().cloned();
+        let scope = self.app_scope(&request);
+        let req = request.into_inner();
+        let app_id = scope.qualify(&req.app_id)?;
+
+        // Operators holding an admin API key may force-stop without a deployer
+        // signature; the override is attributed to them in the audit log
+        let admin_override = match identity {
+            Some(ref identity)
+                if identity.role == ApiKeyRole::Admin && req.signature.is_empty() =>
+            {
+                tracing::warn!(
+                    app_id = %req.app_id,
+                    key_name = %identity.key_name,
+                    event = "STOP_APP_ADMIN_OVERRIDE",
+                    "Application force-stopped by operator without deployer signature"
+                );
+                true
+            }
+            _ => {
+                let measurement = self
+                    .verify_deployer_request(
+                        DeployerAction::StopApp,
+                        &app_id,
+                        &req.app_id,
+                        &req.nonce,
+                        req.timestamp,
+                        &req.signature,
+                    )
+                    .await?;
+                info!(
+                    app_id = %req.app_id,
+                    deployer = %measurement.deployer,
+                    event = "STOP_APP_AUTHORIZED",
+                    "Stop request signed by app deployer"
+                );
+                false
+            }
+        };
+
+        let (cancel, _cancel_on_drop) = rpc_cancellation();
+        self.boot_service.stop_app(&app_id, &cancel).await?;
+
+        let mut response = Response::new(StopAppResponse {
+            success: true,
+            message: format!("Application {} stopped successfully", req.app_id),
+            timestamp: utils::current_timestamp(),
+        });
+        if admin_override {
+            response
+                .extensions_mut()
+                .insert(AuditAnnotation("admin_override".to_string()));
+        }
+        Ok(response)
+    }
+
+    async fn transfer_app_ownership(
+        &self,
+        request: Request<TransferAppOwnershipRequest>,
+    ) -> Result<Response<TransferAppOwnershipResponse>, Status> {
+        let scope = self.app_scope(&request);
+        let req = request.into_inner();
+        let app_id = scope.qualify(&req.app_id)?;
+
+        if let Err(reason) = DeployerIdentity::from_bytes(&req.new_deployer) {
+            return Err(TappError::InvalidParameter {
+                field: "new_deployer".to_string(),
+                reason,
+            }
+            .into());
+        }
+
+        // SECURITY: Only the current deployer may hand over the app
+        let measurement = self
+            .verify_deployer_request(
+                DeployerAction::TransferOwnership {
+                    new_deployer: &req.new_deployer,
+                },
+                &app_id,
+                &req.app_id,
+                &req.nonce,
+                req.timestamp,
+                &req.signature,
+            )
             .await?;
 
-        // Get private key
-        let private_key = self.app_key_service.get_private_key(&req.app_id).await?;
+        let transfer = self
+            .boot_service
+            .transfer_app_ownership(&app_id, &measurement.deployer, &req.new_deployer)
+            .await?;
+
+        tracing::warn!(
+            app_id = %app_id,
+            previous_deployer = %transfer.previous_deployer,
+            new_deployer = %transfer.new_deployer,
+            event = "OWNERSHIP_TRANSFERRED",
+            "Application ownership transferred by current deployer"
+        );
+
+        Ok(Response::new(TransferAppOwnershipResponse {
+            success: true,
+            message: format!("Ownership of application {} transferred", req.app_id),
+            previous_deployer: transfer.previous_deployer,
+            new_deployer: transfer.new_deployer,
+            timestamp: transfer.timestamp,
+        }))
+    }
+
+    async fn get_task_status(
+        &self,
+        request: Request<GetTaskStatusRequest>,
+    ) -> Result<Response<GetTaskStatusResponse>, Status> {
+        let scope = self.app_scope(&request);
+        let req = request.into_inner();
+
+        // v1 reports an unknown or purged task as success=false rather than
+        // NOT_FOUND; existing clients rely on it (v2 returns the status)
+        match self.find_task(&scope, &req.task_id).await {
+            Ok(task) => Ok(Response::new(task_status_response(
+                &self.boot_service,
+                &scope,
+                &task,
+            ))),
+            Err(e) => Ok(Response::new(GetTaskStatusResponse {
+                success: false,
+                message: e.to_string(),
+                task_id: req.task_id,
+                ..Default::default()
+            })),
+        }
+    }
+
+    type WatchTaskStream = std::pin::Pin<
+        Box<dyn tokio_stream::Stream<Item = Result<GetTaskStatusResponse, Status>> + Send>,
+    >;
+
+    async fn watch_task(
+        &self,
+        request: Request<WatchTaskRequest>,
+    ) -> Result<Response<Self::WatchTaskStream>, Status> {
+        let scope = self.app_scope(&request);
+        let req = request.into_inner();
+
+        // Subscribe before the first read so no change is missed in between
+        let mut updates = self.boot_service.watch_tasks().await;
+        let task = self.find_task(&scope, &req.task_id).await?;
+
+        let boot_service = self.boot_service.clone();
+        let (tx, rx) = tokio::sync::mpsc::channel(16);
+        tokio::spawn(async move {
+            let mut finished = task.is_finished();
+            let mut last = task_status_response(&boot_service, &scope, &task);
+            if tx.send(Ok(last.clone())).await.is_err() {
+                return;
+            }
+
+            while !finished {
+                // Any task changing may move this one in the queue, so every
+                // update, or a lag, re-reads it
+                tokio::select! {
+                    _ = tx.closed() => return,
+                    update = updates.recv() => {
+                        if let Err(tokio::sync::broadcast::error::RecvError::Closed) = update {
+                            return;
+                        }
+                    }
+                }
+
+                let boot::TaskLookup::Found(task) =
+                    boot_service.get_task_status(&req.task_id).await
+                else {
+                    // Purged while watched
+                    return;
+                };
+                finished = task.is_finished();
+                let current = task_status_response(&boot_service, &scope, &task);
+                if current != last {
+                    if tx.send(Ok(current.clone())).await.is_err() {
+                        return;
+                    }
+                    last = current;
+                }
+            }
+        });
+
+        Ok(Response::new(Box::pin(
+            tokio_stream::wrappers::ReceiverStream::new(rx),
+        )))
+    }
+
+    async fn cancel_task(
+        &self,
+        request: Request<CancelTaskRequest>,
+    ) -> Result<Response<CancelTaskResponse>, Status> {
+        let identity = request.extensions().get::<AuthIdentity>().cloned();
+        let scope = self.app_scope(&request);
+        let req = request.into_inner();
+
+        let task = self.find_task(&scope, &req.task_id).await?;
+
+        // Only the deployer that started the task, or an operator holding an
+        // admin API key, may cancel it
+        let admin_override = self
+            .authorize_task_request(
+                identity.as_ref(),
+                &scope,
+                &task,
+                DeployerAction::CancelTask { task_id: &task.id },
+                &req.nonce,
+                req.timestamp,
+                &req.signature,
+            )
+            .await?;
+
+        let was_queued = matches!(task.status, boot::TaskState::Queued);
+        let task = match self.boot_service.cancel_task(&task.id).await {
+            boot::TaskLookup::Found(task) => task,
+            _ => {
+                return Err(TappError::TaskExpired {
+                    task_id: req.task_id,
+                    retention_seconds: self.boot_service.task_retention().as_secs(),
+                }
+                .into())
+            }
+        };
+
+        // Cancelling a finished task is a no-op reporting its terminal state
+        let message = match task.status {
+            boot::TaskState::Cancelling => format!("Cancellation of task {} requested", task.id),
+            boot::TaskState::Cancelled if was_queued => {
+                format!("Queued task {} cancelled", task.id)
+            }
+            _ => format!("Task {} already finished", task.id),
+        };
+
+        let mut response = Response::new(CancelTaskResponse {
+            success: true,
+            message,
+            task_id: task.id.clone(),
+            status: task.to_proto_status() as i32,
+        });
+        if admin_override {
+            response
+                .extensions_mut()
+                .insert(AuditAnnotation("admin_override".to_string()));
+        }
+        Ok(response)
+    }
+
+    async fn retry_task(
+        &self,
+        request: Request<RetryTaskRequest>,
+    ) -> Result<Response<RetryTaskResponse>, Status> {
+        let identity = request.extensions().get::<AuthIdentity>().cloned();
+        let request_id = request.extensions().get::<RequestId>().cloned();
+        let scope = self.app_scope(&request);
+        let req = request.into_inner();
+
+        let task = self.find_task(&scope, &req.task_id).await?;
+
+        let admin_override = self
+            .authorize_task_request(
+                identity.as_ref(),
+                &scope,
+                &task,
+                DeployerAction::RetryTask { task_id: &task.id },
+                &req.nonce,
+                req.timestamp,
+                &req.signature,
+            )
+            .await?;
+
+        let new_task = self
+            .boot_service
+            .clone()
+            .retry_task(&task, request_id.as_ref().map(|id| id.0.as_str()))
+            .await?;
+
+        let mut response = Response::new(RetryTaskResponse {
+            success: true,
+            message: format!("Task {} retried as {}", task.id, new_task.id),
+            task_id: task.id,
+            new_task_id: new_task.id,
+        });
+        if admin_override {
+            response
+                .extensions_mut()
+                .insert(AuditAnnotation("admin_override".to_string()));
+        }
+        Ok(response)
+    }
+
+    async fn list_app_measurements(
+        &self,
+        request: Request<ListAppMeasurementsRequest>,
+    ) -> Result<Response<ListAppMeasurementsResponse>, Status> {
+        let scope = self.app_scope(&request);
+        let req = request.into_inner();
+        let measurement_infos = self.measurement_infos(&scope, req.deployer_filter).await;
+
+        let total_count = measurement_infos.len() as i32;
+
+        Ok(Response::new(ListAppMeasurementsResponse {
+            success: true,
+            message: format!("Found {} measurements", total_count),
+            measurements: measurement_infos,
+            total_count,
+            hash_algorithm: self.boot_service.get_hash_algorithm(),
+        }))
+    }
+
+    async fn get_app_key(
+        &self,
+        request: Request<GetAppKeyRequest>,
+    ) -> Result<Response<GetAppKeyResponse>, Status> {
+        let scope = self.app_scope(&request);
+        let req = request.into_inner();
+        let app_id = scope.qualify(&req.app_id)?;
+
+        // Default to "ethereum" if key_type is not specified
+        let key_type = if req.key_type.is_empty() {
+            "ethereum"
+        } else {
+            &req.key_type
+        };
+
+        let response = self.app_key_service.get_app_key(&app_id, key_type).await?;
+        Ok(Response::new(response))
+    }
+
+    async fn get_attested_app_key(
+        &self,
+        request: Request<GetAttestedAppKeyRequest>,
+    ) -> Result<Response<GetAttestedAppKeyResponse>, Status> {
+        let scope = self.app_scope(&request);
+        let req = request.into_inner();
+        let app_id = scope.qualify(&req.app_id)?;
+        if req.nonce.is_empty() || req.nonce.len() > app_key::MAX_ATTESTED_KEY_NONCE_LEN {
+            return Err(Status::invalid_argument(format!(
+                "Nonce must be 1 to {} bytes, got {}",
+                app_key::MAX_ATTESTED_KEY_NONCE_LEN,
+                req.nonce.len()
+            )));
+        }
+
+        let Some(measurement) = self.boot_service.get_app_measurement(&app_id).await else {
+            return Err(TappError::AppNotFound { app_id: req.app_id }.into());
+        };
+        let compose_hash = hex::decode(&measurement.compose_hash).map_err(|e| {
+            Status::internal(format!("Invalid compose hash of app {}: {}", req.app_id, e))
+        })?;
+
+        let key = self
+            .app_key_service
+            .get_app_key(&app_id, "ethereum")
+            .await?;
+        // In-memory keys carry the 0x04 prefix; the binding uses the bare key
+        let public_key = match key.public_key.as_slice() {
+            [0x04, rest @ ..] if rest.len() == 64 => rest.to_vec(),
+            bytes if bytes.len() == 64 => bytes.to_vec(),
+            bytes => {
+                return Err(Status::failed_precondition(format!(
+                    "App key of {} is not a secp256k1 public key ({} bytes)",
+                    req.app_id,
+                    bytes.len()
+                )))
+            }
+        };
+
+        let report_data = app_key::attested_key_report_data(&req.nonce, &public_key, &compose_hash);
+        let evidence = self
+            .boot_service
+            .get_evidence(GetEvidenceRequest { report_data })
+            .await?;
+
+        info!(
+            app_id = %app_id,
+            compose_hash = %measurement.compose_hash,
+            "Attested app key issued"
+        );
+        Ok(Response::new(GetAttestedAppKeyResponse {
+            success: true,
+            message: format!("Attested key for app {}", req.app_id),
+            eth_address: app_key::eth_address(&public_key),
+            public_key,
+            compose_hash,
+            nonce: req.nonce,
+            evidence: evidence.evidence,
+            tee_type: evidence.tee_type,
+            timestamp: evidence.timestamp,
+        }))
+    }
+
+    async fn get_app_secret_key(
+        &self,
+        request: Request<GetAppSecretKeyRequest>,
+    ) -> Result<Response<GetAppSecretKeyResponse>, Status> {
+        // Extract remote address BEFORE consuming request
+        let remote_addr = request.remote_addr();
+        let uds_info = request
+            .extensions()
+            .get::<tonic::transport::server::UdsConnectInfo>()
+            .cloned();
+
+        // Prefer the transport itself as the locality proof: requests arriving
+        // over the Unix socket are inherently local. Otherwise fall back to
+        // the localhost / Docker network IP check.
+        if let Some(ref info) = uds_info {
+            let cred = info.peer_cred;
+            tracing::info!(
+                peer_uid = ?cred.map(|c| c.uid()),
+                peer_gid = ?cred.map(|c| c.gid()),
+                peer_pid = ?cred.and_then(|c| c.pid()),
+                event = "SECRET_KEY_UDS_PEER",
+                "GetAppSecretKey request over unix socket"
+            );
+        }
+        let (is_allowed, source_type) = self.local_source(&request);
+
+        if !is_allowed {
+            // SECURITY: Log rejected attempts with full details
+            tracing::error!(
+                remote_addr = ?remote_addr,
+                event = "SECRET_KEY_ACCESS_DENIED",
+                reason = "not in allowed network range",
+                "Rejected GetAppSecretKey request from non-allowed address"
+            );
+
+            return Err(Status::permission_denied(
+                "GetAppSecretKey can only be called over the local unix socket, from localhost or same-host Docker containers",
+            ));
+        }
+
+        let scope = self.app_scope(&request);
+        let req = request.into_inner();
+        let app_id = scope.qualify(&req.app_id)?;
+
+        // SECURITY: A container may only request its own app's key
+        if let Some(addr) = remote_addr {
+            self.verify_caller_container(&app_id, addr.ip(), "SECRET_KEY_ACCESS_DENIED")
+                .await?;
+        }
+
+        // SECURITY: Verify nonce, timestamp and deployer signature
+        let app_measurement = self
+            .verify_deployer_request(
+                DeployerAction::GetSecretKey,
+                &app_id,
+                &req.app_id,
+                &req.nonce,
+                req.timestamp,
+                &req.signature,
+            )
+            .await
+            .inspect_err(|_| {
+                tracing::error!(
+                    app_id = %app_id,
+                    remote_addr = ?remote_addr,
+                    source_type = source_type,
+                    event = "SECRET_KEY_ACCESS_DENIED",
+                    "Deployer verification failed"
+                );
+            })?;
+
+        // SECURITY: Log all successful private key access attempts
+        tracing::warn!(
+            app_id = %app_id,
+            remote_addr = ?remote_addr,
+            source_type = source_type,
+            deployer = %app_measurement.deployer,
+            event = "SECRET_KEY_ACCESS",
+            timestamp = %chrono::Utc::now(),
+            "Private key access attempt from allowed source with valid signature"
+        );
+
+        // Also get public key and address for response
+        let key_response = self
+            .app_key_service
+            .get_app_key(&app_id, "ethereum")
+            .await?;
+
+        // Get private key
+        let private_key = self.app_key_service.get_private_key(&app_id).await?;
+
+        // SECURITY: Log successful retrieval
+        tracing::warn!(
+            app_id = %app_id,
+            remote_addr = ?remote_addr,
+            source_type = source_type,
+            event = "SECRET_KEY_RETRIEVED",
+            timestamp = %chrono::Utc::now(),
+            "Private key successfully retrieved"
+        );
+
+        Ok(Response::new(GetAppSecretKeyResponse {
+            success: true,
+            message: format!("Private key for app {}", req.app_id),
+            private_key,
+            public_key: key_response.public_key,
+            eth_address: key_response.eth_address,
+        }))
+    }
+
+    async fn sign_transaction(
+        &self,
+        request: Request<SignTransactionRequest>,
+    ) -> Result<Response<SignTransactionResponse>, Status> {
+        let remote_addr = request.remote_addr();
+        let (is_allowed, source_type) = self.local_source(&request);
+        if !is_allowed {
+            tracing::error!(
+                remote_addr = ?remote_addr,
+                event = "SIGN_TRANSACTION_DENIED",
+                reason = "not in allowed network range",
+                "Rejected SignTransaction request from non-allowed address"
+            );
+            return Err(Status::permission_denied(
+                "SignTransaction can only be called over the local unix socket, from localhost or same-host Docker containers",
+            ));
+        }
+
+        let scope = self.app_scope(&request);
+        let req = request.into_inner();
+        let app_id = scope.qualify(&req.app_id)?;
+
+        // SECURITY: A container may only sign with its own app's key
+        if let Some(addr) = remote_addr {
+            self.verify_caller_container(&app_id, addr.ip(), "SIGN_TRANSACTION_DENIED")
+                .await?;
+        }
+
+        let transaction = unsigned_transaction(&req)?;
+        let chain_id = transaction.chain_id();
+        if !self.config.boot.key_policy.allows_chain(&app_id, chain_id) {
+            tracing::error!(
+                app_id = %app_id,
+                chain_id = chain_id,
+                event = "SIGN_TRANSACTION_DENIED",
+                reason = "chain not allowed",
+                "Chain is not in the app's boot.key_policy.allowed_chain_ids"
+            );
+            return Err(Status::permission_denied(format!(
+                "App {} may not sign transactions for chain {}",
+                req.app_id, chain_id
+            )));
+        }
+        let signing_hash = transaction.signing_hash();
+
+        // SECURITY: Verify nonce, timestamp and the deployer signature, which
+        // covers this very transaction
+        let app_measurement = self
+            .verify_deployer_request(
+                DeployerAction::SignTransaction { signing_hash },
+                &app_id,
+                &req.app_id,
+                &req.nonce,
+                req.timestamp,
+                &req.signature,
+            )
+            .await
+            .inspect_err(|_| {
+                tracing::error!(
+                    app_id = %app_id,
+                    remote_addr = ?remote_addr,
+                    source_type = source_type,
+                    event = "SIGN_TRANSACTION_DENIED",
+                    "Deployer verification failed"
+                );
+            })?;
+
+        let key_response = self
+            .app_key_service
+            .get_app_key(&app_id, "ethereum")
+            .await?;
+        let mut private_key = self.app_key_service.get_private_key(&app_id).await?;
+        let signed = transaction.sign(&private_key);
+        cluster::sss::wipe(&mut private_key);
+        let signed = signed?;
+        let tx_hash = signed.hash_hex();
+
+        // SECURITY: Log every signing; nothing is broadcast
+        tracing::warn!(
+            app_id = %app_id,
+            remote_addr = ?remote_addr,
+            source_type = source_type,
+            deployer = %app_measurement.deployer,
+            chain_id = chain_id,
+            legacy = req.legacy,
+            tx_hash = %tx_hash,
+            event = "TRANSACTION_SIGNED",
+            "Transaction signed with the app key"
+        );
+
+        let mut response = Response::new(SignTransactionResponse {
+            success: true,
+            message: format!("Transaction signed for app {}", req.app_id),
+            tx_hash: signed.hash.to_vec(),
+            raw_transaction: signed.raw,
+            signing_hash: signing_hash.to_vec(),
+            eth_address: key_response.eth_address,
+        });
+        response.extensions_mut().insert(AuditAnnotation(format!(
+            "chain_id={} tx_hash={}",
+            chain_id, tx_hash
+        )));
+        Ok(response)
+    }
+
+    async fn get_nonce(
+        &self,
+        request: Request<GetNonceRequest>,
+    ) -> Result<Response<GetNonceResponse>, Status> {
+        // GetNonce needs no API key: unauthenticated callers are told apart
+        // by source address
+        let caller = match request.extensions().get::<AuthIdentity>() {
+            Some(identity) => format!("key:{}", identity.key_name),
+            None => match request.remote_addr() {
+                Some(addr) => format!("ip:{}", addr.ip()),
+                None => "local".to_string(),
+            },
+        };
+        let (nonce, expires_at) = match self.nonce_manager.issue_challenge(&caller).await {
+            Ok(challenge) => challenge,
+            Err(e @ error::NonceError::CapacityExceeded { .. }) => {
+                tracing::warn!(error = %e, event = "NONCE_CAPACITY", "Cannot issue nonce");
+                return Err(Status::resource_exhausted(format!(
+                    "Cannot issue nonce: {}",
+                    e
+                )));
+            }
+            Err(e @ error::NonceError::TooManyChallenges { .. }) => {
+                tracing::warn!(
+                    caller = %caller,
+                    error = %e,
+                    event = "NONCE_CALLER_LIMIT",
+                    "Cannot issue nonce"
+                );
+                return Err(Status::resource_exhausted(format!(
+                    "Cannot issue nonce: {}",
+                    e
+                )));
+            }
+            Err(e) => return Err(Status::internal(format!("Cannot issue nonce: {}", e))),
+        };
+
+        Ok(Response::new(GetNonceResponse {
+            nonce,
+            expires_at,
+            ttl_seconds: nonce_manager::CHALLENGE_TTL_SECONDS,
+        }))
+    }
+
+    async fn get_app_info(
+        &self,
+        request: Request<GetAppInfoRequest>,
+    ) -> Result<Response<GetAppInfoResponse>, Status> {
+        let scope = self.app_scope(&request);
+        let req = request.into_inner();
+        let app_id = scope.qualify(&req.app_id)?;
+
+        let compose_content = self.boot_service.get_app_compose_content(&app_id).await?;
+        let volumes_content = self.boot_service.get_app_mount_files(&app_id).await?;
+
+        let (Some(compose_content), Some(volumes_content)) = (compose_content, volumes_content)
+        else {
+            return Err(TappError::AppNotFound { app_id: req.app_id }.into());
+        };
+
+        // Set in the compose file at deploy time (see `boot::platform`)
+        let platforms = boot::platform::service_platforms(&compose_content)
+            .map(|platforms| {
+                platforms
+                    .into_iter()
+                    .map(|(service, platform)| (service, platform.to_string()))
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        let events = self
+            .boot_service
+            .get_app_events(&app_id)
+            .await
+            .into_iter()
+            .map(|e| AppEventInfo {
+                event: e.event,
+                detail: e.detail,
+                timestamp: e.timestamp,
+            })
+            .collect();
+
+        let deployed_at = self
+            .boot_service
+            .get_app_measurement(&app_id)
+            .await
+            .map(|m| m.timestamp)
+            .unwrap_or_default();
+
+        // Docker being unreachable leaves the runtime fields empty rather
+        // than failing the whole call
+        let status = match self.boot_service.app_status(&app_id).await {
+            Ok(status) => Some(status),
+            Err(e) => {
+                tracing::warn!(app_id = %app_id, error = %e, "Cannot read app container status");
+                None
+            }
+        };
+        let current_started_at = status
+            .as_ref()
+            .and_then(|s| s.started_at)
+            .unwrap_or_default();
+        let uptime_seconds = if current_started_at > 0 {
+            (utils::current_timestamp() - current_started_at).max(0)
+        } else {
+            0
+        };
+
+        Ok(Response::new(GetAppInfoResponse {
+            success: true,
+            message: format!("App info for {}", req.app_id),
+            app_id: req.app_id,
+            compose_content,
+            volumes_content,
+            events,
+            deployed_at,
+            current_started_at,
+            uptime_seconds,
+            restart_count: status.as_ref().map_or(0, |s| s.restart_count),
+            running: status.is_some_and(|s| s.running),
+            platforms,
+        }))
+    }
+
+    async fn get_service_status(
+        &self,
+        request: Request<GetServiceStatusRequest>,
+    ) -> Result<Response<GetServiceStatusResponse>, Status> {
+        let is_admin = request
+            .extensions()
+            .get::<AuthIdentity>()
+            .is_some_and(|identity| identity.role == ApiKeyRole::Admin);
+        let req = request.into_inner();
+
+        let services = if req.service_name.is_empty() || req.service_name == NAME {
+            vec![ServiceInfo {
+                name: NAME.to_string(),
+                status: ServiceHealthStatus::Healthy as i32,
+                status_message: "running".to_string(),
+                pid: std::process::id() as i32,
+                version: VERSION.to_string(),
+                ..Default::default()
+            }]
+        } else {
+            Vec::new()
+        };
+
+        // Admin view: keys operators should rotate before clients are cut off
+        let expiring_keys = if is_admin {
+            self.expiring_api_keys(chrono::Utc::now())
+        } else {
+            Vec::new()
+        };
+
+        let nonce_stats = self.nonce_manager.stats().await;
+        let nonce_status = NonceStatus {
+            validity_seconds: nonce_stats.validity_window,
+            cleanup_interval_seconds: nonce_stats.cleanup_interval.as_secs() as i64,
+            active_nonces: nonce_stats.active_nonces as u64,
+            issued_challenges: nonce_stats.issued_challenges as u64,
+            capacity: nonce_stats.capacity as u64,
+            rejected_at_capacity: nonce_stats.rejected_at_capacity,
+        };
+
+        let notifications = self.notifier.stats();
+
+        Ok(Response::new(GetServiceStatusResponse {
+            success: true,
+            message: format!("{} service(s)", services.len()),
+            services,
+            timestamp: utils::current_timestamp(),
+            system_healthy: true,
+            system_version: VERSION.to_string(),
+            expiring_keys,
+            nonce_status: Some(nonce_status),
+            notification_status: Some(NotificationStatus {
+                enabled: notifications.enabled,
+                delivered: notifications.delivered,
+                failed: notifications.failed,
+                dropped: notifications.dropped,
+            }),
+            image_policy: Some(
+                self.boot_service
+                    .image_policy()
+                    .map_or_else(ImagePolicyStatus::default, |policy| policy.status()),
+            ),
+            container_runtime: self.boot_service.runtime().as_str().to_string(),
+        }))
+    }
+
+    async fn get_host_resources(
+        &self,
+        _request: Request<GetHostResourcesRequest>,
+    ) -> Result<Response<GetHostResourcesResponse>, Status> {
+        let response = self.host_monitor.resources(&self.boot_service).await?;
+        Ok(Response::new(response))
+    }
+
+    async fn get_service_logs(
+        &self,
+        request: Request<GetServiceLogsRequest>,
+    ) -> Result<Response<GetServiceLogsResponse>, Status> {
+        let req = request.into_inner();
+        let response = self.logs_service.get_logs(req).await?;
+        Ok(Response::new(response))
+    }
+
+    type StreamServiceLogsStream =
+        std::pin::Pin<Box<dyn tokio_stream::Stream<Item = Result<ServiceLogLines, Status>> + Send>>;
+
+    async fn stream_service_logs(
+        &self,
+        request: Request<StreamServiceLogsRequest>,
+    ) -> Result<Response<Self::StreamServiceLogsStream>, Status> {
+        let req = request.into_inner();
+        let lines = self.logs_service.stream_logs(req).await?;
+        Ok(Response::new(Box::pin(
+            tokio_stream::wrappers::ReceiverStream::new(lines),
+        )))
+    }
+
+    type DownloadLogFileStream =
+        std::pin::Pin<Box<dyn tokio_stream::Stream<Item = Result<LogFileChunk, Status>> + Send>>;
+
+    async fn download_log_file(
+        &self,
+        request: Request<DownloadLogFileRequest>,
+    ) -> Result<Response<Self::DownloadLogFileStream>, Status> {
+        let req = request.into_inner();
+        let chunks = self.logs_service.download_log(req).await?;
+        Ok(Response::new(Box::pin(
+            tokio_stream::wrappers::ReceiverStream::new(chunks),
+        )))
+    }
+
+    async fn get_app_logs(
+        &self,
+        request: Request<GetAppLogsRequest>,
+    ) -> Result<Response<GetAppLogsResponse>, Status> {
+        let scope = self.app_scope(&request);
+        let req = request.into_inner();
+        let app_id = scope.qualify(&req.app_id)?;
+
+        let (content, task_id) = match req.source.as_str() {
+            "" | "containers" => {
+                let service_name = if req.service_name.is_empty() {
+                    None
+                } else {
+                    Some(req.service_name.as_str())
+                };
+
+                let (cancel, _cancel_on_drop) = rpc_cancellation();
+                let content = self
+                    .boot_service
+                    .get_app_logs(&app_id, req.lines, service_name, &cancel)
+                    .await?;
+                (content, String::new())
+            }
+            "deploy" => {
+                let task_id = (!req.task_id.is_empty()).then_some(req.task_id.as_str());
+                let (task_id, content) = self
+                    .boot_service
+                    .get_deploy_log(&app_id, task_id, req.lines)
+                    .await?;
+                (content, task_id)
+            }
+            other => {
+                return Err(TappError::InvalidParameter {
+                    field: "source".to_string(),
+                    reason: format!(
+                        "Unknown log source {}, expected containers or deploy",
+                        other
+                    ),
+                }
+                .into());
+            }
+        };
+
+        let total_lines = content.lines().count() as i32;
+
+        Ok(Response::new(GetAppLogsResponse {
+            success: true,
+            message: format!("Retrieved {} lines from app {}", total_lines, req.app_id),
+            content,
+            total_lines,
+            task_id,
+        }))
+    }
+
+    async fn set_log_level(
+        &self,
+        request: Request<SetLogLevelRequest>,
+    ) -> Result<Response<SetLogLevelResponse>, Status> {
+        // SECURITY: Debug logs may reveal request details; admin keys only
+        let identity = request.extensions().get::<AuthIdentity>().cloned();
+        let Some(identity) = identity.filter(|identity| identity.role == ApiKeyRole::Admin) else {
+            tracing::warn!(
+                remote_addr = ?request.remote_addr(),
+                event = "LOG_LEVEL_CHANGE_DENIED",
+                "SetLogLevel called without an admin API key"
+            );
+            return Err(Status::permission_denied(
+                "SetLogLevel requires an admin API key",
+            ));
+        };
+        let Some(control) = &self.log_level else {
+            return Err(TappError::ServiceUnavailable {
+                service: "log level control".to_string(),
+            }
+            .into());
+        };
+        let req = request.into_inner();
+
+        // An empty filter restores the configured level for good
+        let (filter, revert_after) = if req.filter.is_empty() {
+            (control.initial().to_string(), 0)
+        } else if req.revert_after_seconds > 0 {
+            (req.filter, req.revert_after_seconds)
+        } else {
+            (req.filter, self.config.logging.level_revert_seconds)
+        };
+        let revert_after = (revert_after > 0).then(|| std::time::Duration::from_secs(revert_after));
+
+        let previous_filter = control.set(&filter, revert_after)?;
+        let revert_at = revert_after
+            .map(|after| utils::current_timestamp() + after.as_secs() as i64)
+            .unwrap_or_default();
+
+        tracing::warn!(
+            key_name = %identity.key_name,
+            previous_filter = %previous_filter,
+            filter = %filter,
+            revert_at,
+            event = "LOG_LEVEL_CHANGED",
+            "Log filter changed"
+        );
+
+        let mut response = Response::new(SetLogLevelResponse {
+            success: true,
+            message: if revert_at > 0 {
+                format!(
+                    "Log filter set, reverting to {} at {}",
+                    control.initial(),
+                    revert_at
+                )
+            } else {
+                "Log filter set".to_string()
+            },
+            previous_filter,
+            filter: filter.clone(),
+            revert_at,
+        });
+        response
+            .extensions_mut()
+            .insert(AuditAnnotation(format!("log_filter={}", filter)));
+        Ok(response)
+    }
+
+    async fn force_remove_app(
+        &self,
+        request: Request<ForceRemoveAppRequest>,
+    ) -> Result<Response<ForceRemoveAppResponse>, Status> {
+        // SECURITY: Deletes app state without the deployer; admin keys only
+        let identity = request.extensions().get::<AuthIdentity>().cloned();
+        let Some(identity) = identity.filter(|identity| identity.role == ApiKeyRole::Admin) else {
+            tracing::warn!(
+                remote_addr = ?request.remote_addr(),
+                event = "APP_FORCE_REMOVE_DENIED",
+                "ForceRemoveApp called without an admin API key"
+            );
+            return Err(Status::permission_denied(
+                "ForceRemoveApp requires an admin API key",
+            ));
+        };
+        let scope = self.app_scope(&request);
+        let req = request.into_inner();
+        let app_id = scope.qualify(&req.app_id)?;
+
+        let (cancel, _cancel_on_drop) = rpc_cancellation();
+        let removal = self
+            .boot_service
+            .force_remove_app(&app_id, req.purge_volumes, req.purge_images, &cancel)
+            .await?;
+        let cleaned = removal.cleaned();
+
+        tracing::warn!(
+            app_id = %req.app_id,
+            key_name = %identity.key_name,
+            cleaned = ?cleaned,
+            errors = removal.errors.len(),
+            event = "APP_FORCE_REMOVED_BY_OPERATOR",
+            "Application force-removed by operator"
+        );
+
+        let mut message = if cleaned.is_empty() {
+            format!("Nothing left to remove for application {}", req.app_id)
+        } else {
+            format!(
+                "Removed {} of application {}",
+                cleaned.join(", "),
+                req.app_id
+            )
+        };
+        if !removal.errors.is_empty() {
+            message.push_str(&format!("; {} step(s) failed", removal.errors.len()));
+        }
+
+        let mut response = Response::new(ForceRemoveAppResponse {
+            success: true,
+            message,
+            cancelled_task_id: removal.cancelled_task.unwrap_or_default(),
+            containers_removed: removal.containers_removed as i32,
+            app_dir_removed: removal.app_dir_removed,
+            measurement_removed: removal.measurement_removed,
+            compose_content_removed: removal.compose_content_removed,
+            mount_files_removed: removal.mount_files_removed,
+            errors: removal.errors,
+        });
+        response.extensions_mut().insert(AuditAnnotation(format!(
+            "force_remove={}",
+            cleaned.join(",")
+        )));
+        Ok(response)
+    }
+
+    async fn seal_data(
+        &self,
+        request: Request<SealDataRequest>,
+    ) -> Result<Response<SealDataResponse>, Status> {
+        let scope = self.app_scope(&request);
+        let req = request.get_ref();
+        let app_id = scope.qualify(&req.app_id)?;
+        let action = DeployerAction::SealedData {
+            operation: "seal_data",
+            label: &req.label,
+        };
+        let measurement = self
+            .authorize_sealed_data(
+                &request,
+                action,
+                &app_id,
+                &req.nonce,
+                req.timestamp,
+                &req.signature,
+            )
+            .await?;
+
+        let master_key = self.app_key_service.sealing_master_key().await?;
+        let blob = self
+            .sealed_data
+            .seal(
+                &master_key,
+                &app_id,
+                &measurement.compose_hash,
+                &req.label,
+                app_key::sealed::Binding::from_proto(req.binding()),
+                &req.data,
+            )
+            .await?;
+        info!(
+            app_id = %app_id,
+            label = %blob.label,
+            binding = ?blob.binding,
+            size = blob.size,
+            event = "SEALED_DATA_STORED",
+            "Sealed data stored"
+        );
+
+        let mut response = Response::new(SealDataResponse {
+            success: true,
+            message: format!("Sealed {} for application {}", blob.label, req.app_id),
+            blob: Some(blob.to_proto()),
+        });
+        response
+            .extensions_mut()
+            .insert(AuditAnnotation(format!("sealed_blob={}", blob.label)));
+        Ok(response)
+    }
+
+    async fn unseal_data(
+        &self,
+        request: Request<UnsealDataRequest>,
+    ) -> Result<Response<UnsealDataResponse>, Status> {
+        let scope = self.app_scope(&request);
+        let req = request.get_ref();
+        let app_id = scope.qualify(&req.app_id)?;
+        let action = DeployerAction::SealedData {
+            operation: "unseal_data",
+            label: &req.label,
+        };
+        let measurement = self
+            .authorize_sealed_data(
+                &request,
+                action,
+                &app_id,
+                &req.nonce,
+                req.timestamp,
+                &req.signature,
+            )
+            .await?;
+
+        let master_key = self.app_key_service.sealing_master_key().await?;
+        let (blob, data) = self
+            .sealed_data
+            .unseal(&master_key, &app_id, &measurement.compose_hash, &req.label)
+            .await
+            .inspect_err(|e| {
+                if let TappError::SealBindingMismatch { .. } = e {
+                    tracing::warn!(
+                        app_id = %app_id,
+                        label = %req.label,
+                        compose_hash = %measurement.compose_hash,
+                        event = "SEALED_DATA_BINDING_MISMATCH",
+                        "Sealed blob does not open for the app as it runs now"
+                    );
+                }
+            })?;
+
+        let mut response = Response::new(UnsealDataResponse {
+            success: true,
+            message: format!("Unsealed {} for application {}", blob.label, req.app_id),
+            blob: Some(blob.to_proto()),
+            data,
+        });
+        response
+            .extensions_mut()
+            .insert(AuditAnnotation(format!("sealed_blob={}", blob.label)));
+        Ok(response)
+    }
+
+    async fn list_sealed_blobs(
+        &self,
+        request: Request<ListSealedBlobsRequest>,
+    ) -> Result<Response<ListSealedBlobsResponse>, Status> {
+        let scope = self.app_scope(&request);
+        let req = request.get_ref();
+        let app_id = scope.qualify(&req.app_id)?;
+        let action = DeployerAction::SealedData {
+            operation: "list_sealed_blobs",
+            label: "",
+        };
+        self.authorize_sealed_data(
+            &request,
+            action,
+            &app_id,
+            &req.nonce,
+            req.timestamp,
+            &req.signature,
+        )
+        .await?;
+
+        let blobs = self.sealed_data.list(&app_id).await?;
+        Ok(Response::new(ListSealedBlobsResponse {
+            success: true,
+            message: format!(
+                "{} sealed blobs for application {}",
+                blobs.len(),
+                req.app_id
+            ),
+            blobs: blobs.iter().map(|blob| blob.to_proto()).collect(),
+        }))
+    }
+
+    async fn delete_sealed_blob(
+        &self,
+        request: Request<DeleteSealedBlobRequest>,
+    ) -> Result<Response<DeleteSealedBlobResponse>, Status> {
+        let scope = self.app_scope(&request);
+        let req = request.get_ref();
+        let app_id = scope.qualify(&req.app_id)?;
+        let action = DeployerAction::SealedData {
+            operation: "delete_sealed_blob",
+            label: &req.label,
+        };
+        self.authorize_sealed_data(
+            &request,
+            action,
+            &app_id,
+            &req.nonce,
+            req.timestamp,
+            &req.signature,
+        )
+        .await?;
+
+        let deleted = self.sealed_data.delete(&app_id, &req.label).await?;
+        if deleted {
+            info!(
+                app_id = %app_id,
+                label = %req.label,
+                event = "SEALED_DATA_DELETED",
+                "Sealed blob deleted"
+            );
+        }
+
+        let mut response = Response::new(DeleteSealedBlobResponse {
+            success: true,
+            message: if deleted {
+                format!("Deleted {} of application {}", req.label, req.app_id)
+            } else {
+                format!(
+                    "No sealed blob {} for application {}",
+                    req.label, req.app_id
+                )
+            },
+            deleted,
+        });
+        response
+            .extensions_mut()
+            .insert(AuditAnnotation(format!("sealed_blob={}", req.label)));
+        Ok(response)
+    }
+
+    async fn init_cluster(
+        &self,
+        request: Request<InitClusterRequest>,
+    ) -> Result<Response<InitClusterResponse>, Status> {
+        // SECURITY: Generates the key the cluster protects; admin keys only
+        let identity = request.extensions().get::<AuthIdentity>().cloned();
+        let Some(identity) = identity.filter(|identity| identity.role == ApiKeyRole::Admin) else {
+            tracing::warn!(
+                remote_addr = ?request.remote_addr(),
+                event = "CLUSTER_INIT_DENIED",
+                "InitCluster called without an admin API key"
+            );
+            return Err(Status::permission_denied(
+                "InitCluster requires an admin API key",
+            ));
+        };
+        let req = request.into_inner();
+        let total_shares = share_count("total_shares", req.total_shares)?;
+        let threshold = share_count("threshold", req.threshold)?;
+
+        let sealing_key = self.app_key_service.sealing_master_key().await?;
+        let status = self
+            .cluster
+            .init(total_shares, threshold, req.peers, &sealing_key)
+            .await?;
+        tracing::warn!(
+            cluster_id = %status.cluster_id,
+            key_name = %identity.key_name,
+            event = "CLUSTER_INITIALIZED_BY_OPERATOR",
+            "Key cluster initialized by operator"
+        );
+
+        let mut response = Response::new(InitClusterResponse {
+            success: true,
+            message: format!(
+                "Initialized cluster {} with {} of {} shares; {} peers to collect theirs",
+                status.cluster_id,
+                threshold,
+                total_shares,
+                status.peers.len()
+            ),
+            status: Some(self.cluster_status_proto(&status).await),
+        });
+        response
+            .extensions_mut()
+            .insert(AuditAnnotation(format!("cluster={}", status.cluster_id)));
+        Ok(response)
+    }
+
+    async fn get_cluster_status(
+        &self,
+        _request: Request<GetClusterStatusRequest>,
+    ) -> Result<Response<GetClusterStatusResponse>, Status> {
+        let status = self.cluster.status().await;
+        Ok(Response::new(GetClusterStatusResponse {
+            success: true,
+            message: match status.state {
+                cluster::ClusterState::Uninitialized => "No cluster initialized".to_string(),
+                _ => format!("Member of cluster {}", status.cluster_id),
+            },
+            status: Some(self.cluster_status_proto(&status).await),
+        }))
+    }
+
+    async fn request_key_share(
+        &self,
+        request: Request<RequestKeyShareRequest>,
+    ) -> Result<Response<RequestKeyShareResponse>, Status> {
+        // SECURITY: Public; the requester is authorized by its evidence and
+        // the cluster whitelist, and the share is sealed to its node key
+        let remote_addr = request.remote_addr();
+        let req = request.into_inner();
+        cluster::exchange::check_names(&req.cluster_id, &req.peer)?;
+        self.check_exchange_timestamp(req.timestamp)?;
+        let status = self.cluster.status().await;
+        if status.cluster_id != req.cluster_id {
+            return Err(TappError::ClusterState(format!(
+                "This node hands out no shares of cluster {}",
+                req.cluster_id
+            ))
+            .into());
+        }
+
+        let request_report_data = cluster::exchange::request_report_data(
+            &req.cluster_id,
+            &req.peer,
+            &req.public_key,
+            req.timestamp,
+        );
+        let (signer, node) = cluster::exchange::check_node(
+            self.cluster_verifier.as_ref(),
+            self.cluster_whitelist.as_ref(),
+            &req.peer,
+            &req.evidence,
+            &request_report_data,
+            &req.public_key,
+        )
+        .await
+        .inspect_err(|e| {
+            tracing::warn!(
+                remote_addr = ?remote_addr,
+                cluster_id = %req.cluster_id,
+                peer = %req.peer,
+                error = %e,
+                event = "CLUSTER_SHARE_REQUEST_REJECTED",
+                "Key share request rejected"
+            );
+        })?;
+
+        let node_key = self.cluster_node_key().await?;
+        let delivery = AttestedShareDelivery {
+            boot_service: &self.boot_service,
+            node_key: &node_key,
+            requester_key: &req.public_key,
+            request_report_data: &request_report_data,
+            delivered: std::sync::Mutex::new(None),
+        };
+        let status = self
+            .cluster
+            .deliver_share(&req.peer, &signer, &delivery)
+            .await?;
+        let Some((share_index, sealed_share, evidence)) = delivery.delivered.into_inner().unwrap()
+        else {
+            return Err(Status::internal(
+                "Share was handed out without being sealed",
+            ));
+        };
+        info!(
+            cluster_id = %req.cluster_id,
+            peer = %req.peer,
+            signer = %signer,
+            code_hash = %node.code_hash,
+            event = "CLUSTER_SHARE_COLLECTED",
+            "Sealed key share sent to an attested node"
+        );
+
+        let mut response = Response::new(RequestKeyShareResponse {
+            success: true,
+            message: format!(
+                "Share {} of cluster {} sealed to {}",
+                share_index, req.cluster_id, signer
+            ),
+            share_index: share_index as u32,
+            total_shares: status.total_shares as u32,
+            threshold: status.threshold as u32,
+            initialized_at: status.initialized_at,
+            sealed_share,
+            public_key: node_key.public_key().to_vec(),
+            evidence,
+        });
+        response.extensions_mut().insert(AuditAnnotation(format!(
+            "peer={} signer={}",
+            req.peer, signer
+        )));
+        Ok(response)
+    }
+
+    async fn join_cluster(
+        &self,
+        request: Request<JoinClusterRequest>,
+    ) -> Result<Response<JoinClusterResponse>, Status> {
+        // SECURITY: Makes this node hold a share of the cluster key; admin keys only
+        let identity = request.extensions().get::<AuthIdentity>().cloned();
+        let Some(identity) = identity.filter(|identity| identity.role == ApiKeyRole::Admin) else {
+            tracing::warn!(
+                remote_addr = ?request.remote_addr(),
+                event = "CLUSTER_JOIN_DENIED",
+                "JoinCluster called without an admin API key"
+            );
+            return Err(Status::permission_denied(
+                "JoinCluster requires an admin API key",
+            ));
+        };
+        let req = request.into_inner();
+        cluster::exchange::check_names(&req.cluster_id, &req.peer)?;
+        let endpoint = cluster_endpoint("endpoint", &req.endpoint, CLUSTER_CALL_TIMEOUT)?;
+        self.check_no_cluster().await?;
+
+        let sealing_key = self.app_key_service.sealing_master_key().await?;
+        let node_key = self.cluster.node_key(&sealing_key).await?;
+        let timestamp = utils::current_timestamp();
+        let request_report_data = cluster::exchange::request_report_data(
+            &req.cluster_id,
+            &req.peer,
+            node_key.public_key(),
+            timestamp,
+        );
+        let evidence = self
+            .boot_service
+            .get_evidence(GetEvidenceRequest {
+                report_data: request_report_data.clone(),
+            })
+            .await?;
+
+        let response = connect_cluster_node(&endpoint)
+            .await?
+            .request_key_share(RequestKeyShareRequest {
+                cluster_id: req.cluster_id.clone(),
+                peer: req.peer.clone(),
+                public_key: node_key.public_key().to_vec(),
+                timestamp,
+                evidence: evidence.evidence,
+            })
+            .await?
+            .into_inner();
+
+        let response_report_data = cluster::exchange::response_report_data(
+            &request_report_data,
+            &response.public_key,
+            &response.sealed_share,
+        );
+        let (start_node, _) = cluster::exchange::check_node(
+            self.cluster_verifier.as_ref(),
+            self.cluster_whitelist.as_ref(),
+            &req.endpoint,
+            &response.evidence,
+            &response_report_data,
+            &response.public_key,
+        )
+        .await
+        .inspect_err(|e| {
+            tracing::warn!(
+                endpoint = %req.endpoint,
+                cluster_id = %req.cluster_id,
+                error = %e,
+                event = "CLUSTER_START_NODE_REJECTED",
+                "Start node of the cluster rejected; its share was not opened"
+            );
+        })?;
+        let share = cluster::exchange::open_share(
+            &node_key,
+            &req.cluster_id,
+            &req.peer,
+            &response.sealed_share,
+        )?;
+        if share.index as u32 != response.share_index {
+            return Err(TappError::Crypto(format!(
+                "Sealed share {} does not match share index {}",
+                share.index, response.share_index
+            ))
+            .into());
+        }
+        let status = self
+            .cluster
+            .join(
+                &req.cluster_id,
+                share_count("total_shares", response.total_shares)?,
+                share_count("threshold", response.threshold)?,
+                response.initialized_at,
+                share,
+                &sealing_key,
+            )
+            .await?;
+        tracing::warn!(
+            cluster_id = %status.cluster_id,
+            start_node = %start_node,
+            key_name = %identity.key_name,
+            event = "CLUSTER_JOINED_BY_OPERATOR",
+            "Key cluster joined by operator"
+        );
+
+        let mut response = Response::new(JoinClusterResponse {
+            success: true,
+            message: format!(
+                "Joined cluster {} with share {} from {}",
+                status.cluster_id, status.own_share_index, start_node
+            ),
+            status: Some(self.cluster_status_proto(&status).await),
+        });
+        response.extensions_mut().insert(AuditAnnotation(format!(
+            "cluster={} start_node={}",
+            status.cluster_id, start_node
+        )));
+        Ok(response)
+    }
+
+    async fn recover_share(
+        &self,
+        request: Request<RecoverShareRequest>,
+    ) -> Result<Response<RecoverShareResponse>, Status> {
+        // SECURITY: Public; the replacement node is authorized by its
+        // evidence and the cluster whitelist, and the share is sealed to its
+        // node key
+        let remote_addr = request.remote_addr();
+        let req = request.into_inner();
+        let share_index = share_count("share_index", req.share_index)?;
+        let label = cluster::exchange::recovery_label(share_index);
+        cluster::exchange::check_names(&req.cluster_id, &label)?;
+        self.check_exchange_timestamp(req.timestamp)?;
+        let peers = req
+            .peer_endpoints
+            .iter()
+            .map(|url| cluster_endpoint("peer_endpoints", url, CLUSTER_CALL_TIMEOUT))
+            .collect::<TappResult<Vec<_>>>()?;
+
+        let request_report_data = cluster::exchange::recovery_report_data(
+            &req.cluster_id,
+            share_index,
+            &req.public_key,
+            req.timestamp,
+        );
+        let (requester, _) = cluster::exchange::check_node(
+            self.cluster_verifier.as_ref(),
+            self.cluster_whitelist.as_ref(),
+            &label,
+            &req.evidence,
+            &request_report_data,
+            &req.public_key,
+        )
+        .await
+        .inspect_err(|e| {
+            tracing::warn!(
+                remote_addr = ?remote_addr,
+                cluster_id = %req.cluster_id,
+                share_index,
+                error = %e,
+                event = "CLUSTER_RECOVERY_REQUEST_REJECTED",
+                "Share recovery request rejected"
+            );
+        })?;
+
+        let mut recovery = self
+            .cluster
+            .begin_recovery(&req.cluster_id, share_index, &requester)
+            .await?;
+        let coordinated = self
+            .coordinate_recovery(
+                &mut recovery,
+                &req.cluster_id,
+                &peers,
+                &req.public_key,
+                &request_report_data,
+            )
+            .await;
+        let (sealed_share, evidence) = match coordinated {
+            Ok(sealed) => sealed,
+            Err(e) => {
+                recovery.abort(&e);
+                return Err(e.into());
+            }
+        };
+        let contributors = self
+            .cluster
+            .recovery_status()
+            .map(|recovery| recovery.contributors)
+            .unwrap_or_default();
+        recovery.delivered();
+
+        let status = self.cluster.status().await;
+        let mut response = Response::new(RecoverShareResponse {
+            success: true,
+            message: format!(
+                "Share {} of cluster {} recovered and sealed to {}",
+                share_index, req.cluster_id, requester
+            ),
+            share_index: share_index as u32,
+            total_shares: status.total_shares as u32,
+            threshold: status.threshold as u32,
+            initialized_at: status.initialized_at,
+            sealed_share,
+            public_key: self.cluster_node_key().await?.public_key().to_vec(),
+            evidence,
+            contributors,
+        });
+        response.extensions_mut().insert(AuditAnnotation(format!(
+            "cluster={} share_index={} requested_by={}",
+            req.cluster_id, share_index, requester
+        )));
+        Ok(response)
+    }
+
+    async fn contribute_share(
+        &self,
+        request: Request<ContributeShareRequest>,
+    ) -> Result<Response<ContributeShareResponse>, Status> {
+        // SECURITY: Public; the coordinator is authorized by its evidence and
+        // the cluster whitelist, and the share is sealed to its node key
+        let remote_addr = request.remote_addr();
+        let req = request.into_inner();
+        let share_index = share_count("share_index", req.share_index)?;
+        let label = cluster::exchange::contribution_label(share_index);
+        cluster::exchange::check_names(&req.cluster_id, &label)?;
+        self.check_exchange_timestamp(req.timestamp)?;
+
+        let request_report_data = cluster::exchange::contribution_report_data(
+            &req.cluster_id,
+            share_index,
+            &req.public_key,
+            req.timestamp,
+        );
+        let (coordinator, _) = cluster::exchange::check_node(
+            self.cluster_verifier.as_ref(),
+            self.cluster_whitelist.as_ref(),
+            "recovery coordinator",
+            &req.evidence,
+            &request_report_data,
+            &req.public_key,
+        )
+        .await
+        .inspect_err(|e| {
+            tracing::warn!(
+                remote_addr = ?remote_addr,
+                cluster_id = %req.cluster_id,
+                share_index,
+                error = %e,
+                event = "CLUSTER_CONTRIBUTION_REJECTED",
+                "Share contribution request rejected"
+            );
+        })?;
+
+        let own_share = self.cluster.own_share(&req.cluster_id).await?;
+        let node_key = self.cluster_node_key().await?;
+        let sealed_share =
+            cluster::exchange::seal_share(&req.public_key, &req.cluster_id, &label, &own_share)?;
+        let report_data = cluster::exchange::response_report_data(
+            &request_report_data,
+            node_key.public_key(),
+            &sealed_share,
+        );
+        let evidence = self
+            .boot_service
+            .get_evidence(GetEvidenceRequest { report_data })
+            .await?;
+        tracing::warn!(
+            cluster_id = %req.cluster_id,
+            coordinator = %coordinator,
+            share_index,
+            own_share_index = own_share.index,
+            event = "CLUSTER_SHARE_CONTRIBUTED",
+            "Share sealed to a recovery coordinator"
+        );
+
+        let mut response = Response::new(ContributeShareResponse {
+            success: true,
+            message: format!(
+                "Share {} sealed to {} to recover share {}",
+                own_share.index, coordinator, share_index
+            ),
+            share_index: own_share.index as u32,
+            sealed_share,
+            public_key: node_key.public_key().to_vec(),
+            evidence: evidence.evidence,
+        });
+        response.extensions_mut().insert(AuditAnnotation(format!(
+            "cluster={} share_index={} coordinator={}",
+            req.cluster_id, share_index, coordinator
+        )));
+        Ok(response)
+    }
+
+    async fn recover_cluster(
+        &self,
+        request: Request<RecoverClusterRequest>,
+    ) -> Result<Response<RecoverClusterResponse>, Status> {
+        // SECURITY: Makes this node hold a share of the cluster key; admin keys only
+        let identity = request.extensions().get::<AuthIdentity>().cloned();
+        let Some(identity) = identity.filter(|identity| identity.role == ApiKeyRole::Admin) else {
+            tracing::warn!(
+                remote_addr = ?request.remote_addr(),
+                event = "CLUSTER_RECOVERY_DENIED",
+                "RecoverCluster called without an admin API key"
+            );
+            return Err(Status::permission_denied(
+                "RecoverCluster requires an admin API key",
+            ));
+        };
+        let req = request.into_inner();
+        let share_index = share_count("share_index", req.share_index)?;
+        let label = cluster::exchange::recovery_label(share_index);
+        cluster::exchange::check_names(&req.cluster_id, &label)?;
+        if req.endpoints.is_empty() {
+            return Err(TappError::InvalidParameter {
+                field: "endpoints".to_string(),
+                reason: "must list at least one member of the cluster".to_string(),
+            }
+            .into());
+        }
+        let endpoints = req
+            .endpoints
+            .iter()
+            .map(|url| cluster_endpoint("endpoints", url, CLUSTER_RECOVERY_TIMEOUT))
+            .collect::<TappResult<Vec<_>>>()?;
+        self.check_no_cluster().await?;
+
+        let sealing_key = self.app_key_service.sealing_master_key().await?;
+        let node_key = self.cluster.node_key(&sealing_key).await?;
+        let timestamp = utils::current_timestamp();
+        let request_report_data = cluster::exchange::recovery_report_data(
+            &req.cluster_id,
+            share_index,
+            node_key.public_key(),
+            timestamp,
+        );
+        let evidence = self
+            .boot_service
+            .get_evidence(GetEvidenceRequest {
+                report_data: request_report_data.clone(),
+            })
+            .await?;
+
+        // The first member that answers coordinates; the others contribute
+        let mut coordinator = None;
+        for (i, endpoint) in endpoints.iter().enumerate() {
+            let Ok(mut client) = connect_cluster_node(endpoint).await else {
+                continue;
+            };
+            match client.get_cluster_status(GetClusterStatusRequest {}).await {
+                Ok(response) => {
+                    let status = response.into_inner().status.unwrap_or_default();
+                    if status.cluster_id == req.cluster_id
+                        && status.own_share_index != share_index as u32
+                    {
+                        coordinator = Some((i, client));
+                        break;
+                    }
+                }
+                Err(status) => tracing::warn!(
+                    endpoint = %req.endpoints[i],
+                    error = %status.message(),
+                    "Cluster member did not report its status"
+                ),
+            }
+        }
+        let Some((i, mut client)) = coordinator else {
+            return Err(TappError::ServiceUnavailable {
+                service: format!("a coordinator for cluster {}", req.cluster_id),
+            }
+            .into());
+        };
+        let coordinator = &req.endpoints[i];
+        let peer_endpoints = req
+            .endpoints
+            .iter()
+            .enumerate()
+            .filter(|(j, _)| *j != i)
+            .map(|(_, url)| url.clone())
+            .collect();
+        let response = client
+            .recover_share(RecoverShareRequest {
+                cluster_id: req.cluster_id.clone(),
+                share_index: share_index as u32,
+                public_key: node_key.public_key().to_vec(),
+                timestamp,
+                evidence: evidence.evidence,
+                peer_endpoints,
+            })
+            .await?
+            .into_inner();
+
+        let response_report_data = cluster::exchange::response_report_data(
+            &request_report_data,
+            &response.public_key,
+            &response.sealed_share,
+        );
+        let (coordinator_signer, _) = cluster::exchange::check_node(
+            self.cluster_verifier.as_ref(),
+            self.cluster_whitelist.as_ref(),
+            coordinator,
+            &response.evidence,
+            &response_report_data,
+            &response.public_key,
+        )
+        .await
+        .inspect_err(|e| {
+            tracing::warn!(
+                endpoint = %coordinator,
+                cluster_id = %req.cluster_id,
+                error = %e,
+                event = "CLUSTER_COORDINATOR_REJECTED",
+                "Recovery coordinator rejected; its share was not opened"
+            );
+        })?;
+        let share = cluster::exchange::open_share(
+            &node_key,
+            &req.cluster_id,
+            &label,
+            &response.sealed_share,
+        )?;
+        if share.index != share_index {
+            return Err(TappError::Crypto(format!(
+                "Sealed share {} is not the requested share {}",
+                share.index, share_index
+            ))
+            .into());
+        }
+        let status = self
+            .cluster
+            .join(
+                &req.cluster_id,
+                share_count("total_shares", response.total_shares)?,
+                share_count("threshold", response.threshold)?,
+                response.initialized_at,
+                share,
+                &sealing_key,
+            )
+            .await?;
+        tracing::warn!(
+            cluster_id = %status.cluster_id,
+            share_index,
+            coordinator = %coordinator_signer,
+            contributors = ?response.contributors,
+            key_name = %identity.key_name,
+            event = "CLUSTER_RECOVERED_BY_OPERATOR",
+            "Failed member replaced by operator"
+        );
+
+        let mut response = Response::new(RecoverClusterResponse {
+            success: true,
+            message: format!(
+                "Recovered share {} of cluster {} through {}",
+                share_index, status.cluster_id, coordinator
+            ),
+            status: Some(self.cluster_status_proto(&status).await),
+            coordinator: coordinator.clone(),
+        });
+        response.extensions_mut().insert(AuditAnnotation(format!(
+            "cluster={} share_index={} coordinator={}",
+            status.cluster_id, share_index, coordinator_signer
+        )));
+        Ok(response)
+    }
+
+    async fn export_keystore_backup(
+        &self,
+        request: Request<ExportKeystoreBackupRequest>,
+    ) -> Result<Response<ExportKeystoreBackupResponse>, Status> {
+        // SECURITY: Takes every key of the node off it; admin keys only
+        let identity = request.extensions().get::<AuthIdentity>().cloned();
+        let Some(identity) = identity.filter(|identity| identity.role == ApiKeyRole::Admin) else {
+            tracing::warn!(
+                remote_addr = ?request.remote_addr(),
+                event = "KEYSTORE_EXPORT_DENIED",
+                "ExportKeystoreBackup called without an admin API key"
+            );
+            return Err(Status::permission_denied(
+                "ExportKeystoreBackup requires an admin API key",
+            ));
+        };
+        let remote_addr = request.remote_addr();
+        let req = request.into_inner();
+
+        let backup = self
+            .app_key_service
+            .export_backup(&req.recovery_public_key)
+            .await?;
+        // The backup leaves the node only once its export is attested
+        let record = serde_json::json!({
+            "manifest_hash": backup.manifest_hash,
+            "created_at": backup.created_at,
+            "recovery_key": backup.recovery_key,
+            "manifest": backup.manifest,
+        });
+        self.boot_service
+            .attest_keystore_operation(
+                boot::OPERATION_NAME_EXPORT_KEYSTORE_BACKUP,
+                &record.to_string(),
+            )
+            .await?;
+
+        tracing::warn!(
+            key_name = %identity.key_name,
+            remote_addr = ?remote_addr,
+            keys = backup.manifest.len(),
+            manifest_hash = %backup.manifest_hash,
+            recovery_key = %backup.recovery_key,
+            event = "KEYSTORE_EXPORTED_BY_OPERATOR",
+            "Keystore backup exported by operator"
+        );
+
+        let mut response = Response::new(ExportKeystoreBackupResponse {
+            success: true,
+            message: format!("Exported {} keys", backup.manifest.len()),
+            backup: backup.to_bytes()?,
+            manifest: backup
+                .manifest
+                .iter()
+                .map(|entry| entry.to_proto())
+                .collect(),
+            manifest_hash: backup.manifest_hash.clone(),
+            created_at: backup.created_at,
+        });
+        response.extensions_mut().insert(AuditAnnotation(format!(
+            "keystore_export manifest_hash={} keys={} recovery_key={}",
+            backup.manifest_hash,
+            backup.manifest.len(),
+            backup.recovery_key
+        )));
+        Ok(response)
+    }
+
+    async fn import_keystore_backup(
+        &self,
+        request: Request<ImportKeystoreBackupRequest>,
+    ) -> Result<Response<ImportKeystoreBackupResponse>, Status> {
+        // SECURITY: Replaces keys of the node; admin keys only
+        let identity = request.extensions().get::<AuthIdentity>().cloned();
+        let Some(identity) = identity.filter(|identity| identity.role == ApiKeyRole::Admin) else {
+            tracing::warn!(
+                remote_addr = ?request.remote_addr(),
+                event = "KEYSTORE_IMPORT_DENIED",
+                "ImportKeystoreBackup called without an admin API key"
+            );
+            return Err(Status::permission_denied(
+                "ImportKeystoreBackup requires an admin API key",
+            ));
+        };
+        // The recovery private key never crosses a network, not even
+        // loopback TCP
+        if request
+            .extensions()
+            .get::<tonic::transport::server::UdsConnectInfo>()
+            .is_none()
+        {
+            tracing::error!(
+                key_name = %identity.key_name,
+                remote_addr = ?request.remote_addr(),
+                event = "KEYSTORE_IMPORT_DENIED",
+                reason = "not over the unix socket",
+                "Rejected ImportKeystoreBackup request"
+            );
+            return Err(Status::permission_denied(
+                "ImportKeystoreBackup can only be called over the local unix socket",
+            ));
+        }
+        let req = request.into_inner();
+        let mode = app_key::backup::ImportMode::from_proto(req.mode());
+        let on_conflict = app_key::backup::ConflictPolicy::from_proto(req.on_conflict());
+
+        let report = match self
+            .app_key_service
+            .import_backup(&req.backup, &req.recovery_private_key, mode, on_conflict)
+            .await
+        {
+            Ok(report) => report,
+            Err(e) => {
+                tracing::warn!(
+                    key_name = %identity.key_name,
+                    error = %e,
+                    event = "KEYSTORE_IMPORT_FAILED",
+                    "Keystore backup import failed"
+                );
+                return Err(e.into());
+            }
+        };
+        let record = serde_json::json!({
+            "mode": mode,
+            "on_conflict": on_conflict,
+            "report": report,
+        });
+        if let Err(e) = self
+            .boot_service
+            .attest_keystore_operation(
+                boot::OPERATION_NAME_IMPORT_KEYSTORE_BACKUP,
+                &record.to_string(),
+            )
+            .await
+        {
+            tracing::error!(
+                key_name = %identity.key_name,
+                manifest_hash = %report.manifest_hash,
+                error = %e,
+                event = "KEYSTORE_IMPORT_UNATTESTED",
+                "Keystore backup imported, but its record was not extended"
+            );
+            return Err(Status::internal(format!(
+                "Keys were imported, but the runtime measurement was not extended: {}",
+                e
+            )));
+        }
+
+        tracing::warn!(
+            key_name = %identity.key_name,
+            manifest_hash = %report.manifest_hash,
+            mode = ?mode,
+            on_conflict = ?on_conflict,
+            imported = report.imported.len(),
+            overwritten = report.overwritten.len(),
+            kept = report.kept.len(),
+            removed = report.removed.len(),
+            event = "KEYSTORE_IMPORTED_BY_OPERATOR",
+            "Keystore backup imported by operator"
+        );
+
+        let entries = |entries: &[app_key::backup::ManifestEntry]| -> Vec<KeystoreEntry> {
+            entries.iter().map(|entry| entry.to_proto()).collect()
+        };
+        let mut response = Response::new(ImportKeystoreBackupResponse {
+            success: true,
+            message: format!(
+                "Imported {} keys, overwrote {}, kept {}, removed {}",
+                report.imported.len(),
+                report.overwritten.len(),
+                report.kept.len(),
+                report.removed.len()
+            ),
+            manifest_hash: report.manifest_hash.clone(),
+            created_at: report.created_at,
+            imported: entries(&report.imported),
+            overwritten: entries(&report.overwritten),
+            kept: entries(&report.kept),
+            unchanged: entries(&report.unchanged),
+            removed: entries(&report.removed),
+        });
+        response.extensions_mut().insert(AuditAnnotation(format!(
+            "keystore_import manifest_hash={} mode={:?} imported={} overwritten={} removed={}",
+            report.manifest_hash,
+            mode,
+            report.imported.len(),
+            report.overwritten.len(),
+            report.removed.len()
+        )));
+        Ok(response)
+    }
+
+    async fn verify_rtmr_replay(
+        &self,
+        request: Request<VerifyRtmrReplayRequest>,
+    ) -> Result<Response<VerifyRtmrReplayResponse>, Status> {
+        let req = request.into_inner();
+        let rtmr_index = req.rtmr_index.unwrap_or(boot::rtmr::TAPP_RTMR_INDEX);
+
+        let (replay, quoted) = self.boot_service.replay_rtmr(rtmr_index).await?;
+        let first_divergent_step = replay.first_divergent_step(&quoted);
+        let replayed = replay.value();
+        let message = match first_divergent_step {
+            None => format!(
+                "RTMR{} matches the replay of {} extends",
+                rtmr_index,
+                replay.steps.len()
+            ),
+            Some(step) => {
+                tracing::warn!(
+                    rtmr_index = rtmr_index,
+                    steps = replay.steps.len(),
+                    first_divergent_step = step,
+                    event = "RTMR_REPLAY_MISMATCH",
+                    "Quoted RTMR differs from the replay of the extend history"
+                );
+                format!(
+                    "RTMR{} does not match the replay of {} extends from step {}",
+                    rtmr_index,
+                    replay.steps.len(),
+                    step
+                )
+            }
+        };
+
+        Ok(Response::new(VerifyRtmrReplayResponse {
+            success: true,
+            message,
+            rtmr_index,
+            initial_value: replay.initial.to_vec(),
+            replayed_value: replayed.to_vec(),
+            quoted_value: quoted,
+            steps: replay
+                .steps
+                .into_iter()
+                .map(|step| RtmrReplayStep {
+                    domain: step.record.domain,
+                    operation: step.record.operation,
+                    content: step.record.content,
+                    timestamp: step.record.timestamp,
+                    event_digest: step.event_digest.to_vec(),
+                    rtmr_value: step.rtmr.to_vec(),
+                })
+                .collect(),
+            matches: first_divergent_step.is_none(),
+            first_divergent_step: first_divergent_step.map(|step| step as u32),
+        }))
+    }
+}
+
+/// Handles to the subscriber installed by `init_tracing`
+pub struct TracingHandles {
+    /// Swaps the log filter at runtime (SetLogLevel)
+    pub log_level: log_level::LogLevelControl,
+    /// Shut down on exit so the last batch of spans is not lost
+    pub telemetry: telemetry::TelemetryGuard,
+    /// Writes the buffered file log lines when dropped; drop it before exiting
+    pub log_writer: Option<tracing_appender::non_blocking::WorkerGuard>,
+}
+
+/// Initialize tracing based on configuration
+/// With `telemetry` set, spans are also exported over OTLP.
+pub fn init_tracing(
+    config: &config::LoggingConfig,
+    telemetry: Option<&config::TelemetryConfig>,
+) -> TappResult<TracingHandles> {
+    use tracing_subscriber::{
+        fmt::{self, format::FmtSpan},
+        layer::SubscriberExt,
+        util::SubscriberInitExt,
+        EnvFilter, Layer,
+    };
+
+    let provider = telemetry.map(telemetry::otlp_provider).transpose()?;
+
+    // Trace spans are only recorded when exported
+    let trace_directive = format!(
+        "{}={}",
+        telemetry::SPAN_TARGET,
+        if provider.is_some() { "info" } else { "off" }
+    );
+    let level = match std::env::var(EnvFilter::DEFAULT_ENV) {
+        Ok(env) if EnvFilter::try_new(&env).is_ok() => env,
+        _ => config.level.clone(),
+    };
+    let (filter, log_level) = log_level::LogLevelControl::new(&level, vec![trace_directive])
+        .map_err(|e| error::ConfigError::InvalidValue {
+            field: "logging.level".to_string(),
+            reason: format!("Invalid log level: {}", e),
+        })?;
+
+    let stdout_layer = match config.format.as_str() {
+        "json" => fmt::layer()
+            .json()
+            .with_writer(std::io::stdout)
+            .with_span_events(FmtSpan::CLOSE)
+            .with_filter(telemetry::without_trace_spans())
+            .boxed(),
+        "pretty" => fmt::layer()
+            .pretty()
+            .with_writer(std::io::stdout)
+            .with_ansi(true)
+            .with_span_events(FmtSpan::CLOSE)
+            .with_filter(telemetry::without_trace_spans())
+            .boxed(),
+        _ => {
+            return Err(error::ConfigError::InvalidValue {
+                field: "logging.format".to_string(),
+                reason: format!("Unsupported log format: {}", config.format),
+            }
+            .into());
+        }
+    };
+
+    let mut log_writer = None;
+    if let Some(file_path) = &config.file_path {
+        // A directory gets an `app.log` in it; rotated files sit next to the active one
+        let path = if file_path.to_string_lossy().ends_with('/') || file_path.is_dir() {
+            file_path.join("app.log")
+        } else {
+            file_path.clone()
+        };
+
+        let file_appender = rolling_file::RollingFileWriter::new(
+            &path,
+            config.max_file_size_mb.max(1),
+            config.max_files,
+        )
+        .map_err(|e| error::ConfigError::InvalidValue {
+            field: "logging.file_path".to_string(),
+            reason: format!("Cannot open log file: {}", e),
+        })?;
+
+        let (non_blocking, guard) = tracing_appender::non_blocking(file_appender);
+        log_writer = Some(guard);
+
+        let file_layer = match config.format.as_str() {
+            "json" => fmt::layer()
+                .json()
+                .with_writer(non_blocking)
+                .with_ansi(false)
+                .with_span_events(FmtSpan::CLOSE)
+                .with_filter(telemetry::without_trace_spans())
+                .boxed(),
+            "pretty" => fmt::layer()
+                .with_writer(non_blocking)
+                .with_ansi(false)
+                .with_span_events(FmtSpan::CLOSE)
+                .with_filter(telemetry::without_trace_spans())
+                .boxed(),
+            _ => unreachable!(),
+        };
+
+        tracing_subscriber::registry()
+            .with(filter)
+            .with(stdout_layer)
+            .with(file_layer)
+            .with(provider.as_ref().map(|provider| {
+                telemetry::otel_layer(provider).with_filter(telemetry::only_trace_spans())
+            }))
+            .init();
+
+        // Rotation keeps `max_files` itself; this clears files left by a larger
+        // max_files or by the daily rotation of earlier versions
+        match rolling_file::sweep_old_files(&path, config.max_files) {
+            Ok(removed) => {
+                for file in removed {
+                    info!(file = %file.display(), "Removed old log file");
+                }
+            }
+            Err(e) => tracing::warn!(error = %e, "Failed to remove old log files"),
+        }
+    } else {
+        tracing_subscriber::registry()
+            .with(filter)
+            .with(stdout_layer)
+            .with(provider.as_ref().map(|provider| {
+                telemetry::otel_layer(provider).with_filter(telemetry::only_trace_spans())
+            }))
+            .init();
+    }
+
+    Ok(TracingHandles {
+        log_level,
+        telemetry: provider
+            .map(telemetry::TelemetryGuard::new)
+            .unwrap_or_default(),
+        log_writer,
+    })
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Service with in-memory app keys and an event-log enabled AA config,
+    /// keeping app files in `aa_dir` too
+    async fn create_test_service(
+        mut config: TappConfig,
+        aa_dir: &std::path::Path,
+    ) -> TappServiceImpl {
+        config.boot.aa_config_path = Some(aa_dir.join("aa.toml").to_string_lossy().to_string());
+        config.boot.data_dir = aa_dir.join("apps");
+        config.boot.extend_history_path = aa_dir
+            .join("rtmr-history.jsonl")
+            .to_string_lossy()
+            .to_string();
+        config.cluster.state_dir = aa_dir.join("cluster").to_string_lossy().to_string();
+        TappServiceImpl::new(config).await.unwrap()
+    }
+
+    /// Random deployer key pair: (32-byte private key, 64-byte public key)
+    fn create_deployer_keypair() -> (Vec<u8>, Vec<u8>) {
+        use k256::ecdsa::SigningKey;
+        use k256::elliptic_curve::{rand_core::OsRng, sec1::ToEncodedPoint};
+
+        let signing_key = SigningKey::random(&mut OsRng);
+        let public_key = signing_key.verifying_key().to_encoded_point(false);
+        (
+            signing_key.to_bytes().to_vec(),
+            public_key.as_bytes()[1..].to_vec(),
+        )
+    }
+
+    /// Signed GetAppSecretKey request arriving from localhost
+    fn create_secret_key_request(
+        app_id: &str,
+        deployer_private_key: &[u8],
+    ) -> Request<GetAppSecretKeyRequest> {
+        let nonce = nonce_manager::generate_nonce();
+        let timestamp = utils::current_timestamp();
+        let message =
+            app_key::deployer::request_message_v2(app_id, &nonce, timestamp, "get_secret_key", b"");
+
+        let mut request = Request::new(GetAppSecretKeyRequest {
+            app_id: app_id.to_string(),
+            nonce,
+            timestamp,
+            signature: app_key::deployer::sign_request_v2(deployer_private_key, &message).unwrap(),
+        });
+        request
+            .extensions_mut()
+            .insert(tonic::transport::server::TcpConnectInfo {
+                local_addr: None,
+                remote_addr: Some("127.0.0.1:40000".parse().unwrap()),
+            });
+        request
+    }
+
+    #[tokio::test]
+    async fn test_transfer_app_ownership() {
+        let aa_dir = tempfile::tempdir().unwrap();
+        let service = create_test_service(TappConfig::default(), aa_dir.path()).await;
+        let app_id = "transfer-app";
+        let (old_private_key, old_public_key) = create_deployer_keypair();
+        let (new_private_key, new_public_key) = create_deployer_keypair();
+
+        service
+            .boot_service
+            .insert_app_measurement(AppMeasurement {
+                app_id: app_id.to_string(),
+                compose_hash: String::new(),
+                volumes_hash: String::new(),
+                deployer: hex::encode(&old_public_key),
+                deployer_address: hex::encode(app_key::eth_address(&old_public_key)),
+                timestamp: utils::current_timestamp(),
+                namespace: None,
+                secret_names: Vec::new(),
+            })
+            .await;
+        service
+            .app_key_service
+            .get_app_key(app_id, "ethereum")
+            .await
+            .unwrap();
+
+        // Current deployer signs app_id || new_deployer || nonce || timestamp
+        let nonce = nonce_manager::generate_nonce();
+        let timestamp = utils::current_timestamp();
+        let message = app_key::deployer_request_message(
+            "transfer_ownership",
+            app_id,
+            &new_public_key,
+            &nonce,
+            timestamp,
+        );
+        let response = service
+            .transfer_app_ownership(Request::new(TransferAppOwnershipRequest {
+                app_id: app_id.to_string(),
+                new_deployer: new_public_key.clone(),
+                nonce,
+                timestamp,
+                signature: app_key::sign_message(&old_private_key, &message).unwrap(),
+            }))
+            .await
+            .unwrap()
+            .into_inner();
+        assert!(response.success);
+        assert_eq!(response.previous_deployer, hex::encode(&old_public_key));
+        assert_eq!(response.new_deployer, hex::encode(&new_public_key));
+
+        // The old key no longer controls the app
+        let status = service
+            .get_app_secret_key(create_secret_key_request(app_id, &old_private_key))
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), tonic::Code::PermissionDenied);
+
+        // The new key does
+        let response = service
+            .get_app_secret_key(create_secret_key_request(app_id, &new_private_key))
+            .await
+            .unwrap()
+            .into_inner();
+        assert!(response.success);
+
+        let events = service.boot_service.get_app_events(app_id).await;
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].event, boot::OPERATION_NAME_TRANSFER_OWNERSHIP);
+    }
+
+    #[tokio::test]
+    async fn test_transfer_to_address_only_deployer() {
+        let aa_dir = tempfile::tempdir().unwrap();
+        let service = create_test_service(TappConfig::default(), aa_dir.path()).await;
+        let app_id = "address-app";
+        let (old_private_key, old_public_key) = create_deployer_keypair();
+        let (new_private_key, new_public_key) = create_deployer_keypair();
+        let new_address = app_key::eth_address(&new_public_key);
+
+        service
+            .boot_service
+            .insert_app_measurement(AppMeasurement {
+                app_id: app_id.to_string(),
+                compose_hash: String::new(),
+                volumes_hash: String::new(),
+                deployer: hex::encode(&old_public_key),
+                deployer_address: hex::encode(app_key::eth_address(&old_public_key)),
+                timestamp: utils::current_timestamp(),
+                namespace: None,
+                secret_names: Vec::new(),
+            })
+            .await;
+        service
+            .app_key_service
+            .get_app_key(app_id, "ethereum")
+            .await
+            .unwrap();
+
+        let nonce = nonce_manager::generate_nonce();
+        let timestamp = utils::current_timestamp();
+        let message = app_key::deployer_request_message(
+            "transfer_ownership",
+            app_id,
+            &new_address,
+            &nonce,
+            timestamp,
+        );
+        let response = service
+            .transfer_app_ownership(Request::new(TransferAppOwnershipRequest {
+                app_id: app_id.to_string(),
+                new_deployer: new_address.clone(),
+                nonce,
+                timestamp,
+                signature: app_key::sign_message(&old_private_key, &message).unwrap(),
+            }))
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(response.new_deployer, hex::encode(&new_address));
+        let measurement = service
+            .boot_service
+            .get_app_measurement(app_id)
+            .await
+            .unwrap();
+        assert_eq!(measurement.deployer_address, hex::encode(&new_address));
+
+        // Only the address is known: the v2 signature is checked against it
+        let response = service
+            .get_app_secret_key(create_secret_key_request(app_id, &new_private_key))
+            .await
+            .unwrap()
+            .into_inner();
+        assert!(response.success);
+
+        // A signature by the previous owner is not accepted anymore
+        let status = service
+            .get_app_secret_key(create_secret_key_request(app_id, &old_private_key))
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), tonic::Code::PermissionDenied);
+    }
+
+    #[tokio::test]
+    async fn test_secret_key_signature_versions() {
+        let aa_dir = tempfile::tempdir().unwrap();
+        let service = create_test_service(TappConfig::default(), aa_dir.path()).await;
+        let (private_key, public_key) = create_deployer_keypair();
+        let address = app_key::eth_address(&public_key);
+
+        // Same deployer, stored with its public key and by address only
+        for (app_id, deployer) in [("by-key", &public_key), ("by-address", &address)] {
+            let identity = DeployerIdentity::from_bytes(deployer).unwrap();
+            service
+                .boot_service
+                .insert_app_measurement(AppMeasurement {
+                    app_id: app_id.to_string(),
+                    compose_hash: String::new(),
+                    volumes_hash: String::new(),
+                    deployer: identity.canonical_hex(),
+                    deployer_address: identity.address_hex(),
+                    timestamp: utils::current_timestamp(),
+                    namespace: None,
+                    secret_names: Vec::new(),
+                })
+                .await;
+            service
+                .app_key_service
+                .get_app_key(app_id, "ethereum")
+                .await
+                .unwrap();
+        }
+        // v2 request signed for `operation`, or legacy 64-byte request
+        let signed = |app_id: &str, operation: Option<&str>| {
+            let mut request = create_secret_key_request(app_id, &private_key);
+            let req = request.get_mut();
+            req.signature = match operation {
+                Some(operation) => {
+                    let message = app_key::deployer::request_message_v2(
+                        app_id,
+                        &req.nonce,
+                        req.timestamp,
+                        operation,
+                        b"",
+                    );
+                    app_key::deployer::sign_request_v2(&private_key, &message).unwrap()
+                }
+                None => {
+                    let message = app_key::deployer_request_message(
+                        "get_secret_key",
+                        app_id,
+                        b"",
+                        &req.nonce,
+                        req.timestamp,
+                    );
+                    app_key::sign_message(&private_key, &message).unwrap()
+                }
+            };
+            request
+        };
+
+        // v2 signatures work for both
+        for app_id in ["by-key", "by-address"] {
+            let response = service
+                .get_app_secret_key(signed(app_id, Some("get_secret_key")))
+                .await
+                .unwrap()
+                .into_inner();
+            assert!(response.success);
+        }
+
+        // The legacy scheme is refused, even where the public key is known
+        for app_id in ["by-key", "by-address"] {
+            let status = service
+                .get_app_secret_key(signed(app_id, None))
+                .await
+                .unwrap_err();
+            assert_eq!(status.code(), tonic::Code::InvalidArgument);
+        }
+
+        // A v2 signature for another operation is rejected
+        let status = service
+            .get_app_secret_key(signed("by-address", Some("stop_app")))
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), tonic::Code::PermissionDenied);
+    }
+
+    #[tokio::test]
+    async fn test_sign_transaction() {
+        let aa_dir = tempfile::tempdir().unwrap();
+        let mut config = TappConfig::default();
+        config
+            .boot
+            .key_policy
+            .allowed_chain_ids
+            .insert("wallet-app".to_string(), vec![16601]);
+        let service = create_test_service(config, aa_dir.path()).await;
+        let (private_key, public_key) = create_deployer_keypair();
+        service
+            .boot_service
+            .insert_app_measurement(AppMeasurement {
+                app_id: "wallet-app".to_string(),
+                compose_hash: String::new(),
+                volumes_hash: String::new(),
+                deployer: hex::encode(&public_key),
+                deployer_address: hex::encode(app_key::eth_address(&public_key)),
+                timestamp: utils::current_timestamp(),
+                namespace: None,
+                secret_names: Vec::new(),
+            })
+            .await;
+        let app_key = service
+            .app_key_service
+            .get_app_key("wallet-app", "ethereum")
+            .await
+            .unwrap();
+        let app_private_key = service
+            .app_key_service
+            .get_private_key("wallet-app")
+            .await
+            .unwrap();
+
+        let transfer = SignTransactionRequest {
+            app_id: "wallet-app".to_string(),
+            chain_id: 16601,
+            tx_nonce: 4,
+            max_priority_fee_per_gas: 1_000_000_000,
+            max_fee_per_gas: 20_000_000_000,
+            gas_limit: 60_000,
+            to: vec![0x35; 20],
+            value: vec![0x0d, 0xe0, 0xb6, 0xb3, 0xa7, 0x64, 0x00, 0x00],
+            data: vec![0xa9, 0x05, 0x9c, 0xbb],
+            access_list: vec![proto::AccessListItem {
+                address: vec![0x35; 20],
+                storage_keys: vec![vec![0; 32]],
+            }],
+            ..Default::default()
+        };
+        // Deployer-signed over the signing hash of `signed_for`
+        let signed = |req: &SignTransactionRequest, signed_for: &SignTransactionRequest| {
+            let mut req = req.clone();
+            req.nonce = nonce_manager::generate_nonce();
+            req.timestamp = utils::current_timestamp();
+            let signing_hash = unsigned_transaction(signed_for).unwrap().signing_hash();
+            let message = app_key::deployer::request_message_v2(
+                &req.app_id,
+                &req.nonce,
+                req.timestamp,
+                "sign_transaction",
+                &signing_hash,
+            );
+            req.signature = app_key::deployer::sign_request_v2(&private_key, &message).unwrap();
+            let mut request = Request::new(req);
+            request
+                .extensions_mut()
+                .insert(tonic::transport::server::TcpConnectInfo {
+                    local_addr: None,
+                    remote_addr: Some("127.0.0.1:40000".parse().unwrap()),
+                });
+            request
+        };
+
+        let response = service
+            .sign_transaction(signed(&transfer, &transfer))
+            .await
+            .unwrap();
+        assert_eq!(
+            response.extensions().get::<AuditAnnotation>().unwrap().0,
+            format!(
+                "chain_id=16601 tx_hash=0x{}",
+                hex::encode(&response.get_ref().tx_hash)
+            )
+        );
+        let response = response.into_inner();
+        let expected = unsigned_transaction(&transfer)
+            .unwrap()
+            .sign(&app_private_key)
+            .unwrap();
+        assert_eq!(response.raw_transaction, expected.raw);
+        assert_eq!(response.tx_hash, expected.hash);
+        assert_eq!(response.raw_transaction[0], contract::tx::EIP1559_TX_TYPE);
+        assert_eq!(response.eth_address, app_key.eth_address);
+
+        // Legacy transactions only when asked for
+        let legacy = SignTransactionRequest {
+            max_priority_fee_per_gas: 0,
+            max_fee_per_gas: 0,
+            gas_price: 20_000_000_000,
+            access_list: Vec::new(),
+            ..transfer.clone()
+        };
+        let status = service
+            .sign_transaction(signed(&legacy, &transfer))
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), tonic::Code::InvalidArgument);
+        let legacy = SignTransactionRequest {
+            legacy: true,
+            ..legacy
+        };
+        let response = service
+            .sign_transaction(signed(&legacy, &legacy))
+            .await
+            .unwrap()
+            .into_inner();
+        // An RLP list rather than a typed envelope
+        assert!(response.raw_transaction[0] >= 0xc0);
+
+        // The signature covers the transaction
+        let more = SignTransactionRequest {
+            value: vec![0xff; 8],
+            ..transfer.clone()
+        };
+        let status = service
+            .sign_transaction(signed(&more, &transfer))
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), tonic::Code::PermissionDenied);
+
+        // Chains outside the app's key policy are refused
+        let mainnet = SignTransactionRequest {
+            chain_id: 1,
+            ..transfer.clone()
+        };
+        let status = service
+            .sign_transaction(signed(&mainnet, &mainnet))
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), tonic::Code::PermissionDenied);
+    }
+
+    #[test]
+    fn test_validate_app_id() {
+        assert!(utils::validate_app_id("my-app"));
+        assert!(utils::validate_app_id("app_123"));
+        assert!(utils::validate_app_id("test-application-1"));
+
+        assert!(!utils::validate_app_id("ab")); // too short
+        assert!(!utils::validate_app_id("a".repeat(65).as_str())); // too long
+        assert!(!utils::validate_app_id("app@123")); // invalid character
+        assert!(!utils::validate_app_id("app space")); // contains space
+    }
+
+    #[test]
+    fn test_sha256_hex() {
+        let data = b"hello world";
+        let hash = utils::sha256_hex(data);
+        assert_eq!(
+            hash,
+            "b94d27b9934d3e08a52e52d7da7dabfac484efe37a5380ee9088f7ace2efcde9"
+        );
+    }
+
+    #[test]
+    fn test_format_bytes() {
+        assert_eq!(utils::format_bytes(0), "0 B");
+        assert_eq!(utils::format_bytes(1024), "1.00 KB");
+        assert_eq!(utils::format_bytes(1536), "1.50 KB");
+        assert_eq!(utils::format_bytes(1048576), "1.00 MB");
+    }
+
+    #[test]
+    fn test_pad_to_length() {
+        let data = b"hello";
+        let padded = utils::pad_to_length(data, 10);
+        assert_eq!(padded.len(), 10);
+        assert_eq!(&padded[0..5], b"hello");
+        assert_eq!(&padded[5..], &[0u8; 5]);
+    }
+
+    #[tokio::test]
+    async fn test_start_app_rejects_oversized_mount_files() {
+        let aa_dir = tempfile::tempdir().unwrap();
+        let mut config = TappConfig::default();
+        config
+            .server
+            .method_max_recv_message_mb
+            .insert("StartApp".to_string(), 1);
+        let service = create_test_service(config, aa_dir.path()).await;
+
+        let request = StartAppRequest {
+            compose_content: "services:\n  web:\n    image: nginx\n".to_string(),
+            app_id: "oversized-app".to_string(),
+            mount_files: vec![
+                MountFile {
+                    source_path: "./a.bin".to_string(),
+                    content: vec![0; 600 * 1024],
+                    mode: "0644".to_string(),
+                    encrypted: false,
+                },
+                MountFile {
+                    source_path: "./b.bin".to_string(),
+                    content: vec![0; 600 * 1024],
+                    mode: "0644".to_string(),
+                    encrypted: false,
+                },
+            ],
+            deployer: vec![0; 64],
+            register_on_chain: None,
+            platform: String::new(),
+            service_platforms: Default::default(),
+        };
+
+        let status = service.start_app(Request::new(request)).await.unwrap_err();
+        assert_eq!(status.code(), tonic::Code::InvalidArgument);
+        assert!(status.message().starts_with("StartApp upload too large"));
+        assert!(status.message().contains("2 mount file(s)"));
+    }
+
+    #[tokio::test]
+    async fn test_get_attested_app_key_rejects() {
+        let aa_dir = tempfile::tempdir().unwrap();
+        let service = create_test_service(TappConfig::default(), aa_dir.path()).await;
+        let request = |nonce: Vec<u8>| {
+            Request::new(GetAttestedAppKeyRequest {
+                app_id: "no-such-app".to_string(),
+                nonce,
+            })
+        };
+
+        for nonce in [vec![], vec![0; 65]] {
+            let status = service
+                .get_attested_app_key(request(nonce))
+                .await
+                .unwrap_err();
+            assert_eq!(status.code(), tonic::Code::InvalidArgument);
+            assert!(status.message().starts_with("Nonce must be 1 to 64 bytes"));
+        }
+
+        let status = service
+            .get_attested_app_key(request(vec![1; 32]))
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), tonic::Code::NotFound);
+        assert_eq!(status.message(), "App no-such-app not found");
+    }
+
+    #[tokio::test]
+    async fn test_get_app_logs_sources() {
+        let aa_dir = tempfile::tempdir().unwrap();
+        let service = create_test_service(TappConfig::default(), aa_dir.path()).await;
+        let request = |source: &str| {
+            Request::new(GetAppLogsRequest {
+                app_id: "no-such-app".to_string(),
+                source: source.to_string(),
+                ..Default::default()
+            })
+        };
+
+        let status = service.get_app_logs(request("journal")).await.unwrap_err();
+        assert_eq!(status.code(), tonic::Code::InvalidArgument);
+        assert!(status.message().starts_with("Invalid source"));
+
+        let status = service.get_app_logs(request("deploy")).await.unwrap_err();
+        assert_eq!(status.code(), tonic::Code::InvalidArgument);
+        assert!(status.message().contains("App no-such-app not found"));
+    }
+
+    #[tokio::test]
+    async fn test_watch_task() {
+        use tokio_stream::StreamExt;
+
+        let aa_dir = tempfile::tempdir().unwrap();
+        let state_dir = aa_dir.path().join("tasks");
+        // A deployment that was running when the service last stopped
+        let task = {
+            let manager = boot::TaskManager::with_retention(std::time::Duration::from_secs(60), 10)
+                .with_store(&state_dir)
+                .unwrap();
+            let task = manager
+                .create_task(boot::TaskKind::StartApp, "watched-app", &[], None)
+                .await;
+            manager.mark_running(&task.id).await;
+            task
+        };
+        let mut config = TappConfig::default();
+        config.boot.task_state_dir = state_dir.to_string_lossy().to_string();
+        let service = create_test_service(config, aa_dir.path()).await;
+
+        let request = |task_id: &str| {
+            Request::new(WatchTaskRequest {
+                task_id: task_id.to_string(),
+            })
+        };
+
+        // A finished task sends its status and ends the stream
+        let messages: Vec<_> = service
+            .watch_task(request(&task.id))
+            .await
+            .unwrap()
+            .into_inner()
+            .collect()
+            .await;
+        assert_eq!(messages.len(), 1);
+        let message = messages[0].as_ref().unwrap();
+        assert_eq!(message.app_id, "watched-app");
+        assert_eq!(message.status, TaskStatus::Failed as i32);
+        assert_eq!(
+            message.result.as_ref().unwrap().error_code,
+            TaskErrorCode::Interrupted as i32
+        );
+
+        let status = service
+            .watch_task(request("no-such-task"))
+            .await
+            .err()
+            .unwrap();
+        assert_eq!(status.code(), tonic::Code::NotFound);
+    }
+
+    #[tokio::test]
+    async fn test_secret_key_rejects_other_apps_container() {
+        use container_identity::{ContainerIdentity, ContainerResolver};
+
+        let aa_dir = tempfile::tempdir().unwrap();
+        let mut service = create_test_service(TappConfig::default(), aa_dir.path()).await;
+        let (private_key, _) = create_deployer_keypair();
+
+        let caller: std::net::SocketAddr = "172.18.0.5:40000".parse().unwrap();
+        service.container_resolver = ContainerResolver::with_containers(vec![(
+            caller.ip(),
+            ContainerIdentity {
+                id: "0123abcd".to_string(),
+                name: "other-app-web-1".to_string(),
+                compose_project: Some("other-app".to_string()),
+            },
+        )]);
+
+        let mut request = create_secret_key_request("victim-app", &private_key);
+        request
+            .extensions_mut()
+            .insert(tonic::transport::server::TcpConnectInfo {
+                local_addr: None,
+                remote_addr: Some(caller),
+            });
+
+        let status = service.get_app_secret_key(request).await.unwrap_err();
+        assert_eq!(status.code(), tonic::Code::PermissionDenied);
+        assert!(status
+            .message()
+            .contains("does not belong to app victim-app"));
+
+        // Addresses no running container owns are rejected as well
+        let mut request = create_secret_key_request("victim-app", &private_key);
+        request
+            .extensions_mut()
+            .insert(tonic::transport::server::TcpConnectInfo {
+                local_addr: None,
+                remote_addr: Some("172.18.0.9:40000".parse().unwrap()),
+            });
+        let status = service.get_app_secret_key(request).await.unwrap_err();
+        assert_eq!(status.code(), tonic::Code::PermissionDenied);
+    }
+
+    #[tokio::test]
+    async fn test_container_networks() {
+        let aa_dir = tempfile::tempdir().unwrap();
+        let from = |addr: &str| {
+            let mut request = Request::new(());
+            request
+                .extensions_mut()
+                .insert(tonic::transport::server::TcpConnectInfo {
+                    local_addr: None,
+                    remote_addr: Some(addr.parse().unwrap()),
+                });
+            request
+        };
+
+        // Docker's networks by default
+        let service = create_test_service(TappConfig::default(), aa_dir.path()).await;
+        for (addr, expected) in [
+            ("127.0.0.1:40000", (true, "localhost")),
+            ("172.17.0.2:40000", (true, "docker-network")),
+            ("172.31.255.1:40000", (true, "docker-network")),
+            ("172.16.0.2:40000", (false, "unknown")),
+            ("10.88.0.2:40000", (false, "unknown")),
+        ] {
+            assert_eq!(service.local_source(&from(addr)), expected, "{}", addr);
+        }
+
+        // Configured ones replace them, e.g. podman's
+        let mut config = TappConfig::default();
+        config.boot.container_networks = config::ContainerRuntime::Podman
+            .default_networks()
+            .iter()
+            .map(|network| network.to_string())
+            .collect();
+        let service = create_test_service(config, aa_dir.path()).await;
+        assert_eq!(
+            service.local_source(&from("10.88.0.2:40000")),
+            (true, "docker-network")
+        );
+        assert_eq!(
+            service.local_source(&from("[::ffff:10.89.0.7]:40000")),
+            (true, "docker-network")
+        );
+        assert_eq!(
+            service.local_source(&from("172.18.0.5:40000")),
+            (false, "unknown")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_service_status_reports_expiring_keys_to_admins() {
+        use config::{ApiKeyConfig, ApiKeyEntry, ApiKeyType};
+
+        let now = chrono::Utc::now();
+        let named = |name: &str, expires_in_days: Option<i64>| ApiKeyEntry::Named {
+            name: name.to_string(),
+            key: format!("{}-api-key", name),
+            key_file: None,
+            key_env: None,
+            role: ApiKeyRole::Client,
+            key_type: ApiKeyType::Plain,
+            not_before: None,
+            expires_at: expires_in_days.map(|d| (now + chrono::Duration::days(d)).to_rfc3339()),
+            deprecated: false,
+            namespace: None,
+        };
+
+        let aa_dir = tempfile::tempdir().unwrap();
+        let mut config = TappConfig::default();
+        config.server.api_key = Some(ApiKeyConfig {
+            enabled: true,
+            keys: vec![
+                named("permanent", None),
+                named("far-future", Some(90)),
+                named("contractor", Some(3)),
+                named("expired", Some(-1)),
+            ],
+            protected_methods: vec![],
+            lockout: Default::default(),
+            hmac: Default::default(),
+            expiry_horizon_days: 14,
+            namespaces: false,
+        });
+        let service = create_test_service(config, aa_dir.path()).await;
+
+        let status_request = |role: ApiKeyRole| {
+            let mut request = Request::new(GetServiceStatusRequest::default());
+            request.extensions_mut().insert(AuthIdentity {
+                key_name: "caller".to_string(),
+                role,
+                namespace: None,
+            });
+            request
+        };
+
+        let response = service
+            .get_service_status(status_request(ApiKeyRole::Admin))
+            .await
+            .unwrap()
+            .into_inner();
+        let names: Vec<&str> = response
+            .expiring_keys
+            .iter()
+            .map(|k| k.name.as_str())
+            .collect();
+        assert_eq!(names, vec!["expired", "contractor"]);
+        assert!(response.expiring_keys[0].expired);
+        assert!(!response.expiring_keys[1].expired);
+
+        let response = service
+            .get_service_status(status_request(ApiKeyRole::Client))
+            .await
+            .unwrap()
+            .into_inner();
+        assert!(response.expiring_keys.is_empty());
+        assert_eq!(response.system_version, VERSION);
+    }
+
+    #[tokio::test]
+    async fn test_force_remove_app() {
+        let aa_dir = tempfile::tempdir().unwrap();
+        let service = create_test_service(TappConfig::default(), aa_dir.path()).await;
+        let app_id = "orphaned-app";
+        service
+            .boot_service
+            .insert_app_measurement(AppMeasurement {
+                app_id: app_id.to_string(),
+                compose_hash: String::new(),
+                volumes_hash: String::new(),
+                deployer: "ab".repeat(20),
+                deployer_address: "ab".repeat(20),
+                timestamp: utils::current_timestamp(),
+                namespace: None,
+                secret_names: Vec::new(),
+            })
+            .await;
+        let request = |role: Option<ApiKeyRole>| {
+            let mut request = Request::new(ForceRemoveAppRequest {
+                app_id: app_id.to_string(),
+                purge_volumes: false,
+                purge_images: false,
+            });
+            if let Some(role) = role {
+                request.extensions_mut().insert(AuthIdentity {
+                    key_name: "operator".to_string(),
+                    role,
+                    namespace: None,
+                });
+            }
+            request
+        };
+
+        for role in [None, Some(ApiKeyRole::Client)] {
+            let status = service.force_remove_app(request(role)).await.unwrap_err();
+            assert_eq!(status.code(), tonic::Code::PermissionDenied);
+        }
+        assert!(service
+            .boot_service
+            .get_app_measurement(app_id)
+            .await
+            .is_some());
+
+        let response = service
+            .force_remove_app(request(Some(ApiKeyRole::Admin)))
+            .await
+            .unwrap();
+        let annotation = response.extensions().get::<AuditAnnotation>().unwrap();
+        assert!(annotation.0.contains("measurement"), "{}", annotation.0);
+        let response = response.into_inner();
+        assert!(response.success);
+        assert!(response.measurement_removed);
+        assert!(service
+            .boot_service
+            .get_app_measurement(app_id)
+            .await
+            .is_none());
+
+        // Nothing is left the second time
+        let response = service
+            .force_remove_app(request(Some(ApiKeyRole::Admin)))
+            .await
+            .unwrap()
+            .into_inner();
+        assert!(!response.measurement_removed);
+        assert!(response.message.starts_with("Nothing left to remove"));
+    }
+
+    #[tokio::test]
+    async fn test_keystore_backup() {
+        let aa_dir = tempfile::tempdir().unwrap();
+        let source = create_test_service(TappConfig::default(), aa_dir.path()).await;
+        let other_dir = tempfile::tempdir().unwrap();
+        let target = create_test_service(TappConfig::default(), other_dir.path()).await;
+        let admin = || AuthIdentity {
+            key_name: "operator".to_string(),
+            role: ApiKeyRole::Admin,
+            namespace: None,
+        };
+        let recovery_key = [0x42; 32];
+        let public_key = source
+            .app_key_service
+            .get_app_key("app-1", "ethereum")
+            .await
+            .unwrap()
+            .public_key;
+
+        let export_request = || {
+            Request::new(ExportKeystoreBackupRequest {
+                recovery_public_key: app_key::public_key_from_private(&recovery_key).unwrap(),
+            })
+        };
+        let status = source
+            .export_keystore_backup(export_request())
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), tonic::Code::PermissionDenied);
+
+        let mut request = export_request();
+        request.extensions_mut().insert(admin());
+        let response = source.export_keystore_backup(request).await.unwrap();
+        assert!(response.extensions().get::<AuditAnnotation>().is_some());
+        let export = response.into_inner();
+        assert_eq!(export.manifest.len(), 1);
+        assert_eq!(export.manifest[0].app_id, "app-1");
+
+        let import_request = |uds: bool| {
+            let mut request = Request::new(ImportKeystoreBackupRequest {
+                backup: export.backup.clone(),
+                recovery_private_key: recovery_key.to_vec(),
+                mode: KeystoreImportMode::KeystoreMerge as i32,
+                on_conflict: KeystoreConflictPolicy::ConflictFail as i32,
+            });
+            request.extensions_mut().insert(admin());
+            if uds {
+                request
+                    .extensions_mut()
+                    .insert(tonic::transport::server::UdsConnectInfo {
+                        peer_addr: None,
+                        peer_cred: None,
+                    });
+            } else {
+                request
+                    .extensions_mut()
+                    .insert(tonic::transport::server::TcpConnectInfo {
+                        local_addr: None,
+                        remote_addr: Some("127.0.0.1:40000".parse().unwrap()),
+                    });
+            }
+            request
+        };
+        // Not even from localhost over TCP
+        let status = target
+            .import_keystore_backup(import_request(false))
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), tonic::Code::PermissionDenied);
+
+        let response = target
+            .import_keystore_backup(import_request(true))
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(response.imported, export.manifest);
+        assert_eq!(response.manifest_hash, export.manifest_hash);
+        assert_eq!(
+            target
+                .app_key_service
+                .get_app_key("app-1", "ethereum")
+                .await
+                .unwrap()
+                .public_key,
+            public_key
+        );
+    }
+
+    #[tokio::test]
+    async fn test_verify_rtmr_replay_index() {
+        let aa_dir = tempfile::tempdir().unwrap();
+        let service = create_test_service(TappConfig::default(), aa_dir.path()).await;
+
+        let status = service
+            .verify_rtmr_replay(Request::new(VerifyRtmrReplayRequest {
+                rtmr_index: Some(4),
+            }))
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), tonic::Code::InvalidArgument);
+        assert!(status.message().contains("below 4"), "{}", status.message());
+    }
+
+    #[tokio::test]
+    async fn test_init_cluster() {
+        let aa_dir = tempfile::tempdir().unwrap();
+        let service = create_test_service(TappConfig::default(), aa_dir.path()).await;
+        let request = |role: Option<ApiKeyRole>, total_shares: u32| {
+            let mut request = Request::new(InitClusterRequest {
+                total_shares,
+                threshold: 2,
+                peers: vec!["node-b".to_string(), "node-c".to_string()],
+            });
+            if let Some(role) = role {
+                request.extensions_mut().insert(AuthIdentity {
+                    key_name: "operator".to_string(),
+                    role,
+                    namespace: None,
+                });
+            }
+            request
+        };
+        let cluster_status = || async {
+            service
+                .get_cluster_status(Request::new(GetClusterStatusRequest {}))
+                .await
+                .unwrap()
+                .into_inner()
+                .status
+                .unwrap()
+        };
+        assert_eq!(
+            cluster_status().await.state(),
+            ClusterState::ClusterUninitialized
+        );
+
+        for role in [None, Some(ApiKeyRole::Client)] {
+            let status = service.init_cluster(request(role, 3)).await.unwrap_err();
+            assert_eq!(status.code(), tonic::Code::PermissionDenied);
+        }
+        let admin = |total_shares| request(Some(ApiKeyRole::Admin), total_shares);
+        let status = service.init_cluster(admin(256)).await.unwrap_err();
+        assert_eq!(status.code(), tonic::Code::InvalidArgument);
+
+        let response = service.init_cluster(admin(3)).await.unwrap();
+        let annotation = response.extensions().get::<AuditAnnotation>().unwrap();
+        let initialized = response.into_inner().status.unwrap();
+        assert_eq!(annotation.0, format!("cluster={}", initialized.cluster_id));
+        assert_eq!(initialized.state(), ClusterState::ClusterDistributing);
+        assert_eq!(initialized.own_share_index, 1);
+        assert_eq!(initialized.peers.len(), 2);
+        assert!(initialized.peers.iter().all(|peer| !peer.delivered));
+        assert_eq!(cluster_status().await, initialized);
+        assert!(aa_dir.path().join("cluster/share.sealed").exists());
 
-        // SECURITY: Log successful retrieval
-        tracing::warn!(
-            app_id = %req.app_id,
-            remote_addr = ?remote_addr,
-            source_type = source_type,
-            event = "SECRET_KEY_RETRIEVED",
-            timestamp = %chrono::Utc::now(),
-            "Private key successfully retrieved"
+        let status = service.init_cluster(admin(3)).await.unwrap_err();
+        assert_eq!(status.code(), tonic::Code::FailedPrecondition);
+    }
+
+    /// Whitelist of signer addresses a test can extend, with any code hash
+    #[derive(Clone, Default)]
+    struct SignerWhitelist(Arc<std::sync::Mutex<std::collections::HashSet<String>>>);
+
+    impl cluster::verifier::Whitelist for SignerWhitelist {
+        fn is_allowed<'a>(
+            &'a self,
+            signer_address: &'a str,
+            _code_hash: &'a str,
+        ) -> futures_util::future::BoxFuture<'a, TappResult<bool>> {
+            let allowed = self.0.lock().unwrap().contains(signer_address);
+            Box::pin(async move { Ok(allowed) })
+        }
+    }
+
+    /// Request made with an admin API key
+    fn as_admin<T>(message: T) -> Request<T> {
+        let mut request = Request::new(message);
+        request.extensions_mut().insert(AuthIdentity {
+            key_name: "operator".to_string(),
+            role: ApiKeyRole::Admin,
+            namespace: None,
+        });
+        request
+    }
+
+    /// Serve `node` on a free local port; returns its endpoint
+    async fn serve(node: Arc<TappServiceImpl>) -> String {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let endpoint = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(
+            tonic::transport::Server::builder()
+                .add_service(TappServiceServer::from_arc(node))
+                .serve_with_incoming(tokio_stream::wrappers::TcpListenerStream::new(listener)),
         );
+        endpoint
+    }
 
-        Ok(Response::new(GetAppSecretKeyResponse {
-            success: true,
-            message: format!("Private key for app {}", req.app_id),
-            private_key,
-            public_key: key_response.public_key,
-            eth_address: key_response.eth_address,
-        }))
+    /// Served nodes of a 3-of-5 cluster whose peers have all joined
+    /// Nodes accept sample evidence and share one whitelist.
+    struct TestCluster {
+        nodes: Vec<Arc<TappServiceImpl>>,
+        endpoints: Vec<String>,
+        cluster_id: String,
+        whitelist: SignerWhitelist,
+        /// Kept until the nodes are dropped
+        dirs: Vec<tempfile::TempDir>,
     }
 
-    async fn get_app_info(
-        &self,
-        request: Request<GetAppInfoRequest>,
-    ) -> Result<Response<GetAppInfoResponse>, Status> {
-        let req = request.into_inner();
-        let app_id = req.app_id;
+    impl TestCluster {
+        async fn create() -> Self {
+            let mut cluster = TestCluster {
+                nodes: Vec::new(),
+                endpoints: Vec::new(),
+                cluster_id: String::new(),
+                whitelist: SignerWhitelist::default(),
+                dirs: Vec::new(),
+            };
+            for _ in 0..5 {
+                let node = cluster.add_node().await;
+                cluster.endpoints.push(serve(node.clone()).await);
+                cluster.nodes.push(node);
+            }
 
-        let compose_content = self.boot_service.get_app_compose_content(&app_id).await?;
-        let volumes_content = self.boot_service.get_app_mount_files(&app_id).await?;
+            let peers = ["node-b", "node-c", "node-d", "node-e"];
+            let start_node = &cluster.nodes[0];
+            start_node
+                .init_cluster(as_admin(InitClusterRequest {
+                    total_shares: 5,
+                    threshold: 3,
+                    peers: peers.iter().map(|peer| peer.to_string()).collect(),
+                }))
+                .await
+                .unwrap();
+            cluster.cluster_id = start_node.cluster.status().await.cluster_id;
+            for (node, peer) in cluster.nodes[1..].iter().zip(peers) {
+                node.join_cluster(as_admin(JoinClusterRequest {
+                    endpoint: cluster.endpoints[0].clone(),
+                    cluster_id: cluster.cluster_id.clone(),
+                    peer: peer.to_string(),
+                }))
+                .await
+                .unwrap();
+            }
+            cluster
+        }
 
-        let compose_content = compose_content.ok_or(TappError::InvalidParameter {
-            field: "app_id".to_string(),
-            reason: format!("App {} not found", app_id),
-        })?;
-        let volumes_content = volumes_content.ok_or(TappError::InvalidParameter {
-            field: "app_id".to_string(),
-            reason: format!("App {} not found", app_id),
-        })?;
+        /// Whitelisted node outside the cluster, not served
+        async fn add_node(&mut self) -> Arc<TappServiceImpl> {
+            let mut config = TappConfig::default();
+            config.cluster.evidence_verifier = config::EvidenceVerifierKind::Sample;
+            let dir = tempfile::tempdir().unwrap();
+            let node = create_test_service(config, dir.path())
+                .await
+                .with_cluster_whitelist(Box::new(self.whitelist.clone()));
+            let signer = signer(&node).await;
+            self.whitelist.0.lock().unwrap().insert(signer);
+            self.dirs.push(dir);
+            Arc::new(node)
+        }
 
-        Ok(Response::new(GetAppInfoResponse {
-            success: true,
-            message: format!("App info for {}", app_id),
-            app_id,
-            compose_content,
-            volumes_content,
-        }))
+        fn recover_request(&self, endpoints: Vec<String>) -> RecoverClusterRequest {
+            RecoverClusterRequest {
+                endpoints,
+                cluster_id: self.cluster_id.clone(),
+                share_index: 5,
+            }
+        }
+
+        /// Last recovery `node` coordinated, as GetClusterStatus reports it
+        async fn recovery(&self, node: usize) -> ClusterRecovery {
+            self.nodes[node]
+                .get_cluster_status(Request::new(GetClusterStatusRequest {}))
+                .await
+                .unwrap()
+                .into_inner()
+                .status
+                .unwrap()
+                .recovery
+                .unwrap()
+        }
     }
 
-    async fn get_service_status(
-        &self,
-        _request: Request<GetServiceStatusRequest>,
-    ) -> Result<Response<GetServiceStatusResponse>, Status> {
-        todo!()
+    async fn signer(node: &TappServiceImpl) -> String {
+        node.cluster_node_key().await.unwrap().signer_address()
     }
 
-    async fn get_service_logs(
-        &self,
-        request: Request<GetServiceLogsRequest>,
-    ) -> Result<Response<GetServiceLogsResponse>, Status> {
-        let req = request.into_inner();
-        let response = self.logs_service.get_logs(req).await?;
-        Ok(Response::new(response))
+    #[tokio::test]
+    async fn test_share_recovery() {
+        let mut cluster = TestCluster::create().await;
+        let replacement = cluster.add_node().await;
+        let cluster_id = cluster.cluster_id.clone();
+
+        // Node E (share 5) failed; its replacement recovers the share
+        // through A, with B and C contributing
+        let status = replacement
+            .recover_cluster(Request::new(
+                cluster.recover_request(cluster.endpoints[..3].to_vec()),
+            ))
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), tonic::Code::PermissionDenied);
+        let response = replacement
+            .recover_cluster(as_admin(
+                cluster.recover_request(cluster.endpoints[..3].to_vec()),
+            ))
+            .await
+            .unwrap();
+        let annotation = response.extensions().get::<AuditAnnotation>().unwrap();
+        assert_eq!(
+            annotation.0,
+            format!(
+                "cluster={} share_index=5 coordinator={}",
+                cluster_id,
+                signer(&cluster.nodes[0]).await
+            )
+        );
+        let response = response.into_inner();
+        assert_eq!(response.coordinator, cluster.endpoints[0]);
+        let status = response.status.unwrap();
+        assert_eq!(status.state(), ClusterState::ClusterReady);
+        assert_eq!(status.own_share_index, 5);
+        assert_eq!((status.total_shares, status.threshold), (5, 3));
+        assert_eq!(
+            replacement.cluster.own_share(&cluster_id).await.unwrap(),
+            cluster.nodes[4]
+                .cluster
+                .own_share(&cluster_id)
+                .await
+                .unwrap()
+        );
+
+        let recovery = cluster.recovery(0).await;
+        assert_eq!(recovery.state(), RecoveryState::RecoveryDelivered);
+        assert_eq!(
+            (recovery.share_index, recovery.collected, recovery.needed),
+            (5, 2, 2)
+        );
+        assert_eq!(recovery.requested_by, signer(&replacement).await);
+        assert_eq!(
+            recovery.contributors,
+            vec![
+                signer(&cluster.nodes[1]).await,
+                signer(&cluster.nodes[2]).await
+            ]
+        );
+
+        let status = replacement
+            .recover_cluster(as_admin(
+                cluster.recover_request(cluster.endpoints[..3].to_vec()),
+            ))
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), tonic::Code::FailedPrecondition);
     }
 
-    async fn get_app_logs(
-        &self,
-        request: Request<GetAppLogsRequest>,
-    ) -> Result<Response<GetAppLogsResponse>, Status> {
-        let req = request.into_inner();
+    #[tokio::test]
+    async fn test_share_recovery_aborts_mid_collection() {
+        let mut cluster = TestCluster::create().await;
+        let replacement = cluster.add_node().await;
 
-        let service_name = if req.service_name.is_empty() {
-            None
-        } else {
-            Some(req.service_name.as_str())
+        // B coordinates and collects C's share; the next member is down
+        let endpoints = vec![
+            cluster.endpoints[1].clone(),
+            cluster.endpoints[2].clone(),
+            "http://127.0.0.1:1".to_string(),
+        ];
+        let status = replacement
+            .recover_cluster(as_admin(cluster.recover_request(endpoints)))
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), tonic::Code::FailedPrecondition);
+        let recovery = cluster.recovery(1).await;
+        assert_eq!(recovery.state(), RecoveryState::RecoveryAborted);
+        assert_eq!((recovery.collected, recovery.needed), (1, 2));
+        assert!(recovery.error.contains("Collected 1 of the 2"));
+        assert_eq!(
+            replacement.cluster.status().await.state,
+            cluster::ClusterState::Uninitialized
+        );
+
+        // The aborted recovery left nothing behind, so it can be retried
+        let response = replacement
+            .recover_cluster(as_admin(
+                cluster.recover_request(cluster.endpoints[1..4].to_vec()),
+            ))
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(response.coordinator, cluster.endpoints[1]);
+        assert_eq!(response.status.unwrap().own_share_index, 5);
+        assert_eq!(
+            cluster.recovery(1).await.state(),
+            RecoveryState::RecoveryDelivered
+        );
+    }
+
+    #[tokio::test]
+    async fn test_key_share_exchange() {
+        let mut config = TappConfig::default();
+        config.cluster.evidence_verifier = config::EvidenceVerifierKind::Sample;
+        let (dir_a, dir_b) = (tempfile::tempdir().unwrap(), tempfile::tempdir().unwrap());
+        let (whitelist_a, whitelist_b) = (SignerWhitelist::default(), SignerWhitelist::default());
+        let node_a = Arc::new(
+            create_test_service(config.clone(), dir_a.path())
+                .await
+                .with_cluster_whitelist(Box::new(whitelist_a.clone())),
+        );
+        let node_b = create_test_service(config, dir_b.path())
+            .await
+            .with_cluster_whitelist(Box::new(whitelist_b.clone()));
+        let signer_a = node_a.cluster_node_key().await.unwrap().signer_address();
+        let signer_b = node_b.cluster_node_key().await.unwrap().signer_address();
+        whitelist_b.0.lock().unwrap().insert(signer_a.clone());
+
+        node_a
+            .init_cluster(as_admin(InitClusterRequest {
+                total_shares: 2,
+                threshold: 2,
+                peers: vec!["node-b".to_string()],
+            }))
+            .await
+            .unwrap();
+        let cluster_id = node_a.cluster.status().await.cluster_id;
+        let endpoint = serve(node_a.clone()).await;
+        let join_request = || JoinClusterRequest {
+            endpoint: endpoint.clone(),
+            cluster_id: cluster_id.clone(),
+            peer: "node-b".to_string(),
         };
 
-        let content = self
-            .boot_service
-            .get_app_logs(&req.app_id, req.lines, service_name)
-            .await?;
+        let status = node_b
+            .join_cluster(Request::new(join_request()))
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), tonic::Code::PermissionDenied);
 
-        let total_lines = content.lines().count() as i32;
+        // Node A does not know node B's signer yet
+        let status = node_b
+            .join_cluster(as_admin(join_request()))
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), tonic::Code::PermissionDenied);
+        assert_eq!(node_a.cluster.status().await.peers[0].delivered_at, None);
 
-        Ok(Response::new(GetAppLogsResponse {
-            success: true,
-            message: format!("Retrieved {} lines from app {}", total_lines, req.app_id),
-            content,
-            total_lines,
-        }))
+        let stale = RequestKeyShareRequest {
+            cluster_id: cluster_id.clone(),
+            peer: "node-b".to_string(),
+            public_key: vec![4; 64],
+            timestamp: utils::current_timestamp() - 3600,
+            evidence: Vec::new(),
+        };
+        let status = node_a
+            .request_key_share(Request::new(stale))
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), tonic::Code::InvalidArgument);
+
+        whitelist_a.0.lock().unwrap().insert(signer_b.clone());
+        let response = node_b.join_cluster(as_admin(join_request())).await.unwrap();
+        let annotation = response.extensions().get::<AuditAnnotation>().unwrap();
+        assert_eq!(
+            annotation.0,
+            format!("cluster={} start_node={}", cluster_id, signer_a)
+        );
+        let joined = response.into_inner().status.unwrap();
+        assert_eq!(joined.state(), ClusterState::ClusterReady);
+        assert_eq!(joined.cluster_id, cluster_id);
+        assert_eq!(joined.own_share_index, 2);
+        assert_eq!(joined.signer_address, signer_b);
+        assert!(dir_b.path().join("cluster/share.sealed").exists());
+
+        let status_a = node_a.cluster.status().await;
+        assert_eq!(status_a.state, cluster::ClusterState::Ready);
+        assert_eq!(
+            status_a.peers[0].collected_by.as_deref(),
+            Some(signer_b.as_str())
+        );
+
+        let status = node_b
+            .join_cluster(as_admin(join_request()))
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), tonic::Code::FailedPrecondition);
     }
-}
 
-/// Initialize tracing based on configuration
-pub fn init_tracing(config: &config::LoggingConfig) -> TappResult<()> {
-    use tracing_subscriber::{
-        fmt::{self, format::FmtSpan},
-        layer::SubscriberExt,
-        util::SubscriberInitExt,
-        EnvFilter, Layer,
-    };
+    #[tokio::test]
+    async fn test_sealed_data() {
+        use container_identity::{ContainerIdentity, ContainerResolver};
 
-    let filter = EnvFilter::try_from_default_env()
-        .or_else(|_| EnvFilter::try_new(&config.level))
-        .map_err(|e| error::ConfigError::InvalidValue {
-            field: "logging.level".to_string(),
-            reason: format!("Invalid log level: {}", e),
-        })?;
+        let aa_dir = tempfile::tempdir().unwrap();
+        let mut service = create_test_service(TappConfig::default(), aa_dir.path()).await;
+        let app_id = "vault-app";
+        let (private_key, public_key) = create_deployer_keypair();
+        let measurement = |compose_hash: &str| AppMeasurement {
+            app_id: app_id.to_string(),
+            compose_hash: compose_hash.to_string(),
+            volumes_hash: String::new(),
+            deployer: hex::encode(&public_key),
+            deployer_address: hex::encode(app_key::eth_address(&public_key)),
+            timestamp: utils::current_timestamp(),
+            namespace: None,
+            secret_names: Vec::new(),
+        };
+        service
+            .boot_service
+            .insert_app_measurement(measurement("hash-1"))
+            .await;
+        std::fs::create_dir_all(aa_dir.path().join("apps").join(app_id)).unwrap();
 
-    let stdout_layer = match config.format.as_str() {
-        "json" => fmt::layer()
-            .json()
-            .with_writer(std::io::stdout)
-            .with_span_events(FmtSpan::CLOSE)
-            .boxed(),
-        "pretty" => fmt::layer()
-            .pretty()
-            .with_writer(std::io::stdout)
-            .with_ansi(true)
-            .with_span_events(FmtSpan::CLOSE)
-            .boxed(),
-        _ => {
-            return Err(error::ConfigError::InvalidValue {
-                field: "logging.format".to_string(),
-                reason: format!("Unsupported log format: {}", config.format),
-            }
-            .into());
+        let app_container: std::net::SocketAddr = "172.18.0.5:40000".parse().unwrap();
+        service.container_resolver = ContainerResolver::with_containers(vec![(
+            app_container.ip(),
+            ContainerIdentity {
+                id: "0123abcd".to_string(),
+                name: "vault-app-web-1".to_string(),
+                compose_project: Some(app_id.to_string()),
+            },
+        )]);
+        fn from<T>(addr: &str, request: &mut Request<T>) {
+            request
+                .extensions_mut()
+                .insert(tonic::transport::server::TcpConnectInfo {
+                    local_addr: None,
+                    remote_addr: Some(addr.parse().unwrap()),
+                });
         }
-    };
+        // Deployer signature fields: (nonce, timestamp, signature)
+        let sign = |operation: &str, label: &str| {
+            let nonce = nonce_manager::generate_nonce();
+            let timestamp = utils::current_timestamp();
+            let action = format!("{}:{}", operation, label);
+            let message = app_key::deployer_request_message(
+                operation,
+                app_id,
+                action.as_bytes(),
+                &nonce,
+                timestamp,
+            );
+            let signature = app_key::sign_message(&private_key, &message).unwrap();
+            (nonce, timestamp, signature)
+        };
+        let seal = |label: &str, binding: SealBinding, data: &[u8]| {
+            let (nonce, timestamp, signature) = sign("seal_data", label);
+            let mut request = Request::new(SealDataRequest {
+                app_id: app_id.to_string(),
+                label: label.to_string(),
+                data: data.to_vec(),
+                binding: binding as i32,
+                nonce,
+                timestamp,
+                signature,
+            });
+            from("127.0.0.1:40000", &mut request);
+            request
+        };
+        let unseal = |label: &str| {
+            let (nonce, timestamp, signature) = sign("unseal_data", label);
+            let mut request = Request::new(UnsealDataRequest {
+                app_id: app_id.to_string(),
+                label: label.to_string(),
+                nonce,
+                timestamp,
+                signature,
+            });
+            from("127.0.0.1:40000", &mut request);
+            request
+        };
 
-    if let Some(file_path) = &config.file_path {
-        use tracing_appender::rolling::{RollingFileAppender, Rotation};
+        // The deployer seals from localhost
+        let response = service
+            .seal_data(seal("db-key", SealBinding::SealToMeasurement, b"secret"))
+            .await
+            .unwrap()
+            .into_inner();
+        let blob = response.blob.unwrap();
+        assert_eq!(blob.size, 6);
+        assert_eq!(blob.binding(), SealBinding::SealToMeasurement);
+        service
+            .seal_data(seal("config", SealBinding::SealToAppId, b"settings"))
+            .await
+            .unwrap();
 
-        let path = std::path::Path::new(file_path);
+        // The app's container unseals without a signature
+        let mut request = Request::new(UnsealDataRequest {
+            app_id: app_id.to_string(),
+            label: "db-key".to_string(),
+            ..Default::default()
+        });
+        from(&app_container.to_string(), &mut request);
+        let response = service.unseal_data(request).await.unwrap().into_inner();
+        assert_eq!(response.data, b"secret");
 
-        let (directory, file_name_prefix) = if file_path.to_string_lossy().ends_with('/') {
-            (path, "app")
-        } else if path.extension().is_some() {
-            let directory = path.parent().unwrap_or(std::path::Path::new("."));
-            let file_name_prefix = path.file_stem().and_then(|n| n.to_str()).unwrap_or("app");
-            (directory, file_name_prefix)
-        } else {
-            let directory = path.parent().unwrap_or(std::path::Path::new("."));
-            let file_name_prefix = path.file_name().and_then(|n| n.to_str()).unwrap_or("app");
-            (directory, file_name_prefix)
-        };
+        // Other callers on the host must sign, and remote callers are
+        // rejected even when they do
+        let mut request = unseal("db-key");
+        request.get_mut().signature.clear();
+        let status = service.unseal_data(request).await.unwrap_err();
+        assert_eq!(status.code(), tonic::Code::InvalidArgument);
+        let mut request = unseal("db-key");
+        from("203.0.113.7:40000", &mut request);
+        let status = service.unseal_data(request).await.unwrap_err();
+        assert_eq!(status.code(), tonic::Code::PermissionDenied);
 
-        std::fs::create_dir_all(directory).map_err(|e| error::ConfigError::InvalidValue {
-            field: "logging.file_path".to_string(),
-            reason: format!("Cannot create log directory: {}", e),
-        })?;
+        // A signature for one label does not open another
+        let mut request = unseal("db-key");
+        request.get_mut().label = "config".to_string();
+        let status = service.unseal_data(request).await.unwrap_err();
+        assert_eq!(status.code(), tonic::Code::PermissionDenied);
+
+        // After a compose update only the blob bound to the app ID opens
+        service
+            .boot_service
+            .insert_app_measurement(measurement("hash-2"))
+            .await;
+        let status = service.unseal_data(unseal("db-key")).await.unwrap_err();
+        assert_eq!(status.code(), tonic::Code::PermissionDenied);
+        assert!(status.message().contains("db-key"), "{}", status.message());
+        let response = service
+            .unseal_data(unseal("config"))
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(response.data, b"settings");
 
-        let file_appender = RollingFileAppender::new(Rotation::DAILY, directory, file_name_prefix);
+        let (nonce, timestamp, signature) = sign("list_sealed_blobs", "");
+        let mut request = Request::new(ListSealedBlobsRequest {
+            app_id: app_id.to_string(),
+            nonce,
+            timestamp,
+            signature,
+        });
+        from("127.0.0.1:40000", &mut request);
+        let response = service
+            .list_sealed_blobs(request)
+            .await
+            .unwrap()
+            .into_inner();
+        let labels: Vec<&str> = response.blobs.iter().map(|b| b.label.as_str()).collect();
+        assert_eq!(labels, vec!["config", "db-key"]);
 
-        let (non_blocking, _guard) = tracing_appender::non_blocking(file_appender);
-        std::mem::forget(_guard);
+        let (nonce, timestamp, signature) = sign("delete_sealed_blob", "db-key");
+        let mut request = Request::new(DeleteSealedBlobRequest {
+            app_id: app_id.to_string(),
+            label: "db-key".to_string(),
+            nonce,
+            timestamp,
+            signature,
+        });
+        from("127.0.0.1:40000", &mut request);
+        let response = service.delete_sealed_blob(request).await.unwrap();
+        let annotation = response.extensions().get::<AuditAnnotation>().unwrap();
+        assert_eq!(annotation.0, "sealed_blob=db-key");
+        assert!(response.into_inner().deleted);
+        let status = service.unseal_data(unseal("db-key")).await.unwrap_err();
+        assert_eq!(status.code(), tonic::Code::NotFound);
+    }
 
-        let file_layer = match config.format.as_str() {
-            "json" => fmt::layer()
-                .json()
-                .with_writer(non_blocking)
-                .with_ansi(false)
-                .with_span_events(FmtSpan::CLOSE)
-                .boxed(),
-            "pretty" => fmt::layer()
-                .with_writer(non_blocking)
-                .with_ansi(false)
-                .with_span_events(FmtSpan::CLOSE)
-                .boxed(),
-            _ => unreachable!(),
+    #[tokio::test]
+    async fn test_namespaces_isolate_apps() {
+        /// Request from a key of `namespace`, or from an admin key
+        fn as_caller<T>(namespace: Option<&str>, message: T) -> Request<T> {
+            let mut request = Request::new(message);
+            request.extensions_mut().insert(AuthIdentity {
+                key_name: namespace.unwrap_or("operator").to_string(),
+                role: namespace.map_or(ApiKeyRole::Admin, |_| ApiKeyRole::Client),
+                namespace: namespace.map(str::to_string),
+            });
+            request
+        }
+
+        let aa_dir = tempfile::tempdir().unwrap();
+        let mut config = TappConfig::default();
+        config.server.api_key = Some(config::ApiKeyConfig {
+            enabled: true,
+            namespaces: true,
+            ..Default::default()
+        });
+        let service = create_test_service(config, aa_dir.path()).await;
+
+        // Both teams deploy "web"; team-b also runs "worker"
+        let mut deployers = std::collections::HashMap::new();
+        for (team, app_id) in [("team-a", "web"), ("team-b", "web"), ("team-b", "worker")] {
+            let (private_key, public_key) = create_deployer_keypair();
+            let app_id = namespace::qualify(team, app_id);
+            service
+                .boot_service
+                .insert_app_measurement(AppMeasurement {
+                    app_id: app_id.clone(),
+                    compose_hash: String::new(),
+                    volumes_hash: String::new(),
+                    deployer: hex::encode(&public_key),
+                    deployer_address: hex::encode(app_key::eth_address(&public_key)),
+                    timestamp: utils::current_timestamp(),
+                    namespace: Some(team.to_string()),
+                    secret_names: Vec::new(),
+                })
+                .await;
+            service
+                .app_key_service
+                .get_app_key(&app_id, "ethereum")
+                .await
+                .unwrap();
+            deployers.insert(app_id, private_key);
+        }
+
+        // List: each team sees its own apps by their bare IDs
+        let listed = |namespace: Option<&'static str>| {
+            let service = &service;
+            async move {
+                let response = service
+                    .list_app_measurements(as_caller(
+                        namespace,
+                        ListAppMeasurementsRequest::default(),
+                    ))
+                    .await
+                    .unwrap()
+                    .into_inner();
+                let mut apps: Vec<(String, String)> = response
+                    .measurements
+                    .into_iter()
+                    .map(|m| (m.namespace, m.app_id))
+                    .collect();
+                apps.sort();
+                apps
+            }
         };
+        let app = |namespace: &str, app_id: &str| (namespace.to_string(), app_id.to_string());
+        assert_eq!(listed(Some("team-a")).await, vec![app("team-a", "web")]);
+        assert_eq!(
+            listed(Some("team-b")).await,
+            vec![app("team-b", "web"), app("team-b", "worker")]
+        );
+        assert!(listed(Some("team-c")).await.is_empty());
+        assert_eq!(
+            listed(None).await,
+            vec![
+                app("team-a", "team-a/web"),
+                app("team-b", "team-b/web"),
+                app("team-b", "team-b/worker"),
+            ]
+        );
 
-        tracing_subscriber::registry()
-            .with(filter)
-            .with(stdout_layer)
-            .with(file_layer)
-            .init();
-    } else {
-        tracing_subscriber::registry()
-            .with(filter)
-            .with(stdout_layer)
-            .init();
+        // Logs: team-a cannot read team-b's deploy output
+        let app_dir = service.boot_service.data_dir().join("team-b__worker");
+        std::fs::create_dir_all(&app_dir).unwrap();
+        boot::deploy_log::append(
+            &app_dir,
+            "task-1",
+            "up",
+            "ok",
+            &["worker started".to_string()],
+        )
+        .await
+        .unwrap();
+        let logs_request = |app_id: &str| GetAppLogsRequest {
+            app_id: app_id.to_string(),
+            source: "deploy".to_string(),
+            ..Default::default()
+        };
+        let response = service
+            .get_app_logs(as_caller(Some("team-b"), logs_request("worker")))
+            .await
+            .unwrap()
+            .into_inner();
+        assert!(response.content.contains("worker started"));
+        for app_id in ["worker", "team-b/worker"] {
+            let status = service
+                .get_app_logs(as_caller(Some("team-a"), logs_request(app_id)))
+                .await
+                .unwrap_err();
+            assert_eq!(status.code(), tonic::Code::InvalidArgument);
+            assert!(!status.message().contains("worker started"));
+        }
+
+        // Stop: team-a's deployer signature does not stop team-b's "web"
+        let nonce = nonce_manager::generate_nonce();
+        let timestamp = utils::current_timestamp();
+        let message =
+            app_key::deployer_request_message("stop_app", "web", b"stop", &nonce, timestamp);
+        let status = service
+            .stop_app(as_caller(
+                Some("team-b"),
+                StopAppRequest {
+                    app_id: "web".to_string(),
+                    nonce,
+                    timestamp,
+                    signature: app_key::sign_message(&deployers["team-a/web"], &message).unwrap(),
+                },
+            ))
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), tonic::Code::PermissionDenied);
+        let status = service
+            .stop_app(as_caller(
+                Some("team-b"),
+                StopAppRequest {
+                    app_id: "team-a/web".to_string(),
+                    ..Default::default()
+                },
+            ))
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), tonic::Code::InvalidArgument);
+        for app_id in ["team-a/web", "team-b/web"] {
+            assert!(service
+                .boot_service
+                .get_app_measurement(app_id)
+                .await
+                .is_some());
+        }
+
+        // Secret keys: each team gets the key of its own "web"
+        let secret_key = |namespace: &'static str, app_id: &'static str, signer: &'static str| {
+            let mut request = create_secret_key_request(app_id, &deployers[signer]);
+            request.extensions_mut().insert(AuthIdentity {
+                key_name: namespace.to_string(),
+                role: ApiKeyRole::Client,
+                namespace: Some(namespace.to_string()),
+            });
+            service.get_app_secret_key(request)
+        };
+        let team_a_key = secret_key("team-a", "web", "team-a/web")
+            .await
+            .unwrap()
+            .into_inner();
+        let team_b_key = secret_key("team-b", "web", "team-b/web")
+            .await
+            .unwrap()
+            .into_inner();
+        assert_ne!(team_a_key.private_key, team_b_key.private_key);
+        assert_eq!(
+            team_a_key.private_key,
+            service
+                .app_key_service
+                .get_private_key("team-a/web")
+                .await
+                .unwrap()
+        );
+        // Signed by team-a's deployer, asked for from team-b
+        let status = secret_key("team-b", "web", "team-a/web").await.unwrap_err();
+        assert_eq!(status.code(), tonic::Code::PermissionDenied);
+        let status = secret_key("team-b", "team-a/web", "team-a/web")
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), tonic::Code::InvalidArgument);
     }
 
-    Ok(())
-}
-#[cfg(test)]
-mod tests {
-    use super::*;
+    #[tokio::test]
+    async fn test_set_log_level() {
+        let aa_dir = tempfile::tempdir().unwrap();
+        let (_layer, control) = log_level::LogLevelControl::new("info", Vec::new()).unwrap();
+        let service = create_test_service(TappConfig::default(), aa_dir.path())
+            .await
+            .with_log_level(control.clone());
 
-    #[test]
-    fn test_validate_app_id() {
-        assert!(utils::validate_app_id("my-app"));
-        assert!(utils::validate_app_id("app_123"));
-        assert!(utils::validate_app_id("test-application-1"));
+        let request = |role: Option<ApiKeyRole>, filter: &str| {
+            let mut request = Request::new(SetLogLevelRequest {
+                filter: filter.to_string(),
+                revert_after_seconds: 0,
+            });
+            if let Some(role) = role {
+                request.extensions_mut().insert(AuthIdentity {
+                    key_name: "operator".to_string(),
+                    role,
+                    namespace: None,
+                });
+            }
+            request
+        };
 
-        assert!(!utils::validate_app_id("ab")); // too short
-        assert!(!utils::validate_app_id("a".repeat(65).as_str())); // too long
-        assert!(!utils::validate_app_id("app@123")); // invalid character
-        assert!(!utils::validate_app_id("app space")); // contains space
-    }
+        for role in [None, Some(ApiKeyRole::Client)] {
+            let status = service
+                .set_log_level(request(role, "debug"))
+                .await
+                .unwrap_err();
+            assert_eq!(status.code(), tonic::Code::PermissionDenied);
+        }
+        assert_eq!(control.current(), "info");
 
-    #[test]
-    fn test_sha256_hex() {
-        let data = b"hello world";
-        let hash = utils::sha256_hex(data);
+        let status = service
+            .set_log_level(request(Some(ApiKeyRole::Admin), "info,tapp_service=loud"))
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), tonic::Code::InvalidArgument);
+
+        let response = service
+            .set_log_level(request(
+                Some(ApiKeyRole::Admin),
+                "info,tapp_service::boot=debug",
+            ))
+            .await
+            .unwrap();
         assert_eq!(
-            hash,
-            "b94d27b9934d3e08a52e52d7da7dabfac484efe37a5380ee9088f7ace2efcde9"
+            response.extensions().get::<AuditAnnotation>(),
+            Some(&AuditAnnotation(
+                "log_filter=info,tapp_service::boot=debug".to_string()
+            ))
         );
-    }
+        let response = response.into_inner();
+        assert_eq!(response.previous_filter, "info");
+        assert_eq!(response.filter, "info,tapp_service::boot=debug");
+        // logging.level_revert_seconds defaults to an hour
+        let expected_revert = utils::current_timestamp() + 3600;
+        assert!((expected_revert - response.revert_at).abs() <= 1);
+        assert_eq!(control.current(), "info,tapp_service::boot=debug");
 
-    #[test]
-    fn test_format_bytes() {
-        assert_eq!(utils::format_bytes(0), "0 B");
-        assert_eq!(utils::format_bytes(1024), "1.00 KB");
-        assert_eq!(utils::format_bytes(1536), "1.50 KB");
-        assert_eq!(utils::format_bytes(1048576), "1.00 MB");
+        // An empty filter restores the configured level without a timer
+        let response = service
+            .set_log_level(request(Some(ApiKeyRole::Admin), ""))
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(response.previous_filter, "info,tapp_service::boot=debug");
+        assert_eq!(response.filter, "info");
+        assert_eq!(response.revert_at, 0);
     }
 
-    #[test]
-    fn test_pad_to_length() {
-        let data = b"hello";
-        let padded = utils::pad_to_length(data, 10);
-        assert_eq!(padded.len(), 10);
-        assert_eq!(&padded[0..5], b"hello");
-        assert_eq!(&padded[5..], &[0u8; 5]);
+    #[tokio::test]
+    async fn test_nonce_settings_from_config() {
+        let aa_dir = tempfile::tempdir().unwrap();
+        let mut config = TappConfig::default();
+        config.security.nonce_validity_seconds = 2;
+        assert!(matches!(
+            TappServiceImpl::new(config.clone()).await,
+            Err(TappError::Config(error::ConfigError::InvalidValue { .. }))
+        ));
+
+        config.security.nonce_validity_seconds = 30;
+        config.security.nonce_cleanup_interval_seconds = 10;
+        let service = create_test_service(config, aa_dir.path()).await;
+
+        let response = service
+            .get_service_status(Request::new(GetServiceStatusRequest::default()))
+            .await
+            .unwrap()
+            .into_inner();
+        let nonce_status = response.nonce_status.unwrap();
+        assert_eq!(nonce_status.validity_seconds, 30);
+        assert_eq!(nonce_status.cleanup_interval_seconds, 10);
+        assert!(!response.image_policy.unwrap().enabled);
+        assert_eq!(response.container_runtime, "docker");
     }
 }
@@ -0,0 +1,174 @@
+use crate::config::Http2Config;
+use socket2::{Domain, Protocol, Socket, Type};
+use std::net::SocketAddr;
+use tokio::net::TcpListener;
+use tonic::transport::Server;
+
+/// Pending connections per listener
+const BACKLOG: i32 = 1024;
+
+/// Bind a TCP listener for `addr` now, so a bad address fails startup
+/// IPv6 listeners are IPv6-only: `[::]:50051` can run next to
+/// `0.0.0.0:50051` instead of colliding with it.
+pub fn bind_tcp(addr: SocketAddr) -> std::io::Result<TcpListener> {
+    let socket = Socket::new(Domain::for_address(addr), Type::STREAM, Some(Protocol::TCP))?;
+    if addr.is_ipv6() {
+        socket.set_only_v6(true)?;
+    }
+    socket.set_reuse_address(true)?;
+    socket.set_nonblocking(true)?;
+    socket.bind(&addr.into())?;
+    socket.listen(BACKLOG)?;
+    TcpListener::from_std(socket.into())
+}
+
+/// Bind every address in order, or fail naming the first that can't be
+/// parsed or bound
+pub fn bind_all<'a>(
+    addresses: impl IntoIterator<Item = &'a str>,
+) -> Result<Vec<(SocketAddr, TcpListener)>, String> {
+    addresses
+        .into_iter()
+        .map(|address| {
+            let addr: SocketAddr = address
+                .parse()
+                .map_err(|e| format!("Invalid bind address '{}': {}", address, e))?;
+            let listener =
+                bind_tcp(addr).map_err(|e| format!("Failed to bind {}: {}", address, e))?;
+            Ok((addr, listener))
+        })
+        .collect()
+}
+
+/// gRPC server builder with the `[server.http2]` connection settings
+pub fn server_builder(http2: &Http2Config) -> Server {
+    let builder = Server::builder()
+        .http2_keepalive_interval(http2.keepalive_interval())
+        .http2_keepalive_timeout(Some(http2.keepalive_timeout()))
+        .max_concurrent_streams(http2.max_concurrent_streams());
+    match http2.max_connection_age() {
+        Some(age) => builder.max_connection_age(age),
+        None => builder,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        GetServiceStatusRequest, StreamServiceLogsRequest, TappConfig, TappServiceClient,
+        TappServiceImpl, TappServiceServer,
+    };
+    use std::io::Write;
+    use std::sync::Arc;
+    use std::time::Duration;
+    use tokio_stream::wrappers::TcpListenerStream;
+    use tonic::transport::Endpoint;
+
+    #[tokio::test]
+    async fn test_serves_every_bind_address() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = TappConfig::default();
+        config.boot.aa_config_path = Some(dir.path().join("aa.toml").to_string_lossy().to_string());
+        config.boot.data_dir = dir.path().join("apps");
+        let service = Arc::new(TappServiceImpl::new(config).await.unwrap());
+
+        // Two loopback listeners sharing one service instance
+        let listeners = bind_all(["127.0.0.1:0", "127.0.0.1:0"]).unwrap();
+        let mut addrs = Vec::new();
+        for (_, listener) in listeners {
+            addrs.push(listener.local_addr().unwrap());
+            tokio::spawn(
+                Server::builder()
+                    .add_service(TappServiceServer::from_arc(service.clone()))
+                    .serve_with_incoming(TcpListenerStream::new(listener)),
+            );
+        }
+        assert_ne!(addrs[0], addrs[1]);
+
+        for addr in &addrs {
+            let mut client = TappServiceClient::connect(format!("http://{}", addr))
+                .await
+                .unwrap();
+            let response = client
+                .get_service_status(GetServiceStatusRequest::default())
+                .await
+                .unwrap()
+                .into_inner();
+            assert!(response.success, "{}", addr);
+        }
+
+        // A taken or invalid address fails naming it
+        let taken = addrs[0].to_string();
+        let err = bind_all(["127.0.0.1:0", taken.as_str()]).unwrap_err();
+        assert!(
+            err.starts_with(&format!("Failed to bind {}: ", taken)),
+            "{}",
+            err
+        );
+        let err = bind_all(["localhost"]).unwrap_err();
+        assert!(
+            err.starts_with("Invalid bind address 'localhost'"),
+            "{}",
+            err
+        );
+    }
+
+    #[tokio::test]
+    async fn test_idle_stream_survives_with_keepalive() {
+        let dir = tempfile::tempdir().unwrap();
+        let log_path = dir.path().join("logs").join("app.log");
+        std::fs::create_dir_all(log_path.parent().unwrap()).unwrap();
+        std::fs::write(&log_path, "first\n").unwrap();
+
+        let mut config = TappConfig::default();
+        config.boot.aa_config_path = Some(dir.path().join("aa.toml").to_string_lossy().to_string());
+        config.boot.data_dir = dir.path().join("apps");
+        config.logging.file_path = Some(log_path.clone());
+        config.server.http2.keepalive_interval_seconds = 1;
+        config.server.http2.keepalive_timeout_seconds = 1;
+        let service = Arc::new(TappServiceImpl::new(config.clone()).await.unwrap());
+
+        let (_, listener) = bind_all(["127.0.0.1:0"]).unwrap().pop().unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(
+            server_builder(&config.server.http2)
+                .add_service(TappServiceServer::from_arc(service))
+                .serve_with_incoming(TcpListenerStream::new(listener)),
+        );
+
+        let channel = Endpoint::from_shared(format!("http://{}", addr))
+            .unwrap()
+            .http2_keep_alive_interval(Duration::from_secs(1))
+            .keep_alive_timeout(Duration::from_secs(1))
+            .keep_alive_while_idle(true)
+            .connect()
+            .await
+            .unwrap();
+        let mut lines = TappServiceClient::new(channel)
+            .stream_service_logs(StreamServiceLogsRequest {
+                file_name: "app.log".to_string(),
+                lines: 10,
+            })
+            .await
+            .unwrap()
+            .into_inner();
+        let message = lines.message().await.unwrap().unwrap();
+        assert_eq!(message.lines, vec!["first"]);
+
+        // Idle for several ping rounds, standing in for a NAT timeout
+        tokio::time::sleep(Duration::from_secs(4)).await;
+        let mut file = std::fs::OpenOptions::new()
+            .append(true)
+            .open(&log_path)
+            .unwrap();
+        file.write_all(b"second\n").unwrap();
+
+        let message = tokio::time::timeout(Duration::from_secs(5), lines.message())
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap();
+        assert_eq!(message.lines, vec!["second"]);
+    }
+}
@@ -0,0 +1,209 @@
+use crate::error::{TappError, TappResult};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tracing::{info, warn};
+use tracing_subscriber::{reload, EnvFilter, Registry};
+
+/// Reloadable filter layer installed by `init_tracing`
+pub type FilterLayer = reload::Layer<EnvFilter, Registry>;
+
+/// Runtime control of the log filter
+/// The filter installed at startup can be swapped for another EnvFilter
+/// (e.g. `info,tapp_service::boot=debug`) without a restart, optionally
+/// returning to the startup filter after a while.
+#[derive(Clone)]
+pub struct LogLevelControl {
+    handle: reload::Handle<EnvFilter, Registry>,
+    initial: String,
+    // Directives appended to every filter (the trace span switch)
+    fixed_directives: Vec<String>,
+    state: Arc<Mutex<FilterState>>,
+}
+
+struct FilterState {
+    filter: String,
+    // Bumped on every change, so a pending revert only undoes its own change
+    generation: u64,
+}
+
+impl LogLevelControl {
+    /// Reloadable layer starting with `initial`, and its control
+    pub fn new(initial: &str, fixed_directives: Vec<String>) -> TappResult<(FilterLayer, Self)> {
+        let filter = Self::build(initial, &fixed_directives)?;
+        let (layer, handle) = reload::Layer::new(filter);
+        Ok((
+            layer,
+            Self {
+                handle,
+                initial: initial.to_string(),
+                fixed_directives,
+                state: Arc::new(Mutex::new(FilterState {
+                    filter: initial.to_string(),
+                    generation: 0,
+                })),
+            },
+        ))
+    }
+
+    /// Filter installed at startup
+    pub fn initial(&self) -> &str {
+        &self.initial
+    }
+
+    /// Filter currently applied
+    pub fn current(&self) -> String {
+        self.state.lock().unwrap().filter.clone()
+    }
+
+    /// Apply a new filter and return the previous one
+    /// The filter is validated first; an invalid one leaves the current
+    /// filter in place. With `revert_after`, the startup filter comes back
+    /// after that long unless the filter was changed again in between; the
+    /// timer needs a Tokio runtime.
+    pub fn set(&self, filter: &str, revert_after: Option<Duration>) -> TappResult<String> {
+        let new_filter = Self::build(filter, &self.fixed_directives)?;
+        let mut state = self.state.lock().unwrap();
+        let previous = self.apply(&mut state, filter, new_filter)?;
+
+        if let Some(delay) = revert_after {
+            let control = self.clone();
+            let generation = state.generation;
+            tokio::spawn(async move {
+                tokio::time::sleep(delay).await;
+                control.revert(generation);
+            });
+        }
+        Ok(previous)
+    }
+
+    fn apply(
+        &self,
+        state: &mut FilterState,
+        filter: &str,
+        new_filter: EnvFilter,
+    ) -> TappResult<String> {
+        self.handle
+            .reload(new_filter)
+            .map_err(|e| TappError::Internal(format!("Cannot reload log filter: {}", e)))?;
+        state.generation += 1;
+        Ok(std::mem::replace(&mut state.filter, filter.to_string()))
+    }
+
+    /// Go back to the startup filter unless it changed since `generation`
+    fn revert(&self, generation: u64) {
+        let mut state = self.state.lock().unwrap();
+        if state.generation != generation {
+            return;
+        }
+        let result = Self::build(&self.initial, &self.fixed_directives)
+            .and_then(|filter| self.apply(&mut state, &self.initial, filter));
+        drop(state);
+        match result {
+            Ok(previous) => info!(
+                previous = %previous,
+                filter = %self.initial,
+                event = "LOG_LEVEL_REVERTED",
+                "Log filter reverted to the configured level"
+            ),
+            Err(e) => warn!(error = %e, "Failed to revert log filter"),
+        }
+    }
+
+    fn build(filter: &str, fixed_directives: &[String]) -> TappResult<EnvFilter> {
+        let invalid = |reason: String| TappError::InvalidParameter {
+            field: "filter".to_string(),
+            reason,
+        };
+
+        let mut env_filter = EnvFilter::try_new(filter).map_err(|e| invalid(e.to_string()))?;
+        for directive in fixed_directives {
+            env_filter = env_filter.add_directive(
+                directive
+                    .parse()
+                    .map_err(|e| invalid(format!("{}: {}", directive, e)))?,
+            );
+        }
+        Ok(env_filter)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use tracing_subscriber::layer::SubscriberExt;
+
+    /// In-memory sink so tests can read back what was logged
+    #[derive(Clone, Default)]
+    struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl SharedBuffer {
+        fn take(&self) -> String {
+            String::from_utf8(std::mem::take(&mut *self.0.lock().unwrap())).unwrap()
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_swapped_filter_applies() {
+        let (layer, control) = LogLevelControl::new("info", vec!["noisy=off".to_string()]).unwrap();
+        let buffer = SharedBuffer::default();
+        let writer = buffer.clone();
+        let subscriber = tracing_subscriber::registry().with(layer).with(
+            tracing_subscriber::fmt::layer()
+                .with_ansi(false)
+                .with_writer(move || writer.clone()),
+        );
+        let _default = tracing::subscriber::set_default(subscriber);
+
+        tracing::debug!("debug before");
+        tracing::info!("info before");
+        let logged = buffer.take();
+        assert!(!logged.contains("debug before"));
+        assert!(logged.contains("info before"));
+
+        // Invalid filters are rejected and change nothing
+        let err = control.set("info,tapp_service=loud", None).unwrap_err();
+        assert!(matches!(err, TappError::InvalidParameter { .. }));
+        assert_eq!(control.current(), "info");
+
+        let previous = control
+            .set(
+                "warn,tapp_service::log_level=debug",
+                Some(Duration::from_secs(60)),
+            )
+            .unwrap();
+        assert_eq!(previous, "info");
+        assert_eq!(control.current(), "warn,tapp_service::log_level=debug");
+        tracing::debug!("debug here");
+        tracing::info!(target: "other", "info elsewhere");
+        tracing::warn!(target: "noisy", "fixed directive kept");
+        let logged = buffer.take();
+        assert!(logged.contains("debug here"));
+        assert!(!logged.contains("info elsewhere"));
+        assert!(!logged.contains("fixed directive kept"));
+
+        // The startup filter comes back once the delay passes
+        tokio::time::sleep(Duration::from_secs(61)).await;
+        assert_eq!(control.current(), "info");
+        buffer.take();
+        tracing::debug!("debug after revert");
+        assert!(!buffer.take().contains("debug after revert"));
+
+        // A later change cancels the pending revert of an earlier one
+        control.set("debug", Some(Duration::from_secs(10))).unwrap();
+        control.set("trace", None).unwrap();
+        tokio::time::sleep(Duration::from_secs(11)).await;
+        assert_eq!(control.current(), "trace");
+    }
+}
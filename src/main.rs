@@ -1,35 +1,107 @@
 use clap::Parser;
+use std::future::Future;
+use std::io::Write;
 use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
 use tapp_service::{
-    auth_layer::ApiKeyLayer, config::TappConfig, init_tracing, TappServiceImpl, TappServiceServer,
-    VERSION,
+    attestor_auth_layer::AttestorAuthLayer,
+    audit_layer::{AuditLayer, AuditLogger},
+    auth_layer::ApiKeyLayer,
+    config::{TappConfig, UnixSocketConfig},
+    config_template,
+    error::ConfigError,
+    health::{self, HealthBypassLayer, HealthMonitor},
+    init_tracing,
+    ip_filter_layer::{IpFilter, IpFilterLayer},
+    listeners,
+    method_filter_layer::{methods_in_scope, MethodFilterLayer, MethodScope},
+    panic_layer::{self, CatchPanicLayer},
+    proto::v2::tapp_service_server::TappServiceServer as TappServiceV2Server,
+    rate_limit_layer::{RateLimitLayer, RateLimiter},
+    reflection,
+    reload::ConfigReloader,
+    request_id_layer::RequestIdLayer,
+    telemetry::TraceLayer,
+    timeout_layer::TimeoutLayer,
+    web_layer::WebLayer,
+    TappServiceImpl, TappServiceServer, VERSION,
 };
-use tonic::transport::Server;
+use tokio::net::UnixListener;
+use tokio::task::JoinSet;
+use tokio_stream::wrappers::{TcpListenerStream, UnixListenerStream};
+use tokio_util::sync::CancellationToken;
 use tower::ServiceBuilder;
-use tracing::{error, info};
+use tracing::{error, info, warn};
 
 #[derive(Parser, Debug)]
 #[command(name = "tapp-server")]
 #[command(about = "TAPP gRPC Server", version = VERSION)]
 struct Args {
-    /// Path to configuration file
+    /// Path to configuration file, or a directory of *.toml files merged in
+    /// lexical order
     #[arg(short, long, default_value = "/etc/tapp/config.toml")]
     config: String,
 
-    /// Bind address (overrides config)
+    /// Bind address(es), comma-separated (overrides config)
     #[arg(short, long)]
     bind: Option<String>,
 
     /// Enable verbose logging (overrides config)
     #[arg(short, long)]
     verbose: bool,
+
+    /// Validate the configuration, print it with secrets masked and exit
+    #[arg(long)]
+    check_config: bool,
+
+    /// Write a commented default configuration to PATH (or stdout) and exit
+    #[arg(long, value_name = "PATH", num_args = 0..=1, default_missing_value = "-")]
+    generate_config: Option<String>,
 }
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let args = Args::parse();
 
+    if let Some(path) = &args.generate_config {
+        let content = config_template::default_config();
+        if path == "-" {
+            print!("{}", content);
+        } else {
+            // Never overwrite an existing configuration
+            std::fs::OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(path)
+                .and_then(|mut file| file.write_all(content.as_bytes()))
+                .map_err(|e| format!("Failed to write {}: {}", path, e))?;
+            println!("✓ Default configuration written to: {}", path);
+        }
+        return Ok(());
+    }
+
+    if args.check_config {
+        // Unlike a normal start, a missing or unreadable file is an error
+        let mut config = match TappConfig::load(args.config.clone()) {
+            Ok(config) => config,
+            Err(e) => {
+                eprintln!("✗ Failed to load config from {}: {}", args.config, e);
+                std::process::exit(1);
+            }
+        };
+        apply_args(&mut config, &args);
+        if let Err(e) = config.validate() {
+            report_invalid(e);
+            std::process::exit(1);
+        }
+        println!("OK");
+        print!("{}", config_template::render(&config.redacted()));
+        return Ok(());
+    }
+
     // Step 1: Load configuration first (before initializing logging)
+    // TAPP_* environment variables override the file (or the defaults)
     let mut config = match TappConfig::load(args.config.clone()) {
         Ok(config) => {
             // Use println because tracing is not initialized yet
@@ -39,17 +111,24 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         Err(e) => {
             println!("⚠ Failed to load config from {}: {}", args.config, e);
             println!("Using default configuration");
-            TappConfig::default()
+            TappConfig::from_env()?
         }
     };
 
-    // Step 2: Override config with command-line args if provided
-    if args.verbose {
-        config.logging.level = "debug".to_string();
+    // Step 2: Override config with command-line args if provided; they take
+    // precedence over the environment
+    apply_args(&mut config, &args);
+
+    // Report every problem of the final configuration before starting anything
+    if let Err(e) = config.validate() {
+        report_invalid(e);
+        std::process::exit(1);
     }
 
     // Step 3: Initialize tracing with config
-    init_tracing(&config.logging)?;
+    let tracing_handles = init_tracing(&config.logging, config.telemetry.as_ref())?;
+    // Caught panics are logged with their backtrace
+    panic_layer::install_panic_hook();
 
     info!("🚀 Starting TDX TAPP Service Server");
     info!("Version: {}", VERSION);
@@ -60,23 +139,49 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         logging_file = ?config.logging.file_path,
         "Logging initialized"
     );
+    if let Some(telemetry) = &config.telemetry {
+        info!(
+            endpoint = %telemetry.endpoint,
+            service_name = %telemetry.service_name,
+            sampling_ratio = telemetry.sampling_ratio,
+            "OpenTelemetry trace export enabled"
+        );
+    }
 
-    // Step 4: Determine bind address
-    let bind_address = args
-        .bind
-        .unwrap_or_else(|| config.server.bind_address.clone());
+    // Step 4: Bind every listen address now; one that is invalid or taken
+    // stops startup before anything runs
+    let tcp_listeners = match listeners::bind_all(config.server.bind_address.iter()) {
+        Ok(bound) => bound,
+        Err(e) => {
+            error!("✗ {}", e);
+            std::process::exit(1);
+        }
+    };
+    for (addr, _) in &tcp_listeners {
+        info!("Binding to address: {}", addr);
+    }
 
-    let addr: SocketAddr = bind_address
-        .parse()
-        .map_err(|e| format!("Invalid bind address '{}': {}", bind_address, e))?;
+    let admin_listener = match config.server.admin_bind_address.as_deref() {
+        Some(admin) => match listeners::bind_all([admin]) {
+            Ok(mut bound) => bound.pop(),
+            Err(e) => {
+                error!("✗ Admin listener: {}", e);
+                std::process::exit(1);
+            }
+        },
+        None => None,
+    };
+    let admin_addr: Option<SocketAddr> = admin_listener.as_ref().map(|(addr, _)| *addr);
 
-    info!("Binding to address: {}", addr);
+    // Health statuses are NOT_SERVING until the service is initialized
+    let (mut health_reporter, health_service) = tonic_health::server::health_reporter();
+    health::report_starting(&mut health_reporter).await;
 
     // Step 5: Initialize service
     let service = match TappServiceImpl::new(config.clone()).await {
         Ok(service) => {
             info!("✓ TAPP service initialized successfully");
-            service
+            service.with_log_level(tracing_handles.log_level)
         }
         Err(e) => {
             error!("✗ Failed to initialize TAPP service: {}", e);
@@ -89,11 +194,19 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     // Log API key configuration status
     if let Some(ref api_config) = api_key_config {
+        if let Err(e) = api_config.validate() {
+            error!("✗ Invalid API key configuration: {}", e);
+            std::process::exit(1);
+        }
         if api_config.enabled {
             info!(
                 "🔐 API key authentication enabled with {} key(s)",
                 api_config.keys.len()
             );
+            // Names and sources only, never the keys themselves
+            for entry in &api_config.keys {
+                info!("   Key '{}' from {}", entry.name(), entry.source());
+            }
             if api_config.protected_methods.is_empty() {
                 info!("   All methods require API key authentication");
             } else {
@@ -109,33 +222,573 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         info!("🔓 API key authentication not configured");
     }
 
-    // Step 7: Create gRPC server with API key layer
-    // The layer automatically validates API keys based on configuration
+    // Log rate limit configuration status
+    let rate_limit_config = config.server.rate_limit.clone();
+    match rate_limit_config {
+        Some(ref rl) if rl.enabled => {
+            info!(
+                "🚦 Rate limiting enabled: {} req/min default, {} method override(s)",
+                rl.default_requests_per_minute,
+                rl.method_limits.len()
+            );
+        }
+        _ => info!("🚦 Rate limiting disabled"),
+    }
+    let rate_limiter = Arc::new(RateLimiter::new(rate_limit_config));
+    // The layer shares the service's key configuration so GetServiceStatus
+    // reports expiring keys from the currently active (reloaded) set
+    let api_key_layer = ApiKeyLayer::with_config_handle(service.api_key_config.clone());
+
+    // Log source address filter status
+    let ip_filter_config = config.server.ip_filter.clone();
+    match ip_filter_config {
+        Some(ref filter) if filter.enabled => {
+            info!(
+                "🧱 IP filter enabled: {} allow, {} deny CIDR(s)",
+                filter.allow.len(),
+                filter.deny.len()
+            );
+        }
+        _ => info!("🧱 IP filter disabled"),
+    }
+    let ip_filter = match IpFilter::new(ip_filter_config) {
+        Ok(filter) => Arc::new(filter),
+        Err(e) => {
+            error!("✗ Invalid IP filter configuration: {}", e);
+            std::process::exit(1);
+        }
+    };
+
+    // Cancelled on SIGINT/SIGTERM; servers and background tasks stop on it
+    let shutdown = CancellationToken::new();
+    let mut background = JoinSet::new();
+
+    // Reload [server.api_key], [server.rate_limit] and [server.ip_filter] on
+    // SIGHUP without restarting
+    let reloader = ConfigReloader::new(
+        args.config.clone(),
+        api_key_layer.config_handle(),
+        rate_limiter.clone(),
+        ip_filter.clone(),
+    );
+    background.spawn(until_shutdown(shutdown.clone(), reloader.run()));
+    info!("♻️  Send SIGHUP to reload API key, rate limit and IP filter configuration");
+
+    // Audit log of who called which method and with what outcome
+    let audit_logger = match config.audit {
+        Some(ref audit_config) if audit_config.enabled => {
+            match AuditLogger::new(audit_config, &config.logging) {
+                Ok(logger) => {
+                    info!(
+                        "📝 Audit log enabled: {}",
+                        audit_config.file_path.display()
+                    );
+                    Some(Arc::new(logger))
+                }
+                Err(e) => {
+                    error!(
+                        "✗ Failed to open audit log {}: {}",
+                        audit_config.file_path.display(),
+                        e
+                    );
+                    std::process::exit(1);
+                }
+            }
+        }
+        _ => {
+            info!("📝 Audit log disabled");
+            None
+        }
+    };
+
+    // Step 7: Create gRPC server with audit, listener method filter, IP filter,
+    // API key, rate limit, attestor and timeout layers
+    // The layers automatically apply based on configuration
     // No need to modify individual RPC methods!
-    let layer = ServiceBuilder::new()
-        .layer(ApiKeyLayer::new(api_key_config))
-        .into_inner();
+    // Each request first gets its request ID, so every log line and the
+    // response carry it, then a trace span when telemetry is configured.
+    // Audit is next so throttled and rejected calls are recorded as well;
+    // blocked sources are dropped before they can present credentials.
+    // Rate limits follow the API key check so buckets are keyed on the
+    // validated key rather than whatever a caller sends.
+    // App key callers are checked against the contract only once their API
+    // key is accepted.
+    // The timeout only applies to handling, and its DEADLINE_EXCEEDED is
+    // audited like any other outcome, as is the INTERNAL status a panicking
+    // handler is answered with.
+    // grpc.health.v1.Health bypasses all of them. gRPC-Web requests are
+    // translated before anything else, so every layer sees plain gRPC.
+    let audit_layer = AuditLayer::new(audit_logger);
+    let ip_filter_layer = IpFilterLayer::new(ip_filter);
+    let rate_limit_layer = RateLimitLayer::new(rate_limiter);
+    let trace_layer = TraceLayer::new(config.telemetry.is_some());
+    let timeout_layer = TimeoutLayer::new(&config.server);
+    let attestor_auth_layer = AttestorAuthLayer::new(service.attestor_auth.clone());
+    let web_layer = WebLayer::new(config.server.web.as_ref());
+    if let Some(web) = &config.server.web {
+        info!(
+            "🌍 gRPC-Web enabled, allowed origins: {}",
+            if web.allowed_origins.is_empty() {
+                "same origin only".to_string()
+            } else {
+                web.allowed_origins.join(", ")
+            }
+        );
+    }
+    info!(
+        "⏱️  Request timeout: {}s ({} method override(s), streams unbounded)",
+        config.server.request_timeout_seconds,
+        config.server.method_timeout_seconds.len()
+    );
+    let layer_for = |method_filter: MethodFilterLayer| {
+        ServiceBuilder::new()
+            .layer(web_layer.clone())
+            .layer(HealthBypassLayer::new(
+                ServiceBuilder::new()
+                    .layer(RequestIdLayer::new())
+                    .layer(trace_layer.clone())
+                    .layer(audit_layer.clone())
+                    .layer(method_filter)
+                    .layer(ip_filter_layer.clone())
+                    .layer(api_key_layer.clone())
+                    .layer(rate_limit_layer.clone())
+                    .layer(attestor_auth_layer.clone())
+                    .layer(timeout_layer.clone())
+                    .layer(CatchPanicLayer::new())
+                    .into_inner(),
+            ))
+            .into_inner()
+    };
 
-    let server = Server::builder()
-        .layer(layer)
-        .add_service(TappServiceServer::new(service))
-        .serve(addr);
+    let service = Arc::new(service);
 
-    info!("🌐 TAPP gRPC server starting on {}", addr);
+    // Health statuses follow the Docker daemon and attestation agent from here on
+    let health_config = &config.server.health;
+    info!(
+        "❤️  gRPC health service: \"{}\" and \"{}\", checked every {}s",
+        health::OVERALL_HEALTH_NAME,
+        health::TAPP_SERVICE_HEALTH_NAME,
+        health_config.check_interval_seconds
+    );
+    background.spawn(until_shutdown(
+        shutdown.clone(),
+        HealthMonitor::new(health_reporter, health_config)
+            .with_notifier(service.notifier.clone())
+            .run(service.boot_service.clone()),
+    ));
 
-    // Step 8: Handle shutdown gracefully
-    tokio::select! {
-        result = server => {
-            if let Err(e) = result {
-                error!("Server error: {}", e);
+    // With webhooks configured, disk thresholds are checked without waiting
+    // for a GetHostResources call
+    if service.notifier.stats().enabled {
+        let service = service.clone();
+        let interval = Duration::from_secs(health_config.check_interval_seconds.max(1));
+        background.spawn(until_shutdown(shutdown.clone(), async move {
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                ticker.tick().await;
+                if let Err(e) = service.host_monitor.resources(&service.boot_service).await {
+                    warn!(error = %e, "Host resource check failed");
+                }
+            }
+        }));
+    }
+
+    // Message size limits; the decoding limit carries headroom above the
+    // per-method limits so StartApp can reject oversized uploads with a
+    // descriptive INVALID_ARGUMENT instead of the codec's generic error
+    let max_decoding_message_size = config.server.codec_max_recv_message_bytes();
+    let max_encoding_message_size = config.server.max_send_message_bytes();
+    info!(
+        "📦 Message size limits: {} MB receive ({} method override(s)), {} MB send",
+        config.server.max_recv_message_mb,
+        config.server.method_max_recv_message_mb.len(),
+        config.server.max_send_message_mb
+    );
+    let compression = config.server.compression_encodings();
+    if !compression.is_empty() {
+        info!(
+            "🗜️  Message compression: {}",
+            config.server.compression.join(", ")
+        );
+    }
+    let grpc_service = |service: Arc<TappServiceImpl>| {
+        let mut server = TappServiceServer::from_arc(service)
+            .max_decoding_message_size(max_decoding_message_size)
+            .max_encoding_message_size(max_encoding_message_size);
+        for &encoding in &compression {
+            server = server.accept_compressed(encoding).send_compressed(encoding);
+        }
+        server
+    };
+    // v2 runs on the same service instance, so both versions share state
+    let grpc_service_v2 = |service: Arc<TappServiceImpl>| {
+        let mut server = TappServiceV2Server::from_arc(service)
+            .max_decoding_message_size(max_decoding_message_size)
+            .max_encoding_message_size(max_encoding_message_size);
+        for &encoding in &compression {
+            server = server.accept_compressed(encoding).send_compressed(encoding);
+        }
+        server
+    };
+
+    // Reflection lets grpcurl and grpcui discover the API; it requires an API
+    // key whenever authentication is enabled
+    let (reflection_v1, reflection_v1alpha) = if config.server.enable_reflection {
+        match (reflection::v1_service(), reflection::v1alpha_service()) {
+            (Ok(v1), Ok(v1alpha)) => {
+                info!("🔎 gRPC server reflection enabled");
+                (Some(v1), Some(v1alpha))
+            }
+            (Err(e), _) | (_, Err(e)) => {
+                error!("✗ {}", e);
+                std::process::exit(1);
+            }
+        }
+    } else {
+        info!("🔎 gRPC server reflection disabled");
+        (None, None)
+    };
+
+    let http2 = &config.server.http2;
+    if let Some(interval) = http2.keepalive_interval() {
+        info!(
+            "💓 HTTP/2 keepalive every {}s, timeout {}s",
+            interval.as_secs(),
+            http2.keepalive_timeout_seconds
+        );
+    }
+
+    // With a separate admin listener the main listener only serves public methods
+    let main_method_filter = if admin_addr.is_some() {
+        MethodFilterLayer::public_only()
+    } else {
+        MethodFilterLayer::unrestricted()
+    };
+
+    // Step 8: One server task per listener, all sharing the service instance
+    // and the layers' state
+    let mut servers = JoinSet::new();
+    for (addr, listener) in tcp_listeners {
+        info!("🌐 TAPP gRPC server starting on {}", addr);
+        let server = listeners::server_builder(&config.server.http2)
+            .accept_http1(web_layer.enabled())
+            .layer(layer_for(main_method_filter.clone()))
+            .add_service(health_service.clone())
+            .add_service(grpc_service(service.clone()))
+            .add_service(grpc_service_v2(service.clone()))
+            .add_optional_service(reflection_v1.clone())
+            .add_optional_service(reflection_v1alpha.clone())
+            .serve_with_incoming_shutdown(
+                TcpListenerStream::new(listener),
+                shutdown.clone().cancelled_owned(),
+            );
+        servers.spawn(async move { (addr.to_string(), server.await) });
+    }
+
+    let bind_addresses = &config.server.bind_address;
+    let public_methods = methods_in_scope(MethodScope::Public).join(", ");
+    let admin_methods = methods_in_scope(MethodScope::Admin).join(", ");
+    match admin_addr {
+        Some(admin_addr) => {
+            info!(
+                "   {} serves public methods: {}",
+                bind_addresses, public_methods
+            );
+            info!(
+                "🛡️  Admin gRPC server starting on {} (all methods, admin-only: {})",
+                admin_addr, admin_methods
+            );
+        }
+        None => info!(
+            "   {} serves all methods (no admin listener configured)",
+            bind_addresses
+        ),
+    }
+
+    if let Some((admin_addr, listener)) = admin_listener {
+        let server = listeners::server_builder(&config.server.http2)
+            .accept_http1(web_layer.enabled())
+            .layer(layer_for(MethodFilterLayer::unrestricted()))
+            .add_service(health_service.clone())
+            .add_service(grpc_service(service.clone()))
+            .add_service(grpc_service_v2(service.clone()))
+            .add_optional_service(reflection_v1.clone())
+            .add_optional_service(reflection_v1alpha.clone())
+            .serve_with_incoming_shutdown(
+                TcpListenerStream::new(listener),
+                shutdown.clone().cancelled_owned(),
+            );
+        servers.spawn(async move { (format!("{} (admin)", admin_addr), server.await) });
+    }
+
+    // Step 8: Optional Unix domain socket listener serving the same service
+    let unix_listener = match config.server.unix_socket {
+        Some(ref uds_config) => match bind_unix_socket(uds_config) {
+            Ok(listener) => {
+                info!(
+                    "🔌 TAPP gRPC server listening on unix socket {} (all methods)",
+                    uds_config.path.display()
+                );
+                Some(listener)
+            }
+            Err(e) => {
+                error!(
+                    "✗ Failed to bind unix socket {}: {}",
+                    uds_config.path.display(),
+                    e
+                );
                 std::process::exit(1);
             }
+        },
+        None => None,
+    };
+
+    if let Some(listener) = unix_listener {
+        let label = config
+            .server
+            .unix_socket
+            .as_ref()
+            .map(|uds_config| uds_config.path.display().to_string())
+            .unwrap_or_default();
+        let server = listeners::server_builder(&config.server.http2)
+            .accept_http1(web_layer.enabled())
+            .layer(layer_for(MethodFilterLayer::unrestricted()))
+            .add_service(health_service)
+            .add_service(grpc_service(service.clone()))
+            .add_service(grpc_service_v2(service.clone()))
+            .add_optional_service(reflection_v1.clone())
+            .add_optional_service(reflection_v1alpha.clone())
+            .serve_with_incoming_shutdown(
+                UnixListenerStream::new(listener),
+                shutdown.clone().cancelled_owned(),
+            );
+        servers.spawn(async move { (label, server.await) });
+    }
+
+    // Step 9: Shut down on SIGINT/SIGTERM; a listener that stops takes the
+    // others down with it
+    let failed = tokio::select! {
+        Some(joined) = servers.join_next() => {
+            match joined {
+                Ok((listener, Ok(()))) => {
+                    warn!("Server on {} stopped", listener);
+                    false
+                }
+                Ok((listener, Err(e))) => {
+                    error!("Server error on {}: {}", listener, e);
+                    true
+                }
+                Err(e) => {
+                    error!("Server task failed: {}", e);
+                    true
+                }
+            }
+        }
+        signal = shutdown_signal() => {
+            info!("Received {}, shutting down", signal);
+            false
         }
-        _ = tokio::signal::ctrl_c() => {
-            info!("Received shutdown signal, stopping server");
+    };
+
+    // Stop accepting connections; in-flight RPCs and deployments get the
+    // grace period to finish before they are cut off
+    let grace = Duration::from_secs(config.server.shutdown_grace_seconds);
+    info!(
+        grace_seconds = grace.as_secs(),
+        "Draining connections and deployments"
+    );
+    shutdown.cancel();
+    let drain_servers = async {
+        while let Some(joined) = servers.join_next().await {
+            if let Ok((listener, Err(e))) = joined {
+                warn!("Server on {} stopped with an error: {}", listener, e);
+            }
         }
+    };
+    let (drained, ()) = tokio::join!(
+        tokio::time::timeout(grace, drain_servers),
+        service.shutdown(grace),
+    );
+    if drained.is_err() {
+        warn!(
+            "RPCs still in flight after {}s, closing their connections",
+            grace.as_secs()
+        );
+    }
+    servers.shutdown().await;
+    while background.join_next().await.is_some() {}
+
+    if let Some(ref uds_config) = config.server.unix_socket {
+        let _ = std::fs::remove_file(&uds_config.path);
+    }
+
+    if !failed {
+        info!("TAPP server shutdown complete");
     }
 
-    info!("TAPP server shutdown complete");
+    // Export the spans of the last requests and write the buffered log lines
+    // before exiting; process::exit skips destructors
+    tracing_handles.telemetry.shutdown();
+    drop(tracing_handles.log_writer);
+    if failed {
+        std::process::exit(1);
+    }
     Ok(())
 }
+
+/// Resolve on the first SIGINT or SIGTERM with the signal's name
+async fn shutdown_signal() -> &'static str {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let terminate = async {
+        match signal(SignalKind::terminate()) {
+            Ok(mut terminate) => {
+                terminate.recv().await;
+            }
+            Err(e) => {
+                error!("Failed to install SIGTERM handler: {}", e);
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+    tokio::select! {
+        _ = tokio::signal::ctrl_c() => "SIGINT",
+        _ = terminate => "SIGTERM",
+    }
+}
+
+/// Run `task` until it ends or `shutdown` is cancelled
+async fn until_shutdown(shutdown: CancellationToken, task: impl Future<Output = ()>) {
+    tokio::select! {
+        _ = shutdown.cancelled() => {}
+        _ = task => {}
+    }
+}
+
+/// Apply the command-line overrides to the loaded configuration
+fn apply_args(config: &mut TappConfig, args: &Args) {
+    if args.verbose {
+        config.logging.level = "debug".to_string();
+    }
+    if let Some(bind) = &args.bind {
+        config.server.bind_address = bind.as_str().into();
+    }
+}
+
+/// Print the problems found by `TappConfig::validate`
+fn report_invalid(e: ConfigError) {
+    match e {
+        ConfigError::Invalid { problems } => {
+            eprintln!("✗ Invalid configuration ({} problem(s)):", problems.len());
+            for problem in problems {
+                eprintln!("  - {}", problem);
+            }
+        }
+        e => eprintln!("✗ Invalid configuration: {}", e),
+    }
+}
+
+/// Bind the Unix domain socket listener, replacing any stale socket file
+/// and applying the configured permissions and ownership
+/// The socket is bound inside a private (0700) directory and only moved to
+/// `path` once its mode and owner are set, so nobody can connect through
+/// looser permissions in between. Anything at `path` other than a socket is
+/// left alone and fails the bind, as does a socket something still accepts
+/// connections on (e.g. another running server).
+fn bind_unix_socket(config: &UnixSocketConfig) -> std::io::Result<UnixListener> {
+    use std::os::unix::fs::{DirBuilderExt, FileTypeExt, PermissionsExt};
+
+    let mode = u32::from_str_radix(&config.mode, 8).map_err(|e| {
+        std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            format!("Invalid socket mode '{}': {}", config.mode, e),
+        )
+    })?;
+
+    let parent = match config.path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => std::path::Path::new("."),
+    };
+    std::fs::create_dir_all(parent)?;
+
+    // Only a socket left behind by a previous run is replaced
+    match std::fs::symlink_metadata(&config.path) {
+        Ok(metadata) if !metadata.file_type().is_socket() => {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::AlreadyExists,
+                format!(
+                    "{} exists and is not a socket; refusing to replace it",
+                    config.path.display()
+                ),
+            ));
+        }
+        Ok(_) => match std::os::unix::net::UnixStream::connect(&config.path) {
+            Ok(_) => {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::AddrInUse,
+                    format!(
+                        "{} is in use by a running process; refusing to replace it",
+                        config.path.display()
+                    ),
+                ));
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::ConnectionRefused => {}
+            Err(e) => return Err(e),
+        },
+        Err(_) => {}
+    }
+
+    let staging = parent.join(format!(".tapp-socket-{}", std::process::id()));
+    if staging.exists() {
+        std::fs::remove_dir_all(&staging)?;
+    }
+    std::fs::DirBuilder::new().mode(0o700).create(&staging)?;
+    let staged = staging.join("tapp.sock");
+    let bound = (|| -> std::io::Result<UnixListener> {
+        let listener = UnixListener::bind(&staged)?;
+        std::fs::set_permissions(&staged, std::fs::Permissions::from_mode(mode))?;
+        if config.uid.is_some() || config.gid.is_some() {
+            std::os::unix::fs::chown(&staged, config.uid, config.gid)?;
+        }
+        std::fs::rename(&staged, &config.path)?;
+        Ok(listener)
+    })();
+    let _ = std::fs::remove_dir_all(&staging);
+    bound
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_bind_unix_socket_keeps_live_socket() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = UnixSocketConfig {
+            path: dir.path().join("tapp.sock"),
+            ..Default::default()
+        };
+
+        let first = bind_unix_socket(&config).unwrap();
+        let err = bind_unix_socket(&config).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::AddrInUse);
+        // The first listener still owns the path
+        assert!(std::os::unix::net::UnixStream::connect(&config.path).is_ok());
+
+        // Once it is gone the stale socket is replaced
+        drop(first);
+        let _second = bind_unix_socket(&config).unwrap();
+        assert!(std::os::unix::net::UnixStream::connect(&config.path).is_ok());
+
+        // Other files are never replaced
+        let file = UnixSocketConfig {
+            path: dir.path().join("not-a-socket"),
+            ..Default::default()
+        };
+        std::fs::write(&file.path, "data").unwrap();
+        assert_eq!(
+            bind_unix_socket(&file).unwrap_err().kind(),
+            std::io::ErrorKind::AlreadyExists
+        );
+    }
+}
@@ -0,0 +1,227 @@
+use std::task::{Context, Poll};
+use tonic::body::BoxBody;
+use tonic::Status;
+use tower::{Layer, Service};
+use tracing::warn;
+
+/// Where an RPC may be served when public and admin listeners are split
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MethodScope {
+    /// Read-only and attestation methods, reachable on every listener
+    Public,
+    /// Mutating and sensitive methods, reachable only on the admin listener
+    /// (and the Unix socket)
+    Admin,
+}
+
+/// Classification of every TappService RPC
+/// Every RPC in proto/tapp_service.proto must be listed here; unlisted
+/// methods are treated as admin-only. Method names are matched without the
+/// service, so the entries also cover tapp_service.v2.TappService.
+pub const METHOD_SCOPES: &[(&str, MethodScope)] = &[
+    ("StartApp", MethodScope::Admin),
+    ("StopApp", MethodScope::Admin),
+    ("TransferAppOwnership", MethodScope::Admin),
+    ("GetTaskStatus", MethodScope::Public),
+    ("WatchTask", MethodScope::Public),
+    ("CancelTask", MethodScope::Admin),
+    ("RetryTask", MethodScope::Admin),
+    ("ListAppMeasurements", MethodScope::Public),
+    ("GetEvidence", MethodScope::Public),
+    ("GetAppKey", MethodScope::Public),
+    ("GetAttestedAppKey", MethodScope::Public),
+    ("GetAppSecretKey", MethodScope::Admin),
+    ("SignTransaction", MethodScope::Admin),
+    ("GetNonce", MethodScope::Public),
+    ("GetAppInfo", MethodScope::Public),
+    ("GetServiceStatus", MethodScope::Public),
+    ("GetHostResources", MethodScope::Public),
+    ("GetServiceLogs", MethodScope::Public),
+    ("StreamServiceLogs", MethodScope::Public),
+    ("DownloadLogFile", MethodScope::Public),
+    ("GetAppLogs", MethodScope::Public),
+    ("SetLogLevel", MethodScope::Admin),
+    ("ForceRemoveApp", MethodScope::Admin),
+    ("SealData", MethodScope::Admin),
+    ("UnsealData", MethodScope::Admin),
+    ("ListSealedBlobs", MethodScope::Admin),
+    ("DeleteSealedBlob", MethodScope::Admin),
+    ("InitCluster", MethodScope::Admin),
+    ("GetClusterStatus", MethodScope::Public),
+    ("RequestKeyShare", MethodScope::Public),
+    ("JoinCluster", MethodScope::Admin),
+    ("RecoverShare", MethodScope::Public),
+    ("ContributeShare", MethodScope::Public),
+    ("RecoverCluster", MethodScope::Admin),
+    ("ExportKeystoreBackup", MethodScope::Admin),
+    ("ImportKeystoreBackup", MethodScope::Admin),
+    ("VerifyRtmrReplay", MethodScope::Public),
+];
+
+/// Scope of a method (admin-only if not classified)
+pub fn method_scope(method: &str) -> MethodScope {
+    METHOD_SCOPES
+        .iter()
+        .find(|(name, _)| *name == method)
+        .map(|(_, scope)| *scope)
+        .unwrap_or(MethodScope::Admin)
+}
+
+/// Names of the methods with the given scope
+pub fn methods_in_scope(scope: MethodScope) -> Vec<&'static str> {
+    METHOD_SCOPES
+        .iter()
+        .filter(|(_, s)| *s == scope)
+        .map(|(name, _)| *name)
+        .collect()
+}
+
+/// Tower Layer restricting a listener to public methods
+#[derive(Clone)]
+pub struct MethodFilterLayer {
+    admin_allowed: bool,
+}
+
+impl MethodFilterLayer {
+    /// Listener serving only public methods
+    pub fn public_only() -> Self {
+        Self {
+            admin_allowed: false,
+        }
+    }
+
+    /// Listener serving every method
+    pub fn unrestricted() -> Self {
+        Self {
+            admin_allowed: true,
+        }
+    }
+}
+
+impl<S> Layer<S> for MethodFilterLayer {
+    type Service = MethodFilterMiddleware<S>;
+
+    fn layer(&self, service: S) -> Self::Service {
+        MethodFilterMiddleware {
+            inner: service,
+            admin_allowed: self.admin_allowed,
+        }
+    }
+}
+
+/// Middleware that rejects admin-only methods on a public listener
+#[derive(Clone)]
+pub struct MethodFilterMiddleware<S> {
+    inner: S,
+    admin_allowed: bool,
+}
+
+impl<S> Service<http::Request<BoxBody>> for MethodFilterMiddleware<S>
+where
+    S: Service<http::Request<BoxBody>, Response = http::Response<BoxBody>>
+        + Clone
+        + Send
+        + 'static,
+    S::Error: Into<Box<dyn std::error::Error + Send + Sync>> + Send,
+    S::Future: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = futures_util::future::BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: http::Request<BoxBody>) -> Self::Future {
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+        let admin_allowed = self.admin_allowed;
+
+        Box::pin(async move {
+            let method_name = crate::utils::grpc_method_name(req.uri().path());
+
+            if !admin_allowed && method_scope(method_name) == MethodScope::Admin {
+                warn!(
+                    method = %method_name,
+                    remote_addr = ?crate::utils::remote_ip(&req),
+                    event = "ADMIN_METHOD_ON_PUBLIC_LISTENER",
+                    "Admin-only method called on the public listener"
+                );
+                return Ok(Status::permission_denied(format!(
+                    "{} is only available on the admin listener",
+                    method_name
+                ))
+                .into_http());
+            }
+
+            inner.call(req).await
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(method: &str) -> http::Request<BoxBody> {
+        http::Request::builder()
+            .uri(format!("/tapp_service.TappService/{}", method))
+            .body(tonic::body::empty_body())
+            .unwrap()
+    }
+
+    fn grpc_code(response: &http::Response<BoxBody>) -> tonic::Code {
+        Status::from_header_map(response.headers())
+            .map(|s| s.code())
+            .unwrap_or(tonic::Code::Ok)
+    }
+
+    #[test]
+    fn test_every_rpc_is_classified() {
+        // v2 keeps the v1 method names, so one table covers both
+        for proto in [
+            include_str!("../proto/tapp_service.proto"),
+            include_str!("../proto/tapp_service_v2.proto"),
+        ] {
+            let rpcs: Vec<&str> = proto
+                .lines()
+                .filter_map(|line| line.trim().strip_prefix("rpc "))
+                .filter_map(|rest| rest.split('(').next())
+                .map(str::trim)
+                .collect();
+
+            assert!(!rpcs.is_empty());
+            for rpc in &rpcs {
+                assert!(
+                    METHOD_SCOPES.iter().any(|(name, _)| name == rpc),
+                    "RPC {} is missing from METHOD_SCOPES",
+                    rpc
+                );
+            }
+            assert_eq!(rpcs.len(), METHOD_SCOPES.len());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_public_listener_rejects_admin_methods() {
+        let inner = tower::service_fn(|_req: http::Request<BoxBody>| async {
+            Ok::<_, std::convert::Infallible>(http::Response::new(tonic::body::empty_body()))
+        });
+        let mut public = MethodFilterLayer::public_only().layer(inner.clone());
+        let mut admin = MethodFilterLayer::unrestricted().layer(inner);
+
+        let response = public.call(request("GetEvidence")).await.unwrap();
+        assert_eq!(grpc_code(&response), tonic::Code::Ok);
+
+        let response = public.call(request("StartApp")).await.unwrap();
+        assert_eq!(grpc_code(&response), tonic::Code::PermissionDenied);
+
+        // Unclassified methods are treated as admin-only
+        let response = public.call(request("SomethingNew")).await.unwrap();
+        assert_eq!(grpc_code(&response), tonic::Code::PermissionDenied);
+
+        let response = admin.call(request("StartApp")).await.unwrap();
+        assert_eq!(grpc_code(&response), tonic::Code::Ok);
+    }
+}
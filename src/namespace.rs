@@ -0,0 +1,229 @@
+//! App namespaces on shared nodes
+//!
+//! With `server.api_key.namespaces` enabled, every client key works in the
+//! namespace its key entry names. Callers keep addressing apps by their bare
+//! app ID; internally apps are stored and addressed as `namespace/app_id`, so
+//! two teams may both deploy "web". On disk and in Docker the namespace
+//! prefixes the app directory and compose project (`namespace__app_id`).
+//!
+//! Admin keys see every app and address namespaced ones by their full ID.
+//! Callers without a namespaced key only see apps outside any namespace.
+
+use crate::auth_layer::AuthIdentity;
+use crate::config::ApiKeyRole;
+use crate::error::{TappError, TappResult};
+use std::borrow::Cow;
+
+/// Separator between namespace and app ID in internal app IDs
+pub const SEPARATOR: char = '/';
+
+/// Separator between namespace and app ID in directory and project names
+/// Namespaces cannot contain `_`, and app IDs outside a namespace cannot
+/// contain it twice in a row, so the mapping stays unambiguous.
+const STORAGE_SEPARATOR: &str = "__";
+
+/// Longest namespace accepted
+pub const MAX_NAMESPACE_LEN: usize = 32;
+
+/// Whether `namespace` is usable: lowercase letters, digits and `-`, up to
+/// 32 characters, starting with a letter or digit
+pub fn validate_namespace(namespace: &str) -> bool {
+    !namespace.is_empty()
+        && namespace.len() <= MAX_NAMESPACE_LEN
+        && !namespace.starts_with('-')
+        && namespace
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-')
+}
+
+/// Internal ID of `app_id` in `namespace`
+pub fn qualify(namespace: &str, app_id: &str) -> String {
+    format!("{}{}{}", namespace, SEPARATOR, app_id)
+}
+
+/// Namespace (if any) and bare app ID of an internal app ID
+pub fn split(app_id: &str) -> (Option<&str>, &str) {
+    match app_id.split_once(SEPARATOR) {
+        Some((namespace, app_id)) => (Some(namespace), app_id),
+        None => (None, app_id),
+    }
+}
+
+/// Whether an internal app ID is well formed: a valid app ID, optionally
+/// prefixed with a valid namespace
+pub fn validate_qualified_app_id(app_id: &str) -> bool {
+    match split(app_id) {
+        (Some(namespace), app_id) => {
+            validate_namespace(namespace) && crate::utils::validate_app_id(app_id)
+        }
+        (None, app_id) => crate::utils::validate_app_id(app_id),
+    }
+}
+
+/// Name of the app directory and compose project of an internal app ID
+pub fn storage_name(app_id: &str) -> Cow<'_, str> {
+    match split(app_id) {
+        (Some(namespace), app_id) => {
+            Cow::Owned(format!("{}{}{}", namespace, STORAGE_SEPARATOR, app_id))
+        }
+        (None, _) => Cow::Borrowed(app_id),
+    }
+}
+
+/// Internal app ID of an app directory or compose project name, the
+/// reverse of `storage_name`
+pub fn from_storage_name(name: &str) -> String {
+    match name.split_once(STORAGE_SEPARATOR) {
+        Some((namespace, app_id)) => qualify(namespace, app_id),
+        None => name.to_string(),
+    }
+}
+
+/// Apps a caller can see and how it addresses them
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AppScope {
+    /// Namespaces are disabled: app IDs are used as given
+    Disabled,
+    /// Every app; namespaced ones as `namespace/app_id` (admin keys)
+    All,
+    /// Apps outside any namespace (callers without a namespaced key)
+    Unnamespaced,
+    /// Apps of one namespace, by their bare app ID
+    Namespace(String),
+}
+
+impl AppScope {
+    /// Scope of a caller, `enabled` being `server.api_key.namespaces`
+    pub fn of(enabled: bool, identity: Option<&AuthIdentity>) -> Self {
+        if !enabled {
+            return AppScope::Disabled;
+        }
+        match identity {
+            Some(identity) if identity.role == ApiKeyRole::Admin => AppScope::All,
+            Some(AuthIdentity {
+                namespace: Some(namespace),
+                ..
+            }) => AppScope::Namespace(namespace.clone()),
+            _ => AppScope::Unnamespaced,
+        }
+    }
+
+    /// Internal ID of the app the caller calls `app_id`
+    pub fn qualify(&self, app_id: &str) -> TappResult<String> {
+        let invalid = || TappError::InvalidParameter {
+            field: "app_id".to_string(),
+            reason: format!("Invalid app ID format: {}", app_id),
+        };
+        match self {
+            AppScope::Disabled | AppScope::Unnamespaced if app_id.contains(SEPARATOR) => {
+                Err(invalid())
+            }
+            AppScope::Disabled => Ok(app_id.to_string()),
+            AppScope::All if app_id.contains(SEPARATOR) => Ok(app_id.to_string()),
+            AppScope::All | AppScope::Unnamespaced => {
+                if app_id.contains(STORAGE_SEPARATOR) {
+                    return Err(TappError::InvalidParameter {
+                        field: "app_id".to_string(),
+                        reason: format!(
+                            "App IDs outside a namespace cannot contain '{}': {}",
+                            STORAGE_SEPARATOR, app_id
+                        ),
+                    });
+                }
+                Ok(app_id.to_string())
+            }
+            AppScope::Namespace(_) if app_id.contains(SEPARATOR) => Err(invalid()),
+            AppScope::Namespace(namespace) => Ok(qualify(namespace, app_id)),
+        }
+    }
+
+    /// ID the caller knows an app by, or None if the app is outside the scope
+    pub fn localize<'a>(&self, app_id: &'a str) -> Option<&'a str> {
+        match (self, split(app_id)) {
+            (AppScope::Disabled | AppScope::All, _) => Some(app_id),
+            (AppScope::Unnamespaced, (None, app_id)) => Some(app_id),
+            (AppScope::Namespace(scope), (Some(namespace), app_id)) if scope == namespace => {
+                Some(app_id)
+            }
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn identity(role: ApiKeyRole, namespace: Option<&str>) -> AuthIdentity {
+        AuthIdentity {
+            key_name: "caller".to_string(),
+            role,
+            namespace: namespace.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn test_validate_namespace() {
+        for namespace in ["team-a", "a", "0g", &"a".repeat(MAX_NAMESPACE_LEN)] {
+            assert!(validate_namespace(namespace), "{}", namespace);
+        }
+        for namespace in ["", "Team", "team_a", "team/a", "-team", &"a".repeat(33)] {
+            assert!(!validate_namespace(namespace), "{}", namespace);
+        }
+    }
+
+    #[test]
+    fn test_scope_of_caller() {
+        let team = identity(ApiKeyRole::Client, Some("team-a"));
+        assert_eq!(AppScope::of(false, Some(&team)), AppScope::Disabled);
+        assert_eq!(
+            AppScope::of(true, Some(&team)),
+            AppScope::Namespace("team-a".to_string())
+        );
+        let admin = identity(ApiKeyRole::Admin, Some("team-a"));
+        assert_eq!(AppScope::of(true, Some(&admin)), AppScope::All);
+        let shared = identity(ApiKeyRole::Client, None);
+        assert_eq!(AppScope::of(true, Some(&shared)), AppScope::Unnamespaced);
+        assert_eq!(AppScope::of(true, None), AppScope::Unnamespaced);
+    }
+
+    #[test]
+    fn test_qualify_and_localize() {
+        let team_a = AppScope::Namespace("team-a".to_string());
+        let team_b = AppScope::Namespace("team-b".to_string());
+        let web = team_a.qualify("web").unwrap();
+        assert_eq!(web, "team-a/web");
+        assert_eq!(team_a.localize(&web), Some("web"));
+        assert_eq!(team_b.localize(&web), None);
+        assert_eq!(AppScope::Unnamespaced.localize(&web), None);
+        assert_eq!(AppScope::All.localize(&web), Some("team-a/web"));
+        assert_eq!(AppScope::All.qualify(&web).unwrap(), web);
+
+        // Namespaced callers cannot reach into another namespace
+        assert!(team_b.qualify("team-a/web").is_err());
+        assert!(AppScope::Unnamespaced.qualify("team-a/web").is_err());
+        assert!(AppScope::Disabled.qualify("team-a/web").is_err());
+
+        // Outside namespaces IDs stay as they are
+        assert_eq!(AppScope::Unnamespaced.qualify("web").unwrap(), "web");
+        assert_eq!(AppScope::Unnamespaced.localize("web"), Some("web"));
+        assert_eq!(team_a.localize("web"), None);
+        assert!(AppScope::Unnamespaced.qualify("team-a__web").is_err());
+        assert_eq!(
+            AppScope::Disabled.qualify("team-a__web").unwrap(),
+            "team-a__web"
+        );
+    }
+
+    #[test]
+    fn test_storage_name() {
+        assert_eq!(storage_name("web"), "web");
+        assert_eq!(storage_name("team-a/web"), "team-a__web");
+        assert_eq!(from_storage_name("web"), "web");
+        assert_eq!(from_storage_name("team-a__web_1"), "team-a/web_1");
+        assert!(validate_qualified_app_id("team-a/web"));
+        assert!(validate_qualified_app_id("web"));
+        assert!(!validate_qualified_app_id("Team/web"));
+        assert!(!validate_qualified_app_id("team-a/web/x"));
+    }
+}
@@ -1,14 +1,129 @@
-use std::collections::HashMap;
-use std::sync::Arc;
+use crate::config::SecurityConfig;
+use crate::error::NonceError;
+use std::collections::{BTreeSet, HashMap};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, Weak};
+use std::time::Duration;
 use tokio::sync::RwLock;
+use tokio::task::JoinHandle;
+
+/// Default interval between sweeps of expired nonces
+const DEFAULT_CLEANUP_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Default number of seconds a request may be older than the newest one
+/// accepted from the same deployer for the same operation
+pub const DEFAULT_TIMESTAMP_TOLERANCE: i64 = 5;
+
+/// Highest accepted timestamp per (deployer, operation)
+type HighWaterMarks = HashMap<(String, String), i64>;
+
+/// How a nonce was accepted
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NonceKind {
+    /// Client-chosen nonce with a timestamp inside the validity window
+    Client,
+    /// Challenge issued by this server; the timestamp is not used
+    Challenge,
+}
+
+/// Default maximum number of nonces remembered at once
+pub const DEFAULT_MAX_NONCES: usize = 100_000;
+
+/// Longest accepted nonce in bytes, so the entry cap also bounds memory
+pub const MAX_NONCE_LEN: usize = 128;
+
+/// Random client nonce for a deployer request: 16 bytes, hex encoded
+pub fn generate_nonce() -> String {
+    hex::encode(rand::random::<[u8; 16]>())
+}
+
+/// Start of the PERMISSION_DENIED message of a request whose nonce was
+/// rejected; clients retry those with a fresh challenge
+pub const NONCE_REJECTED_MESSAGE: &str = "Nonce verification failed";
+
+/// Lifetime of a server-issued challenge in seconds
+pub const CHALLENGE_TTL_SECONDS: i64 = 60;
+
+/// Size of a server-issued challenge in bytes (hex encoded on the wire)
+const CHALLENGE_LEN: usize = 32;
+
+/// Most unused, unexpired challenges one caller may hold, so callers
+/// without credentials cannot fill the challenge table
+pub const MAX_CHALLENGES_PER_CALLER: usize = 16;
+
+/// Used nonces indexed by nonce and by expiry
+#[derive(Default)]
+struct NonceTable {
+    // Map: nonce -> expiry timestamp
+    expiry_by_nonce: HashMap<String, i64>,
+    // (expiry timestamp, nonce), earliest expiry first
+    by_expiry: BTreeSet<(i64, String)>,
+    // Challenges issued per caller (challenge table only); consumed ones
+    // stay listed until they expire
+    by_caller: HashMap<String, Vec<(i64, String)>>,
+}
+
+impl NonceTable {
+    /// Remove nonces whose expiry has passed; returns how many were removed
+    fn purge_expired(&mut self, current_time: i64) -> usize {
+        let mut removed = 0;
+        while let Some((expiry, _)) = self.by_expiry.first() {
+            if *expiry > current_time {
+                break;
+            }
+            if let Some((_, nonce)) = self.by_expiry.pop_first() {
+                self.expiry_by_nonce.remove(&nonce);
+                removed += 1;
+            }
+        }
+        self.by_caller.retain(|_, issued| {
+            issued.retain(|(expiry, _)| *expiry > current_time);
+            !issued.is_empty()
+        });
+        removed
+    }
+
+    fn len(&self) -> usize {
+        self.expiry_by_nonce.len()
+    }
+}
 
 /// Nonce manager to prevent replay attacks
-/// Tracks used nonces with expiration
+/// Tracks used nonces with expiration, up to a fixed number of entries, and
+/// server-issued challenges that have not been used yet (separately, with
+/// the same capacity, so issuing challenges cannot crowd out used nonces).
+///
+/// At capacity, nonces that have already expired are dropped first. If the
+/// table is still full, new nonces are rejected (RESOURCE_EXHAUSTED) rather
+/// than evicting live entries: forgetting a nonce whose timestamp is still
+/// inside the window would let that request be replayed.
+///
+/// Expired entries are also swept by a background task. It is started on
+/// first use inside a tokio runtime (so construction works anywhere), is
+/// restarted if it dies, and stops when the manager is dropped.
+///
+/// Per deployer and operation, the highest accepted timestamp is kept as a
+/// high-water mark, so an older captured request with a different nonce
+/// cannot be replayed within the window. A mark older than the window is
+/// dropped: every timestamp that passes the window check is newer anyway.
 pub struct NonceManager {
-    // Map: nonce -> expiry timestamp
-    used_nonces: Arc<RwLock<HashMap<String, i64>>>,
+    used_nonces: Arc<RwLock<NonceTable>>,
+    // Issued but unconsumed challenges
+    issued_challenges: Arc<RwLock<NonceTable>>,
+    // Highest accepted timestamp per (deployer, operation)
+    high_water_marks: Arc<RwLock<HighWaterMarks>>,
+    // Seconds a request may lag behind its high-water mark
+    timestamp_tolerance: i64,
     // Nonce validity window in seconds (default: 5 minutes)
     validity_window: i64,
+    // Interval between sweeps of expired entries
+    cleanup_interval: Duration,
+    // Maximum number of nonces remembered at once (see `set_capacity`)
+    capacity: AtomicUsize,
+    // Nonces rejected because the table was full
+    rejected_at_capacity: AtomicU64,
+    // Background sweep of expired entries, started lazily
+    cleanup_task: Mutex<Option<JoinHandle<()>>>,
 }
 
 impl NonceManager {
@@ -19,68 +134,329 @@ impl NonceManager {
 
     /// Create a new NonceManager with custom validity window
     pub fn with_validity_window(validity_window: i64) -> Self {
-        let manager = Self {
-            used_nonces: Arc::new(RwLock::new(HashMap::new())),
+        Self::with_limits(validity_window, DEFAULT_MAX_NONCES)
+    }
+
+    /// Create a new NonceManager with custom validity window and capacity
+    pub fn with_limits(validity_window: i64, capacity: usize) -> Self {
+        Self::with_settings(validity_window, DEFAULT_CLEANUP_INTERVAL, capacity)
+    }
+
+    /// Create a NonceManager from the `[security]` config section
+    pub fn from_config(config: &SecurityConfig, capacity: usize) -> Self {
+        let mut manager = Self::with_settings(
+            config.nonce_validity_seconds as i64,
+            Duration::from_secs(config.nonce_cleanup_interval_seconds),
+            capacity,
+        );
+        manager.timestamp_tolerance = config.timestamp_tolerance_seconds as i64;
+        manager
+    }
+
+    /// Create a new NonceManager with custom validity window, cleanup interval and capacity
+    pub fn with_settings(
+        validity_window: i64,
+        cleanup_interval: Duration,
+        capacity: usize,
+    ) -> Self {
+        Self {
+            used_nonces: Arc::new(RwLock::new(NonceTable::default())),
+            issued_challenges: Arc::new(RwLock::new(NonceTable::default())),
+            high_water_marks: Arc::new(RwLock::new(HashMap::new())),
+            timestamp_tolerance: DEFAULT_TIMESTAMP_TOLERANCE,
             validity_window,
-        };
+            cleanup_interval,
+            capacity: AtomicUsize::new(capacity),
+            rejected_at_capacity: AtomicU64::new(0),
+            cleanup_task: Mutex::new(None),
+        }
+    }
+
+    /// Maximum number of nonces remembered at once, per table
+    pub fn capacity(&self) -> usize {
+        self.capacity.load(Ordering::Relaxed)
+    }
+
+    /// Change the capacity, e.g. after a configuration reload
+    /// Remembered nonces are kept: below the new capacity, nonces are
+    /// rejected until enough of them have expired.
+    pub fn set_capacity(&self, capacity: usize) {
+        let previous = self.capacity.swap(capacity, Ordering::Relaxed);
+        if previous != capacity {
+            tracing::info!(
+                previous = previous,
+                capacity = capacity,
+                "Nonce capacity changed"
+            );
+        }
+    }
 
-        // Spawn background task to clean up expired nonces
-        let nonces = manager.used_nonces.clone();
-        tokio::spawn(async move {
-            loop {
-                tokio::time::sleep(tokio::time::Duration::from_secs(60)).await;
-                Self::cleanup_expired_nonces(&nonces).await;
+    /// Start the cleanup task if it is not running
+    /// Outside a tokio runtime nothing is started; expired entries are then
+    /// only dropped when a table reaches its capacity.
+    fn ensure_cleanup_task(&self) {
+        let mut task = self.cleanup_task.lock().unwrap();
+        if let Some(handle) = task.as_ref() {
+            if !handle.is_finished() {
+                return;
             }
-        });
+            tracing::error!(
+                event = "NONCE_CLEANUP_RESTARTED",
+                "Nonce cleanup task stopped unexpectedly, restarting it"
+            );
+        }
 
-        manager
+        let Ok(runtime) = tokio::runtime::Handle::try_current() else {
+            return;
+        };
+
+        // The task only holds weak references, so it cannot keep the tables
+        // alive after the manager is gone
+        *task = Some(runtime.spawn(Self::cleanup_loop(
+            Arc::downgrade(&self.used_nonces),
+            Arc::downgrade(&self.issued_challenges),
+            Arc::downgrade(&self.high_water_marks),
+            self.cleanup_interval,
+            self.validity_window,
+        )));
+    }
+
+    async fn cleanup_loop(
+        nonces: Weak<RwLock<NonceTable>>,
+        challenges: Weak<RwLock<NonceTable>>,
+        marks: Weak<RwLock<HighWaterMarks>>,
+        interval: Duration,
+        validity_window: i64,
+    ) {
+        loop {
+            tokio::time::sleep(interval).await;
+            let (Some(nonces), Some(challenges), Some(marks)) =
+                (nonces.upgrade(), challenges.upgrade(), marks.upgrade())
+            else {
+                return;
+            };
+            Self::cleanup_expired_nonces(&nonces).await;
+            Self::cleanup_expired_nonces(&challenges).await;
+
+            let cutoff = chrono::Utc::now().timestamp() - validity_window;
+            Self::cleanup_high_water_marks(&mut *marks.write().await, cutoff);
+        }
+    }
+
+    /// Drop marks older than `cutoff`: timestamps inside the window beat them
+    fn cleanup_high_water_marks(marks: &mut HighWaterMarks, cutoff: i64) {
+        marks.retain(|_, last| *last >= cutoff);
+    }
+
+    /// Stop the cleanup task
+    /// It is started again on next use; dropping the manager also stops it.
+    pub fn shutdown(&self) {
+        if let Some(handle) = self.cleanup_task.lock().unwrap().take() {
+            handle.abort();
+        }
     }
 
     /// Verify and consume a nonce
-    /// Returns Ok(()) if nonce is valid and not used
+    /// A server-issued challenge is accepted once before it expires, whatever
+    /// the timestamp; any other nonce must carry a timestamp within the window.
+    /// Deployer nonces are hex digits only, like challenges, so they cannot
+    /// carry text of the signed message's other fields.
+    /// Returns how the nonce was accepted if it is valid and not used
     /// Returns Err if nonce is invalid, expired, or already used
     pub async fn verify_and_consume(
         &self,
         nonce: &str,
         timestamp: i64,
-    ) -> Result<(), String> {
+    ) -> Result<NonceKind, NonceError> {
+        if !nonce.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return Err(NonceError::InvalidFormat);
+        }
+        let current_time = chrono::Utc::now().timestamp();
+        if self.consume_challenge(nonce, current_time).await? {
+            return Ok(NonceKind::Challenge);
+        }
+
+        self.verify_and_consume_at(
+            nonce,
+            timestamp,
+            self.validity_window,
+            self.validity_window,
+            current_time,
+        )
+        .await?;
+        Ok(NonceKind::Client)
+    }
+
+    /// Enforce increasing timestamps per deployer and operation
+    /// Rejects a timestamp more than `timestamp_tolerance` seconds behind the
+    /// highest one accepted before (not strictly newer when the tolerance is
+    /// 0), then raises the mark. Call only after the request's signature has
+    /// been verified, so forged requests cannot move the mark.
+    pub async fn check_timestamp_order(
+        &self,
+        deployer: &str,
+        operation: &str,
+        timestamp: i64,
+    ) -> Result<(), NonceError> {
+        let mut marks = self.high_water_marks.write().await;
+        let last = marks
+            .entry((deployer.to_string(), operation.to_string()))
+            .or_insert(i64::MIN);
+
+        if timestamp.saturating_add(self.timestamp_tolerance) <= *last {
+            return Err(NonceError::OutOfOrder {
+                timestamp,
+                last_accepted: *last,
+                tolerance: self.timestamp_tolerance,
+            });
+        }
+
+        *last = (*last).max(timestamp);
+        Ok(())
+    }
+
+    /// Issue a single-use challenge to `caller` (an API key or source
+    /// address), who may hold at most `MAX_CHALLENGES_PER_CALLER` unused ones
+    /// Returns the hex encoded challenge and its expiry timestamp
+    pub async fn issue_challenge(&self, caller: &str) -> Result<(String, i64), NonceError> {
+        self.ensure_cleanup_task();
+        let current_time = chrono::Utc::now().timestamp();
+        let challenge = hex::encode(rand::random::<[u8; CHALLENGE_LEN]>());
+        let expiry = current_time + CHALLENGE_TTL_SECONDS;
+
+        let mut challenges = self.issued_challenges.write().await;
+        let NonceTable {
+            expiry_by_nonce,
+            by_caller,
+            ..
+        } = &mut *challenges;
+        if let Some(issued) = by_caller.get_mut(caller) {
+            issued.retain(|(until, nonce)| {
+                *until > current_time && expiry_by_nonce.contains_key(nonce)
+            });
+            if issued.len() >= MAX_CHALLENGES_PER_CALLER {
+                return Err(NonceError::TooManyChallenges {
+                    max: MAX_CHALLENGES_PER_CALLER,
+                });
+            }
+        }
+
+        let capacity = self.capacity();
+        if challenges.len() >= capacity {
+            challenges.purge_expired(current_time);
+            if challenges.len() >= capacity {
+                self.rejected_at_capacity.fetch_add(1, Ordering::Relaxed);
+                return Err(NonceError::CapacityExceeded { capacity });
+            }
+        }
+
+        challenges.expiry_by_nonce.insert(challenge.clone(), expiry);
+        challenges.by_expiry.insert((expiry, challenge.clone()));
+        challenges
+            .by_caller
+            .entry(caller.to_string())
+            .or_default()
+            .push((expiry, challenge.clone()));
+
+        Ok((challenge, expiry))
+    }
+
+    /// Consume a challenge issued by `issue_challenge`, for requests that
+    /// accept no client-chosen nonce
+    /// Returns Ok(false) if the nonce was never issued or is already used
+    pub async fn consume_issued_challenge(&self, nonce: &str) -> Result<bool, NonceError> {
+        self.consume_challenge(nonce, chrono::Utc::now().timestamp())
+            .await
+    }
+
+    /// Consume an issued challenge
+    /// Returns Ok(false) if the nonce was never issued (a client-chosen nonce)
+    async fn consume_challenge(&self, nonce: &str, current_time: i64) -> Result<bool, NonceError> {
+        let mut challenges = self.issued_challenges.write().await;
+        let Some(expiry) = challenges.expiry_by_nonce.remove(nonce) else {
+            return Ok(false);
+        };
+        challenges.by_expiry.remove(&(expiry, nonce.to_string()));
+
+        if expiry <= current_time {
+            return Err(NonceError::ChallengeExpired);
+        }
+        Ok(true)
+    }
+
+    /// Verify and consume a nonce with explicit windows
+    /// The timestamp may differ from the current time by at most `max_skew`
+    /// seconds, and the nonce is remembered until `timestamp + retention`.
+    /// `retention` must be at least `max_skew` to rule out replays.
+    pub async fn verify_and_consume_within(
+        &self,
+        nonce: &str,
+        timestamp: i64,
+        max_skew: i64,
+        retention: i64,
+    ) -> Result<(), NonceError> {
         let current_time = chrono::Utc::now().timestamp();
+        self.verify_and_consume_at(nonce, timestamp, max_skew, retention, current_time)
+            .await
+    }
+
+    async fn verify_and_consume_at(
+        &self,
+        nonce: &str,
+        timestamp: i64,
+        max_skew: i64,
+        retention: i64,
+        current_time: i64,
+    ) -> Result<(), NonceError> {
+        self.ensure_cleanup_task();
+        if nonce.is_empty() || nonce.len() > MAX_NONCE_LEN {
+            return Err(NonceError::InvalidLength { max: MAX_NONCE_LEN });
+        }
 
         // 1. Check timestamp validity
         let time_diff = (current_time - timestamp).abs();
-        if time_diff > self.validity_window {
-            return Err(format!(
-                "Timestamp outside validity window. Diff: {}s, Max: {}s",
-                time_diff, self.validity_window
-            ));
+        if time_diff > max_skew {
+            return Err(NonceError::OutsideWindow {
+                diff: time_diff,
+                max: max_skew,
+            });
         }
 
         // 2. Check if nonce already used
         let mut nonces = self.used_nonces.write().await;
-        if nonces.contains_key(nonce) {
-            return Err("Nonce already used (replay attack detected)".to_string());
+        if nonces.expiry_by_nonce.contains_key(nonce) {
+            return Err(NonceError::Replayed);
+        }
+
+        // 3. Make room: only already-expired nonces may be dropped
+        let capacity = self.capacity();
+        if nonces.len() >= capacity {
+            nonces.purge_expired(current_time);
+            if nonces.len() >= capacity {
+                self.rejected_at_capacity.fetch_add(1, Ordering::Relaxed);
+                return Err(NonceError::CapacityExceeded { capacity });
+            }
         }
 
-        // 3. Record nonce with expiry time
-        let expiry = timestamp + self.validity_window;
-        nonces.insert(nonce.to_string(), expiry);
+        // 4. Record nonce with expiry time
+        let expiry = timestamp + retention;
+        nonces.expiry_by_nonce.insert(nonce.to_string(), expiry);
+        nonces.by_expiry.insert((expiry, nonce.to_string()));
 
         Ok(())
     }
 
-    /// Clean up expired nonces
-    async fn cleanup_expired_nonces(nonces: &Arc<RwLock<HashMap<String, i64>>>) {
+    /// Clean up expired nonces (or challenges)
+    async fn cleanup_expired_nonces(nonces: &Arc<RwLock<NonceTable>>) {
         let current_time = chrono::Utc::now().timestamp();
         let mut nonces = nonces.write().await;
 
-        let before_count = nonces.len();
-        nonces.retain(|_, &mut expiry| expiry > current_time);
-        let after_count = nonces.len();
+        let removed = nonces.purge_expired(current_time);
 
-        if before_count != after_count {
+        if removed > 0 {
             tracing::debug!(
-                removed = before_count - after_count,
-                remaining = after_count,
+                removed = removed,
+                remaining = nonces.len(),
                 "Cleaned up expired nonces"
             );
         }
@@ -89,17 +465,45 @@ impl NonceManager {
     /// Get statistics about nonce usage
     pub async fn stats(&self) -> NonceStats {
         let nonces = self.used_nonces.read().await;
+        let challenges = self.issued_challenges.read().await;
         NonceStats {
             active_nonces: nonces.len(),
+            issued_challenges: challenges.len(),
+            capacity: self.capacity(),
+            rejected_at_capacity: self.rejected_at_capacity.load(Ordering::Relaxed),
             validity_window: self.validity_window,
+            cleanup_interval: self.cleanup_interval,
+            high_water_marks: self.high_water_marks.read().await.clone(),
         }
     }
 }
 
+impl Drop for NonceManager {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
+
 #[derive(Debug)]
 pub struct NonceStats {
     pub active_nonces: usize,
+    pub issued_challenges: usize,
+    pub capacity: usize,
+    pub rejected_at_capacity: u64,
     pub validity_window: i64,
+    pub cleanup_interval: Duration,
+    /// Highest accepted timestamp per (deployer, operation)
+    pub high_water_marks: HashMap<(String, String), i64>,
+}
+
+impl NonceStats {
+    /// Fraction of the capacity in use (0.0 - 1.0)
+    pub fn occupancy(&self) -> f64 {
+        if self.capacity == 0 {
+            return 1.0;
+        }
+        self.active_nonces as f64 / self.capacity as f64
+    }
 }
 
 #[cfg(test)]
@@ -109,7 +513,7 @@ mod tests {
     #[tokio::test]
     async fn test_nonce_verify_and_consume() {
         let manager = NonceManager::with_validity_window(60);
-        let nonce = "test-nonce-123";
+        let nonce = "7e57a123";
         let timestamp = chrono::Utc::now().timestamp();
 
         // First use should succeed
@@ -122,7 +526,7 @@ mod tests {
     #[tokio::test]
     async fn test_nonce_expired_timestamp() {
         let manager = NonceManager::with_validity_window(60);
-        let nonce = "test-nonce-456";
+        let nonce = "7e57a456";
         let old_timestamp = chrono::Utc::now().timestamp() - 120; // 2 minutes ago
 
         // Should fail due to expired timestamp
@@ -132,10 +536,345 @@ mod tests {
     #[tokio::test]
     async fn test_nonce_future_timestamp() {
         let manager = NonceManager::with_validity_window(60);
-        let nonce = "test-nonce-789";
+        let nonce = "7e57a789";
         let future_timestamp = chrono::Utc::now().timestamp() + 120; // 2 minutes in future
 
         // Should fail due to future timestamp
         assert!(manager.verify_and_consume(nonce, future_timestamp).await.is_err());
     }
+
+    #[tokio::test]
+    async fn test_nonce_capacity_rejects_without_evicting_live_nonces() {
+        const CAPACITY: usize = 1_000;
+        let manager = NonceManager::with_limits(60, CAPACITY);
+        let now = chrono::Utc::now().timestamp();
+
+        // Twice the capacity of unique nonces: the second half is rejected
+        let mut rejected = 0;
+        for i in 0..2 * CAPACITY {
+            match manager
+                .verify_and_consume_at(&format!("nonce-{}", i), now, 60, 60, now)
+                .await
+            {
+                Ok(()) => {}
+                Err(NonceError::CapacityExceeded { capacity }) => {
+                    assert_eq!(capacity, CAPACITY);
+                    rejected += 1;
+                }
+                Err(e) => panic!("unexpected error: {}", e),
+            }
+        }
+        assert_eq!(rejected, CAPACITY);
+
+        let stats = manager.stats().await;
+        assert_eq!(stats.active_nonces, CAPACITY);
+        assert_eq!(stats.rejected_at_capacity, CAPACITY as u64);
+        assert_eq!(stats.occupancy(), 1.0);
+
+        // Live nonces were kept, so replays are still detected
+        assert!(matches!(
+            manager
+                .verify_and_consume_at("nonce-0", now, 60, 60, now)
+                .await,
+            Err(NonceError::Replayed)
+        ));
+
+        // Once they expire, new nonces displace them
+        let later = now + 61;
+        for i in 0..2 * CAPACITY {
+            manager
+                .verify_and_consume_at(&format!("later-{}", i), later, 60, 60, later)
+                .await
+                .ok();
+        }
+        let stats = manager.stats().await;
+        assert_eq!(stats.active_nonces, CAPACITY);
+        assert_eq!(stats.rejected_at_capacity, 2 * CAPACITY as u64);
+    }
+
+    #[tokio::test]
+    async fn test_nonce_length_is_bounded() {
+        let manager = NonceManager::new();
+        let timestamp = chrono::Utc::now().timestamp();
+
+        let long_nonce = "a".repeat(MAX_NONCE_LEN + 1);
+        assert!(matches!(
+            manager.verify_and_consume(&long_nonce, timestamp).await,
+            Err(NonceError::InvalidLength { .. })
+        ));
+        assert!(manager.verify_and_consume("", timestamp).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_deployer_nonces_are_hex() {
+        let manager = NonceManager::new();
+        let timestamp = chrono::Utc::now().timestamp();
+
+        for nonce in ["stop0123", "nonce-1", "ab cd"] {
+            assert!(matches!(
+                manager.verify_and_consume(nonce, timestamp).await,
+                Err(NonceError::InvalidFormat)
+            ));
+        }
+        let nonce = generate_nonce();
+        assert_eq!(nonce.len(), 32);
+        assert!(manager.verify_and_consume(&nonce, timestamp).await.is_ok());
+        assert!(manager
+            .verify_and_consume("0123ABcd", timestamp)
+            .await
+            .is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_issued_challenge_is_single_use() {
+        let manager = NonceManager::with_validity_window(60);
+        let (challenge, expires_at) = manager.issue_challenge("ip:10.0.0.1").await.unwrap();
+        assert_eq!(challenge.len(), 2 * CHALLENGE_LEN);
+        assert!(expires_at > chrono::Utc::now().timestamp());
+        assert_eq!(manager.stats().await.issued_challenges, 1);
+
+        // No timestamp is needed with a challenge, but it is accepted only once
+        assert!(matches!(
+            manager.verify_and_consume(&challenge, 0).await,
+            Ok(NonceKind::Challenge)
+        ));
+        assert_eq!(manager.stats().await.issued_challenges, 0);
+        assert!(manager.verify_and_consume(&challenge, 0).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_expired_challenges_are_rejected_and_cleaned() {
+        let manager = NonceManager::with_validity_window(60);
+        let (challenge, expires_at) = manager.issue_challenge("ip:10.0.0.1").await.unwrap();
+        let (unused, _) = manager.issue_challenge("ip:10.0.0.1").await.unwrap();
+
+        assert!(matches!(
+            manager.consume_challenge(&challenge, expires_at).await,
+            Err(NonceError::ChallengeExpired)
+        ));
+
+        // Issued-but-never-used challenges are dropped once expired
+        let removed = manager
+            .issued_challenges
+            .write()
+            .await
+            .purge_expired(expires_at);
+        assert_eq!(removed, 1);
+        assert_eq!(manager.stats().await.issued_challenges, 0);
+        assert!(!manager
+            .consume_challenge(&unused, expires_at)
+            .await
+            .unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_validity_window_from_config() {
+        let config = SecurityConfig {
+            nonce_validity_seconds: 10,
+            nonce_cleanup_interval_seconds: 5,
+            ..Default::default()
+        };
+        let strict = NonceManager::from_config(&config, DEFAULT_MAX_NONCES);
+        let default = NonceManager::new();
+
+        let stats = strict.stats().await;
+        assert_eq!(stats.validity_window, 10);
+        assert_eq!(stats.cleanup_interval, Duration::from_secs(5));
+
+        // 30 seconds old: outside the custom window, inside the default one
+        let timestamp = chrono::Utc::now().timestamp() - 30;
+        assert!(matches!(
+            strict.verify_and_consume("de1a7ed", timestamp).await,
+            Err(NonceError::OutsideWindow { max: 10, .. })
+        ));
+        assert!(default
+            .verify_and_consume("de1a7ed", timestamp)
+            .await
+            .is_ok());
+    }
+
+    #[test]
+    fn test_construction_outside_runtime() {
+        let manager = NonceManager::new();
+        assert!(manager.cleanup_task.lock().unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_cleanup_task_sweeps_and_stops_on_drop() {
+        let manager = NonceManager::with_settings(1, Duration::from_millis(10), DEFAULT_MAX_NONCES);
+        // Started lazily on first use
+        assert!(manager.cleanup_task.lock().unwrap().is_none());
+
+        // Expires right away: remembered until timestamp + 1
+        let timestamp = chrono::Utc::now().timestamp() - 1;
+        manager.verify_and_consume("01d", timestamp).await.unwrap();
+        let task = manager
+            .cleanup_task
+            .lock()
+            .unwrap()
+            .as_ref()
+            .unwrap()
+            .abort_handle();
+
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(manager.stats().await.active_nonces, 0);
+        assert!(!task.is_finished());
+
+        let table = Arc::downgrade(&manager.used_nonces);
+        drop(manager);
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(task.is_finished());
+        assert!(table.upgrade().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_challenges_per_caller() {
+        let manager = NonceManager::with_validity_window(60);
+        let mut issued = Vec::new();
+        for _ in 0..MAX_CHALLENGES_PER_CALLER {
+            issued.push(manager.issue_challenge("ip:10.0.0.1").await.unwrap().0);
+        }
+        assert!(matches!(
+            manager.issue_challenge("ip:10.0.0.1").await,
+            Err(NonceError::TooManyChallenges { .. })
+        ));
+        // Other callers are unaffected
+        assert!(manager.issue_challenge("ip:10.0.0.2").await.is_ok());
+
+        // Using a challenge frees its slot
+        manager.verify_and_consume(&issued[0], 0).await.unwrap();
+        assert!(manager.issue_challenge("ip:10.0.0.1").await.is_ok());
+
+        // Expired challenges do not count
+        let expired = chrono::Utc::now().timestamp() + CHALLENGE_TTL_SECONDS;
+        manager
+            .issued_challenges
+            .write()
+            .await
+            .purge_expired(expired);
+        assert!(manager.issued_challenges.read().await.by_caller.is_empty());
+        assert!(manager.issue_challenge("ip:10.0.0.1").await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_set_capacity() {
+        let manager = NonceManager::with_limits(60, 1);
+        let now = chrono::Utc::now().timestamp();
+        manager.verify_and_consume("aa", now).await.unwrap();
+        assert!(matches!(
+            manager.verify_and_consume("bb", now).await,
+            Err(NonceError::CapacityExceeded { capacity: 1 })
+        ));
+
+        manager.set_capacity(2);
+        assert_eq!(manager.stats().await.capacity, 2);
+        manager.verify_and_consume("bb", now).await.unwrap();
+        // Shrinking keeps what is remembered, so replays stay rejected
+        manager.set_capacity(1);
+        assert!(matches!(
+            manager.verify_and_consume("aa", now).await,
+            Err(NonceError::Replayed)
+        ));
+        assert!(matches!(
+            manager.verify_and_consume("cc", now).await,
+            Err(NonceError::CapacityExceeded { capacity: 1 })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_cleanup_task_restarts() {
+        let manager =
+            NonceManager::with_settings(60, Duration::from_millis(10), DEFAULT_MAX_NONCES);
+        manager.issue_challenge("ip:10.0.0.1").await.unwrap();
+
+        manager.shutdown();
+        assert!(manager.cleanup_task.lock().unwrap().is_none());
+
+        // Simulate a task that died on its own
+        let dead = tokio::spawn(async {});
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        *manager.cleanup_task.lock().unwrap() = Some(dead);
+
+        manager.issue_challenge("ip:10.0.0.1").await.unwrap();
+        let task = manager.cleanup_task.lock().unwrap();
+        assert!(!task.as_ref().unwrap().is_finished());
+    }
+
+    #[tokio::test]
+    async fn test_timestamp_order_per_deployer() {
+        let manager = NonceManager::new();
+        let now = chrono::Utc::now().timestamp();
+
+        manager
+            .check_timestamp_order("deployer-a", "stop_app", now)
+            .await
+            .unwrap();
+        // Concurrent or slightly out-of-order requests within the tolerance pass
+        manager
+            .check_timestamp_order("deployer-a", "stop_app", now)
+            .await
+            .unwrap();
+        manager
+            .check_timestamp_order("deployer-a", "stop_app", now - 4)
+            .await
+            .unwrap();
+        // An older captured request does not, and does not lower the mark
+        assert!(matches!(
+            manager
+                .check_timestamp_order("deployer-a", "stop_app", now - 5)
+                .await,
+            Err(NonceError::OutOfOrder {
+                last_accepted,
+                tolerance: DEFAULT_TIMESTAMP_TOLERANCE,
+                ..
+            }) if last_accepted == now
+        ));
+
+        // Marks are independent per deployer and per operation
+        manager
+            .check_timestamp_order("deployer-a", "get_secret_key", now - 60)
+            .await
+            .unwrap();
+        manager
+            .check_timestamp_order("deployer-b", "stop_app", now - 60)
+            .await
+            .unwrap();
+
+        let marks = manager.stats().await.high_water_marks;
+        assert_eq!(
+            marks.get(&("deployer-a".to_string(), "stop_app".to_string())),
+            Some(&now)
+        );
+        assert_eq!(marks.len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_strict_order_and_mark_expiry() {
+        let config = SecurityConfig {
+            timestamp_tolerance_seconds: 0,
+            ..Default::default()
+        };
+        let manager = NonceManager::from_config(&config, DEFAULT_MAX_NONCES);
+        let now = chrono::Utc::now().timestamp();
+
+        manager
+            .check_timestamp_order("d", "stop_app", now)
+            .await
+            .unwrap();
+        // Without tolerance the timestamp must be strictly newer
+        assert!(manager
+            .check_timestamp_order("d", "stop_app", now)
+            .await
+            .is_err());
+        manager
+            .check_timestamp_order("d", "stop_app", now + 1)
+            .await
+            .unwrap();
+
+        // Marks older than the window are dropped; requests that pass the
+        // window check are newer than any dropped mark
+        let mut marks = manager.high_water_marks.write().await;
+        NonceManager::cleanup_high_water_marks(&mut marks, now + 2);
+        assert!(marks.is_empty());
+    }
 }
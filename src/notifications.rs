@@ -0,0 +1,405 @@
+use crate::config::NotificationConfig;
+use ring::hmac;
+use serde::Serialize;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tracing::{debug, warn};
+
+/// Events waiting for the dispatcher; further events are dropped
+const QUEUE_CAPACITY: usize = 256;
+
+/// Header carrying the hex HMAC-SHA256 of the body, as `sha256=<hex>`
+pub const SIGNATURE_HEADER: &str = "x-tapp-signature";
+
+/// App and service events sent to the webhooks
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum NotificationEvent {
+    /// A deployment task failed
+    TaskFailed {
+        task_id: String,
+        app_id: String,
+        error_code: String,
+        message: String,
+    },
+    /// The attestation agent failed enough consecutive health checks to
+    /// make the service NOT_SERVING
+    AttestationFailed {
+        consecutive_failures: u32,
+        error: String,
+    },
+    /// A monitored filesystem reached the disk warning threshold
+    DiskThreshold {
+        name: String,
+        path: String,
+        used_percent: f64,
+        threshold_percent: f64,
+    },
+}
+
+impl NotificationEvent {
+    /// Event type, as used in `notifications.events`
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::TaskFailed { .. } => "task_failed",
+            Self::AttestationFailed { .. } => "attestation_failed",
+            Self::DiskThreshold { .. } => "disk_threshold",
+        }
+    }
+}
+
+/// JSON body of a webhook call
+#[derive(Serialize)]
+struct Payload<'a> {
+    #[serde(flatten)]
+    event: &'a NotificationEvent,
+    service: &'static str,
+    version: &'static str,
+    timestamp: i64,
+}
+
+/// Delivery counters
+#[derive(Debug, Default)]
+struct Counters {
+    delivered: AtomicU64,
+    failed: AtomicU64,
+    dropped: AtomicU64,
+}
+
+/// Snapshot of the delivery counters
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct NotificationStats {
+    pub enabled: bool,
+    /// Webhook calls answered with a success status
+    pub delivered: u64,
+    /// Webhook calls that still failed after all retries
+    pub failed: u64,
+    /// Events dropped because the queue was full
+    pub dropped: u64,
+}
+
+/// Handle for reporting events to the webhook dispatcher
+/// Reporting never waits: events are queued for a background task, which
+/// POSTs them with retries. A disabled notifier ignores events.
+#[derive(Clone, Default)]
+pub struct Notifier {
+    tx: Option<mpsc::Sender<NotificationEvent>>,
+    counters: Arc<Counters>,
+}
+
+impl Notifier {
+    /// Notifier ignoring every event
+    pub fn disabled() -> Self {
+        Self::default()
+    }
+
+    /// Start the dispatcher for the configured webhooks (disabled if not
+    /// configured); must be called inside a Tokio runtime
+    pub fn start(config: Option<&NotificationConfig>) -> Self {
+        let Some(config) = config.filter(|c| c.enabled && !c.webhooks.is_empty()) else {
+            return Self::disabled();
+        };
+
+        let client = match reqwest::Client::builder()
+            .timeout(Duration::from_secs(config.timeout_seconds.max(1)))
+            .build()
+        {
+            Ok(client) => client,
+            Err(e) => {
+                warn!(error = %e, "Cannot create webhook client, notifications disabled");
+                return Self::disabled();
+            }
+        };
+
+        let (tx, rx) = mpsc::channel(QUEUE_CAPACITY);
+        let counters = Arc::new(Counters::default());
+        tokio::spawn(dispatch(config.clone(), client, rx, counters.clone()));
+        Self {
+            tx: Some(tx),
+            counters,
+        }
+    }
+
+    /// Queue an event for delivery
+    pub fn notify(&self, event: NotificationEvent) {
+        let Some(tx) = &self.tx else {
+            return;
+        };
+        if let Err(e) = tx.try_send(event) {
+            self.counters.dropped.fetch_add(1, Ordering::Relaxed);
+            warn!(
+                event = "NOTIFICATION_DROPPED",
+                reason = %e,
+                "Notification queue full, event dropped"
+            );
+        }
+    }
+
+    pub fn stats(&self) -> NotificationStats {
+        NotificationStats {
+            enabled: self.tx.is_some(),
+            delivered: self.counters.delivered.load(Ordering::Relaxed),
+            failed: self.counters.failed.load(Ordering::Relaxed),
+            dropped: self.counters.dropped.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// `sha256=<hex HMAC-SHA256 of body>`
+pub fn sign_body(secret: &str, body: &[u8]) -> String {
+    let key = hmac::Key::new(hmac::HMAC_SHA256, secret.as_bytes());
+    format!("sha256={}", hex::encode(hmac::sign(&key, body).as_ref()))
+}
+
+/// Send queued events to every webhook until the notifiers are dropped
+/// Each delivery runs on its own task, so a slow or failing webhook delays
+/// neither the other webhooks nor later events.
+async fn dispatch(
+    config: NotificationConfig,
+    client: reqwest::Client,
+    mut rx: mpsc::Receiver<NotificationEvent>,
+    counters: Arc<Counters>,
+) {
+    let config = Arc::new(config);
+    while let Some(event) = rx.recv().await {
+        if !config.wants(event.kind()) {
+            continue;
+        }
+
+        let payload = Payload {
+            event: &event,
+            service: crate::NAME,
+            version: crate::VERSION,
+            timestamp: crate::utils::current_timestamp(),
+        };
+        let body = match serde_json::to_vec(&payload) {
+            Ok(body) => body,
+            Err(e) => {
+                warn!(error = %e, "Cannot serialize notification");
+                continue;
+            }
+        };
+        let signature = config.secret.as_deref().map(|s| sign_body(s, &body));
+
+        for url in &config.webhooks {
+            tokio::spawn(deliver(
+                client.clone(),
+                config.clone(),
+                url.clone(),
+                event.kind(),
+                body.clone(),
+                signature.clone(),
+                counters.clone(),
+            ));
+        }
+    }
+}
+
+/// POST one event to one webhook, retrying with exponential backoff
+async fn deliver(
+    client: reqwest::Client,
+    config: Arc<NotificationConfig>,
+    url: String,
+    kind: &'static str,
+    body: Vec<u8>,
+    signature: Option<String>,
+    counters: Arc<Counters>,
+) {
+    // Webhook URLs often embed a token; only the host is logged
+    let host = reqwest::Url::parse(&url)
+        .ok()
+        .and_then(|u| u.host_str().map(str::to_string))
+        .unwrap_or_default();
+
+    let mut delay = Duration::from_millis(config.retry_delay_ms);
+    let mut attempt = 0;
+    loop {
+        let mut request = client
+            .post(&url)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .body(body.clone());
+        if let Some(signature) = &signature {
+            request = request.header(SIGNATURE_HEADER, signature);
+        }
+
+        let error = match request.send().await {
+            Ok(response) if response.status().is_success() => {
+                counters.delivered.fetch_add(1, Ordering::Relaxed);
+                debug!(webhook = %host, kind, "Notification delivered");
+                return;
+            }
+            Ok(response) => format!("HTTP {}", response.status()),
+            Err(e) => e.to_string(),
+        };
+
+        if attempt >= config.max_retries {
+            counters.failed.fetch_add(1, Ordering::Relaxed);
+            warn!(
+                webhook = %host,
+                kind,
+                attempts = attempt + 1,
+                error = %error,
+                event = "NOTIFICATION_FAILED",
+                "Webhook delivery failed"
+            );
+            return;
+        }
+        attempt += 1;
+        debug!(webhook = %host, kind, attempt, error = %error, "Retrying webhook delivery");
+        tokio::time::sleep(delay).await;
+        delay = delay.saturating_mul(2);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+    use tokio::net::TcpListener;
+
+    /// Request received by the test webhook
+    #[derive(Debug)]
+    struct Received {
+        signature: Option<String>,
+        body: Vec<u8>,
+    }
+
+    /// Local HTTP server answering the first `failures` requests with 500,
+    /// then 200, and passing every request on
+    async fn webhook_server(failures: usize) -> (String, mpsc::UnboundedReceiver<Received>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        let (tx, rx) = mpsc::unbounded_channel();
+
+        tokio::spawn(async move {
+            let mut served = 0;
+            loop {
+                let (stream, _) = listener.accept().await.unwrap();
+                let mut stream = BufReader::new(stream);
+
+                let mut length = 0;
+                let mut signature = None;
+                loop {
+                    let mut line = String::new();
+                    stream.read_line(&mut line).await.unwrap();
+                    let line = line.trim_end();
+                    if line.is_empty() {
+                        break;
+                    }
+                    if let Some((name, value)) = line.split_once(':') {
+                        match name.to_ascii_lowercase().as_str() {
+                            "content-length" => length = value.trim().parse().unwrap(),
+                            SIGNATURE_HEADER => signature = Some(value.trim().to_string()),
+                            _ => {}
+                        }
+                    }
+                }
+                let mut body = vec![0; length];
+                stream.read_exact(&mut body).await.unwrap();
+                let _ = tx.send(Received { signature, body });
+
+                let status = if served < failures {
+                    "500 Internal Server Error"
+                } else {
+                    "200 OK"
+                };
+                served += 1;
+                let response = format!(
+                    "HTTP/1.1 {}\r\ncontent-length: 0\r\nconnection: close\r\n\r\n",
+                    status
+                );
+                stream
+                    .get_mut()
+                    .write_all(response.as_bytes())
+                    .await
+                    .unwrap();
+            }
+        });
+
+        (url, rx)
+    }
+
+    fn config(url: String) -> NotificationConfig {
+        NotificationConfig {
+            enabled: true,
+            webhooks: vec![url],
+            events: vec!["task_failed".to_string()],
+            secret: Some("webhook-secret".to_string()),
+            max_retries: 2,
+            retry_delay_ms: 10,
+            timeout_seconds: 5,
+        }
+    }
+
+    fn task_failed() -> NotificationEvent {
+        NotificationEvent::TaskFailed {
+            task_id: "task-1".to_string(),
+            app_id: "app-1".to_string(),
+            error_code: "IMAGE_PULL_FAILED".to_string(),
+            message: "pull access denied".to_string(),
+        }
+    }
+
+    async fn wait_for(notifier: &Notifier, done: impl Fn(NotificationStats) -> bool) {
+        for _ in 0..500 {
+            if done(notifier.stats()) {
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        panic!("timed out, stats: {:?}", notifier.stats());
+    }
+
+    #[tokio::test]
+    async fn test_signed_delivery_with_retries() {
+        let (url, mut received) = webhook_server(1).await;
+        let notifier = Notifier::start(Some(&config(url)));
+
+        // Filtered out by `events`
+        notifier.notify(NotificationEvent::AttestationFailed {
+            consecutive_failures: 3,
+            error: "timeout".to_string(),
+        });
+        notifier.notify(task_failed());
+        wait_for(&notifier, |stats| stats.delivered == 1).await;
+
+        // The first attempt got a 500, the retry went through
+        let mut requests = Vec::new();
+        while let Ok(request) = received.try_recv() {
+            requests.push(request);
+        }
+        assert_eq!(requests.len(), 2);
+        for request in &requests {
+            assert_eq!(
+                request.signature.as_deref(),
+                Some(sign_body("webhook-secret", &request.body).as_str())
+            );
+            let body: serde_json::Value = serde_json::from_slice(&request.body).unwrap();
+            assert_eq!(body["event"], "task_failed");
+            assert_eq!(body["app_id"], "app-1");
+            assert_eq!(body["error_code"], "IMAGE_PULL_FAILED");
+            assert_eq!(body["service"], crate::NAME);
+        }
+
+        let stats = notifier.stats();
+        assert!(stats.enabled);
+        assert_eq!((stats.failed, stats.dropped), (0, 0));
+    }
+
+    #[tokio::test]
+    async fn test_failures_are_counted() {
+        let (url, _received) = webhook_server(usize::MAX).await;
+        let notifier = Notifier::start(Some(&config(url)));
+
+        notifier.notify(task_failed());
+        wait_for(&notifier, |stats| stats.failed == 1).await;
+        assert_eq!(notifier.stats().delivered, 0);
+
+        // Disabled notifiers ignore events
+        let disabled = Notifier::disabled();
+        disabled.notify(task_failed());
+        assert_eq!(disabled.stats(), NotificationStats::default());
+    }
+}
@@ -0,0 +1,257 @@
+use crate::error::TappError;
+use crate::request_id_layer::RequestId;
+use futures_util::FutureExt;
+use std::any::Any;
+use std::backtrace::Backtrace;
+use std::cell::RefCell;
+use std::future::Future;
+use std::panic::AssertUnwindSafe;
+use std::sync::Once;
+use std::task::{Context, Poll};
+use tonic::body::BoxBody;
+use tonic::Status;
+use tower::{Layer, Service};
+use tracing::error;
+
+thread_local! {
+    /// Backtrace of the last panic on this thread, taken by whoever catches it
+    static LAST_BACKTRACE: RefCell<Option<String>> = const { RefCell::new(None) };
+}
+
+static INSTALL_HOOK: Once = Once::new();
+
+/// Record the backtrace of every panic, so caught panics are logged with it
+/// The previous hook still runs. Only the first call installs the hook.
+pub fn install_panic_hook() {
+    INSTALL_HOOK.call_once(|| {
+        let previous = std::panic::take_hook();
+        std::panic::set_hook(Box::new(move |info| {
+            let backtrace = Backtrace::force_capture().to_string();
+            LAST_BACKTRACE.with(|last| *last.borrow_mut() = Some(backtrace));
+            previous(info);
+        }));
+    });
+}
+
+/// A panic caught while polling a future
+#[derive(Debug)]
+pub struct CaughtPanic {
+    pub message: String,
+    /// None unless `install_panic_hook` was called
+    pub backtrace: Option<String>,
+}
+
+impl CaughtPanic {
+    /// Describe a panic payload caught on this thread
+    fn new(payload: Box<dyn Any + Send>) -> Self {
+        let message = payload
+            .downcast_ref::<&str>()
+            .map(|message| message.to_string())
+            .or_else(|| payload.downcast_ref::<String>().cloned())
+            .unwrap_or_else(|| "panic with a non-string payload".to_string());
+        Self {
+            message,
+            backtrace: LAST_BACKTRACE.with(|last| last.borrow_mut().take()),
+        }
+    }
+
+    /// Backtrace for logging
+    pub fn backtrace(&self) -> &str {
+        self.backtrace
+            .as_deref()
+            .unwrap_or("unavailable (panic hook not installed)")
+    }
+}
+
+/// Run `future`, returning a panic raised while it is polled as an error
+/// State the future shared with others (tokio locks, channels) stays usable:
+/// tokio locks are not poisoned by a panic.
+pub async fn catch_panic<F: Future>(future: F) -> Result<F::Output, CaughtPanic> {
+    AssertUnwindSafe(future)
+        .catch_unwind()
+        .await
+        .map_err(CaughtPanic::new)
+}
+
+/// Tower Layer answering INTERNAL when a handler panics
+/// The panic is logged with the method, request ID and backtrace
+/// (`HANDLER_PANICKED`) instead of resetting the stream. Place it innermost
+/// so the audit log and traces record the INTERNAL status. Panics while a
+/// streaming response is being sent are not caught.
+#[derive(Clone, Default)]
+pub struct CatchPanicLayer;
+
+impl CatchPanicLayer {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl<S> Layer<S> for CatchPanicLayer {
+    type Service = CatchPanicMiddleware<S>;
+
+    fn layer(&self, service: S) -> Self::Service {
+        CatchPanicMiddleware { inner: service }
+    }
+}
+
+/// Middleware catching panics of the handler future
+#[derive(Clone)]
+pub struct CatchPanicMiddleware<S> {
+    inner: S,
+}
+
+impl<S> Service<http::Request<BoxBody>> for CatchPanicMiddleware<S>
+where
+    S: Service<http::Request<BoxBody>, Response = http::Response<BoxBody>>
+        + Clone
+        + Send
+        + 'static,
+    S::Error: Into<Box<dyn std::error::Error + Send + Sync>> + Send,
+    S::Future: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = futures_util::future::BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: http::Request<BoxBody>) -> Self::Future {
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+
+        let method_name = crate::utils::grpc_method_name(req.uri().path()).to_string();
+        let request_id = req.extensions().get::<RequestId>().map(|id| id.0.clone());
+
+        Box::pin(async move {
+            // Calling the handler inside the future also catches panics
+            // raised before it returns its future
+            match catch_panic(async move { inner.call(req).await }).await {
+                Ok(result) => result,
+                Err(panic) => {
+                    error!(
+                        event = "HANDLER_PANICKED",
+                        method = %method_name,
+                        request_id = %request_id.as_deref().unwrap_or(""),
+                        panic = %panic.message,
+                        backtrace = %panic.backtrace(),
+                        "Request handler panicked"
+                    );
+                    let error = TappError::Internal(format!("{} failed unexpectedly", method_name));
+                    Ok(Status::from(error).into_http())
+                }
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::request_id_layer::{RequestIdLayer, REQUEST_ID_HEADER};
+    use std::io::Write;
+    use std::sync::{Arc, Mutex};
+    use tracing_subscriber::layer::SubscriberExt;
+
+    /// In-memory sink so tests can read back what was logged
+    #[derive(Clone, Default)]
+    struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn request(method: &str) -> http::Request<BoxBody> {
+        http::Request::builder()
+            .uri(format!("/tapp_service.TappService/{}", method))
+            .header(REQUEST_ID_HEADER, format!("req-{}", method))
+            .body(tonic::body::empty_body())
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_handler_panic_becomes_internal() {
+        install_panic_hook();
+        let buffer = SharedBuffer::default();
+        let writer = buffer.clone();
+        let subscriber = tracing_subscriber::registry().with(
+            tracing_subscriber::fmt::layer()
+                .with_ansi(false)
+                .with_writer(move || writer.clone()),
+        );
+        let _default = tracing::subscriber::set_default(subscriber);
+
+        // GetServiceStatus panics while running, GetAppInfo before returning
+        // its future, the other methods succeed
+        let handler = tower::service_fn(|req: http::Request<BoxBody>| {
+            let method = crate::utils::grpc_method_name(req.uri().path()).to_string();
+            if method == "GetAppInfo" {
+                panic!("not yet implemented: GetAppInfo");
+            }
+            async move {
+                if method == "GetServiceStatus" {
+                    None::<()>.expect("service table is empty");
+                }
+                Ok::<_, std::convert::Infallible>(http::Response::new(tonic::body::empty_body()))
+            }
+        });
+        let mut service = tower::ServiceBuilder::new()
+            .layer(RequestIdLayer::new())
+            .layer(CatchPanicLayer::new())
+            .service(handler);
+
+        for method in ["GetServiceStatus", "GetAppInfo"] {
+            let response = service.call(request(method)).await.unwrap();
+            let status = Status::from_header_map(response.headers()).unwrap();
+            assert_eq!(status.code(), tonic::Code::Internal);
+            assert_eq!(
+                status.message(),
+                format!("Internal error: {} failed unexpectedly", method)
+            );
+            // The client sees the request ID, not the panic
+            assert_eq!(
+                response.headers()[REQUEST_ID_HEADER],
+                format!("req-{}", method).as_str()
+            );
+        }
+
+        let logged = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+        let panics: Vec<&str> = logged
+            .lines()
+            .filter(|line| line.contains("HANDLER_PANICKED"))
+            .collect();
+        assert_eq!(panics.len(), 2);
+        assert!(panics[0].contains("method=GetServiceStatus"));
+        assert!(panics[0].contains("request_id=req-GetServiceStatus"));
+        assert!(panics[0].contains("service table is empty"));
+        assert!(panics[1].contains("not yet implemented: GetAppInfo"));
+        assert!(!panics[1].contains("panic hook not installed"));
+
+        // Other requests are unaffected
+        let response = service.call(request("GetEvidence")).await.unwrap();
+        assert!(Status::from_header_map(response.headers()).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_catch_panic() {
+        assert_eq!(catch_panic(async { 7 }).await.unwrap(), 7);
+
+        let panic = catch_panic(async { panic!("deploy of {} failed", "app-1") })
+            .await
+            .unwrap_err();
+        assert_eq!(panic.message, "deploy of app-1 failed");
+        let panic = catch_panic(async { std::panic::panic_any(7u32) })
+            .await
+            .unwrap_err();
+        assert_eq!(panic.message, "panic with a non-string payload");
+    }
+}
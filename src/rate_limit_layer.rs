@@ -0,0 +1,319 @@
+use crate::auth_layer::AuthIdentity;
+use crate::config::RateLimitConfig;
+use crate::error::{throttled_status, ErrorReason};
+use lru::LruCache;
+use std::collections::HashMap;
+use std::num::NonZeroUsize;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+use tonic::body::BoxBody;
+use tower::{Layer, Service};
+use tracing::{debug, warn};
+
+/// Token bucket state for a single (client, method) pair
+struct TokenBucket {
+    tokens: f64,
+    last_refill: Instant,
+}
+
+/// Snapshot of rate limiter counters (for the metrics endpoint)
+#[derive(Debug, Clone, Default)]
+pub struct RateLimitStats {
+    pub throttled_total: u64,
+    pub throttled_by_method: HashMap<String, u64>,
+    pub tracked_clients: usize,
+}
+
+/// Shared rate limiter state
+/// Buckets are keyed by (client, method) and bounded by an LRU cache
+pub struct RateLimiter {
+    config: RwLock<Option<RateLimitConfig>>,
+    buckets: Mutex<LruCache<(String, String), TokenBucket>>,
+    throttled_total: AtomicU64,
+    throttled_by_method: Mutex<HashMap<String, u64>>,
+}
+
+impl RateLimiter {
+    pub fn new(config: Option<RateLimitConfig>) -> Self {
+        let capacity = Self::capacity(&config);
+        Self {
+            config: RwLock::new(config),
+            buckets: Mutex::new(LruCache::new(capacity)),
+            throttled_total: AtomicU64::new(0),
+            throttled_by_method: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn capacity(config: &Option<RateLimitConfig>) -> NonZeroUsize {
+        let max = config
+            .as_ref()
+            .map(|c| c.max_tracked_clients)
+            .unwrap_or_else(|| RateLimitConfig::default().max_tracked_clients);
+        NonZeroUsize::new(max).unwrap_or(NonZeroUsize::MIN)
+    }
+
+    /// Replace the active configuration (used by hot reload)
+    /// Existing buckets are kept so a reload does not reset clients' budgets
+    pub fn update_config(&self, config: Option<RateLimitConfig>) {
+        let capacity = Self::capacity(&config);
+        self.buckets.lock().unwrap().resize(capacity);
+        *self.config.write().unwrap() = config;
+    }
+
+    /// Check whether a request from `client` to `method` is allowed
+    /// Returns the time to wait before retrying when the request is throttled
+    pub fn check(&self, client: &str, method: &str) -> Result<(), Duration> {
+        let per_minute = {
+            let config = self.config.read().unwrap();
+            let Some(config) = config.as_ref().filter(|c| c.enabled) else {
+                return Ok(());
+            };
+            config
+                .method_limits
+                .get(method)
+                .copied()
+                .unwrap_or(config.default_requests_per_minute)
+        };
+
+        // A limit of 0 means unlimited
+        if per_minute == 0 {
+            return Ok(());
+        }
+
+        let capacity = per_minute as f64;
+        let refill_per_sec = capacity / 60.0;
+        let now = Instant::now();
+
+        let mut buckets = self.buckets.lock().unwrap();
+        let bucket = buckets.get_or_insert_mut((client.to_string(), method.to_string()), || {
+            TokenBucket {
+                tokens: capacity,
+                last_refill: now,
+            }
+        });
+
+        let elapsed = now.duration_since(bucket.last_refill).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * refill_per_sec).min(capacity);
+        bucket.last_refill = now;
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            return Ok(());
+        }
+
+        let wait = Duration::from_secs_f64((1.0 - bucket.tokens) / refill_per_sec);
+        drop(buckets);
+
+        self.throttled_total.fetch_add(1, Ordering::Relaxed);
+        *self
+            .throttled_by_method
+            .lock()
+            .unwrap()
+            .entry(method.to_string())
+            .or_insert(0) += 1;
+
+        Err(wait)
+    }
+
+    /// Get counters about throttled requests
+    pub fn stats(&self) -> RateLimitStats {
+        RateLimitStats {
+            throttled_total: self.throttled_total.load(Ordering::Relaxed),
+            throttled_by_method: self.throttled_by_method.lock().unwrap().clone(),
+            tracked_clients: self.buckets.lock().unwrap().len(),
+        }
+    }
+}
+
+/// Tower Layer for per-client request rate limiting
+#[derive(Clone)]
+pub struct RateLimitLayer {
+    limiter: Arc<RateLimiter>,
+}
+
+impl RateLimitLayer {
+    pub fn new(limiter: Arc<RateLimiter>) -> Self {
+        Self { limiter }
+    }
+}
+
+impl<S> Layer<S> for RateLimitLayer {
+    type Service = RateLimitMiddleware<S>;
+
+    fn layer(&self, service: S) -> Self::Service {
+        RateLimitMiddleware {
+            inner: service,
+            limiter: self.limiter.clone(),
+        }
+    }
+}
+
+/// Middleware that throttles requests exceeding the configured rate
+#[derive(Clone)]
+pub struct RateLimitMiddleware<S> {
+    inner: S,
+    limiter: Arc<RateLimiter>,
+}
+
+impl<S> Service<http::Request<BoxBody>> for RateLimitMiddleware<S>
+where
+    S: Service<http::Request<BoxBody>, Response = http::Response<BoxBody>>
+        + Clone
+        + Send
+        + 'static,
+    S::Error: Into<Box<dyn std::error::Error + Send + Sync>> + Send,
+    S::Future: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = futures_util::future::BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: http::Request<BoxBody>) -> Self::Future {
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+        let limiter = self.limiter.clone();
+
+        Box::pin(async move {
+            let method_name = crate::utils::grpc_method_name(req.uri().path()).to_string();
+            let client = client_key(&req);
+
+            if let Err(wait) = limiter.check(&client, &method_name) {
+                let retry_after = wait.as_secs().max(1);
+                warn!(
+                    method = %method_name,
+                    client = %client,
+                    retry_after_seconds = retry_after,
+                    event = "RATE_LIMITED",
+                    "Request throttled"
+                );
+
+                let status = throttled_status(
+                    format!(
+                        "Rate limit exceeded for {}. Retry after {}s",
+                        method_name, retry_after
+                    ),
+                    ErrorReason::RateLimited,
+                    HashMap::from([("method".to_string(), method_name)]),
+                    Duration::from_secs(retry_after),
+                );
+                return Ok(status.into_http());
+            }
+
+            debug!(method = %method_name, client = %client, "Rate limit check passed");
+            inner.call(req).await
+        })
+    }
+}
+
+/// Identify the client: by the API key name the auth layer validated, otherwise
+/// by source IP. Unvalidated credentials never pick the bucket, so callers
+/// cannot dodge their limit by inventing keys
+fn client_key<B>(req: &http::Request<B>) -> String {
+    if let Some(identity) = req.extensions().get::<AuthIdentity>() {
+        return format!("key:{}", identity.key_name);
+    }
+
+    match crate::utils::remote_ip(req) {
+        Some(ip) => format!("ip:{}", ip),
+        None => "local".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(default: u32, overrides: &[(&str, u32)], max_clients: usize) -> RateLimitConfig {
+        RateLimitConfig {
+            enabled: true,
+            default_requests_per_minute: default,
+            method_limits: overrides
+                .iter()
+                .map(|(m, l)| (m.to_string(), *l))
+                .collect(),
+            max_tracked_clients: max_clients,
+        }
+    }
+
+    #[test]
+    fn test_bucket_exhaustion_and_retry_after() {
+        let limiter = RateLimiter::new(Some(config(3, &[], 100)));
+
+        for _ in 0..3 {
+            assert!(limiter.check("ip:10.0.0.1", "GetAppKey").is_ok());
+        }
+        let wait = limiter.check("ip:10.0.0.1", "GetAppKey").unwrap_err();
+        // 3 per minute refills one token every 20 seconds
+        assert!(wait <= Duration::from_secs(20));
+        assert!(wait > Duration::from_secs(19));
+
+        // Other clients are unaffected
+        assert!(limiter.check("ip:10.0.0.2", "GetAppKey").is_ok());
+        assert_eq!(limiter.stats().throttled_total, 1);
+    }
+
+    #[test]
+    fn test_method_override() {
+        let limiter = RateLimiter::new(Some(config(120, &[("GetEvidence", 1)], 100)));
+
+        assert!(limiter.check("c", "GetEvidence").is_ok());
+        assert!(limiter.check("c", "GetEvidence").is_err());
+        assert!(limiter.check("c", "GetAppKey").is_ok());
+        assert_eq!(limiter.stats().throttled_by_method.get("GetEvidence"), Some(&1));
+    }
+
+    #[test]
+    fn test_disabled_and_unlimited() {
+        let limiter = RateLimiter::new(None);
+        for _ in 0..1000 {
+            assert!(limiter.check("c", "StartApp").is_ok());
+        }
+
+        let limiter = RateLimiter::new(Some(config(0, &[], 100)));
+        for _ in 0..1000 {
+            assert!(limiter.check("c", "StartApp").is_ok());
+        }
+    }
+
+    #[test]
+    fn test_tracked_clients_bounded() {
+        let limiter = RateLimiter::new(Some(config(10, &[], 50)));
+        for i in 0..500 {
+            limiter.check(&format!("ip:{}", i), "GetAppKey").unwrap();
+        }
+        assert_eq!(limiter.stats().tracked_clients, 50);
+    }
+
+    #[test]
+    fn test_update_config() {
+        let limiter = RateLimiter::new(Some(config(1, &[], 100)));
+        assert!(limiter.check("c", "StopApp").is_ok());
+        assert!(limiter.check("c", "StopApp").is_err());
+
+        limiter.update_config(Some(config(1, &[("StopApp", 0)], 100)));
+        assert!(limiter.check("c", "StopApp").is_ok());
+    }
+
+    #[test]
+    fn test_client_key_uses_validated_identity() {
+        let mut req = http::Request::builder()
+            .header("x-api-key", "made-up")
+            .body(())
+            .unwrap();
+        // A presented but unvalidated key does not choose the bucket
+        assert_eq!(client_key(&req), "local");
+
+        req.extensions_mut().insert(AuthIdentity {
+            key_name: "deployer".to_string(),
+            role: crate::config::ApiKeyRole::Admin,
+            namespace: None,
+        });
+        assert_eq!(client_key(&req), "key:deployer");
+    }
+}
@@ -0,0 +1,88 @@
+//! gRPC server reflection, so grpcurl and grpcui work without the .proto files
+//!
+//! Both the v1 and the older v1alpha protocol are served; clients differ in
+//! which one they try first. While API key authentication is enabled they
+//! always require a key (see `auth_layer::ALWAYS_PROTECTED_SERVICES`).
+
+use crate::error::{TappError, TappResult};
+use crate::proto::FILE_DESCRIPTOR_SET;
+use tonic_reflection::server::{v1, v1alpha, Builder};
+
+/// Fully qualified names of the reflection services
+pub const REFLECTION_V1: &str = "grpc.reflection.v1.ServerReflection";
+pub const REFLECTION_V1ALPHA: &str = "grpc.reflection.v1alpha.ServerReflection";
+
+fn builder() -> Builder<'static> {
+    Builder::configure()
+        .register_encoded_file_descriptor_set(FILE_DESCRIPTOR_SET)
+        .register_encoded_file_descriptor_set(tonic_health::pb::FILE_DESCRIPTOR_SET)
+}
+
+fn build_failed(e: tonic_reflection::server::Error) -> TappError {
+    TappError::Internal(format!("Cannot build the reflection service: {}", e))
+}
+
+/// Reflection service, v1 protocol
+pub fn v1_service() -> TappResult<v1::ServerReflectionServer<impl v1::ServerReflection>> {
+    builder().build_v1().map_err(build_failed)
+}
+
+/// Reflection service, v1alpha protocol
+pub fn v1alpha_service(
+) -> TappResult<v1alpha::ServerReflectionServer<impl v1alpha::ServerReflection>> {
+    builder().build_v1alpha().map_err(build_failed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::health::{TAPP_SERVICE_HEALTH_NAME, TAPP_SERVICE_V2_HEALTH_NAME};
+    use tokio_stream::wrappers::TcpListenerStream;
+    use tonic_reflection::pb::v1::server_reflection_client::ServerReflectionClient;
+    use tonic_reflection::pb::v1::server_reflection_request::MessageRequest;
+    use tonic_reflection::pb::v1::server_reflection_response::MessageResponse;
+    use tonic_reflection::pb::v1::ServerReflectionRequest;
+
+    #[tokio::test]
+    async fn test_reflection_lists_services() {
+        let (_, health_service) = tonic_health::server::health_reporter();
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(
+            tonic::transport::Server::builder()
+                .add_service(health_service)
+                .add_service(v1_service().unwrap())
+                .add_service(v1alpha_service().unwrap())
+                .serve_with_incoming(TcpListenerStream::new(listener)),
+        );
+
+        let mut client = ServerReflectionClient::connect(format!("http://{}", addr))
+            .await
+            .unwrap();
+        let request = ServerReflectionRequest {
+            host: String::new(),
+            message_request: Some(MessageRequest::ListServices(String::new())),
+        };
+        let mut responses = client
+            .server_reflection_info(tokio_stream::iter([request]))
+            .await
+            .unwrap()
+            .into_inner();
+        let response = responses.message().await.unwrap().unwrap();
+
+        let Some(MessageResponse::ListServicesResponse(list)) = response.message_response else {
+            panic!("unexpected response: {:?}", response.message_response);
+        };
+        let mut services: Vec<String> = list.service.into_iter().map(|s| s.name).collect();
+        services.sort();
+        assert_eq!(
+            services,
+            vec![
+                "grpc.health.v1.Health",
+                REFLECTION_V1,
+                TAPP_SERVICE_HEALTH_NAME,
+                TAPP_SERVICE_V2_HEALTH_NAME
+            ]
+        );
+    }
+}
@@ -0,0 +1,218 @@
+use crate::auth_layer::SharedApiKeyConfig;
+use crate::config::TappConfig;
+use crate::error::TappResult;
+use crate::ip_filter_layer::IpFilter;
+use crate::rate_limit_layer::RateLimiter;
+use std::sync::Arc;
+use tracing::{error, info};
+
+/// Hot reload of the authentication related configuration sections
+/// Only `[server.api_key]`, `[server.rate_limit]` and `[server.ip_filter]`
+/// are re-read; everything else still requires a restart.
+pub struct ConfigReloader {
+    config_path: String,
+    api_key_config: SharedApiKeyConfig,
+    rate_limiter: Arc<RateLimiter>,
+    ip_filter: Arc<IpFilter>,
+}
+
+impl ConfigReloader {
+    pub fn new(
+        config_path: String,
+        api_key_config: SharedApiKeyConfig,
+        rate_limiter: Arc<RateLimiter>,
+        ip_filter: Arc<IpFilter>,
+    ) -> Self {
+        Self {
+            config_path,
+            api_key_config,
+            rate_limiter,
+            ip_filter,
+        }
+    }
+
+    /// Re-read the config file and atomically swap the reloadable sections
+    /// If the new config is invalid, the old config stays active
+    pub fn reload(&self) -> TappResult<()> {
+        let config = TappConfig::load(self.config_path.clone())?;
+
+        // Validate everything before swapping anything; a file that would not
+        // pass at startup is not applied either
+        config.validate()?;
+        let api_key = config.server.api_key;
+        let rate_limit = config.server.rate_limit;
+        let ip_filter = config.server.ip_filter;
+
+        let key_count = api_key.as_ref().map(|c| c.keys.len()).unwrap_or(0);
+        let key_sources: Vec<String> = api_key
+            .iter()
+            .flat_map(|c| &c.keys)
+            .map(|entry| format!("{} ({})", entry.name(), entry.source()))
+            .collect();
+        // Validated above, so the filter update cannot fail halfway
+        self.ip_filter.update_config(ip_filter)?;
+        *self.api_key_config.write().unwrap() = api_key;
+        self.rate_limiter.update_config(rate_limit);
+
+        info!(
+            config_path = %self.config_path,
+            api_key_count = key_count,
+            api_keys = %key_sources.join(", "),
+            event = "CONFIG_RELOADED",
+            "Authentication configuration reloaded"
+        );
+
+        Ok(())
+    }
+
+    /// Reload on every SIGHUP until the process exits
+    pub async fn run(self) {
+        use tokio::signal::unix::{signal, SignalKind};
+
+        let mut hangup = match signal(SignalKind::hangup()) {
+            Ok(hangup) => hangup,
+            Err(e) => {
+                error!("Failed to install SIGHUP handler, config reload disabled: {}", e);
+                return;
+            }
+        };
+
+        while hangup.recv().await.is_some() {
+            info!("Received SIGHUP, reloading configuration");
+            if let Err(e) = self.reload() {
+                error!(
+                    config_path = %self.config_path,
+                    error = %e,
+                    event = "CONFIG_RELOAD_FAILED",
+                    "Config reload rejected, keeping previous configuration"
+                );
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auth_layer::ApiKeyLayer;
+    use crate::config::{ApiKeyConfig, ApiKeyEntry};
+
+    fn write_config(file: &tempfile::NamedTempFile, content: &str) {
+        std::fs::write(file.path(), content).unwrap();
+    }
+
+    #[test]
+    fn test_reload_applies_new_keys() {
+        let file = tempfile::NamedTempFile::new().unwrap();
+        write_config(
+            &file,
+            "[server.api_key]\nenabled = true\nkeys = [\"new-key\"]\n\n[server.rate_limit]\nenabled = true\ndefault_requests_per_minute = 1\n\n[server.ip_filter]\nenabled = true\ndeny = [\"203.0.113.0/24\"]\n",
+        );
+
+        let layer = ApiKeyLayer::new(None);
+        let handle = layer.config_handle();
+        let limiter = Arc::new(RateLimiter::new(None));
+        let ip_filter = Arc::new(IpFilter::new(None).unwrap());
+        let reloader = ConfigReloader::new(
+            file.path().to_string_lossy().to_string(),
+            handle.clone(),
+            limiter.clone(),
+            ip_filter.clone(),
+        );
+
+        reloader.reload().unwrap();
+
+        let active = handle.read().unwrap().clone().unwrap();
+        assert!(active.enabled);
+        assert_eq!(active.keys, vec![ApiKeyEntry::Inline("new-key".to_string())]);
+        assert!(limiter.check("c", "StartApp").is_ok());
+        assert!(limiter.check("c", "StartApp").is_err());
+        assert!(!ip_filter.is_allowed("203.0.113.7".parse().unwrap()));
+    }
+
+    #[test]
+    fn test_invalid_reload_keeps_old_config() {
+        let file = tempfile::NamedTempFile::new().unwrap();
+        write_config(&file, "[server.api_key]\nenabled = true\nkeys = []\n");
+
+        let layer = ApiKeyLayer::new(Some(ApiKeyConfig {
+            enabled: true,
+            keys: vec![ApiKeyEntry::Inline("old-key".to_string())],
+            protected_methods: vec![],
+            lockout: Default::default(),
+            hmac: Default::default(),
+            expiry_horizon_days: 14,
+            namespaces: false,
+        }));
+        let handle = layer.config_handle();
+        let reloader = ConfigReloader::new(
+            file.path().to_string_lossy().to_string(),
+            handle.clone(),
+            Arc::new(RateLimiter::new(None)),
+            Arc::new(IpFilter::new(None).unwrap()),
+        );
+
+        assert!(reloader.reload().is_err());
+        assert_eq!(
+            handle.read().unwrap().as_ref().unwrap().keys,
+            vec![ApiKeyEntry::Inline("old-key".to_string())]
+        );
+
+        // Unparseable files are rejected too
+        write_config(&file, "[server.api_key\n");
+        assert!(reloader.reload().is_err());
+        assert!(handle.read().unwrap().is_some());
+
+        // A bad CIDR rejects the whole reload
+        write_config(
+            &file,
+            "[server.api_key]\nenabled = true\nkeys = [\"new-key\"]\n\n[server.ip_filter]\nenabled = true\nallow = [\"10.0.0.0/99\"]\n",
+        );
+        assert!(reloader.reload().is_err());
+        assert_eq!(
+            handle.read().unwrap().as_ref().unwrap().keys,
+            vec![ApiKeyEntry::Inline("old-key".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_reload_rereads_key_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let key_path = dir.path().join("ci-key");
+        std::fs::write(&key_path, "first-key\n").unwrap();
+        let file = tempfile::NamedTempFile::new().unwrap();
+        write_config(
+            &file,
+            &format!(
+                "[server.api_key]\nenabled = true\nkeys = [{{ name = \"ci\", key_file = {:?} }}]\n",
+                key_path
+            ),
+        );
+
+        let layer = ApiKeyLayer::new(None);
+        let handle = layer.config_handle();
+        let reloader = ConfigReloader::new(
+            file.path().to_string_lossy().to_string(),
+            handle.clone(),
+            Arc::new(RateLimiter::new(None)),
+            Arc::new(IpFilter::new(None).unwrap()),
+        );
+        let active_key = || {
+            let active = handle.read().unwrap();
+            active.as_ref().unwrap().keys[0].key().to_string()
+        };
+
+        reloader.reload().unwrap();
+        assert_eq!(active_key(), "first-key");
+
+        // A rotated secret is picked up by the next reload
+        std::fs::write(&key_path, "second-key\n").unwrap();
+        reloader.reload().unwrap();
+        assert_eq!(active_key(), "second-key");
+
+        // A vanished secret keeps the previous keys
+        std::fs::remove_file(&key_path).unwrap();
+        assert!(reloader.reload().is_err());
+        assert_eq!(active_key(), "second-key");
+    }
+}
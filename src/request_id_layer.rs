@@ -0,0 +1,173 @@
+use std::task::{Context, Poll};
+use tonic::body::BoxBody;
+use tower::{Layer, Service};
+use tracing::Instrument;
+
+/// Metadata key carrying the request ID, in both directions
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+/// Longest client-supplied ID that is kept; longer ones are replaced
+const MAX_REQUEST_ID_LEN: usize = 128;
+
+/// ID correlating the logs of one RPC, stored in the request extensions
+#[derive(Debug, Clone, PartialEq)]
+pub struct RequestId(pub String);
+
+impl RequestId {
+    /// The caller's `x-request-id`, or a new UUID when it is missing or
+    /// unsafe to log (too long, or not `[A-Za-z0-9._:-]`)
+    pub fn from_headers(headers: &http::HeaderMap) -> Self {
+        headers
+            .get(REQUEST_ID_HEADER)
+            .and_then(|value| value.to_str().ok())
+            .filter(|id| {
+                !id.is_empty()
+                    && id.len() <= MAX_REQUEST_ID_LEN
+                    && id
+                        .chars()
+                        .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | ':' | '-'))
+            })
+            .map(|id| Self(id.to_string()))
+            .unwrap_or_else(|| Self(crate::utils::generate_session_id()))
+    }
+}
+
+/// Tower Layer giving every RPC a request ID
+/// Everything logged while the request is handled carries the ID, and the
+/// response echoes it in `x-request-id`. Place it outermost so rejections
+/// by the other layers get an ID too.
+#[derive(Clone, Default)]
+pub struct RequestIdLayer;
+
+impl RequestIdLayer {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl<S> Layer<S> for RequestIdLayer {
+    type Service = RequestIdMiddleware<S>;
+
+    fn layer(&self, service: S) -> Self::Service {
+        RequestIdMiddleware { inner: service }
+    }
+}
+
+/// Middleware running each request inside a span holding its ID
+#[derive(Clone)]
+pub struct RequestIdMiddleware<S> {
+    inner: S,
+}
+
+impl<S> Service<http::Request<BoxBody>> for RequestIdMiddleware<S>
+where
+    S: Service<http::Request<BoxBody>, Response = http::Response<BoxBody>>
+        + Clone
+        + Send
+        + 'static,
+    S::Error: Into<Box<dyn std::error::Error + Send + Sync>> + Send,
+    S::Future: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = futures_util::future::BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut req: http::Request<BoxBody>) -> Self::Future {
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+
+        let request_id = RequestId::from_headers(req.headers());
+        let span = tracing::info_span!(
+            "rpc",
+            request_id = %request_id.0,
+            method = %crate::utils::grpc_method_name(req.uri().path()),
+        );
+        let header = request_id.0.parse();
+        req.extensions_mut().insert(request_id);
+
+        Box::pin(
+            async move {
+                let mut response = inner.call(req).await?;
+                if let Ok(value) = header {
+                    response.headers_mut().insert(REQUEST_ID_HEADER, value);
+                }
+                Ok(response)
+            }
+            .instrument(span),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use std::sync::{Arc, Mutex};
+    use tonic::Status;
+    use tracing_subscriber::layer::SubscriberExt;
+
+    /// In-memory sink so tests can read back what was logged
+    #[derive(Clone, Default)]
+    struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn request(request_id: Option<&str>) -> http::Request<BoxBody> {
+        let mut builder = http::Request::builder().uri("/tapp_service.TappService/StartApp");
+        if let Some(request_id) = request_id {
+            builder = builder.header(REQUEST_ID_HEADER, request_id);
+        }
+        builder.body(tonic::body::empty_body()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_request_id_round_trips_and_tags_logs() {
+        let buffer = SharedBuffer::default();
+        let writer = buffer.clone();
+        let subscriber = tracing_subscriber::registry().with(
+            tracing_subscriber::fmt::layer()
+                .with_ansi(false)
+                .with_writer(move || writer.clone()),
+        );
+        let _default = tracing::subscriber::set_default(subscriber);
+
+        // The handler sees the ID and its logs carry it
+        let mut service = RequestIdLayer::new().layer(tower::service_fn(
+            |req: http::Request<BoxBody>| async move {
+                let request_id = req.extensions().get::<RequestId>().unwrap().clone();
+                tracing::info!("handling {}", request_id.0);
+                Ok::<_, std::convert::Infallible>(
+                    Status::invalid_argument("bad compose").into_http(),
+                )
+            },
+        ));
+
+        let response = service.call(request(Some("client-42"))).await.unwrap();
+        assert_eq!(response.headers()[REQUEST_ID_HEADER], "client-42");
+        let logged = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+        assert!(logged.contains("request_id=client-42"));
+        assert!(logged.contains("method=StartApp"));
+        assert!(logged.contains("handling client-42"));
+
+        // Missing or unsafe IDs are replaced with a generated one
+        let too_long = "x".repeat(129);
+        for incoming in [None, Some("two words"), Some(too_long.as_str())] {
+            let response = service.call(request(incoming)).await.unwrap();
+            let echoed = response.headers()[REQUEST_ID_HEADER].to_str().unwrap();
+            assert!(uuid::Uuid::parse_str(echoed).is_ok(), "{}", echoed);
+        }
+    }
+}
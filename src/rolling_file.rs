@@ -0,0 +1,254 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+/// Size-based rolling file writer
+/// The active file is `path`; rotated files are `path.1` (newest) to
+/// `path.{max_files - 1}` (oldest). Files beyond `max_files` are deleted.
+pub struct RollingFileWriter {
+    path: PathBuf,
+    max_bytes: u64,
+    max_files: usize,
+    file: File,
+    written: u64,
+}
+
+impl RollingFileWriter {
+    /// Open (or create) the active file, appending to existing content
+    pub fn new(path: impl Into<PathBuf>, max_file_size_mb: u64, max_files: usize) -> io::Result<Self> {
+        Self::with_max_bytes(path, max_file_size_mb.saturating_mul(1024 * 1024), max_files)
+    }
+
+    /// Same as `new` with the size limit in bytes (useful for tests)
+    pub fn with_max_bytes(
+        path: impl Into<PathBuf>,
+        max_bytes: u64,
+        max_files: usize,
+    ) -> io::Result<Self> {
+        let path = path.into();
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let file = Self::open(&path)?;
+        let written = file.metadata()?.len();
+
+        Ok(Self {
+            path,
+            max_bytes,
+            max_files: max_files.max(1),
+            file,
+            written,
+        })
+    }
+
+    fn open(path: &Path) -> io::Result<File> {
+        OpenOptions::new().create(true).append(true).open(path)
+    }
+
+    fn rotated_path(&self, index: usize) -> PathBuf {
+        let mut name = self.path.as_os_str().to_owned();
+        name.push(format!(".{}", index));
+        PathBuf::from(name)
+    }
+
+    /// Shift rotated files up by one and start a fresh active file
+    fn rotate(&mut self) -> io::Result<()> {
+        self.file.flush()?;
+
+        if self.max_files == 1 {
+            // No history kept: simply truncate the active file
+            self.file = OpenOptions::new()
+                .create(true)
+                .write(true)
+                .truncate(true)
+                .open(&self.path)?;
+            self.written = 0;
+            return Ok(());
+        }
+
+        let oldest = self.rotated_path(self.max_files - 1);
+        if oldest.exists() {
+            fs::remove_file(&oldest)?;
+        }
+
+        for index in (1..self.max_files - 1).rev() {
+            let from = self.rotated_path(index);
+            if from.exists() {
+                fs::rename(&from, self.rotated_path(index + 1))?;
+            }
+        }
+
+        fs::rename(&self.path, self.rotated_path(1))?;
+        self.file = Self::open(&self.path)?;
+        self.written = 0;
+        Ok(())
+    }
+}
+
+impl Write for RollingFileWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.written > 0 && self.written + buf.len() as u64 > self.max_bytes {
+            self.rotate()?;
+        }
+
+        self.file.write_all(buf)?;
+        self.written += buf.len() as u64;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+/// Delete the oldest files of a log beyond `max_files`, the active file
+/// included, and return their paths
+/// Files of the log are the rotated `path.N` files and the daily
+/// `<stem>.YYYY-MM-DD` files of the date-based rotation used before;
+/// the newest (by modification time) are kept.
+pub fn sweep_old_files(path: &Path, max_files: usize) -> io::Result<Vec<PathBuf>> {
+    let Some(name) = path.file_name().and_then(|n| n.to_str()) else {
+        return Ok(Vec::new());
+    };
+    let stem = path.file_stem().and_then(|n| n.to_str()).unwrap_or(name);
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+
+    let mut files = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let file_name = entry.file_name();
+        let Some(file_name) = file_name.to_str() else {
+            continue;
+        };
+        let rotated = file_name
+            .strip_prefix(name)
+            .and_then(|rest| rest.strip_prefix('.'))
+            .is_some_and(|index| !index.is_empty() && index.bytes().all(|b| b.is_ascii_digit()));
+        let daily = [name, stem].into_iter().any(|prefix| {
+            file_name
+                .strip_prefix(prefix)
+                .and_then(|rest| rest.strip_prefix('.'))
+                .is_some_and(is_date)
+        });
+        if !rotated && !daily {
+            continue;
+        }
+
+        let metadata = entry.metadata()?;
+        if metadata.is_file() {
+            files.push((metadata.modified()?, entry.path()));
+        }
+    }
+
+    // Newest first; the active file takes one of the `max_files` places
+    files.sort_by(|a, b| b.0.cmp(&a.0));
+    let mut removed = Vec::new();
+    for (_, file) in files.into_iter().skip(max_files.max(1) - 1) {
+        fs::remove_file(&file)?;
+        removed.push(file);
+    }
+    Ok(removed)
+}
+
+/// `YYYY-MM-DD`
+fn is_date(s: &str) -> bool {
+    s.len() == 10
+        && s.char_indices().all(|(i, c)| match i {
+            4 | 7 => c == '-',
+            _ => c.is_ascii_digit(),
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rotation_and_retention() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("audit.log");
+        let mut writer = RollingFileWriter::with_max_bytes(&path, 100, 3).unwrap();
+
+        // Each line is 50 bytes, so every third write rotates
+        let line = format!("{}\n", "x".repeat(49));
+        for _ in 0..20 {
+            writer.write_all(line.as_bytes()).unwrap();
+        }
+        writer.flush().unwrap();
+
+        assert!(path.exists());
+        assert!(dir.path().join("audit.log.1").exists());
+        assert!(dir.path().join("audit.log.2").exists());
+        assert!(!dir.path().join("audit.log.3").exists());
+
+        for entry in fs::read_dir(dir.path()).unwrap() {
+            let len = entry.unwrap().metadata().unwrap().len();
+            assert!(len <= 100);
+        }
+    }
+
+    #[test]
+    fn test_appends_to_existing_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("app.log");
+        fs::write(&path, "existing\n").unwrap();
+
+        let mut writer = RollingFileWriter::with_max_bytes(&path, 1024, 2).unwrap();
+        writer.write_all(b"new\n").unwrap();
+        writer.flush().unwrap();
+
+        assert_eq!(fs::read_to_string(&path).unwrap(), "existing\nnew\n");
+    }
+
+    #[test]
+    fn test_sweep_old_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("app.log");
+        let now = std::time::SystemTime::now();
+
+        // Oldest first: two leftover daily files, then rotated files
+        let names = [
+            "app.2026-10-14",
+            "app.2026-10-15",
+            "app.log.4",
+            "app.log.3",
+            "app.log.2",
+            "app.log.1",
+        ];
+        for (age, name) in names.iter().rev().enumerate() {
+            let file = File::create(dir.path().join(name)).unwrap();
+            file.set_modified(now - std::time::Duration::from_secs(60 * (age as u64 + 1)))
+                .unwrap();
+        }
+        fs::write(&path, "active\n").unwrap();
+        fs::write(dir.path().join("audit.log.1"), "other log\n").unwrap();
+        fs::write(dir.path().join("app.log.bak"), "not rotated\n").unwrap();
+
+        let mut removed = sweep_old_files(&path, 4).unwrap();
+        removed.sort();
+        assert_eq!(
+            removed,
+            vec![
+                dir.path().join("app.2026-10-14"),
+                dir.path().join("app.2026-10-15"),
+                dir.path().join("app.log.4"),
+            ]
+        );
+        for kept in [
+            "app.log",
+            "app.log.1",
+            "app.log.2",
+            "app.log.3",
+            "audit.log.1",
+            "app.log.bak",
+        ] {
+            assert!(dir.path().join(kept).exists(), "{} should be kept", kept);
+        }
+
+        assert!(sweep_old_files(&path, 4).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,181 @@
+use crate::proto::LogFileChunk;
+use crate::TappResult;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use sha2::{Digest, Sha256};
+use std::io::Write;
+use std::path::Path;
+use tokio::fs::File;
+use tokio::io::AsyncReadExt;
+use tokio::sync::{mpsc, OwnedSemaphorePermit};
+use tonic::Status;
+
+/// Bytes of the file read per chunk
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// Streams a whole log file in chunks, optionally gzip-compressed
+/// The file is sent as it was when the download started: data appended
+/// meanwhile is left out, so the size and checksum in the final message
+/// describe exactly the bytes sent.
+pub struct LogDownload {
+    file: File,
+    len: u64,
+    encoder: Option<GzEncoder<Vec<u8>>>,
+    hasher: Sha256,
+}
+
+impl LogDownload {
+    pub async fn open(path: &Path, compress: bool) -> TappResult<Self> {
+        let file = File::open(path).await?;
+        let len = file.metadata().await?.len();
+        Ok(Self {
+            file,
+            len,
+            encoder: compress.then(|| GzEncoder::new(Vec::new(), Compression::default())),
+            hasher: Sha256::new(),
+        })
+    }
+
+    /// Send the file, then a final message with `done` set, the
+    /// uncompressed size and its SHA-256
+    /// The permit is held until the download ends or the client goes away.
+    pub async fn run(
+        mut self,
+        tx: mpsc::Sender<Result<LogFileChunk, Status>>,
+        _permit: OwnedSemaphorePermit,
+    ) {
+        let compressed = self.encoder.is_some();
+        let final_chunk = match self.send_chunks(&tx).await {
+            Ok(Some(final_chunk)) => final_chunk,
+            // Client gone
+            Ok(None) => return,
+            Err(e) => {
+                let _ = tx.send(Err(e.into())).await;
+                return;
+            }
+        };
+
+        let _ = tx
+            .send(Ok(LogFileChunk {
+                data: final_chunk,
+                done: true,
+                compressed,
+                total_bytes: self.len,
+                sha256: hex::encode(self.hasher.finalize()),
+            }))
+            .await;
+    }
+
+    /// Send every chunk but the last; returns the data left for the final
+    /// message, or None once the receiver is dropped
+    async fn send_chunks(
+        &mut self,
+        tx: &mpsc::Sender<Result<LogFileChunk, Status>>,
+    ) -> TappResult<Option<Vec<u8>>> {
+        let compressed = self.encoder.is_some();
+        let mut remaining = self.len;
+        let mut buf = vec![0u8; CHUNK_SIZE];
+        while remaining > 0 {
+            let size = buf.len().min(remaining as usize);
+            self.file.read_exact(&mut buf[..size]).await?;
+            remaining -= size as u64;
+            self.hasher.update(&buf[..size]);
+
+            let data = match &mut self.encoder {
+                Some(encoder) => {
+                    encoder.write_all(&buf[..size])?;
+                    std::mem::take(encoder.get_mut())
+                }
+                None => buf[..size].to_vec(),
+            };
+            // The encoder buffers internally and may have nothing to send yet
+            if data.is_empty() {
+                continue;
+            }
+
+            let chunk = LogFileChunk {
+                data,
+                compressed,
+                ..Default::default()
+            };
+            if tx.send(Ok(chunk)).await.is_err() {
+                return Ok(None);
+            }
+        }
+
+        Ok(Some(match self.encoder.take() {
+            Some(encoder) => encoder.finish()?,
+            None => Vec::new(),
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::read::GzDecoder;
+    use std::io::Read;
+    use std::sync::Arc;
+    use tokio::sync::Semaphore;
+
+    async fn download(path: &Path, compress: bool) -> Vec<LogFileChunk> {
+        let permit = Arc::new(Semaphore::new(1)).acquire_owned().await.unwrap();
+        let (tx, mut rx) = mpsc::channel(4);
+        let download = LogDownload::open(path, compress).await.unwrap();
+        tokio::spawn(download.run(tx, permit));
+
+        let mut chunks = Vec::new();
+        while let Some(chunk) = rx.recv().await {
+            chunks.push(chunk.unwrap());
+        }
+        chunks
+    }
+
+    #[tokio::test]
+    async fn test_chunks_reassemble_with_checksum() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("tapp.log");
+        let content: String = (0..100_000)
+            .map(|i| format!("2026-10-16T11:00:00Z  INFO tapp_service: Line {}\n", i))
+            .collect();
+        assert!(content.len() > 4 * 1024 * 1024);
+        std::fs::write(&path, &content).unwrap();
+        let checksum = hex::encode(Sha256::digest(content.as_bytes()));
+
+        let chunks = download(&path, false).await;
+        assert!(chunks.len() > 1);
+        assert!(chunks.iter().all(|c| c.data.len() <= CHUNK_SIZE));
+        let last = chunks.last().unwrap();
+        assert!(last.done && !last.compressed);
+        assert_eq!(last.total_bytes, content.len() as u64);
+        assert_eq!(last.sha256, checksum);
+        let data: Vec<u8> = chunks.iter().flat_map(|c| c.data.clone()).collect();
+        assert_eq!(data, content.as_bytes());
+
+        // Compressed: the concatenated chunks are one gzip stream of the file
+        let chunks = download(&path, true).await;
+        let last = chunks.last().unwrap();
+        assert!(last.done && last.compressed);
+        assert_eq!(last.sha256, checksum);
+        let data: Vec<u8> = chunks.iter().flat_map(|c| c.data.clone()).collect();
+        assert!(data.len() < content.len() / 4);
+        let mut decompressed = String::new();
+        GzDecoder::new(data.as_slice())
+            .read_to_string(&mut decompressed)
+            .unwrap();
+        assert_eq!(decompressed, content);
+    }
+
+    #[tokio::test]
+    async fn test_empty_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("empty.log");
+        std::fs::write(&path, "").unwrap();
+
+        let chunks = download(&path, false).await;
+        assert_eq!(chunks.len(), 1);
+        assert!(chunks[0].done && chunks[0].data.is_empty());
+        assert_eq!(chunks[0].total_bytes, 0);
+        assert_eq!(chunks[0].sha256, hex::encode(Sha256::digest(b"")));
+    }
+}
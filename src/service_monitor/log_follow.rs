@@ -0,0 +1,353 @@
+use super::logs::MAX_TAIL_SCAN_BYTES;
+use super::tail::ReverseLines;
+use crate::proto::ServiceLogLines;
+use crate::TappResult;
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tokio::fs::{self, File};
+use tokio::io::{AsyncReadExt, AsyncSeekExt, SeekFrom};
+use tokio::sync::mpsc;
+use tonic::Status;
+use tracing::debug;
+
+/// Interval between checks for appended data
+const FOLLOW_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Most lines sent in one stream message
+const MAX_BATCH_LINES: usize = 500;
+
+/// Follows a log file like `tail -F`
+/// Appended data is read from the last offset. The file is reopened when it
+/// is replaced (size-based rotation renames it) or truncated, and a daily
+/// file (`<prefix>.YYYY-MM-DD`) is left for the next day's file once one
+/// appears.
+pub struct LogFollower {
+    path: PathBuf,
+    file: File,
+    offset: u64,
+    // (device, inode) of the open file
+    identity: (u64, u64),
+    // Bytes after the last newline, waiting for the rest of their line
+    partial: Vec<u8>,
+}
+
+impl LogFollower {
+    /// Open a file positioned for its last `history` lines
+    pub async fn open(path: &Path, history: usize) -> TappResult<Self> {
+        let mut follower = Self::open_at_start(path).await?;
+        follower.offset = Self::tail_offset(&follower.file, history).await?;
+        follower.file.seek(SeekFrom::Start(follower.offset)).await?;
+        Ok(follower)
+    }
+
+    async fn open_at_start(path: &Path) -> TappResult<Self> {
+        let file = File::open(path).await?;
+        let metadata = file.metadata().await?;
+        Ok(Self {
+            path: path.to_path_buf(),
+            file,
+            offset: 0,
+            identity: (metadata.dev(), metadata.ino()),
+            partial: Vec::new(),
+        })
+    }
+
+    /// Name of the file currently followed
+    pub fn file_name(&self) -> String {
+        self.path
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default()
+    }
+
+    /// Offset of the start of the last `lines` lines, reading backwards
+    /// from the end so large files are not read in full
+    async fn tail_offset(file: &File, lines: usize) -> TappResult<u64> {
+        let mut reader = ReverseLines::new(file.try_clone().await?, MAX_TAIL_SCAN_BYTES).await?;
+        for _ in 0..lines {
+            if reader.next_line().await?.is_none() {
+                break;
+            }
+        }
+        Ok(reader.line_start())
+    }
+
+    /// Complete lines appended since the last read
+    pub async fn read_new_lines(&mut self) -> TappResult<Vec<String>> {
+        let mut data = Vec::new();
+        self.file.read_to_end(&mut data).await?;
+        self.offset += data.len() as u64;
+
+        self.partial.extend_from_slice(&data);
+        let Some(last_newline) = self.partial.iter().rposition(|b| *b == b'\n') else {
+            return Ok(Vec::new());
+        };
+        let rest = self.partial.split_off(last_newline + 1);
+        let complete = std::mem::replace(&mut self.partial, rest);
+
+        Ok(String::from_utf8_lossy(&complete)
+            .lines()
+            .map(str::to_string)
+            .collect())
+    }
+
+    /// Switch files if the followed one was rotated
+    /// Call once the current file is drained. Returns the unterminated last
+    /// line of the old file (if any) when it switched, None otherwise.
+    pub async fn check_rotation(&mut self) -> TappResult<Option<Vec<String>>> {
+        let next = match fs::metadata(&self.path).await {
+            // Replaced: follow the new file at the same path
+            Ok(metadata) if (metadata.dev(), metadata.ino()) != self.identity => {
+                Some(self.path.clone())
+            }
+            // Truncated in place: start over
+            Ok(metadata) if metadata.len() < self.offset => {
+                self.file.seek(SeekFrom::Start(0)).await?;
+                self.offset = 0;
+                self.partial.clear();
+                return Ok(Some(Vec::new()));
+            }
+            // Removed: wait for it to come back
+            Err(_) => None,
+            Ok(_) => self.next_daily_file().await?,
+        };
+
+        let Some(next) = next else {
+            return Ok(None);
+        };
+        let next = match Self::open_at_start(&next).await {
+            Ok(next) => next,
+            // The new file may not be created yet
+            Err(_) => return Ok(None),
+        };
+        debug!(from = %self.path.display(), to = %next.path.display(), "Following rotated log file");
+
+        let unterminated = std::mem::take(&mut self.partial);
+        *self = next;
+        Ok(Some(if unterminated.is_empty() {
+            Vec::new()
+        } else {
+            vec![String::from_utf8_lossy(&unterminated).to_string()]
+        }))
+    }
+
+    /// Newer file of a daily series (`<prefix>.YYYY-MM-DD`), if one exists
+    async fn next_daily_file(&self) -> TappResult<Option<PathBuf>> {
+        let name = self.file_name();
+        let Some(prefix) = daily_prefix(&name) else {
+            return Ok(None);
+        };
+        let Some(dir) = self.path.parent() else {
+            return Ok(None);
+        };
+
+        let mut newest = name.clone();
+        let mut entries = fs::read_dir(dir).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            let candidate = entry.file_name().to_string_lossy().to_string();
+            if daily_prefix(&candidate) == Some(prefix) && candidate > newest {
+                newest = candidate;
+            }
+        }
+
+        Ok((newest != name).then(|| dir.join(newest)))
+    }
+
+    /// Stream the file until the receiver is dropped
+    /// The first message carries the history lines (possibly none); a message
+    /// with `rotated` set starts each new or truncated file.
+    pub async fn run(mut self, tx: mpsc::Sender<Result<ServiceLogLines, Status>>) {
+        let mut announce = true;
+        let mut rotated = false;
+        loop {
+            let lines = match self.read_new_lines().await {
+                Ok(lines) => lines,
+                Err(e) => {
+                    let _ = tx.send(Err(e.into())).await;
+                    return;
+                }
+            };
+
+            if !lines.is_empty() || announce {
+                if !self.send_lines(&tx, lines, rotated).await {
+                    return;
+                }
+                announce = false;
+                rotated = false;
+            } else {
+                let old_name = self.file_name();
+                match self.check_rotation().await {
+                    Ok(Some(unterminated)) => {
+                        // The old file's unterminated last line still belongs to it
+                        if !unterminated.is_empty()
+                            && !Self::send(&tx, old_name, unterminated, false).await
+                        {
+                            return;
+                        }
+                        announce = true;
+                        rotated = true;
+                        continue;
+                    }
+                    Ok(None) => {}
+                    Err(e) => {
+                        let _ = tx.send(Err(e.into())).await;
+                        return;
+                    }
+                }
+            }
+
+            tokio::select! {
+                _ = tx.closed() => return,
+                _ = tokio::time::sleep(FOLLOW_POLL_INTERVAL) => {}
+            }
+        }
+    }
+
+    /// Send lines of the current file in batches; returns false once the
+    /// client is gone
+    async fn send_lines(
+        &self,
+        tx: &mpsc::Sender<Result<ServiceLogLines, Status>>,
+        lines: Vec<String>,
+        rotated: bool,
+    ) -> bool {
+        if lines.is_empty() {
+            return Self::send(tx, self.file_name(), Vec::new(), rotated).await;
+        }
+        for (i, batch) in lines.chunks(MAX_BATCH_LINES).enumerate() {
+            if !Self::send(tx, self.file_name(), batch.to_vec(), rotated && i == 0).await {
+                return false;
+            }
+        }
+        true
+    }
+
+    async fn send(
+        tx: &mpsc::Sender<Result<ServiceLogLines, Status>>,
+        file_name: String,
+        lines: Vec<String>,
+        rotated: bool,
+    ) -> bool {
+        let message = ServiceLogLines {
+            file_name,
+            lines,
+            rotated,
+        };
+        tx.send(Ok(message)).await.is_ok()
+    }
+}
+
+/// Prefix of a daily log file name (`<prefix>.YYYY-MM-DD`)
+fn daily_prefix(name: &str) -> Option<&str> {
+    let (prefix, date) = name.rsplit_once('.')?;
+    let is_date = date.len() == 10
+        && date.char_indices().all(|(i, c)| match i {
+            4 | 7 => c == '-',
+            _ => c.is_ascii_digit(),
+        });
+    is_date.then_some(prefix)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    fn append(path: &Path, data: &str) {
+        std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .unwrap()
+            .write_all(data.as_bytes())
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_history_and_appended_lines() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("tapp.log");
+        let history: String = (0..2000).map(|i| format!("line {}\n", i)).collect();
+        append(&path, &history);
+
+        let mut follower = LogFollower::open(&path, 3).await.unwrap();
+        assert_eq!(
+            follower.read_new_lines().await.unwrap(),
+            vec!["line 1997", "line 1998", "line 1999"]
+        );
+
+        // Partial lines wait for their newline
+        append(&path, "line 2000\nline 20");
+        assert_eq!(follower.read_new_lines().await.unwrap(), vec!["line 2000"]);
+        append(&path, "01\n");
+        assert_eq!(follower.read_new_lines().await.unwrap(), vec!["line 2001"]);
+        assert!(follower.check_rotation().await.unwrap().is_none());
+
+        // Fewer lines than requested: the whole file
+        let short = dir.path().join("short.log");
+        append(&short, "only\n");
+        let mut follower = LogFollower::open(&short, 10).await.unwrap();
+        assert_eq!(follower.read_new_lines().await.unwrap(), vec!["only"]);
+    }
+
+    #[tokio::test]
+    async fn test_follows_replaced_and_daily_files() {
+        let dir = tempfile::tempdir().unwrap();
+
+        // Size-based rotation renames the file and starts a new one
+        let path = dir.path().join("tapp.log");
+        append(&path, "old 1\n");
+        let mut follower = LogFollower::open(&path, 10).await.unwrap();
+        assert_eq!(follower.read_new_lines().await.unwrap(), vec!["old 1"]);
+        append(&path, "old 2\nunterminated");
+        std::fs::rename(&path, dir.path().join("tapp.log.1")).unwrap();
+        append(&path, "new 1\n");
+
+        assert_eq!(follower.read_new_lines().await.unwrap(), vec!["old 2"]);
+        assert_eq!(
+            follower.check_rotation().await.unwrap(),
+            Some(vec!["unterminated".to_string()])
+        );
+        assert_eq!(follower.read_new_lines().await.unwrap(), vec!["new 1"]);
+
+        // Daily files switch to the next day's file once it exists
+        let today = dir.path().join("tapp.2026-10-16");
+        append(&today, "today\n");
+        append(&dir.path().join("other.2026-10-17"), "unrelated\n");
+        let mut follower = LogFollower::open(&today, 10).await.unwrap();
+        assert_eq!(follower.read_new_lines().await.unwrap(), vec!["today"]);
+        assert!(follower.check_rotation().await.unwrap().is_none());
+
+        append(&dir.path().join("tapp.2026-10-17"), "tomorrow\n");
+        assert!(follower.check_rotation().await.unwrap().is_some());
+        assert_eq!(follower.file_name(), "tapp.2026-10-17");
+        assert_eq!(follower.read_new_lines().await.unwrap(), vec!["tomorrow"]);
+    }
+
+    #[tokio::test]
+    async fn test_stops_when_stream_closes() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("tapp.log");
+        append(&path, "line 1\n");
+        let follower = LogFollower::open(&path, 10).await.unwrap();
+        let (tx, mut rx) = mpsc::channel(16);
+        let task = tokio::spawn(follower.run(tx));
+
+        assert_eq!(rx.recv().await.unwrap().unwrap().lines, vec!["line 1"]);
+        // Tonic drops the receiver once the client goes away
+        drop(rx);
+        tokio::time::timeout(FOLLOW_POLL_INTERVAL * 4, task)
+            .await
+            .expect("follower still running")
+            .unwrap();
+    }
+
+    #[test]
+    fn test_daily_prefix() {
+        assert_eq!(daily_prefix("tapp.log.2026-10-16"), Some("tapp.log"));
+        assert_eq!(daily_prefix("tapp.log"), None);
+        assert_eq!(daily_prefix("tapp.log.1"), None);
+        assert_eq!(daily_prefix("tapp.2026-1x-16"), None);
+    }
+}
@@ -1,11 +1,111 @@
-use crate::proto::{GetServiceLogsRequest, GetServiceLogsResponse, LogFileInfo};
-use crate::TappResult;
-use std::path::PathBuf;
+use super::log_download::LogDownload;
+use super::log_follow::LogFollower;
+use super::tail::ReverseLines;
+use crate::proto::{
+    DownloadLogFileRequest, GetServiceLogsRequest, GetServiceLogsResponse, LogFileChunk,
+    LogFileInfo, ServiceLogLines, StreamServiceLogsRequest,
+};
+use crate::{TappError, TappResult};
+use chrono::{DateTime, Utc};
+use std::path::{Component, Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Arc;
 use tokio::fs;
-use tokio::io::{AsyncBufReadExt, BufReader};
+use tokio::sync::{mpsc, Semaphore};
+use tonic::Status;
+use tracing::{warn, Level};
+
+/// Lines returned (or replayed before following) when the request sets none
+const DEFAULT_LOG_LINES: usize = 100;
+
+/// Most bytes read from the end of a log file for one request
+/// Bounds the time and memory of filtered reads that match rarely.
+pub const MAX_TAIL_SCAN_BYTES: u64 = 64 * 1024 * 1024;
+
+/// Whole-file downloads served at once, so they cannot starve other disk I/O
+const MAX_CONCURRENT_DOWNLOADS: usize = 2;
+
+/// Selection of log lines by level and time
+#[derive(Debug, Default)]
+struct LogFilter {
+    min_level: Option<Level>,
+    since: Option<DateTime<Utc>>,
+    until: Option<DateTime<Utc>>,
+}
+
+impl LogFilter {
+    fn from_request(request: &GetServiceLogsRequest) -> TappResult<Self> {
+        let min_level = match request.min_level.trim() {
+            "" => None,
+            level => Some(
+                Level::from_str(level).map_err(|_| TappError::InvalidParameter {
+                    field: "min_level".to_string(),
+                    reason: format!("Unknown log level: {}", level),
+                })?,
+            ),
+        };
+        let time = |field: &str, seconds: i64| -> TappResult<Option<DateTime<Utc>>> {
+            if seconds == 0 {
+                return Ok(None);
+            }
+            DateTime::from_timestamp(seconds, 0)
+                .map(Some)
+                .ok_or_else(|| TappError::InvalidParameter {
+                    field: field.to_string(),
+                    reason: format!("Invalid timestamp: {}", seconds),
+                })
+        };
+
+        Ok(Self {
+            min_level,
+            since: time("since", request.since)?,
+            until: time("until", request.until)?,
+        })
+    }
+
+    fn is_empty(&self) -> bool {
+        self.min_level.is_none() && self.since.is_none() && self.until.is_none()
+    }
+
+    /// Whether a line passes the filter
+    /// Lines whose level and time cannot be parsed pass only without a level filter.
+    fn matches(&self, line: &str) -> bool {
+        let Some((timestamp, level)) = parse_log_line(line) else {
+            return self.min_level.is_none();
+        };
+
+        // tracing orders levels by verbosity: ERROR < WARN < ... < TRACE
+        self.min_level.is_none_or(|min| level <= min)
+            && self.since.is_none_or(|since| timestamp >= since)
+            && self.until.is_none_or(|until| timestamp < until)
+    }
+}
+
+/// Timestamp and level of a log line
+/// JSON lines carry `timestamp` and `level` fields; text lines start with
+/// `<RFC 3339 timestamp> <LEVEL>`.
+fn parse_log_line(line: &str) -> Option<(DateTime<Utc>, Level)> {
+    let (timestamp, level) = if line.trim_start().starts_with('{') {
+        let value: serde_json::Value = serde_json::from_str(line).ok()?;
+        (
+            value.get("timestamp")?.as_str()?.to_string(),
+            value.get("level")?.as_str()?.to_string(),
+        )
+    } else {
+        let mut fields = line.split_whitespace();
+        (fields.next()?.to_string(), fields.next()?.to_string())
+    };
+
+    let timestamp = DateTime::parse_from_rfc3339(&timestamp)
+        .ok()?
+        .with_timezone(&Utc);
+    let level = Level::from_str(&level).ok()?;
+    Some((timestamp, level))
+}
 
 pub struct LogsService {
     log_dir: Option<PathBuf>,
+    downloads: Arc<Semaphore>,
 }
 
 impl LogsService {
@@ -19,27 +119,65 @@ impl LogsService {
             }
         });
 
-        Self { log_dir }
+        Self {
+            log_dir,
+            downloads: Arc::new(Semaphore::new(MAX_CONCURRENT_DOWNLOADS)),
+        }
     }
 
-    /// Get service logs: list files or return file content
-    pub async fn get_logs(&self, request: GetServiceLogsRequest) -> TappResult<GetServiceLogsResponse> {
-        let log_dir = match &self.log_dir {
-            Some(dir) => dir,
-            None => {
-                return Ok(GetServiceLogsResponse {
-                    success: false,
-                    message: "Logging to file is not configured".to_string(),
-                    available_files: vec![],
-                    content: String::new(),
-                    total_lines: 0,
-                });
-            }
+    /// Directory the service logs are written to, if logging to file
+    pub fn log_dir(&self) -> Option<&Path> {
+        self.log_dir.as_deref()
+    }
+
+    /// Directory of the log files; unavailable when not logging to file
+    fn require_log_dir(&self) -> TappResult<&PathBuf> {
+        self.log_dir
+            .as_ref()
+            .ok_or_else(|| TappError::ServiceUnavailable {
+                service: "file logging".to_string(),
+            })
+    }
+
+    /// Log files in the log directory, newest first
+    pub async fn log_files(&self) -> TappResult<Vec<LogFileInfo>> {
+        let log_dir = self.require_log_dir()?;
+        self.list_log_files(log_dir).await
+    }
+
+    /// Last lines of the requested file passing the request's filters
+    pub async fn tail_logs(&self, request: &GetServiceLogsRequest) -> TappResult<LogTail> {
+        let log_dir = self.require_log_dir()?;
+        let file_path = Self::resolve_log_file(log_dir, &request.file_name).await?;
+        let lines = if request.lines > 0 {
+            request.lines as usize
+        } else {
+            DEFAULT_LOG_LINES
         };
 
+        let filter = LogFilter::from_request(request)?;
+        self.read_log_file(&file_path, lines, &filter).await
+    }
+
+    /// Get service logs: list files or return file content
+    /// v1 shape: without file logging this succeeds with success=false.
+    pub async fn get_logs(
+        &self,
+        request: GetServiceLogsRequest,
+    ) -> TappResult<GetServiceLogsResponse> {
+        if self.log_dir.is_none() {
+            return Ok(GetServiceLogsResponse {
+                success: false,
+                message: "Logging to file is not configured".to_string(),
+                available_files: vec![],
+                content: String::new(),
+                total_lines: 0,
+            });
+        }
+
         // If file_name is empty, list all available log files
         if request.file_name.is_empty() {
-            let files = self.list_log_files(log_dir).await?;
+            let files = self.log_files().await?;
             return Ok(GetServiceLogsResponse {
                 success: true,
                 message: format!("Found {} log file(s)", files.len()),
@@ -50,21 +188,138 @@ impl LogsService {
         }
 
         // Otherwise, return the specified file's content
-        let file_path = log_dir.join(&request.file_name);
-        let lines = if request.lines > 0 { request.lines as usize } else { 100 };
+        let tail = self.tail_logs(&request).await?;
+        let total_lines = tail.lines.len() as i32;
 
-        let content = self.read_log_file(&file_path, lines).await?;
-        let total_lines = content.lines().count() as i32;
+        let mut message = if LogFilter::from_request(&request)?.is_empty() {
+            format!("Retrieved {} lines from {}", total_lines, request.file_name)
+        } else {
+            format!(
+                "Retrieved {} matching lines from {} ({} lines scanned)",
+                total_lines, request.file_name, tail.scanned
+            )
+        };
+        if tail.truncated {
+            message.push_str(&format!(
+                "; stopped after reading the last {} MiB",
+                MAX_TAIL_SCAN_BYTES / (1024 * 1024)
+            ));
+        }
 
         Ok(GetServiceLogsResponse {
             success: true,
-            message: format!("Retrieved {} lines from {}", total_lines, request.file_name),
+            message,
             available_files: vec![],
-            content,
+            content: tail.lines.join("\n"),
             total_lines,
         })
     }
 
+    /// Follow a log file: its last lines, then whatever is appended to it
+    /// The returned stream ends when the client disconnects.
+    pub async fn stream_logs(
+        &self,
+        request: StreamServiceLogsRequest,
+    ) -> TappResult<mpsc::Receiver<Result<ServiceLogLines, Status>>> {
+        let log_dir = self.require_log_dir()?;
+        let path = Self::resolve_log_file(log_dir, &request.file_name).await?;
+        let lines = if request.lines > 0 {
+            request.lines as usize
+        } else {
+            DEFAULT_LOG_LINES
+        };
+
+        let follower = LogFollower::open(&path, lines).await?;
+        let (tx, rx) = mpsc::channel(16);
+        tokio::spawn(follower.run(tx));
+        Ok(rx)
+    }
+
+    /// Stream a whole log file, optionally gzip-compressed
+    /// Fails with `ResourceExhausted` while the maximum number of downloads
+    /// is running.
+    pub async fn download_log(
+        &self,
+        request: DownloadLogFileRequest,
+    ) -> TappResult<mpsc::Receiver<Result<LogFileChunk, Status>>> {
+        let log_dir = self.require_log_dir()?;
+        let path = Self::resolve_log_file(log_dir, &request.file_name).await?;
+
+        let permit = self.downloads.clone().try_acquire_owned().map_err(|_| {
+            TappError::ResourceExhausted(format!(
+                "{} log downloads in progress, retry later",
+                MAX_CONCURRENT_DOWNLOADS
+            ))
+        })?;
+
+        let download = LogDownload::open(&path, request.compress).await?;
+        let (tx, rx) = mpsc::channel(4);
+        tokio::spawn(download.run(tx, permit));
+        Ok(rx)
+    }
+
+    /// Path of a log file, which must be a file directly inside the log
+    /// directory (no separators, no `..`, no symlinks pointing elsewhere)
+    /// Rejected names are logged as security events.
+    async fn resolve_log_file(log_dir: &Path, file_name: &str) -> TappResult<PathBuf> {
+        Self::check_log_file(log_dir, file_name)
+            .await
+            .map_err(|reason| {
+                warn!(
+                    file_name = %file_name,
+                    reason,
+                    event = "LOG_FILE_ACCESS_DENIED",
+                    "Rejected log file request"
+                );
+                TappError::InvalidParameter {
+                    field: "file_name".to_string(),
+                    reason: format!("{}: {}", reason, file_name),
+                }
+            })
+    }
+
+    async fn check_log_file(log_dir: &Path, file_name: &str) -> Result<PathBuf, &'static str> {
+        let name = Path::new(file_name);
+        if name.is_absolute() {
+            return Err("Absolute paths are not allowed");
+        }
+        if name
+            .components()
+            .any(|c| matches!(c, Component::ParentDir | Component::CurDir))
+            || file_name.is_empty()
+            || file_name.contains(['/', '\\'])
+        {
+            return Err("Invalid log file name");
+        }
+
+        // Only names the directory actually lists
+        let mut listed = false;
+        let mut entries = fs::read_dir(log_dir)
+            .await
+            .map_err(|_| "Log directory unavailable")?;
+        while let Ok(Some(entry)) = entries.next_entry().await {
+            if entry.file_name() == file_name {
+                listed = true;
+                break;
+            }
+        }
+        if !listed {
+            return Err("Log file not found");
+        }
+
+        // Symlinks must resolve to a file in the log directory itself
+        let path = fs::canonicalize(log_dir.join(file_name))
+            .await
+            .map_err(|_| "Log file not found")?;
+        let dir = fs::canonicalize(log_dir)
+            .await
+            .map_err(|_| "Log directory unavailable")?;
+        if path.parent() != Some(dir.as_path()) || !path.is_file() {
+            return Err("Not a file in the log directory");
+        }
+        Ok(path)
+    }
+
     /// List all log files in the directory
     async fn list_log_files(&self, dir: &PathBuf) -> TappResult<Vec<LogFileInfo>> {
         let mut files = Vec::new();
@@ -104,34 +359,269 @@ impl LogsService {
         Ok(files)
     }
 
-    /// Read last N lines from a log file (tail -n behavior)
-    async fn read_log_file(&self, path: &PathBuf, max_lines: usize) -> TappResult<String> {
-        if !path.exists() {
-           return Err(crate::TappError::InvalidParameter {
-                field: "file_name".to_string(),
-                reason: format!("Log file not found: {:?}", path), 
-           });
+    /// Read the last N lines passing the filter from a log file (tail -n behavior)
+    /// The file is read backwards from the end, at most `MAX_TAIL_SCAN_BYTES`
+    /// of it.
+    async fn read_log_file(
+        &self,
+        path: &Path,
+        max_lines: usize,
+        filter: &LogFilter,
+    ) -> TappResult<LogTail> {
+        Self::tail_file(path, max_lines, filter, MAX_TAIL_SCAN_BYTES).await
+    }
+
+    async fn tail_file(
+        path: &Path,
+        max_lines: usize,
+        filter: &LogFilter,
+        max_bytes: u64,
+    ) -> TappResult<LogTail> {
+        let mut reader = ReverseLines::open(path, max_bytes).await?;
+
+        let mut lines = Vec::with_capacity(max_lines.min(1024));
+        let mut scanned = 0;
+        while lines.len() < max_lines {
+            let Some(line) = reader.next_line().await? else {
+                break;
+            };
+            scanned += 1;
+            if filter.matches(&line) {
+                lines.push(line);
+            }
         }
+        lines.reverse();
 
-        let file = fs::File::open(path).await?;
-        let reader = BufReader::new(file);
-        let mut lines_stream = reader.lines();
+        Ok(LogTail {
+            lines,
+            scanned,
+            truncated: reader.truncated(),
+        })
+    }
+}
+
+/// Last lines of a log file
+pub struct LogTail {
+    pub lines: Vec<String>,
+    /// Lines read, matching or not
+    pub scanned: usize,
+    /// The byte limit was reached before the start of the file
+    pub truncated: bool,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_resolve_log_file_stays_in_log_dir() {
+        let root = tempfile::tempdir().unwrap();
+        let log_dir = root.path().join("logs");
+        std::fs::create_dir(&log_dir).unwrap();
+        std::fs::write(log_dir.join("tapp.log"), "line\n").unwrap();
+        std::fs::write(root.path().join("secret"), "key\n").unwrap();
+        std::os::unix::fs::symlink(root.path().join("secret"), log_dir.join("link")).unwrap();
 
-        // Read all lines
-        let mut all_lines = Vec::new();
-        while let Some(line) = lines_stream.next_line().await? {
-            all_lines.push(line);
+        let path = LogsService::resolve_log_file(&log_dir, "tapp.log")
+            .await
+            .unwrap();
+        assert_eq!(path.file_name().unwrap(), "tapp.log");
+
+        for name in [
+            "",
+            ".",
+            "..",
+            "../secret",
+            "../../etc/shadow",
+            "logs/../../secret",
+            "/etc/passwd",
+            "\\..\\secret",
+            "link",
+            "missing.log",
+        ] {
+            assert!(
+                LogsService::resolve_log_file(&log_dir, name).await.is_err(),
+                "{} should be rejected",
+                name
+            );
         }
 
-        // Take last N lines (tail behavior)
-        let start_index = if all_lines.len() > max_lines {
-            all_lines.len() - max_lines
-        } else {
-            0
+        // A symlink to another file of the log directory is fine
+        std::os::unix::fs::symlink(log_dir.join("tapp.log"), log_dir.join("current")).unwrap();
+        let path = LogsService::resolve_log_file(&log_dir, "current")
+            .await
+            .unwrap();
+        assert_eq!(path.file_name().unwrap(), "tapp.log");
+
+        // Violations surface as INVALID_ARGUMENT
+        let service = LogsService::new(Some(log_dir.join("tapp.log")));
+        let request = GetServiceLogsRequest {
+            file_name: "../secret".to_string(),
+            ..Default::default()
         };
+        let status = Status::from(service.get_logs(request).await.unwrap_err());
+        assert_eq!(status.code(), tonic::Code::InvalidArgument);
+    }
 
-        let content = all_lines[start_index..].join("\n");
-        Ok(content)
+    fn request(min_level: &str, since: i64, until: i64) -> GetServiceLogsRequest {
+        GetServiceLogsRequest {
+            file_name: "tapp.log".to_string(),
+            lines: 2,
+            min_level: min_level.to_string(),
+            since,
+            until,
+        }
     }
-}
 
+    async fn filtered(log_dir: &Path, request: GetServiceLogsRequest) -> (Vec<String>, String) {
+        let service = LogsService::new(Some(log_dir.join("tapp.log")));
+        let response = service.get_logs(request).await.unwrap();
+        let lines = response.content.lines().map(str::to_string).collect();
+        (lines, response.message)
+    }
+
+    #[tokio::test]
+    async fn test_filter_json_logs() {
+        let dir = tempfile::tempdir().unwrap();
+        let line = |ts: &str, level: &str, msg: &str| {
+            format!(
+                r#"{{"timestamp":"{}","level":"{}","fields":{{"message":"{}"}},"target":"tapp_service"}}"#,
+                ts, level, msg
+            )
+        };
+        let content = [
+            line("2026-10-16T10:00:00.000000Z", "ERROR", "old error"),
+            line("2026-10-16T11:10:00.000000Z", "INFO", "info"),
+            line("2026-10-16T11:20:00.000000Z", "ERROR", "error 1"),
+            "not json at all".to_string(),
+            line("2026-10-16T11:30:00.000000Z", "WARN", "warning"),
+            line("2026-10-16T11:40:00.000000Z", "ERROR", "error 2"),
+            line("2026-10-16T12:10:00.000000Z", "ERROR", "too new"),
+        ]
+        .join("\n");
+        std::fs::write(dir.path().join("tapp.log"), content).unwrap();
+
+        let since = DateTime::parse_from_rfc3339("2026-10-16T11:00:00Z")
+            .unwrap()
+            .timestamp();
+        let until = since + 3600;
+
+        // ERROR lines of the hour, capped at the last two after filtering
+        let (lines, message) = filtered(dir.path(), request("error", since, until)).await;
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].contains("error 1") && lines[1].contains("error 2"));
+        assert!(message.contains("2 matching lines"));
+        // Reading stops once enough lines match
+        assert!(message.contains("5 lines scanned"));
+
+        // WARN includes more severe levels
+        let (lines, _) = filtered(dir.path(), request("WARN", since, until)).await;
+        assert!(lines[0].contains("warning") && lines[1].contains("error 2"));
+
+        // Without a level filter, unparsable lines are kept
+        let mut any_level = request("", since, until);
+        any_level.lines = 10;
+        let (lines, _) = filtered(dir.path(), any_level).await;
+        assert_eq!(lines.len(), 5);
+        assert!(lines.contains(&"not json at all".to_string()));
+
+        let service = LogsService::new(Some(dir.path().join("tapp.log")));
+        assert!(service.get_logs(request("loud", 0, 0)).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_filter_text_logs() {
+        let dir = tempfile::tempdir().unwrap();
+        let content = [
+            "2026-10-16T11:00:01.123456Z  INFO tapp_service: Server started",
+            "2026-10-16T11:00:02.123456Z ERROR tapp_service::boot: Deployment failed",
+            "    continuation of a multi-line message",
+            "2026-10-16T11:00:03.123456Z DEBUG tapp_service: Polling",
+            "2026-10-16T11:00:04.123456Z  WARN tapp_service: Slow pull",
+        ]
+        .join("\n");
+        std::fs::write(dir.path().join("tapp.log"), content).unwrap();
+
+        let mut all = request("info", 0, 0);
+        all.lines = 10;
+        let (lines, message) = filtered(dir.path(), all).await;
+        assert_eq!(lines.len(), 3);
+        assert!(lines.iter().all(|line| !line.contains("DEBUG")));
+        assert!(message.contains("5 lines scanned"));
+
+        let since = DateTime::parse_from_rfc3339("2026-10-16T11:00:02Z")
+            .unwrap()
+            .timestamp();
+        let mut later = request("", since, 0);
+        later.lines = 10;
+        let (lines, _) = filtered(dir.path(), later).await;
+        assert_eq!(lines.len(), 4);
+        assert!(lines[0].contains("Deployment failed"));
+    }
+
+    #[tokio::test]
+    async fn test_tail_stops_at_byte_limit() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("tapp.log");
+        let mut content = String::from("2026-10-16T11:00:00Z ERROR tapp_service: Early error\n");
+        for i in 0..10_000 {
+            content.push_str(&format!(
+                "2026-10-16T11:00:01Z  INFO tapp_service: Line {}\n",
+                i
+            ));
+        }
+        std::fs::write(&path, content).unwrap();
+
+        let errors = LogFilter {
+            min_level: Some(Level::ERROR),
+            ..Default::default()
+        };
+        let tail = LogsService::tail_file(&path, 10, &errors, 4096)
+            .await
+            .unwrap();
+        assert!(tail.lines.is_empty());
+        assert!(tail.truncated);
+        assert!(tail.scanned < 100);
+
+        let tail = LogsService::tail_file(&path, 10, &errors, u64::MAX)
+            .await
+            .unwrap();
+        assert_eq!(tail.lines.len(), 1);
+        assert!(tail.lines[0].contains("Early error"));
+        assert!(!tail.truncated);
+        assert_eq!(tail.scanned, 10_001);
+    }
+
+    #[tokio::test]
+    async fn test_download_limit() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("tapp.log"), "line\n").unwrap();
+        let service = LogsService::new(Some(dir.path().join("tapp.log")));
+        let request = || DownloadLogFileRequest {
+            file_name: "tapp.log".to_string(),
+            compress: false,
+        };
+
+        let mut running = Vec::new();
+        for _ in 0..MAX_CONCURRENT_DOWNLOADS {
+            running.push(service.download_log(request()).await.unwrap());
+        }
+        assert!(matches!(
+            service.download_log(request()).await,
+            Err(TappError::ResourceExhausted(_))
+        ));
+
+        // A slot frees up once a download has been read to the end
+        let mut first = running.remove(0);
+        while first.recv().await.is_some() {}
+        let mut next = service.download_log(request()).await.unwrap();
+        let chunk = next.recv().await.unwrap().unwrap();
+        assert_eq!(chunk.data, b"line\n");
+
+        let traversal = DownloadLogFileRequest {
+            file_name: "../tapp.log".to_string(),
+            compress: true,
+        };
+        assert!(service.download_log(traversal).await.is_err());
+    }
+}
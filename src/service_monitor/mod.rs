@@ -1,2 +1,5 @@
+pub mod log_download;
+pub mod log_follow;
 pub mod logs;
 pub mod system_info;
+pub mod tail;
@@ -1,2 +1,268 @@
-use std::process;
-use sysinfo::{Pid, Process, System};
+use crate::boot::{BootService, DockerHostInfo};
+use crate::config::MonitoringConfig;
+use crate::notifications::{NotificationEvent, Notifier};
+use crate::proto::{DiskUsage, GetHostResourcesResponse};
+use crate::{TappError, TappResult};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use sysinfo::System;
+use tokio::sync::Mutex;
+use tracing::{debug, warn};
+
+/// Memory, load and disk usage of the host the service runs on
+/// Readings come from /proc and statvfs (plus one Docker API call) and are
+/// reused for `cache_seconds`, so frequent polling stays cheap. A disk
+/// reaching its warning threshold is reported through the notifier.
+pub struct HostMonitor {
+    config: MonitoringConfig,
+    log_dir: Option<PathBuf>,
+    notifier: Notifier,
+    cache: Mutex<Option<(Instant, GetHostResourcesResponse)>>,
+}
+
+impl HostMonitor {
+    pub fn new(config: &MonitoringConfig, log_dir: Option<PathBuf>, notifier: Notifier) -> Self {
+        Self {
+            config: config.clone(),
+            log_dir,
+            notifier,
+            cache: Mutex::new(None),
+        }
+    }
+
+    /// Current readings, or the cached ones if still fresh
+    pub async fn resources(
+        &self,
+        boot_service: &BootService,
+    ) -> TappResult<GetHostResourcesResponse> {
+        // Held while reading so concurrent callers share one reading
+        let mut cache = self.cache.lock().await;
+        let max_age = Duration::from_secs(self.config.cache_seconds);
+        if let Some((taken, reading)) = cache.as_ref() {
+            if taken.elapsed() < max_age {
+                return Ok(reading.clone());
+            }
+        }
+
+        let docker = match boot_service.docker_host_info().await {
+            Ok(info) => Some(info),
+            Err(e) => {
+                warn!(error = %e, "Cannot read Docker host information");
+                None
+            }
+        };
+
+        let mut dirs = vec![("tapp_data", boot_service.data_dir().to_path_buf())];
+        if let Some(root) = docker.as_ref().and_then(|d| d.root_dir.clone()) {
+            dirs.push(("docker_data", root));
+        }
+        if let Some(log_dir) = &self.log_dir {
+            dirs.push(("logs", log_dir.clone()));
+        }
+
+        // statvfs may block on a stuck filesystem; keep it off the executor
+        let config = self.config.clone();
+        let reading = tokio::task::spawn_blocking(move || read_host(&config, &dirs, docker))
+            .await
+            .map_err(|e| TappError::Internal(format!("Host resource reading failed: {}", e)))?;
+
+        let previous = cache.as_ref().map(|(_, reading)| reading);
+        for disk in crossed_disk_warnings(previous, &reading) {
+            self.notifier.notify(NotificationEvent::DiskThreshold {
+                name: disk.name.clone(),
+                path: disk.path.clone(),
+                used_percent: disk.used_percent,
+                threshold_percent: self.config.disk_warning_percent,
+            });
+        }
+
+        *cache = Some((Instant::now(), reading.clone()));
+        Ok(reading)
+    }
+}
+
+/// Disks warning in `current` that did not in `previous`
+fn crossed_disk_warnings<'a>(
+    previous: Option<&GetHostResourcesResponse>,
+    current: &'a GetHostResourcesResponse,
+) -> Vec<&'a DiskUsage> {
+    current
+        .disks
+        .iter()
+        .filter(|disk| disk.warning)
+        .filter(|disk| {
+            !previous.is_some_and(|previous| {
+                previous
+                    .disks
+                    .iter()
+                    .any(|p| p.name == disk.name && p.warning)
+            })
+        })
+        .collect()
+}
+
+/// Take one reading of the host
+fn read_host(
+    config: &MonitoringConfig,
+    dirs: &[(&str, PathBuf)],
+    docker: Option<DockerHostInfo>,
+) -> GetHostResourcesResponse {
+    let mut system = System::new();
+    system.refresh_memory();
+    let memory_total_bytes = system.total_memory();
+    let memory_available_bytes = system.available_memory();
+    let memory_used_percent = used_percent(memory_total_bytes, memory_available_bytes);
+    let load = System::load_average();
+
+    let disks: Vec<DiskUsage> = dirs
+        .iter()
+        .filter_map(|(name, path)| disk_usage(name, path, config.disk_warning_percent))
+        .collect();
+
+    let disk_warning = disks.iter().any(|disk| disk.warning);
+    let memory_warning = memory_used_percent >= config.memory_warning_percent;
+    let (docker_available, running_containers) = match docker {
+        Some(info) => (true, info.running_containers),
+        None => (false, 0),
+    };
+
+    GetHostResourcesResponse {
+        success: true,
+        message: if disk_warning || memory_warning {
+            "Host resources low".to_string()
+        } else {
+            "Host resources retrieved".to_string()
+        },
+        timestamp: crate::utils::current_timestamp(),
+        memory_total_bytes,
+        memory_available_bytes,
+        memory_used_percent,
+        load_average_1m: load.one,
+        load_average_5m: load.five,
+        load_average_15m: load.fifteen,
+        disks,
+        docker_available,
+        running_containers,
+        disk_warning,
+        memory_warning,
+    }
+}
+
+/// Usage of the filesystem a directory lives on (None if it cannot be read)
+fn disk_usage(name: &str, path: &Path, warning_percent: f64) -> Option<DiskUsage> {
+    let usage = match psutil::disk::disk_usage(path) {
+        Ok(usage) => usage,
+        Err(e) => {
+            debug!(path = %path.display(), error = %e, "Skipping disk usage");
+            return None;
+        }
+    };
+
+    // `free` is what unprivileged users can still write; like `df`, the
+    // percentage leaves out the blocks reserved for root
+    let used_percent = used_percent(usage.used() + usage.free(), usage.free());
+    Some(DiskUsage {
+        name: name.to_string(),
+        path: path.display().to_string(),
+        total_bytes: usage.total(),
+        available_bytes: usage.free(),
+        used_percent,
+        warning: used_percent >= warning_percent,
+    })
+}
+
+fn used_percent(total: u64, available: u64) -> f64 {
+    if total == 0 {
+        return 0.0;
+    }
+    total.saturating_sub(available) as f64 * 100.0 / total as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(warning_percent: f64) -> MonitoringConfig {
+        MonitoringConfig {
+            disk_warning_percent: warning_percent,
+            memory_warning_percent: warning_percent,
+            cache_seconds: 5,
+        }
+    }
+
+    #[test]
+    fn test_read_host_and_warnings() {
+        let dir = tempfile::tempdir().unwrap();
+        let dirs = [
+            ("logs", dir.path().to_path_buf()),
+            ("missing", dir.path().join("missing")),
+        ];
+        let docker = DockerHostInfo {
+            root_dir: None,
+            running_containers: 3,
+            ..Default::default()
+        };
+
+        let reading = read_host(&config(100.1), &dirs, Some(docker));
+        assert!(reading.memory_total_bytes > 0);
+        assert!(reading.memory_available_bytes <= reading.memory_total_bytes);
+        assert_eq!(reading.disks.len(), 1);
+        assert_eq!(reading.disks[0].name, "logs");
+        assert!(reading.disks[0].total_bytes > 0);
+        assert!(reading.docker_available);
+        assert_eq!(reading.running_containers, 3);
+        assert!(!reading.disk_warning && !reading.memory_warning);
+
+        // A threshold of 0% flags everything
+        let reading = read_host(&config(0.0), &dirs, None);
+        assert!(reading.disks[0].warning);
+        assert!(reading.disk_warning && reading.memory_warning);
+        assert!(!reading.docker_available);
+    }
+
+    #[test]
+    fn test_crossed_disk_warnings() {
+        let reading = |warnings: &[(&str, bool)]| GetHostResourcesResponse {
+            disks: warnings
+                .iter()
+                .map(|(name, warning)| DiskUsage {
+                    name: name.to_string(),
+                    warning: *warning,
+                    ..Default::default()
+                })
+                .collect(),
+            ..Default::default()
+        };
+        let names = |disks: Vec<&DiskUsage>| -> Vec<String> {
+            disks.into_iter().map(|d| d.name.clone()).collect()
+        };
+
+        let first = reading(&[("tapp_data", true), ("logs", false)]);
+        assert_eq!(
+            names(crossed_disk_warnings(None, &first)),
+            vec!["tapp_data"]
+        );
+
+        // Still warning: reported once only
+        let second = reading(&[("tapp_data", true), ("logs", true)]);
+        assert_eq!(
+            names(crossed_disk_warnings(Some(&first), &second)),
+            vec!["logs"]
+        );
+
+        // Reported again after recovering
+        let recovered = reading(&[("tapp_data", false), ("logs", true)]);
+        assert!(crossed_disk_warnings(Some(&second), &recovered).is_empty());
+        assert_eq!(
+            names(crossed_disk_warnings(Some(&recovered), &second)),
+            vec!["tapp_data"]
+        );
+    }
+
+    #[test]
+    fn test_used_percent() {
+        assert_eq!(used_percent(200, 50), 75.0);
+        assert_eq!(used_percent(0, 0), 0.0);
+        assert_eq!(used_percent(100, 150), 0.0);
+    }
+}
@@ -0,0 +1,225 @@
+use crate::TappResult;
+use std::path::Path;
+use tokio::fs::File;
+use tokio::io::{AsyncReadExt, AsyncSeekExt, SeekFrom};
+
+/// Size of the blocks read backwards from the end of the file
+const BLOCK_SIZE: u64 = 64 * 1024;
+
+/// Reads the lines of a file from last to first
+/// The file is read backwards in fixed-size blocks, so only the blocks
+/// holding the lines actually returned are read, and at most `max_bytes` of
+/// the file in total. Lines are split on `\n` bytes, which never occur inside
+/// a multi-byte UTF-8 sequence, and decoded only once complete. A trailing
+/// newline ends the last line rather than starting an empty one.
+pub struct ReverseLines {
+    file: File,
+    // Offset of the start of `block`
+    pos: u64,
+    // Unconsumed bytes of the file at `pos..`, up to the last line returned
+    block: Vec<u8>,
+    // Later pieces of the line being assembled, last piece first
+    carry: Vec<Vec<u8>>,
+    // Offset of the start of the last line returned
+    line_start: u64,
+    scanned_bytes: u64,
+    max_bytes: u64,
+    truncated: bool,
+    // Every line has been returned
+    done: bool,
+}
+
+impl ReverseLines {
+    pub async fn open(path: &Path, max_bytes: u64) -> TappResult<Self> {
+        Self::new(File::open(path).await?, max_bytes).await
+    }
+
+    /// Read an open file from its end; moves the file's cursor
+    pub async fn new(file: File, max_bytes: u64) -> TappResult<Self> {
+        let len = file.metadata().await?.len();
+        let mut reader = Self {
+            file,
+            pos: len,
+            block: Vec::new(),
+            carry: Vec::new(),
+            line_start: len,
+            scanned_bytes: 0,
+            max_bytes,
+            truncated: false,
+            done: len == 0,
+        };
+
+        if reader.read_block().await? && reader.block.last() == Some(&b'\n') {
+            reader.block.pop();
+        }
+        Ok(reader)
+    }
+
+    /// The previous line, or None at the start of the file or once
+    /// `max_bytes` have been read
+    pub async fn next_line(&mut self) -> TappResult<Option<String>> {
+        loop {
+            if let Some(i) = self.block.iter().rposition(|b| *b == b'\n') {
+                let line = self.block.split_off(i + 1);
+                self.block.truncate(i);
+                self.line_start = self.pos + i as u64 + 1;
+                return Ok(Some(self.assemble(line)));
+            }
+
+            // No newline left: the whole block belongs to the current line
+            if !self.block.is_empty() {
+                self.carry.push(std::mem::take(&mut self.block));
+            }
+            if self.read_block().await? {
+                continue;
+            }
+
+            if self.done {
+                return Ok(None);
+            }
+            self.done = true;
+            if self.pos > 0 {
+                // Stopped by the byte limit in the middle of a line
+                self.truncated = true;
+                self.carry.clear();
+                return Ok(None);
+            }
+            // The first line of the file
+            self.line_start = 0;
+            return Ok(Some(self.assemble(Vec::new())));
+        }
+    }
+
+    /// Offset of the start of the last line returned (the file length
+    /// before any line is)
+    pub fn line_start(&self) -> u64 {
+        self.line_start
+    }
+
+    /// Bytes of the file read so far
+    pub fn scanned_bytes(&self) -> u64 {
+        self.scanned_bytes
+    }
+
+    /// Whether reading stopped at `max_bytes` before the start of the file
+    pub fn truncated(&self) -> bool {
+        self.truncated
+    }
+
+    /// Read the block before `pos` into `block`; false at the start of the
+    /// file or when the byte limit is reached
+    async fn read_block(&mut self) -> TappResult<bool> {
+        let budget = self.max_bytes.saturating_sub(self.scanned_bytes);
+        let size = BLOCK_SIZE.min(self.pos).min(budget);
+        if size == 0 {
+            return Ok(false);
+        }
+
+        self.pos -= size;
+        self.block.resize(size as usize, 0);
+        self.file.seek(SeekFrom::Start(self.pos)).await?;
+        self.file.read_exact(&mut self.block).await?;
+        self.scanned_bytes += size;
+        Ok(true)
+    }
+
+    fn assemble(&mut self, mut line: Vec<u8>) -> String {
+        for piece in self.carry.drain(..).rev() {
+            line.extend_from_slice(&piece);
+        }
+        String::from_utf8_lossy(&line).into_owned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    async fn last_lines(path: &Path, count: usize, max_bytes: u64) -> Vec<String> {
+        let mut reader = ReverseLines::open(path, max_bytes).await.unwrap();
+        let mut lines = Vec::new();
+        while lines.len() < count {
+            match reader.next_line().await.unwrap() {
+                Some(line) => lines.push(line),
+                None => break,
+            }
+        }
+        lines.reverse();
+        lines
+    }
+
+    #[tokio::test]
+    async fn test_last_lines_of_large_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("large.log");
+        let mut file = std::io::BufWriter::new(std::fs::File::create(&path).unwrap());
+        for i in 0..400_000 {
+            writeln!(
+                file,
+                "2026-10-16T11:00:00Z  INFO line number {:08} of the log",
+                i
+            )
+            .unwrap();
+        }
+        file.flush().unwrap();
+        drop(file);
+        let len = std::fs::metadata(&path).unwrap().len();
+        assert!(len > 16 * 1024 * 1024);
+
+        let mut reader = ReverseLines::open(&path, u64::MAX).await.unwrap();
+        let mut lines = Vec::new();
+        for _ in 0..100 {
+            lines.push(reader.next_line().await.unwrap().unwrap());
+        }
+        assert!(lines[0].contains("line number 00399999"));
+        assert!(lines[99].contains("line number 00399900"));
+        // Only the last block was read
+        assert_eq!(reader.scanned_bytes(), BLOCK_SIZE);
+        assert_eq!(reader.line_start(), len - 100 * (lines[0].len() as u64 + 1));
+
+        // The byte limit stops the scan, dropping the line it cuts through
+        let mut reader = ReverseLines::open(&path, 1000).await.unwrap();
+        let mut count = 0;
+        while reader.next_line().await.unwrap().is_some() {
+            count += 1;
+        }
+        assert_eq!(count, 1000 / (lines[0].len() + 1));
+        assert!(reader.truncated());
+        assert_eq!(reader.scanned_bytes(), 1000);
+    }
+
+    #[tokio::test]
+    async fn test_line_boundaries() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("tapp.log");
+
+        // Missing trailing newline
+        std::fs::write(&path, "first\nsecond\nthird").unwrap();
+        assert_eq!(
+            last_lines(&path, 2, u64::MAX).await,
+            vec!["second", "third"]
+        );
+
+        // Trailing newline, empty lines kept, fewer lines than requested
+        std::fs::write(&path, "first\n\nthird\n").unwrap();
+        assert_eq!(
+            last_lines(&path, 10, u64::MAX).await,
+            vec!["first", "", "third"]
+        );
+
+        std::fs::write(&path, "").unwrap();
+        assert!(last_lines(&path, 10, u64::MAX).await.is_empty());
+        std::fs::write(&path, "\n").unwrap();
+        assert_eq!(last_lines(&path, 10, u64::MAX).await, vec![""]);
+
+        // Lines spanning several blocks, with multi-byte characters across
+        // block boundaries
+        let long: String = "é€😀".repeat(BLOCK_SIZE as usize / 3);
+        std::fs::write(&path, format!("head\n{}\ntail ✓\n", long)).unwrap();
+        assert_eq!(
+            last_lines(&path, 3, u64::MAX).await,
+            vec!["head".to_string(), long, "tail ✓".to_string()]
+        );
+    }
+}
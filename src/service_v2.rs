@@ -0,0 +1,958 @@
+//! TappService v2 (proto/tapp_service_v2.proto), served next to v1
+//!
+//! Both versions run on the same `TappServiceImpl`: most v2 handlers call the
+//! v1 handler and drop its `success`/`message` fields, so authorization,
+//! auditing and validation cannot drift apart. The handlers whose v1 shape
+//! reports failures in the response body (GetTaskStatus, GetServiceLogs) and
+//! the paginated lists are built from the shared lookups instead.
+//!
+//! Migrating a client from v1:
+//! - A response arriving means the call succeeded; stop checking `success`.
+//!   Failures are non-OK statuses whose `ErrorInfo` reason says what failed.
+//! - GetTaskStatus on an unknown or purged task returns NOT_FOUND (reasons
+//!   TASK_NOT_FOUND, TASK_EXPIRED) instead of success=false.
+//! - GetServiceLogs without file logging returns UNAVAILABLE instead of
+//!   success=false; the scan count and truncation that v1 put in `message`
+//!   are `lines_scanned` and `truncated`.
+//! - ListAppMeasurements and the GetServiceLogs file list are paginated:
+//!   repeat the call with `next_page_token` until it comes back empty.
+
+use crate::proto::v2::{self, tapp_service_server::TappService as TappServiceV2};
+use crate::proto::{
+    CancelTaskRequest, ContributeShareRequest, DeleteSealedBlobRequest, DownloadLogFileRequest,
+    ExportKeystoreBackupRequest, ForceRemoveAppRequest, GetAppInfoRequest, GetAppKeyRequest,
+    GetAppLogsRequest, GetAppSecretKeyRequest, GetAttestedAppKeyRequest, GetClusterStatusRequest,
+    GetEvidenceRequest, GetHostResourcesRequest, GetNonceRequest, GetNonceResponse,
+    GetServiceLogsRequest, GetServiceStatusRequest, GetTaskStatusRequest,
+    ImportKeystoreBackupRequest, InitClusterRequest, JoinClusterRequest, ListSealedBlobsRequest,
+    RecoverClusterRequest, RecoverShareRequest, RequestKeyShareRequest, RetryTaskRequest,
+    SealDataRequest, SetLogLevelRequest, SignTransactionRequest, StartAppRequest, StopAppRequest,
+    StreamServiceLogsRequest, TransferAppOwnershipRequest, UnsealDataRequest,
+    VerifyRtmrReplayRequest, WatchTaskRequest,
+};
+use crate::{proto, task_status_response, TappError, TappResult, TappService, TappServiceImpl};
+use tokio_stream::StreamExt;
+use tonic::{Request, Response, Status};
+
+/// Items per page when the request sets no page_size
+pub const DEFAULT_PAGE_SIZE: usize = 100;
+
+/// Largest page_size honored; larger requests get pages of this size
+pub const MAX_PAGE_SIZE: usize = 1000;
+
+/// One page of `items`, and the token of the next page (empty on the last)
+/// Tokens are opaque to clients; they hold the offset of the page's first
+/// item, so a list that changes between calls may skip or repeat an item.
+fn paginate<T>(items: Vec<T>, page_size: i32, page_token: &str) -> TappResult<(Vec<T>, String)> {
+    let page_size = match page_size {
+        0 => DEFAULT_PAGE_SIZE,
+        size if size < 0 => {
+            return Err(TappError::InvalidParameter {
+                field: "page_size".to_string(),
+                reason: format!("Must not be negative, got {}", size),
+            })
+        }
+        size => (size as usize).min(MAX_PAGE_SIZE),
+    };
+    let offset = if page_token.is_empty() {
+        0
+    } else {
+        page_token
+            .parse::<usize>()
+            .map_err(|_| TappError::InvalidParameter {
+                field: "page_token".to_string(),
+                reason: "Not a token returned as next_page_token".to_string(),
+            })?
+    };
+
+    let total = items.len();
+    let page: Vec<T> = items.into_iter().skip(offset).take(page_size).collect();
+    let next = offset.saturating_add(page_size);
+    let next_page_token = if next < total {
+        next.to_string()
+    } else {
+        String::new()
+    };
+    Ok((page, next_page_token))
+}
+
+/// v1 response reshaped as its v2 counterpart, keeping the metadata and the
+/// extensions (e.g. audit annotations) the v1 handler set
+fn reshape<T, U: From<T>>(response: Result<Response<T>, Status>) -> Result<Response<U>, Status> {
+    let (metadata, message, extensions) = response?.into_parts();
+    Ok(Response::from_parts(metadata, U::from(message), extensions))
+}
+
+#[tonic::async_trait]
+impl TappServiceV2 for TappServiceImpl {
+    async fn start_app(
+        &self,
+        request: Request<StartAppRequest>,
+    ) -> Result<Response<v2::StartAppResponse>, Status> {
+        reshape(TappService::start_app(self, request).await)
+    }
+
+    async fn stop_app(
+        &self,
+        request: Request<StopAppRequest>,
+    ) -> Result<Response<v2::StopAppResponse>, Status> {
+        reshape(TappService::stop_app(self, request).await)
+    }
+
+    async fn transfer_app_ownership(
+        &self,
+        request: Request<TransferAppOwnershipRequest>,
+    ) -> Result<Response<v2::TransferAppOwnershipResponse>, Status> {
+        reshape(TappService::transfer_app_ownership(self, request).await)
+    }
+
+    async fn get_task_status(
+        &self,
+        request: Request<GetTaskStatusRequest>,
+    ) -> Result<Response<v2::GetTaskStatusResponse>, Status> {
+        let scope = self.app_scope(&request);
+        let req = request.into_inner();
+        let task = self.find_task(&scope, &req.task_id).await?;
+        Ok(Response::new(
+            task_status_response(&self.boot_service, &scope, &task).into(),
+        ))
+    }
+
+    type WatchTaskStream = std::pin::Pin<
+        Box<dyn tokio_stream::Stream<Item = Result<v2::GetTaskStatusResponse, Status>> + Send>,
+    >;
+
+    async fn watch_task(
+        &self,
+        request: Request<WatchTaskRequest>,
+    ) -> Result<Response<Self::WatchTaskStream>, Status> {
+        let (metadata, updates, extensions) =
+            TappService::watch_task(self, request).await?.into_parts();
+        let updates = updates.map(|update| update.map(v2::GetTaskStatusResponse::from));
+        Ok(Response::from_parts(
+            metadata,
+            Box::pin(updates) as Self::WatchTaskStream,
+            extensions,
+        ))
+    }
+
+    async fn cancel_task(
+        &self,
+        request: Request<CancelTaskRequest>,
+    ) -> Result<Response<v2::CancelTaskResponse>, Status> {
+        reshape(TappService::cancel_task(self, request).await)
+    }
+
+    async fn retry_task(
+        &self,
+        request: Request<RetryTaskRequest>,
+    ) -> Result<Response<v2::RetryTaskResponse>, Status> {
+        reshape(TappService::retry_task(self, request).await)
+    }
+
+    async fn list_app_measurements(
+        &self,
+        request: Request<v2::ListAppMeasurementsRequest>,
+    ) -> Result<Response<v2::ListAppMeasurementsResponse>, Status> {
+        let scope = self.app_scope(&request);
+        let req = request.into_inner();
+        let measurements = self.measurement_infos(&scope, req.deployer_filter).await;
+        let total_count = measurements.len() as i32;
+        let (measurements, next_page_token) =
+            paginate(measurements, req.page_size, &req.page_token)?;
+
+        Ok(Response::new(v2::ListAppMeasurementsResponse {
+            measurements,
+            next_page_token,
+            total_count,
+            hash_algorithm: self.boot_service.get_hash_algorithm(),
+        }))
+    }
+
+    async fn get_evidence(
+        &self,
+        request: Request<GetEvidenceRequest>,
+    ) -> Result<Response<v2::GetEvidenceResponse>, Status> {
+        reshape(TappService::get_evidence(self, request).await)
+    }
+
+    async fn get_app_key(
+        &self,
+        request: Request<GetAppKeyRequest>,
+    ) -> Result<Response<v2::GetAppKeyResponse>, Status> {
+        reshape(TappService::get_app_key(self, request).await)
+    }
+
+    async fn get_attested_app_key(
+        &self,
+        request: Request<GetAttestedAppKeyRequest>,
+    ) -> Result<Response<v2::GetAttestedAppKeyResponse>, Status> {
+        reshape(TappService::get_attested_app_key(self, request).await)
+    }
+
+    async fn get_app_secret_key(
+        &self,
+        request: Request<GetAppSecretKeyRequest>,
+    ) -> Result<Response<v2::GetAppSecretKeyResponse>, Status> {
+        reshape(TappService::get_app_secret_key(self, request).await)
+    }
+
+    async fn sign_transaction(
+        &self,
+        request: Request<SignTransactionRequest>,
+    ) -> Result<Response<v2::SignTransactionResponse>, Status> {
+        reshape(TappService::sign_transaction(self, request).await)
+    }
+
+    async fn get_nonce(
+        &self,
+        request: Request<GetNonceRequest>,
+    ) -> Result<Response<GetNonceResponse>, Status> {
+        TappService::get_nonce(self, request).await
+    }
+
+    async fn get_app_info(
+        &self,
+        request: Request<GetAppInfoRequest>,
+    ) -> Result<Response<v2::GetAppInfoResponse>, Status> {
+        reshape(TappService::get_app_info(self, request).await)
+    }
+
+    async fn get_service_status(
+        &self,
+        request: Request<GetServiceStatusRequest>,
+    ) -> Result<Response<v2::GetServiceStatusResponse>, Status> {
+        reshape(TappService::get_service_status(self, request).await)
+    }
+
+    async fn get_host_resources(
+        &self,
+        request: Request<GetHostResourcesRequest>,
+    ) -> Result<Response<v2::GetHostResourcesResponse>, Status> {
+        reshape(TappService::get_host_resources(self, request).await)
+    }
+
+    async fn get_service_logs(
+        &self,
+        request: Request<v2::GetServiceLogsRequest>,
+    ) -> Result<Response<v2::GetServiceLogsResponse>, Status> {
+        let req = request.into_inner();
+
+        if req.file_name.is_empty() {
+            let files = self.logs_service.log_files().await?;
+            let total_count = files.len() as i32;
+            let (available_files, next_page_token) =
+                paginate(files, req.page_size, &req.page_token)?;
+            return Ok(Response::new(v2::GetServiceLogsResponse {
+                available_files,
+                next_page_token,
+                total_count,
+                ..Default::default()
+            }));
+        }
+
+        let tail = self
+            .logs_service
+            .tail_logs(&GetServiceLogsRequest {
+                file_name: req.file_name,
+                lines: req.lines,
+                min_level: req.min_level,
+                since: req.since,
+                until: req.until,
+            })
+            .await?;
+        Ok(Response::new(v2::GetServiceLogsResponse {
+            total_lines: tail.lines.len() as i32,
+            content: tail.lines.join("\n"),
+            lines_scanned: tail.scanned as i64,
+            truncated: tail.truncated,
+            ..Default::default()
+        }))
+    }
+
+    type StreamServiceLogsStream = <TappServiceImpl as TappService>::StreamServiceLogsStream;
+
+    async fn stream_service_logs(
+        &self,
+        request: Request<StreamServiceLogsRequest>,
+    ) -> Result<Response<Self::StreamServiceLogsStream>, Status> {
+        TappService::stream_service_logs(self, request).await
+    }
+
+    type DownloadLogFileStream = <TappServiceImpl as TappService>::DownloadLogFileStream;
+
+    async fn download_log_file(
+        &self,
+        request: Request<DownloadLogFileRequest>,
+    ) -> Result<Response<Self::DownloadLogFileStream>, Status> {
+        TappService::download_log_file(self, request).await
+    }
+
+    async fn get_app_logs(
+        &self,
+        request: Request<GetAppLogsRequest>,
+    ) -> Result<Response<v2::GetAppLogsResponse>, Status> {
+        reshape(TappService::get_app_logs(self, request).await)
+    }
+
+    async fn set_log_level(
+        &self,
+        request: Request<SetLogLevelRequest>,
+    ) -> Result<Response<v2::SetLogLevelResponse>, Status> {
+        reshape(TappService::set_log_level(self, request).await)
+    }
+
+    async fn force_remove_app(
+        &self,
+        request: Request<ForceRemoveAppRequest>,
+    ) -> Result<Response<v2::ForceRemoveAppResponse>, Status> {
+        reshape(TappService::force_remove_app(self, request).await)
+    }
+
+    async fn seal_data(
+        &self,
+        request: Request<SealDataRequest>,
+    ) -> Result<Response<v2::SealDataResponse>, Status> {
+        reshape(TappService::seal_data(self, request).await)
+    }
+
+    async fn unseal_data(
+        &self,
+        request: Request<UnsealDataRequest>,
+    ) -> Result<Response<v2::UnsealDataResponse>, Status> {
+        reshape(TappService::unseal_data(self, request).await)
+    }
+
+    async fn list_sealed_blobs(
+        &self,
+        request: Request<ListSealedBlobsRequest>,
+    ) -> Result<Response<v2::ListSealedBlobsResponse>, Status> {
+        reshape(TappService::list_sealed_blobs(self, request).await)
+    }
+
+    async fn delete_sealed_blob(
+        &self,
+        request: Request<DeleteSealedBlobRequest>,
+    ) -> Result<Response<v2::DeleteSealedBlobResponse>, Status> {
+        reshape(TappService::delete_sealed_blob(self, request).await)
+    }
+
+    async fn init_cluster(
+        &self,
+        request: Request<InitClusterRequest>,
+    ) -> Result<Response<v2::InitClusterResponse>, Status> {
+        reshape(TappService::init_cluster(self, request).await)
+    }
+
+    async fn get_cluster_status(
+        &self,
+        request: Request<GetClusterStatusRequest>,
+    ) -> Result<Response<v2::GetClusterStatusResponse>, Status> {
+        reshape(TappService::get_cluster_status(self, request).await)
+    }
+
+    async fn request_key_share(
+        &self,
+        request: Request<RequestKeyShareRequest>,
+    ) -> Result<Response<v2::RequestKeyShareResponse>, Status> {
+        reshape(TappService::request_key_share(self, request).await)
+    }
+
+    async fn join_cluster(
+        &self,
+        request: Request<JoinClusterRequest>,
+    ) -> Result<Response<v2::JoinClusterResponse>, Status> {
+        reshape(TappService::join_cluster(self, request).await)
+    }
+
+    async fn recover_share(
+        &self,
+        request: Request<RecoverShareRequest>,
+    ) -> Result<Response<v2::RecoverShareResponse>, Status> {
+        reshape(TappService::recover_share(self, request).await)
+    }
+
+    async fn contribute_share(
+        &self,
+        request: Request<ContributeShareRequest>,
+    ) -> Result<Response<v2::ContributeShareResponse>, Status> {
+        reshape(TappService::contribute_share(self, request).await)
+    }
+
+    async fn recover_cluster(
+        &self,
+        request: Request<RecoverClusterRequest>,
+    ) -> Result<Response<v2::RecoverClusterResponse>, Status> {
+        reshape(TappService::recover_cluster(self, request).await)
+    }
+
+    async fn export_keystore_backup(
+        &self,
+        request: Request<ExportKeystoreBackupRequest>,
+    ) -> Result<Response<v2::ExportKeystoreBackupResponse>, Status> {
+        reshape(TappService::export_keystore_backup(self, request).await)
+    }
+
+    async fn import_keystore_backup(
+        &self,
+        request: Request<ImportKeystoreBackupRequest>,
+    ) -> Result<Response<v2::ImportKeystoreBackupResponse>, Status> {
+        reshape(TappService::import_keystore_backup(self, request).await)
+    }
+
+    async fn verify_rtmr_replay(
+        &self,
+        request: Request<VerifyRtmrReplayRequest>,
+    ) -> Result<Response<v2::VerifyRtmrReplayResponse>, Status> {
+        reshape(TappService::verify_rtmr_replay(self, request).await)
+    }
+}
+
+impl From<proto::StartAppResponse> for v2::StartAppResponse {
+    fn from(r: proto::StartAppResponse) -> Self {
+        Self {
+            task_id: r.task_id,
+            timestamp: r.timestamp,
+        }
+    }
+}
+
+impl From<proto::StopAppResponse> for v2::StopAppResponse {
+    fn from(r: proto::StopAppResponse) -> Self {
+        Self {
+            timestamp: r.timestamp,
+        }
+    }
+}
+
+impl From<proto::TransferAppOwnershipResponse> for v2::TransferAppOwnershipResponse {
+    fn from(r: proto::TransferAppOwnershipResponse) -> Self {
+        Self {
+            previous_deployer: r.previous_deployer,
+            new_deployer: r.new_deployer,
+            timestamp: r.timestamp,
+        }
+    }
+}
+
+impl From<proto::GetTaskStatusResponse> for v2::GetTaskStatusResponse {
+    fn from(r: proto::GetTaskStatusResponse) -> Self {
+        Self {
+            task_id: r.task_id,
+            status: r.status,
+            result: r.result,
+            created_at: r.created_at,
+            updated_at: r.updated_at,
+            stage: r.stage,
+            progress_percent: r.progress_percent,
+            app_id: r.app_id,
+            kind: r.kind,
+            retried_from: r.retried_from,
+            queue_position: r.queue_position,
+            image_scans: r.image_scans,
+        }
+    }
+}
+
+impl From<proto::CancelTaskResponse> for v2::CancelTaskResponse {
+    fn from(r: proto::CancelTaskResponse) -> Self {
+        Self {
+            task_id: r.task_id,
+            status: r.status,
+        }
+    }
+}
+
+impl From<proto::RetryTaskResponse> for v2::RetryTaskResponse {
+    fn from(r: proto::RetryTaskResponse) -> Self {
+        Self {
+            task_id: r.task_id,
+            new_task_id: r.new_task_id,
+        }
+    }
+}
+
+impl From<proto::GetEvidenceResponse> for v2::GetEvidenceResponse {
+    fn from(r: proto::GetEvidenceResponse) -> Self {
+        Self {
+            evidence: r.evidence,
+            tee_type: r.tee_type,
+            timestamp: r.timestamp,
+        }
+    }
+}
+
+impl From<proto::GetAppKeyResponse> for v2::GetAppKeyResponse {
+    fn from(r: proto::GetAppKeyResponse) -> Self {
+        Self {
+            public_key: r.public_key,
+            eth_address: r.eth_address,
+            key_source: r.key_source,
+        }
+    }
+}
+
+impl From<proto::GetAttestedAppKeyResponse> for v2::GetAttestedAppKeyResponse {
+    fn from(r: proto::GetAttestedAppKeyResponse) -> Self {
+        Self {
+            public_key: r.public_key,
+            eth_address: r.eth_address,
+            compose_hash: r.compose_hash,
+            nonce: r.nonce,
+            evidence: r.evidence,
+            tee_type: r.tee_type,
+            timestamp: r.timestamp,
+        }
+    }
+}
+
+impl From<proto::GetAppSecretKeyResponse> for v2::GetAppSecretKeyResponse {
+    fn from(r: proto::GetAppSecretKeyResponse) -> Self {
+        Self {
+            private_key: r.private_key,
+            public_key: r.public_key,
+            eth_address: r.eth_address,
+        }
+    }
+}
+
+impl From<proto::SignTransactionResponse> for v2::SignTransactionResponse {
+    fn from(r: proto::SignTransactionResponse) -> Self {
+        Self {
+            raw_transaction: r.raw_transaction,
+            tx_hash: r.tx_hash,
+            signing_hash: r.signing_hash,
+            eth_address: r.eth_address,
+        }
+    }
+}
+
+impl From<proto::GetAppInfoResponse> for v2::GetAppInfoResponse {
+    fn from(r: proto::GetAppInfoResponse) -> Self {
+        Self {
+            app_id: r.app_id,
+            compose_content: r.compose_content,
+            volumes_content: r.volumes_content,
+            events: r.events,
+            deployed_at: r.deployed_at,
+            current_started_at: r.current_started_at,
+            uptime_seconds: r.uptime_seconds,
+            restart_count: r.restart_count,
+            running: r.running,
+            platforms: r.platforms,
+        }
+    }
+}
+
+impl From<proto::GetServiceStatusResponse> for v2::GetServiceStatusResponse {
+    fn from(r: proto::GetServiceStatusResponse) -> Self {
+        Self {
+            services: r.services,
+            timestamp: r.timestamp,
+            system_healthy: r.system_healthy,
+            system_version: r.system_version,
+            expiring_keys: r.expiring_keys,
+            nonce_status: r.nonce_status,
+            notification_status: r.notification_status,
+            image_policy: r.image_policy,
+            container_runtime: r.container_runtime,
+        }
+    }
+}
+
+impl From<proto::GetHostResourcesResponse> for v2::GetHostResourcesResponse {
+    fn from(r: proto::GetHostResourcesResponse) -> Self {
+        Self {
+            timestamp: r.timestamp,
+            memory_total_bytes: r.memory_total_bytes,
+            memory_available_bytes: r.memory_available_bytes,
+            memory_used_percent: r.memory_used_percent,
+            load_average_1m: r.load_average_1m,
+            load_average_5m: r.load_average_5m,
+            load_average_15m: r.load_average_15m,
+            disks: r.disks,
+            docker_available: r.docker_available,
+            running_containers: r.running_containers,
+            disk_warning: r.disk_warning,
+            memory_warning: r.memory_warning,
+        }
+    }
+}
+
+impl From<proto::GetAppLogsResponse> for v2::GetAppLogsResponse {
+    fn from(r: proto::GetAppLogsResponse) -> Self {
+        Self {
+            content: r.content,
+            total_lines: r.total_lines,
+            task_id: r.task_id,
+        }
+    }
+}
+
+impl From<proto::SetLogLevelResponse> for v2::SetLogLevelResponse {
+    fn from(r: proto::SetLogLevelResponse) -> Self {
+        Self {
+            previous_filter: r.previous_filter,
+            filter: r.filter,
+            revert_at: r.revert_at,
+        }
+    }
+}
+
+impl From<proto::ForceRemoveAppResponse> for v2::ForceRemoveAppResponse {
+    fn from(r: proto::ForceRemoveAppResponse) -> Self {
+        Self {
+            cancelled_task_id: r.cancelled_task_id,
+            containers_removed: r.containers_removed,
+            app_dir_removed: r.app_dir_removed,
+            measurement_removed: r.measurement_removed,
+            compose_content_removed: r.compose_content_removed,
+            mount_files_removed: r.mount_files_removed,
+            errors: r.errors,
+        }
+    }
+}
+
+impl From<proto::SealDataResponse> for v2::SealDataResponse {
+    fn from(r: proto::SealDataResponse) -> Self {
+        Self { blob: r.blob }
+    }
+}
+
+impl From<proto::UnsealDataResponse> for v2::UnsealDataResponse {
+    fn from(r: proto::UnsealDataResponse) -> Self {
+        Self {
+            blob: r.blob,
+            data: r.data,
+        }
+    }
+}
+
+impl From<proto::ListSealedBlobsResponse> for v2::ListSealedBlobsResponse {
+    fn from(r: proto::ListSealedBlobsResponse) -> Self {
+        Self { blobs: r.blobs }
+    }
+}
+
+impl From<proto::DeleteSealedBlobResponse> for v2::DeleteSealedBlobResponse {
+    fn from(r: proto::DeleteSealedBlobResponse) -> Self {
+        Self { deleted: r.deleted }
+    }
+}
+
+impl From<proto::InitClusterResponse> for v2::InitClusterResponse {
+    fn from(r: proto::InitClusterResponse) -> Self {
+        Self { status: r.status }
+    }
+}
+
+impl From<proto::GetClusterStatusResponse> for v2::GetClusterStatusResponse {
+    fn from(r: proto::GetClusterStatusResponse) -> Self {
+        Self { status: r.status }
+    }
+}
+
+impl From<proto::RequestKeyShareResponse> for v2::RequestKeyShareResponse {
+    fn from(r: proto::RequestKeyShareResponse) -> Self {
+        Self {
+            share_index: r.share_index,
+            total_shares: r.total_shares,
+            threshold: r.threshold,
+            initialized_at: r.initialized_at,
+            sealed_share: r.sealed_share,
+            public_key: r.public_key,
+            evidence: r.evidence,
+        }
+    }
+}
+
+impl From<proto::JoinClusterResponse> for v2::JoinClusterResponse {
+    fn from(r: proto::JoinClusterResponse) -> Self {
+        Self { status: r.status }
+    }
+}
+
+impl From<proto::RecoverShareResponse> for v2::RecoverShareResponse {
+    fn from(r: proto::RecoverShareResponse) -> Self {
+        Self {
+            share_index: r.share_index,
+            total_shares: r.total_shares,
+            threshold: r.threshold,
+            initialized_at: r.initialized_at,
+            sealed_share: r.sealed_share,
+            public_key: r.public_key,
+            evidence: r.evidence,
+            contributors: r.contributors,
+        }
+    }
+}
+
+impl From<proto::ContributeShareResponse> for v2::ContributeShareResponse {
+    fn from(r: proto::ContributeShareResponse) -> Self {
+        Self {
+            share_index: r.share_index,
+            sealed_share: r.sealed_share,
+            public_key: r.public_key,
+            evidence: r.evidence,
+        }
+    }
+}
+
+impl From<proto::RecoverClusterResponse> for v2::RecoverClusterResponse {
+    fn from(r: proto::RecoverClusterResponse) -> Self {
+        Self {
+            status: r.status,
+            coordinator: r.coordinator,
+        }
+    }
+}
+
+impl From<proto::ExportKeystoreBackupResponse> for v2::ExportKeystoreBackupResponse {
+    fn from(r: proto::ExportKeystoreBackupResponse) -> Self {
+        Self {
+            backup: r.backup,
+            manifest: r.manifest,
+            manifest_hash: r.manifest_hash,
+            created_at: r.created_at,
+        }
+    }
+}
+
+impl From<proto::ImportKeystoreBackupResponse> for v2::ImportKeystoreBackupResponse {
+    fn from(r: proto::ImportKeystoreBackupResponse) -> Self {
+        Self {
+            manifest_hash: r.manifest_hash,
+            created_at: r.created_at,
+            imported: r.imported,
+            overwritten: r.overwritten,
+            kept: r.kept,
+            unchanged: r.unchanged,
+            removed: r.removed,
+        }
+    }
+}
+
+impl From<proto::VerifyRtmrReplayResponse> for v2::VerifyRtmrReplayResponse {
+    fn from(r: proto::VerifyRtmrReplayResponse) -> Self {
+        Self {
+            rtmr_index: r.rtmr_index,
+            initial_value: r.initial_value,
+            replayed_value: r.replayed_value,
+            quoted_value: r.quoted_value,
+            steps: r.steps,
+            matches: r.matches,
+            first_divergent_step: r.first_divergent_step,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::boot::measurement::AppMeasurement;
+    use crate::proto::v2::tapp_service_client::TappServiceClient as TappServiceV2Client;
+    use crate::proto::v2::tapp_service_server::TappServiceServer as TappServiceV2Server;
+    use crate::{TappConfig, TappServiceClient, TappServiceServer};
+    use std::sync::Arc;
+    use tokio_stream::wrappers::TcpListenerStream;
+    use tonic_types::StatusExt;
+
+    async fn create_test_service(dir: &std::path::Path) -> TappServiceImpl {
+        let mut config = TappConfig::default();
+        config.boot.aa_config_path = Some(dir.join("aa.toml").to_string_lossy().to_string());
+        config.boot.data_dir = dir.join("apps");
+        TappServiceImpl::new(config).await.unwrap()
+    }
+
+    fn reason(status: &Status) -> String {
+        status
+            .check_error_details()
+            .unwrap()
+            .error_info()
+            .map(|info| info.reason.clone())
+            .unwrap_or_default()
+    }
+
+    #[test]
+    fn test_paginate() {
+        let items: Vec<u32> = (0..5).collect();
+
+        let (page, token) = paginate(items.clone(), 2, "").unwrap();
+        assert_eq!(page, vec![0, 1]);
+        let (page, token) = paginate(items.clone(), 2, &token).unwrap();
+        assert_eq!(page, vec![2, 3]);
+        let (page, token) = paginate(items.clone(), 2, &token).unwrap();
+        assert_eq!(page, vec![4]);
+        assert!(token.is_empty());
+
+        // Defaults and caps
+        let many: Vec<usize> = (0..MAX_PAGE_SIZE + 1).collect();
+        let (page, _) = paginate(many.clone(), 0, "").unwrap();
+        assert_eq!(page.len(), DEFAULT_PAGE_SIZE);
+        let (page, token) = paginate(many, i32::MAX, "").unwrap();
+        assert_eq!(page.len(), MAX_PAGE_SIZE);
+        assert!(!token.is_empty());
+
+        // A token past the end of a shrunk list is an empty last page
+        let (page, token) = paginate(items.clone(), 2, "10").unwrap();
+        assert!(page.is_empty() && token.is_empty());
+
+        for (size, token, field) in [(-1, "", "page_size"), (2, "abc", "page_token")] {
+            let err = paginate(items.clone(), size, token).unwrap_err();
+            assert!(
+                matches!(&err, TappError::InvalidParameter { field: f, .. } if f == field),
+                "{:?}",
+                err
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_missing_task_v1_and_v2() {
+        let dir = tempfile::tempdir().unwrap();
+        let service = create_test_service(dir.path()).await;
+        let request = || {
+            Request::new(GetTaskStatusRequest {
+                task_id: "no-such-task".to_string(),
+            })
+        };
+
+        // v1: success=false in an OK response
+        let response = TappService::get_task_status(&service, request())
+            .await
+            .unwrap()
+            .into_inner();
+        assert!(!response.success);
+        assert_eq!(response.message, "Task not found: no-such-task");
+
+        // v2: NOT_FOUND with a reason
+        let status = TappServiceV2::get_task_status(&service, request())
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), tonic::Code::NotFound);
+        assert_eq!(status.message(), "Task not found: no-such-task");
+        assert_eq!(reason(&status), "TASK_NOT_FOUND");
+
+        let status = TappServiceV2::get_app_info(
+            &service,
+            Request::new(GetAppInfoRequest {
+                app_id: "no-such-app".to_string(),
+            }),
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(status.code(), tonic::Code::NotFound);
+        assert_eq!(reason(&status), "APP_NOT_FOUND");
+    }
+
+    #[tokio::test]
+    async fn test_service_logs_without_file_logging() {
+        let dir = tempfile::tempdir().unwrap();
+        let service = create_test_service(dir.path()).await;
+
+        let response =
+            TappService::get_service_logs(&service, Request::new(GetServiceLogsRequest::default()))
+                .await
+                .unwrap()
+                .into_inner();
+        assert!(!response.success);
+
+        let status = TappServiceV2::get_service_logs(
+            &service,
+            Request::new(v2::GetServiceLogsRequest::default()),
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(status.code(), tonic::Code::Unavailable);
+        assert_eq!(reason(&status), "SERVICE_UNAVAILABLE");
+    }
+
+    #[tokio::test]
+    async fn test_list_app_measurements_v1_and_v2() {
+        let dir = tempfile::tempdir().unwrap();
+        let service = create_test_service(dir.path()).await;
+        for (i, app_id) in ["app-one", "app-two", "app-three"].iter().enumerate() {
+            service
+                .boot_service
+                .insert_app_measurement(AppMeasurement {
+                    app_id: app_id.to_string(),
+                    compose_hash: String::new(),
+                    volumes_hash: String::new(),
+                    deployer: "ab".repeat(32),
+                    deployer_address: "ab".repeat(20),
+                    timestamp: 1_000 + i as i64,
+                    namespace: None,
+                    secret_names: Vec::new(),
+                })
+                .await;
+        }
+
+        // v1 returns every measurement at once
+        let response = TappService::list_app_measurements(
+            &service,
+            Request::new(proto::ListAppMeasurementsRequest::default()),
+        )
+        .await
+        .unwrap()
+        .into_inner();
+        assert!(response.success);
+        assert_eq!(response.measurements.len(), 3);
+
+        // v2 pages through the same list, newest first
+        let mut app_ids = Vec::new();
+        let mut page_token = String::new();
+        loop {
+            let response = TappServiceV2::list_app_measurements(
+                &service,
+                Request::new(v2::ListAppMeasurementsRequest {
+                    page_size: 2,
+                    page_token,
+                    ..Default::default()
+                }),
+            )
+            .await
+            .unwrap()
+            .into_inner();
+            assert_eq!(response.total_count, 3);
+            app_ids.extend(response.measurements.into_iter().map(|m| m.app_id));
+            if response.next_page_token.is_empty() {
+                break;
+            }
+            page_token = response.next_page_token;
+        }
+        assert_eq!(app_ids, vec!["app-three", "app-two", "app-one"]);
+    }
+
+    #[tokio::test]
+    async fn test_v1_and_v2_on_one_server() {
+        let dir = tempfile::tempdir().unwrap();
+        let service = Arc::new(create_test_service(dir.path()).await);
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(
+            tonic::transport::Server::builder()
+                .add_service(TappServiceServer::from_arc(service.clone()))
+                .add_service(TappServiceV2Server::from_arc(service))
+                .serve_with_incoming(TcpListenerStream::new(listener)),
+        );
+
+        let v1 = TappServiceClient::connect(addr.clone())
+            .await
+            .unwrap()
+            .get_service_status(GetServiceStatusRequest::default())
+            .await
+            .unwrap()
+            .into_inner();
+        let v2 = TappServiceV2Client::connect(addr)
+            .await
+            .unwrap()
+            .get_service_status(GetServiceStatusRequest::default())
+            .await
+            .unwrap()
+            .into_inner();
+        assert!(v1.success);
+        assert_eq!(v2.services, v1.services);
+        assert_eq!(v2.system_version, v1.system_version);
+    }
+}
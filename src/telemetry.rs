@@ -0,0 +1,276 @@
+use crate::config::TelemetryConfig;
+use crate::error::ConfigError;
+use crate::request_id_layer::RequestId;
+use crate::TappResult;
+use opentelemetry::propagation::{Extractor, TextMapPropagator};
+use opentelemetry::trace::TracerProvider as _;
+use opentelemetry::KeyValue;
+use opentelemetry_otlp::WithExportConfig;
+use opentelemetry_sdk::propagation::TraceContextPropagator;
+use opentelemetry_sdk::trace::{Sampler, TracerProvider};
+use opentelemetry_sdk::{runtime, Resource};
+use std::task::{Context, Poll};
+use tower::{Layer, Service};
+use tracing::instrument::{Instrument, Instrumented};
+use tracing::Span;
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+use tracing_subscriber::filter::{self, FilterFn};
+use tracing_subscriber::registry::LookupSpan;
+
+/// Target of the spans exported as traces
+/// Log output leaves these spans out, so enabling export does not change
+/// the logs.
+pub const SPAN_TARGET: &str = "tapp_trace";
+
+/// Exporter state to shut down on exit
+#[derive(Default)]
+pub struct TelemetryGuard {
+    provider: Option<TracerProvider>,
+}
+
+impl TelemetryGuard {
+    pub fn new(provider: TracerProvider) -> Self {
+        Self {
+            provider: Some(provider),
+        }
+    }
+
+    /// Export the spans still batched and stop the exporter
+    pub fn shutdown(self) {
+        if let Some(provider) = self.provider {
+            if let Err(e) = provider.shutdown() {
+                tracing::warn!(error = %e, "Failed to flush trace exporter");
+            }
+        }
+    }
+}
+
+/// Tracer provider batching spans to the configured OTLP collector; must be
+/// called inside a Tokio runtime
+pub fn otlp_provider(config: &TelemetryConfig) -> TappResult<TracerProvider> {
+    config.validate()?;
+
+    let exporter = opentelemetry_otlp::SpanExporter::builder()
+        .with_tonic()
+        .with_endpoint(config.endpoint.clone())
+        .build()
+        .map_err(|e| ConfigError::InvalidValue {
+            field: "telemetry.endpoint".to_string(),
+            reason: format!("Cannot create OTLP exporter: {}", e),
+        })?;
+
+    Ok(TracerProvider::builder()
+        .with_batch_exporter(exporter, runtime::Tokio)
+        .with_sampler(Sampler::ParentBased(Box::new(Sampler::TraceIdRatioBased(
+            config.sampling_ratio,
+        ))))
+        .with_resource(Resource::new(vec![KeyValue::new(
+            "service.name",
+            config.service_name.clone(),
+        )]))
+        .build())
+}
+
+/// Subscriber layer exporting the spans through `provider`
+pub fn otel_layer<S>(
+    provider: &TracerProvider,
+) -> tracing_opentelemetry::OpenTelemetryLayer<S, opentelemetry_sdk::trace::Tracer>
+where
+    S: tracing::Subscriber + for<'a> LookupSpan<'a>,
+{
+    tracing_opentelemetry::layer().with_tracer(provider.tracer(crate::NAME))
+}
+
+/// Per-layer filter keeping the exported spans out of a log layer
+pub fn without_trace_spans() -> FilterFn<impl Fn(&tracing::Metadata<'_>) -> bool> {
+    filter::filter_fn(|metadata| metadata.target() != SPAN_TARGET)
+}
+
+/// Per-layer filter keeping the log-only spans (request and task IDs) out of
+/// the exported traces; events still reach the trace span they occur in
+pub fn only_trace_spans() -> FilterFn<impl Fn(&tracing::Metadata<'_>) -> bool> {
+    filter::filter_fn(|metadata| !metadata.is_span() || metadata.target() == SPAN_TARGET)
+}
+
+/// Tower layer starting a span for every gRPC request
+/// The span continues a trace passed in a W3C `traceparent` header; when
+/// export is disabled no span is created.
+#[derive(Clone)]
+pub struct TraceLayer {
+    enabled: bool,
+}
+
+impl TraceLayer {
+    pub fn new(enabled: bool) -> Self {
+        Self { enabled }
+    }
+}
+
+impl<S> Layer<S> for TraceLayer {
+    type Service = TraceMiddleware<S>;
+
+    fn layer(&self, service: S) -> Self::Service {
+        TraceMiddleware {
+            inner: service,
+            enabled: self.enabled,
+        }
+    }
+}
+
+/// Middleware running each request inside its span
+#[derive(Clone)]
+pub struct TraceMiddleware<S> {
+    inner: S,
+    enabled: bool,
+}
+
+impl<S, B> Service<http::Request<B>> for TraceMiddleware<S>
+where
+    S: Service<http::Request<B>>,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = Instrumented<S::Future>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: http::Request<B>) -> Self::Future {
+        let span = if self.enabled {
+            request_span(&req)
+        } else {
+            Span::none()
+        };
+        let future = span.in_scope(|| self.inner.call(req));
+        future.instrument(span)
+    }
+}
+
+/// Span for one request, named after the gRPC path like other gRPC servers
+fn request_span<B>(req: &http::Request<B>) -> Span {
+    let path = req.uri().path();
+    let span = tracing::info_span!(
+        target: SPAN_TARGET,
+        "grpc.request",
+        otel.name = %path.trim_start_matches('/'),
+        otel.kind = "server",
+        rpc.system = "grpc",
+        rpc.method = %crate::utils::grpc_method_name(path),
+        net.peer = %crate::audit_layer::request_source(req),
+        request.id = tracing::field::Empty,
+    );
+    if let Some(request_id) = req.extensions().get::<RequestId>() {
+        span.record("request.id", request_id.0.as_str());
+    }
+
+    let parent = TraceContextPropagator::new().extract(&HeaderExtractor(req.headers()));
+    span.set_parent(parent);
+    span
+}
+
+/// Reads propagation headers from a request
+struct HeaderExtractor<'a>(&'a http::HeaderMap);
+
+impl Extractor for HeaderExtractor<'_> {
+    fn get(&self, key: &str) -> Option<&str> {
+        self.0.get(key).and_then(|value| value.to_str().ok())
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        self.0.keys().map(|key| key.as_str()).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use opentelemetry::trace::{SpanId, TraceId};
+    use opentelemetry_sdk::testing::trace::InMemorySpanExporter;
+    use std::future::{ready, Ready};
+    use tracing_subscriber::layer::SubscriberExt;
+
+    /// Handler opening one child span, like a deploy phase
+    #[derive(Clone)]
+    struct Handler;
+
+    impl Service<http::Request<()>> for Handler {
+        type Response = ();
+        type Error = std::convert::Infallible;
+        type Future = Ready<Result<(), Self::Error>>;
+
+        fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn call(&mut self, _req: http::Request<()>) -> Self::Future {
+            tracing::info_span!(target: SPAN_TARGET, "measurement").in_scope(|| ready(Ok(())))
+        }
+    }
+
+    fn request(traceparent: Option<&str>) -> http::Request<()> {
+        let mut builder = http::Request::builder().uri("/tapp_service.TappService/StartApp");
+        if let Some(traceparent) = traceparent {
+            builder = builder.header("traceparent", traceparent);
+        }
+        builder.body(()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_request_and_child_spans_exported() {
+        let exporter = InMemorySpanExporter::default();
+        let provider = TracerProvider::builder()
+            .with_simple_exporter(exporter.clone())
+            .build();
+        let subscriber = tracing_subscriber::registry().with(otel_layer(&provider));
+        let _default = tracing::subscriber::set_default(subscriber);
+
+        let mut service = TraceLayer::new(true).layer(Handler);
+        let mut req = request(None);
+        req.extensions_mut().insert(RequestId("req-1".to_string()));
+        service.call(req).await.unwrap();
+
+        let spans = exporter.get_finished_spans().unwrap();
+        assert_eq!(spans.len(), 2);
+        let (child, root) = (&spans[0], &spans[1]);
+        assert_eq!(root.name, "tapp_service.TappService/StartApp");
+        assert_eq!(root.parent_span_id, SpanId::INVALID);
+        assert!(root
+            .attributes
+            .iter()
+            .any(|kv| kv.key.as_str() == "rpc.method" && kv.value.as_str() == "StartApp"));
+        assert!(root
+            .attributes
+            .iter()
+            .any(|kv| kv.key.as_str() == "request.id" && kv.value.as_str() == "req-1"));
+        assert_eq!(child.name, "measurement");
+        assert_eq!(child.parent_span_id, root.span_context.span_id());
+        assert_eq!(child.span_context.trace_id(), root.span_context.trace_id());
+
+        // A caller's trace is continued
+        exporter.reset();
+        service
+            .call(request(Some(
+                "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01",
+            )))
+            .await
+            .unwrap();
+        let spans = exporter.get_finished_spans().unwrap();
+        assert_eq!(
+            spans[1].span_context.trace_id(),
+            TraceId::from_hex("4bf92f3577b34da6a3ce929d0e0e4736").unwrap()
+        );
+        assert_eq!(
+            spans[1].parent_span_id,
+            SpanId::from_hex("00f067aa0ba902b7").unwrap()
+        );
+
+        // Disabled: no request span, the handler's span has no parent
+        exporter.reset();
+        let mut service = TraceLayer::new(false).layer(Handler);
+        service.call(request(None)).await.unwrap();
+        let spans = exporter.get_finished_spans().unwrap();
+        assert_eq!(spans.len(), 1);
+        assert_eq!(spans[0].parent_span_id, SpanId::INVALID);
+    }
+}
@@ -0,0 +1,155 @@
+use crate::config::ServerConfig;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use tonic::body::BoxBody;
+use tonic::Status;
+use tower::{Layer, Service};
+use tracing::warn;
+
+/// Tower Layer bounding the time an RPC may take
+/// Past `server.request_timeout_seconds` (or the method's override) the
+/// handler future is dropped and the caller gets DEADLINE_EXCEEDED; child
+/// processes started by the handler are killed with it.
+#[derive(Clone)]
+pub struct TimeoutLayer {
+    config: Arc<ServerConfig>,
+}
+
+impl TimeoutLayer {
+    pub fn new(config: &ServerConfig) -> Self {
+        Self {
+            config: Arc::new(config.clone()),
+        }
+    }
+}
+
+impl<S> Layer<S> for TimeoutLayer {
+    type Service = TimeoutMiddleware<S>;
+
+    fn layer(&self, service: S) -> Self::Service {
+        TimeoutMiddleware {
+            inner: service,
+            config: self.config.clone(),
+        }
+    }
+}
+
+/// Middleware abandoning requests that exceed their time limit
+#[derive(Clone)]
+pub struct TimeoutMiddleware<S> {
+    inner: S,
+    config: Arc<ServerConfig>,
+}
+
+impl<S> Service<http::Request<BoxBody>> for TimeoutMiddleware<S>
+where
+    S: Service<http::Request<BoxBody>, Response = http::Response<BoxBody>>
+        + Clone
+        + Send
+        + 'static,
+    S::Error: Into<Box<dyn std::error::Error + Send + Sync>> + Send,
+    S::Future: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = futures_util::future::BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: http::Request<BoxBody>) -> Self::Future {
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+
+        let method_name = crate::utils::grpc_method_name(req.uri().path()).to_string();
+        let Some(limit) = self.config.request_timeout(&method_name) else {
+            return Box::pin(inner.call(req));
+        };
+
+        Box::pin(async move {
+            match tokio::time::timeout(limit, inner.call(req)).await {
+                Ok(result) => result,
+                Err(_) => {
+                    warn!(
+                        method = %method_name,
+                        timeout_seconds = limit.as_secs(),
+                        event = "REQUEST_TIMEOUT",
+                        "Request abandoned after its time limit"
+                    );
+                    Ok(Status::deadline_exceeded(format!(
+                        "{} did not complete within {}s",
+                        method_name,
+                        limit.as_secs()
+                    ))
+                    .into_http())
+                }
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::time::Duration;
+
+    /// Sets its flag when dropped, i.e. when the handler future goes away
+    struct DropFlag(Arc<AtomicBool>);
+
+    impl Drop for DropFlag {
+        fn drop(&mut self) {
+            self.0.store(true, Ordering::SeqCst);
+        }
+    }
+
+    fn request(method: &str) -> http::Request<BoxBody> {
+        http::Request::builder()
+            .uri(format!("/tapp_service.TappService/{}", method))
+            .body(tonic::body::empty_body())
+            .unwrap()
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_slow_handler_is_abandoned() {
+        let config = ServerConfig {
+            request_timeout_seconds: 30,
+            method_timeout_seconds: [("StopApp".to_string(), 120), ("GetAppLogs".to_string(), 0)]
+                .into_iter()
+                .collect(),
+            ..Default::default()
+        };
+        let dropped = Arc::new(AtomicBool::new(false));
+        let flag = dropped.clone();
+        // Every call takes 60 seconds
+        let mut service = TimeoutLayer::new(&config).layer(tower::service_fn(
+            move |_req: http::Request<BoxBody>| {
+                let guard = DropFlag(flag.clone());
+                async move {
+                    let _guard = guard;
+                    tokio::time::sleep(Duration::from_secs(60)).await;
+                    Ok::<_, std::convert::Infallible>(
+                        http::Response::new(tonic::body::empty_body()),
+                    )
+                }
+            },
+        ));
+
+        let started = tokio::time::Instant::now();
+        let response = service.call(request("StartApp")).await.unwrap();
+        assert_eq!(started.elapsed(), Duration::from_secs(30));
+        let status = Status::from_header_map(response.headers()).unwrap();
+        assert_eq!(status.code(), tonic::Code::DeadlineExceeded);
+        assert_eq!(status.message(), "StartApp did not complete within 30s");
+        assert!(dropped.load(Ordering::SeqCst));
+
+        // Methods with a longer or no limit, and streams, run to completion
+        for method in ["StopApp", "GetAppLogs", "StreamServiceLogs"] {
+            let started = tokio::time::Instant::now();
+            let response = service.call(request(method)).await.unwrap();
+            assert_eq!(started.elapsed(), Duration::from_secs(60), "{}", method);
+            assert!(Status::from_header_map(response.headers()).is_none());
+        }
+    }
+}
@@ -71,3 +71,23 @@ pub fn format_bytes(bytes: u64) -> String {
 
     format!("{:.2} {}", size, UNITS[unit_index])
 }
+
+/// Extract the remote IP address of a gRPC request at the HTTP layer
+/// Returns None when the connection is not TCP (e.g. Unix socket)
+pub fn remote_ip<B>(req: &http::Request<B>) -> Option<std::net::IpAddr> {
+    req.extensions()
+        .get::<tonic::transport::server::TcpConnectInfo>()
+        .and_then(|info| info.remote_addr())
+        .map(|addr| addr.ip())
+}
+
+/// Extract the gRPC method name from a request path (/package.Service/Method)
+pub fn grpc_method_name(path: &str) -> &str {
+    path.rsplit('/').next().unwrap_or("Unknown")
+}
+
+/// Extract the fully qualified service name from a request path
+/// (/package.Service/Method)
+pub fn grpc_service_name(path: &str) -> &str {
+    path.trim_start_matches('/').split('/').next().unwrap_or("")
+}
@@ -0,0 +1,418 @@
+use crate::config::WebConfig;
+use futures_util::future::BoxFuture;
+use std::task::{Context, Poll};
+use tonic::body::BoxBody;
+use tower::{Layer, Service};
+
+#[cfg(feature = "grpc-web")]
+use {
+    crate::attestor_auth_layer::{
+        ATTESTOR_ADDRESS_HEADER, ATTESTOR_NONCE_HEADER, ATTESTOR_SIGNATURE_HEADER,
+    },
+    crate::auth_layer::{
+        API_KEY_HEADER, HMAC_KEY_ID_HEADER, HMAC_NONCE_HEADER, HMAC_SIGNATURE_HEADER,
+        HMAC_TIMESTAMP_HEADER,
+    },
+    crate::request_id_layer::REQUEST_ID_HEADER,
+    http::{HeaderName, HeaderValue, Method},
+    std::time::Duration,
+    tonic_web::{GrpcWebLayer, GrpcWebService},
+    tower_http::cors::{AllowHeaders, AllowOrigin, Cors, CorsLayer, ExposeHeaders},
+};
+
+/// Request headers pages may send cross-origin: the gRPC-Web ones, the
+/// credentials of the authentication layers and the request ID
+#[cfg(feature = "grpc-web")]
+const ALLOWED_HEADERS: &[&str] = &[
+    "content-type",
+    "x-grpc-web",
+    "x-user-agent",
+    "grpc-timeout",
+    API_KEY_HEADER,
+    HMAC_KEY_ID_HEADER,
+    HMAC_TIMESTAMP_HEADER,
+    HMAC_NONCE_HEADER,
+    HMAC_SIGNATURE_HEADER,
+    ATTESTOR_ADDRESS_HEADER,
+    ATTESTOR_NONCE_HEADER,
+    ATTESTOR_SIGNATURE_HEADER,
+    REQUEST_ID_HEADER,
+];
+
+/// Response headers pages may read: the status of trailers-only answers
+/// and the request ID
+#[cfg(feature = "grpc-web")]
+const EXPOSED_HEADERS: &[&str] = &[
+    "grpc-status",
+    "grpc-message",
+    "grpc-status-details-bin",
+    REQUEST_ID_HEADER,
+];
+
+/// Tower Layer serving gRPC-Web next to native gRPC (`[server.web]`)
+/// Browser requests are translated into gRPC before any other layer sees
+/// them, so the authentication layers read the same metadata headers, and
+/// server-streaming responses are sent message by message. Place it
+/// outermost, on listeners that accept HTTP/1.1 (see `enabled`). Without the
+/// `grpc-web` cargo feature it passes every request through.
+#[derive(Clone, Default)]
+pub struct WebLayer {
+    #[cfg(feature = "grpc-web")]
+    cors: Option<CorsLayer>,
+}
+
+#[cfg(feature = "grpc-web")]
+impl WebLayer {
+    pub fn new(config: Option<&WebConfig>) -> Self {
+        Self {
+            cors: config.map(cors_layer),
+        }
+    }
+
+    /// Whether browsers are served, which needs HTTP/1.1 on the listeners
+    pub fn enabled(&self) -> bool {
+        self.cors.is_some()
+    }
+}
+
+#[cfg(not(feature = "grpc-web"))]
+impl WebLayer {
+    /// `[server.web]` is refused by validation in this build
+    pub fn new(_config: Option<&WebConfig>) -> Self {
+        Self {}
+    }
+
+    pub fn enabled(&self) -> bool {
+        false
+    }
+}
+
+/// CORS answers for `config.allowed_origins`
+#[cfg(feature = "grpc-web")]
+fn cors_layer(config: &WebConfig) -> CorsLayer {
+    let origins = if config.allowed_origins.iter().any(|origin| origin == "*") {
+        AllowOrigin::any()
+    } else {
+        // Validated as origins when the configuration was loaded
+        AllowOrigin::list(
+            config
+                .allowed_origins
+                .iter()
+                .filter_map(|origin| HeaderValue::from_str(origin).ok()),
+        )
+    };
+    CorsLayer::new()
+        .allow_origin(origins)
+        .allow_methods([Method::POST])
+        .allow_headers(AllowHeaders::list(
+            ALLOWED_HEADERS.iter().copied().map(HeaderName::from_static),
+        ))
+        .expose_headers(ExposeHeaders::list(
+            EXPOSED_HEADERS.iter().copied().map(HeaderName::from_static),
+        ))
+        .max_age(Duration::from_secs(config.max_age_seconds))
+}
+
+impl<S> Layer<S> for WebLayer {
+    type Service = WebService<S>;
+
+    fn layer(&self, service: S) -> Self::Service {
+        #[cfg(feature = "grpc-web")]
+        if let Some(cors) = &self.cors {
+            return WebService::Web(cors.layer(GrpcWebLayer::new().layer(service)));
+        }
+        WebService::Native(service)
+    }
+}
+
+/// Service behind a `WebLayer`
+#[derive(Clone)]
+pub enum WebService<S> {
+    /// Native gRPC only
+    Native(S),
+    /// gRPC-Web translated to gRPC, with CORS answers; native gRPC requests
+    /// pass through unchanged
+    #[cfg(feature = "grpc-web")]
+    Web(Cors<GrpcWebService<S>>),
+}
+
+impl<S> Service<http::Request<BoxBody>> for WebService<S>
+where
+    S: Service<http::Request<BoxBody>, Response = http::Response<BoxBody>>
+        + Clone
+        + Send
+        + 'static,
+    S::Error: Into<Box<dyn std::error::Error + Send + Sync>> + Send,
+    S::Future: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        match self {
+            Self::Native(inner) => inner.poll_ready(cx),
+            #[cfg(feature = "grpc-web")]
+            Self::Web(inner) => inner.poll_ready(cx),
+        }
+    }
+
+    fn call(&mut self, req: http::Request<BoxBody>) -> Self::Future {
+        match self {
+            Self::Native(inner) => Box::pin(inner.call(req)),
+            #[cfg(feature = "grpc-web")]
+            Self::Web(inner) => Box::pin(inner.call(req)),
+        }
+    }
+}
+
+#[cfg(all(test, feature = "grpc-web"))]
+mod tests {
+    use super::*;
+    use crate::auth_layer::ApiKeyLayer;
+    use crate::config::{ApiKeyConfig, ApiKeyEntry};
+    use crate::listeners::{bind_all, server_builder};
+    use crate::{
+        GetServiceStatusRequest, GetServiceStatusResponse, ServiceLogLines,
+        StreamServiceLogsRequest, TappConfig, TappServiceImpl, TappServiceServer,
+    };
+    use prost::Message;
+    use std::io::Write;
+    use std::net::SocketAddr;
+    use std::sync::Arc;
+    use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+    use tokio::net::TcpStream;
+    use tokio_stream::wrappers::TcpListenerStream;
+
+    const ORIGIN: &str = "https://dashboard.example.com";
+    const API_KEY: &str = "dashboard-key";
+
+    /// Flag of the frame carrying the trailers
+    const TRAILERS_FRAME: u8 = 0x80;
+
+    /// A gRPC-Web response read off an HTTP/1.1 connection
+    struct WebResponse {
+        /// Status line and headers, lowercased
+        head: String,
+        reader: BufReader<TcpStream>,
+        /// Body bytes received and not yet taken as frames
+        body: Vec<u8>,
+    }
+
+    impl WebResponse {
+        /// Append the next chunk of the chunked body
+        async fn read_chunk(&mut self) {
+            let mut size = String::new();
+            self.reader.read_line(&mut size).await.unwrap();
+            let size = usize::from_str_radix(size.trim(), 16).unwrap();
+            assert!(size > 0, "body ended before the expected frame");
+            let mut chunk = vec![0; size + 2];
+            self.reader.read_exact(&mut chunk).await.unwrap();
+            self.body.extend_from_slice(&chunk[..size]);
+        }
+
+        /// Flag and payload of the next frame
+        async fn frame(&mut self) -> (u8, Vec<u8>) {
+            loop {
+                if self.body.len() >= 5 {
+                    let len = u32::from_be_bytes(self.body[1..5].try_into().unwrap()) as usize;
+                    if self.body.len() >= 5 + len {
+                        let frame: Vec<u8> = self.body.drain(..5 + len).collect();
+                        return (frame[0], frame[5..].to_vec());
+                    }
+                }
+                self.read_chunk().await;
+            }
+        }
+    }
+
+    /// POST `message` to `method` the way a grpc-web client in a page of
+    /// `ORIGIN` does
+    async fn post(
+        addr: SocketAddr,
+        method: &str,
+        api_key: Option<&str>,
+        message: &impl Message,
+    ) -> WebResponse {
+        let payload = message.encode_to_vec();
+        let mut body = vec![0];
+        body.extend_from_slice(&(payload.len() as u32).to_be_bytes());
+        body.extend_from_slice(&payload);
+
+        let mut request = format!(
+            "POST /tapp_service.TappService/{} HTTP/1.1\r\n\
+             host: {}\r\n\
+             origin: {}\r\n\
+             content-type: application/grpc-web+proto\r\n\
+             x-grpc-web: 1\r\n\
+             content-length: {}\r\n",
+            method,
+            addr,
+            ORIGIN,
+            body.len()
+        );
+        if let Some(api_key) = api_key {
+            request.push_str(&format!("x-api-key: {}\r\n", api_key));
+        }
+        request.push_str("\r\n");
+
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        stream.write_all(request.as_bytes()).await.unwrap();
+        stream.write_all(&body).await.unwrap();
+
+        let mut reader = BufReader::new(stream);
+        let mut head = String::new();
+        loop {
+            let mut line = String::new();
+            reader.read_line(&mut line).await.unwrap();
+            if line == "\r\n" {
+                break;
+            }
+            head.push_str(&line.to_lowercase());
+        }
+        WebResponse {
+            head,
+            reader,
+            body: Vec::new(),
+        }
+    }
+
+    /// A server with gRPC-Web for `ORIGIN` and API key authentication, as
+    /// main.rs stacks them
+    async fn serve(dir: &std::path::Path) -> SocketAddr {
+        std::fs::create_dir_all(dir.join("logs")).unwrap();
+        let mut config = TappConfig::default();
+        config.boot.aa_config_path = Some(dir.join("aa.toml").to_string_lossy().to_string());
+        config.boot.data_dir = dir.join("apps");
+        config.logging.file_path = Some(dir.join("logs").join("app.log"));
+        config.server.api_key = Some(ApiKeyConfig {
+            enabled: true,
+            keys: vec![ApiKeyEntry::Inline(API_KEY.to_string())],
+            ..Default::default()
+        });
+        config.server.web = Some(WebConfig {
+            allowed_origins: vec![ORIGIN.to_string()],
+            ..Default::default()
+        });
+        let service = Arc::new(TappServiceImpl::new(config.clone()).await.unwrap());
+
+        let web_layer = WebLayer::new(config.server.web.as_ref());
+        assert!(web_layer.enabled());
+        let (_, listener) = bind_all(["127.0.0.1:0"]).unwrap().pop().unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(
+            server_builder(&config.server.http2)
+                .accept_http1(web_layer.enabled())
+                .layer(
+                    tower::ServiceBuilder::new()
+                        .layer(web_layer)
+                        .layer(ApiKeyLayer::with_config_handle(
+                            service.api_key_config.clone(),
+                        ))
+                        .into_inner(),
+                )
+                .add_service(TappServiceServer::from_arc(service))
+                .serve_with_incoming(TcpListenerStream::new(listener)),
+        );
+        addr
+    }
+
+    #[tokio::test]
+    async fn test_unary_call_and_api_key() {
+        let dir = tempfile::tempdir().unwrap();
+        let addr = serve(dir.path()).await;
+
+        // The key reaches the authentication layer through the translation
+        let mut response = post(
+            addr,
+            "GetServiceStatus",
+            Some(API_KEY),
+            &GetServiceStatusRequest::default(),
+        )
+        .await;
+        assert!(
+            response.head.starts_with("http/1.1 200"),
+            "{}",
+            response.head
+        );
+        assert!(response
+            .head
+            .contains("content-type: application/grpc-web+proto"));
+        assert!(response
+            .head
+            .contains(&format!("access-control-allow-origin: {}", ORIGIN)));
+        let (flag, payload) = response.frame().await;
+        assert_eq!(flag, 0);
+        assert!(
+            GetServiceStatusResponse::decode(payload.as_slice())
+                .unwrap()
+                .success
+        );
+        let (flag, trailers) = response.frame().await;
+        assert_eq!(flag, TRAILERS_FRAME);
+        assert!(String::from_utf8(trailers)
+            .unwrap()
+            .contains("grpc-status:0"));
+
+        // Without it the call is refused, in a trailers-only response
+        let response = post(
+            addr,
+            "GetServiceStatus",
+            None,
+            &GetServiceStatusRequest::default(),
+        )
+        .await;
+        assert!(
+            response.head.contains("grpc-status: 16"),
+            "{}",
+            response.head
+        );
+    }
+
+    #[tokio::test]
+    async fn test_server_streaming_call() {
+        let dir = tempfile::tempdir().unwrap();
+        let log_path = dir.path().join("logs").join("app.log");
+        std::fs::create_dir_all(log_path.parent().unwrap()).unwrap();
+        std::fs::write(&log_path, "first\n").unwrap();
+        let addr = serve(dir.path()).await;
+
+        let mut response = post(
+            addr,
+            "StreamServiceLogs",
+            Some(API_KEY),
+            &StreamServiceLogsRequest {
+                file_name: "app.log".to_string(),
+                lines: 10,
+            },
+        )
+        .await;
+        assert!(
+            response.head.starts_with("http/1.1 200"),
+            "{}",
+            response.head
+        );
+        let (flag, payload) = response.frame().await;
+        assert_eq!(flag, 0);
+        assert_eq!(
+            ServiceLogLines::decode(payload.as_slice()).unwrap().lines,
+            vec!["first"]
+        );
+
+        // Lines written later arrive on the open response
+        let mut file = std::fs::OpenOptions::new()
+            .append(true)
+            .open(&log_path)
+            .unwrap();
+        file.write_all(b"second\n").unwrap();
+        let (flag, payload) =
+            tokio::time::timeout(std::time::Duration::from_secs(5), response.frame())
+                .await
+                .unwrap();
+        assert_eq!(flag, 0);
+        assert_eq!(
+            ServiceLogLines::decode(payload.as_slice()).unwrap().lines,
+            vec!["second"]
+        );
+    }
+}